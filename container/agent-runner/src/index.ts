@@ -3,7 +3,9 @@
  * Runs inside a container, receives config via stdin, outputs result to stdout
  *
  * Input protocol:
- *   Stdin: Full ContainerInput JSON (read until EOF, like before)
+ *   Stdin: Full ContainerInput JSON (read until EOF, or its first line when
+ *          the host keeps stdin open; follow-ups then also arrive on stdin
+ *          as {type:"message"|"close"} JSON lines)
  *   IPC:   Follow-up messages written as JSON files to /workspace/ipc/input/
  *          Files: {type:"message", text:"..."}.json — polled and consumed
  *          Sentinel: /workspace/ipc/input/_close — signals session end
@@ -29,6 +31,7 @@ interface ContainerInput {
   assistantName?: string;
   model?: string;
   secrets?: Record<string, string>;
+  stdinKeepalive?: boolean;
 }
 
 interface StreamEvent {
@@ -38,6 +41,14 @@ interface StreamEvent {
   text?: string;
 }
 
+interface TokenUsage {
+  inputTokens: number;
+  outputTokens: number;
+  cacheReadTokens?: number;
+  cacheWriteTokens?: number;
+  costUsd?: number;
+}
+
 interface ContainerOutput {
   status: 'success' | 'error';
   result: string | null;
@@ -45,6 +56,7 @@ interface ContainerOutput {
   model?: string;
   error?: string;
   event?: StreamEvent;
+  usage?: TokenUsage;
 }
 
 interface SessionEntry {
@@ -109,9 +121,22 @@ class MessageStream {
 async function readStdin(): Promise<string> {
   return new Promise((resolve, reject) => {
     let data = '';
+    const onData = (chunk: string) => {
+      data += chunk;
+      const newline = data.indexOf('\n');
+      if (newline === -1) return;
+      // Keep-alive: the input is the first line; follow-ups come after it
+      process.stdin.off('data', onData);
+      process.stdin.off('end', onEnd);
+      process.stdin.pause();
+      const rest = data.slice(newline + 1);
+      if (rest) process.stdin.unshift(rest, 'utf8');
+      resolve(data.slice(0, newline));
+    };
+    const onEnd = () => resolve(data);
     process.stdin.setEncoding('utf8');
-    process.stdin.on('data', chunk => { data += chunk; });
-    process.stdin.on('end', () => resolve(data));
+    process.stdin.on('data', onData);
+    process.stdin.on('end', onEnd);
     process.stdin.on('error', reject);
   });
 }
@@ -298,10 +323,40 @@ function formatTranscriptMarkdown(messages: ParsedMessage[], title?: string | nu
   return lines.join('\n');
 }
 
+const stdinMessages: string[] = [];
+let stdinClosed = false;
+
+/**
+ * Take follow-ups from stdin as JSONL alongside IPC files. EOF closes.
+ */
+function followStdin(): void {
+  let buf = '';
+  process.stdin.setEncoding('utf8');
+  process.stdin.on('data', (chunk: string) => {
+    buf += chunk;
+    let newline;
+    while ((newline = buf.indexOf('\n')) !== -1) {
+      const line = buf.slice(0, newline).trim();
+      buf = buf.slice(newline + 1);
+      if (!line) continue;
+      try {
+        const data = JSON.parse(line);
+        if (data.type === 'close') stdinClosed = true;
+        else if (data.type === 'message' && data.text) stdinMessages.push(data.text);
+      } catch (err) {
+        log(`Failed to parse stdin input: ${err instanceof Error ? err.message : String(err)}`);
+      }
+    }
+  });
+  process.stdin.on('end', () => { stdinClosed = true; });
+  process.stdin.resume();
+}
+
 /**
- * Check for _close sentinel.
+ * Check for _close sentinel (or close on stdin).
  */
 function shouldClose(): boolean {
+  if (stdinClosed && stdinMessages.length === 0) return true;
   if (fs.existsSync(IPC_INPUT_CLOSE_SENTINEL)) {
     try { fs.unlinkSync(IPC_INPUT_CLOSE_SENTINEL); } catch { /* ignore */ }
     return true;
@@ -314,13 +369,14 @@ function shouldClose(): boolean {
  * Returns messages found, or empty array.
  */
 function drainIpcInput(): string[] {
+  const fromStdin = stdinMessages.splice(0);
   try {
     fs.mkdirSync(IPC_INPUT_DIR, { recursive: true });
     const files = fs.readdirSync(IPC_INPUT_DIR)
       .filter(f => f.endsWith('.json'))
       .sort();
 
-    const messages: string[] = [];
+    const messages: string[] = fromStdin;
     for (const file of files) {
       const filePath = path.join(IPC_INPUT_DIR, file);
       try {
@@ -337,7 +393,7 @@ function drainIpcInput(): string[] {
     return messages;
   } catch (err) {
     log(`IPC drain error: ${err instanceof Error ? err.message : String(err)}`);
-    return [];
+    return fromStdin;
   }
 }
 
@@ -520,7 +576,8 @@ async function runQuery(
       writeOutput({
         status: 'success',
         result: textResult || null,
-        newSessionId
+        newSessionId,
+        usage: resultUsage(message),
       });
     }
   }
@@ -530,6 +587,27 @@ async function runQuery(
   return { newSessionId, lastAssistantUuid, closedDuringQuery };
 }
 
+/** Token counts and cost from an SDK result message, in the wire shape. */
+function resultUsage(message: unknown): TokenUsage | undefined {
+  const result = message as {
+    usage?: {
+      input_tokens?: number;
+      output_tokens?: number;
+      cache_read_input_tokens?: number;
+      cache_creation_input_tokens?: number;
+    };
+    total_cost_usd?: number;
+  };
+  if (!result.usage) return undefined;
+  return {
+    inputTokens: result.usage.input_tokens || 0,
+    outputTokens: result.usage.output_tokens || 0,
+    cacheReadTokens: result.usage.cache_read_input_tokens || 0,
+    cacheWriteTokens: result.usage.cache_creation_input_tokens || 0,
+    costUsd: result.total_cost_usd,
+  };
+}
+
 async function main(): Promise<void> {
   let containerInput: ContainerInput;
 
@@ -538,6 +616,7 @@ async function main(): Promise<void> {
     containerInput = JSON.parse(stdinData);
     // Delete the temp file the entrypoint wrote — it contains secrets
     try { fs.unlinkSync('/tmp/input.json'); } catch { /* may not exist */ }
+    if (containerInput.stdinKeepalive) followStdin();
     log(`Received input for group: ${containerInput.groupFolder}`);
     if (containerInput.model) {
       CLAUDE_MODEL = containerInput.model;
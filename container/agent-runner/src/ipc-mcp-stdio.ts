@@ -21,7 +21,32 @@ const chatJid = process.env.INTERCOM_CHAT_JID!;
 const groupFolder = process.env.INTERCOM_GROUP_FOLDER!;
 const isMain = process.env.INTERCOM_IS_MAIN === '1';
 
-function writeIpcFile(dir: string, data: object): string {
+/** IPC protocol version this server writes; see capabilities.json. */
+const IPC_PROTOCOL_VERSION = 2;
+const CAPABILITIES_PATH = path.join(IPC_DIR, 'capabilities.json');
+
+/**
+ * Whether the host accepts `type` in the given IPC directory. Hosts that
+ * predate capabilities.json are assumed to accept everything they did then.
+ */
+function hostSupports(kind: 'messageTypes' | 'taskTypes' | 'queryTypes', type: string): boolean {
+  try {
+    const capabilities = JSON.parse(fs.readFileSync(CAPABILITIES_PATH, 'utf-8'));
+    return Array.isArray(capabilities[kind]) ? capabilities[kind].includes(type) : true;
+  } catch {
+    return !['broadcast', 'mail'].includes(type);
+  }
+}
+
+function unsupported(type: string) {
+  return {
+    content: [{ type: 'text' as const, text: `This Intercom host does not support ${type} yet.` }],
+    isError: true,
+  };
+}
+
+function writeIpcFile(dir: string, payload: object): string {
+  const data = { version: IPC_PROTOCOL_VERSION, ...payload };
   fs.mkdirSync(dir, { recursive: true });
 
   const filename = `${Date.now()}-${Math.random().toString(36).slice(2, 8)}.json`;
@@ -46,15 +71,17 @@ server.tool(
   {
     text: z.string().describe('The message text to send'),
     sender: z.string().optional().describe('Your role/identity name (e.g. "Researcher"). When set, messages appear from a dedicated bot in Telegram.'),
+    options: z.array(z.string()).optional().describe('Short replies offered as tap-to-select buttons (Telegram only, up to 60 bytes each). A tap arrives as a message from the user with the option text.'),
   },
   async (args) => {
-    const data: Record<string, string | undefined> = {
+    const data: Record<string, string | string[] | undefined> = {
       type: 'message',
       chatJid,
       text: args.text,
       sender: args.sender || undefined,
       groupFolder,
       timestamp: new Date().toISOString(),
+      options: args.options?.length ? args.options : undefined,
     };
 
     writeIpcFile(MESSAGES_DIR, data);
@@ -286,9 +313,29 @@ Use available_groups.json to find the JID for a group. The folder name should be
 const QUERIES_DIR = path.join(IPC_DIR, 'queries');
 const RESPONSES_DIR = path.join(IPC_DIR, 'responses');
 
+/** Read a response and the parts it chains to via `next`, removing each file. */
+function readResponse(responsePath: string): { status: string; result: string; data?: unknown } {
+  const first = JSON.parse(fs.readFileSync(responsePath, 'utf-8'));
+  try { fs.unlinkSync(responsePath); } catch { /* ignore */ }
+  let result: string = first.result || '';
+  let next: string | undefined = first.next;
+  while (next) {
+    const partPath = path.join(RESPONSES_DIR, path.basename(next));
+    const part = JSON.parse(fs.readFileSync(partPath, 'utf-8'));
+    try { fs.unlinkSync(partPath); } catch { /* ignore */ }
+    result += part.result || '';
+    next = part.next;
+  }
+  if (first.truncated) {
+    result += `\n[truncated: ${Buffer.byteLength(result)} of ${first.totalBytes ?? '?'} bytes shown]`;
+  }
+  return { status: first.status, result, data: first.data };
+}
+
 async function queryKernel(type: string, params: Record<string, unknown> = {}): Promise<string> {
+  if (!hostSupports('queryTypes', type)) return `Error: this Intercom host does not support the ${type} query.`;
   const uuid = crypto.randomUUID();
-  const query = { uuid, type, params, timestamp: new Date().toISOString() };
+  const query = { version: IPC_PROTOCOL_VERSION, uuid, type, params, timestamp: new Date().toISOString() };
 
   fs.mkdirSync(QUERIES_DIR, { recursive: true });
   const queryPath = path.join(QUERIES_DIR, `${uuid}.json`);
@@ -302,9 +349,7 @@ async function queryKernel(type: string, params: Record<string, unknown> = {}):
   while (Date.now() < deadline) {
     if (fs.existsSync(responsePath)) {
       try {
-        const raw = fs.readFileSync(responsePath, 'utf-8');
-        const response = JSON.parse(raw);
-        try { fs.unlinkSync(responsePath); } catch { /* ignore */ }
+        const response = readResponse(responsePath);
         if (response.status === 'error') return `Error: ${response.result || 'Unknown error'}`;
         return response.result || '';
       } catch (err) {
@@ -318,6 +363,109 @@ async function queryKernel(type: string, params: Record<string, unknown> = {}):
   return 'Error: Query timed out — Demarch kernel may not be available.';
 }
 
+server.tool(
+  'broadcast_message',
+  'Send the same message to every registered group, or to the groups listed by folder. Main group only. Returns which chats it was delivered to and which groups were refused.',
+  {
+    text: z.string().describe('The message text to send'),
+    folders: z.array(z.string()).optional().describe('Group folders to send to. Omit to send to every registered group.'),
+    sender: z.string().optional().describe('Your role/identity name (e.g. "Researcher").'),
+  },
+  async (args) => {
+    if (!isMain) {
+      return {
+        content: [{ type: 'text' as const, text: 'Only the main group can broadcast.' }],
+        isError: true,
+      };
+    }
+    if (!hostSupports('messageTypes', 'broadcast')) return unsupported('broadcasts');
+
+    const filename = writeIpcFile(MESSAGES_DIR, {
+      type: 'broadcast',
+      text: args.text,
+      target: args.folders?.length ? args.folders : 'all',
+      sender: args.sender || undefined,
+      groupFolder,
+      timestamp: new Date().toISOString(),
+    });
+
+    // intercomd writes a delivery report named after the message file.
+    const reportPath = path.join(RESPONSES_DIR, filename);
+    const deadline = Date.now() + 10_000;
+    while (Date.now() < deadline) {
+      if (fs.existsSync(reportPath)) {
+        const report = readResponse(reportPath);
+        return {
+          content: [{ type: 'text' as const, text: report.result }],
+          isError: report.status === 'error',
+        };
+      }
+      await new Promise((resolve) => setTimeout(resolve, 200));
+    }
+    return { content: [{ type: 'text' as const, text: 'Broadcast sent; no delivery report yet.' }] };
+  },
+);
+
+const INBOX_DIR = path.join(IPC_DIR, 'inbox');
+
+server.tool(
+  'send_mail',
+  "Leave a message in another group's inbox for its agent to pick up, e.g. to hand over work. The receiving group must accept mail from this group (its acceptMailFrom setting); the main group can mail any group.",
+  {
+    to: z.string().describe('Folder of the group to mail (e.g. "team-eng")'),
+    text: z.string().describe('The message for the other agent'),
+    subject: z.string().optional().describe('Short subject line'),
+  },
+  async (args) => {
+    if (!hostSupports('messageTypes', 'mail')) return unsupported('mail');
+    const filename = writeIpcFile(MESSAGES_DIR, {
+      type: 'mail',
+      to: args.to,
+      subject: args.subject || undefined,
+      text: args.text,
+      groupFolder,
+      timestamp: new Date().toISOString(),
+    });
+
+    const ackPath = path.join(RESPONSES_DIR, filename);
+    const deadline = Date.now() + 10_000;
+    while (Date.now() < deadline) {
+      if (fs.existsSync(ackPath)) {
+        const ack = readResponse(ackPath);
+        return {
+          content: [{ type: 'text' as const, text: ack.result }],
+          isError: ack.status === 'error',
+        };
+      }
+      await new Promise((resolve) => setTimeout(resolve, 200));
+    }
+    return { content: [{ type: 'text' as const, text: 'Mail sent; no delivery confirmation yet.' }] };
+  },
+);
+
+server.tool(
+  'read_mail',
+  'Read the mail other groups have left in this group\'s inbox, oldest first. Mail is removed once read.',
+  {},
+  async () => {
+    let files: string[] = [];
+    try {
+      files = fs.readdirSync(INBOX_DIR).filter((f) => f.endsWith('.json')).sort();
+    } catch { /* no inbox yet */ }
+    if (files.length === 0) {
+      return { content: [{ type: 'text' as const, text: 'No mail.' }] };
+    }
+    const mail = files.map((file) => {
+      const filePath = path.join(INBOX_DIR, file);
+      const item = JSON.parse(fs.readFileSync(filePath, 'utf-8'));
+      try { fs.unlinkSync(filePath); } catch { /* ignore */ }
+      const subject = item.subject ? ` — ${item.subject}` : '';
+      return `From ${item.from} at ${item.sentAt}${subject}:\n${item.text}`;
+    });
+    return { content: [{ type: 'text' as const, text: mail.join('\n\n') }] };
+  },
+);
+
 // --- Demarch Platform Tools ---
 
 server.tool(
@@ -404,6 +552,73 @@ server.tool(
   },
 );
 
+server.tool(
+  'demarch_list_gates',
+  'List gates on a run that are waiting for approval. Defaults to open gates on the current run.',
+  {
+    run_id: z.string().optional().describe('Run ID (optional, defaults to current)'),
+    status: z.string().optional().describe('Gate status filter (default: open)'),
+  },
+  async (args) => {
+    const params: Record<string, unknown> = {};
+    if (args.run_id) params.runId = args.run_id;
+    if (args.status) params.status = args.status;
+    const result = await queryKernel('list_gates', params);
+    return { content: [{ type: 'text' as const, text: result }] };
+  },
+);
+
+server.tool(
+  'demarch_issue_comments',
+  'Fetch the comment thread on a work item (bead).',
+  { id: z.string().describe('Bead ID') },
+  async (args) => {
+    const result = await queryKernel('issue_comments', { id: args.id });
+    return { content: [{ type: 'text' as const, text: result }] };
+  },
+);
+
+server.tool(
+  'demarch_issue_history',
+  'Fetch the change history of a work item (bead): status, priority and field edits.',
+  { id: z.string().describe('Bead ID') },
+  async (args) => {
+    const result = await queryKernel('issue_history', { id: args.id });
+    return { content: [{ type: 'text' as const, text: result }] };
+  },
+);
+
+server.tool(
+  'demarch_run_diff',
+  "Summarize how a run's artifacts changed (files and line counts, not the full diff).",
+  { run_id: z.string().optional().describe('Run ID (optional, defaults to current)') },
+  async (args) => {
+    const result = await queryKernel('run_diff', args.run_id ? { runId: args.run_id } : {});
+    return { content: [{ type: 'text' as const, text: result }] };
+  },
+);
+
+server.tool(
+  'demarch_batch',
+  'Run several Demarch reads at once (e.g. run_status, next_work and review_summary for a briefing). Returns a JSON array with one answer per query, in order.',
+  {
+    queries: z
+      .array(
+        z.object({
+          type: z.string().describe('Read query type, e.g. run_status or list_gates'),
+          params: z.record(z.string(), z.unknown()).optional().describe('Query params, as for the single query'),
+        }),
+      )
+      .min(1)
+      .max(16)
+      .describe('Reads to run'),
+  },
+  async (args) => {
+    const result = await queryKernel('batch', { queries: args.queries });
+    return { content: [{ type: 'text' as const, text: result }] };
+  },
+);
+
 server.tool(
   'demarch_research',
   'Search for research findings, discoveries, and knowledge in the Demarch platform.',
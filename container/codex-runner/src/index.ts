@@ -20,6 +20,7 @@ import {
 } from '../../shared/protocol.js';
 import {
   drainIpcInput,
+  followStdin,
   shouldClose,
   waitForIpcMessage,
   IPC_INPUT_DIR,
@@ -110,6 +111,7 @@ async function main(): Promise<void> {
     const stdinData = await readStdin();
     containerInput = JSON.parse(stdinData);
     try { fs.unlinkSync('/tmp/input.json'); } catch { /* may not exist */ }
+    if (containerInput.stdinKeepalive) followStdin();
     log(`Received input for group: ${containerInput.groupFolder}`);
     if (containerInput.model) {
       MODEL = containerInput.model;
@@ -19,6 +19,7 @@ import { getAccessToken } from './auth.js';
 import { buildSystemPrompt } from '../../shared/system-prompt.js';
 import {
   ContainerInput,
+  TokenUsage,
   writeOutput,
   readStdin,
   log,
@@ -26,6 +27,7 @@ import {
 import type { IpcContext } from '../../shared/ipc-tools.js';
 import {
   drainIpcInput,
+  followStdin,
   shouldClose,
   waitForIpcMessage,
   IPC_INPUT_DIR,
@@ -218,7 +220,7 @@ async function runQuery(
   systemInstruction: string,
   ipcCtx: IpcContext,
   toolDeclarations: unknown[],
-): Promise<{ result: string | null; closedDuringQuery: boolean }> {
+): Promise<{ result: string | null; closedDuringQuery: boolean; usage: TokenUsage }> {
   contents.push({ role: 'user', parts: [{ text: prompt }] });
 
   let closedDuringQuery = false;
@@ -241,6 +243,8 @@ async function runQuery(
   setTimeout(pollIpc, IPC_POLL_MS);
 
   let result: string | null = null;
+  // Every round is billed, so usage is summed over the tool rounds
+  const usage: TokenUsage = { inputTokens: 0, outputTokens: 0 };
 
   for (let round = 0; round < MAX_TOOL_ROUNDS; round++) {
     if (closedDuringQuery) break;
@@ -258,6 +262,12 @@ async function runQuery(
       toolDeclarations,
     );
 
+    const roundUsage = response.response?.usageMetadata;
+    if (roundUsage) {
+      usage.inputTokens += roundUsage.promptTokenCount || 0;
+      usage.outputTokens += (roundUsage.candidatesTokenCount || 0) + (roundUsage.thoughtsTokenCount || 0);
+    }
+
     const candidate = response.response?.candidates?.[0];
     const parts = candidate?.content?.parts || [];
 
@@ -314,7 +324,7 @@ async function runQuery(
   }
 
   ipcPolling = false;
-  return { result, closedDuringQuery };
+  return { result, closedDuringQuery, usage };
 }
 
 // --- Main ---
@@ -326,6 +336,7 @@ async function main(): Promise<void> {
     const stdinData = await readStdin();
     containerInput = JSON.parse(stdinData);
     try { fs.unlinkSync('/tmp/input.json'); } catch { /* may not exist */ }
+    if (containerInput.stdinKeepalive) followStdin();
     log(`Received input for group: ${containerInput.groupFolder}`);
     if (containerInput.model) {
       MODEL = GEMINI_API_MODELS[containerInput.model] || containerInput.model;
@@ -436,6 +447,7 @@ async function main(): Promise<void> {
         status: 'success',
         result: queryResult.result,
         newSessionId: sessionId,
+        usage: queryResult.usage,
       });
 
       if (queryResult.closedDuringQuery) {
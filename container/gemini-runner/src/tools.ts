@@ -237,6 +237,67 @@ export function getToolDeclarations(isMain: boolean): ToolDeclaration[] {
         },
       },
     },
+    {
+      name: 'demarch_list_gates',
+      description: 'List gates waiting for approval (default: open gates on the current run).',
+      parameters: {
+        type: 'object',
+        properties: {
+          run_id: { type: 'string', description: 'Run ID (optional, defaults to current)' },
+          status: { type: 'string', description: 'Gate status filter (default: open)' },
+        },
+      },
+    },
+    {
+      name: 'demarch_issue_comments',
+      description: 'Fetch the comment thread on a work item (bead).',
+      parameters: {
+        type: 'object',
+        properties: { id: { type: 'string', description: 'Bead ID' } },
+        required: ['id'],
+      },
+    },
+    {
+      name: 'demarch_issue_history',
+      description: 'Fetch the change history of a work item (bead).',
+      parameters: {
+        type: 'object',
+        properties: { id: { type: 'string', description: 'Bead ID' } },
+        required: ['id'],
+      },
+    },
+    {
+      name: 'demarch_run_diff',
+      description: "Summarize how a run's artifacts changed.",
+      parameters: {
+        type: 'object',
+        properties: {
+          run_id: { type: 'string', description: 'Run ID (optional, defaults to current)' },
+        },
+      },
+    },
+    {
+      name: 'demarch_batch',
+      description: 'Run several Demarch reads at once. Returns a JSON array with one answer per query, in order.',
+      parameters: {
+        type: 'object',
+        properties: {
+          queries: {
+            type: 'array',
+            description: 'Reads to run (at most 16)',
+            items: {
+              type: 'object',
+              properties: {
+                type: { type: 'string', description: 'Read query type, e.g. run_status or list_gates' },
+                params: { type: 'object', description: 'Query params, as for the single query' },
+              },
+              required: ['type'],
+            },
+          },
+        },
+        required: ['queries'],
+      },
+    },
   );
 
   if (isMain) {
@@ -351,6 +412,11 @@ export async function executeTool(
     case 'demarch_review_summary':
     case 'demarch_next_work':
     case 'demarch_run_events':
+    case 'demarch_list_gates':
+    case 'demarch_issue_comments':
+    case 'demarch_issue_history':
+    case 'demarch_run_diff':
+    case 'demarch_batch':
       return executeDemarchTool(name, args, ipcCtx);
 
     default:
@@ -387,6 +453,20 @@ async function executeDemarchTool(
         args.limit as number | undefined,
         args.since as string | undefined,
       );
+    case 'demarch_list_gates':
+      return demarchTools.demarchListGates(
+        ipcCtx,
+        args.run_id as string | undefined,
+        args.status as string | undefined,
+      );
+    case 'demarch_issue_comments':
+      return demarchTools.demarchIssueComments(ipcCtx, args.id as string);
+    case 'demarch_issue_history':
+      return demarchTools.demarchIssueHistory(ipcCtx, args.id as string);
+    case 'demarch_run_diff':
+      return demarchTools.demarchRunDiff(ipcCtx, args.run_id as string | undefined);
+    case 'demarch_batch':
+      return demarchTools.demarchBatch(ipcCtx, args.queries as demarchTools.DemarchBatchQuery[]);
     default:
       return `Unknown Demarch tool: ${name}`;
   }
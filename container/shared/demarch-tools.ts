@@ -50,6 +50,39 @@ export function demarchRunEvents(
   return queryKernel('run_events', params);
 }
 
+export function demarchListGates(
+  _ctx: IpcContext,
+  runId?: string,
+  status?: string,
+): Promise<string> {
+  const params: Record<string, unknown> = {};
+  if (runId) params.runId = runId;
+  if (status) params.status = status;
+  return queryKernel('list_gates', params);
+}
+
+export function demarchIssueComments(_ctx: IpcContext, id: string): Promise<string> {
+  return queryKernel('issue_comments', { id });
+}
+
+export function demarchIssueHistory(_ctx: IpcContext, id: string): Promise<string> {
+  return queryKernel('issue_history', { id });
+}
+
+export function demarchRunDiff(_ctx: IpcContext, runId?: string): Promise<string> {
+  return queryKernel('run_diff', runId ? { runId } : {});
+}
+
+export interface DemarchBatchQuery {
+  type: string;
+  params?: Record<string, unknown>;
+}
+
+/** Several reads in one round trip; answers come back as a JSON array in order. */
+export function demarchBatch(_ctx: IpcContext, queries: DemarchBatchQuery[]): Promise<string> {
+  return queryKernel('batch', { queries });
+}
+
 export function demarchResearch(_ctx: IpcContext, query: string): Promise<string> {
   return queryKernel('research', { query });
 }
@@ -13,6 +13,10 @@ const IPC_DIR = '/workspace/ipc';
 const QUERIES_DIR = path.join(IPC_DIR, 'queries');
 const RESPONSES_DIR = path.join(IPC_DIR, 'responses');
 
+/** IPC protocol version written in queries; see capabilities.json. */
+const IPC_PROTOCOL_VERSION = 2;
+const CAPABILITIES_PATH = path.join(IPC_DIR, 'capabilities.json');
+
 const DEFAULT_TIMEOUT_MS = 30_000;
 const POLL_INTERVAL_MS = 200;
 
@@ -25,9 +29,13 @@ export async function queryKernel(
   params: Record<string, unknown> = {},
   timeoutMs: number = DEFAULT_TIMEOUT_MS,
 ): Promise<string> {
+  if (!hostSupportsQuery(type)) {
+    return `Error: this Intercom host does not support the ${type} query.`;
+  }
   const uuid = crypto.randomUUID();
 
   const query = {
+    version: IPC_PROTOCOL_VERSION,
     uuid,
     type,
     params,
@@ -50,10 +58,7 @@ export async function queryKernel(
   while (Date.now() < deadline) {
     if (fs.existsSync(responsePath)) {
       try {
-        const raw = fs.readFileSync(responsePath, 'utf-8');
-        const response = JSON.parse(raw);
-        // Clean up response file
-        try { fs.unlinkSync(responsePath); } catch { /* ignore */ }
+        const response = readResponse(responsePath);
         if (response.status === 'error') {
           return `Error: ${response.result || 'Unknown error'}`;
         }
@@ -70,6 +75,49 @@ export async function queryKernel(
   return 'Error: Query timed out — Demarch kernel may not be available.';
 }
 
+/** Whether the host lists `type` in capabilities.json; older hosts have no file. */
+function hostSupportsQuery(type: string): boolean {
+  try {
+    const capabilities = JSON.parse(fs.readFileSync(CAPABILITIES_PATH, 'utf-8'));
+    return !Array.isArray(capabilities.queryTypes) || capabilities.queryTypes.includes(type);
+  } catch {
+    return true;
+  }
+}
+
+interface QueryResponse {
+  status: string;
+  result: string;
+  /** `result` parsed, when it is JSON and fits in one part. */
+  data?: unknown;
+  next?: string;
+  truncated?: boolean;
+  totalBytes?: number;
+}
+
+/**
+ * Read a response and any further parts it chains to via `next`, removing
+ * each file. intercomd writes the later parts first, so they all exist once
+ * the first one does.
+ */
+function readResponse(responsePath: string): QueryResponse {
+  const first: QueryResponse = JSON.parse(fs.readFileSync(responsePath, 'utf-8'));
+  try { fs.unlinkSync(responsePath); } catch { /* ignore */ }
+  let result = first.result || '';
+  let next = first.next;
+  while (next) {
+    const partPath = path.join(path.dirname(responsePath), path.basename(next));
+    const part: QueryResponse = JSON.parse(fs.readFileSync(partPath, 'utf-8'));
+    try { fs.unlinkSync(partPath); } catch { /* ignore */ }
+    result += part.result || '';
+    next = part.next;
+  }
+  if (first.truncated) {
+    result += `\n[truncated: ${Buffer.byteLength(result)} of ${first.totalBytes ?? '?'} bytes shown]`;
+  }
+  return { status: first.status, result, data: first.data };
+}
+
 function sleep(ms: number): Promise<void> {
   return new Promise((resolve) => setTimeout(resolve, ms));
 }
@@ -1,6 +1,7 @@
 /**
  * Shared IPC input handling for Intercom container agents.
- * Handles polling for follow-up messages and close sentinel.
+ * Handles polling for follow-up messages and close sentinel, and follow-ups
+ * streamed on stdin when the host keeps it open (`stdinKeepalive`).
  */
 
 import fs from 'fs';
@@ -13,10 +14,43 @@ const IPC_POLL_MS = 500;
 
 export { IPC_INPUT_DIR, IPC_POLL_MS };
 
+const stdinMessages: string[] = [];
+let stdinClosed = false;
+
+/**
+ * Take follow-ups from stdin as JSONL: `{"type":"message","text":...}` or
+ * `{"type":"close"}`. EOF also closes. IPC files keep working alongside.
+ */
+export function followStdin(): void {
+  let buf = '';
+  const handle = (line: string) => {
+    try {
+      const data = JSON.parse(line);
+      if (data.type === 'close') stdinClosed = true;
+      else if (data.type === 'message' && data.text) stdinMessages.push(data.text);
+    } catch (err) {
+      log(`Failed to parse stdin input: ${err instanceof Error ? err.message : String(err)}`);
+    }
+  };
+  process.stdin.setEncoding('utf8');
+  process.stdin.on('data', (chunk: string) => {
+    buf += chunk;
+    let newline;
+    while ((newline = buf.indexOf('\n')) !== -1) {
+      const line = buf.slice(0, newline).trim();
+      buf = buf.slice(newline + 1);
+      if (line) handle(line);
+    }
+  });
+  process.stdin.on('end', () => { stdinClosed = true; });
+  process.stdin.resume();
+}
+
 /**
- * Check for _close sentinel.
+ * Check for _close sentinel (or close on stdin).
  */
 export function shouldClose(): boolean {
+  if (stdinClosed && stdinMessages.length === 0) return true;
   if (fs.existsSync(IPC_INPUT_CLOSE_SENTINEL)) {
     try { fs.unlinkSync(IPC_INPUT_CLOSE_SENTINEL); } catch { /* ignore */ }
     return true;
@@ -29,13 +63,14 @@ export function shouldClose(): boolean {
  * Returns messages found, or empty array.
  */
 export function drainIpcInput(): string[] {
+  const fromStdin = stdinMessages.splice(0);
   try {
     fs.mkdirSync(IPC_INPUT_DIR, { recursive: true });
     const files = fs.readdirSync(IPC_INPUT_DIR)
       .filter(f => f.endsWith('.json'))
       .sort();
 
-    const messages: string[] = [];
+    const messages: string[] = fromStdin;
     for (const file of files) {
       const filePath = path.join(IPC_INPUT_DIR, file);
       try {
@@ -52,7 +87,7 @@ export function drainIpcInput(): string[] {
     return messages;
   } catch (err) {
     log(`IPC drain error: ${err instanceof Error ? err.message : String(err)}`);
-    return [];
+    return fromStdin;
   }
 }
 
@@ -12,6 +12,8 @@ export interface ContainerInput {
   isScheduledTask?: boolean;
   model?: string;
   secrets?: Record<string, string>;
+  /** Host keeps stdin open and streams follow-ups on it as JSONL. */
+  stdinKeepalive?: boolean;
 }
 
 export interface StreamEvent {
@@ -21,6 +23,15 @@ export interface StreamEvent {
   text?: string;       // for text_delta: text content
 }
 
+/** Tokens a turn used. `costUsd` is set when the runtime reports a cost. */
+export interface TokenUsage {
+  inputTokens: number;
+  outputTokens: number;
+  cacheReadTokens?: number;
+  cacheWriteTokens?: number;
+  costUsd?: number;
+}
+
 export interface ContainerOutput {
   status: 'success' | 'error';
   result: string | null;
@@ -28,6 +39,7 @@ export interface ContainerOutput {
   error?: string;
   model?: string;
   event?: StreamEvent;
+  usage?: TokenUsage;
 }
 
 export const OUTPUT_START_MARKER = '---INTERCOM_OUTPUT_START---';
@@ -43,12 +55,29 @@ export function log(message: string): void {
   console.error(`[agent-runner] ${message}`);
 }
 
+/**
+ * Read the ContainerInput: the first line of stdin, or all of it when the
+ * host sends no newline and closes it. Anything after the first line is
+ * left on stdin for followStdin().
+ */
 export async function readStdin(): Promise<string> {
   return new Promise((resolve, reject) => {
     let data = '';
+    const onData = (chunk: string) => {
+      data += chunk;
+      const newline = data.indexOf('\n');
+      if (newline === -1) return;
+      process.stdin.off('data', onData);
+      process.stdin.off('end', onEnd);
+      process.stdin.pause();
+      const rest = data.slice(newline + 1);
+      if (rest) process.stdin.unshift(rest, 'utf8');
+      resolve(data.slice(0, newline));
+    };
+    const onEnd = () => resolve(data);
     process.stdin.setEncoding('utf8');
-    process.stdin.on('data', (chunk: string) => { data += chunk; });
-    process.stdin.on('end', () => resolve(data));
+    process.stdin.on('data', onData);
+    process.stdin.on('end', onEnd);
     process.stdin.on('error', reject);
   });
 }
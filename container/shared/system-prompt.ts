@@ -85,6 +85,11 @@ export function buildSystemPrompt(
   parts.push('- **demarch_review_summary**: Get the latest code review summary.');
   parts.push('- **demarch_next_work**: Get prioritized recommendations for what to work on next.');
   parts.push('- **demarch_run_events**: Query recent kernel events (phase transitions, dispatches).');
+  parts.push('- **demarch_list_gates**: List gates waiting for approval on a run.');
+  parts.push('- **demarch_issue_comments**: Fetch the comment thread on a work item.');
+  parts.push('- **demarch_issue_history**: Fetch the change history of a work item.');
+  parts.push("- **demarch_run_diff**: Summarize how a run's artifacts changed.");
+  parts.push('- **demarch_batch**: Run several of the reads above in one call, e.g. for a briefing.');
   parts.push('');
   parts.push('# Guidelines');
   parts.push('');
@@ -154,14 +154,14 @@ pub async fn migrate_legacy_to_postgres(
     }
 
     let tx = client.transaction().await?;
-    let mut migrated = MigratedCounts::default();
-
-    migrated.chats = migrate_chats(&sqlite, &tx).await?;
-    migrated.messages = migrate_messages(&sqlite, &tx).await?;
-    migrated.registered_groups = migrate_registered_groups(&sqlite, &tx).await?;
-    migrated.sessions = migrate_sessions(&sqlite, &tx).await?;
-    migrated.scheduled_tasks = migrate_scheduled_tasks(&sqlite, &tx).await?;
-    migrated.task_run_logs = migrate_task_run_logs(&sqlite, &tx).await?;
+    let migrated = MigratedCounts {
+        chats: migrate_chats(&sqlite, &tx).await?,
+        messages: migrate_messages(&sqlite, &tx).await?,
+        registered_groups: migrate_registered_groups(&sqlite, &tx).await?,
+        sessions: migrate_sessions(&sqlite, &tx).await?,
+        scheduled_tasks: migrate_scheduled_tasks(&sqlite, &tx).await?,
+        task_run_logs: migrate_task_run_logs(&sqlite, &tx).await?,
+    };
 
     let details = serde_json::to_string(&migrated)?;
     tx.execute(
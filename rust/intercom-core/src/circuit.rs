@@ -0,0 +1,205 @@
+//! Circuit breaker for the Postgres connection.
+//!
+//! After `failure_threshold` consecutive connection failures the circuit
+//! opens: callers fail fast instead of each paying for a handshake against a
+//! database that is down. Once the backoff elapses a single reconnect is
+//! allowed through (half-open); success closes the circuit, failure reopens
+//! it with the backoff doubled up to `max_backoff`.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub failure_threshold: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Open => "open",
+            Self::HalfOpen => "half_open",
+        }
+    }
+}
+
+/// Point-in-time view of the breaker, for readiness and health output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CircuitSnapshot {
+    pub state: CircuitState,
+    pub connected: bool,
+    pub consecutive_failures: u32,
+    /// Time until the next reconnect attempt is allowed while open.
+    pub retry_in_ms: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    policy: ReconnectPolicy,
+    state: CircuitState,
+    consecutive_failures: u32,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+    last_error: Option<String>,
+}
+
+impl CircuitBreaker {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Self {
+            policy,
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            backoff: policy.initial_backoff,
+            retry_at: None,
+            last_error: None,
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    /// Whether a reconnect may be attempted now. While open, returns the
+    /// remaining wait instead; once it has elapsed the circuit goes half-open.
+    pub fn allow_attempt(&mut self, now: Instant) -> Result<(), Duration> {
+        match (self.state, self.retry_at) {
+            (CircuitState::Open, Some(retry_at)) if now < retry_at => Err(retry_at - now),
+            (CircuitState::Open, _) => {
+                self.state = CircuitState::HalfOpen;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a successful (re)connect. Returns true if the circuit was not
+    /// already closed, so callers can log the recovery once.
+    pub fn on_success(&mut self) -> bool {
+        let recovered = self.state != CircuitState::Closed;
+        self.state = CircuitState::Closed;
+        self.consecutive_failures = 0;
+        self.backoff = self.policy.initial_backoff;
+        self.retry_at = None;
+        recovered
+    }
+
+    /// Record a failed connect or health check. Returns true if this
+    /// failure opened the circuit.
+    pub fn on_failure(&mut self, now: Instant, error: &str) -> bool {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.last_error = Some(error.to_string());
+        let trips = self.state == CircuitState::HalfOpen
+            || self.consecutive_failures >= self.policy.failure_threshold.max(1);
+        if !trips {
+            return false;
+        }
+        let opened = self.state != CircuitState::Open;
+        self.state = CircuitState::Open;
+        self.retry_at = Some(now + self.backoff);
+        self.backoff = (self.backoff * 2).min(self.policy.max_backoff);
+        opened
+    }
+
+    pub fn snapshot(&self, now: Instant, connected: bool) -> CircuitSnapshot {
+        CircuitSnapshot {
+            state: self.state,
+            connected,
+            consecutive_failures: self.consecutive_failures,
+            retry_in_ms: match self.state {
+                CircuitState::Open => self
+                    .retry_at
+                    .map(|at| at.saturating_duration_since(now).as_millis() as u64),
+                _ => None,
+            },
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(ReconnectPolicy {
+            failure_threshold: 2,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(3),
+        })
+    }
+
+    #[test]
+    fn opens_after_threshold_and_fails_fast() {
+        let mut b = breaker();
+        let t0 = Instant::now();
+        assert!(!b.on_failure(t0, "refused"));
+        assert_eq!(b.state(), CircuitState::Closed);
+        assert!(b.allow_attempt(t0).is_ok());
+
+        assert!(b.on_failure(t0, "refused"));
+        assert_eq!(b.state(), CircuitState::Open);
+        assert_eq!(b.allow_attempt(t0), Err(Duration::from_secs(1)));
+
+        let snap = b.snapshot(t0, false);
+        assert_eq!(snap.retry_in_ms, Some(1000));
+        assert_eq!(snap.last_error.as_deref(), Some("refused"));
+    }
+
+    #[test]
+    fn half_open_failure_doubles_backoff_up_to_max() {
+        let mut b = breaker();
+        let t0 = Instant::now();
+        b.on_failure(t0, "x");
+        b.on_failure(t0, "x");
+
+        let t1 = t0 + Duration::from_secs(1);
+        assert!(b.allow_attempt(t1).is_ok());
+        assert_eq!(b.state(), CircuitState::HalfOpen);
+        assert!(b.on_failure(t1, "x"));
+        assert_eq!(b.allow_attempt(t1), Err(Duration::from_secs(2)));
+
+        let t2 = t1 + Duration::from_secs(2);
+        assert!(b.allow_attempt(t2).is_ok());
+        b.on_failure(t2, "x");
+        assert_eq!(b.allow_attempt(t2), Err(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn success_closes_and_resets_backoff() {
+        let mut b = breaker();
+        let t0 = Instant::now();
+        b.on_failure(t0, "x");
+        b.on_failure(t0, "x");
+        assert!(b.allow_attempt(t0 + Duration::from_secs(1)).is_ok());
+        assert!(b.on_success());
+        assert!(!b.on_success());
+        assert_eq!(b.state(), CircuitState::Closed);
+
+        b.on_failure(t0, "x");
+        b.on_failure(t0, "x");
+        assert_eq!(b.allow_attempt(t0), Err(Duration::from_secs(1)));
+    }
+}
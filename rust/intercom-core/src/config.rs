@@ -5,6 +5,8 @@ use std::path::Path;
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 
+use crate::protocol::RuntimeProtocol;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct IntercomConfig {
@@ -14,7 +16,16 @@ pub struct IntercomConfig {
     pub demarch: DemarchConfig,
     pub events: EventsConfig,
     pub orchestrator: OrchestratorConfig,
+    pub container: ContainerRuntimeConfig,
     pub scheduler: SchedulerConfig,
+    pub ipc: IpcConfig,
+    pub queries: QueryConfig,
+    pub snapshots: SnapshotConfig,
+    pub commands: CommandsConfig,
+    pub telegram: TelegramConfig,
+    pub matrix: MatrixConfig,
+    pub email: EmailConfig,
+    pub channels: ChannelsConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +39,10 @@ pub struct EventsConfig {
     pub batch_size: u32,
     /// Chat JID to send push notifications to (usually main group).
     pub notification_jid: Option<String>,
+    /// How events reach the consumer.
+    pub mode: EventsMode,
+    /// Delay before respawning `ic events follow` after it exits (follow mode).
+    pub follow_restart_ms: u64,
 }
 
 impl Default for EventsConfig {
@@ -37,10 +52,23 @@ impl Default for EventsConfig {
             poll_interval_ms: 1000,
             batch_size: 20,
             notification_jid: None,
+            mode: EventsMode::Poll,
+            follow_restart_ms: 5000,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EventsMode {
+    /// Run `ic events tail` every `poll_interval_ms`.
+    #[default]
+    Poll,
+    /// Keep one `ic events follow` process running and handle each event as
+    /// it is printed.
+    Follow,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct ServerConfig {
@@ -49,6 +77,12 @@ pub struct ServerConfig {
     pub max_body_bytes: usize,
     /// URL of the Node host's callback server for message/task forwarding.
     pub host_callback_url: String,
+    /// Bearer token for `POST /v1/admin/shutdown`; the route is refused
+    /// while unset.
+    pub admin_token: Option<String>,
+    /// How long shutdown waits for running containers before detaching.
+    pub shutdown_grace_ms: u64,
+    pub rate_limit: RateLimitConfig,
 }
 
 impl Default for ServerConfig {
@@ -58,6 +92,47 @@ impl Default for ServerConfig {
             request_timeout_ms: 30_000,
             max_body_bytes: 1_048_576,
             host_callback_url: "http://127.0.0.1:7341".to_string(),
+            admin_token: None,
+            shutdown_grace_ms: 60_000,
+            rate_limit: RateLimitConfig::default(),
+        }
+    }
+}
+
+/// Token buckets in front of the Telegram routes and `/v1/commands`. A
+/// `*_per_minute` of 0 turns that bucket off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Sustained requests per client address.
+    pub per_ip_per_minute: u32,
+    /// Requests one address may make at once before the rate applies.
+    pub per_ip_burst: u32,
+    /// Sustained requests per bearer or Telegram secret token.
+    pub per_token_per_minute: u32,
+    pub per_token_burst: u32,
+    /// Peers never limited; the Node host calls in from loopback. Only a
+    /// direct peer is matched, never a forwarded address.
+    pub exempt_ips: Vec<String>,
+    /// Take the client address from `X-Forwarded-For` (behind a proxy).
+    pub trust_forwarded_for: bool,
+    /// Proxies whose `X-Forwarded-For` entries are believed. The client is
+    /// the rightmost entry that is not one of these.
+    pub trusted_proxies: Vec<String>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            per_ip_per_minute: 120,
+            per_ip_burst: 30,
+            per_token_per_minute: 600,
+            per_token_burst: 100,
+            exempt_ips: vec!["127.0.0.1".to_string(), "::1".to_string()],
+            trust_forwarded_for: false,
+            trusted_proxies: vec!["127.0.0.1".to_string(), "::1".to_string()],
         }
     }
 }
@@ -65,21 +140,63 @@ impl Default for ServerConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct StorageConfig {
+    /// Which backend serves the live tables.
+    pub backend: StorageBackend,
     pub postgres_dsn: Option<String>,
+    /// Database file used when `backend = "sqlite"`.
+    pub sqlite_path: String,
     pub sqlite_legacy_path: String,
     pub groups_dir: String,
+    /// Log a warning when a stored message's timestamp differs from the
+    /// Postgres clock by more than this many milliseconds (0 disables).
+    pub clock_skew_warn_ms: i64,
+    /// How often the background monitor probes the database (0 disables).
+    pub health_check_interval_ms: u64,
+    /// First reconnect backoff once the circuit opens; doubles per failure.
+    pub reconnect_backoff_initial_ms: u64,
+    pub reconnect_backoff_max_ms: u64,
+    /// Consecutive connection failures before the circuit opens.
+    pub circuit_failure_threshold: u32,
+}
+
+impl StorageConfig {
+    pub fn reconnect_policy(&self) -> crate::circuit::ReconnectPolicy {
+        crate::circuit::ReconnectPolicy {
+            failure_threshold: self.circuit_failure_threshold,
+            initial_backoff: std::time::Duration::from_millis(self.reconnect_backoff_initial_ms),
+            max_backoff: std::time::Duration::from_millis(
+                self.reconnect_backoff_max_ms
+                    .max(self.reconnect_backoff_initial_ms),
+            ),
+        }
+    }
 }
 
 impl Default for StorageConfig {
     fn default() -> Self {
         Self {
+            backend: StorageBackend::Postgres,
             postgres_dsn: None,
+            sqlite_path: "store/intercomd.db".to_string(),
             sqlite_legacy_path: "store/messages.db".to_string(),
             groups_dir: "groups".to_string(),
+            clock_skew_warn_ms: crate::persistence::DEFAULT_CLOCK_SKEW_WARN_MS,
+            health_check_interval_ms: 5_000,
+            reconnect_backoff_initial_ms: 500,
+            reconnect_backoff_max_ms: 30_000,
+            circuit_failure_threshold: 3,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    #[default]
+    Postgres,
+    Sqlite,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct RuntimeConfig {
@@ -97,6 +214,8 @@ impl Default for RuntimeConfig {
                 provider: "anthropic".to_string(),
                 default_model: "claude-opus-4-6".to_string(),
                 required_env: vec!["CLAUDE_CODE_OAUTH_TOKEN".to_string()],
+                protocol: RuntimeProtocol::MarkerJson,
+                ..RuntimeProfile::default()
             },
         );
         profiles.insert(
@@ -109,6 +228,8 @@ impl Default for RuntimeConfig {
                     "GEMINI_OAUTH_CLIENT_ID".to_string(),
                     "GEMINI_OAUTH_CLIENT_SECRET".to_string(),
                 ],
+                protocol: RuntimeProtocol::MarkerJson,
+                ..RuntimeProfile::default()
             },
         );
         profiles.insert(
@@ -122,6 +243,8 @@ impl Default for RuntimeConfig {
                     "CODEX_OAUTH_ID_TOKEN".to_string(),
                     "CODEX_OAUTH_ACCOUNT_ID".to_string(),
                 ],
+                protocol: RuntimeProtocol::MarkerJson,
+                ..RuntimeProfile::default()
             },
         );
 
@@ -139,6 +262,261 @@ pub struct RuntimeProfile {
     pub provider: String,
     pub default_model: String,
     pub required_env: Vec<String>,
+    /// Stdin/stdout framing spoken by the runner image.
+    pub protocol: RuntimeProtocol,
+    /// Image to run instead of the built-in one for this runtime.
+    pub image: String,
+    /// Pinned image digest (`sha256:...`), matched against the local
+    /// image's id or registry digests. Empty leaves the image unpinned.
+    pub image_digest: String,
+    /// Keep the container's stdin open and stream follow-up messages on it
+    /// as JSONL instead of IPC files. Needs a runner that reads stdin
+    /// line by line; `plain-text` runtimes always use files.
+    pub stdin_keepalive: bool,
+}
+
+/// Container engine that runs agents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContainerRuntimeConfig {
+    /// Overridden by `CONTAINER_RUNTIME`.
+    pub engine: ContainerEngine,
+    /// Binary to invoke instead of the engine's own name, e.g.
+    /// `/opt/podman/bin/podman`.
+    pub binary: Option<String>,
+    pub egress: EgressProxyConfig,
+    pub warm_pool: WarmPoolConfig,
+    pub images: ImageConfig,
+    pub logs: ContainerLogConfig,
+    pub quota: DiskQuotaConfig,
+    /// Where agents run: local containers or Kubernetes pods.
+    pub executor: ContainerExecutorKind,
+    pub kubernetes: KubernetesConfig,
+    /// Seconds a stopped container gets to exit after SIGTERM before it is
+    /// killed.
+    pub stop_timeout_secs: u64,
+    /// Seconds a new container has to write its first stdout line before
+    /// it is stopped as failed to start; 0 disables the probe.
+    pub startup_timeout_secs: u64,
+}
+
+impl Default for ContainerRuntimeConfig {
+    fn default() -> Self {
+        Self {
+            engine: ContainerEngine::default(),
+            binary: None,
+            egress: EgressProxyConfig::default(),
+            warm_pool: WarmPoolConfig::default(),
+            images: ImageConfig::default(),
+            logs: ContainerLogConfig::default(),
+            quota: DiskQuotaConfig::default(),
+            executor: ContainerExecutorKind::default(),
+            kubernetes: KubernetesConfig::default(),
+            stop_timeout_secs: 10,
+            startup_timeout_secs: 120,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerExecutorKind {
+    /// The detected docker/podman/nerdctl engine on this host.
+    #[default]
+    Local,
+    /// Pods in a cluster, driven through `kubectl`.
+    Kubernetes,
+}
+
+/// Cluster settings for `executor = "kubernetes"`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KubernetesConfig {
+    /// `kubectl` binary; it must already be authenticated (kubeconfig or
+    /// in-cluster service account).
+    pub kubectl: String,
+    /// kubeconfig context; empty uses the current one.
+    pub context: String,
+    pub namespace: String,
+    /// PersistentVolumeClaim holding the project root. Group folders, IPC
+    /// and sessions are mounted from it by sub-path, so intercomd must see
+    /// the same volume at its own project root.
+    pub volume_claim: String,
+    /// Service account for agent pods; empty uses the namespace default.
+    pub service_account: String,
+    pub image_pull_policy: String,
+    /// Labels a node must carry to run agents.
+    pub node_selector: BTreeMap<String, String>,
+    /// How long to wait for a pod to start before failing the run.
+    pub start_timeout_secs: u64,
+}
+
+impl Default for KubernetesConfig {
+    fn default() -> Self {
+        Self {
+            kubectl: "kubectl".to_string(),
+            context: String::new(),
+            namespace: "intercom".to_string(),
+            volume_claim: String::new(),
+            service_account: String::new(),
+            image_pull_policy: "IfNotPresent".to_string(),
+            node_selector: BTreeMap::new(),
+            start_timeout_secs: 300,
+        }
+    }
+}
+
+/// Retention for the per-run logs in `groups/{folder}/logs/`, applied
+/// after every run. Each limit is skipped when 0.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContainerLogConfig {
+    /// Newest successful-run logs kept per group.
+    pub keep_success: usize,
+    /// Newest failed or timed-out run logs kept per group.
+    pub keep_error: usize,
+    pub max_age_days: u64,
+    /// Cap on a group's log directory, oldest logs removed first.
+    pub max_total_mb: u64,
+    /// Gzip logs older than this.
+    pub compress_after_hours: u64,
+    /// Record each run's decoded outputs to `data/runs/{run_id}.jsonl`.
+    pub run_events: bool,
+    /// Stdout and stderr kept in memory per run; groups may override it
+    /// with `maxOutputBytes`.
+    pub max_output_bytes: usize,
+    /// Past the cap, write the full stream to an overflow file next to the
+    /// run log instead of dropping the rest.
+    pub spool_overflow: bool,
+}
+
+impl Default for ContainerLogConfig {
+    fn default() -> Self {
+        Self {
+            keep_success: 20,
+            keep_error: 50,
+            max_age_days: 30,
+            max_total_mb: 200,
+            compress_after_hours: 24,
+            run_events: true,
+            max_output_bytes: 1_048_576,
+            spool_overflow: true,
+        }
+    }
+}
+
+/// Disk quotas on group folders (`groups/{folder}`), checked against
+/// periodic scans. Groups may override the limits in their container config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskQuotaConfig {
+    /// Usage past this is flagged in `/status` and logged; 0 disables it.
+    pub soft_mb: u64,
+    /// New runs are refused past this; 0 disables it.
+    pub hard_mb: u64,
+    /// How often group folders are measured; 0 disables accounting.
+    pub scan_interval_secs: u64,
+}
+
+impl Default for DiskQuotaConfig {
+    fn default() -> Self {
+        Self {
+            soft_mb: 0,
+            hard_mb: 0,
+            scan_interval_secs: 600,
+        }
+    }
+}
+
+/// Pulling and verifying runtime images (see `RuntimeProfile::image_digest`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageConfig {
+    /// Pull every runtime image when intercomd starts.
+    pub pull_on_startup: bool,
+    /// Refuse to run an image whose digest differs from its profile's pin.
+    pub verify_digests: bool,
+    /// Re-pull unpinned images this often; 0 disables the check.
+    pub update_check_interval_secs: u64,
+}
+
+/// Pre-started containers that skip engine and runtime start-up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WarmPoolConfig {
+    pub enabled: bool,
+    /// Idle containers kept across all groups.
+    pub max_idle: usize,
+    /// How long a container may wait for work before it is stopped.
+    pub ttl_secs: u64,
+}
+
+impl Default for WarmPoolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_idle: 4,
+            ttl_secs: 600,
+        }
+    }
+}
+
+/// Proxy for groups with `network: "egress-allowlist"`. The proxy itself
+/// (squid, tinyproxy, ...) holds the allowlist; intercomd only attaches the
+/// container to its network and points the proxy variables at it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EgressProxyConfig {
+    /// Internal network shared with the proxy container, with no route out
+    /// of its own.
+    pub network: String,
+    /// e.g. `http://intercom-egress:3128`. Empty disables the mode.
+    pub proxy_url: String,
+    /// Hosts reached without the proxy.
+    pub no_proxy: String,
+}
+
+impl Default for EgressProxyConfig {
+    fn default() -> Self {
+        Self {
+            network: "intercom-egress".to_string(),
+            proxy_url: String::new(),
+            no_proxy: "localhost,127.0.0.1".to_string(),
+        }
+    }
+}
+
+/// Container CLI dialect.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerEngine {
+    /// The first of docker, podman and nerdctl that answers `info`.
+    #[default]
+    Auto,
+    Docker,
+    Podman,
+    Nerdctl,
+}
+
+impl ContainerEngine {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+            Self::Nerdctl => "nerdctl",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "docker" => Some(Self::Docker),
+            "podman" => Some(Self::Podman),
+            "nerdctl" => Some(Self::Nerdctl),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -155,6 +533,19 @@ pub struct OrchestratorConfig {
     pub idle_timeout_ms: u64,
     /// Folder name for the main group.
     pub main_group_folder: String,
+    /// Wake the message loop on Postgres NOTIFY instead of waiting for the
+    /// next poll. Polling continues as a fallback either way.
+    pub listen_notify: bool,
+    /// Drop a queued message check not started within this long, and tell
+    /// the chat (milliseconds). 0 waits indefinitely.
+    pub queue_deadline_ms: u64,
+    /// Ask a group's busy message container to wind down once a task has
+    /// waited behind it this long (milliseconds). 0 lets tasks wait.
+    pub task_preempt_after_ms: u64,
+    /// Stop the container outright once a critical task (main group or
+    /// one-shot) has waited behind it this long (milliseconds). 0 never
+    /// stops it.
+    pub critical_task_stop_after_ms: u64,
 }
 
 impl Default for OrchestratorConfig {
@@ -165,6 +556,10 @@ impl Default for OrchestratorConfig {
             poll_interval_ms: 1000,
             idle_timeout_ms: 300_000,
             main_group_folder: "main".to_string(),
+            listen_notify: true,
+            queue_deadline_ms: 0,
+            task_preempt_after_ms: 900_000,
+            critical_task_stop_after_ms: 0,
         }
     }
 }
@@ -178,6 +573,18 @@ pub struct SchedulerConfig {
     pub poll_interval_ms: u64,
     /// IANA timezone for cron expressions.
     pub timezone: String,
+    /// Defer low-priority task runs while interactive messages have been
+    /// queued behind the concurrency cap for longer than this (milliseconds).
+    /// 0 disables admission control.
+    pub max_interactive_wait_ms: u64,
+    /// Longest a task may start after it comes due, for tasks that do not
+    /// set `jitterMs` (milliseconds). 0 starts them on time.
+    pub jitter_ms: u64,
+    /// How often groups' calendar feeds are fetched (milliseconds).
+    /// 0 disables calendar feeds.
+    pub calendar_refresh_ms: u64,
+    /// How far ahead calendar events become tasks (days).
+    pub calendar_horizon_days: u32,
 }
 
 impl Default for SchedulerConfig {
@@ -186,23 +593,457 @@ impl Default for SchedulerConfig {
             enabled: false,
             poll_interval_ms: 10_000,
             timezone: "UTC".to_string(),
+            max_interactive_wait_ms: 30_000,
+            jitter_ms: 0,
+            calendar_refresh_ms: 900_000,
+            calendar_horizon_days: 7,
+        }
+    }
+}
+
+/// Container IPC directory watcher.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpcConfig {
+    /// Poll interval for each shard (milliseconds).
+    pub poll_interval_ms: u64,
+    /// Number of watcher tasks group directories are spread across.
+    pub shards: usize,
+    /// Re-list every directory regardless of mtime this often (milliseconds).
+    pub rescan_interval_ms: u64,
+    /// Who carries out task commands from containers.
+    pub tasks: IpcTaskHandling,
+    /// How long a successful Demarch read answer is reused for an identical
+    /// query (milliseconds); 0 disables the cache.
+    pub query_cache_ttl_ms: u64,
+    /// Query results larger than this are split into part files (bytes);
+    /// 0 writes every result as one file.
+    pub response_chunk_bytes: usize,
+    /// Query results are cut to this size (bytes); 0 for no limit.
+    pub response_max_bytes: usize,
+}
+
+impl Default for IpcConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval_ms: 1000,
+            shards: 4,
+            rescan_interval_ms: 60_000,
+            tasks: IpcTaskHandling::default(),
+            query_cache_ttl_ms: 10_000,
+            response_chunk_bytes: 256 * 1024,
+            response_max_bytes: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Who carries out the `schedule_task`, `pause_task`, `resume_task` and
+/// `cancel_task` commands containers write to `ipc/{group}/tasks/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IpcTaskHandling {
+    /// intercomd applies them to storage itself, and forwards them to the
+    /// Node host only when no storage is configured.
+    #[default]
+    Native,
+    /// They are forwarded to the Node host.
+    Host,
+}
+
+/// Named read-only report queries served by `POST /v1/db/query`. Only
+/// queries listed here can run; callers supply a name and parameters,
+/// never SQL.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryConfig {
+    /// Row cap for queries that don't set their own `max_rows`.
+    pub max_rows: usize,
+    /// Statement timeout for report queries (milliseconds, Postgres only).
+    pub timeout_ms: u64,
+    pub named: BTreeMap<String, NamedQuery>,
+}
+
+impl Default for QueryConfig {
+    fn default() -> Self {
+        Self {
+            max_rows: 1000,
+            timeout_ms: 5_000,
+            named: BTreeMap::new(),
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamedQuery {
+    #[serde(default)]
+    pub description: String,
+    /// A single SELECT. Parameters are bound as text, in `params` order, to
+    /// `$1`, `$2`, ...; cast them in SQL where a typed value is needed.
+    pub sql: String,
+    #[serde(default)]
+    pub params: Vec<String>,
+    #[serde(default)]
+    pub max_rows: Option<usize>,
+}
+
+/// Git snapshots of group folders around container runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// Commit the group folder before and after every container run.
+    pub enabled: bool,
+    /// Snapshot commits kept per group; older history is squashed into a
+    /// single base commit. 0 keeps everything.
+    pub keep: usize,
+    /// Sender IDs allowed to run `/revert-last`. Empty disables the command.
+    pub admins: Vec<String>,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keep: 100,
+            admins: Vec::new(),
+        }
+    }
+}
+
+/// Who may run a chat command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandPermission {
+    #[default]
+    Anyone,
+    /// Only in the main group.
+    Main,
+    /// Only senders listed in `commands.admins`.
+    Admin,
+}
+
+/// Chat commands beyond the built-in ones.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandsConfig {
+    /// Sender IDs allowed to run commands whose permission is `admin`.
+    pub admins: Vec<String>,
+    /// Prompt template commands, by name (`/<name>` in chat).
+    pub custom: BTreeMap<String, PromptCommand>,
+}
+
+/// A command that posts a canned prompt into the chat as the sender, so the
+/// agent runs it like a typed message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptCommand {
+    /// Shown in `/help`.
+    #[serde(default)]
+    pub description: String,
+    /// `{args}` is replaced with whatever followed the command; without it,
+    /// any arguments are appended on a new paragraph.
+    pub prompt: String,
+    #[serde(default)]
+    pub permission: CommandPermission,
+}
+
+/// Where inbound Telegram updates come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TelegramIngest {
+    /// The Node host receives updates and forwards them.
+    #[default]
+    Host,
+    /// intercomd long-polls `getUpdates` itself. The host must not poll the
+    /// same bot (set `TELEGRAM_INGEST=poll` there too).
+    Poll,
+    /// Telegram pushes updates to `/v1/telegram/webhook`. The host must not
+    /// poll (set `TELEGRAM_INGEST=webhook` there too).
+    Webhook,
+}
+
+/// Where `/v1/telegram/ingress` looks up the registered group for a chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IngressGroupSource {
+    /// The daemon's registered groups, then the live store, then the legacy
+    /// SQLite file for chats registered nowhere else.
+    #[default]
+    Live,
+    /// Only the legacy SQLite file, as before the Postgres cutover.
+    Sqlite,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelegramConfig {
+    pub ingest: TelegramIngest,
+    /// Long-poll timeout passed to `getUpdates`.
+    pub poll_timeout_secs: u64,
+    /// Delay before retrying after a failed `getUpdates` or `setWebhook` call.
+    pub poll_retry_ms: u64,
+    /// Public HTTPS URL Telegram should push to, ending in
+    /// `/v1/telegram/webhook`. Registered with `setWebhook` at startup; leave
+    /// unset to manage the webhook yourself.
+    pub webhook_url: Option<String>,
+    /// Expected `X-Telegram-Bot-Api-Secret-Token`. Derived from the bot token
+    /// when unset.
+    pub webhook_secret: Option<String>,
+    /// Minimum gap between outbound messages to one chat. Telegram allows
+    /// about one per second per chat before answering 429.
+    pub send_interval_ms: u64,
+    /// Retries per send request after a 429, across all of its chunks.
+    pub max_send_retries: u32,
+    /// Longest `retry_after` worth waiting for; a longer one fails the send.
+    pub max_retry_after_secs: u64,
+    pub ingress_groups: IngressGroupSource,
+    /// Post agent output as it streams and edit it in place until the final
+    /// text arrives, instead of sending only the final message.
+    pub draft_edits: bool,
+    /// Minimum gap between edits of one draft message (milliseconds).
+    pub draft_edit_interval_ms: u64,
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        Self {
+            ingest: TelegramIngest::Host,
+            poll_timeout_secs: 30,
+            poll_retry_ms: 5_000,
+            webhook_url: None,
+            webhook_secret: None,
+            send_interval_ms: 1_000,
+            max_send_retries: 3,
+            max_retry_after_secs: 60,
+            ingress_groups: IngressGroupSource::Live,
+            draft_edits: false,
+            draft_edit_interval_ms: 2_000,
+        }
+    }
+}
+
+/// Matrix client bridge. The access token comes from `MATRIX_ACCESS_TOKEN`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatrixConfig {
+    /// Run the `/sync` loop and store messages from registered rooms.
+    pub enabled: bool,
+    /// Client-server API base, e.g. `https://matrix.example.org`.
+    pub homeserver_url: String,
+    /// The bot's own user id; its events are never ingested.
+    pub user_id: String,
+    /// Long-poll timeout passed to `/sync`.
+    pub sync_timeout_secs: u64,
+    /// Delay before retrying after a failed `/sync`.
+    pub sync_retry_ms: u64,
+}
+
+impl Default for MatrixConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            homeserver_url: String::new(),
+            user_id: String::new(),
+            sync_timeout_secs: 30,
+            sync_retry_ms: 5_000,
+        }
+    }
+}
+
+/// Email gateway: inbound mail posted by a provider webhook, replies over
+/// SMTP. The SMTP password comes from `EMAIL_SMTP_PASSWORD`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    /// Accept `/v1/email/inbound` and register the SMTP bridge.
+    pub enabled: bool,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_security: SmtpSecurity,
+    /// Login for the relay; empty sends unauthenticated.
+    pub smtp_username: String,
+    /// Sender of replies, e.g. `Amtiskaw <amtiskaw@example.org>`.
+    pub from_address: String,
+    /// HMAC-SHA256 key inbound posts are signed with. Overridden by
+    /// `EMAIL_INBOUND_SECRET`.
+    pub inbound_secret: Option<String>,
+    /// Subject for a reply to a chat with no inbound mail since startup.
+    pub default_subject: String,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            smtp_host: String::new(),
+            smtp_port: 587,
+            smtp_security: SmtpSecurity::Starttls,
+            smtp_username: String::new(),
+            from_address: String::new(),
+            inbound_secret: None,
+            default_subject: "Message from your assistant".to_string(),
+        }
+    }
+}
+
+/// How the SMTP connection is secured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    #[default]
+    Starttls,
+    /// Implicit TLS, usually port 465.
+    Tls,
+    /// Plaintext, for a local relay only.
+    None,
+}
+
+/// Markup a channel renders in outbound text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkdownDialect {
+    #[default]
+    Plain,
+    /// Single-character emphasis (`*bold*`, `_italic_`), as in WhatsApp or
+    /// Telegram's legacy `Markdown` mode.
+    Markdown,
+    MarkdownV2,
+    Html,
+}
+
+/// What a channel can carry, consulted by the outbound splitter, the
+/// bridges and the runner context instead of per-channel constants.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelProfile {
+    /// JIDs starting with this prefix belong to the channel.
+    pub jid_prefix: String,
+    /// Longest single message, in characters. Longer text is split.
+    pub max_chars: usize,
+    pub markdown: MarkdownDialect,
+    pub supports_edit: bool,
+    pub supports_files: bool,
+}
+
+impl Default for ChannelProfile {
+    fn default() -> Self {
+        Self {
+            jid_prefix: String::new(),
+            max_chars: 4096,
+            markdown: MarkdownDialect::Plain,
+            supports_edit: true,
+            supports_files: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelsConfig {
+    /// Profile for JIDs no profile's `jid_prefix` matches.
+    pub default_channel: String,
+    pub profiles: BTreeMap<String, ChannelProfile>,
+}
+
+impl Default for ChannelsConfig {
+    fn default() -> Self {
+        let mut profiles = BTreeMap::new();
+        profiles.insert(
+            "telegram".to_string(),
+            ChannelProfile {
+                jid_prefix: "tg:".to_string(),
+                markdown: MarkdownDialect::MarkdownV2,
+                ..ChannelProfile::default()
+            },
+        );
+        profiles.insert(
+            "whatsapp".to_string(),
+            ChannelProfile {
+                jid_prefix: "wa:".to_string(),
+                markdown: MarkdownDialect::Markdown,
+                supports_edit: false,
+                ..ChannelProfile::default()
+            },
+        );
+        profiles.insert(
+            "matrix".to_string(),
+            ChannelProfile {
+                jid_prefix: "mx:".to_string(),
+                max_chars: 16_000,
+                markdown: MarkdownDialect::Html,
+                ..ChannelProfile::default()
+            },
+        );
+        profiles.insert(
+            "email".to_string(),
+            ChannelProfile {
+                jid_prefix: "email:".to_string(),
+                max_chars: 100_000,
+                supports_edit: false,
+                supports_files: false,
+                ..ChannelProfile::default()
+            },
+        );
+        profiles.insert(
+            "webhook".to_string(),
+            ChannelProfile {
+                jid_prefix: "hook:".to_string(),
+                supports_files: false,
+                ..ChannelProfile::default()
+            },
+        );
+        Self {
+            default_channel: "telegram".to_string(),
+            profiles,
+        }
+    }
+}
+
+impl ChannelsConfig {
+    /// The named profile, or the built-in defaults if it is not configured.
+    pub fn profile(&self, channel: &str) -> ChannelProfile {
+        self.profiles.get(channel).cloned().unwrap_or_default()
+    }
+
+    /// Channel name for a JID: the longest matching `jid_prefix`, else
+    /// `default_channel`.
+    pub fn channel_for_jid(&self, jid: &str) -> &str {
+        self.profiles
+            .iter()
+            .filter(|(_, p)| !p.jid_prefix.is_empty() && jid.starts_with(&p.jid_prefix))
+            .max_by_key(|(_, p)| p.jid_prefix.len())
+            .map(|(name, _)| name.as_str())
+            .unwrap_or(&self.default_channel)
+    }
+
+    pub fn profile_for_jid(&self, jid: &str) -> ChannelProfile {
+        self.profile(self.channel_for_jid(jid))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct DemarchConfig {
     pub enabled: bool,
+    /// Where answers come from; `mock` serves canned data without `ic`/`bd`.
+    pub backend: DemarchBackendKind,
     pub require_main_group_for_writes: bool,
     pub read_allowlist: Vec<String>,
     pub write_allowlist: Vec<String>,
+    /// Wall-clock limit for one `ic`/`bd` invocation; the child is killed when
+    /// it elapses.
+    pub timeout_ms: u64,
+    /// Upper bound on CLI invocations running at the same time.
+    pub max_concurrent: usize,
+    /// How long successful read answers are reused, keyed by operation name
+    /// (`sprint_phase`, `review_summary`, ...). Unlisted operations are never
+    /// cached; any successful write empties the cache.
+    pub cache_ttl_ms: BTreeMap<String, u64>,
 }
 
 impl Default for DemarchConfig {
     fn default() -> Self {
         Self {
             enabled: true,
+            backend: DemarchBackendKind::Cli,
             require_main_group_for_writes: true,
             read_allowlist: vec![
                 "ic run current --json".to_string(),
@@ -211,9 +1052,14 @@ impl Default for DemarchConfig {
                 "ic run artifact list --json".to_string(),
                 "ic run artifact get --json".to_string(),
                 "ic events tail --consumer=intercom --json".to_string(),
+                "ic events follow --consumer=intercom --json".to_string(),
                 "bd list --json".to_string(),
                 "bd ready --json".to_string(),
                 "bd show --json".to_string(),
+                "ic gate list --json".to_string(),
+                "bd comments --json".to_string(),
+                "bd history --json".to_string(),
+                "ic run artifact diff --json".to_string(),
             ],
             write_allowlist: vec![
                 "bd create --json".to_string(),
@@ -222,10 +1068,26 @@ impl Default for DemarchConfig {
                 "ic gate override --json".to_string(),
                 "ic run create --json".to_string(),
             ],
+            timeout_ms: 30_000,
+            max_concurrent: 4,
+            cache_ttl_ms: BTreeMap::from([
+                ("sprint_phase".to_string(), 5_000),
+                ("review_summary".to_string(), 30_000),
+            ]),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DemarchBackendKind {
+    /// Shell out to the `ic` and `bd` CLIs.
+    #[default]
+    Cli,
+    /// In-memory issues, runs and gates, for tests and demos without a kernel.
+    Mock,
+}
+
 pub fn load_config(path: impl AsRef<Path>) -> anyhow::Result<IntercomConfig> {
     let path = path.as_ref();
     if !path.exists() {
@@ -261,6 +1123,36 @@ impl IntercomConfig {
             }
         }
 
+        if let Ok(token) = std::env::var("INTERCOMD_ADMIN_TOKEN") {
+            if !token.trim().is_empty() {
+                self.server.admin_token = Some(token.trim().to_string());
+            }
+        }
+
+        if let Ok(url) = std::env::var("MATRIX_HOMESERVER_URL") {
+            if !url.trim().is_empty() {
+                self.matrix.homeserver_url = url.trim().to_string();
+            }
+        }
+
+        if let Ok(engine) = std::env::var("CONTAINER_RUNTIME") {
+            if let Some(engine) = ContainerEngine::parse(&engine) {
+                self.container.engine = engine;
+            }
+        }
+
+        if let Ok(secret) = std::env::var("EMAIL_INBOUND_SECRET") {
+            if !secret.trim().is_empty() {
+                self.email.inbound_secret = Some(secret.trim().to_string());
+            }
+        }
+
+        if let Ok(secret) = std::env::var("TELEGRAM_WEBHOOK_SECRET") {
+            if !secret.trim().is_empty() {
+                self.telegram.webhook_secret = Some(secret.trim().to_string());
+            }
+        }
+
         self
     }
 }
@@ -291,4 +1183,210 @@ mod tests {
         assert_eq!(parsed.server.request_timeout_ms, 30_000);
         assert!(parsed.runtimes.profiles.contains_key("claude"));
     }
+
+    #[test]
+    fn parse_sqlite_storage_backend() {
+        let parsed: IntercomConfig = toml::from_str(
+            r#"
+            [storage]
+            backend = "sqlite"
+            "#,
+        )
+        .expect("parse toml");
+
+        assert_eq!(parsed.storage.backend, StorageBackend::Sqlite);
+        assert_eq!(parsed.storage.sqlite_path, "store/intercomd.db");
+        assert_eq!(
+            IntercomConfig::default().storage.backend,
+            StorageBackend::Postgres
+        );
+    }
+
+    #[test]
+    fn parse_container_engine() {
+        let parsed: IntercomConfig = toml::from_str(
+            r#"
+            [container]
+            engine = "podman"
+            binary = "/opt/podman/bin/podman"
+            executor = "kubernetes"
+
+            [container.kubernetes]
+            namespace = "agents"
+            volume_claim = "intercom-data"
+            node_selector = { pool = "agents" }
+
+            [container.warm_pool]
+            enabled = true
+            ttl_secs = 120
+
+            [container.logs]
+            keep_error = 10
+            compress_after_hours = 0
+            "#,
+        )
+        .expect("parse toml");
+
+        assert_eq!(parsed.container.engine, ContainerEngine::Podman);
+        assert_eq!(
+            parsed.container.binary.as_deref(),
+            Some("/opt/podman/bin/podman")
+        );
+        assert_eq!(parsed.container.egress.network, "intercom-egress");
+        assert!(parsed.container.egress.proxy_url.is_empty());
+        assert!(parsed.container.warm_pool.enabled);
+        assert_eq!(parsed.container.warm_pool.ttl_secs, 120);
+        assert_eq!(parsed.container.warm_pool.max_idle, 4);
+        assert_eq!(parsed.container.logs.keep_error, 10);
+        assert_eq!(parsed.container.logs.keep_success, 20);
+        assert_eq!(parsed.container.logs.compress_after_hours, 0);
+        assert_eq!(parsed.container.executor, ContainerExecutorKind::Kubernetes);
+        assert_eq!(parsed.container.kubernetes.namespace, "agents");
+        assert_eq!(parsed.container.kubernetes.kubectl, "kubectl");
+        assert_eq!(parsed.container.kubernetes.node_selector["pool"], "agents");
+        assert_eq!(
+            IntercomConfig::default().container.executor,
+            ContainerExecutorKind::Local
+        );
+        assert_eq!(
+            IntercomConfig::default().container.engine,
+            ContainerEngine::Auto
+        );
+        assert_eq!(
+            ContainerEngine::parse(" Nerdctl "),
+            Some(ContainerEngine::Nerdctl)
+        );
+        assert_eq!(ContainerEngine::parse("lxc"), None);
+    }
+
+    #[test]
+    fn parse_runtime_profile_protocol() {
+        let parsed: IntercomConfig = toml::from_str(
+            r#"
+            [runtimes.profiles.echo]
+            provider = "local"
+            protocol = "plain-text"
+            image = "registry.example/echo:1.2"
+            image_digest = "sha256:abc123"
+
+            [container.images]
+            verify_digests = true
+            "#,
+        )
+        .expect("parse toml");
+
+        assert_eq!(
+            parsed.runtimes.profiles["echo"].protocol,
+            RuntimeProtocol::PlainText
+        );
+        assert_eq!(
+            parsed.runtimes.profiles["echo"].image_digest,
+            "sha256:abc123"
+        );
+        assert!(parsed.container.images.verify_digests);
+        assert!(!parsed.container.images.pull_on_startup);
+        assert!(
+            IntercomConfig::default().runtimes.profiles["claude"]
+                .image
+                .is_empty()
+        );
+        assert_eq!(
+            IntercomConfig::default().runtimes.profiles["claude"].protocol,
+            RuntimeProtocol::MarkerJson
+        );
+    }
+
+    #[test]
+    fn parse_named_queries() {
+        let parsed: IntercomConfig = toml::from_str(
+            r#"
+            [queries]
+            max_rows = 50
+
+            [queries.named.recent_volume]
+            description = "Messages per chat since a timestamp"
+            sql = "SELECT chat_jid, COUNT(*) AS n FROM messages WHERE timestamp >= $1 GROUP BY chat_jid"
+            params = ["since"]
+            "#,
+        )
+        .expect("parse toml");
+
+        assert_eq!(parsed.queries.max_rows, 50);
+        assert_eq!(parsed.queries.timeout_ms, 5_000);
+        let query = &parsed.queries.named["recent_volume"];
+        assert_eq!(query.params, ["since"]);
+        assert_eq!(query.max_rows, None);
+    }
+
+    #[test]
+    fn parse_prompt_commands() {
+        let parsed: IntercomConfig = toml::from_str(
+            r#"
+            [commands]
+            admins = ["42"]
+
+            [commands.custom.standup]
+            description = "Summarize yesterday"
+            prompt = "Summarize yesterday's commits. {args}"
+
+            [commands.custom.deploy]
+            prompt = "Deploy the staging branch."
+            permission = "admin"
+            "#,
+        )
+        .expect("parse toml");
+
+        assert_eq!(parsed.commands.admins, ["42"]);
+        assert_eq!(
+            parsed.commands.custom["standup"].permission,
+            CommandPermission::Anyone
+        );
+        assert_eq!(
+            parsed.commands.custom["deploy"].permission,
+            CommandPermission::Admin
+        );
+        assert!(parsed.commands.custom["deploy"].description.is_empty());
+    }
+
+    #[test]
+    fn channel_profiles_resolve_by_jid_prefix() {
+        let parsed: IntercomConfig = toml::from_str(
+            r#"
+            [channels.profiles.telegram]
+            jid_prefix = "tg:"
+            max_chars = 2000
+            markdown = "markdown_v2"
+
+            [channels.profiles.matrix]
+            jid_prefix = "mx:"
+            max_chars = 30000
+            markdown = "html"
+            supports_edit = false
+            "#,
+        )
+        .expect("parse toml");
+
+        let channels = &parsed.channels;
+        assert_eq!(channels.channel_for_jid("mx:!room:example.org"), "matrix");
+        assert_eq!(channels.channel_for_jid("tg:-100"), "telegram");
+        assert_eq!(channels.channel_for_jid("120363@g.us"), "telegram");
+        let telegram = channels.profile_for_jid("tg:-100");
+        assert_eq!(telegram.max_chars, 2000);
+        assert_eq!(telegram.markdown, MarkdownDialect::MarkdownV2);
+        assert!(telegram.supports_edit);
+        assert!(!channels.profile("matrix").supports_edit);
+        assert_eq!(
+            IntercomConfig::default()
+                .channels
+                .profile_for_jid("tg:1")
+                .max_chars,
+            4096
+        );
+        let defaults = IntercomConfig::default().channels;
+        assert_eq!(defaults.channel_for_jid("mx:!room:example.org"), "matrix");
+        assert_eq!(defaults.profile("matrix").markdown, MarkdownDialect::Html);
+        assert_eq!(defaults.channel_for_jid("email:ana@example.org"), "email");
+        assert!(!defaults.profile("email").supports_edit);
+        assert_eq!(defaults.channel_for_jid("hook:support"), "webhook");
+    }
 }
@@ -9,6 +9,7 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use crate::persistence::Attachment;
 use crate::runtime::RuntimeKind;
 
 /// Sentinel markers for robust output parsing.
@@ -36,6 +37,18 @@ pub struct ContainerInput {
     /// Zeroed from memory after writing to the container process.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub secrets: Option<HashMap<String, String>>,
+    /// Media that arrived with the prompt messages, so the agent can open it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// Ties the run to the inbound message (or task run) that caused it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// Channel message id of the question the reply will be threaded under.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to_message_id: Option<String>,
+    /// Stdin stays open after this line and carries follow-ups as JSONL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdin_keepalive: Option<bool>,
 }
 
 /// Output payload extracted from container stdout between OUTPUT markers.
@@ -52,6 +65,38 @@ pub struct ContainerOutput {
     pub model: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub event: Option<StreamEvent>,
+    /// Tokens the turn used, when the runtime reports them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+}
+
+/// Token counts for a turn or a whole run, in the runners' wire format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_read_tokens: u64,
+    #[serde(default)]
+    pub cache_write_tokens: u64,
+    /// Cost in USD, when the runtime works it out itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+impl TokenUsage {
+    /// Add another turn's usage. The cost stays known only while every
+    /// turn reported one.
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+        self.cache_write_tokens += other.cache_write_tokens;
+        self.cost_usd = self.cost_usd.zip(other.cost_usd).map(|(a, b)| a + b);
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -125,11 +170,8 @@ pub fn extract_output_markers(buf: &str) -> (Vec<String>, usize) {
     let mut consumed = 0;
 
     let mut search_from = 0;
-    loop {
-        let start = match buf[search_from..].find(OUTPUT_START_MARKER) {
-            Some(pos) => search_from + pos,
-            None => break,
-        };
+    while let Some(pos) = buf[search_from..].find(OUTPUT_START_MARKER) {
+        let start = search_from + pos;
 
         let after_start = start + OUTPUT_START_MARKER.len();
         let end = match buf[after_start..].find(OUTPUT_END_MARKER) {
@@ -163,6 +205,10 @@ mod tests {
             assistant_name: Some("Amtiskaw".to_string()),
             model: None,
             secrets: None,
+            attachments: Vec::new(),
+            correlation_id: None,
+            reply_to_message_id: None,
+            stdin_keepalive: None,
         };
         let json = serde_json::to_string(&input).unwrap();
         assert!(json.contains("\"chatJid\""));
@@ -172,6 +218,7 @@ mod tests {
         // Optional None fields should be absent
         assert!(!json.contains("\"model\""));
         assert!(!json.contains("\"secrets\""));
+        assert!(!json.contains("\"attachments\""));
     }
 
     #[test]
@@ -0,0 +1,39 @@
+//! Correlation ids tie one inbound message to everything it causes: the
+//! queued run, the container input and log, task run rows, the stored reply
+//! and the tracing spans in between.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static SEQUENCE: AtomicU32 = AtomicU32::new(0);
+
+/// New correlation id: 16 hex chars, millisecond time followed by a
+/// per-process sequence mixed with the pid, so ids from concurrent daemons
+/// do not collide and roughly sort by creation time.
+pub fn new_correlation_id() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let seq = SEQUENCE
+        .fetch_add(1, Ordering::Relaxed)
+        .wrapping_add(std::process::id().wrapping_mul(2_654_435_761));
+    format!("{:011x}{:05x}", millis & 0xfff_ffff_ffff, seq & 0xf_ffff)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn ids_are_fixed_width_hex_and_unique() {
+        let ids: HashSet<String> = (0..1000).map(|_| new_correlation_id()).collect();
+        assert_eq!(ids.len(), 1000);
+        assert!(
+            ids.iter()
+                .all(|id| id.len() == 16 && id.chars().all(|c| c.is_ascii_hexdigit()))
+        );
+    }
+}
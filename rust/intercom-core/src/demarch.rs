@@ -1,11 +1,22 @@
+use std::collections::HashMap;
 use std::fs;
+use std::future::Future;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::pin::Pin;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, anyhow};
+use anyhow::{Context, anyhow, bail};
 use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::{Semaphore, mpsc};
+use tracing::warn;
 
-use crate::config::DemarchConfig;
+use crate::config::{DemarchBackendKind, DemarchConfig};
+use crate::demarch_mock::MockBackend;
+use crate::persistence::DemarchAuditEntry;
+use crate::storage::SharedStorage;
 
 const STANDALONE_MSG: &str =
     "Demarch kernel not available — Intercom is running in standalone mode.";
@@ -17,17 +28,22 @@ pub enum DemarchStatus {
     Error,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct DemarchResponse {
     pub status: DemarchStatus,
     pub result: String,
+    /// `result` parsed, when a successful answer is a JSON object or array.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
 }
 
 impl DemarchResponse {
     pub fn ok(result: impl Into<String>) -> Self {
+        let result = result.into();
         Self {
             status: DemarchStatus::Ok,
-            result: result.into(),
+            data: parse_structured(&result),
+            result,
         }
     }
 
@@ -35,10 +51,20 @@ impl DemarchResponse {
         Self {
             status: DemarchStatus::Error,
             result: result.into(),
+            data: None,
         }
     }
 }
 
+/// Bare scalars are left alone so plain-text answers like `42` or `true`
+/// stay text.
+fn parse_structured(result: &str) -> Option<serde_json::Value> {
+    match serde_json::from_str(result.trim()) {
+        Ok(value @ (serde_json::Value::Object(_) | serde_json::Value::Array(_))) => Some(value),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(tag = "op", rename_all = "snake_case")]
 pub enum ReadOperation {
@@ -60,6 +86,51 @@ pub enum ReadOperation {
         limit: Option<u32>,
         since: Option<String>,
     },
+    /// Gates on a run (the current one when `run_id` is absent), open ones
+    /// unless `status` says otherwise.
+    ListGates {
+        run_id: Option<String>,
+        status: Option<String>,
+    },
+    IssueComments {
+        id: String,
+    },
+    IssueHistory {
+        id: String,
+    },
+    /// Per-artifact change summary for a run, not the full diff.
+    RunDiff {
+        run_id: Option<String>,
+    },
+    /// Several reads run concurrently. Answers with a JSON array holding one
+    /// response per op, in the order given. Batches do not nest.
+    Batch {
+        ops: Vec<ReadOperation>,
+    },
+}
+
+/// Most reads one [`ReadOperation::Batch`] may carry.
+pub const MAX_BATCH_OPS: usize = 16;
+
+impl ReadOperation {
+    /// The serialized `op` tag, which is also the key for per-operation
+    /// settings such as `[demarch.cache_ttl_ms]`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RunStatus { .. } => "run_status",
+            Self::SprintPhase => "sprint_phase",
+            Self::SearchBeads { .. } => "search_beads",
+            Self::SpecLookup { .. } => "spec_lookup",
+            Self::ReviewSummary => "review_summary",
+            Self::NextWork => "next_work",
+            Self::RunEvents { .. } => "run_events",
+            Self::ListGates { .. } => "list_gates",
+            Self::IssueComments { .. } => "issue_comments",
+            Self::IssueHistory { .. } => "issue_history",
+            Self::RunDiff { .. } => "run_diff",
+            Self::Batch { .. } => "batch",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -94,6 +165,19 @@ pub enum WriteOperation {
     },
 }
 
+impl WriteOperation {
+    /// The serialized `op` tag, as recorded in the audit log.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CreateIssue { .. } => "create_issue",
+            Self::UpdateIssue { .. } => "update_issue",
+            Self::CloseIssue { .. } => "close_issue",
+            Self::StartRun { .. } => "start_run",
+            Self::ApproveGate { .. } => "approve_gate",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DemarchCommandPlan {
     pub bin: &'static str,
@@ -101,45 +185,271 @@ pub struct DemarchCommandPlan {
     pub args: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+/// Boxed future returned by `DemarchBackend` methods (keeps the trait
+/// object-safe).
+pub type DemarchFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Where Demarch answers come from. [`DemarchAdapter`] applies the enabled
+/// flag, the main-group write rule and the read cache on top of whichever
+/// backend `demarch.backend` selects.
+pub trait DemarchBackend: Send + Sync {
+    /// Short backend name for logs and health output.
+    fn kind(&self) -> &'static str;
+
+    /// Whether the kernel can answer at all. Blocking.
+    fn kernel_available(&self) -> bool;
+
+    /// Answer one read. The adapter splits up [`ReadOperation::Batch`] before
+    /// it gets here.
+    fn read<'a>(&'a self, operation: &'a ReadOperation) -> DemarchFuture<'a, DemarchResponse>;
+
+    fn write<'a>(&'a self, operation: &'a WriteOperation) -> DemarchFuture<'a, DemarchResponse>;
+
+    /// Stream kernel events after `since`, one JSON object per item. The
+    /// channel closes when the source ends.
+    fn follow_events(
+        &self,
+        since: Option<String>,
+    ) -> DemarchFuture<'_, anyhow::Result<mpsc::Receiver<String>>>;
+}
+
+#[derive(Clone)]
 pub struct DemarchAdapter {
     config: DemarchConfig,
-    project_root: PathBuf,
+    backend: Arc<dyn DemarchBackend>,
+    /// Successful read answers keyed by the serialized operation; shared by
+    /// clones so every caller sees one cache.
+    cache: Arc<Mutex<HashMap<String, (Instant, DemarchResponse)>>>,
+    /// Where every write is recorded, when storage is configured.
+    audit: Option<SharedStorage>,
+}
+
+impl std::fmt::Debug for DemarchAdapter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DemarchAdapter")
+            .field("config", &self.config)
+            .field("backend", &self.backend.kind())
+            .field(
+                "audit",
+                &self.audit.as_ref().map(|storage| storage.backend()),
+            )
+            .finish()
+    }
 }
 
 impl DemarchAdapter {
+    /// Build the adapter with the backend named by `config.backend`.
     pub fn new(config: DemarchConfig, project_root: impl AsRef<Path>) -> Self {
+        let backend: Arc<dyn DemarchBackend> = match config.backend {
+            DemarchBackendKind::Cli => Arc::new(CliBackend::new(config.clone(), project_root)),
+            DemarchBackendKind::Mock => Arc::new(MockBackend::new()),
+        };
+        Self::with_backend(config, backend)
+    }
+
+    pub fn with_backend(config: DemarchConfig, backend: Arc<dyn DemarchBackend>) -> Self {
         Self {
             config,
-            project_root: project_root.as_ref().to_path_buf(),
+            backend,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            audit: None,
         }
     }
 
-    pub fn execute_read(&self, operation: ReadOperation) -> DemarchResponse {
+    /// Record every write, allowed or not, in `storage`'s `demarch_audit`.
+    pub fn with_audit(mut self, storage: SharedStorage) -> Self {
+        self.audit = Some(storage);
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn backend_kind(&self) -> &'static str {
+        self.backend.kind()
+    }
+
+    /// Whether the kernel can answer (for the CLI backend, `ic` is on PATH).
+    /// Blocking.
+    pub fn kernel_available(&self) -> bool {
+        self.backend.kernel_available()
+    }
+
+    pub async fn execute_read(&self, operation: ReadOperation) -> DemarchResponse {
         if !self.config.enabled {
             return DemarchResponse::error("Demarch integration is disabled.");
         }
-
         match operation {
-            ReadOperation::ReviewSummary => self.handle_review_summary(),
-            op => match Self::plan_read(&op) {
-                Some(plan) => self.execute_plan(plan, false),
-                None => DemarchResponse::error("Read operation is not implemented."),
-            },
+            ReadOperation::Batch { ops } => self.execute_batch(ops).await,
+            operation => self.read_one(operation).await,
         }
     }
 
-    pub fn execute_write(&self, operation: WriteOperation, is_main: bool) -> DemarchResponse {
-        if !self.config.enabled {
-            return DemarchResponse::error("Demarch integration is disabled.");
+    /// One read, answered from the cache when its TTL allows.
+    async fn read_one(&self, operation: ReadOperation) -> DemarchResponse {
+        let ttl = self
+            .config
+            .cache_ttl_ms
+            .get(operation.name())
+            .map(|ms| Duration::from_millis(*ms))
+            .filter(|ttl| !ttl.is_zero());
+        let key = ttl.map(|_| serde_json::to_string(&operation).unwrap_or_default());
+        if let (Some(ttl), Some(key)) = (ttl, &key)
+            && let Some((at, response)) = self.cache.lock().unwrap().get(key)
+            && at.elapsed() < ttl
+        {
+            return response.clone();
+        }
+
+        let response = self.backend.read(&operation).await;
+
+        if let Some(key) = key
+            && response.status == DemarchStatus::Ok
+        {
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(key, (Instant::now(), response.clone()));
+        }
+        response
+    }
+
+    /// Run each op on its own task, going through the cache like a single
+    /// read, and collect the answers by index.
+    async fn execute_batch(&self, ops: Vec<ReadOperation>) -> DemarchResponse {
+        if ops.is_empty() {
+            return DemarchResponse::error("Batch has no operations.");
+        }
+        if ops.len() > MAX_BATCH_OPS {
+            return DemarchResponse::error(format!(
+                "Batch has {} operations; at most {MAX_BATCH_OPS} are allowed.",
+                ops.len()
+            ));
+        }
+        if ops
+            .iter()
+            .any(|op| matches!(op, ReadOperation::Batch { .. }))
+        {
+            return DemarchResponse::error("Batches cannot be nested.");
+        }
+
+        let handles: Vec<_> = ops
+            .into_iter()
+            .map(|op| {
+                let adapter = self.clone();
+                tokio::spawn(async move { adapter.read_one(op).await })
+            })
+            .collect();
+        let mut responses = Vec::with_capacity(handles.len());
+        for handle in handles {
+            responses.push(handle.await.unwrap_or_else(|err| {
+                DemarchResponse::error(format!("Batch operation failed: {err}"))
+            }));
         }
+        DemarchResponse::ok(serde_json::to_string(&responses).unwrap_or_default())
+    }
+
+    /// Apply a write on behalf of `source_group` (a group folder; `None` when
+    /// the caller did not say) and record it in the audit log.
+    pub async fn execute_write(
+        &self,
+        operation: WriteOperation,
+        source_group: Option<&str>,
+        is_main: bool,
+    ) -> DemarchResponse {
+        let response = if !self.config.enabled {
+            DemarchResponse::error("Demarch integration is disabled.")
+        } else if self.config.require_main_group_for_writes && !is_main {
+            DemarchResponse::error("Write operation requires main group privileges.")
+        } else {
+            let response = self.backend.write(&operation).await;
+            if response.status == DemarchStatus::Ok {
+                self.cache.lock().unwrap().clear();
+            }
+            response
+        };
+        self.audit_write(&operation, source_group, is_main, &response)
+            .await;
+        response
+    }
+
+    /// A failure to record is logged; it never fails the write.
+    async fn audit_write(
+        &self,
+        operation: &WriteOperation,
+        source_group: Option<&str>,
+        is_main: bool,
+        response: &DemarchResponse,
+    ) {
+        let Some(storage) = &self.audit else {
+            return;
+        };
+        let entry = DemarchAuditEntry {
+            created_at: String::new(),
+            operation: operation.name().to_string(),
+            params: serde_json::to_value(operation).unwrap_or_default(),
+            source_group: source_group.map(str::to_string),
+            is_main,
+            status: match response.status {
+                DemarchStatus::Ok => "ok",
+                DemarchStatus::Error => "error",
+            }
+            .to_string(),
+            result: response.result.clone(),
+        };
+        if let Err(err) = storage.record_demarch_write(&entry).await {
+            warn!(operation = %entry.operation, error = %err, "failed to record Demarch write in audit log");
+        }
+    }
 
-        if self.config.require_main_group_for_writes && !is_main {
-            return DemarchResponse::error("Write operation requires main group privileges.");
+    /// Stream kernel events as they happen, resuming after `since`. For the
+    /// CLI backend this is a long-lived `ic events follow`: the channel closes
+    /// when the process exits, dropping the receiver kills it, and it takes no
+    /// concurrency permit and ignores `timeout_ms`.
+    pub async fn follow_events(
+        &self,
+        since: Option<String>,
+    ) -> anyhow::Result<mpsc::Receiver<String>> {
+        if !self.config.enabled {
+            bail!("Demarch integration is disabled.");
         }
+        self.backend.follow_events(since).await
+    }
 
-        let plan = Self::plan_write(&operation);
-        self.execute_plan(plan, true)
+    /// [`Self::execute_read`] for synchronous callers such as the IPC watcher's
+    /// blocking shard passes.
+    pub fn execute_read_blocking(&self, operation: ReadOperation) -> DemarchResponse {
+        block_on(self.execute_read(operation))
+    }
+
+    /// [`Self::execute_write`] for synchronous callers.
+    pub fn execute_write_blocking(
+        &self,
+        operation: WriteOperation,
+        source_group: Option<&str>,
+        is_main: bool,
+    ) -> DemarchResponse {
+        block_on(self.execute_write(operation, source_group, is_main))
+    }
+}
+
+/// Runs the allowlisted `ic` and `bd` commands in the project root.
+pub struct CliBackend {
+    config: DemarchConfig,
+    project_root: PathBuf,
+    /// Bounds how many CLI calls run at once.
+    permits: Semaphore,
+}
+
+impl CliBackend {
+    pub fn new(config: DemarchConfig, project_root: impl AsRef<Path>) -> Self {
+        let permits = Semaphore::new(config.max_concurrent.max(1));
+        Self {
+            config,
+            project_root: project_root.as_ref().to_path_buf(),
+            permits,
+        }
     }
 
     pub fn plan_read(operation: &ReadOperation) -> Option<DemarchCommandPlan> {
@@ -230,7 +540,8 @@ impl DemarchAdapter {
                     args,
                 })
             }
-            ReadOperation::ReviewSummary => None,
+            // Handled without a CLI call, by the backend and the adapter.
+            ReadOperation::ReviewSummary | ReadOperation::Batch { .. } => None,
             ReadOperation::NextWork => Some(DemarchCommandPlan {
                 bin: "bd",
                 signature: "bd ready --json",
@@ -254,6 +565,47 @@ impl DemarchAdapter {
                     args,
                 })
             }
+            ReadOperation::ListGates { run_id, status } => {
+                let mut args = vec!["gate".to_string(), "list".to_string(), "--json".to_string()];
+                if let Some(run_id) = run_id {
+                    args.push(format!("--run={run_id}"));
+                }
+                args.push(format!("--status={}", status.as_deref().unwrap_or("open")));
+
+                Some(DemarchCommandPlan {
+                    bin: "ic",
+                    signature: "ic gate list --json",
+                    args,
+                })
+            }
+            ReadOperation::IssueComments { id } => Some(DemarchCommandPlan {
+                bin: "bd",
+                signature: "bd comments --json",
+                args: vec!["comments".to_string(), id.clone(), "--json".to_string()],
+            }),
+            ReadOperation::IssueHistory { id } => Some(DemarchCommandPlan {
+                bin: "bd",
+                signature: "bd history --json",
+                args: vec!["history".to_string(), id.clone(), "--json".to_string()],
+            }),
+            ReadOperation::RunDiff { run_id } => {
+                let mut args = vec![
+                    "run".to_string(),
+                    "artifact".to_string(),
+                    "diff".to_string(),
+                ];
+                if let Some(run_id) = run_id {
+                    args.push(run_id.clone());
+                }
+                args.push("--stat".to_string());
+                args.push("--json".to_string());
+
+                Some(DemarchCommandPlan {
+                    bin: "ic",
+                    signature: "ic run artifact diff --json",
+                    args,
+                })
+            }
         }
     }
 
@@ -390,7 +742,7 @@ impl DemarchAdapter {
         }
     }
 
-    fn execute_plan(&self, plan: DemarchCommandPlan, write: bool) -> DemarchResponse {
+    async fn execute_plan(&self, plan: DemarchCommandPlan, write: bool) -> DemarchResponse {
         if !self.is_signature_allowed(plan.signature, write) {
             return DemarchResponse::error(format!(
                 "Operation blocked by demarch {} allowlist: {}",
@@ -399,11 +751,11 @@ impl DemarchAdapter {
             ));
         }
 
-        if !is_cli_available(plan.bin) {
+        if !cli_available(plan.bin).await {
             return DemarchResponse::error(STANDALONE_MSG);
         }
 
-        match self.exec_cli(plan.bin, &plan.args) {
+        match self.exec_cli(plan.bin, &plan.args).await {
             Ok(result) => DemarchResponse::ok(result),
             Err(err) => DemarchResponse::error(err.to_string()),
         }
@@ -419,12 +771,32 @@ impl DemarchAdapter {
         allowlist.iter().any(|allowed| allowed == signature)
     }
 
-    fn exec_cli(&self, bin: &str, args: &[String]) -> anyhow::Result<String> {
-        let output = Command::new(bin)
+    async fn exec_cli(&self, bin: &str, args: &[String]) -> anyhow::Result<String> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .context("demarch concurrency limiter closed")?;
+
+        // kill_on_drop reaps the child when the timeout drops the output future.
+        let child = tokio::process::Command::new(bin)
             .args(args)
             .current_dir(&self.project_root)
-            .output()
-            .with_context(|| format!("failed to execute {} with args {:?}", bin, args))?;
+            .kill_on_drop(true)
+            .output();
+        let limit = Duration::from_millis(self.config.timeout_ms);
+        let output = match tokio::time::timeout(limit, child).await {
+            Ok(output) => {
+                output.with_context(|| format!("failed to execute {} with args {:?}", bin, args))?
+            }
+            Err(_) => {
+                return Err(anyhow!(
+                    "`{}` timed out after {}ms and was killed",
+                    bin,
+                    self.config.timeout_ms
+                ));
+            }
+        };
 
         let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
         if output.status.success() {
@@ -445,6 +817,39 @@ impl DemarchAdapter {
         )))
     }
 
+    fn stream_lines(&self, bin: &str, args: &[String]) -> anyhow::Result<mpsc::Receiver<String>> {
+        let mut child = tokio::process::Command::new(bin)
+            .args(args)
+            .current_dir(&self.project_root)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("failed to execute {} with args {:?}", bin, args))?;
+        let stdout = child.stdout.take().context("child has no stdout")?;
+
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            loop {
+                tokio::select! {
+                    line = lines.next_line() => match line {
+                        Ok(Some(line)) if line.trim().is_empty() => {}
+                        Ok(Some(line)) => {
+                            if tx.send(line).await.is_err() {
+                                break;
+                            }
+                        }
+                        _ => break,
+                    },
+                    _ = tx.closed() => break,
+                }
+            }
+            let _ = child.kill().await;
+        });
+        Ok(rx)
+    }
+
     fn handle_review_summary(&self) -> DemarchResponse {
         let search_dirs = [
             self.project_root.join("docs/research/flux-drive"),
@@ -489,6 +894,58 @@ impl DemarchAdapter {
     }
 }
 
+impl DemarchBackend for CliBackend {
+    fn kind(&self) -> &'static str {
+        "cli"
+    }
+
+    fn kernel_available(&self) -> bool {
+        is_cli_available("ic")
+    }
+
+    fn read<'a>(&'a self, operation: &'a ReadOperation) -> DemarchFuture<'a, DemarchResponse> {
+        Box::pin(async move {
+            match operation {
+                ReadOperation::ReviewSummary => self.handle_review_summary(),
+                op => match Self::plan_read(op) {
+                    Some(plan) => self.execute_plan(plan, false).await,
+                    None => DemarchResponse::error("Read operation is not implemented."),
+                },
+            }
+        })
+    }
+
+    fn write<'a>(&'a self, operation: &'a WriteOperation) -> DemarchFuture<'a, DemarchResponse> {
+        Box::pin(self.execute_plan(Self::plan_write(operation), true))
+    }
+
+    fn follow_events(
+        &self,
+        since: Option<String>,
+    ) -> DemarchFuture<'_, anyhow::Result<mpsc::Receiver<String>>> {
+        Box::pin(async move {
+            const SIGNATURE: &str = "ic events follow --consumer=intercom --json";
+            if !self.is_signature_allowed(SIGNATURE, false) {
+                bail!("Operation blocked by demarch read allowlist: {SIGNATURE}");
+            }
+            if !cli_available("ic").await {
+                bail!(STANDALONE_MSG);
+            }
+
+            let mut args = vec![
+                "events".to_string(),
+                "follow".to_string(),
+                "--consumer=intercom".to_string(),
+                "--json".to_string(),
+            ];
+            if let Some(since) = since {
+                args.push(format!("--since={since}"));
+            }
+            self.stream_lines("ic", &args)
+        })
+    }
+}
+
 fn is_cli_available(bin: &str) -> bool {
     Command::new("which")
         .arg(bin)
@@ -497,34 +954,196 @@ fn is_cli_available(bin: &str) -> bool {
         .unwrap_or(false)
 }
 
+async fn cli_available(bin: &str) -> bool {
+    tokio::process::Command::new("which")
+        .arg(bin)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Drive a demarch future to completion from synchronous code. Inside a
+/// runtime (e.g. a `spawn_blocking` thread) the ambient handle is reused so
+/// children stay on the main reactor; otherwise a throwaway runtime is built.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => handle.block_on(future),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build demarch runtime")
+            .block_on(future),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::persistence::DemarchAuditFilter;
 
     fn adapter() -> DemarchAdapter {
         DemarchAdapter::new(DemarchConfig::default(), ".")
     }
 
-    #[test]
-    fn write_requires_main_group_by_default() {
-        let response = adapter().execute_write(
-            WriteOperation::CreateIssue {
-                title: "x".to_string(),
-                description: None,
-                priority: None,
-                issue_type: None,
-                labels: None,
-            },
-            false,
-        );
+    #[tokio::test]
+    async fn write_requires_main_group_by_default() {
+        let response = adapter()
+            .execute_write(
+                WriteOperation::CreateIssue {
+                    title: "x".to_string(),
+                    description: None,
+                    priority: None,
+                    issue_type: None,
+                    labels: None,
+                },
+                Some("team"),
+                false,
+            )
+            .await;
 
         assert_eq!(response.status, DemarchStatus::Error);
         assert!(response.result.contains("main group"));
     }
 
+    #[tokio::test]
+    async fn refused_and_applied_writes_are_audited() {
+        let store = Arc::new(crate::sqlite::SqliteStore::new(":memory:"));
+        let config = DemarchConfig {
+            backend: DemarchBackendKind::Mock,
+            ..Default::default()
+        };
+        let demarch = DemarchAdapter::new(config, ".").with_audit(store.clone());
+        let close = || WriteOperation::CloseIssue {
+            id: "mock-1".to_string(),
+            reason: None,
+        };
+
+        demarch.execute_write(close(), Some("team"), false).await;
+        demarch.execute_write(close(), Some("main"), true).await;
+
+        let all = DemarchAuditFilter {
+            limit: 10,
+            ..Default::default()
+        };
+        let entries = store.list_demarch_audit(&all).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].source_group.as_deref(), Some("main"));
+        assert_eq!(entries[0].status, "ok");
+        assert_eq!(entries[0].params["id"], "mock-1");
+        assert_eq!(entries[1].operation, "close_issue");
+        assert_eq!(entries[1].status, "error");
+        assert!(!entries[1].is_main);
+
+        let refused = DemarchAuditFilter {
+            status: Some("error".to_string()),
+            ..all
+        };
+        assert_eq!(store.list_demarch_audit(&refused).await.unwrap().len(), 1);
+    }
+
+    async fn summary(adapter: &DemarchAdapter) -> String {
+        adapter
+            .execute_read(ReadOperation::ReviewSummary)
+            .await
+            .result
+    }
+
+    #[tokio::test]
+    async fn cached_reads_are_reused_within_their_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let research = dir.path().join("docs/research");
+        fs::create_dir_all(&research).unwrap();
+        let verdict = research.join("a-verdict.json");
+        fs::write(&verdict, r#"{"v":1}"#).unwrap();
+
+        let cached = DemarchAdapter::new(DemarchConfig::default(), dir.path());
+        let uncached = DemarchAdapter::new(
+            DemarchConfig {
+                cache_ttl_ms: Default::default(),
+                ..Default::default()
+            },
+            dir.path(),
+        );
+        assert_eq!(summary(&cached).await, r#"[{"v":1}]"#);
+        summary(&uncached).await;
+
+        fs::write(&verdict, r#"{"v":2}"#).unwrap();
+        assert_eq!(summary(&cached).await, r#"[{"v":1}]"#);
+        assert_eq!(summary(&cached.clone()).await, r#"[{"v":1}]"#);
+        assert_eq!(summary(&uncached).await, r#"[{"v":2}]"#);
+
+        cached.cache.lock().unwrap().clear();
+        assert_eq!(summary(&cached).await, r#"[{"v":2}]"#);
+    }
+
+    #[tokio::test]
+    async fn streamed_lines_arrive_until_the_process_exits() {
+        let script = "echo '{\"id\":\"1\"}'; echo; echo '{\"id\":\"2\"}'".to_string();
+        let backend = CliBackend::new(DemarchConfig::default(), ".");
+        let mut lines = backend
+            .stream_lines("sh", &["-c".to_string(), script])
+            .unwrap();
+
+        assert_eq!(lines.recv().await.as_deref(), Some(r#"{"id":"1"}"#));
+        assert_eq!(lines.recv().await.as_deref(), Some(r#"{"id":"2"}"#));
+        assert_eq!(lines.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn stalled_cli_is_killed_at_the_timeout() {
+        let config = DemarchConfig {
+            timeout_ms: 100,
+            ..Default::default()
+        };
+        let backend = CliBackend::new(config, ".");
+
+        let started = std::time::Instant::now();
+        let err = backend
+            .exec_cli("sleep", &["5".to_string()])
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("timed out after 100ms"), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn batches_reject_nesting_and_oversize() {
+        let nested = ReadOperation::Batch {
+            ops: vec![ReadOperation::Batch {
+                ops: vec![ReadOperation::SprintPhase],
+            }],
+        };
+        let response = adapter().execute_read(nested).await;
+        assert!(
+            response.result.contains("cannot be nested"),
+            "{}",
+            response.result
+        );
+
+        let oversize = ReadOperation::Batch {
+            ops: vec![ReadOperation::SprintPhase; MAX_BATCH_OPS + 1],
+        };
+        assert_eq!(
+            adapter().execute_read(oversize).await.status,
+            DemarchStatus::Error
+        );
+    }
+
+    #[test]
+    fn only_json_containers_are_parsed_into_data() {
+        let parsed = DemarchResponse::ok("{\"phase\":\"execute\"}\n");
+        assert_eq!(parsed.data, Some(serde_json::json!({ "phase": "execute" })));
+        assert_eq!(DemarchResponse::ok("42").data, None);
+        assert_eq!(DemarchResponse::ok("Created iv-1").data, None);
+        assert_eq!(DemarchResponse::error("[]").data, None);
+    }
+
     #[test]
     fn create_issue_plan_contains_expected_flags() {
-        let plan = DemarchAdapter::plan_write(&WriteOperation::CreateIssue {
+        let plan = CliBackend::plan_write(&WriteOperation::CreateIssue {
             title: "test title".to_string(),
             description: Some("desc".to_string()),
             priority: Some("1".to_string()),
@@ -541,9 +1160,28 @@ mod tests {
         assert!(plan.args.contains(&"a,b".to_string()));
     }
 
+    #[test]
+    fn list_gates_plan_defaults_to_open_gates() {
+        let plan = CliBackend::plan_read(&ReadOperation::ListGates {
+            run_id: Some("run-7".to_string()),
+            status: None,
+        })
+        .expect("plan exists");
+
+        assert_eq!(plan.signature, "ic gate list --json");
+        assert!(plan.args.contains(&"--run=run-7".to_string()));
+        assert!(plan.args.contains(&"--status=open".to_string()));
+        assert!(
+            DemarchConfig::default()
+                .read_allowlist
+                .iter()
+                .any(|s| s == plan.signature)
+        );
+    }
+
     #[test]
     fn run_events_plan_uses_consumer_and_default_limit() {
-        let plan = DemarchAdapter::plan_read(&ReadOperation::RunEvents {
+        let plan = CliBackend::plan_read(&ReadOperation::RunEvents {
             limit: None,
             since: None,
         })
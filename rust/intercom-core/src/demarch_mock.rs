@@ -0,0 +1,449 @@
+//! In-memory Demarch backend (`demarch.backend = "mock"`).
+//!
+//! Serves a small canned project (two issues, one run in `execute`, one open
+//! review gate) and applies writes to it, so handler tests can exercise
+//! success paths and standalone installs can demo the Demarch tools without
+//! `ic` or `bd` on PATH. State lives for the life of the process.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+
+use crate::demarch::{
+    DemarchBackend, DemarchFuture, DemarchResponse, ReadOperation, WriteOperation,
+};
+
+#[derive(Debug, Clone, Serialize)]
+struct MockIssue {
+    id: String,
+    title: String,
+    description: Option<String>,
+    status: String,
+    priority: String,
+    issue_type: String,
+    labels: Vec<String>,
+    #[serde(skip)]
+    comments: Vec<Value>,
+    #[serde(skip)]
+    history: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MockRun {
+    id: String,
+    title: String,
+    phase: String,
+    status: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MockGate {
+    id: String,
+    run_id: String,
+    status: String,
+    reason: Option<String>,
+}
+
+#[derive(Debug)]
+struct MockState {
+    issues: Vec<MockIssue>,
+    /// The last run is the current one.
+    runs: Vec<MockRun>,
+    gates: Vec<MockGate>,
+    events: Vec<Value>,
+    next_id: u32,
+}
+
+impl MockState {
+    fn seeded() -> Self {
+        let issue = |id: &str, title: &str, status: &str, priority: &str| MockIssue {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: None,
+            status: status.to_string(),
+            priority: priority.to_string(),
+            issue_type: "task".to_string(),
+            labels: Vec::new(),
+            comments: Vec::new(),
+            history: vec![json!({ "field": "status", "to": "open" })],
+        };
+        let mut first = issue("mock-1", "Write the release notes", "open", "1");
+        first
+            .comments
+            .push(json!({ "author": "mock", "body": "Draft is in docs/" }));
+        Self {
+            issues: vec![
+                first,
+                issue("mock-2", "Fix flaky smoke test", "in_progress", "2"),
+            ],
+            runs: vec![MockRun {
+                id: "run-mock".to_string(),
+                title: "Mock sprint".to_string(),
+                phase: "execute".to_string(),
+                status: "active".to_string(),
+            }],
+            gates: vec![MockGate {
+                id: "gate-review".to_string(),
+                run_id: "run-mock".to_string(),
+                status: "open".to_string(),
+                reason: None,
+            }],
+            events: vec![
+                json!({ "id": "evt-1", "kind": "phase.changed", "run_id": "run-mock", "phase": "execute" }),
+                json!({ "id": "evt-2", "kind": "gate.pending", "run_id": "run-mock", "gate_id": "gate-review" }),
+            ],
+            next_id: 3,
+        }
+    }
+
+    fn current_run(&self, run_id: Option<&str>) -> Option<&MockRun> {
+        match run_id {
+            Some(id) => self.runs.iter().find(|run| run.id == id),
+            None => self.runs.last(),
+        }
+    }
+
+    fn issue_mut(&mut self, id: &str) -> Result<&mut MockIssue, DemarchResponse> {
+        self.issues
+            .iter_mut()
+            .find(|issue| issue.id == id)
+            .ok_or_else(|| DemarchResponse::error(format!("issue {id} not found")))
+    }
+
+    fn read(&self, operation: &ReadOperation) -> DemarchResponse {
+        match operation {
+            ReadOperation::RunStatus { run_id } => match self.current_run(run_id.as_deref()) {
+                Some(run) => ok_json(run),
+                None => DemarchResponse::error("run not found"),
+            },
+            ReadOperation::SprintPhase => match self.current_run(None) {
+                Some(run) => ok_json(&json!({ "run_id": run.id, "phase": run.phase })),
+                None => DemarchResponse::error("no active run"),
+            },
+            ReadOperation::SearchBeads { id: Some(id), .. } => {
+                match self.issues.iter().find(|issue| &issue.id == id) {
+                    Some(issue) => ok_json(issue),
+                    None => DemarchResponse::error(format!("issue {id} not found")),
+                }
+            }
+            ReadOperation::SearchBeads {
+                id: None,
+                query,
+                status,
+            } => {
+                let query = query.as_deref().map(str::to_lowercase);
+                let found: Vec<_> = self
+                    .issues
+                    .iter()
+                    .filter(|issue| status.as_ref().is_none_or(|s| &issue.status == s))
+                    .filter(|issue| {
+                        query
+                            .as_ref()
+                            .is_none_or(|q| issue.title.to_lowercase().contains(q))
+                    })
+                    .collect();
+                ok_json(&found)
+            }
+            ReadOperation::SpecLookup { artifact_id } => {
+                let prd = json!({ "id": "prd", "kind": "prd", "body": "Mock requirements." });
+                match artifact_id.as_deref() {
+                    None => ok_json(&[prd]),
+                    Some("prd") => ok_json(&prd),
+                    Some(id) => DemarchResponse::error(format!("artifact {id} not found")),
+                }
+            }
+            ReadOperation::ReviewSummary => {
+                ok_json(&[json!({ "verdict": "pass", "findings": [] })])
+            }
+            ReadOperation::NextWork => {
+                let mut ready: Vec<_> = self
+                    .issues
+                    .iter()
+                    .filter(|issue| issue.status == "open")
+                    .collect();
+                ready.sort_by(|a, b| a.priority.cmp(&b.priority));
+                ok_json(&ready)
+            }
+            ReadOperation::RunEvents { limit, since } => {
+                let limit = limit.unwrap_or(20) as usize;
+                ok_json(
+                    &self
+                        .events_after(since.as_deref())
+                        .into_iter()
+                        .take(limit)
+                        .collect::<Vec<_>>(),
+                )
+            }
+            ReadOperation::ListGates { run_id, status } => {
+                let Some(run) = self.current_run(run_id.as_deref()) else {
+                    return DemarchResponse::error("run not found");
+                };
+                let status = status.as_deref().unwrap_or("open");
+                let gates: Vec<_> = self
+                    .gates
+                    .iter()
+                    .filter(|gate| gate.run_id == run.id && gate.status == status)
+                    .collect();
+                ok_json(&gates)
+            }
+            ReadOperation::IssueComments { id } | ReadOperation::IssueHistory { id } => {
+                match self.issues.iter().find(|issue| &issue.id == id) {
+                    Some(issue) if matches!(operation, ReadOperation::IssueComments { .. }) => {
+                        ok_json(&issue.comments)
+                    }
+                    Some(issue) => ok_json(&issue.history),
+                    None => DemarchResponse::error(format!("issue {id} not found")),
+                }
+            }
+            ReadOperation::RunDiff { run_id } => match self.current_run(run_id.as_deref()) {
+                Some(run) => ok_json(&json!({
+                    "run_id": run.id,
+                    "artifacts": [{ "id": "prd", "added": 12, "removed": 3 }],
+                })),
+                None => DemarchResponse::error("run not found"),
+            },
+            ReadOperation::Batch { .. } => DemarchResponse::error("batches are run by the adapter"),
+        }
+    }
+
+    fn write(&mut self, operation: &WriteOperation) -> DemarchResponse {
+        match operation {
+            WriteOperation::CreateIssue {
+                title,
+                description,
+                priority,
+                issue_type,
+                labels,
+            } => {
+                let issue = MockIssue {
+                    id: format!("mock-{}", self.next_id),
+                    title: title.clone(),
+                    description: description.clone(),
+                    status: "open".to_string(),
+                    priority: priority.clone().unwrap_or_else(|| "2".to_string()),
+                    issue_type: issue_type.clone().unwrap_or_else(|| "task".to_string()),
+                    labels: labels.clone().unwrap_or_default(),
+                    comments: Vec::new(),
+                    history: vec![json!({ "field": "status", "to": "open" })],
+                };
+                self.next_id += 1;
+                let response = ok_json(&issue);
+                self.issues.push(issue);
+                response
+            }
+            WriteOperation::UpdateIssue {
+                id,
+                status,
+                priority,
+                title,
+                description,
+                notes,
+            } => {
+                let issue = match self.issue_mut(id) {
+                    Ok(issue) => issue,
+                    Err(response) => return response,
+                };
+                for (field, value, slot) in [
+                    ("status", status, &mut issue.status),
+                    ("priority", priority, &mut issue.priority),
+                    ("title", title, &mut issue.title),
+                ] {
+                    if let Some(value) = value {
+                        issue.history.push(json!({ "field": field, "to": value }));
+                        *slot = value.clone();
+                    }
+                }
+                if description.is_some() {
+                    issue.description = description.clone();
+                }
+                if let Some(notes) = notes {
+                    issue
+                        .comments
+                        .push(json!({ "author": "intercom", "body": notes }));
+                }
+                ok_json(issue)
+            }
+            WriteOperation::CloseIssue { id, reason } => {
+                let issue = match self.issue_mut(id) {
+                    Ok(issue) => issue,
+                    Err(response) => return response,
+                };
+                issue.status = "closed".to_string();
+                issue
+                    .history
+                    .push(json!({ "field": "status", "to": "closed", "reason": reason }));
+                ok_json(issue)
+            }
+            WriteOperation::StartRun { title, .. } => {
+                let run = MockRun {
+                    id: format!("run-mock-{}", self.next_id),
+                    title: title.clone().unwrap_or_else(|| "Mock sprint".to_string()),
+                    phase: "brainstorm".to_string(),
+                    status: "active".to_string(),
+                };
+                self.next_id += 1;
+                let response = ok_json(&run);
+                self.runs.push(run);
+                response
+            }
+            WriteOperation::ApproveGate { gate_id, reason } => {
+                let current = self.current_run(None).map(|run| run.id.clone());
+                let gate = self.gates.iter_mut().find(|gate| {
+                    gate.status == "open"
+                        && match gate_id {
+                            Some(id) => &gate.id == id,
+                            None => Some(&gate.run_id) == current.as_ref(),
+                        }
+                });
+                match gate {
+                    Some(gate) => {
+                        gate.status = "approved".to_string();
+                        gate.reason = reason.clone();
+                        ok_json(gate)
+                    }
+                    None => DemarchResponse::error("no open gate to approve"),
+                }
+            }
+        }
+    }
+
+    fn events_after(&self, since: Option<&str>) -> Vec<Value> {
+        let start = since
+            .and_then(|since| self.events.iter().position(|event| event["id"] == since))
+            .map_or(0, |at| at + 1);
+        self.events[start..].to_vec()
+    }
+}
+
+fn ok_json(value: &impl Serialize) -> DemarchResponse {
+    DemarchResponse::ok(serde_json::to_string(value).unwrap_or_default())
+}
+
+/// Canned in-memory Demarch project. See the module docs.
+pub struct MockBackend {
+    state: Mutex<MockState>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(MockState::seeded()),
+        }
+    }
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DemarchBackend for MockBackend {
+    fn kind(&self) -> &'static str {
+        "mock"
+    }
+
+    fn kernel_available(&self) -> bool {
+        true
+    }
+
+    fn read<'a>(&'a self, operation: &'a ReadOperation) -> DemarchFuture<'a, DemarchResponse> {
+        let response = self.state.lock().unwrap().read(operation);
+        Box::pin(async move { response })
+    }
+
+    fn write<'a>(&'a self, operation: &'a WriteOperation) -> DemarchFuture<'a, DemarchResponse> {
+        let response = self.state.lock().unwrap().write(operation);
+        Box::pin(async move { response })
+    }
+
+    /// Replays the canned events after `since`, then closes the channel.
+    fn follow_events(
+        &self,
+        since: Option<String>,
+    ) -> DemarchFuture<'_, anyhow::Result<mpsc::Receiver<String>>> {
+        let events = self.state.lock().unwrap().events_after(since.as_deref());
+        Box::pin(async move {
+            let (tx, rx) = mpsc::channel(events.len().max(1));
+            for event in events {
+                let _ = tx.send(event.to_string()).await;
+            }
+            Ok(rx)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DemarchBackendKind, DemarchConfig};
+    use crate::demarch::{DemarchAdapter, DemarchStatus};
+
+    fn adapter() -> DemarchAdapter {
+        let config = DemarchConfig {
+            backend: DemarchBackendKind::Mock,
+            ..Default::default()
+        };
+        DemarchAdapter::new(config, ".")
+    }
+
+    #[tokio::test]
+    async fn writes_are_visible_to_later_reads() {
+        let demarch = adapter();
+        let created = demarch
+            .execute_write(
+                WriteOperation::CreateIssue {
+                    title: "Ship it".to_string(),
+                    description: None,
+                    priority: Some("0".to_string()),
+                    issue_type: None,
+                    labels: None,
+                },
+                None,
+                true,
+            )
+            .await;
+        assert_eq!(created.status, DemarchStatus::Ok);
+        assert!(created.result.contains("mock-3"));
+
+        let next: Vec<Value> =
+            serde_json::from_str(&demarch.execute_read(ReadOperation::NextWork).await.result)
+                .unwrap();
+        assert_eq!(next[0]["id"], "mock-3");
+
+        let gates = |demarch: DemarchAdapter| async move {
+            let read = ReadOperation::ListGates {
+                run_id: None,
+                status: None,
+            };
+            demarch.execute_read(read).await.result
+        };
+        assert!(gates(demarch.clone()).await.contains("gate-review"));
+        let approved = demarch
+            .execute_write(
+                WriteOperation::ApproveGate {
+                    gate_id: None,
+                    reason: None,
+                },
+                None,
+                true,
+            )
+            .await;
+        assert_eq!(approved.status, DemarchStatus::Ok);
+        assert_eq!(gates(demarch).await, "[]");
+    }
+
+    #[tokio::test]
+    async fn followed_events_resume_after_the_cursor() {
+        let backend = MockBackend::new();
+        let mut events = backend
+            .follow_events(Some("evt-1".to_string()))
+            .await
+            .unwrap();
+
+        assert!(events.recv().await.unwrap().contains("evt-2"));
+        assert_eq!(events.recv().await, None);
+    }
+}
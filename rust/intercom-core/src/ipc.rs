@@ -8,17 +8,52 @@
 //! - **messages**: outbound messages from containers (container → host → channel)
 //! - **tasks**: task management commands (schedule, pause, resume, cancel, register_group)
 //! - **queries**: Demarch kernel queries with UUID request/response pattern
+//!
+//! Payloads may carry the protocol `version` they were written for; files
+//! without one are version 1. The host advertises what it supports in
+//! `/workspace/ipc/capabilities.json` so agents can check before sending.
 
 use serde::{Deserialize, Serialize};
 
+/// IPC protocol version spoken by this host.
+pub const IPC_PROTOCOL_VERSION: u32 = 2;
+
+/// Name of the capabilities file in each group's IPC directory.
+pub const CAPABILITIES_FILE: &str = "capabilities.json";
+
+/// What the host supports, written to [`CAPABILITIES_FILE`] for each group.
+/// Fields are only ever added within a protocol version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpcCapabilities {
+    pub version: u32,
+    /// Oldest payload version still accepted.
+    pub min_version: u32,
+    /// Accepted `type`s in `messages/`.
+    pub message_types: Vec<String>,
+    /// Accepted `type`s in `tasks/`.
+    pub task_types: Vec<String>,
+    /// Accepted `type`s in `queries/`.
+    pub query_types: Vec<String>,
+    /// Whether task commands are acked in `responses/`.
+    pub task_acks: bool,
+    /// Query results over this many bytes are split into parts; 0 never.
+    pub response_chunk_bytes: usize,
+    /// Query results are cut at this many bytes; 0 for no limit.
+    pub max_response_bytes: usize,
+    /// Whether a socket transport is offered; files are always available.
+    pub socket: bool,
+}
+
 /// Outbound message from a container agent to a messaging channel.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpcMessage {
-    /// Must be "message".
+    /// "message" for one chat, "broadcast" for the groups in `target`, or
+    /// "mail" for the inbox of the group in `to`.
     #[serde(rename = "type")]
     pub msg_type: String,
-    /// Target chat JID (e.g., "tg:1108701034").
-    #[serde(rename = "chatJid")]
+    /// Target chat JID (e.g., "tg:1108701034"); unused by broadcasts.
+    #[serde(rename = "chatJid", default)]
     pub chat_jid: String,
     /// Message text content.
     pub text: String,
@@ -28,6 +63,30 @@ pub struct IpcMessage {
     #[serde(rename = "groupFolder")]
     pub group_folder: Option<String>,
     pub timestamp: Option<String>,
+    /// Replies offered as tap-to-select buttons under the message. A tap
+    /// posts the option's text into the chat as the user.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    /// Groups a broadcast goes to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<BroadcastTarget>,
+    /// Group folder whose inbox a mail goes to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Protocol version the payload was written for; absent means 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+}
+
+/// Groups a broadcast goes to: `"all"` or a list of group folders.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BroadcastTarget {
+    Folders(Vec<String>),
+    /// Only `"all"` is meaningful.
+    Keyword(String),
 }
 
 /// Task management command from a container agent.
@@ -90,33 +149,71 @@ pub struct IpcQuery {
     /// Unique request ID — used as the response filename.
     pub uuid: String,
     /// Query type: run_status, sprint_phase, search_beads, spec_lookup,
-    /// review_summary, next_work, run_events.
+    /// review_summary, next_work, run_events, list_gates, issue_comments,
+    /// issue_history, run_diff, or one of the write queries.
     #[serde(rename = "type")]
     pub query_type: String,
     /// Type-specific parameters.
     #[serde(default)]
     pub params: serde_json::Value,
+    /// Protocol version the payload was written for; absent means 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
 }
 
 /// Response to a Demarch kernel query.
+///
+/// A large result is split across files: `{uuid}.json` holds part 1 and
+/// names the file holding the next part in `next`, until the last part.
+/// A result over the size cap is cut short, with `truncated` set and the
+/// full size in `totalBytes`. `data` is only sent with a result that fits
+/// in one part.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpcQueryResponse {
     pub status: String,
     pub result: String,
+    /// The result parsed as JSON, when the kernel answered with JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    /// This part's 1-based index, when the result is split.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub part: Option<u32>,
+    /// How many parts the result is split into.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parts: Option<u32>,
+    /// File name, in the same directory, of the next part.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Size of the whole result in bytes, when it was truncated.
+    #[serde(
+        default,
+        rename = "totalBytes",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub total_bytes: Option<usize>,
 }
 
 impl IpcQueryResponse {
     pub fn ok(result: impl Into<String>) -> Self {
-        Self {
-            status: "ok".to_string(),
-            result: result.into(),
-        }
+        Self::with_status("ok", result)
     }
 
     pub fn error(result: impl Into<String>) -> Self {
+        Self::with_status("error", result)
+    }
+
+    fn with_status(status: &str, result: impl Into<String>) -> Self {
         Self {
-            status: "error".to_string(),
+            status: status.to_string(),
             result: result.into(),
+            data: None,
+            part: None,
+            parts: None,
+            next: None,
+            truncated: false,
+            total_bytes: None,
         }
     }
 }
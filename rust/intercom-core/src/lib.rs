@@ -1,25 +1,50 @@
+pub mod circuit;
 pub mod config;
 pub mod container;
+pub mod correlation;
 pub mod demarch;
+pub mod demarch_mock;
 pub mod ipc;
 pub mod persistence;
+pub mod protocol;
+pub mod provision;
 pub mod runtime;
+pub mod sqlite;
+pub mod storage;
 
+pub use circuit::{CircuitSnapshot, CircuitState, ReconnectPolicy};
 pub use config::{
-    EventsConfig, IntercomConfig, OrchestratorConfig, SchedulerConfig, load_config,
+    ChannelProfile, ChannelsConfig, CommandPermission, CommandsConfig, ContainerEngine,
+    ContainerExecutorKind, ContainerLogConfig, ContainerRuntimeConfig, DemarchBackendKind,
+    DiskQuotaConfig, EgressProxyConfig, EmailConfig, EventsConfig, EventsMode, ImageConfig,
+    IngressGroupSource, IntercomConfig, IpcConfig, IpcTaskHandling, KubernetesConfig,
+    MarkdownDialect, MatrixConfig, NamedQuery, OrchestratorConfig, PromptCommand, QueryConfig,
+    RateLimitConfig, RuntimeProfile, SchedulerConfig, SmtpSecurity, SnapshotConfig, StorageBackend,
+    TelegramConfig, TelegramIngest, WarmPoolConfig, load_config,
 };
 pub use container::{
-    ContainerInput, ContainerOutput, ContainerStatus, StreamEvent, VolumeMount,
-    OUTPUT_END_MARKER, OUTPUT_START_MARKER, container_image, extract_output_markers,
+    ContainerInput, ContainerOutput, ContainerStatus, OUTPUT_END_MARKER, OUTPUT_START_MARKER,
+    StreamEvent, TokenUsage, VolumeMount, container_image, extract_output_markers,
     runner_container_path, runner_dir_name,
 };
+pub use correlation::new_correlation_id;
 pub use demarch::{
-    DemarchAdapter, DemarchCommandPlan, DemarchResponse, DemarchStatus, ReadOperation,
-    WriteOperation,
+    CliBackend, DemarchAdapter, DemarchBackend, DemarchCommandPlan, DemarchFuture, DemarchResponse,
+    DemarchStatus, ReadOperation, WriteOperation,
+};
+pub use demarch_mock::MockBackend;
+pub use ipc::{
+    BroadcastTarget, CAPABILITIES_FILE, IPC_PROTOCOL_VERSION, IpcCapabilities, IpcGroupContext,
+    IpcMessage, IpcQuery, IpcQueryResponse, IpcTask,
 };
-pub use ipc::{IpcGroupContext, IpcMessage, IpcQuery, IpcQueryResponse, IpcTask};
 pub use persistence::{
-    ChatInfo, ConversationMessage, NewMessage, PgPool, RegisteredGroup, ScheduledTask, TaskRunLog,
-    TaskUpdate,
+    Attachment, ChatInfo, ChatStats, ContainerRun, ConversationMessage, CorrelationTrace,
+    DemarchAuditEntry, DemarchAuditFilter, MessageEdit, MessageFilter, NEW_MESSAGE_CHANNEL,
+    NewMessage, PgPool, QueryResult, RegisteredGroup, ScheduledTask, SenderStats, SortOrder,
+    TaskFilter, TaskRunLog, TaskRunStats, TaskTemplate, TaskUpdate, UsageTotals,
 };
+pub use protocol::{OutputDecoder, RuntimeProtocol};
+pub use provision::{ProvisionOptions, ProvisionReport, provision_database};
 pub use runtime::RuntimeKind;
+pub use sqlite::SqliteStore;
+pub use storage::{SharedStorage, Storage, StorageFuture};
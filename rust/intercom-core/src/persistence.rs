@@ -1,11 +1,15 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, anyhow};
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
-use tokio_postgres::{Client, NoTls};
-use tracing::{error, info};
+use tokio::sync::{RwLock, mpsc};
+use tokio_postgres::types::{ToSql, Type};
+use tokio_postgres::{AsyncMessage, Client, GenericClient, NoTls};
+use tracing::{error, info, warn};
+
+use crate::circuit::{CircuitBreaker, CircuitSnapshot, ReconnectPolicy};
 
 // ---------------------------------------------------------------------------
 // Types — mirror the Node.js interfaces from types.ts and db.ts
@@ -23,6 +27,143 @@ pub struct NewMessage {
     pub is_from_me: bool,
     #[serde(default)]
     pub is_bot_message: bool,
+    /// Set when the content was replaced by a later edit.
+    #[serde(default)]
+    pub edited: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// Assigned at ingress and copied onto the runs and replies it causes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+/// Media or file metadata for a message. The payload itself stays on disk
+/// (usually under the group folder); only its location is stored.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// Filled from the owning message when nested in a `NewMessage`.
+    #[serde(default)]
+    pub message_id: String,
+    #[serde(default)]
+    pub chat_jid: String,
+    /// `photo`, `document`, `voice`, `video`, `audio`, `sticker`, ...
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<i64>,
+}
+
+impl Attachment {
+    /// One-line description for agent prompts, e.g.
+    /// `[photo image/jpeg media/1.jpg: sunset]`.
+    pub fn describe(&self) -> String {
+        let mut out = format!("[{}", self.kind);
+        if let Some(mime) = &self.mime_type {
+            out.push(' ');
+            out.push_str(mime);
+        }
+        if let Some(location) = self.file_path.as_ref().or(self.file_name.as_ref()) {
+            out.push(' ');
+            out.push_str(location);
+        }
+        if let Some(caption) = self.caption.as_deref().filter(|c| !c.is_empty()) {
+            out.push_str(": ");
+            out.push_str(caption);
+        }
+        out.push(']');
+        out
+    }
+}
+
+/// A prior version of an edited message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageEdit {
+    pub message_id: String,
+    pub chat_jid: String,
+    pub previous_content: String,
+    pub edited_at: String,
+}
+
+/// Usage summary for a single chat. Deleted messages are not counted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatStats {
+    pub chat_jid: String,
+    pub total_messages: i64,
+    pub bot_messages: i64,
+    pub human_messages: i64,
+    /// Share of messages sent by the bot, 0.0 for an empty chat.
+    pub bot_ratio: f64,
+    pub first_message_at: Option<String>,
+    pub last_message_at: Option<String>,
+    /// Busiest senders first.
+    pub senders: Vec<SenderStats>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SenderStats {
+    pub sender: String,
+    pub sender_name: String,
+    pub message_count: i64,
+    pub is_bot: bool,
+}
+
+impl ChatStats {
+    /// Assemble stats from the aggregate counts, deriving the human count
+    /// and bot ratio so both backends report them identically.
+    pub fn from_counts(
+        chat_jid: &str,
+        total_messages: i64,
+        bot_messages: i64,
+        first_message_at: Option<String>,
+        last_message_at: Option<String>,
+        senders: Vec<SenderStats>,
+    ) -> Self {
+        let bot_ratio = if total_messages > 0 {
+            bot_messages as f64 / total_messages as f64
+        } else {
+            0.0
+        };
+        Self {
+            chat_jid: chat_jid.to_string(),
+            total_messages,
+            bot_messages,
+            human_messages: total_messages - bot_messages,
+            bot_ratio,
+            first_message_at,
+            last_message_at,
+            senders,
+        }
+    }
+}
+
+/// Rows returned by a named read-only report query.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    /// One JSON object per row, keyed by column name.
+    pub rows: Vec<serde_json::Value>,
+    /// More rows matched than the query's row cap allowed.
+    pub truncated: bool,
+}
+
+impl QueryResult {
+    /// Cap `rows` at `max_rows`; callers fetch one extra row to detect overflow.
+    pub fn capped(columns: Vec<String>, mut rows: Vec<serde_json::Value>, max_rows: usize) -> Self {
+        let truncated = rows.len() > max_rows;
+        rows.truncate(max_rows);
+        Self {
+            columns,
+            rows,
+            truncated,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,6 +199,9 @@ pub struct ScheduledTask {
     #[serde(default = "default_status")]
     pub status: String,
     pub created_at: String,
+    /// Per-task settings such as `jitterMs`, as JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_config: Option<serde_json::Value>,
 }
 
 fn default_context_mode() -> String {
@@ -76,6 +220,232 @@ pub struct TaskRunLog {
     pub status: String,
     pub result: Option<String>,
     pub error: Option<String>,
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+}
+
+/// A named prompt and schedule that tasks can be created from, so the same
+/// task can be rolled out to many groups.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskTemplate {
+    pub name: String,
+    pub prompt: String,
+    pub schedule_type: String,
+    pub schedule_value: String,
+    #[serde(default = "default_context_mode")]
+    pub context_mode: String,
+    /// Copied into each created task's `task_config`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_config: Option<serde_json::Value>,
+    #[serde(default)]
+    pub created_at: String,
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+/// Health summary over all of a task's logged runs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskRunStats {
+    pub task_id: String,
+    pub runs: i64,
+    pub successes: i64,
+    pub failures: i64,
+    /// Share of runs that succeeded, 0.0 for a task that never ran.
+    pub success_rate: f64,
+    pub avg_duration_ms: Option<f64>,
+    pub last_run_at: Option<String>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<String>,
+}
+
+impl TaskRunStats {
+    /// Assemble stats from the aggregate counts, deriving the failure count
+    /// and success rate so both backends report them identically.
+    pub fn from_counts(
+        task_id: &str,
+        runs: i64,
+        successes: i64,
+        avg_duration_ms: Option<f64>,
+        last_run_at: Option<String>,
+        last_error_at: Option<String>,
+        last_error: Option<String>,
+    ) -> Self {
+        let success_rate = if runs > 0 {
+            successes as f64 / runs as f64
+        } else {
+            0.0
+        };
+        Self {
+            task_id: task_id.to_string(),
+            runs,
+            successes,
+            failures: runs - successes,
+            success_rate,
+            avg_duration_ms,
+            last_run_at,
+            last_error,
+            last_error_at,
+        }
+    }
+}
+
+/// One agent container run, whatever triggered it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContainerRun {
+    /// Names the run's transcript; `None` if the container never started.
+    #[serde(default)]
+    pub run_id: Option<String>,
+    pub group_folder: String,
+    pub chat_jid: String,
+    /// `message`, `task` or `replay`.
+    pub trigger: String,
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_ms: i64,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub timed_out: bool,
+    /// `success` or `error`.
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Bytes the container wrote to stdout.
+    #[serde(default)]
+    pub output_bytes: i64,
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+    /// Tokens the run used, summed over its turns.
+    #[serde(default)]
+    pub input_tokens: i64,
+    #[serde(default)]
+    pub output_tokens: i64,
+    #[serde(default)]
+    pub cache_read_tokens: i64,
+    #[serde(default)]
+    pub cache_write_tokens: i64,
+    /// Reported by the runtime or estimated from the model's prices;
+    /// `None` when neither is known.
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+}
+
+/// Runs, tokens and cost summed over a period.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub runs: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cache_read_tokens: i64,
+    pub cache_write_tokens: i64,
+    /// Sum over the runs with a known cost.
+    pub cost_usd: f64,
+    /// Runs without a known cost, left out of `cost_usd`.
+    pub unpriced_runs: i64,
+    /// Runs whose status is `success`.
+    pub succeeded_runs: i64,
+    /// Wall-clock time summed over the runs.
+    pub duration_ms: i64,
+}
+
+/// One Demarch write, whether or not it went through.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DemarchAuditEntry {
+    /// Set by the database when the entry is recorded.
+    #[serde(default)]
+    pub created_at: String,
+    /// The write's `op` tag, e.g. `create_issue`.
+    pub operation: String,
+    /// The whole operation as sent.
+    pub params: serde_json::Value,
+    #[serde(default)]
+    pub source_group: Option<String>,
+    #[serde(default)]
+    pub is_main: bool,
+    /// `ok` or `error`.
+    pub status: String,
+    /// The kernel's answer, or why the write was refused.
+    pub result: String,
+}
+
+/// Which audit entries to list; unset fields match everything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DemarchAuditFilter {
+    pub source_group: Option<String>,
+    pub operation: Option<String>,
+    pub status: Option<String>,
+    /// Inclusive RFC 3339 lower bound on `created_at`.
+    pub since: Option<String>,
+    /// Exclusive RFC 3339 upper bound on `created_at`.
+    pub until: Option<String>,
+    pub limit: i64,
+}
+
+/// Direction of a paged listing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    pub(crate) fn sql(self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+
+    /// Comparison that keeps the rows past a cursor in this order.
+    pub(crate) fn past(self) -> &'static str {
+        match self {
+            Self::Asc => ">",
+            Self::Desc => "<",
+        }
+    }
+}
+
+/// Which user messages to list, oldest first: what `get_messages_since`
+/// returns, over several chats and one page at a time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageFilter {
+    pub chat_jids: Vec<String>,
+    /// Exclusive lower bound on `timestamp`; empty for none.
+    pub since: String,
+    /// Messages starting with `{bot_prefix}:` are the bot's own.
+    pub bot_prefix: String,
+    pub sender: Option<String>,
+    /// `(timestamp, chat_jid, id)` of the last message of the previous page.
+    pub after: Option<(String, String, String)>,
+    pub limit: i64,
+}
+
+/// Which scheduled tasks to list, by creation time; unset fields match
+/// everything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskFilter {
+    pub group_folder: Option<String>,
+    pub chat_jid: Option<String>,
+    pub status: Option<String>,
+    pub order: SortOrder,
+    /// `(created_at, id)` of the last task of the previous page.
+    pub after: Option<(String, String)>,
+    pub limit: i64,
+}
+
+/// Everything recorded under one correlation id: the inbound messages and
+/// stored replies, plus any task runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CorrelationTrace {
+    pub correlation_id: String,
+    pub messages: Vec<NewMessage>,
+    pub task_runs: Vec<TaskRunLog>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -93,6 +463,14 @@ pub struct RegisteredGroup {
     pub runtime: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
+    /// While set and in the future, the group's messages are stored but the
+    /// agent isn't run for them (`/mute`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub muted_until: Option<String>,
+    /// Language for command replies and notices, e.g. `de`; English when
+    /// unset (`/language`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -107,18 +485,36 @@ pub struct TaskUpdate {
     pub next_run: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_config: Option<serde_json::Value>,
 }
 
 // ---------------------------------------------------------------------------
 // Pool — reconnecting single-client wrapper
 // ---------------------------------------------------------------------------
 
+/// NOTIFY channel fired by the `messages` insert trigger. The payload is the
+/// chat JID of the new row.
+pub const NEW_MESSAGE_CHANNEL: &str = "intercom_new_message";
+
+/// Default tolerance between a client-supplied message timestamp and the
+/// server clock before a skew warning is logged.
+pub const DEFAULT_CLOCK_SKEW_WARN_MS: i64 = 5_000;
+
+/// Upper bound for one connect attempt or health probe.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// A simple Postgres connection pool that holds a single client behind a
-/// RwLock. Reconnects automatically on connection loss.
+/// RwLock. Reconnects on connection loss, one attempt at a time, behind a
+/// circuit breaker so callers fail fast while the database is down.
 #[derive(Clone)]
 pub struct PgPool {
     dsn: String,
     client: Arc<RwLock<Option<Client>>>,
+    clock_skew_warn_ms: i64,
+    breaker: Arc<std::sync::Mutex<CircuitBreaker>>,
+    /// Serializes reconnects so concurrent callers share one handshake.
+    reconnect_lock: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl PgPool {
@@ -126,6 +522,51 @@ impl PgPool {
         Self {
             dsn,
             client: Arc::new(RwLock::new(None)),
+            clock_skew_warn_ms: DEFAULT_CLOCK_SKEW_WARN_MS,
+            breaker: Arc::new(std::sync::Mutex::new(CircuitBreaker::new(
+                ReconnectPolicy::default(),
+            ))),
+            reconnect_lock: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Override the skew threshold used when storing client-stamped messages.
+    pub fn with_clock_skew_warn_ms(mut self, ms: i64) -> Self {
+        self.clock_skew_warn_ms = ms;
+        self
+    }
+
+    /// Override reconnect backoff and the circuit-breaker threshold.
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.breaker = Arc::new(std::sync::Mutex::new(CircuitBreaker::new(policy)));
+        self
+    }
+
+    fn breaker(&self) -> std::sync::MutexGuard<'_, CircuitBreaker> {
+        self.breaker.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Current breaker state, plus whether a live client is held.
+    pub fn circuit(&self) -> CircuitSnapshot {
+        let connected = self
+            .client
+            .try_read()
+            .map(|guard| guard.as_ref().is_some_and(|c| !c.is_closed()))
+            .unwrap_or(true);
+        self.breaker().snapshot(Instant::now(), connected)
+    }
+
+    fn record_failure(&self, err: &anyhow::Error) {
+        let opened = self
+            .breaker()
+            .on_failure(Instant::now(), &format!("{err:#}"));
+        if opened {
+            let snapshot = self.circuit();
+            warn!(
+                err = %format!("{err:#}"),
+                retry_in_ms = snapshot.retry_in_ms,
+                "postgres circuit opened"
+            );
         }
     }
 
@@ -137,17 +578,94 @@ impl PgPool {
         Ok(())
     }
 
+    async fn has_live_client(&self) -> bool {
+        self.client
+            .read()
+            .await
+            .as_ref()
+            .is_some_and(|c| !c.is_closed())
+    }
+
+    /// Reconnect unless another caller already did. Fails fast while the
+    /// circuit is open.
+    async fn reconnect(&self) -> anyhow::Result<()> {
+        let _single_flight = self.reconnect_lock.lock().await;
+        if self.has_live_client().await {
+            return Ok(());
+        }
+        if let Err(wait) = self.breaker().allow_attempt(Instant::now()) {
+            return Err(anyhow!(
+                "postgres circuit open, next reconnect in {}ms",
+                wait.as_millis()
+            ));
+        }
+        let outcome = tokio::time::timeout(CONNECT_TIMEOUT, self.connect())
+            .await
+            .unwrap_or_else(|_| {
+                Err(anyhow!(
+                    "connect timed out after {}s",
+                    CONNECT_TIMEOUT.as_secs()
+                ))
+            });
+        match outcome {
+            Ok(()) => {
+                if self.breaker().on_success() {
+                    info!("postgres circuit closed");
+                }
+                Ok(())
+            }
+            Err(err) => {
+                self.record_failure(&err);
+                Err(err)
+            }
+        }
+    }
+
+    /// Probe the connection and reconnect if it is gone. Meant to be called
+    /// periodically so connection loss is noticed before a caller hits it.
+    pub async fn health_check(&self) -> anyhow::Result<()> {
+        let probe = {
+            let guard = self.client.read().await;
+            match guard.as_ref().filter(|c| !c.is_closed()) {
+                Some(client) => Some(
+                    tokio::time::timeout(CONNECT_TIMEOUT, client.simple_query("SELECT 1")).await,
+                ),
+                None => None,
+            }
+        };
+        match probe {
+            Some(Ok(Ok(_))) => {
+                self.breaker().on_success();
+                Ok(())
+            }
+            Some(outcome) => {
+                let err = match outcome {
+                    Ok(Err(e)) => anyhow::Error::new(e),
+                    _ => anyhow!(
+                        "health check timed out after {}s",
+                        CONNECT_TIMEOUT.as_secs()
+                    ),
+                };
+                let err = err.context("health_check");
+                *self.client.write().await = None;
+                self.record_failure(&err);
+                Err(err)
+            }
+            None => self.reconnect().await.context("health_check"),
+        }
+    }
+
     /// Get a reference to the underlying client. Reconnects if necessary.
     async fn get(&self) -> anyhow::Result<tokio::sync::RwLockReadGuard<'_, Option<Client>>> {
         // Fast path: client exists and is alive
         {
             let guard = self.client.read().await;
-            if guard.is_some() {
+            if guard.as_ref().is_some_and(|c| !c.is_closed()) {
                 return Ok(guard);
             }
         }
         // Slow path: reconnect
-        self.connect().await?;
+        self.reconnect().await?;
         let guard = self.client.read().await;
         if guard.is_some() {
             Ok(guard)
@@ -167,7 +685,68 @@ impl PgPool {
     }
 }
 
-async fn connect_postgres(dsn: &str) -> anyhow::Result<Client> {
+impl PgPool {
+    /// Open a dedicated connection that LISTENs on `channel` and forwards
+    /// notification payloads. The receiver yields `None` once the listener
+    /// connection drops; callers should fall back to polling and retry.
+    pub async fn listen(&self, channel: &str) -> anyhow::Result<mpsc::UnboundedReceiver<String>> {
+        let (client, mut connection) = tokio_postgres::connect(&self.dsn, NoTls)
+            .await
+            .context("failed to open postgres listener connection")?;
+
+        // The connection has to be driven while LISTEN executes.
+        let listen_sql = format!("LISTEN {}", quote_ident(channel));
+        {
+            let mut listen = std::pin::pin!(client.batch_execute(&listen_sql));
+            loop {
+                tokio::select! {
+                    result = &mut listen => {
+                        result.context("listen")?;
+                        break;
+                    }
+                    message = std::future::poll_fn(|cx| connection.poll_message(cx)) => {
+                        match message {
+                            Some(Ok(_)) => {}
+                            Some(Err(err)) => return Err(err).context("listen"),
+                            None => return Err(anyhow!("listener connection closed during LISTEN")),
+                        }
+                    }
+                }
+            }
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let channel_name = channel.to_string();
+        tokio::spawn(async move {
+            // The connection closes when the client drops, so it lives here.
+            let _client = client;
+            loop {
+                tokio::select! {
+                    _ = tx.closed() => break,
+                    message = std::future::poll_fn(|cx| connection.poll_message(cx)) => {
+                        match message {
+                            Some(Ok(AsyncMessage::Notification(n))) => {
+                                if tx.send(n.payload().to_string()).is_err() {
+                                    break;
+                                }
+                            }
+                            Some(Ok(_)) => {}
+                            Some(Err(err)) => {
+                                warn!(channel = %channel_name, err = %err, "postgres listener connection error");
+                                break;
+                            }
+                            None => break,
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+}
+
+pub(crate) async fn connect_postgres(dsn: &str) -> anyhow::Result<Client> {
     let (client, connection) = tokio_postgres::connect(dsn, NoTls)
         .await
         .context("failed to connect to postgres")?;
@@ -183,7 +762,49 @@ async fn connect_postgres(dsn: &str) -> anyhow::Result<Client> {
 // Schema — live tables (not the legacy migration tables)
 // ---------------------------------------------------------------------------
 
-async fn ensure_schema(client: &Client) -> anyhow::Result<()> {
+/// Version of the DDL in [`apply_schema`]. Bump it whenever that DDL changes
+/// so databases provisioned by `intercomd provision-db` get migrated.
+pub const SCHEMA_VERSION: i32 = 9;
+
+/// Highest schema version recorded in the current search path, 0 if none.
+async fn installed_schema_version(client: &impl GenericClient) -> anyhow::Result<i32> {
+    let row = client
+        .query_one(
+            "SELECT to_regclass('intercom_schema_version') IS NOT NULL AS present",
+            &[],
+        )
+        .await
+        .context("installed_schema_version")?;
+    if !row.get::<_, bool>("present") {
+        return Ok(0);
+    }
+    let row = client
+        .query_one(
+            "SELECT COALESCE(MAX(version), 0) AS version FROM intercom_schema_version",
+            &[],
+        )
+        .await
+        .context("installed_schema_version")?;
+    Ok(row.get("version"))
+}
+
+/// Create or migrate the live tables unless the schema is already current.
+/// Skipping DDL when up to date lets a least-privilege role (see
+/// [`crate::provision`]) connect without owning the tables.
+pub(crate) async fn ensure_schema(client: &impl GenericClient) -> anyhow::Result<()> {
+    let installed = installed_schema_version(client).await?;
+    if installed >= SCHEMA_VERSION {
+        return Ok(());
+    }
+    apply_schema(client).await.with_context(|| {
+        format!(
+            "schema is at version {installed}, expected {SCHEMA_VERSION}; \
+             if this role was provisioned, rerun `intercomd provision-db` with an admin DSN"
+        )
+    })
+}
+
+async fn apply_schema(client: &impl GenericClient) -> anyhow::Result<()> {
     client
         .batch_execute(
             "\
@@ -207,6 +828,33 @@ async fn ensure_schema(client: &Client) -> anyhow::Result<()> {
               PRIMARY KEY (id, chat_jid)
             );
             CREATE INDEX IF NOT EXISTS idx_messages_timestamp ON messages(timestamp);
+            ALTER TABLE messages ADD COLUMN IF NOT EXISTS edited_at TIMESTAMPTZ;
+            ALTER TABLE messages ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+            ALTER TABLE messages ADD COLUMN IF NOT EXISTS correlation_id TEXT;
+            CREATE INDEX IF NOT EXISTS idx_messages_correlation ON messages(correlation_id);
+
+            CREATE TABLE IF NOT EXISTS message_edits (
+              id SERIAL PRIMARY KEY,
+              message_id TEXT NOT NULL,
+              chat_jid TEXT NOT NULL,
+              previous_content TEXT,
+              edited_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            );
+            CREATE INDEX IF NOT EXISTS idx_message_edits_message ON message_edits(message_id, chat_jid);
+
+            CREATE TABLE IF NOT EXISTS attachments (
+              id SERIAL PRIMARY KEY,
+              message_id TEXT NOT NULL,
+              chat_jid TEXT NOT NULL,
+              kind TEXT NOT NULL,
+              mime_type TEXT,
+              file_name TEXT,
+              file_path TEXT,
+              caption TEXT,
+              size_bytes BIGINT,
+              created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            );
+            CREATE INDEX IF NOT EXISTS idx_attachments_message ON attachments(message_id, chat_jid);
 
             CREATE TABLE IF NOT EXISTS scheduled_tasks (
               id TEXT PRIMARY KEY,
@@ -224,6 +872,7 @@ async fn ensure_schema(client: &Client) -> anyhow::Result<()> {
             );
             CREATE INDEX IF NOT EXISTS idx_tasks_next_run ON scheduled_tasks(next_run);
             CREATE INDEX IF NOT EXISTS idx_tasks_status ON scheduled_tasks(status);
+            ALTER TABLE scheduled_tasks ADD COLUMN IF NOT EXISTS task_config JSONB;
 
             CREATE TABLE IF NOT EXISTS task_run_logs (
               id SERIAL PRIMARY KEY,
@@ -235,6 +884,57 @@ async fn ensure_schema(client: &Client) -> anyhow::Result<()> {
               error TEXT
             );
             CREATE INDEX IF NOT EXISTS idx_task_run_logs_task ON task_run_logs(task_id, run_at);
+            ALTER TABLE task_run_logs ADD COLUMN IF NOT EXISTS correlation_id TEXT;
+            CREATE INDEX IF NOT EXISTS idx_task_run_logs_correlation ON task_run_logs(correlation_id);
+
+            CREATE TABLE IF NOT EXISTS task_templates (
+              name TEXT PRIMARY KEY,
+              prompt TEXT NOT NULL,
+              schedule_type TEXT NOT NULL,
+              schedule_value TEXT NOT NULL,
+              context_mode TEXT NOT NULL DEFAULT 'isolated',
+              task_config JSONB,
+              created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+              updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            );
+
+            CREATE TABLE IF NOT EXISTS container_runs (
+              id BIGSERIAL PRIMARY KEY,
+              run_id TEXT,
+              group_folder TEXT NOT NULL,
+              chat_jid TEXT NOT NULL,
+              trigger TEXT NOT NULL,
+              started_at TIMESTAMPTZ NOT NULL,
+              finished_at TIMESTAMPTZ NOT NULL,
+              duration_ms BIGINT NOT NULL,
+              exit_code INTEGER,
+              timed_out BOOLEAN NOT NULL DEFAULT FALSE,
+              status TEXT NOT NULL,
+              error TEXT,
+              model TEXT,
+              session_id TEXT,
+              output_bytes BIGINT NOT NULL DEFAULT 0,
+              correlation_id TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_container_runs_group ON container_runs(group_folder, started_at);
+            CREATE INDEX IF NOT EXISTS idx_container_runs_started ON container_runs(started_at);
+            ALTER TABLE container_runs ADD COLUMN IF NOT EXISTS input_tokens BIGINT NOT NULL DEFAULT 0;
+            ALTER TABLE container_runs ADD COLUMN IF NOT EXISTS output_tokens BIGINT NOT NULL DEFAULT 0;
+            ALTER TABLE container_runs ADD COLUMN IF NOT EXISTS cache_read_tokens BIGINT NOT NULL DEFAULT 0;
+            ALTER TABLE container_runs ADD COLUMN IF NOT EXISTS cache_write_tokens BIGINT NOT NULL DEFAULT 0;
+            ALTER TABLE container_runs ADD COLUMN IF NOT EXISTS cost_usd DOUBLE PRECISION;
+
+            CREATE TABLE IF NOT EXISTS demarch_audit (
+              id BIGSERIAL PRIMARY KEY,
+              created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+              operation TEXT NOT NULL,
+              params JSONB NOT NULL,
+              source_group TEXT,
+              is_main BOOLEAN NOT NULL DEFAULT FALSE,
+              status TEXT NOT NULL,
+              result TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_demarch_audit_created ON demarch_audit(created_at);
 
             CREATE TABLE IF NOT EXISTS router_state (
               key TEXT PRIMARY KEY,
@@ -257,10 +957,43 @@ async fn ensure_schema(client: &Client) -> anyhow::Result<()> {
               runtime TEXT,
               model TEXT
             );
+            ALTER TABLE registered_groups ADD COLUMN IF NOT EXISTS muted_until TIMESTAMPTZ;
+            ALTER TABLE registered_groups ADD COLUMN IF NOT EXISTS locale TEXT;
+
+            CREATE TABLE IF NOT EXISTS intercom_schema_version (
+              version INTEGER PRIMARY KEY,
+              applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+            );
+            ",
+        )
+        .await
+        .context("failed to create postgres schema")?;
+    client
+        .batch_execute(&format!(
+            "\
+            CREATE OR REPLACE FUNCTION intercom_notify_new_message() RETURNS trigger AS $$
+            BEGIN
+              PERFORM pg_notify({channel}, NEW.chat_jid);
+              RETURN NEW;
+            END;
+            $$ LANGUAGE plpgsql;
+            DROP TRIGGER IF EXISTS messages_notify_insert ON messages;
+            CREATE TRIGGER messages_notify_insert
+              AFTER INSERT ON messages
+              FOR EACH ROW EXECUTE FUNCTION intercom_notify_new_message();
             ",
+            channel = quote_literal(NEW_MESSAGE_CHANNEL),
+        ))
+        .await
+        .context("failed to create new-message trigger")?;
+    client
+        .execute(
+            "INSERT INTO intercom_schema_version (version) VALUES ($1) ON CONFLICT DO NOTHING",
+            &[&SCHEMA_VERSION],
         )
         .await
-        .context("failed to create postgres schema")
+        .context("failed to record postgres schema version")?;
+    Ok(())
 }
 
 // ---------------------------------------------------------------------------
@@ -287,7 +1020,7 @@ impl PgPool {
                     .execute(
                         "\
                         INSERT INTO chats (jid, name, last_message_time, channel, is_group)
-                        VALUES ($1, $2, $3::timestamptz, $4, $5)
+                        VALUES ($1, $2, $3::text::timestamptz, $4, $5)
                         ON CONFLICT (jid) DO UPDATE SET
                           name = COALESCE(NULLIF(EXCLUDED.name, EXCLUDED.jid), chats.name),
                           last_message_time = GREATEST(chats.last_message_time, EXCLUDED.last_message_time),
@@ -314,7 +1047,7 @@ impl PgPool {
                     .execute(
                         "\
                         INSERT INTO chats (jid, name, last_message_time)
-                        VALUES ($1, $2, $3::timestamptz)
+                        VALUES ($1, $2, $3::text::timestamptz)
                         ON CONFLICT (jid) DO UPDATE SET name = EXCLUDED.name
                         ",
                         &[&jid, &name, &now],
@@ -353,22 +1086,184 @@ impl PgPool {
         .await
     }
 
+    pub async fn get_chat_stats(&self, chat_jid: &str) -> anyhow::Result<ChatStats> {
+        self.with_client(|client| {
+            let chat_jid = chat_jid.to_string();
+            Box::pin(async move {
+                let totals = client
+                    .query_one(
+                        "\
+                        SELECT COUNT(*) AS total,
+                               COUNT(*) FILTER (WHERE is_bot_message) AS bot,
+                               MIN(timestamp) AS first_at,
+                               MAX(timestamp) AS last_at
+                        FROM messages
+                        WHERE chat_jid = $1 AND deleted_at IS NULL
+                        ",
+                        &[&chat_jid],
+                    )
+                    .await
+                    .context("get_chat_stats")?;
+                let rows = client
+                    .query(
+                        "\
+                        SELECT COALESCE(sender, '') AS sender,
+                               COALESCE(MAX(sender_name), '') AS sender_name,
+                               COUNT(*) AS message_count,
+                               COALESCE(BOOL_OR(is_bot_message), FALSE) AS is_bot
+                        FROM messages
+                        WHERE chat_jid = $1 AND deleted_at IS NULL
+                        GROUP BY COALESCE(sender, '')
+                        ORDER BY message_count DESC, sender
+                        ",
+                        &[&chat_jid],
+                    )
+                    .await
+                    .context("get_chat_stats senders")?;
+                let senders = rows
+                    .iter()
+                    .map(|r| SenderStats {
+                        sender: r.get("sender"),
+                        sender_name: r.get("sender_name"),
+                        message_count: r.get("message_count"),
+                        is_bot: r.get("is_bot"),
+                    })
+                    .collect();
+                Ok(ChatStats::from_counts(
+                    &chat_jid,
+                    totals.get("total"),
+                    totals.get("bot"),
+                    totals
+                        .get::<_, Option<std::time::SystemTime>>("first_at")
+                        .map(format_ts),
+                    totals
+                        .get::<_, Option<std::time::SystemTime>>("last_at")
+                        .map(format_ts),
+                    senders,
+                ))
+            })
+        })
+        .await
+    }
+
+    /// Run a configured report query on a dedicated connection inside a
+    /// READ ONLY transaction, so it can neither write nor join another
+    /// caller's transaction on the shared client.
+    ///
+    /// Parameters are bound as text; the SQL casts them where needed
+    /// (`$1::timestamptz`). Rows are serialized by Postgres via `to_json`.
+    pub async fn run_read_query(
+        &self,
+        sql: &str,
+        params: &[Option<String>],
+        max_rows: usize,
+        timeout_ms: u64,
+    ) -> anyhow::Result<QueryResult> {
+        let sql = sql.trim().trim_end_matches(';');
+        let mut client = connect_postgres(&self.dsn)
+            .await
+            .context("run_read_query connect")?;
+        let tx = client
+            .build_transaction()
+            .read_only(true)
+            .start()
+            .await
+            .context("run_read_query begin")?;
+        if timeout_ms > 0 {
+            tx.batch_execute(&format!("SET LOCAL statement_timeout = {timeout_ms}"))
+                .await
+                .context("run_read_query timeout")?;
+        }
+
+        let types = vec![Type::TEXT; params.len()];
+        let inner = tx
+            .prepare_typed(sql, &types)
+            .await
+            .context("run_read_query prepare")?;
+        let columns = inner
+            .columns()
+            .iter()
+            .map(|c| c.name().to_string())
+            .collect();
+
+        let wrapped = format!(
+            "SELECT to_json(q) FROM ({sql}) q LIMIT {}",
+            max_rows.saturating_add(1)
+        );
+        let stmt = tx
+            .prepare_typed(&wrapped, &types)
+            .await
+            .context("run_read_query prepare")?;
+        let bound: Vec<&(dyn ToSql + Sync)> =
+            params.iter().map(|p| p as &(dyn ToSql + Sync)).collect();
+        let rows = tx
+            .query(&stmt, &bound)
+            .await
+            .context("run_read_query")?
+            .iter()
+            .map(|r| r.get::<_, serde_json::Value>(0))
+            .collect();
+        tx.rollback().await.ok();
+        Ok(QueryResult::capped(columns, rows, max_rows))
+    }
+
     // -----------------------------------------------------------------------
     // Message operations
     // -----------------------------------------------------------------------
 
+    /// Store a message, clamping client timestamps that lie in the future to
+    /// the server clock so a skewed host cannot push rows past the cursors.
+    /// The clamp is truncated to milliseconds, the precision cursors carry,
+    /// or the row would sort after its own cursor and be delivered again.
+    ///
+    /// The message and its attachments are written in one statement, so the
+    /// new-message NOTIFY (sent on commit) never wakes a reader before the
+    /// attachments are visible. A message without attachments keeps any
+    /// stored earlier.
     pub async fn store_message(&self, msg: &NewMessage) -> anyhow::Result<()> {
+        let skew_warn_ms = self.clock_skew_warn_ms;
         self.with_client(|client| {
             let msg = msg.clone();
             Box::pin(async move {
-                client
-                    .execute(
+                // One array per attachments column, unnested in the insert.
+                let attachments = &msg.attachments;
+                let kinds: Vec<&str> = attachments.iter().map(|a| a.kind.as_str()).collect();
+                let mime_types: Vec<Option<&str>> =
+                    attachments.iter().map(|a| a.mime_type.as_deref()).collect();
+                let file_names: Vec<Option<&str>> =
+                    attachments.iter().map(|a| a.file_name.as_deref()).collect();
+                let file_paths: Vec<Option<&str>> =
+                    attachments.iter().map(|a| a.file_path.as_deref()).collect();
+                let captions: Vec<Option<&str>> =
+                    attachments.iter().map(|a| a.caption.as_deref()).collect();
+                let sizes: Vec<Option<i64>> = attachments.iter().map(|a| a.size_bytes).collect();
+                let row = client
+                    .query_one(
                         "\
-                        INSERT INTO messages (id, chat_jid, sender, sender_name, content, timestamp, is_from_me, is_bot_message)
-                        VALUES ($1, $2, $3, $4, $5, $6::timestamptz, $7, $8)
-                        ON CONFLICT (id, chat_jid) DO UPDATE SET
-                          content = EXCLUDED.content,
-                          is_bot_message = EXCLUDED.is_bot_message
+                        WITH stored AS (
+                          INSERT INTO messages (id, chat_jid, sender, sender_name, content, timestamp, is_from_me, is_bot_message, correlation_id)
+                          VALUES ($1, $2, $3, $4, $5, LEAST($6::text::timestamptz, date_trunc('milliseconds', now())), $7, $8, $9)
+                          ON CONFLICT (id, chat_jid) DO UPDATE SET
+                            content = EXCLUDED.content,
+                            is_bot_message = EXCLUDED.is_bot_message,
+                            correlation_id = COALESCE(messages.correlation_id, EXCLUDED.correlation_id)
+                          RETURNING id, chat_jid
+                        ), cleared AS (
+                          DELETE FROM attachments a
+                          USING stored
+                          WHERE cardinality($10::text[]) > 0
+                            AND a.message_id = stored.id AND a.chat_jid = stored.chat_jid
+                        ), added AS (
+                          INSERT INTO attachments
+                            (message_id, chat_jid, kind, mime_type, file_name, file_path, caption, size_bytes)
+                          SELECT stored.id, stored.chat_jid, a.kind, a.mime_type, a.file_name, a.file_path, a.caption, a.size_bytes
+                          FROM stored,
+                            unnest($10::text[], $11::text[], $12::text[], $13::text[], $14::text[], $15::bigint[])
+                              WITH ORDINALITY AS a(kind, mime_type, file_name, file_path, caption, size_bytes, n)
+                          ORDER BY a.n
+                        )
+                        SELECT (EXTRACT(EPOCH FROM ($6::text::timestamptz - now())) * 1000)::float8 AS skew_ms
+                        FROM stored
                         ",
                         &[
                             &msg.id,
@@ -379,10 +1274,27 @@ impl PgPool {
                             &msg.timestamp,
                             &msg.is_from_me,
                             &msg.is_bot_message,
+                            &msg.correlation_id,
+                            &kinds,
+                            &mime_types,
+                            &file_names,
+                            &file_paths,
+                            &captions,
+                            &sizes,
                         ],
                     )
                     .await
                     .context("store_message")?;
+                let skew_ms: f64 = row.get("skew_ms");
+                if skew_exceeds(skew_ms, skew_warn_ms) {
+                    warn!(
+                        id = %msg.id,
+                        chat_jid = %msg.chat_jid,
+                        client_timestamp = %msg.timestamp,
+                        skew_ms = skew_ms as i64,
+                        "message timestamp differs from server clock"
+                    );
+                }
                 Ok(())
             })
         })
@@ -403,6 +1315,7 @@ impl PgPool {
                         SELECT sender_name, content, timestamp, is_bot_message
                         FROM messages
                         WHERE chat_jid = $1 AND content != '' AND content IS NOT NULL
+                          AND deleted_at IS NULL
                         ORDER BY timestamp DESC
                         LIMIT $2
                         ",
@@ -455,14 +1368,16 @@ impl PgPool {
                 let bot_idx = jids.len() + 2;
 
                 let sql = format!(
-                    "SELECT id, chat_jid, sender, sender_name, content, timestamp \
+                    "SELECT id, chat_jid, sender, sender_name, content, timestamp, edited_at, correlation_id \
                      FROM messages \
-                     WHERE timestamp > $1::timestamptz AND chat_jid IN ({}) \
-                       AND is_bot_message = FALSE AND content NOT LIKE ${} \
-                       AND content != '' AND content IS NOT NULL \
+                     WHERE timestamp > {} AND chat_jid IN ({}) \
+                       AND is_bot_message = FALSE AND COALESCE(content, '') NOT LIKE ${} \
+                       AND deleted_at IS NULL AND {} \
                      ORDER BY timestamp",
+                    cursor_bound("$1"),
                     placeholders.join(", "),
                     bot_idx,
+                    HAS_BODY,
                 );
 
                 let param_refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> =
@@ -472,14 +1387,12 @@ impl PgPool {
                     .await
                     .context("get_new_messages")?;
 
-                let mut new_timestamp = last_timestamp;
-                let messages: Vec<NewMessage> = rows
+                // Rows are ordered by the server-side TIMESTAMPTZ, so the last
+                // row is the new high-water mark. Never compare the strings.
+                let mut messages: Vec<NewMessage> = rows
                     .iter()
                     .map(|r| {
                         let ts = format_ts(r.get("timestamp"));
-                        if ts > new_timestamp {
-                            new_timestamp = ts.clone();
-                        }
                         NewMessage {
                             id: r.get("id"),
                             chat_jid: r.get("chat_jid"),
@@ -489,10 +1402,18 @@ impl PgPool {
                             timestamp: ts,
                             is_from_me: false,
                             is_bot_message: false,
+                            edited: r.get::<_, Option<std::time::SystemTime>>("edited_at").is_some(),
+                            attachments: Vec::new(),
+                            correlation_id: r.get("correlation_id"),
                         }
                     })
                     .collect();
+                load_attachments(client, &mut messages).await?;
 
+                let new_timestamp = messages
+                    .last()
+                    .map(|m| m.timestamp.clone())
+                    .unwrap_or(last_timestamp);
                 Ok((messages, new_timestamp))
             })
         })
@@ -510,21 +1431,21 @@ impl PgPool {
             let since_timestamp = since_timestamp.to_string();
             let bot_prefix = format!("{}:%", bot_prefix);
             Box::pin(async move {
+                let sql = format!(
+                    "SELECT id, chat_jid, sender, sender_name, content, timestamp, edited_at, correlation_id \
+                     FROM messages \
+                     WHERE chat_jid = $1 AND timestamp > {} \
+                       AND is_bot_message = FALSE AND COALESCE(content, '') NOT LIKE $3 \
+                       AND deleted_at IS NULL AND {} \
+                     ORDER BY timestamp",
+                    cursor_bound("$2"),
+                    HAS_BODY,
+                );
                 let rows = client
-                    .query(
-                        "\
-                        SELECT id, chat_jid, sender, sender_name, content, timestamp
-                        FROM messages
-                        WHERE chat_jid = $1 AND timestamp > $2::timestamptz
-                          AND is_bot_message = FALSE AND content NOT LIKE $3
-                          AND content != '' AND content IS NOT NULL
-                        ORDER BY timestamp
-                        ",
-                        &[&chat_jid, &since_timestamp, &bot_prefix],
-                    )
+                    .query(&sql, &[&chat_jid, &since_timestamp, &bot_prefix])
                     .await
                     .context("get_messages_since")?;
-                Ok(rows
+                let mut messages: Vec<NewMessage> = rows
                     .iter()
                     .map(|r| NewMessage {
                         id: r.get("id"),
@@ -535,15 +1456,375 @@ impl PgPool {
                         timestamp: format_ts(r.get("timestamp")),
                         is_from_me: false,
                         is_bot_message: false,
+                        edited: r.get::<_, Option<std::time::SystemTime>>("edited_at").is_some(),
+                        attachments: Vec::new(),
+                        correlation_id: r.get("correlation_id"),
                     })
-                    .collect())
+                    .collect();
+                load_attachments(client, &mut messages).await?;
+                Ok(messages)
             })
         })
         .await
     }
 
-    // -----------------------------------------------------------------------
-    // Scheduled task operations
+    /// A page of [`MessageFilter`] matches. Timestamps are compared at the
+    /// millisecond precision they are returned with, so the last message's
+    /// timestamp is an exact cursor.
+    pub async fn list_messages(&self, filter: &MessageFilter) -> anyhow::Result<Vec<NewMessage>> {
+        if filter.chat_jids.is_empty() {
+            return Ok(vec![]);
+        }
+        self.with_client(|client| {
+            let filter = filter.clone();
+            Box::pin(async move {
+                let bot_prefix = format!("{}:%", filter.bot_prefix);
+                let (after_ts, after_jid, after_id) = match filter.after {
+                    Some((ts, jid, id)) => (Some(ts), Some(jid), Some(id)),
+                    None => (None, None, None),
+                };
+                let sql = format!(
+                    "SELECT id, chat_jid, sender, sender_name, content, timestamp, edited_at, correlation_id \
+                     FROM messages \
+                     WHERE chat_jid = ANY($1) AND timestamp > {} \
+                       AND is_bot_message = FALSE AND COALESCE(content, '') NOT LIKE $3 \
+                       AND deleted_at IS NULL AND {} \
+                       AND ($4::text IS NULL OR sender = $4) \
+                       AND ($5::text IS NULL OR (date_trunc('milliseconds', timestamp), chat_jid, id) \
+                            > ($5::text::timestamptz, $6::text, $7::text)) \
+                     ORDER BY date_trunc('milliseconds', timestamp), chat_jid, id \
+                     LIMIT $8",
+                    cursor_bound("$2"),
+                    HAS_BODY,
+                );
+                let rows = client
+                    .query(
+                        &sql,
+                        &[
+                            &filter.chat_jids,
+                            &filter.since,
+                            &bot_prefix,
+                            &filter.sender,
+                            &after_ts,
+                            &after_jid,
+                            &after_id,
+                            &filter.limit,
+                        ],
+                    )
+                    .await
+                    .context("list_messages")?;
+                let mut messages: Vec<NewMessage> = rows
+                    .iter()
+                    .map(|r| NewMessage {
+                        id: r.get("id"),
+                        chat_jid: r.get("chat_jid"),
+                        sender: r.get::<_, Option<String>>("sender").unwrap_or_default(),
+                        sender_name: r.get::<_, Option<String>>("sender_name").unwrap_or_default(),
+                        content: r.get::<_, Option<String>>("content").unwrap_or_default(),
+                        timestamp: format_ts(r.get("timestamp")),
+                        is_from_me: false,
+                        is_bot_message: false,
+                        edited: r.get::<_, Option<std::time::SystemTime>>("edited_at").is_some(),
+                        attachments: Vec::new(),
+                        correlation_id: r.get("correlation_id"),
+                    })
+                    .collect();
+                load_attachments(client, &mut messages).await?;
+                Ok(messages)
+            })
+        })
+        .await
+    }
+
+    /// Rebuild the batch a stored message was processed in: the user
+    /// messages after the last bot reply that precedes it, up to and
+    /// including the message itself. Empty if the message is unknown.
+    pub async fn get_replay_batch(
+        &self,
+        chat_jid: &str,
+        message_id: &str,
+        bot_prefix: &str,
+    ) -> anyhow::Result<Vec<NewMessage>> {
+        self.with_client(|client| {
+            let chat_jid = chat_jid.to_string();
+            let message_id = message_id.to_string();
+            let bot_prefix = format!("{}:%", bot_prefix);
+            Box::pin(async move {
+                let sql = format!(
+                    "WITH target AS ( \
+                       SELECT timestamp FROM messages WHERE chat_jid = $1 AND id = $2 \
+                     ), last_reply AS ( \
+                       SELECT MAX(m.timestamp) AS timestamp FROM messages m, target t \
+                       WHERE m.chat_jid = $1 AND m.timestamp < t.timestamp \
+                         AND (m.is_bot_message OR COALESCE(m.content, '') LIKE $3) \
+                     ) \
+                     SELECT id, chat_jid, sender, sender_name, content, timestamp, edited_at, correlation_id \
+                     FROM messages \
+                     WHERE chat_jid = $1 \
+                       AND timestamp <= (SELECT timestamp FROM target) \
+                       AND timestamp > COALESCE((SELECT timestamp FROM last_reply), '-infinity') \
+                       AND is_bot_message = FALSE AND COALESCE(content, '') NOT LIKE $3 \
+                       AND deleted_at IS NULL AND {} \
+                     ORDER BY timestamp",
+                    HAS_BODY,
+                );
+                let rows = client
+                    .query(&sql, &[&chat_jid, &message_id, &bot_prefix])
+                    .await
+                    .context("get_replay_batch")?;
+                let mut messages: Vec<NewMessage> = rows
+                    .iter()
+                    .map(|r| NewMessage {
+                        id: r.get("id"),
+                        chat_jid: r.get("chat_jid"),
+                        sender: r.get::<_, Option<String>>("sender").unwrap_or_default(),
+                        sender_name: r.get::<_, Option<String>>("sender_name").unwrap_or_default(),
+                        content: r.get::<_, Option<String>>("content").unwrap_or_default(),
+                        timestamp: format_ts(r.get("timestamp")),
+                        is_from_me: false,
+                        is_bot_message: false,
+                        edited: r.get::<_, Option<std::time::SystemTime>>("edited_at").is_some(),
+                        attachments: Vec::new(),
+                        correlation_id: r.get("correlation_id"),
+                    })
+                    .collect();
+                load_attachments(client, &mut messages).await?;
+                Ok(messages)
+            })
+        })
+        .await
+    }
+
+    /// Messages (inbound and replies) and task runs tagged with
+    /// `correlation_id`, oldest first.
+    pub async fn get_correlation_trace(
+        &self,
+        correlation_id: &str,
+    ) -> anyhow::Result<CorrelationTrace> {
+        self.with_client(|client| {
+            let correlation_id = correlation_id.to_string();
+            Box::pin(async move {
+                let rows = client
+                    .query(
+                        "\
+                        SELECT id, chat_jid, sender, sender_name, content, timestamp,
+                               is_from_me, is_bot_message, edited_at
+                        FROM messages
+                        WHERE correlation_id = $1
+                        ORDER BY timestamp
+                        ",
+                        &[&correlation_id],
+                    )
+                    .await
+                    .context("get_correlation_trace")?;
+                let mut messages: Vec<NewMessage> = rows
+                    .iter()
+                    .map(|r| NewMessage {
+                        id: r.get("id"),
+                        chat_jid: r.get("chat_jid"),
+                        sender: r.get::<_, Option<String>>("sender").unwrap_or_default(),
+                        sender_name: r
+                            .get::<_, Option<String>>("sender_name")
+                            .unwrap_or_default(),
+                        content: r.get::<_, Option<String>>("content").unwrap_or_default(),
+                        timestamp: format_ts(r.get("timestamp")),
+                        is_from_me: r.get::<_, Option<bool>>("is_from_me").unwrap_or(false),
+                        is_bot_message: r.get::<_, Option<bool>>("is_bot_message").unwrap_or(false),
+                        edited: r
+                            .get::<_, Option<std::time::SystemTime>>("edited_at")
+                            .is_some(),
+                        attachments: Vec::new(),
+                        correlation_id: Some(correlation_id.clone()),
+                    })
+                    .collect();
+                load_attachments(client, &mut messages).await?;
+
+                let rows = client
+                    .query(
+                        "\
+                        SELECT task_id, run_at, duration_ms, status, result, error
+                        FROM task_run_logs
+                        WHERE correlation_id = $1
+                        ORDER BY run_at
+                        ",
+                        &[&correlation_id],
+                    )
+                    .await
+                    .context("get_correlation_trace")?;
+                let task_runs = rows
+                    .iter()
+                    .map(|r| TaskRunLog {
+                        task_id: r.get("task_id"),
+                        run_at: format_ts(r.get("run_at")),
+                        duration_ms: i64::from(r.get::<_, i32>("duration_ms")),
+                        status: r.get("status"),
+                        result: r.get("result"),
+                        error: r.get("error"),
+                        correlation_id: Some(correlation_id.clone()),
+                    })
+                    .collect();
+                Ok(CorrelationTrace {
+                    correlation_id,
+                    messages,
+                    task_runs,
+                })
+            })
+        })
+        .await
+    }
+
+    /// Replace a message's content, keeping the previous version in
+    /// `message_edits`. Returns `false` if the message is unknown.
+    pub async fn edit_message(
+        &self,
+        id: &str,
+        chat_jid: &str,
+        content: &str,
+        edited_at: &str,
+    ) -> anyhow::Result<bool> {
+        self.with_client(|client| {
+            let id = id.to_string();
+            let chat_jid = chat_jid.to_string();
+            let content = content.to_string();
+            let edited_at = edited_at.to_string();
+            Box::pin(async move {
+                let found = client
+                    .query_opt(
+                        "\
+                        WITH prior AS (
+                          SELECT id, chat_jid, content FROM messages
+                          WHERE id = $1 AND chat_jid = $2
+                          FOR UPDATE
+                        ), history AS (
+                          INSERT INTO message_edits (message_id, chat_jid, previous_content, edited_at)
+                          SELECT id, chat_jid, content, LEAST($4::text::timestamptz, date_trunc('milliseconds', now())) FROM prior
+                          WHERE content IS DISTINCT FROM $3
+                        ), updated AS (
+                          UPDATE messages m
+                          SET content = $3, edited_at = LEAST($4::text::timestamptz, date_trunc('milliseconds', now()))
+                          FROM prior
+                          WHERE m.id = prior.id AND m.chat_jid = prior.chat_jid
+                            AND prior.content IS DISTINCT FROM $3
+                        )
+                        SELECT 1 FROM prior
+                        ",
+                        &[&id, &chat_jid, &content, &edited_at],
+                    )
+                    .await
+                    .context("edit_message")?;
+                Ok(found.is_some())
+            })
+        })
+        .await
+    }
+
+    /// Tombstone a message: the row stays (so cursors and history remain
+    /// consistent) but is excluded from agent prompts.
+    pub async fn delete_message(
+        &self,
+        id: &str,
+        chat_jid: &str,
+        deleted_at: &str,
+    ) -> anyhow::Result<bool> {
+        self.with_client(|client| {
+            let id = id.to_string();
+            let chat_jid = chat_jid.to_string();
+            let deleted_at = deleted_at.to_string();
+            Box::pin(async move {
+                let rows = client
+                    .execute(
+                        "\
+                        UPDATE messages
+                        SET deleted_at = COALESCE(deleted_at, LEAST($3::text::timestamptz, date_trunc('milliseconds', now())))
+                        WHERE id = $1 AND chat_jid = $2
+                        ",
+                        &[&id, &chat_jid, &deleted_at],
+                    )
+                    .await
+                    .context("delete_message")?;
+                Ok(rows > 0)
+            })
+        })
+        .await
+    }
+
+    pub async fn get_message_edits(
+        &self,
+        id: &str,
+        chat_jid: &str,
+    ) -> anyhow::Result<Vec<MessageEdit>> {
+        self.with_client(|client| {
+            let id = id.to_string();
+            let chat_jid = chat_jid.to_string();
+            Box::pin(async move {
+                let rows = client
+                    .query(
+                        "\
+                        SELECT message_id, chat_jid, previous_content, edited_at
+                        FROM message_edits
+                        WHERE message_id = $1 AND chat_jid = $2
+                        ORDER BY edited_at, id
+                        ",
+                        &[&id, &chat_jid],
+                    )
+                    .await
+                    .context("get_message_edits")?;
+                Ok(rows
+                    .iter()
+                    .map(|r| MessageEdit {
+                        message_id: r.get("message_id"),
+                        chat_jid: r.get("chat_jid"),
+                        previous_content: r
+                            .get::<_, Option<String>>("previous_content")
+                            .unwrap_or_default(),
+                        edited_at: format_ts(r.get("edited_at")),
+                    })
+                    .collect())
+            })
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Attachment operations
+    // -----------------------------------------------------------------------
+
+    pub async fn store_attachment(&self, attachment: &Attachment) -> anyhow::Result<()> {
+        self.with_client(|client| {
+            let attachment = attachment.clone();
+            Box::pin(async move {
+                insert_attachment(client, &attachment)
+                    .await
+                    .context("store_attachment")
+            })
+        })
+        .await
+    }
+
+    pub async fn get_attachments(
+        &self,
+        message_id: &str,
+        chat_jid: &str,
+    ) -> anyhow::Result<Vec<Attachment>> {
+        self.with_client(|client| {
+            let message_id = message_id.to_string();
+            let chat_jid = chat_jid.to_string();
+            Box::pin(async move {
+                let rows = client
+                    .query(
+                        "SELECT * FROM attachments WHERE message_id = $1 AND chat_jid = $2 ORDER BY id",
+                        &[&message_id, &chat_jid],
+                    )
+                    .await
+                    .context("get_attachments")?;
+                Ok(rows.iter().map(row_to_attachment).collect())
+            })
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Scheduled task operations
     // -----------------------------------------------------------------------
 
     pub async fn create_task(&self, task: &ScheduledTask) -> anyhow::Result<()> {
@@ -554,8 +1835,8 @@ impl PgPool {
                     .execute(
                         "\
                         INSERT INTO scheduled_tasks
-                          (id, group_folder, chat_jid, prompt, schedule_type, schedule_value, context_mode, next_run, status, created_at)
-                        VALUES ($1, $2, $3, $4, $5, $6, $7, $8::timestamptz, $9, $10::timestamptz)
+                          (id, group_folder, chat_jid, prompt, schedule_type, schedule_value, context_mode, next_run, status, created_at, task_config)
+                        VALUES ($1, $2, $3, $4, $5, $6, $7, $8::text::timestamptz, $9, $10::text::timestamptz, $11)
                         ",
                         &[
                             &task.id,
@@ -568,6 +1849,7 @@ impl PgPool {
                             &task.next_run,
                             &task.status,
                             &task.created_at,
+                            &task.task_config,
                         ],
                     )
                     .await
@@ -606,7 +1888,7 @@ impl PgPool {
                     )
                     .await
                     .context("get_tasks_for_group")?;
-                Ok(rows.iter().map(|r| row_to_task(r)).collect())
+                Ok(rows.iter().map(row_to_task).collect())
             })
         })
         .await
@@ -622,7 +1904,46 @@ impl PgPool {
                     )
                     .await
                     .context("get_all_tasks")?;
-                Ok(rows.iter().map(|r| row_to_task(r)).collect())
+                Ok(rows.iter().map(row_to_task).collect())
+            })
+        })
+        .await
+    }
+
+    /// A page of [`TaskFilter`] matches, compared at millisecond precision
+    /// like [`Self::list_messages`].
+    pub async fn list_tasks(&self, filter: &TaskFilter) -> anyhow::Result<Vec<ScheduledTask>> {
+        self.with_client(|client| {
+            let filter = filter.clone();
+            Box::pin(async move {
+                let (after_ts, after_id) = filter.after.clone().unzip();
+                let sql = format!(
+                    "SELECT * FROM scheduled_tasks \
+                     WHERE ($1::text IS NULL OR group_folder = $1) \
+                       AND ($2::text IS NULL OR chat_jid = $2) \
+                       AND ($3::text IS NULL OR status = $3) \
+                       AND ($4::text IS NULL OR (date_trunc('milliseconds', created_at), id) \
+                            {past} ($4::text::timestamptz, $5::text)) \
+                     ORDER BY date_trunc('milliseconds', created_at) {order}, id {order} \
+                     LIMIT $6",
+                    past = filter.order.past(),
+                    order = filter.order.sql(),
+                );
+                let rows = client
+                    .query(
+                        &sql,
+                        &[
+                            &filter.group_folder,
+                            &filter.chat_jid,
+                            &filter.status,
+                            &after_ts,
+                            &after_id,
+                            &filter.limit,
+                        ],
+                    )
+                    .await
+                    .context("list_tasks")?;
+                Ok(rows.iter().map(row_to_task).collect())
             })
         })
         .await
@@ -650,7 +1971,7 @@ impl PgPool {
             idx += 1;
         }
         if let Some(ref next_run) = updates.next_run {
-            fields.push(format!("next_run = ${idx}::timestamptz"));
+            fields.push(format!("next_run = ${idx}::text::timestamptz"));
             params.push(next_run.clone());
             idx += 1;
         }
@@ -659,6 +1980,11 @@ impl PgPool {
             params.push(status.clone());
             idx += 1;
         }
+        if let Some(ref task_config) = updates.task_config {
+            fields.push(format!("task_config = ${idx}::text::jsonb"));
+            params.push(task_config.to_string());
+            idx += 1;
+        }
 
         if fields.is_empty() {
             return Ok(());
@@ -714,7 +2040,7 @@ impl PgPool {
                     )
                     .await
                     .context("get_due_tasks")?;
-                Ok(rows.iter().map(|r| row_to_task(r)).collect())
+                Ok(rows.iter().map(row_to_task).collect())
             })
         })
         .await
@@ -742,7 +2068,7 @@ impl PgPool {
                     .execute(
                         "\
                         UPDATE scheduled_tasks
-                        SET next_run = $1::timestamptz, last_run = $2::timestamptz,
+                        SET next_run = $1::text::timestamptz, last_run = $2::text::timestamptz,
                             last_result = $3,
                             status = CASE WHEN $1 IS NULL THEN 'completed' ELSE status END
                         WHERE id = $4
@@ -758,6 +2084,38 @@ impl PgPool {
         .await
     }
 
+    /// Set a task's status, and its next run when `next_run` is given.
+    /// Completed tasks are left alone; returns the task as updated, or
+    /// `None` if it does not exist or has completed.
+    pub async fn set_task_status(
+        &self,
+        id: &str,
+        status: &str,
+        next_run: Option<&str>,
+    ) -> anyhow::Result<Option<ScheduledTask>> {
+        self.with_client(|client| {
+            let id = id.to_string();
+            let status = status.to_string();
+            let next_run = next_run.map(|s| s.to_string());
+            Box::pin(async move {
+                let row = client
+                    .query_opt(
+                        "\
+                        UPDATE scheduled_tasks
+                        SET status = $1, next_run = COALESCE($2::text::timestamptz, next_run)
+                        WHERE id = $3 AND status <> 'completed'
+                        RETURNING *
+                        ",
+                        &[&status, &next_run, &id],
+                    )
+                    .await
+                    .context("set_task_status")?;
+                Ok(row.map(|r| row_to_task(&r)))
+            })
+        })
+        .await
+    }
+
     pub async fn log_task_run(&self, log: &TaskRunLog) -> anyhow::Result<()> {
         self.with_client(|client| {
             let log = log.clone();
@@ -765,8 +2123,8 @@ impl PgPool {
                 client
                     .execute(
                         "\
-                        INSERT INTO task_run_logs (task_id, run_at, duration_ms, status, result, error)
-                        VALUES ($1, $2::timestamptz, $3, $4, $5, $6)
+                        INSERT INTO task_run_logs (task_id, run_at, duration_ms, status, result, error, correlation_id)
+                        VALUES ($1, $2::text::timestamptz, $3, $4, $5, $6, $7)
                         ",
                         &[
                             &log.task_id,
@@ -775,6 +2133,7 @@ impl PgPool {
                             &log.status,
                             &log.result,
                             &log.error,
+                            &log.correlation_id,
                         ],
                     )
                     .await
@@ -785,6 +2144,410 @@ impl PgPool {
         .await
     }
 
+    /// A task's runs, newest first, skipping the `offset` most recent.
+    pub async fn get_task_runs(
+        &self,
+        task_id: &str,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<TaskRunLog>> {
+        self.with_client(|client| {
+            let task_id = task_id.to_string();
+            Box::pin(async move {
+                let rows = client
+                    .query(
+                        "\
+                        SELECT task_id, run_at, duration_ms, status, result, error, correlation_id
+                        FROM task_run_logs
+                        WHERE task_id = $1
+                        ORDER BY run_at DESC, id DESC
+                        LIMIT $2 OFFSET $3
+                        ",
+                        &[&task_id, &limit, &offset],
+                    )
+                    .await
+                    .context("get_task_runs")?;
+                Ok(rows
+                    .iter()
+                    .map(|r| TaskRunLog {
+                        task_id: r.get("task_id"),
+                        run_at: format_ts(r.get("run_at")),
+                        duration_ms: i64::from(r.get::<_, i32>("duration_ms")),
+                        status: r.get("status"),
+                        result: r.get("result"),
+                        error: r.get("error"),
+                        correlation_id: r.get("correlation_id"),
+                    })
+                    .collect())
+            })
+        })
+        .await
+    }
+
+    /// Aggregates over all of a task's logged runs.
+    pub async fn get_task_run_stats(&self, task_id: &str) -> anyhow::Result<TaskRunStats> {
+        self.with_client(|client| {
+            let task_id = task_id.to_string();
+            Box::pin(async move {
+                let totals = client
+                    .query_one(
+                        "\
+                        SELECT COUNT(*) AS runs,
+                               COUNT(*) FILTER (WHERE status = 'success') AS successes,
+                               AVG(duration_ms)::float8 AS avg_duration_ms,
+                               MAX(run_at) AS last_run_at
+                        FROM task_run_logs
+                        WHERE task_id = $1
+                        ",
+                        &[&task_id],
+                    )
+                    .await
+                    .context("get_task_run_stats")?;
+                let last_error = client
+                    .query_opt(
+                        "\
+                        SELECT run_at, error
+                        FROM task_run_logs
+                        WHERE task_id = $1 AND status = 'error'
+                        ORDER BY run_at DESC, id DESC
+                        LIMIT 1
+                        ",
+                        &[&task_id],
+                    )
+                    .await
+                    .context("get_task_run_stats last error")?;
+                Ok(TaskRunStats::from_counts(
+                    &task_id,
+                    totals.get("runs"),
+                    totals.get("successes"),
+                    totals.get("avg_duration_ms"),
+                    totals
+                        .get::<_, Option<std::time::SystemTime>>("last_run_at")
+                        .map(format_ts),
+                    last_error.as_ref().map(|r| format_ts(r.get("run_at"))),
+                    last_error.and_then(|r| r.get("error")),
+                ))
+            })
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Task template operations
+    // -----------------------------------------------------------------------
+
+    /// Create the template, or replace the prompt, schedule and settings of
+    /// the one with the same name.
+    pub async fn upsert_task_template(&self, template: &TaskTemplate) -> anyhow::Result<()> {
+        self.with_client(|client| {
+            let template = template.clone();
+            Box::pin(async move {
+                client
+                    .execute(
+                        "\
+                        INSERT INTO task_templates (name, prompt, schedule_type, schedule_value, context_mode, task_config)
+                        VALUES ($1, $2, $3, $4, $5, $6)
+                        ON CONFLICT (name) DO UPDATE SET
+                          prompt = EXCLUDED.prompt,
+                          schedule_type = EXCLUDED.schedule_type,
+                          schedule_value = EXCLUDED.schedule_value,
+                          context_mode = EXCLUDED.context_mode,
+                          task_config = EXCLUDED.task_config,
+                          updated_at = now()
+                        ",
+                        &[
+                            &template.name,
+                            &template.prompt,
+                            &template.schedule_type,
+                            &template.schedule_value,
+                            &template.context_mode,
+                            &template.task_config,
+                        ],
+                    )
+                    .await
+                    .context("upsert_task_template")?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    pub async fn get_task_template(&self, name: &str) -> anyhow::Result<Option<TaskTemplate>> {
+        self.with_client(|client| {
+            let name = name.to_string();
+            Box::pin(async move {
+                let row = client
+                    .query_opt("SELECT * FROM task_templates WHERE name = $1", &[&name])
+                    .await
+                    .context("get_task_template")?;
+                Ok(row.as_ref().map(row_to_task_template))
+            })
+        })
+        .await
+    }
+
+    pub async fn list_task_templates(&self) -> anyhow::Result<Vec<TaskTemplate>> {
+        self.with_client(|client| {
+            Box::pin(async move {
+                let rows = client
+                    .query("SELECT * FROM task_templates ORDER BY name", &[])
+                    .await
+                    .context("list_task_templates")?;
+                Ok(rows.iter().map(row_to_task_template).collect())
+            })
+        })
+        .await
+    }
+
+    /// Delete a template; tasks created from it are kept. `false` when
+    /// there was no such template.
+    pub async fn delete_task_template(&self, name: &str) -> anyhow::Result<bool> {
+        self.with_client(|client| {
+            let name = name.to_string();
+            Box::pin(async move {
+                let deleted = client
+                    .execute("DELETE FROM task_templates WHERE name = $1", &[&name])
+                    .await
+                    .context("delete_task_template")?;
+                Ok(deleted > 0)
+            })
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Container run operations
+    // -----------------------------------------------------------------------
+
+    pub async fn record_container_run(&self, run: &ContainerRun) -> anyhow::Result<()> {
+        self.with_client(|client| {
+            let run = run.clone();
+            Box::pin(async move {
+                client
+                    .execute(
+                        "\
+                        INSERT INTO container_runs (
+                          run_id, group_folder, chat_jid, trigger, started_at, finished_at,
+                          duration_ms, exit_code, timed_out, status, error, model, session_id,
+                          output_bytes, correlation_id, input_tokens, output_tokens,
+                          cache_read_tokens, cache_write_tokens, cost_usd
+                        )
+                        VALUES ($1, $2, $3, $4, $5::text::timestamptz, $6::text::timestamptz,
+                                $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)
+                        ",
+                        &[
+                            &run.run_id,
+                            &run.group_folder,
+                            &run.chat_jid,
+                            &run.trigger,
+                            &run.started_at,
+                            &run.finished_at,
+                            &run.duration_ms,
+                            &run.exit_code,
+                            &run.timed_out,
+                            &run.status,
+                            &run.error,
+                            &run.model,
+                            &run.session_id,
+                            &run.output_bytes,
+                            &run.correlation_id,
+                            &run.input_tokens,
+                            &run.output_tokens,
+                            &run.cache_read_tokens,
+                            &run.cache_write_tokens,
+                            &run.cost_usd,
+                        ],
+                    )
+                    .await
+                    .context("record_container_run")?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Newest runs first, optionally for one group only.
+    pub async fn list_container_runs(
+        &self,
+        group_folder: Option<&str>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<ContainerRun>> {
+        self.with_client(|client| {
+            let group_folder = group_folder.map(str::to_string);
+            Box::pin(async move {
+                let rows = client
+                    .query(
+                        "\
+                        SELECT run_id, group_folder, chat_jid, trigger, started_at, finished_at,
+                               duration_ms, exit_code, timed_out, status, error, model, session_id,
+                               output_bytes, correlation_id, input_tokens, output_tokens,
+                               cache_read_tokens, cache_write_tokens, cost_usd
+                        FROM container_runs
+                        WHERE $1::text IS NULL OR group_folder = $1
+                        ORDER BY started_at DESC, id DESC
+                        LIMIT $2
+                        ",
+                        &[&group_folder, &limit],
+                    )
+                    .await
+                    .context("list_container_runs")?;
+                Ok(rows
+                    .iter()
+                    .map(|r| ContainerRun {
+                        run_id: r.get("run_id"),
+                        group_folder: r.get("group_folder"),
+                        chat_jid: r.get("chat_jid"),
+                        trigger: r.get("trigger"),
+                        started_at: format_ts(r.get("started_at")),
+                        finished_at: format_ts(r.get("finished_at")),
+                        duration_ms: r.get("duration_ms"),
+                        exit_code: r.get("exit_code"),
+                        timed_out: r.get("timed_out"),
+                        status: r.get("status"),
+                        error: r.get("error"),
+                        model: r.get("model"),
+                        session_id: r.get("session_id"),
+                        output_bytes: r.get("output_bytes"),
+                        correlation_id: r.get("correlation_id"),
+                        input_tokens: r.get("input_tokens"),
+                        output_tokens: r.get("output_tokens"),
+                        cache_read_tokens: r.get("cache_read_tokens"),
+                        cache_write_tokens: r.get("cache_write_tokens"),
+                        cost_usd: r.get("cost_usd"),
+                    })
+                    .collect())
+            })
+        })
+        .await
+    }
+
+    /// Runs, tokens and cost since `since` (RFC 3339), optionally for one
+    /// group only.
+    pub async fn usage_totals(
+        &self,
+        group_folder: Option<&str>,
+        since: &str,
+    ) -> anyhow::Result<UsageTotals> {
+        self.with_client(|client| {
+            let group_folder = group_folder.map(str::to_string);
+            let since = since.to_string();
+            Box::pin(async move {
+                let r = client
+                    .query_one(
+                        "\
+                        SELECT COUNT(*)::bigint AS runs,
+                               COALESCE(SUM(input_tokens), 0)::bigint AS input_tokens,
+                               COALESCE(SUM(output_tokens), 0)::bigint AS output_tokens,
+                               COALESCE(SUM(cache_read_tokens), 0)::bigint AS cache_read_tokens,
+                               COALESCE(SUM(cache_write_tokens), 0)::bigint AS cache_write_tokens,
+                               COALESCE(SUM(cost_usd), 0)::double precision AS cost_usd,
+                               COUNT(*) FILTER (WHERE cost_usd IS NULL)::bigint AS unpriced_runs,
+                               COUNT(*) FILTER (WHERE status = 'success')::bigint AS succeeded_runs,
+                               COALESCE(SUM(duration_ms), 0)::bigint AS duration_ms
+                        FROM container_runs
+                        WHERE ($1::text IS NULL OR group_folder = $1)
+                          AND started_at >= $2::text::timestamptz
+                        ",
+                        &[&group_folder, &since],
+                    )
+                    .await
+                    .context("usage_totals")?;
+                Ok(UsageTotals {
+                    runs: r.get("runs"),
+                    input_tokens: r.get("input_tokens"),
+                    output_tokens: r.get("output_tokens"),
+                    cache_read_tokens: r.get("cache_read_tokens"),
+                    cache_write_tokens: r.get("cache_write_tokens"),
+                    cost_usd: r.get("cost_usd"),
+                    unpriced_runs: r.get("unpriced_runs"),
+                    succeeded_runs: r.get("succeeded_runs"),
+                    duration_ms: r.get("duration_ms"),
+                })
+            })
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Demarch audit operations
+    // -----------------------------------------------------------------------
+
+    pub async fn record_demarch_write(&self, entry: &DemarchAuditEntry) -> anyhow::Result<()> {
+        self.with_client(|client| {
+            let entry = entry.clone();
+            Box::pin(async move {
+                client
+                    .execute(
+                        "\
+                        INSERT INTO demarch_audit (operation, params, source_group, is_main, status, result)
+                        VALUES ($1, $2, $3, $4, $5, $6)
+                        ",
+                        &[
+                            &entry.operation,
+                            &entry.params,
+                            &entry.source_group,
+                            &entry.is_main,
+                            &entry.status,
+                            &entry.result,
+                        ],
+                    )
+                    .await
+                    .context("record_demarch_write")?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Newest entries first.
+    pub async fn list_demarch_audit(
+        &self,
+        filter: &DemarchAuditFilter,
+    ) -> anyhow::Result<Vec<DemarchAuditEntry>> {
+        self.with_client(|client| {
+            let filter = filter.clone();
+            Box::pin(async move {
+                let rows = client
+                    .query(
+                        "\
+                        SELECT created_at, operation, params, source_group, is_main, status, result
+                        FROM demarch_audit
+                        WHERE ($1::text IS NULL OR source_group = $1)
+                          AND ($2::text IS NULL OR operation = $2)
+                          AND ($3::text IS NULL OR status = $3)
+                          AND ($4::text IS NULL OR created_at >= $4::text::timestamptz)
+                          AND ($5::text IS NULL OR created_at < $5::text::timestamptz)
+                        ORDER BY created_at DESC, id DESC
+                        LIMIT $6
+                        ",
+                        &[
+                            &filter.source_group,
+                            &filter.operation,
+                            &filter.status,
+                            &filter.since,
+                            &filter.until,
+                            &filter.limit,
+                        ],
+                    )
+                    .await
+                    .context("list_demarch_audit")?;
+                Ok(rows
+                    .iter()
+                    .map(|r| DemarchAuditEntry {
+                        created_at: format_ts(r.get("created_at")),
+                        operation: r.get("operation"),
+                        params: r.get("params"),
+                        source_group: r.get("source_group"),
+                        is_main: r.get("is_main"),
+                        status: r.get("status"),
+                        result: r.get("result"),
+                    })
+                    .collect())
+            })
+        })
+        .await
+    }
+
     // -----------------------------------------------------------------------
     // Router state operations
     // -----------------------------------------------------------------------
@@ -937,8 +2700,8 @@ impl PgPool {
                     .execute(
                         "\
                         INSERT INTO registered_groups
-                          (jid, name, folder, trigger_pattern, added_at, container_config, requires_trigger, runtime, model)
-                        VALUES ($1, $2, $3, $4, $5::timestamptz, $6, $7, $8, $9)
+                          (jid, name, folder, trigger_pattern, added_at, container_config, requires_trigger, runtime, model, muted_until, locale)
+                        VALUES ($1, $2, $3, $4, $5::text::timestamptz, $6, $7, $8, $9, $10::text::timestamptz, $11)
                         ON CONFLICT (jid) DO UPDATE SET
                           name = EXCLUDED.name,
                           folder = EXCLUDED.folder,
@@ -946,7 +2709,9 @@ impl PgPool {
                           container_config = EXCLUDED.container_config,
                           requires_trigger = EXCLUDED.requires_trigger,
                           runtime = EXCLUDED.runtime,
-                          model = EXCLUDED.model
+                          model = EXCLUDED.model,
+                          muted_until = EXCLUDED.muted_until,
+                          locale = EXCLUDED.locale
                         ",
                         &[
                             &group.jid,
@@ -958,6 +2723,8 @@ impl PgPool {
                             &requires_trigger,
                             &group.runtime,
                             &group.model,
+                            &group.muted_until,
+                            &group.locale,
                         ],
                     )
                     .await
@@ -985,6 +2752,20 @@ impl PgPool {
         })
         .await
     }
+
+    pub async fn delete_registered_group(&self, jid: &str) -> anyhow::Result<bool> {
+        self.with_client(|client| {
+            let jid = jid.to_string();
+            Box::pin(async move {
+                let deleted = client
+                    .execute("DELETE FROM registered_groups WHERE jid = $1", &[&jid])
+                    .await
+                    .context("delete_registered_group")?;
+                Ok(deleted > 0)
+            })
+        })
+        .await
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -1000,8 +2781,7 @@ fn chrono_now() -> String {
     let secs = now.as_secs();
     let millis = now.as_millis() % 1000;
     // Use a simple formatter — no chrono dependency needed
-    let dt = time_from_epoch(secs, millis as u32);
-    dt
+    time_from_epoch(secs, millis as u32)
 }
 
 fn time_from_epoch(secs: u64, millis: u32) -> String {
@@ -1037,6 +2817,37 @@ fn days_to_date(days: u64) -> (u64, u64, u64) {
     (y, m, d)
 }
 
+/// Quote a Postgres identifier (LISTEN does not accept bind parameters).
+pub(crate) fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Quote a Postgres string literal, for DDL that cannot take parameters.
+pub(crate) fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// SQL expression for a cursor parameter compared against `messages.timestamp`.
+///
+/// An empty cursor means "from the beginning", and a cursor ahead of the
+/// server clock (written by a skewed host) is clamped to `now()`, in
+/// milliseconds like the stored clamps, so it cannot hide messages that
+/// arrive before the clocks converge.
+fn cursor_bound(param: &str) -> String {
+    format!(
+        "LEAST(COALESCE(NULLIF({param}, '')::timestamptz, '-infinity'::timestamptz), date_trunc('milliseconds', now()))"
+    )
+}
+
+/// Keeps rows with text or at least one attachment (a captionless photo
+/// is still something the agent should see).
+pub(crate) const HAS_BODY: &str = "(COALESCE(content, '') != '' OR EXISTS (\
+    SELECT 1 FROM attachments a WHERE a.message_id = messages.id AND a.chat_jid = messages.chat_jid))";
+
+pub(crate) fn skew_exceeds(skew_ms: f64, threshold_ms: i64) -> bool {
+    threshold_ms > 0 && skew_ms.abs() > threshold_ms as f64
+}
+
 /// Format a Postgres TIMESTAMPTZ value as ISO 8601 string.
 /// tokio-postgres returns TIMESTAMPTZ as `SystemTime` when no chrono feature.
 fn format_ts(ts: std::time::SystemTime) -> String {
@@ -1046,6 +2857,67 @@ fn format_ts(ts: std::time::SystemTime) -> String {
     time_from_epoch(dur.as_secs(), (dur.as_millis() % 1000) as u32)
 }
 
+async fn insert_attachment(client: &Client, attachment: &Attachment) -> anyhow::Result<()> {
+    client
+        .execute(
+            "\
+            INSERT INTO attachments
+              (message_id, chat_jid, kind, mime_type, file_name, file_path, caption, size_bytes)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ",
+            &[
+                &attachment.message_id,
+                &attachment.chat_jid,
+                &attachment.kind,
+                &attachment.mime_type,
+                &attachment.file_name,
+                &attachment.file_path,
+                &attachment.caption,
+                &attachment.size_bytes,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Fill `attachments` on each message with one extra round-trip.
+async fn load_attachments(client: &Client, messages: &mut [NewMessage]) -> anyhow::Result<()> {
+    if messages.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<String> = messages.iter().map(|m| m.id.clone()).collect();
+    let jids: Vec<String> = messages.iter().map(|m| m.chat_jid.clone()).collect();
+    let rows = client
+        .query(
+            "SELECT * FROM attachments WHERE message_id = ANY($1) AND chat_jid = ANY($2) ORDER BY id",
+            &[&ids, &jids],
+        )
+        .await
+        .context("load_attachments")?;
+    for attachment in rows.iter().map(row_to_attachment) {
+        if let Some(msg) = messages
+            .iter_mut()
+            .find(|m| m.id == attachment.message_id && m.chat_jid == attachment.chat_jid)
+        {
+            msg.attachments.push(attachment);
+        }
+    }
+    Ok(())
+}
+
+fn row_to_attachment(r: &tokio_postgres::Row) -> Attachment {
+    Attachment {
+        message_id: r.get("message_id"),
+        chat_jid: r.get("chat_jid"),
+        kind: r.get("kind"),
+        mime_type: r.get("mime_type"),
+        file_name: r.get("file_name"),
+        file_path: r.get("file_path"),
+        caption: r.get("caption"),
+        size_bytes: r.get("size_bytes"),
+    }
+}
+
 fn row_to_task(r: &tokio_postgres::Row) -> ScheduledTask {
     ScheduledTask {
         id: r.get("id"),
@@ -1064,6 +2936,20 @@ fn row_to_task(r: &tokio_postgres::Row) -> ScheduledTask {
             .get::<_, Option<String>>("status")
             .unwrap_or_else(|| "active".to_string()),
         created_at: format_ts(r.get("created_at")),
+        task_config: r.get("task_config"),
+    }
+}
+
+fn row_to_task_template(r: &tokio_postgres::Row) -> TaskTemplate {
+    TaskTemplate {
+        name: r.get("name"),
+        prompt: r.get("prompt"),
+        schedule_type: r.get("schedule_type"),
+        schedule_value: r.get("schedule_value"),
+        context_mode: r.get("context_mode"),
+        task_config: r.get("task_config"),
+        created_at: format_ts(r.get("created_at")),
+        updated_at: format_ts(r.get("updated_at")),
     }
 }
 
@@ -1078,6 +2964,10 @@ fn row_to_registered_group(r: &tokio_postgres::Row) -> RegisteredGroup {
         requires_trigger: r.get::<_, Option<bool>>("requires_trigger"),
         runtime: r.get("runtime"),
         model: r.get("model"),
+        muted_until: r
+            .get::<_, Option<std::time::SystemTime>>("muted_until")
+            .map(format_ts),
+        locale: r.get("locale"),
     }
 }
 
@@ -1105,6 +2995,182 @@ mod tests {
         assert_eq!(ts, "2024-01-15T12:30:45.123Z");
     }
 
+    /// DSN for a fresh, empty `schema` in the database named by
+    /// `INTERCOM_TEST_POSTGRES_DSN` (key-value form), or `None` to skip the
+    /// test when it is unset.
+    async fn test_dsn(schema: &str) -> Option<String> {
+        let dsn = std::env::var("INTERCOM_TEST_POSTGRES_DSN").ok()?;
+        let admin = connect_postgres(&dsn).await.unwrap();
+        admin
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}",
+                quote_ident(schema)
+            ))
+            .await
+            .unwrap();
+        Some(format!("{dsn} options='-csearch_path={schema}'"))
+    }
+
+    /// A connected pool on a fresh `schema`; see [`test_dsn`].
+    async fn test_pool(schema: &str) -> Option<PgPool> {
+        let pool = PgPool::new(test_dsn(schema).await?);
+        pool.connect().await.unwrap();
+        Some(pool)
+    }
+
+    #[tokio::test]
+    async fn ensure_schema_migrates_the_previous_version() {
+        let Some(dsn) = test_dsn("intercom_test_migrate").await else {
+            return;
+        };
+        let client = connect_postgres(&dsn).await.unwrap();
+        ensure_schema(&client).await.unwrap();
+        // Roll back to version 8, from before per-group locales.
+        client
+            .batch_execute(
+                "ALTER TABLE registered_groups DROP COLUMN locale; \
+                 DELETE FROM intercom_schema_version; \
+                 INSERT INTO intercom_schema_version (version) VALUES (8)",
+            )
+            .await
+            .unwrap();
+        assert_eq!(installed_schema_version(&client).await.unwrap(), 8);
+
+        ensure_schema(&client).await.unwrap();
+        assert_eq!(
+            installed_schema_version(&client).await.unwrap(),
+            SCHEMA_VERSION
+        );
+        let locale = client
+            .query_opt(
+                "SELECT 1 FROM information_schema.columns \
+                 WHERE table_schema = current_schema() \
+                   AND table_name = 'registered_groups' AND column_name = 'locale'",
+                &[],
+            )
+            .await
+            .unwrap();
+        assert!(locale.is_some(), "locale column was not added");
+    }
+
+    fn message(id: &str, timestamp: &str) -> NewMessage {
+        NewMessage {
+            id: id.into(),
+            chat_jid: "tg:1".into(),
+            sender: "42".into(),
+            sender_name: "Ada".into(),
+            content: "hello".into(),
+            timestamp: timestamp.into(),
+            is_from_me: false,
+            is_bot_message: false,
+            edited: false,
+            attachments: Vec::new(),
+            correlation_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn future_message_is_delivered_once() {
+        let Some(pool) = test_pool("intercom_test_future_message").await else {
+            return;
+        };
+        pool.store_message(&message("1", "2999-01-01T00:00:00.000Z"))
+            .await
+            .unwrap();
+
+        let jids = ["tg:1".to_string()];
+        let (first, cursor) = pool.get_new_messages(&jids, "", "Amtiskaw").await.unwrap();
+        assert_eq!(first.len(), 1);
+        let (second, _) = pool
+            .get_new_messages(&jids, &cursor, "Amtiskaw")
+            .await
+            .unwrap();
+        assert!(second.is_empty(), "redelivered after cursor {cursor}");
+    }
+
+    #[tokio::test]
+    async fn store_message_replaces_attachments_in_the_same_statement() {
+        let Some(pool) = test_pool("intercom_test_attachments").await else {
+            return;
+        };
+        let attachment = |kind: &str, file_path: &str| Attachment {
+            kind: kind.into(),
+            file_path: Some(file_path.into()),
+            size_bytes: Some(42),
+            ..Default::default()
+        };
+        let mut msg = message("1", "2024-01-15T12:00:00.000Z");
+        msg.attachments = vec![
+            attachment("photo", "media/1.jpg"),
+            attachment("document", "media/1.pdf"),
+        ];
+        pool.store_message(&msg).await.unwrap();
+        let stored = pool.get_attachments("1", "tg:1").await.unwrap();
+        let paths: Vec<_> = stored.iter().map(|a| a.file_path.as_deref()).collect();
+        assert_eq!(paths, [Some("media/1.jpg"), Some("media/1.pdf")]);
+        assert_eq!(stored[0].size_bytes, Some(42));
+        assert_eq!(stored[0].mime_type, None);
+
+        // Re-storing without attachments keeps them; with some replaces them.
+        pool.store_message(&message("1", "2024-01-15T12:00:00.000Z"))
+            .await
+            .unwrap();
+        assert_eq!(pool.get_attachments("1", "tg:1").await.unwrap().len(), 2);
+        msg.attachments = vec![attachment("voice", "media/1.ogg")];
+        pool.store_message(&msg).await.unwrap();
+        let stored = pool.get_attachments("1", "tg:1").await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].kind, "voice");
+    }
+
+    #[tokio::test]
+    async fn store_message_notifies_on_new_message_channel() {
+        let Some(pool) = test_pool("intercom_test_notify").await else {
+            return;
+        };
+        let mut rx = pool.listen(NEW_MESSAGE_CHANNEL).await.unwrap();
+        pool.store_message(&message("1", "2024-01-15T12:00:00.000Z"))
+            .await
+            .unwrap();
+        let jid = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap();
+        assert_eq!(jid.as_deref(), Some("tg:1"));
+    }
+
+    #[test]
+    fn quote_ident_escapes_quotes() {
+        assert_eq!(quote_ident(NEW_MESSAGE_CHANNEL), "\"intercom_new_message\"");
+        assert_eq!(quote_ident("a\"b"), "\"a\"\"b\"");
+    }
+
+    #[test]
+    fn skew_threshold() {
+        assert!(!skew_exceeds(4_999.0, 5_000));
+        assert!(skew_exceeds(5_001.0, 5_000));
+        assert!(skew_exceeds(-60_000.0, 5_000));
+        // Zero disables the warning entirely.
+        assert!(!skew_exceeds(1e9, 0));
+    }
+
+    #[test]
+    fn attachment_describe() {
+        let photo = Attachment {
+            kind: "photo".into(),
+            mime_type: Some("image/jpeg".into()),
+            file_path: Some("media/1.jpg".into()),
+            caption: Some("sunset".into()),
+            ..Default::default()
+        };
+        assert_eq!(photo.describe(), "[photo image/jpeg media/1.jpg: sunset]");
+        let bare = Attachment {
+            kind: "voice".into(),
+            caption: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(bare.describe(), "[voice]");
+    }
+
     #[test]
     fn days_to_date_epoch() {
         let (y, m, d) = days_to_date(0);
@@ -1139,6 +3205,8 @@ mod tests {
             requires_trigger: Some(true),
             runtime: Some("claude".to_string()),
             model: None,
+            muted_until: None,
+            locale: None,
         };
         let json = serde_json::to_string(&group).unwrap();
         let parsed: RegisteredGroup = serde_json::from_str(&json).unwrap();
@@ -0,0 +1,241 @@
+//! Stdin/stdout protocol adapters for runner images.
+//!
+//! The bundled runners speak `marker-json`: a `ContainerInput` JSON document
+//! on stdin and `ContainerOutput` JSON wrapped in OUTPUT markers on stdout.
+//! Runtime profiles can pick a different framing so third-party or minimal
+//! images work without reimplementing the markers:
+//!
+//! - `marker-json`: the default, described above
+//! - `jsonl-stream`: one JSON object per stdout line — either a full
+//!   `ContainerOutput` or a bare `StreamEvent`; the input is a single JSON line
+//! - `plain-text`: the prompt is written to stdin as-is and the whole of
+//!   stdout becomes the result when the process exits. No secrets are sent.
+
+use serde::{Deserialize, Serialize};
+
+use crate::container::{
+    ContainerInput, ContainerOutput, ContainerStatus, OUTPUT_START_MARKER, StreamEvent,
+    extract_output_markers,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuntimeProtocol {
+    #[default]
+    MarkerJson,
+    JsonlStream,
+    PlainText,
+}
+
+impl RuntimeProtocol {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MarkerJson => "marker-json",
+            Self::JsonlStream => "jsonl-stream",
+            Self::PlainText => "plain-text",
+        }
+    }
+
+    /// Serialize the container input for this protocol's stdin.
+    pub fn encode_input(self, input: &ContainerInput) -> serde_json::Result<String> {
+        match self {
+            Self::MarkerJson => serde_json::to_string(input),
+            Self::JsonlStream => serde_json::to_string(input).map(|json| json + "\n"),
+            Self::PlainText => Ok(input.prompt.clone()),
+        }
+    }
+}
+
+/// Incremental stdout decoder. Feed it raw stdout as it arrives, then call
+/// `finish` at EOF to flush anything the protocol only emits on exit.
+#[derive(Debug)]
+pub struct OutputDecoder {
+    protocol: RuntimeProtocol,
+    buf: String,
+}
+
+impl OutputDecoder {
+    pub fn new(protocol: RuntimeProtocol) -> Self {
+        Self {
+            protocol,
+            buf: String::new(),
+        }
+    }
+
+    pub fn protocol(&self) -> RuntimeProtocol {
+        self.protocol
+    }
+
+    /// Append stdout data and return every output that is now complete.
+    pub fn push(&mut self, data: &str) -> Vec<serde_json::Result<ContainerOutput>> {
+        self.buf.push_str(data);
+        match self.protocol {
+            RuntimeProtocol::MarkerJson => {
+                let (results, consumed) = extract_output_markers(&self.buf);
+                if consumed > 0 {
+                    self.buf.drain(..consumed);
+                }
+                // Noise between marker pairs is never needed again.
+                if !self.buf.contains(OUTPUT_START_MARKER) {
+                    self.buf.clear();
+                }
+                results
+                    .iter()
+                    .map(|json| serde_json::from_str::<ContainerOutput>(json))
+                    .collect()
+            }
+            RuntimeProtocol::JsonlStream => {
+                let Some(last_newline) = self.buf.rfind('\n') else {
+                    return Vec::new();
+                };
+                let complete: String = self.buf.drain(..=last_newline).collect();
+                complete.lines().filter_map(decode_jsonl_line).collect()
+            }
+            // Everything is buffered until exit.
+            RuntimeProtocol::PlainText => Vec::new(),
+        }
+    }
+
+    /// Flush remaining output at EOF.
+    pub fn finish(&mut self) -> Vec<serde_json::Result<ContainerOutput>> {
+        let rest = std::mem::take(&mut self.buf);
+        match self.protocol {
+            RuntimeProtocol::MarkerJson => Vec::new(),
+            RuntimeProtocol::JsonlStream => rest.lines().filter_map(decode_jsonl_line).collect(),
+            RuntimeProtocol::PlainText => {
+                let text = rest.trim();
+                vec![Ok(ContainerOutput {
+                    status: ContainerStatus::Success,
+                    result: (!text.is_empty()).then(|| text.to_string()),
+                    new_session_id: None,
+                    error: None,
+                    model: None,
+                    event: None,
+                    usage: None,
+                })]
+            }
+        }
+    }
+}
+
+/// A JSONL line is either a full output or a bare stream event. Blank lines
+/// are skipped.
+fn decode_jsonl_line(line: &str) -> Option<serde_json::Result<ContainerOutput>> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    Some(match serde_json::from_str::<ContainerOutput>(line) {
+        Ok(output) => Ok(output),
+        Err(err) => match serde_json::from_str::<StreamEvent>(line) {
+            Ok(event) => Ok(ContainerOutput {
+                status: ContainerStatus::Success,
+                result: None,
+                new_session_id: None,
+                error: None,
+                model: None,
+                event: Some(event),
+                usage: None,
+            }),
+            Err(_) => Err(err),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::OUTPUT_END_MARKER;
+
+    fn input() -> ContainerInput {
+        ContainerInput {
+            prompt: "hello".into(),
+            session_id: None,
+            group_folder: "main".into(),
+            chat_jid: "tg:1".into(),
+            is_main: true,
+            is_scheduled_task: None,
+            assistant_name: None,
+            model: None,
+            secrets: Some([("TOKEN".to_string(), "s3cret".to_string())].into()),
+            attachments: Vec::new(),
+            correlation_id: None,
+            reply_to_message_id: None,
+            stdin_keepalive: None,
+        }
+    }
+
+    fn results(decoded: Vec<serde_json::Result<ContainerOutput>>) -> Vec<Option<String>> {
+        decoded.into_iter().map(|r| r.unwrap().result).collect()
+    }
+
+    #[test]
+    fn protocol_names_parse_from_kebab_case() {
+        let parsed: RuntimeProtocol = serde_json::from_str("\"jsonl-stream\"").unwrap();
+        assert_eq!(parsed, RuntimeProtocol::JsonlStream);
+        assert_eq!(RuntimeProtocol::default().as_str(), "marker-json");
+    }
+
+    #[test]
+    fn plain_text_input_is_prompt_only() {
+        assert_eq!(
+            RuntimeProtocol::PlainText.encode_input(&input()).unwrap(),
+            "hello"
+        );
+        let jsonl = RuntimeProtocol::JsonlStream.encode_input(&input()).unwrap();
+        assert!(jsonl.ends_with('\n'));
+        assert!(jsonl.contains("\"chatJid\""));
+    }
+
+    #[test]
+    fn marker_json_decodes_across_chunks() {
+        let mut decoder = OutputDecoder::new(RuntimeProtocol::MarkerJson);
+        assert!(decoder.push("noise\n").is_empty());
+        assert!(decoder.push(&format!("{OUTPUT_START_MARKER}\n")).is_empty());
+        let out = decoder.push(&format!(
+            "{{\"status\":\"success\",\"result\":\"hi\"}}\n{OUTPUT_END_MARKER}\n"
+        ));
+        assert_eq!(results(out), [Some("hi".to_string())]);
+        assert!(decoder.finish().is_empty());
+    }
+
+    #[test]
+    fn jsonl_decodes_outputs_and_events_per_line() {
+        let mut decoder = OutputDecoder::new(RuntimeProtocol::JsonlStream);
+        let out = decoder.push(
+            "{\"type\":\"text_delta\",\"text\":\"He\"}\n{\"status\":\"success\",\"result\":\"Hello\"}\n{\"status\":",
+        );
+        assert_eq!(out.len(), 2);
+        let first = out[0].as_ref().unwrap();
+        assert!(matches!(first.event, Some(StreamEvent::TextDelta { .. })));
+        assert_eq!(out[1].as_ref().unwrap().result.as_deref(), Some("Hello"));
+
+        // The partial trailing line is completed by the next chunk.
+        let out = decoder.push("\"error\",\"result\":null,\"error\":\"boom\"}");
+        assert!(out.is_empty());
+        let out = decoder.finish();
+        assert_eq!(out[0].as_ref().unwrap().status, ContainerStatus::Error);
+    }
+
+    #[test]
+    fn jsonl_reports_garbage_lines() {
+        let mut decoder = OutputDecoder::new(RuntimeProtocol::JsonlStream);
+        let out = decoder.push("not json\n\n");
+        assert_eq!(out.len(), 1);
+        assert!(out[0].is_err());
+    }
+
+    #[test]
+    fn plain_text_emits_result_on_finish() {
+        let mut decoder = OutputDecoder::new(RuntimeProtocol::PlainText);
+        assert!(decoder.push("Hello\n").is_empty());
+        assert!(decoder.push("world\n").is_empty());
+        assert_eq!(
+            results(decoder.finish()),
+            [Some("Hello\nworld".to_string())]
+        );
+
+        let mut empty = OutputDecoder::new(RuntimeProtocol::PlainText);
+        assert_eq!(results(empty.finish()), [None]);
+    }
+}
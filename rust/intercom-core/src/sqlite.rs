@@ -0,0 +1,3135 @@
+//! SQLite implementation of [`Storage`] for single-host deployments that
+//! don't want to run Postgres.
+//!
+//! Timestamps are stored as normalized ISO 8601 text
+//! (`YYYY-MM-DDTHH:MM:SS.mmmZ`), which sorts the same lexically and
+//! chronologically, so cursor comparisons stay plain string comparisons.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, anyhow};
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{Connection, OptionalExtension, Row, params, params_from_iter};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::circuit::CircuitSnapshot;
+use crate::persistence::{
+    Attachment, ChatInfo, ChatStats, ContainerRun, ConversationMessage, CorrelationTrace,
+    DEFAULT_CLOCK_SKEW_WARN_MS, DemarchAuditEntry, DemarchAuditFilter, HAS_BODY, MessageEdit,
+    MessageFilter, NEW_MESSAGE_CHANNEL, NewMessage, QueryResult, RegisteredGroup, ScheduledTask,
+    SenderStats, TaskFilter, TaskRunLog, TaskRunStats, TaskTemplate, TaskUpdate, UsageTotals,
+    skew_exceeds,
+};
+use crate::storage::{Storage, StorageFuture};
+
+/// Current time in the stored timestamp format.
+const NOW: &str = "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')";
+
+type Listeners = Arc<Mutex<Vec<(String, mpsc::UnboundedSender<String>)>>>;
+
+/// A single SQLite connection shared behind a mutex. Queries run on the
+/// blocking thread pool; the file is opened lazily on first use.
+#[derive(Clone)]
+pub struct SqliteStore {
+    path: PathBuf,
+    conn: Arc<Mutex<Option<Connection>>>,
+    listeners: Listeners,
+    clock_skew_warn_ms: i64,
+}
+
+impl SqliteStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            conn: Arc::new(Mutex::new(None)),
+            listeners: Arc::new(Mutex::new(Vec::new())),
+            clock_skew_warn_ms: DEFAULT_CLOCK_SKEW_WARN_MS,
+        }
+    }
+
+    /// Override the skew threshold used when storing client-stamped messages.
+    pub fn with_clock_skew_warn_ms(mut self, ms: i64) -> Self {
+        self.clock_skew_warn_ms = ms;
+        self
+    }
+
+    /// Run `f` against the connection on the blocking pool, opening the
+    /// database first if needed.
+    async fn with_conn<F, T>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: FnOnce(&mut Connection) -> anyhow::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut guard = conn
+                .lock()
+                .map_err(|_| anyhow!("sqlite connection lock poisoned"))?;
+            if guard.is_none() {
+                *guard = Some(open_sqlite(&path)?);
+            }
+            f(guard.as_mut().unwrap())
+        })
+        .await
+        .context("sqlite worker panicked")?
+    }
+
+    /// Wake every listener subscribed to `channel`, dropping closed ones.
+    fn notify(&self, channel: &str, payload: &str) {
+        if let Ok(mut listeners) = self.listeners.lock() {
+            listeners.retain(|(name, tx)| name != channel || tx.send(payload.to_string()).is_ok());
+        }
+    }
+}
+
+fn open_sqlite(path: &PathBuf) -> anyhow::Result<Connection> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let conn = Connection::open(path)
+        .with_context(|| format!("failed to open sqlite database {}", path.display()))?;
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;")
+        .context("failed to configure sqlite")?;
+    ensure_schema(&conn)?;
+    Ok(conn)
+}
+
+/// SQL expression normalizing a bound timestamp to the stored format.
+/// Yields NULL for empty or unparseable input.
+fn iso(param: &str) -> String {
+    format!("strftime('%Y-%m-%dT%H:%M:%fZ', {param})")
+}
+
+// ---------------------------------------------------------------------------
+// Schema — mirrors the Postgres live tables
+// ---------------------------------------------------------------------------
+
+fn ensure_schema(conn: &Connection) -> anyhow::Result<()> {
+    conn.execute_batch(
+        "\
+        CREATE TABLE IF NOT EXISTS chats (
+          jid TEXT PRIMARY KEY,
+          name TEXT,
+          last_message_time TEXT,
+          channel TEXT,
+          is_group INTEGER DEFAULT 0
+        );
+
+        CREATE TABLE IF NOT EXISTS messages (
+          id TEXT NOT NULL,
+          chat_jid TEXT NOT NULL,
+          sender TEXT,
+          sender_name TEXT,
+          content TEXT,
+          timestamp TEXT NOT NULL,
+          is_from_me INTEGER DEFAULT 0,
+          is_bot_message INTEGER DEFAULT 0,
+          edited_at TEXT,
+          deleted_at TEXT,
+          correlation_id TEXT,
+          PRIMARY KEY (id, chat_jid)
+        );
+        CREATE INDEX IF NOT EXISTS idx_messages_timestamp ON messages(timestamp);
+
+        CREATE TABLE IF NOT EXISTS message_edits (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          message_id TEXT NOT NULL,
+          chat_jid TEXT NOT NULL,
+          previous_content TEXT,
+          edited_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_message_edits_message ON message_edits(message_id, chat_jid);
+
+        CREATE TABLE IF NOT EXISTS attachments (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          message_id TEXT NOT NULL,
+          chat_jid TEXT NOT NULL,
+          kind TEXT NOT NULL,
+          mime_type TEXT,
+          file_name TEXT,
+          file_path TEXT,
+          caption TEXT,
+          size_bytes INTEGER,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_attachments_message ON attachments(message_id, chat_jid);
+
+        CREATE TABLE IF NOT EXISTS scheduled_tasks (
+          id TEXT PRIMARY KEY,
+          group_folder TEXT NOT NULL,
+          chat_jid TEXT NOT NULL,
+          prompt TEXT NOT NULL,
+          schedule_type TEXT NOT NULL,
+          schedule_value TEXT NOT NULL,
+          context_mode TEXT DEFAULT 'isolated',
+          next_run TEXT,
+          last_run TEXT,
+          last_result TEXT,
+          status TEXT DEFAULT 'active',
+          created_at TEXT NOT NULL,
+          task_config TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_tasks_next_run ON scheduled_tasks(next_run);
+        CREATE INDEX IF NOT EXISTS idx_tasks_status ON scheduled_tasks(status);
+
+        CREATE TABLE IF NOT EXISTS task_run_logs (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          task_id TEXT NOT NULL REFERENCES scheduled_tasks(id) ON DELETE CASCADE,
+          run_at TEXT NOT NULL,
+          duration_ms INTEGER NOT NULL,
+          status TEXT NOT NULL,
+          result TEXT,
+          error TEXT,
+          correlation_id TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_task_run_logs_task ON task_run_logs(task_id, run_at);
+
+        CREATE TABLE IF NOT EXISTS task_templates (
+          name TEXT PRIMARY KEY,
+          prompt TEXT NOT NULL,
+          schedule_type TEXT NOT NULL,
+          schedule_value TEXT NOT NULL,
+          context_mode TEXT NOT NULL DEFAULT 'isolated',
+          task_config TEXT,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+          updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+        );
+
+        CREATE TABLE IF NOT EXISTS container_runs (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          run_id TEXT,
+          group_folder TEXT NOT NULL,
+          chat_jid TEXT NOT NULL,
+          trigger TEXT NOT NULL,
+          started_at TEXT NOT NULL,
+          finished_at TEXT NOT NULL,
+          duration_ms INTEGER NOT NULL,
+          exit_code INTEGER,
+          timed_out INTEGER NOT NULL DEFAULT 0,
+          status TEXT NOT NULL,
+          error TEXT,
+          model TEXT,
+          session_id TEXT,
+          output_bytes INTEGER NOT NULL DEFAULT 0,
+          correlation_id TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_container_runs_group ON container_runs(group_folder, started_at);
+        CREATE INDEX IF NOT EXISTS idx_container_runs_started ON container_runs(started_at);
+
+        CREATE TABLE IF NOT EXISTS demarch_audit (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+          operation TEXT NOT NULL,
+          params TEXT NOT NULL,
+          source_group TEXT,
+          is_main INTEGER NOT NULL DEFAULT 0,
+          status TEXT NOT NULL,
+          result TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_demarch_audit_created ON demarch_audit(created_at);
+
+        CREATE TABLE IF NOT EXISTS router_state (
+          key TEXT PRIMARY KEY,
+          value TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS sessions (
+          group_folder TEXT PRIMARY KEY,
+          session_id TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS registered_groups (
+          jid TEXT PRIMARY KEY,
+          name TEXT NOT NULL,
+          folder TEXT NOT NULL UNIQUE,
+          trigger_pattern TEXT NOT NULL,
+          added_at TEXT NOT NULL,
+          container_config TEXT,
+          requires_trigger INTEGER DEFAULT 1,
+          runtime TEXT,
+          model TEXT
+        );
+        ",
+    )
+    .context("failed to create sqlite schema")?;
+
+    // Columns added after the first release; CREATE TABLE above only covers
+    // new databases.
+    add_column_if_missing(conn, "messages", "correlation_id", "TEXT")?;
+    add_column_if_missing(conn, "task_run_logs", "correlation_id", "TEXT")?;
+    add_column_if_missing(conn, "scheduled_tasks", "task_config", "TEXT")?;
+    for column in [
+        "input_tokens",
+        "output_tokens",
+        "cache_read_tokens",
+        "cache_write_tokens",
+    ] {
+        add_column_if_missing(conn, "container_runs", column, "INTEGER NOT NULL DEFAULT 0")?;
+    }
+    add_column_if_missing(conn, "container_runs", "cost_usd", "REAL")?;
+    add_column_if_missing(conn, "registered_groups", "muted_until", "TEXT")?;
+    add_column_if_missing(conn, "registered_groups", "locale", "TEXT")?;
+    conn.execute_batch(
+        "\
+        CREATE INDEX IF NOT EXISTS idx_messages_correlation ON messages(correlation_id);
+        CREATE INDEX IF NOT EXISTS idx_task_run_logs_correlation ON task_run_logs(correlation_id);
+        ",
+    )
+    .context("failed to create sqlite indexes")
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    decl: &str,
+) -> anyhow::Result<()> {
+    let present: bool = conn
+        .query_row(
+            &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{table}') WHERE name = ?1"),
+            [column],
+            |r| r.get(0),
+        )
+        .with_context(|| format!("failed to inspect {table}"))?;
+    if !present {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))
+            .with_context(|| format!("failed to add {table}.{column}"))?;
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Query functions
+// ---------------------------------------------------------------------------
+
+impl SqliteStore {
+    pub async fn connect(&self) -> anyhow::Result<()> {
+        self.with_conn(|_| Ok(())).await?;
+        info!(path = %self.path.display(), "sqlite storage opened and schema ensured");
+        Ok(())
+    }
+
+    pub async fn health_check(&self) -> anyhow::Result<()> {
+        self.with_conn(|conn| {
+            conn.query_row("SELECT 1", [], |_| Ok(()))
+                .context("health_check")
+        })
+        .await
+    }
+
+    /// In-process change feed: `store_message` wakes subscribers directly,
+    /// since every writer goes through this store.
+    pub async fn listen(&self, channel: &str) -> anyhow::Result<mpsc::UnboundedReceiver<String>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.listeners
+            .lock()
+            .map_err(|_| anyhow!("sqlite listener lock poisoned"))?
+            .push((channel.to_string(), tx));
+        Ok(rx)
+    }
+
+    pub async fn store_chat_metadata(
+        &self,
+        jid: &str,
+        timestamp: &str,
+        name: Option<&str>,
+        channel: Option<&str>,
+        is_group: Option<bool>,
+    ) -> anyhow::Result<()> {
+        let jid = jid.to_string();
+        let timestamp = timestamp.to_string();
+        let name = name.map(|s| s.to_string());
+        let channel = channel.map(|s| s.to_string());
+        self.with_conn(move |conn| {
+            let display_name = name.as_deref().unwrap_or(&jid);
+            conn.execute(
+                &format!(
+                    "\
+                    INSERT INTO chats (jid, name, last_message_time, channel, is_group)
+                    VALUES (?1, ?2, {}, ?4, ?5)
+                    ON CONFLICT (jid) DO UPDATE SET
+                      name = COALESCE(NULLIF(excluded.name, excluded.jid), chats.name),
+                      last_message_time = CASE
+                        WHEN chats.last_message_time IS NULL
+                          OR excluded.last_message_time > chats.last_message_time
+                        THEN excluded.last_message_time
+                        ELSE chats.last_message_time END,
+                      channel = COALESCE(excluded.channel, chats.channel),
+                      is_group = COALESCE(excluded.is_group, chats.is_group)
+                    ",
+                    iso("?3")
+                ),
+                params![jid, display_name, timestamp, channel, is_group],
+            )
+            .context("store_chat_metadata")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn update_chat_name(&self, jid: &str, name: &str) -> anyhow::Result<()> {
+        let jid = jid.to_string();
+        let name = name.to_string();
+        self.with_conn(move |conn| {
+            conn.execute(
+                &format!(
+                    "\
+                    INSERT INTO chats (jid, name, last_message_time)
+                    VALUES (?1, ?2, {NOW})
+                    ON CONFLICT (jid) DO UPDATE SET name = excluded.name
+                    "
+                ),
+                params![jid, name],
+            )
+            .context("update_chat_name")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_all_chats(&self) -> anyhow::Result<Vec<ChatInfo>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT jid, name, last_message_time, channel, is_group \
+                 FROM chats ORDER BY last_message_time DESC",
+            )?;
+            let rows = stmt
+                .query_map([], |r| {
+                    Ok(ChatInfo {
+                        jid: r.get("jid")?,
+                        name: r.get::<_, Option<String>>("name")?.unwrap_or_default(),
+                        last_message_time: r
+                            .get::<_, Option<String>>("last_message_time")?
+                            .unwrap_or_default(),
+                        channel: r.get("channel")?,
+                        is_group: r.get::<_, Option<bool>>("is_group")?.unwrap_or(false),
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_all_chats")?;
+            Ok(rows)
+        })
+        .await
+    }
+
+    pub async fn get_chat_stats(&self, chat_jid: &str) -> anyhow::Result<ChatStats> {
+        let chat_jid = chat_jid.to_string();
+        self.with_conn(move |conn| {
+            let (total, bot, first_at, last_at) = conn
+                .query_row(
+                    "\
+                    SELECT COUNT(*), COALESCE(SUM(is_bot_message), 0), MIN(timestamp), MAX(timestamp)
+                    FROM messages
+                    WHERE chat_jid = ?1 AND deleted_at IS NULL
+                    ",
+                    params![chat_jid],
+                    |r| {
+                        Ok((
+                            r.get::<_, i64>(0)?,
+                            r.get::<_, i64>(1)?,
+                            r.get::<_, Option<String>>(2)?,
+                            r.get::<_, Option<String>>(3)?,
+                        ))
+                    },
+                )
+                .context("get_chat_stats")?;
+            let mut stmt = conn.prepare(
+                "\
+                SELECT COALESCE(sender, '') AS sender,
+                       COALESCE(MAX(sender_name), '') AS sender_name,
+                       COUNT(*) AS message_count,
+                       COALESCE(MAX(is_bot_message), 0) AS is_bot
+                FROM messages
+                WHERE chat_jid = ?1 AND deleted_at IS NULL
+                GROUP BY COALESCE(sender, '')
+                ORDER BY message_count DESC, sender
+                ",
+            )?;
+            let senders = stmt
+                .query_map(params![chat_jid], |r| {
+                    Ok(SenderStats {
+                        sender: r.get("sender")?,
+                        sender_name: r.get("sender_name")?,
+                        message_count: r.get("message_count")?,
+                        is_bot: r.get("is_bot")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_chat_stats senders")?;
+            Ok(ChatStats::from_counts(&chat_jid, total, bot, first_at, last_at, senders))
+        })
+        .await
+    }
+
+    /// Run a configured report query. Statements that could write are
+    /// rejected before execution. Parameters are bound as text to `$N` or
+    /// `?N` placeholders. The timeout only applies to Postgres.
+    pub async fn run_read_query(
+        &self,
+        sql: &str,
+        params: &[Option<String>],
+        max_rows: usize,
+        _timeout_ms: u64,
+    ) -> anyhow::Result<QueryResult> {
+        let sql = sql.trim().trim_end_matches(';').to_string();
+        let params = params.to_vec();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(&sql).context("run_read_query prepare")?;
+            if !stmt.readonly() {
+                return Err(anyhow!("query is not read-only"));
+            }
+            for (i, value) in params.iter().enumerate() {
+                let n = i + 1;
+                let index = match stmt.parameter_index(&format!("${n}"))? {
+                    Some(index) => index,
+                    None => n,
+                };
+                if index <= stmt.parameter_count() {
+                    stmt.raw_bind_parameter(index, value)?;
+                }
+            }
+            let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+            let mut rows = Vec::new();
+            let mut cursor = stmt.raw_query();
+            while let Some(row) = cursor.next().context("run_read_query")? {
+                if rows.len() > max_rows {
+                    break;
+                }
+                let mut object = serde_json::Map::new();
+                for (i, name) in columns.iter().enumerate() {
+                    object.insert(name.clone(), sqlite_value_to_json(row.get_ref(i)?));
+                }
+                rows.push(serde_json::Value::Object(object));
+            }
+            Ok(QueryResult::capped(columns, rows, max_rows))
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Message operations
+    // -----------------------------------------------------------------------
+
+    /// Store a message, clamping future timestamps to the local clock (same
+    /// contract as the Postgres backend).
+    pub async fn store_message(&self, msg: &NewMessage) -> anyhow::Result<()> {
+        let skew_warn_ms = self.clock_skew_warn_ms;
+        let msg = msg.clone();
+        let chat_jid = msg.chat_jid.clone();
+        self.with_conn(move |conn| {
+            // One transaction, so a failed attachment write does not leave
+            // the message stored without them.
+            let tx = conn.transaction().context("store_message")?;
+            tx.execute(
+                &format!(
+                    "\
+                    INSERT INTO messages (id, chat_jid, sender, sender_name, content, timestamp, is_from_me, is_bot_message, correlation_id)
+                    VALUES (?1, ?2, ?3, ?4, ?5, MIN({}, {NOW}), ?7, ?8, ?9)
+                    ON CONFLICT (id, chat_jid) DO UPDATE SET
+                      content = excluded.content,
+                      is_bot_message = excluded.is_bot_message,
+                      correlation_id = COALESCE(messages.correlation_id, excluded.correlation_id)
+                    ",
+                    iso("?6")
+                ),
+                params![
+                    msg.id,
+                    msg.chat_jid,
+                    msg.sender,
+                    msg.sender_name,
+                    msg.content,
+                    msg.timestamp,
+                    msg.is_from_me,
+                    msg.is_bot_message,
+                    msg.correlation_id,
+                ],
+            )
+            .context("store_message")?;
+            if !msg.attachments.is_empty() {
+                replace_attachments(&tx, &msg).context("store_message_attachments")?;
+            }
+            tx.commit().context("store_message")?;
+            let skew_ms: Option<f64> = conn
+                .query_row(
+                    "SELECT (julianday(?1) - julianday('now')) * 86400000.0",
+                    [&msg.timestamp],
+                    |r| r.get(0),
+                )
+                .context("store_message")?;
+            if let Some(skew_ms) = skew_ms
+                && skew_exceeds(skew_ms, skew_warn_ms)
+            {
+                warn!(
+                    id = %msg.id,
+                    chat_jid = %msg.chat_jid,
+                    client_timestamp = %msg.timestamp,
+                    skew_ms = skew_ms as i64,
+                    "message timestamp differs from server clock"
+                );
+            }
+            Ok(())
+        })
+        .await?;
+        self.notify(NEW_MESSAGE_CHANNEL, &chat_jid);
+        Ok(())
+    }
+
+    pub async fn get_recent_conversation(
+        &self,
+        chat_jid: &str,
+        limit: i64,
+    ) -> anyhow::Result<Vec<ConversationMessage>> {
+        let chat_jid = chat_jid.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "\
+                SELECT sender_name, content, timestamp, is_bot_message
+                FROM messages
+                WHERE chat_jid = ?1 AND content != '' AND content IS NOT NULL
+                  AND deleted_at IS NULL
+                ORDER BY timestamp DESC
+                LIMIT ?2
+                ",
+            )?;
+            let mut result = stmt
+                .query_map(params![chat_jid, limit], |r| {
+                    Ok(ConversationMessage {
+                        sender_name: r
+                            .get::<_, Option<String>>("sender_name")?
+                            .unwrap_or_default(),
+                        content: r.get::<_, Option<String>>("content")?.unwrap_or_default(),
+                        timestamp: r.get("timestamp")?,
+                        is_bot_message: r
+                            .get::<_, Option<bool>>("is_bot_message")?
+                            .unwrap_or(false),
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_recent_conversation")?;
+            result.reverse(); // Return in chronological order
+            Ok(result)
+        })
+        .await
+    }
+
+    pub async fn get_new_messages(
+        &self,
+        jids: &[String],
+        last_timestamp: &str,
+        bot_prefix: &str,
+    ) -> anyhow::Result<(Vec<NewMessage>, String)> {
+        if jids.is_empty() {
+            return Ok((vec![], last_timestamp.to_string()));
+        }
+        let jids = jids.to_vec();
+        let last_timestamp = last_timestamp.to_string();
+        let bot_prefix = format!("{bot_prefix}:");
+        self.with_conn(move |conn| {
+            let placeholders: Vec<String> = (0..jids.len()).map(|i| format!("?{}", i + 3)).collect();
+            let sql = format!(
+                "SELECT id, chat_jid, sender, sender_name, content, timestamp, edited_at, correlation_id \
+                 FROM messages \
+                 WHERE timestamp > {} AND chat_jid IN ({}) \
+                   AND is_bot_message = 0 AND substr(COALESCE(content, ''), 1, length(?2)) != ?2 \
+                   AND deleted_at IS NULL AND {HAS_BODY} \
+                 ORDER BY timestamp",
+                cursor_bound("?1"),
+                placeholders.join(", "),
+            );
+            let mut values = vec![last_timestamp.clone(), bot_prefix];
+            values.extend(jids);
+            let mut stmt = conn.prepare(&sql)?;
+            let mut messages = stmt
+                .query_map(params_from_iter(values.iter()), row_to_new_message)?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_new_messages")?;
+            load_attachments(conn, &mut messages)?;
+            let new_timestamp = messages
+                .last()
+                .map(|m| m.timestamp.clone())
+                .unwrap_or(last_timestamp);
+            Ok((messages, new_timestamp))
+        })
+        .await
+    }
+
+    pub async fn get_messages_since(
+        &self,
+        chat_jid: &str,
+        since_timestamp: &str,
+        bot_prefix: &str,
+    ) -> anyhow::Result<Vec<NewMessage>> {
+        let chat_jid = chat_jid.to_string();
+        let since_timestamp = since_timestamp.to_string();
+        let bot_prefix = format!("{bot_prefix}:");
+        self.with_conn(move |conn| {
+            let sql = format!(
+                "SELECT id, chat_jid, sender, sender_name, content, timestamp, edited_at, correlation_id \
+                 FROM messages \
+                 WHERE chat_jid = ?1 AND timestamp > {} \
+                   AND is_bot_message = 0 AND substr(COALESCE(content, ''), 1, length(?3)) != ?3 \
+                   AND deleted_at IS NULL AND {HAS_BODY} \
+                 ORDER BY timestamp",
+                cursor_bound("?2"),
+            );
+            let mut stmt = conn.prepare(&sql)?;
+            let mut messages = stmt
+                .query_map(params![chat_jid, since_timestamp, bot_prefix], row_to_new_message)?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_messages_since")?;
+            load_attachments(conn, &mut messages)?;
+            Ok(messages)
+        })
+        .await
+    }
+
+    pub async fn list_messages(&self, filter: &MessageFilter) -> anyhow::Result<Vec<NewMessage>> {
+        if filter.chat_jids.is_empty() {
+            return Ok(vec![]);
+        }
+        let filter = filter.clone();
+        self.with_conn(move |conn| {
+            let placeholders: Vec<String> = (0..filter.chat_jids.len()).map(|i| format!("?{}", i + 8)).collect();
+            let sql = format!(
+                "SELECT id, chat_jid, sender, sender_name, content, timestamp, edited_at, correlation_id \
+                 FROM messages \
+                 WHERE timestamp > {} AND chat_jid IN ({}) \
+                   AND is_bot_message = 0 AND substr(COALESCE(content, ''), 1, length(?2)) != ?2 \
+                   AND deleted_at IS NULL AND {HAS_BODY} \
+                   AND (?3 IS NULL OR sender = ?3) \
+                   AND (?4 IS NULL OR (timestamp, chat_jid, id) > (?4, ?5, ?6)) \
+                 ORDER BY timestamp, chat_jid, id \
+                 LIMIT ?7",
+                cursor_bound("?1"),
+                placeholders.join(", "),
+            );
+            let (after_ts, after_jid, after_id) = match filter.after {
+                Some((ts, jid, id)) => (Some(ts), Some(jid), Some(id)),
+                None => (None, None, None),
+            };
+            let mut values: Vec<Value> = vec![
+                filter.since.into(),
+                format!("{}:", filter.bot_prefix).into(),
+                filter.sender.into(),
+                after_ts.into(),
+                after_jid.into(),
+                after_id.into(),
+                filter.limit.into(),
+            ];
+            values.extend(filter.chat_jids.into_iter().map(Value::from));
+            let mut stmt = conn.prepare(&sql)?;
+            let mut messages = stmt
+                .query_map(params_from_iter(values.iter()), row_to_new_message)?
+                .collect::<Result<Vec<_>, _>>()
+                .context("list_messages")?;
+            load_attachments(conn, &mut messages)?;
+            Ok(messages)
+        })
+        .await
+    }
+
+    pub async fn get_replay_batch(
+        &self,
+        chat_jid: &str,
+        message_id: &str,
+        bot_prefix: &str,
+    ) -> anyhow::Result<Vec<NewMessage>> {
+        let chat_jid = chat_jid.to_string();
+        let message_id = message_id.to_string();
+        let bot_prefix = format!("{bot_prefix}:");
+        self.with_conn(move |conn| {
+            let sql = format!(
+                "WITH target AS ( \
+                   SELECT timestamp FROM messages WHERE chat_jid = ?1 AND id = ?2 \
+                 ), last_reply AS ( \
+                   SELECT MAX(m.timestamp) AS timestamp FROM messages m, target t \
+                   WHERE m.chat_jid = ?1 AND m.timestamp < t.timestamp \
+                     AND (m.is_bot_message != 0 \
+                          OR substr(COALESCE(m.content, ''), 1, length(?3)) = ?3) \
+                 ) \
+                 SELECT id, chat_jid, sender, sender_name, content, timestamp, edited_at, correlation_id \
+                 FROM messages \
+                 WHERE chat_jid = ?1 \
+                   AND timestamp <= (SELECT timestamp FROM target) \
+                   AND timestamp > COALESCE((SELECT timestamp FROM last_reply), '') \
+                   AND is_bot_message = 0 AND substr(COALESCE(content, ''), 1, length(?3)) != ?3 \
+                   AND deleted_at IS NULL AND {HAS_BODY} \
+                 ORDER BY timestamp",
+            );
+            let mut stmt = conn.prepare(&sql)?;
+            let mut messages = stmt
+                .query_map(params![chat_jid, message_id, bot_prefix], row_to_new_message)?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_replay_batch")?;
+            load_attachments(conn, &mut messages)?;
+            Ok(messages)
+        })
+        .await
+    }
+
+    pub async fn get_correlation_trace(
+        &self,
+        correlation_id: &str,
+    ) -> anyhow::Result<CorrelationTrace> {
+        let correlation_id = correlation_id.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "\
+                SELECT id, chat_jid, sender, sender_name, content, timestamp,
+                       is_from_me, is_bot_message, edited_at, correlation_id
+                FROM messages
+                WHERE correlation_id = ?1
+                ORDER BY timestamp
+                ",
+            )?;
+            let mut messages = stmt
+                .query_map([&correlation_id], |r| {
+                    let mut msg = row_to_new_message(r)?;
+                    msg.is_from_me = r.get::<_, Option<bool>>("is_from_me")?.unwrap_or(false);
+                    msg.is_bot_message =
+                        r.get::<_, Option<bool>>("is_bot_message")?.unwrap_or(false);
+                    Ok(msg)
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_correlation_trace")?;
+            load_attachments(conn, &mut messages)?;
+
+            let mut stmt = conn.prepare(
+                "\
+                SELECT task_id, run_at, duration_ms, status, result, error, correlation_id
+                FROM task_run_logs
+                WHERE correlation_id = ?1
+                ORDER BY run_at
+                ",
+            )?;
+            let task_runs = stmt
+                .query_map([&correlation_id], |r| {
+                    Ok(TaskRunLog {
+                        task_id: r.get("task_id")?,
+                        run_at: r.get("run_at")?,
+                        duration_ms: r.get("duration_ms")?,
+                        status: r.get("status")?,
+                        result: r.get("result")?,
+                        error: r.get("error")?,
+                        correlation_id: r.get("correlation_id")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_correlation_trace")?;
+            Ok(CorrelationTrace {
+                correlation_id,
+                messages,
+                task_runs,
+            })
+        })
+        .await
+    }
+
+    /// Replace a message's content, keeping the previous version in
+    /// `message_edits`. Returns `false` if the message is unknown.
+    pub async fn edit_message(
+        &self,
+        id: &str,
+        chat_jid: &str,
+        content: &str,
+        edited_at: &str,
+    ) -> anyhow::Result<bool> {
+        let id = id.to_string();
+        let chat_jid = chat_jid.to_string();
+        let content = content.to_string();
+        let edited_at = edited_at.to_string();
+        self.with_conn(move |conn| {
+            let tx = conn.transaction().context("edit_message")?;
+            let prior: Option<Option<String>> = tx
+                .query_row(
+                    "SELECT content FROM messages WHERE id = ?1 AND chat_jid = ?2",
+                    params![id, chat_jid],
+                    |r| r.get(0),
+                )
+                .optional()
+                .context("edit_message")?;
+            let Some(prior) = prior else {
+                return Ok(false);
+            };
+            if prior.as_deref() != Some(content.as_str()) {
+                let edited = format!("MIN(COALESCE({}, {NOW}), {NOW})", iso("?3"));
+                tx.execute(
+                    &format!(
+                        "INSERT INTO message_edits (message_id, chat_jid, previous_content, edited_at) \
+                         VALUES (?1, ?2, ?4, {edited})"
+                    ),
+                    params![id, chat_jid, edited_at, prior],
+                )
+                .context("edit_message")?;
+                tx.execute(
+                    &format!(
+                        "UPDATE messages SET content = ?4, edited_at = {edited} \
+                         WHERE id = ?1 AND chat_jid = ?2"
+                    ),
+                    params![id, chat_jid, edited_at, content],
+                )
+                .context("edit_message")?;
+            }
+            tx.commit().context("edit_message")?;
+            Ok(true)
+        })
+        .await
+    }
+
+    /// Tombstone a message: the row stays but is excluded from agent prompts.
+    pub async fn delete_message(
+        &self,
+        id: &str,
+        chat_jid: &str,
+        deleted_at: &str,
+    ) -> anyhow::Result<bool> {
+        let id = id.to_string();
+        let chat_jid = chat_jid.to_string();
+        let deleted_at = deleted_at.to_string();
+        self.with_conn(move |conn| {
+            let rows = conn
+                .execute(
+                    &format!(
+                        "UPDATE messages \
+                         SET deleted_at = COALESCE(deleted_at, MIN(COALESCE({}, {NOW}), {NOW})) \
+                         WHERE id = ?1 AND chat_jid = ?2",
+                        iso("?3")
+                    ),
+                    params![id, chat_jid, deleted_at],
+                )
+                .context("delete_message")?;
+            Ok(rows > 0)
+        })
+        .await
+    }
+
+    pub async fn get_message_edits(
+        &self,
+        id: &str,
+        chat_jid: &str,
+    ) -> anyhow::Result<Vec<MessageEdit>> {
+        let id = id.to_string();
+        let chat_jid = chat_jid.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "\
+                SELECT message_id, chat_jid, previous_content, edited_at
+                FROM message_edits
+                WHERE message_id = ?1 AND chat_jid = ?2
+                ORDER BY edited_at, id
+                ",
+            )?;
+            let edits = stmt
+                .query_map(params![id, chat_jid], |r| {
+                    Ok(MessageEdit {
+                        message_id: r.get("message_id")?,
+                        chat_jid: r.get("chat_jid")?,
+                        previous_content: r
+                            .get::<_, Option<String>>("previous_content")?
+                            .unwrap_or_default(),
+                        edited_at: r.get("edited_at")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_message_edits")?;
+            Ok(edits)
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Attachment operations
+    // -----------------------------------------------------------------------
+
+    pub async fn store_attachment(&self, attachment: &Attachment) -> anyhow::Result<()> {
+        let attachment = attachment.clone();
+        self.with_conn(move |conn| insert_attachment(conn, &attachment).context("store_attachment"))
+            .await
+    }
+
+    pub async fn get_attachments(
+        &self,
+        message_id: &str,
+        chat_jid: &str,
+    ) -> anyhow::Result<Vec<Attachment>> {
+        let message_id = message_id.to_string();
+        let chat_jid = chat_jid.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT * FROM attachments WHERE message_id = ?1 AND chat_jid = ?2 ORDER BY id",
+            )?;
+            let attachments = stmt
+                .query_map(params![message_id, chat_jid], row_to_attachment)?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_attachments")?;
+            Ok(attachments)
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Scheduled task operations
+    // -----------------------------------------------------------------------
+
+    pub async fn create_task(&self, task: &ScheduledTask) -> anyhow::Result<()> {
+        let task = task.clone();
+        self.with_conn(move |conn| {
+            let config_json = task.task_config.as_ref().map(serde_json::to_string).transpose()?;
+            conn.execute(
+                &format!(
+                    "\
+                    INSERT INTO scheduled_tasks
+                      (id, group_folder, chat_jid, prompt, schedule_type, schedule_value, context_mode, next_run, status, created_at, task_config)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, {}, ?9, {}, ?11)
+                    ",
+                    iso("?8"),
+                    iso("?10")
+                ),
+                params![
+                    task.id,
+                    task.group_folder,
+                    task.chat_jid,
+                    task.prompt,
+                    task.schedule_type,
+                    task.schedule_value,
+                    task.context_mode,
+                    task.next_run,
+                    task.status,
+                    task.created_at,
+                    config_json,
+                ],
+            )
+            .context("create_task")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_task_by_id(&self, id: &str) -> anyhow::Result<Option<ScheduledTask>> {
+        let id = id.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM scheduled_tasks WHERE id = ?1",
+                [&id],
+                row_to_task,
+            )
+            .optional()
+            .context("get_task_by_id")
+        })
+        .await
+    }
+
+    pub async fn get_tasks_for_group(
+        &self,
+        group_folder: &str,
+    ) -> anyhow::Result<Vec<ScheduledTask>> {
+        let group_folder = group_folder.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT * FROM scheduled_tasks WHERE group_folder = ?1 ORDER BY created_at DESC",
+            )?;
+            let tasks = stmt
+                .query_map([&group_folder], row_to_task)?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_tasks_for_group")?;
+            Ok(tasks)
+        })
+        .await
+    }
+
+    pub async fn get_all_tasks(&self) -> anyhow::Result<Vec<ScheduledTask>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare("SELECT * FROM scheduled_tasks ORDER BY created_at DESC")?;
+            let tasks = stmt
+                .query_map([], row_to_task)?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_all_tasks")?;
+            Ok(tasks)
+        })
+        .await
+    }
+
+    pub async fn list_tasks(&self, filter: &TaskFilter) -> anyhow::Result<Vec<ScheduledTask>> {
+        let filter = filter.clone();
+        self.with_conn(move |conn| {
+            let (after_ts, after_id) = filter.after.unzip();
+            let mut stmt = conn.prepare(&format!(
+                "SELECT * FROM scheduled_tasks \
+                 WHERE (?1 IS NULL OR group_folder = ?1) \
+                   AND (?2 IS NULL OR chat_jid = ?2) \
+                   AND (?3 IS NULL OR status = ?3) \
+                   AND (?4 IS NULL OR (created_at, id) {past} (?4, ?5)) \
+                 ORDER BY created_at {order}, id {order} \
+                 LIMIT ?6",
+                past = filter.order.past(),
+                order = filter.order.sql(),
+            ))?;
+            let tasks = stmt
+                .query_map(
+                    params![
+                        filter.group_folder,
+                        filter.chat_jid,
+                        filter.status,
+                        after_ts,
+                        after_id,
+                        filter.limit
+                    ],
+                    row_to_task,
+                )?
+                .collect::<Result<Vec<_>, _>>()
+                .context("list_tasks")?;
+            Ok(tasks)
+        })
+        .await
+    }
+
+    pub async fn update_task(&self, id: &str, updates: &TaskUpdate) -> anyhow::Result<()> {
+        let mut fields = Vec::new();
+        let mut params: Vec<String> = Vec::new();
+
+        if let Some(ref prompt) = updates.prompt {
+            params.push(prompt.clone());
+            fields.push(format!("prompt = ?{}", params.len()));
+        }
+        if let Some(ref schedule_type) = updates.schedule_type {
+            params.push(schedule_type.clone());
+            fields.push(format!("schedule_type = ?{}", params.len()));
+        }
+        if let Some(ref schedule_value) = updates.schedule_value {
+            params.push(schedule_value.clone());
+            fields.push(format!("schedule_value = ?{}", params.len()));
+        }
+        if let Some(ref next_run) = updates.next_run {
+            params.push(next_run.clone());
+            fields.push(format!("next_run = {}", iso(&format!("?{}", params.len()))));
+        }
+        if let Some(ref status) = updates.status {
+            params.push(status.clone());
+            fields.push(format!("status = ?{}", params.len()));
+        }
+        if let Some(ref task_config) = updates.task_config {
+            params.push(task_config.to_string());
+            fields.push(format!("task_config = ?{}", params.len()));
+        }
+
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        params.push(id.to_string());
+        let sql = format!(
+            "UPDATE scheduled_tasks SET {} WHERE id = ?{}",
+            fields.join(", "),
+            params.len()
+        );
+
+        self.with_conn(move |conn| {
+            conn.execute(&sql, params_from_iter(params.iter()))
+                .context("update_task")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn delete_task(&self, id: &str) -> anyhow::Result<()> {
+        let id = id.to_string();
+        self.with_conn(move |conn| {
+            conn.execute("DELETE FROM task_run_logs WHERE task_id = ?1", [&id])
+                .context("delete_task_logs")?;
+            conn.execute("DELETE FROM scheduled_tasks WHERE id = ?1", [&id])
+                .context("delete_task")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_due_tasks(&self) -> anyhow::Result<Vec<ScheduledTask>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "\
+                SELECT * FROM scheduled_tasks
+                WHERE status = 'active' AND next_run IS NOT NULL AND next_run <= {NOW}
+                ORDER BY next_run
+                "
+            ))?;
+            let tasks = stmt
+                .query_map([], row_to_task)?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_due_tasks")?;
+            Ok(tasks)
+        })
+        .await
+    }
+
+    pub async fn update_task_after_run(
+        &self,
+        id: &str,
+        next_run: Option<&str>,
+        last_result: &str,
+    ) -> anyhow::Result<()> {
+        let id = id.to_string();
+        let next_run = next_run.map(|s| s.to_string());
+        let last_result = last_result.to_string();
+        self.with_conn(move |conn| {
+            conn.execute(
+                &format!(
+                    "\
+                    UPDATE scheduled_tasks
+                    SET next_run = {}, last_run = {NOW},
+                        last_result = ?2,
+                        status = CASE WHEN ?1 IS NULL THEN 'completed' ELSE status END
+                    WHERE id = ?3
+                    ",
+                    iso("?1")
+                ),
+                params![next_run, last_result, id],
+            )
+            .context("update_task_after_run")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn set_task_status(
+        &self,
+        id: &str,
+        status: &str,
+        next_run: Option<&str>,
+    ) -> anyhow::Result<Option<ScheduledTask>> {
+        let id = id.to_string();
+        let status = status.to_string();
+        let next_run = next_run.map(|s| s.to_string());
+        self.with_conn(move |conn| {
+            let updated = conn
+                .execute(
+                    &format!(
+                        "\
+                        UPDATE scheduled_tasks
+                        SET status = ?1, next_run = COALESCE({}, next_run)
+                        WHERE id = ?3 AND status <> 'completed'
+                        ",
+                        iso("?2")
+                    ),
+                    params![status, next_run, id],
+                )
+                .context("set_task_status")?;
+            if updated == 0 {
+                return Ok(None);
+            }
+            conn.query_row(
+                "SELECT * FROM scheduled_tasks WHERE id = ?1",
+                [&id],
+                row_to_task,
+            )
+            .optional()
+            .context("set_task_status")
+        })
+        .await
+    }
+
+    pub async fn log_task_run(&self, log: &TaskRunLog) -> anyhow::Result<()> {
+        let log = log.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                &format!(
+                    "\
+                    INSERT INTO task_run_logs (task_id, run_at, duration_ms, status, result, error, correlation_id)
+                    VALUES (?1, {}, ?3, ?4, ?5, ?6, ?7)
+                    ",
+                    iso("?2")
+                ),
+                params![
+                    log.task_id,
+                    log.run_at,
+                    log.duration_ms,
+                    log.status,
+                    log.result,
+                    log.error,
+                    log.correlation_id,
+                ],
+            )
+            .context("log_task_run")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_task_runs(
+        &self,
+        task_id: &str,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<TaskRunLog>> {
+        let task_id = task_id.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "\
+                SELECT task_id, run_at, duration_ms, status, result, error, correlation_id
+                FROM task_run_logs
+                WHERE task_id = ?1
+                ORDER BY run_at DESC, id DESC
+                LIMIT ?2 OFFSET ?3
+                ",
+            )?;
+            let runs = stmt
+                .query_map(params![task_id, limit, offset], |r| {
+                    Ok(TaskRunLog {
+                        task_id: r.get("task_id")?,
+                        run_at: r.get("run_at")?,
+                        duration_ms: r.get("duration_ms")?,
+                        status: r.get("status")?,
+                        result: r.get("result")?,
+                        error: r.get("error")?,
+                        correlation_id: r.get("correlation_id")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .context("get_task_runs")?;
+            Ok(runs)
+        })
+        .await
+    }
+
+    pub async fn get_task_run_stats(&self, task_id: &str) -> anyhow::Result<TaskRunStats> {
+        let task_id = task_id.to_string();
+        self.with_conn(move |conn| {
+            let (runs, successes, avg_duration_ms, last_run_at) = conn
+                .query_row(
+                    "\
+                    SELECT COUNT(*), COALESCE(SUM(status = 'success'), 0), AVG(duration_ms), MAX(run_at)
+                    FROM task_run_logs
+                    WHERE task_id = ?1
+                    ",
+                    params![task_id],
+                    |r| {
+                        Ok((
+                            r.get::<_, i64>(0)?,
+                            r.get::<_, i64>(1)?,
+                            r.get::<_, Option<f64>>(2)?,
+                            r.get::<_, Option<String>>(3)?,
+                        ))
+                    },
+                )
+                .context("get_task_run_stats")?;
+            let last_error = conn
+                .query_row(
+                    "\
+                    SELECT run_at, error
+                    FROM task_run_logs
+                    WHERE task_id = ?1 AND status = 'error'
+                    ORDER BY run_at DESC, id DESC
+                    LIMIT 1
+                    ",
+                    params![task_id],
+                    |r| Ok((r.get::<_, String>(0)?, r.get::<_, Option<String>>(1)?)),
+                )
+                .optional()
+                .context("get_task_run_stats last error")?;
+            let (last_error_at, last_error) = match last_error {
+                Some((at, error)) => (Some(at), error),
+                None => (None, None),
+            };
+            Ok(TaskRunStats::from_counts(
+                &task_id,
+                runs,
+                successes,
+                avg_duration_ms,
+                last_run_at,
+                last_error_at,
+                last_error,
+            ))
+        })
+        .await
+    }
+
+    pub async fn upsert_task_template(&self, template: &TaskTemplate) -> anyhow::Result<()> {
+        let template = template.clone();
+        self.with_conn(move |conn| {
+            let config_json = template.task_config.as_ref().map(serde_json::to_string).transpose()?;
+            conn.execute(
+                &format!(
+                    "\
+                    INSERT INTO task_templates (name, prompt, schedule_type, schedule_value, context_mode, task_config)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                    ON CONFLICT (name) DO UPDATE SET
+                      prompt = excluded.prompt,
+                      schedule_type = excluded.schedule_type,
+                      schedule_value = excluded.schedule_value,
+                      context_mode = excluded.context_mode,
+                      task_config = excluded.task_config,
+                      updated_at = {NOW}
+                    "
+                ),
+                params![
+                    template.name,
+                    template.prompt,
+                    template.schedule_type,
+                    template.schedule_value,
+                    template.context_mode,
+                    config_json,
+                ],
+            )
+            .context("upsert_task_template")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_task_template(&self, name: &str) -> anyhow::Result<Option<TaskTemplate>> {
+        let name = name.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM task_templates WHERE name = ?1",
+                [&name],
+                row_to_task_template,
+            )
+            .optional()
+            .context("get_task_template")
+        })
+        .await
+    }
+
+    pub async fn list_task_templates(&self) -> anyhow::Result<Vec<TaskTemplate>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM task_templates ORDER BY name")?;
+            let templates = stmt
+                .query_map([], row_to_task_template)?
+                .collect::<Result<Vec<_>, _>>()
+                .context("list_task_templates")?;
+            Ok(templates)
+        })
+        .await
+    }
+
+    pub async fn delete_task_template(&self, name: &str) -> anyhow::Result<bool> {
+        let name = name.to_string();
+        self.with_conn(move |conn| {
+            let deleted = conn
+                .execute("DELETE FROM task_templates WHERE name = ?1", [&name])
+                .context("delete_task_template")?;
+            Ok(deleted > 0)
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Container run operations
+    // -----------------------------------------------------------------------
+
+    pub async fn record_container_run(&self, run: &ContainerRun) -> anyhow::Result<()> {
+        let run = run.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                &format!(
+                    "\
+                    INSERT INTO container_runs (
+                      run_id, group_folder, chat_jid, trigger, started_at, finished_at,
+                      duration_ms, exit_code, timed_out, status, error, model, session_id,
+                      output_bytes, correlation_id, input_tokens, output_tokens,
+                      cache_read_tokens, cache_write_tokens, cost_usd
+                    )
+                    VALUES (?1, ?2, ?3, ?4, {}, {}, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15,
+                            ?16, ?17, ?18, ?19, ?20)
+                    ",
+                    iso("?5"),
+                    iso("?6")
+                ),
+                params![
+                    run.run_id,
+                    run.group_folder,
+                    run.chat_jid,
+                    run.trigger,
+                    run.started_at,
+                    run.finished_at,
+                    run.duration_ms,
+                    run.exit_code,
+                    run.timed_out,
+                    run.status,
+                    run.error,
+                    run.model,
+                    run.session_id,
+                    run.output_bytes,
+                    run.correlation_id,
+                    run.input_tokens,
+                    run.output_tokens,
+                    run.cache_read_tokens,
+                    run.cache_write_tokens,
+                    run.cost_usd,
+                ],
+            )
+            .context("record_container_run")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Newest runs first, optionally for one group only.
+    pub async fn list_container_runs(
+        &self,
+        group_folder: Option<&str>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<ContainerRun>> {
+        let group_folder = group_folder.map(str::to_string);
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(
+                "\
+                SELECT run_id, group_folder, chat_jid, trigger, started_at, finished_at,
+                       duration_ms, exit_code, timed_out, status, error, model, session_id,
+                       output_bytes, correlation_id, input_tokens, output_tokens,
+                       cache_read_tokens, cache_write_tokens, cost_usd
+                FROM container_runs
+                WHERE ?1 IS NULL OR group_folder = ?1
+                ORDER BY started_at DESC, id DESC
+                LIMIT ?2
+                ",
+            )?;
+            let runs = stmt
+                .query_map(params![group_folder, limit], |r| {
+                    Ok(ContainerRun {
+                        run_id: r.get("run_id")?,
+                        group_folder: r.get("group_folder")?,
+                        chat_jid: r.get("chat_jid")?,
+                        trigger: r.get("trigger")?,
+                        started_at: r.get("started_at")?,
+                        finished_at: r.get("finished_at")?,
+                        duration_ms: r.get("duration_ms")?,
+                        exit_code: r.get("exit_code")?,
+                        timed_out: r.get("timed_out")?,
+                        status: r.get("status")?,
+                        error: r.get("error")?,
+                        model: r.get("model")?,
+                        session_id: r.get("session_id")?,
+                        output_bytes: r.get("output_bytes")?,
+                        correlation_id: r.get("correlation_id")?,
+                        input_tokens: r.get("input_tokens")?,
+                        output_tokens: r.get("output_tokens")?,
+                        cache_read_tokens: r.get("cache_read_tokens")?,
+                        cache_write_tokens: r.get("cache_write_tokens")?,
+                        cost_usd: r.get("cost_usd")?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .context("list_container_runs")?;
+            Ok(runs)
+        })
+        .await
+    }
+
+    /// Runs, tokens and cost since `since` (RFC 3339), optionally for one
+    /// group only.
+    pub async fn usage_totals(
+        &self,
+        group_folder: Option<&str>,
+        since: &str,
+    ) -> anyhow::Result<UsageTotals> {
+        let group_folder = group_folder.map(str::to_string);
+        let since = since.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                &format!(
+                    "\
+                    SELECT COUNT(*),
+                           COALESCE(SUM(input_tokens), 0),
+                           COALESCE(SUM(output_tokens), 0),
+                           COALESCE(SUM(cache_read_tokens), 0),
+                           COALESCE(SUM(cache_write_tokens), 0),
+                           COALESCE(SUM(cost_usd), 0.0),
+                           COALESCE(SUM(cost_usd IS NULL), 0),
+                           COALESCE(SUM(status = 'success'), 0),
+                           COALESCE(SUM(duration_ms), 0)
+                    FROM container_runs
+                    WHERE (?1 IS NULL OR group_folder = ?1)
+                      AND started_at >= {}
+                    ",
+                    iso("?2")
+                ),
+                params![group_folder, since],
+                |r| {
+                    Ok(UsageTotals {
+                        runs: r.get(0)?,
+                        input_tokens: r.get(1)?,
+                        output_tokens: r.get(2)?,
+                        cache_read_tokens: r.get(3)?,
+                        cache_write_tokens: r.get(4)?,
+                        cost_usd: r.get(5)?,
+                        unpriced_runs: r.get(6)?,
+                        succeeded_runs: r.get(7)?,
+                        duration_ms: r.get(8)?,
+                    })
+                },
+            )
+            .context("usage_totals")
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Demarch audit operations
+    // -----------------------------------------------------------------------
+
+    pub async fn record_demarch_write(&self, entry: &DemarchAuditEntry) -> anyhow::Result<()> {
+        let entry = entry.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "\
+                INSERT INTO demarch_audit (operation, params, source_group, is_main, status, result)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ",
+                params![
+                    entry.operation,
+                    entry.params.to_string(),
+                    entry.source_group,
+                    entry.is_main,
+                    entry.status,
+                    entry.result,
+                ],
+            )
+            .context("record_demarch_write")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Newest entries first.
+    pub async fn list_demarch_audit(
+        &self,
+        filter: &DemarchAuditFilter,
+    ) -> anyhow::Result<Vec<DemarchAuditEntry>> {
+        let filter = filter.clone();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare(&format!(
+                "\
+                SELECT created_at, operation, params, source_group, is_main, status, result
+                FROM demarch_audit
+                WHERE (?1 IS NULL OR source_group = ?1)
+                  AND (?2 IS NULL OR operation = ?2)
+                  AND (?3 IS NULL OR status = ?3)
+                  AND (?4 IS NULL OR created_at >= {})
+                  AND (?5 IS NULL OR created_at < {})
+                ORDER BY created_at DESC, id DESC
+                LIMIT ?6
+                ",
+                iso("?4"),
+                iso("?5")
+            ))?;
+            let entries = stmt
+                .query_map(
+                    params![
+                        filter.source_group,
+                        filter.operation,
+                        filter.status,
+                        filter.since,
+                        filter.until,
+                        filter.limit,
+                    ],
+                    |r| {
+                        Ok(DemarchAuditEntry {
+                            created_at: r.get("created_at")?,
+                            operation: r.get("operation")?,
+                            params: serde_json::from_str(&r.get::<_, String>("params")?)
+                                .unwrap_or_default(),
+                            source_group: r.get("source_group")?,
+                            is_main: r.get("is_main")?,
+                            status: r.get("status")?,
+                            result: r.get("result")?,
+                        })
+                    },
+                )?
+                .collect::<Result<Vec<_>, _>>()
+                .context("list_demarch_audit")?;
+            Ok(entries)
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Router state operations
+    // -----------------------------------------------------------------------
+
+    pub async fn get_router_state(&self, key: &str) -> anyhow::Result<Option<String>> {
+        let key = key.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT value FROM router_state WHERE key = ?1",
+                [&key],
+                |r| r.get(0),
+            )
+            .optional()
+            .context("get_router_state")
+        })
+        .await
+    }
+
+    pub async fn set_router_state(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        let key = key.to_string();
+        let value = value.to_string();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "\
+                INSERT INTO router_state (key, value) VALUES (?1, ?2)
+                ON CONFLICT (key) DO UPDATE SET value = excluded.value
+                ",
+                params![key, value],
+            )
+            .context("set_router_state")?;
+            Ok(())
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Session operations
+    // -----------------------------------------------------------------------
+
+    pub async fn get_session(&self, group_folder: &str) -> anyhow::Result<Option<String>> {
+        let group_folder = group_folder.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT session_id FROM sessions WHERE group_folder = ?1",
+                [&group_folder],
+                |r| r.get(0),
+            )
+            .optional()
+            .context("get_session")
+        })
+        .await
+    }
+
+    pub async fn set_session(&self, group_folder: &str, session_id: &str) -> anyhow::Result<()> {
+        let group_folder = group_folder.to_string();
+        let session_id = session_id.to_string();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "\
+                INSERT INTO sessions (group_folder, session_id) VALUES (?1, ?2)
+                ON CONFLICT (group_folder) DO UPDATE SET session_id = excluded.session_id
+                ",
+                params![group_folder, session_id],
+            )
+            .context("set_session")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_all_sessions(&self) -> anyhow::Result<HashMap<String, String>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT group_folder, session_id FROM sessions")?;
+            let sessions = stmt
+                .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+                .collect::<Result<HashMap<_, _>, _>>()
+                .context("get_all_sessions")?;
+            Ok(sessions)
+        })
+        .await
+    }
+
+    pub async fn delete_session(&self, group_folder: &str) -> anyhow::Result<()> {
+        let group_folder = group_folder.to_string();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "DELETE FROM sessions WHERE group_folder = ?1",
+                [&group_folder],
+            )
+            .context("delete_session")?;
+            Ok(())
+        })
+        .await
+    }
+
+    // -----------------------------------------------------------------------
+    // Registered group operations
+    // -----------------------------------------------------------------------
+
+    pub async fn get_registered_group(&self, jid: &str) -> anyhow::Result<Option<RegisteredGroup>> {
+        let jid = jid.to_string();
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT * FROM registered_groups WHERE jid = ?1",
+                [&jid],
+                row_to_registered_group,
+            )
+            .optional()
+            .context("get_registered_group")
+        })
+        .await
+    }
+
+    pub async fn set_registered_group(&self, group: &RegisteredGroup) -> anyhow::Result<()> {
+        let group = group.clone();
+        self.with_conn(move |conn| {
+            let config_json = group
+                .container_config
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?;
+            let requires_trigger = group.requires_trigger.unwrap_or(true);
+            conn.execute(
+                &format!(
+                    "\
+                    INSERT INTO registered_groups
+                      (jid, name, folder, trigger_pattern, added_at, container_config, requires_trigger, runtime, model, muted_until, locale)
+                    VALUES (?1, ?2, ?3, ?4, {}, ?6, ?7, ?8, ?9, {}, ?11)
+                    ON CONFLICT (jid) DO UPDATE SET
+                      name = excluded.name,
+                      folder = excluded.folder,
+                      trigger_pattern = excluded.trigger_pattern,
+                      container_config = excluded.container_config,
+                      requires_trigger = excluded.requires_trigger,
+                      runtime = excluded.runtime,
+                      model = excluded.model,
+                      muted_until = excluded.muted_until,
+                      locale = excluded.locale
+                    ",
+                    iso("?5"),
+                    iso("?10")
+                ),
+                params![
+                    group.jid,
+                    group.name,
+                    group.folder,
+                    group.trigger,
+                    group.added_at,
+                    config_json,
+                    requires_trigger,
+                    group.runtime,
+                    group.model,
+                    group.muted_until,
+                    group.locale,
+                ],
+            )
+            .context("set_registered_group")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn get_all_registered_groups(
+        &self,
+    ) -> anyhow::Result<HashMap<String, RegisteredGroup>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM registered_groups")?;
+            let groups = stmt
+                .query_map([], row_to_registered_group)?
+                .map(|g| g.map(|g| (g.jid.clone(), g)))
+                .collect::<Result<HashMap<_, _>, _>>()
+                .context("get_all_registered_groups")?;
+            Ok(groups)
+        })
+        .await
+    }
+
+    pub async fn delete_registered_group(&self, jid: &str) -> anyhow::Result<bool> {
+        let jid = jid.to_string();
+        self.with_conn(move |conn| {
+            let deleted = conn
+                .execute("DELETE FROM registered_groups WHERE jid = ?1", [&jid])
+                .context("delete_registered_group")?;
+            Ok(deleted > 0)
+        })
+        .await
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// SQL expression for a cursor parameter compared against
+/// `messages.timestamp`: empty means "from the beginning", and a cursor
+/// ahead of the local clock is clamped to now.
+fn cursor_bound(param: &str) -> String {
+    format!("MIN(COALESCE({}, ''), {NOW})", iso(param))
+}
+
+fn row_to_new_message(r: &Row<'_>) -> rusqlite::Result<NewMessage> {
+    Ok(NewMessage {
+        id: r.get("id")?,
+        chat_jid: r.get("chat_jid")?,
+        sender: r.get::<_, Option<String>>("sender")?.unwrap_or_default(),
+        sender_name: r
+            .get::<_, Option<String>>("sender_name")?
+            .unwrap_or_default(),
+        content: r.get::<_, Option<String>>("content")?.unwrap_or_default(),
+        timestamp: r.get("timestamp")?,
+        is_from_me: false,
+        is_bot_message: false,
+        edited: r.get::<_, Option<String>>("edited_at")?.is_some(),
+        attachments: Vec::new(),
+        correlation_id: r.get("correlation_id")?,
+    })
+}
+
+fn insert_attachment(conn: &Connection, attachment: &Attachment) -> anyhow::Result<()> {
+    conn.execute(
+        "\
+        INSERT INTO attachments
+          (message_id, chat_jid, kind, mime_type, file_name, file_path, caption, size_bytes)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        ",
+        params![
+            attachment.message_id,
+            attachment.chat_jid,
+            attachment.kind,
+            attachment.mime_type,
+            attachment.file_name,
+            attachment.file_path,
+            attachment.caption,
+            attachment.size_bytes,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Replace the attachments of a (re-)stored message with the ones it carries.
+fn replace_attachments(conn: &Connection, msg: &NewMessage) -> anyhow::Result<()> {
+    conn.execute(
+        "DELETE FROM attachments WHERE message_id = ?1 AND chat_jid = ?2",
+        params![msg.id, msg.chat_jid],
+    )?;
+    for attachment in &msg.attachments {
+        let attachment = Attachment {
+            message_id: msg.id.clone(),
+            chat_jid: msg.chat_jid.clone(),
+            ..attachment.clone()
+        };
+        insert_attachment(conn, &attachment)?;
+    }
+    Ok(())
+}
+
+fn load_attachments(conn: &Connection, messages: &mut [NewMessage]) -> anyhow::Result<()> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT * FROM attachments WHERE message_id = ?1 AND chat_jid = ?2 ORDER BY id",
+    )?;
+    for msg in messages.iter_mut() {
+        msg.attachments = stmt
+            .query_map(params![msg.id, msg.chat_jid], row_to_attachment)?
+            .collect::<Result<Vec<_>, _>>()
+            .context("load_attachments")?;
+    }
+    Ok(())
+}
+
+fn row_to_attachment(r: &Row<'_>) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        message_id: r.get("message_id")?,
+        chat_jid: r.get("chat_jid")?,
+        kind: r.get("kind")?,
+        mime_type: r.get("mime_type")?,
+        file_name: r.get("file_name")?,
+        file_path: r.get("file_path")?,
+        caption: r.get("caption")?,
+        size_bytes: r.get("size_bytes")?,
+    })
+}
+
+fn row_to_task(r: &Row<'_>) -> rusqlite::Result<ScheduledTask> {
+    Ok(ScheduledTask {
+        id: r.get("id")?,
+        group_folder: r.get("group_folder")?,
+        chat_jid: r.get("chat_jid")?,
+        prompt: r.get("prompt")?,
+        schedule_type: r.get("schedule_type")?,
+        schedule_value: r.get("schedule_value")?,
+        context_mode: r
+            .get::<_, Option<String>>("context_mode")?
+            .unwrap_or_else(|| "isolated".to_string()),
+        next_run: r.get("next_run")?,
+        last_run: r.get("last_run")?,
+        last_result: r.get("last_result")?,
+        status: r
+            .get::<_, Option<String>>("status")?
+            .unwrap_or_else(|| "active".to_string()),
+        created_at: r.get("created_at")?,
+        task_config: r
+            .get::<_, Option<String>>("task_config")?
+            .and_then(|raw| serde_json::from_str(&raw).ok()),
+    })
+}
+
+fn row_to_task_template(r: &Row<'_>) -> rusqlite::Result<TaskTemplate> {
+    Ok(TaskTemplate {
+        name: r.get("name")?,
+        prompt: r.get("prompt")?,
+        schedule_type: r.get("schedule_type")?,
+        schedule_value: r.get("schedule_value")?,
+        context_mode: r.get("context_mode")?,
+        task_config: r
+            .get::<_, Option<String>>("task_config")?
+            .and_then(|raw| serde_json::from_str(&raw).ok()),
+        created_at: r.get("created_at")?,
+        updated_at: r.get("updated_at")?,
+    })
+}
+
+/// Column value as JSON. Blobs have no useful JSON form and become null.
+fn sqlite_value_to_json(value: ValueRef<'_>) -> serde_json::Value {
+    match value {
+        ValueRef::Null | ValueRef::Blob(_) => serde_json::Value::Null,
+        ValueRef::Integer(n) => n.into(),
+        ValueRef::Real(f) => serde_json::Number::from_f64(f)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned().into(),
+    }
+}
+
+fn row_to_registered_group(r: &Row<'_>) -> rusqlite::Result<RegisteredGroup> {
+    let container_config = r
+        .get::<_, Option<String>>("container_config")?
+        .and_then(|raw| serde_json::from_str(&raw).ok());
+    Ok(RegisteredGroup {
+        jid: r.get("jid")?,
+        name: r.get("name")?,
+        folder: r.get("folder")?,
+        trigger: r.get("trigger_pattern")?,
+        added_at: r.get("added_at")?,
+        container_config,
+        requires_trigger: r.get("requires_trigger")?,
+        runtime: r.get("runtime")?,
+        model: r.get("model")?,
+        muted_until: r.get("muted_until")?,
+        locale: r.get("locale")?,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Storage impl — delegate to the inherent methods
+// ---------------------------------------------------------------------------
+
+impl Storage for SqliteStore {
+    fn backend(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn connect(&self) -> StorageFuture<'_, ()> {
+        Box::pin(SqliteStore::connect(self))
+    }
+
+    fn health_check(&self) -> StorageFuture<'_, ()> {
+        Box::pin(SqliteStore::health_check(self))
+    }
+
+    fn circuit(&self) -> Option<CircuitSnapshot> {
+        None
+    }
+
+    fn listen<'a>(
+        &'a self,
+        channel: &'a str,
+    ) -> StorageFuture<'a, mpsc::UnboundedReceiver<String>> {
+        Box::pin(SqliteStore::listen(self, channel))
+    }
+
+    fn store_chat_metadata<'a>(
+        &'a self,
+        jid: &'a str,
+        timestamp: &'a str,
+        name: Option<&'a str>,
+        channel: Option<&'a str>,
+        is_group: Option<bool>,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::store_chat_metadata(
+            self, jid, timestamp, name, channel, is_group,
+        ))
+    }
+
+    fn update_chat_name<'a>(&'a self, jid: &'a str, name: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::update_chat_name(self, jid, name))
+    }
+
+    fn get_all_chats(&self) -> StorageFuture<'_, Vec<ChatInfo>> {
+        Box::pin(SqliteStore::get_all_chats(self))
+    }
+
+    fn get_chat_stats<'a>(&'a self, chat_jid: &'a str) -> StorageFuture<'a, ChatStats> {
+        Box::pin(SqliteStore::get_chat_stats(self, chat_jid))
+    }
+
+    fn run_read_query<'a>(
+        &'a self,
+        sql: &'a str,
+        params: &'a [Option<String>],
+        max_rows: usize,
+        timeout_ms: u64,
+    ) -> StorageFuture<'a, QueryResult> {
+        Box::pin(SqliteStore::run_read_query(
+            self, sql, params, max_rows, timeout_ms,
+        ))
+    }
+
+    fn store_message<'a>(&'a self, msg: &'a NewMessage) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::store_message(self, msg))
+    }
+
+    fn get_recent_conversation<'a>(
+        &'a self,
+        chat_jid: &'a str,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<ConversationMessage>> {
+        Box::pin(SqliteStore::get_recent_conversation(self, chat_jid, limit))
+    }
+
+    fn get_new_messages<'a>(
+        &'a self,
+        jids: &'a [String],
+        last_timestamp: &'a str,
+        bot_prefix: &'a str,
+    ) -> StorageFuture<'a, (Vec<NewMessage>, String)> {
+        Box::pin(SqliteStore::get_new_messages(
+            self,
+            jids,
+            last_timestamp,
+            bot_prefix,
+        ))
+    }
+
+    fn get_messages_since<'a>(
+        &'a self,
+        chat_jid: &'a str,
+        since_timestamp: &'a str,
+        bot_prefix: &'a str,
+    ) -> StorageFuture<'a, Vec<NewMessage>> {
+        Box::pin(SqliteStore::get_messages_since(
+            self,
+            chat_jid,
+            since_timestamp,
+            bot_prefix,
+        ))
+    }
+
+    fn list_messages<'a>(
+        &'a self,
+        filter: &'a MessageFilter,
+    ) -> StorageFuture<'a, Vec<NewMessage>> {
+        Box::pin(SqliteStore::list_messages(self, filter))
+    }
+
+    fn get_replay_batch<'a>(
+        &'a self,
+        chat_jid: &'a str,
+        message_id: &'a str,
+        bot_prefix: &'a str,
+    ) -> StorageFuture<'a, Vec<NewMessage>> {
+        Box::pin(SqliteStore::get_replay_batch(
+            self, chat_jid, message_id, bot_prefix,
+        ))
+    }
+
+    fn get_correlation_trace<'a>(
+        &'a self,
+        correlation_id: &'a str,
+    ) -> StorageFuture<'a, CorrelationTrace> {
+        Box::pin(SqliteStore::get_correlation_trace(self, correlation_id))
+    }
+
+    fn edit_message<'a>(
+        &'a self,
+        id: &'a str,
+        chat_jid: &'a str,
+        content: &'a str,
+        edited_at: &'a str,
+    ) -> StorageFuture<'a, bool> {
+        Box::pin(SqliteStore::edit_message(
+            self, id, chat_jid, content, edited_at,
+        ))
+    }
+
+    fn delete_message<'a>(
+        &'a self,
+        id: &'a str,
+        chat_jid: &'a str,
+        deleted_at: &'a str,
+    ) -> StorageFuture<'a, bool> {
+        Box::pin(SqliteStore::delete_message(self, id, chat_jid, deleted_at))
+    }
+
+    fn get_message_edits<'a>(
+        &'a self,
+        id: &'a str,
+        chat_jid: &'a str,
+    ) -> StorageFuture<'a, Vec<MessageEdit>> {
+        Box::pin(SqliteStore::get_message_edits(self, id, chat_jid))
+    }
+
+    fn store_attachment<'a>(&'a self, attachment: &'a Attachment) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::store_attachment(self, attachment))
+    }
+
+    fn get_attachments<'a>(
+        &'a self,
+        message_id: &'a str,
+        chat_jid: &'a str,
+    ) -> StorageFuture<'a, Vec<Attachment>> {
+        Box::pin(SqliteStore::get_attachments(self, message_id, chat_jid))
+    }
+
+    fn create_task<'a>(&'a self, task: &'a ScheduledTask) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::create_task(self, task))
+    }
+
+    fn get_task_by_id<'a>(&'a self, id: &'a str) -> StorageFuture<'a, Option<ScheduledTask>> {
+        Box::pin(SqliteStore::get_task_by_id(self, id))
+    }
+
+    fn get_tasks_for_group<'a>(
+        &'a self,
+        group_folder: &'a str,
+    ) -> StorageFuture<'a, Vec<ScheduledTask>> {
+        Box::pin(SqliteStore::get_tasks_for_group(self, group_folder))
+    }
+
+    fn get_all_tasks(&self) -> StorageFuture<'_, Vec<ScheduledTask>> {
+        Box::pin(SqliteStore::get_all_tasks(self))
+    }
+
+    fn list_tasks<'a>(&'a self, filter: &'a TaskFilter) -> StorageFuture<'a, Vec<ScheduledTask>> {
+        Box::pin(SqliteStore::list_tasks(self, filter))
+    }
+
+    fn update_task<'a>(&'a self, id: &'a str, updates: &'a TaskUpdate) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::update_task(self, id, updates))
+    }
+
+    fn delete_task<'a>(&'a self, id: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::delete_task(self, id))
+    }
+
+    fn get_due_tasks(&self) -> StorageFuture<'_, Vec<ScheduledTask>> {
+        Box::pin(SqliteStore::get_due_tasks(self))
+    }
+
+    fn set_task_status<'a>(
+        &'a self,
+        id: &'a str,
+        status: &'a str,
+        next_run: Option<&'a str>,
+    ) -> StorageFuture<'a, Option<ScheduledTask>> {
+        Box::pin(SqliteStore::set_task_status(self, id, status, next_run))
+    }
+
+    fn update_task_after_run<'a>(
+        &'a self,
+        id: &'a str,
+        next_run: Option<&'a str>,
+        last_result: &'a str,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::update_task_after_run(
+            self,
+            id,
+            next_run,
+            last_result,
+        ))
+    }
+
+    fn log_task_run<'a>(&'a self, log: &'a TaskRunLog) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::log_task_run(self, log))
+    }
+
+    fn get_task_runs<'a>(
+        &'a self,
+        task_id: &'a str,
+        limit: i64,
+        offset: i64,
+    ) -> StorageFuture<'a, Vec<TaskRunLog>> {
+        Box::pin(SqliteStore::get_task_runs(self, task_id, limit, offset))
+    }
+
+    fn get_task_run_stats<'a>(&'a self, task_id: &'a str) -> StorageFuture<'a, TaskRunStats> {
+        Box::pin(SqliteStore::get_task_run_stats(self, task_id))
+    }
+
+    fn upsert_task_template<'a>(&'a self, template: &'a TaskTemplate) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::upsert_task_template(self, template))
+    }
+
+    fn get_task_template<'a>(&'a self, name: &'a str) -> StorageFuture<'a, Option<TaskTemplate>> {
+        Box::pin(SqliteStore::get_task_template(self, name))
+    }
+
+    fn list_task_templates(&self) -> StorageFuture<'_, Vec<TaskTemplate>> {
+        Box::pin(SqliteStore::list_task_templates(self))
+    }
+
+    fn delete_task_template<'a>(&'a self, name: &'a str) -> StorageFuture<'a, bool> {
+        Box::pin(SqliteStore::delete_task_template(self, name))
+    }
+
+    fn record_container_run<'a>(&'a self, run: &'a ContainerRun) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::record_container_run(self, run))
+    }
+
+    fn list_container_runs<'a>(
+        &'a self,
+        group_folder: Option<&'a str>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<ContainerRun>> {
+        Box::pin(SqliteStore::list_container_runs(self, group_folder, limit))
+    }
+
+    fn usage_totals<'a>(
+        &'a self,
+        group_folder: Option<&'a str>,
+        since: &'a str,
+    ) -> StorageFuture<'a, UsageTotals> {
+        Box::pin(SqliteStore::usage_totals(self, group_folder, since))
+    }
+
+    fn record_demarch_write<'a>(&'a self, entry: &'a DemarchAuditEntry) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::record_demarch_write(self, entry))
+    }
+
+    fn list_demarch_audit<'a>(
+        &'a self,
+        filter: &'a DemarchAuditFilter,
+    ) -> StorageFuture<'a, Vec<DemarchAuditEntry>> {
+        Box::pin(SqliteStore::list_demarch_audit(self, filter))
+    }
+
+    fn get_router_state<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(SqliteStore::get_router_state(self, key))
+    }
+
+    fn set_router_state<'a>(&'a self, key: &'a str, value: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::set_router_state(self, key, value))
+    }
+
+    fn get_session<'a>(&'a self, group_folder: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(SqliteStore::get_session(self, group_folder))
+    }
+
+    fn set_session<'a>(
+        &'a self,
+        group_folder: &'a str,
+        session_id: &'a str,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::set_session(self, group_folder, session_id))
+    }
+
+    fn get_all_sessions(&self) -> StorageFuture<'_, HashMap<String, String>> {
+        Box::pin(SqliteStore::get_all_sessions(self))
+    }
+
+    fn delete_session<'a>(&'a self, group_folder: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::delete_session(self, group_folder))
+    }
+
+    fn get_registered_group<'a>(
+        &'a self,
+        jid: &'a str,
+    ) -> StorageFuture<'a, Option<RegisteredGroup>> {
+        Box::pin(SqliteStore::get_registered_group(self, jid))
+    }
+
+    fn set_registered_group<'a>(&'a self, group: &'a RegisteredGroup) -> StorageFuture<'a, ()> {
+        Box::pin(SqliteStore::set_registered_group(self, group))
+    }
+
+    fn get_all_registered_groups(&self) -> StorageFuture<'_, HashMap<String, RegisteredGroup>> {
+        Box::pin(SqliteStore::get_all_registered_groups(self))
+    }
+
+    fn delete_registered_group<'a>(&'a self, jid: &'a str) -> StorageFuture<'a, bool> {
+        Box::pin(SqliteStore::delete_registered_group(self, jid))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::SortOrder;
+
+    fn message(id: &str, content: &str, timestamp: &str) -> NewMessage {
+        NewMessage {
+            id: id.into(),
+            chat_jid: "tg:1".into(),
+            sender: "u1".into(),
+            sender_name: "Alice".into(),
+            content: content.into(),
+            timestamp: timestamp.into(),
+            is_from_me: false,
+            is_bot_message: false,
+            edited: false,
+            attachments: Vec::new(),
+            correlation_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn messages_roundtrip_with_cursor() {
+        let store = SqliteStore::new(":memory:");
+        store
+            .store_message(&message("1", "hello", "2024-01-15T12:00:00Z"))
+            .await
+            .unwrap();
+        store
+            .store_message(&message(
+                "2",
+                "Amtiskaw: bot echo",
+                "2024-01-15T12:01:00+00:00",
+            ))
+            .await
+            .unwrap();
+        store
+            .store_message(&message("3", "again", "2024-01-15T14:02:00+02:00"))
+            .await
+            .unwrap();
+
+        let jids = vec!["tg:1".to_string()];
+        let (all, cursor) = store.get_new_messages(&jids, "", "Amtiskaw").await.unwrap();
+        assert_eq!(
+            all.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(),
+            ["1", "3"]
+        );
+        assert_eq!(cursor, "2024-01-15T12:02:00.000Z");
+
+        let (none, same) = store
+            .get_new_messages(&jids, &cursor, "Amtiskaw")
+            .await
+            .unwrap();
+        assert!(none.is_empty());
+        assert_eq!(same, cursor);
+
+        let since = store
+            .get_messages_since("tg:1", "2024-01-15T12:00:00.000Z", "Amtiskaw")
+            .await
+            .unwrap();
+        assert_eq!(since.len(), 1);
+        assert_eq!(since[0].id, "3");
+    }
+
+    #[tokio::test]
+    async fn replay_batch_starts_after_previous_reply() {
+        let store = SqliteStore::new(":memory:");
+        store
+            .store_message(&message("1", "first", "2024-01-15T12:00:00Z"))
+            .await
+            .unwrap();
+        store
+            .store_message(&message("2", "Amtiskaw: answer", "2024-01-15T12:01:00Z"))
+            .await
+            .unwrap();
+        store
+            .store_message(&message("3", "second", "2024-01-15T12:02:00Z"))
+            .await
+            .unwrap();
+        store
+            .store_message(&message("4", "third", "2024-01-15T12:03:00Z"))
+            .await
+            .unwrap();
+        store
+            .store_message(&message("5", "later", "2024-01-15T12:04:00Z"))
+            .await
+            .unwrap();
+
+        let ids = |msgs: Vec<NewMessage>| msgs.into_iter().map(|m| m.id).collect::<Vec<_>>();
+        let batch = store
+            .get_replay_batch("tg:1", "4", "Amtiskaw")
+            .await
+            .unwrap();
+        assert_eq!(ids(batch), ["3", "4"]);
+        let batch = store
+            .get_replay_batch("tg:1", "1", "Amtiskaw")
+            .await
+            .unwrap();
+        assert_eq!(ids(batch), ["1"]);
+        assert!(
+            store
+                .get_replay_batch("tg:1", "nope", "Amtiskaw")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            store
+                .get_replay_batch("tg:2", "4", "Amtiskaw")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn message_listing_pages_across_chats() {
+        let store = SqliteStore::new(":memory:");
+        store
+            .store_message(&message("1", "one", "2024-01-15T12:00:00Z"))
+            .await
+            .unwrap();
+        store
+            .store_message(&message("2", "Amtiskaw: reply", "2024-01-15T12:01:00Z"))
+            .await
+            .unwrap();
+        let other = NewMessage {
+            chat_jid: "tg:2".into(),
+            sender: "u2".into(),
+            ..message("3", "two", "2024-01-15T12:02:00Z")
+        };
+        store.store_message(&other).await.unwrap();
+        store
+            .store_message(&message("4", "three", "2024-01-15T12:02:00Z"))
+            .await
+            .unwrap();
+
+        let ids = |msgs: &[NewMessage]| msgs.iter().map(|m| m.id.clone()).collect::<Vec<_>>();
+        let mut filter = MessageFilter {
+            chat_jids: vec!["tg:1".into(), "tg:2".into()],
+            bot_prefix: "Amtiskaw".into(),
+            limit: 2,
+            ..Default::default()
+        };
+        let page = store.list_messages(&filter).await.unwrap();
+        assert_eq!(ids(&page), ["1", "4"]);
+        let last = &page[1];
+        filter.after = Some((
+            last.timestamp.clone(),
+            last.chat_jid.clone(),
+            last.id.clone(),
+        ));
+        assert_eq!(ids(&store.list_messages(&filter).await.unwrap()), ["3"]);
+
+        let filter = MessageFilter {
+            sender: Some("u2".into()),
+            after: None,
+            ..filter
+        };
+        assert_eq!(ids(&store.list_messages(&filter).await.unwrap()), ["3"]);
+        let filter = MessageFilter {
+            since: "2024-01-15T12:00:00.000Z".into(),
+            sender: None,
+            limit: 10,
+            ..filter
+        };
+        assert_eq!(
+            ids(&store.list_messages(&filter).await.unwrap()),
+            ["4", "3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn correlation_trace_collects_messages_replies_and_task_runs() {
+        let store = SqliteStore::new(":memory:");
+        let tagged = |mut msg: NewMessage, id: &str| {
+            msg.correlation_id = Some(id.into());
+            msg
+        };
+        store
+            .store_message(&tagged(message("1", "hi", "2024-01-15T12:00:00Z"), "c1"))
+            .await
+            .unwrap();
+        let mut reply = tagged(message("bot-1", "hello", "2024-01-15T12:00:05Z"), "c1");
+        reply.is_bot_message = true;
+        store.store_message(&reply).await.unwrap();
+        store
+            .store_message(&tagged(message("2", "other", "2024-01-15T12:01:00Z"), "c2"))
+            .await
+            .unwrap();
+        // A re-delivered message keeps the id it was first stored with.
+        store
+            .store_message(&tagged(message("1", "hi", "2024-01-15T12:00:00Z"), "c9"))
+            .await
+            .unwrap();
+
+        let since = store
+            .get_messages_since("tg:1", "", "Amtiskaw")
+            .await
+            .unwrap();
+        assert_eq!(since[0].correlation_id.as_deref(), Some("c1"));
+
+        let trace = store.get_correlation_trace("c1").await.unwrap();
+        assert_eq!(
+            trace
+                .messages
+                .iter()
+                .map(|m| (m.id.as_str(), m.is_bot_message))
+                .collect::<Vec<_>>(),
+            [("1", false), ("bot-1", true)]
+        );
+        assert!(trace.task_runs.is_empty());
+        assert!(
+            store
+                .get_correlation_trace("c9")
+                .await
+                .unwrap()
+                .messages
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn schema_adds_correlation_columns_to_existing_tables() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE messages (id TEXT NOT NULL, chat_jid TEXT NOT NULL, content TEXT, \
+               timestamp TEXT NOT NULL, PRIMARY KEY (id, chat_jid)); \
+             CREATE TABLE task_run_logs (id INTEGER PRIMARY KEY, task_id TEXT NOT NULL, \
+               run_at TEXT NOT NULL, duration_ms INTEGER NOT NULL, status TEXT NOT NULL);",
+        )
+        .unwrap();
+        ensure_schema(&conn).unwrap();
+        ensure_schema(&conn).unwrap();
+        conn.execute("INSERT INTO messages (id, chat_jid, timestamp, correlation_id) VALUES ('1', 'tg:1', 'x', 'c1')", [])
+            .unwrap();
+        conn.execute(
+            "INSERT INTO task_run_logs (task_id, run_at, duration_ms, status, correlation_id) VALUES ('t', 'x', 1, 'ok', 'c1')",
+            [],
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn future_timestamps_and_cursors_are_clamped() {
+        let store = SqliteStore::new(":memory:");
+        store
+            .store_message(&message("1", "skewed", "2999-01-01T00:00:00Z"))
+            .await
+            .unwrap();
+        let jids = vec!["tg:1".to_string()];
+        let (msgs, cursor) = store.get_new_messages(&jids, "", "Bot").await.unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert!(
+            cursor.as_str() < "2999",
+            "stored timestamp was not clamped: {cursor}"
+        );
+
+        // A cursor written by a host running ahead is clamped rather than
+        // compared verbatim.
+        let (again, _) = store
+            .get_new_messages(&jids, "2999-06-01T00:00:00Z", "Bot")
+            .await
+            .unwrap();
+        assert!(again.is_empty());
+        let older = store
+            .get_messages_since("tg:1", "2000-01-01T00:00:00Z", "Bot")
+            .await
+            .unwrap();
+        assert_eq!(older.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn edits_and_tombstones() {
+        let store = SqliteStore::new(":memory:");
+        store
+            .store_message(&message("1", "helo", "2024-01-15T12:00:00Z"))
+            .await
+            .unwrap();
+        assert!(
+            store
+                .edit_message("1", "tg:1", "hello", "2024-01-15T12:01:00Z")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !store
+                .edit_message("9", "tg:1", "x", "2024-01-15T12:01:00Z")
+                .await
+                .unwrap()
+        );
+
+        let msgs = store.get_messages_since("tg:1", "", "Bot").await.unwrap();
+        assert_eq!(msgs[0].content, "hello");
+        assert!(msgs[0].edited);
+        let edits = store.get_message_edits("1", "tg:1").await.unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].previous_content, "helo");
+
+        assert!(
+            store
+                .delete_message("1", "tg:1", "2024-01-15T12:02:00Z")
+                .await
+                .unwrap()
+        );
+        assert!(
+            store
+                .get_messages_since("tg:1", "", "Bot")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            store
+                .get_recent_conversation("tg:1", 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_stats_count_senders_and_skip_deleted() {
+        let store = SqliteStore::new(":memory:");
+        let empty = store.get_chat_stats("tg:1").await.unwrap();
+        assert_eq!(empty.total_messages, 0);
+        assert_eq!(empty.bot_ratio, 0.0);
+        assert!(empty.first_message_at.is_none());
+
+        store
+            .store_message(&message("1", "hi", "2024-01-15T12:00:00Z"))
+            .await
+            .unwrap();
+        store
+            .store_message(&message("2", "again", "2024-01-15T12:01:00Z"))
+            .await
+            .unwrap();
+        store
+            .store_message(&message("3", "oops", "2024-01-15T12:02:00Z"))
+            .await
+            .unwrap();
+        let mut reply = message("4", "hello", "2024-01-15T12:03:00Z");
+        reply.sender = "bot".into();
+        reply.sender_name = "Amtiskaw".into();
+        reply.is_bot_message = true;
+        store.store_message(&reply).await.unwrap();
+        store
+            .delete_message("3", "tg:1", "2024-01-15T12:04:00Z")
+            .await
+            .unwrap();
+
+        let stats = store.get_chat_stats("tg:1").await.unwrap();
+        assert_eq!(stats.total_messages, 3);
+        assert_eq!(stats.bot_messages, 1);
+        assert_eq!(stats.human_messages, 2);
+        assert!((stats.bot_ratio - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            stats.first_message_at.as_deref(),
+            Some("2024-01-15T12:00:00.000Z")
+        );
+        assert_eq!(
+            stats.last_message_at.as_deref(),
+            Some("2024-01-15T12:03:00.000Z")
+        );
+        assert_eq!(stats.senders.len(), 2);
+        assert_eq!(stats.senders[0].sender, "u1");
+        assert_eq!(stats.senders[0].message_count, 2);
+        assert!(!stats.senders[0].is_bot);
+        assert!(stats.senders[1].is_bot);
+    }
+
+    #[tokio::test]
+    async fn read_query_binds_text_params_and_caps_rows() {
+        let store = SqliteStore::new(":memory:");
+        for (id, ts) in [
+            ("1", "2024-01-15T12:00:00Z"),
+            ("2", "2024-01-15T12:01:00Z"),
+            ("3", "2024-01-15T12:02:00Z"),
+        ] {
+            store.store_message(&message(id, "hi", ts)).await.unwrap();
+        }
+
+        let sql = "SELECT id, length(content) AS len FROM messages WHERE chat_jid = $2 AND timestamp >= $1 ORDER BY id;";
+        let params = [
+            Some("2024-01-15T12:01:00".to_string()),
+            Some("tg:1".to_string()),
+        ];
+        let all = store.run_read_query(sql, &params, 10, 0).await.unwrap();
+        assert_eq!(all.columns, ["id", "len"]);
+        assert_eq!(
+            all.rows,
+            [
+                serde_json::json!({"id": "2", "len": 2}),
+                serde_json::json!({"id": "3", "len": 2})
+            ]
+        );
+        assert!(!all.truncated);
+
+        let capped = store.run_read_query(sql, &params, 1, 0).await.unwrap();
+        assert_eq!(capped.rows.len(), 1);
+        assert!(capped.truncated);
+
+        let write = store
+            .run_read_query("DELETE FROM messages", &[], 10, 0)
+            .await;
+        assert!(write.unwrap_err().to_string().contains("read-only"));
+    }
+
+    #[tokio::test]
+    async fn captionless_attachments_reach_the_prompt() {
+        let store = SqliteStore::new(":memory:");
+        let mut photo = message("1", "", "2024-01-15T12:00:00Z");
+        photo.attachments.push(Attachment {
+            kind: "photo".into(),
+            mime_type: Some("image/jpeg".into()),
+            file_path: Some("media/1.jpg".into()),
+            ..Default::default()
+        });
+        store.store_message(&photo).await.unwrap();
+        store
+            .store_message(&message("2", "", "2024-01-15T12:01:00Z"))
+            .await
+            .unwrap();
+
+        let msgs = store.get_messages_since("tg:1", "", "Bot").await.unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].attachments.len(), 1);
+        assert_eq!(msgs[0].attachments[0].message_id, "1");
+        assert_eq!(
+            msgs[0].attachments[0].file_path.as_deref(),
+            Some("media/1.jpg")
+        );
+
+        // Re-storing the message replaces rather than duplicates attachments.
+        store.store_message(&photo).await.unwrap();
+        assert_eq!(store.get_attachments("1", "tg:1").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn task_templates_are_replaced_by_name() {
+        let store = SqliteStore::new(":memory:");
+        let mut template = TaskTemplate {
+            name: "standup".into(),
+            prompt: "Post the standup".into(),
+            schedule_type: "cron".into(),
+            schedule_value: "0 9 * * 1-5".into(),
+            context_mode: "isolated".into(),
+            task_config: Some(serde_json::json!({"jitterMs": 60000})),
+            created_at: String::new(),
+            updated_at: String::new(),
+        };
+        store.upsert_task_template(&template).await.unwrap();
+        template.schedule_value = "30 9 * * 1-5".into();
+        store.upsert_task_template(&template).await.unwrap();
+
+        let templates = store.list_task_templates().await.unwrap();
+        assert_eq!(templates.len(), 1);
+        let stored = store.get_task_template("standup").await.unwrap().unwrap();
+        assert_eq!(stored.schedule_value, "30 9 * * 1-5");
+        assert_eq!(stored.task_config, template.task_config);
+        assert!(!stored.created_at.is_empty());
+        assert!(store.delete_task_template("standup").await.unwrap());
+        assert!(!store.delete_task_template("standup").await.unwrap());
+        assert!(store.get_task_template("standup").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn tasks_state_and_groups() {
+        let store = SqliteStore::new(":memory:");
+        let task = ScheduledTask {
+            id: "t1".into(),
+            group_folder: "main".into(),
+            chat_jid: "tg:1".into(),
+            prompt: "ping".into(),
+            schedule_type: "once".into(),
+            schedule_value: "2024-01-01T00:00:00Z".into(),
+            context_mode: "isolated".into(),
+            next_run: Some("2024-01-01T00:00:00Z".into()),
+            last_run: None,
+            last_result: None,
+            status: "active".into(),
+            created_at: "2024-01-01T00:00:00Z".into(),
+            task_config: None,
+        };
+        store.create_task(&task).await.unwrap();
+        let due = store.get_due_tasks().await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].next_run.as_deref(), Some("2024-01-01T00:00:00.000Z"));
+
+        let paused = store
+            .set_task_status("t1", "paused", None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(paused.status, "paused");
+        assert!(store.get_due_tasks().await.unwrap().is_empty());
+        let resumed = store
+            .set_task_status("t1", "active", Some("2024-01-02T00:00:00Z"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            resumed.next_run.as_deref(),
+            Some("2024-01-02T00:00:00.000Z")
+        );
+        assert!(
+            store
+                .set_task_status("missing", "paused", None)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        store
+            .update_task_after_run("t1", None, "done")
+            .await
+            .unwrap();
+        let task = store.get_task_by_id("t1").await.unwrap().unwrap();
+        assert_eq!(task.status, "completed");
+        // Completed tasks cannot be paused or revived.
+        assert!(
+            store
+                .set_task_status("t1", "active", None)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(task.last_result.as_deref(), Some("done"));
+
+        store
+            .log_task_run(&TaskRunLog {
+                task_id: "t1".into(),
+                run_at: "2024-01-01T00:00:01Z".into(),
+                duration_ms: 1200,
+                status: "success".into(),
+                result: Some("done".into()),
+                error: None,
+                correlation_id: Some("c-task".into()),
+            })
+            .await
+            .unwrap();
+        store
+            .log_task_run(&TaskRunLog {
+                task_id: "t1".into(),
+                run_at: "2024-01-01T00:01:00Z".into(),
+                duration_ms: 800,
+                status: "error".into(),
+                result: None,
+                error: Some("boom".into()),
+                correlation_id: None,
+            })
+            .await
+            .unwrap();
+        let runs = store.get_task_runs("t1", 5, 0).await.unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].status, "error");
+        let older = store.get_task_runs("t1", 5, 1).await.unwrap();
+        assert_eq!(older.len(), 1);
+        assert_eq!(older[0].status, "success");
+        assert!(store.get_task_runs("t2", 5, 0).await.unwrap().is_empty());
+        let stats = store.get_task_run_stats("t1").await.unwrap();
+        assert_eq!((stats.runs, stats.successes, stats.failures), (2, 1, 1));
+        assert_eq!(stats.success_rate, 0.5);
+        assert_eq!(stats.avg_duration_ms, Some(1000.0));
+        assert_eq!(
+            stats.last_run_at.as_deref(),
+            Some("2024-01-01T00:01:00.000Z")
+        );
+        assert_eq!(stats.last_error.as_deref(), Some("boom"));
+        assert_eq!(stats.last_error_at, stats.last_run_at);
+        let never = store.get_task_run_stats("t2").await.unwrap();
+        assert_eq!(
+            (never.runs, never.success_rate, never.avg_duration_ms),
+            (0, 0.0, None)
+        );
+        let trace = store.get_correlation_trace("c-task").await.unwrap();
+        assert_eq!(trace.task_runs.len(), 1);
+        assert_eq!(trace.task_runs[0].run_at, "2024-01-01T00:00:01.000Z");
+        assert!(store.get_due_tasks().await.unwrap().is_empty());
+
+        store.set_router_state("k", "v1").await.unwrap();
+        store.set_router_state("k", "v2").await.unwrap();
+        assert_eq!(
+            store.get_router_state("k").await.unwrap().as_deref(),
+            Some("v2")
+        );
+
+        let group = RegisteredGroup {
+            jid: "tg:1".into(),
+            name: "Main".into(),
+            folder: "main".into(),
+            trigger: "@Amtiskaw".into(),
+            added_at: "2024-01-01T00:00:00Z".into(),
+            container_config: Some(serde_json::json!({"timeout": 60})),
+            requires_trigger: Some(false),
+            runtime: None,
+            model: None,
+            muted_until: None,
+            locale: None,
+        };
+        store.set_registered_group(&group).await.unwrap();
+        let groups = store.get_all_registered_groups().await.unwrap();
+        let loaded = &groups["tg:1"];
+        assert_eq!(loaded.requires_trigger, Some(false));
+        assert_eq!(loaded.container_config, group.container_config);
+        assert!(store.delete_registered_group("tg:1").await.unwrap());
+        assert!(!store.delete_registered_group("tg:1").await.unwrap());
+        assert!(store.get_registered_group("tg:1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn task_listing_filters_sorts_and_pages() {
+        let store = SqliteStore::new(":memory:");
+        for (id, folder, status, created_at) in [
+            ("t1", "main", "active", "2024-01-01T00:00:00Z"),
+            ("t2", "main", "paused", "2024-01-02T00:00:00Z"),
+            ("t3", "ops", "active", "2024-01-03T00:00:00Z"),
+            ("t4", "main", "active", "2024-01-03T00:00:00Z"),
+        ] {
+            let task = ScheduledTask {
+                id: id.into(),
+                group_folder: folder.into(),
+                chat_jid: format!("tg:{folder}"),
+                prompt: "ping".into(),
+                schedule_type: "interval".into(),
+                schedule_value: "60000".into(),
+                context_mode: "isolated".into(),
+                next_run: None,
+                last_run: None,
+                last_result: None,
+                status: status.into(),
+                created_at: created_at.into(),
+                task_config: None,
+            };
+            store.create_task(&task).await.unwrap();
+        }
+
+        let ids = |tasks: Vec<ScheduledTask>| tasks.into_iter().map(|t| t.id).collect::<Vec<_>>();
+        let mut filter = TaskFilter {
+            limit: 2,
+            ..Default::default()
+        };
+        let page = store.list_tasks(&filter).await.unwrap();
+        let last = page.last().unwrap();
+        filter.after = Some((last.created_at.clone(), last.id.clone()));
+        assert_eq!(ids(page), ["t4", "t3"]);
+        assert_eq!(ids(store.list_tasks(&filter).await.unwrap()), ["t2", "t1"]);
+
+        let filter = TaskFilter {
+            order: SortOrder::Asc,
+            group_folder: Some("main".into()),
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(store.list_tasks(&filter).await.unwrap()),
+            ["t1", "t2", "t4"]
+        );
+        let filter = TaskFilter {
+            status: Some("active".into()),
+            ..filter
+        };
+        assert_eq!(ids(store.list_tasks(&filter).await.unwrap()), ["t1", "t4"]);
+        let filter = TaskFilter {
+            chat_jid: Some("tg:ops".into()),
+            group_folder: None,
+            ..filter
+        };
+        assert_eq!(ids(store.list_tasks(&filter).await.unwrap()), ["t3"]);
+    }
+
+    #[tokio::test]
+    async fn container_runs_list_newest_first() {
+        let store = SqliteStore::new(":memory:");
+        for (i, group) in ["main", "team", "main"].iter().enumerate() {
+            let run = ContainerRun {
+                run_id: Some(format!("intercom-{group}-{i}")),
+                group_folder: group.to_string(),
+                chat_jid: "tg:1".into(),
+                trigger: "message".into(),
+                started_at: format!("2024-01-01T00:00:0{i}Z"),
+                finished_at: format!("2024-01-01T00:00:0{i}.5Z"),
+                duration_ms: 500,
+                exit_code: Some(0),
+                status: "success".into(),
+                output_bytes: 42,
+                ..ContainerRun::default()
+            };
+            store.record_container_run(&run).await.unwrap();
+        }
+        store
+            .record_container_run(&ContainerRun {
+                group_folder: "main".into(),
+                chat_jid: "tg:1".into(),
+                trigger: "task".into(),
+                started_at: "2024-01-01T00:00:09Z".into(),
+                finished_at: "2024-01-01T00:00:09Z".into(),
+                timed_out: true,
+                status: "error".into(),
+                error: Some("spawn failed".into()),
+                ..ContainerRun::default()
+            })
+            .await
+            .unwrap();
+
+        let runs = store.list_container_runs(Some("main"), 10).await.unwrap();
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].run_id, None);
+        assert!(runs[0].timed_out);
+        assert_eq!(runs[1].run_id.as_deref(), Some("intercom-main-2"));
+        assert_eq!(runs[1].started_at, "2024-01-01T00:00:02.000Z");
+        assert_eq!(runs[1].output_bytes, 42);
+        assert_eq!(store.list_container_runs(None, 2).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn usage_totals_sum_runs_since() {
+        let store = SqliteStore::new(":memory:");
+        for (day, group, cost) in [
+            (1, "main", Some(0.5)),
+            (5, "main", None),
+            (6, "main", Some(0.25)),
+            (6, "team", Some(9.0)),
+        ] {
+            let run = ContainerRun {
+                group_folder: group.into(),
+                chat_jid: "tg:1".into(),
+                trigger: "message".into(),
+                started_at: format!("2024-01-0{day}T12:00:00Z"),
+                finished_at: format!("2024-01-0{day}T12:01:00Z"),
+                duration_ms: 60_000,
+                status: if cost.is_some() { "success" } else { "error" }.into(),
+                input_tokens: 1_000,
+                output_tokens: 200,
+                cache_read_tokens: 50,
+                cost_usd: cost,
+                ..ContainerRun::default()
+            };
+            store.record_container_run(&run).await.unwrap();
+        }
+
+        let totals = store
+            .usage_totals(Some("main"), "2024-01-05T00:00:00+00:00")
+            .await
+            .unwrap();
+        assert_eq!(
+            totals,
+            UsageTotals {
+                runs: 2,
+                input_tokens: 2_000,
+                output_tokens: 400,
+                cache_read_tokens: 100,
+                cache_write_tokens: 0,
+                cost_usd: 0.25,
+                unpriced_runs: 1,
+                succeeded_runs: 1,
+                duration_ms: 120_000,
+            }
+        );
+        assert_eq!(
+            store
+                .usage_totals(None, "2024-01-01T00:00:00Z")
+                .await
+                .unwrap()
+                .runs,
+            4
+        );
+        let none = store
+            .usage_totals(Some("main"), "2025-01-01T00:00:00Z")
+            .await
+            .unwrap();
+        assert_eq!(none, UsageTotals::default());
+    }
+
+    #[tokio::test]
+    async fn store_message_wakes_listeners() {
+        let store = SqliteStore::new(":memory:");
+        let mut rx = store.listen(NEW_MESSAGE_CHANNEL).await.unwrap();
+        store
+            .store_message(&message("1", "hi", "2024-01-15T12:00:00Z"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap(), "tg:1");
+    }
+}
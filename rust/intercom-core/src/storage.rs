@@ -0,0 +1,556 @@
+//! Backend-neutral persistence interface.
+//!
+//! `Storage` covers the full `PgPool` method surface so intercomd can run
+//! against Postgres or a local SQLite file (`storage.backend = "sqlite"`)
+//! without knowing which one it has.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+
+use crate::circuit::CircuitSnapshot;
+use crate::persistence::{
+    Attachment, ChatInfo, ChatStats, ContainerRun, ConversationMessage, CorrelationTrace,
+    DemarchAuditEntry, DemarchAuditFilter, MessageEdit, MessageFilter, NewMessage, PgPool,
+    QueryResult, RegisteredGroup, ScheduledTask, TaskFilter, TaskRunLog, TaskRunStats,
+    TaskTemplate, TaskUpdate, UsageTotals,
+};
+
+/// Boxed future returned by every `Storage` method (keeps the trait object-safe).
+pub type StorageFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// Shared handle to whichever backend `storage.backend` selected.
+pub type SharedStorage = Arc<dyn Storage>;
+
+pub trait Storage: Send + Sync {
+    /// Short backend name for logs and readiness output.
+    fn backend(&self) -> &'static str;
+
+    /// Open the backend and make sure the schema exists.
+    fn connect(&self) -> StorageFuture<'_, ()>;
+
+    /// Probe the backend, reconnecting if the connection was lost.
+    fn health_check(&self) -> StorageFuture<'_, ()>;
+
+    /// Connection circuit-breaker state; `None` for backends without one.
+    fn circuit(&self) -> Option<CircuitSnapshot>;
+
+    /// Subscribe to change notifications on `channel` (see
+    /// [`crate::NEW_MESSAGE_CHANNEL`]). The receiver yields `None` once the
+    /// subscription is lost.
+    fn listen<'a>(&'a self, channel: &'a str)
+    -> StorageFuture<'a, mpsc::UnboundedReceiver<String>>;
+
+    // Chats
+    fn store_chat_metadata<'a>(
+        &'a self,
+        jid: &'a str,
+        timestamp: &'a str,
+        name: Option<&'a str>,
+        channel: Option<&'a str>,
+        is_group: Option<bool>,
+    ) -> StorageFuture<'a, ()>;
+    fn update_chat_name<'a>(&'a self, jid: &'a str, name: &'a str) -> StorageFuture<'a, ()>;
+    fn get_all_chats(&self) -> StorageFuture<'_, Vec<ChatInfo>>;
+    fn get_chat_stats<'a>(&'a self, chat_jid: &'a str) -> StorageFuture<'a, ChatStats>;
+
+    // Reports
+    fn run_read_query<'a>(
+        &'a self,
+        sql: &'a str,
+        params: &'a [Option<String>],
+        max_rows: usize,
+        timeout_ms: u64,
+    ) -> StorageFuture<'a, QueryResult>;
+
+    // Messages
+    fn store_message<'a>(&'a self, msg: &'a NewMessage) -> StorageFuture<'a, ()>;
+    fn get_recent_conversation<'a>(
+        &'a self,
+        chat_jid: &'a str,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<ConversationMessage>>;
+    fn get_new_messages<'a>(
+        &'a self,
+        jids: &'a [String],
+        last_timestamp: &'a str,
+        bot_prefix: &'a str,
+    ) -> StorageFuture<'a, (Vec<NewMessage>, String)>;
+    fn get_messages_since<'a>(
+        &'a self,
+        chat_jid: &'a str,
+        since_timestamp: &'a str,
+        bot_prefix: &'a str,
+    ) -> StorageFuture<'a, Vec<NewMessage>>;
+    /// A bounded page of user messages; see [`MessageFilter`].
+    fn list_messages<'a>(&'a self, filter: &'a MessageFilter)
+    -> StorageFuture<'a, Vec<NewMessage>>;
+    fn get_replay_batch<'a>(
+        &'a self,
+        chat_jid: &'a str,
+        message_id: &'a str,
+        bot_prefix: &'a str,
+    ) -> StorageFuture<'a, Vec<NewMessage>>;
+    fn get_correlation_trace<'a>(
+        &'a self,
+        correlation_id: &'a str,
+    ) -> StorageFuture<'a, CorrelationTrace>;
+    fn edit_message<'a>(
+        &'a self,
+        id: &'a str,
+        chat_jid: &'a str,
+        content: &'a str,
+        edited_at: &'a str,
+    ) -> StorageFuture<'a, bool>;
+    fn delete_message<'a>(
+        &'a self,
+        id: &'a str,
+        chat_jid: &'a str,
+        deleted_at: &'a str,
+    ) -> StorageFuture<'a, bool>;
+    fn get_message_edits<'a>(
+        &'a self,
+        id: &'a str,
+        chat_jid: &'a str,
+    ) -> StorageFuture<'a, Vec<MessageEdit>>;
+
+    // Attachments
+    fn store_attachment<'a>(&'a self, attachment: &'a Attachment) -> StorageFuture<'a, ()>;
+    fn get_attachments<'a>(
+        &'a self,
+        message_id: &'a str,
+        chat_jid: &'a str,
+    ) -> StorageFuture<'a, Vec<Attachment>>;
+
+    // Scheduled tasks
+    fn create_task<'a>(&'a self, task: &'a ScheduledTask) -> StorageFuture<'a, ()>;
+    fn get_task_by_id<'a>(&'a self, id: &'a str) -> StorageFuture<'a, Option<ScheduledTask>>;
+    fn get_tasks_for_group<'a>(
+        &'a self,
+        group_folder: &'a str,
+    ) -> StorageFuture<'a, Vec<ScheduledTask>>;
+    fn get_all_tasks(&self) -> StorageFuture<'_, Vec<ScheduledTask>>;
+    /// A bounded page of tasks; see [`TaskFilter`].
+    fn list_tasks<'a>(&'a self, filter: &'a TaskFilter) -> StorageFuture<'a, Vec<ScheduledTask>>;
+    fn update_task<'a>(&'a self, id: &'a str, updates: &'a TaskUpdate) -> StorageFuture<'a, ()>;
+    fn delete_task<'a>(&'a self, id: &'a str) -> StorageFuture<'a, ()>;
+    fn get_due_tasks(&self) -> StorageFuture<'_, Vec<ScheduledTask>>;
+    /// Set a task's status (and next run, if given) unless it has
+    /// completed; `None` when there is no such task to update.
+    fn set_task_status<'a>(
+        &'a self,
+        id: &'a str,
+        status: &'a str,
+        next_run: Option<&'a str>,
+    ) -> StorageFuture<'a, Option<ScheduledTask>>;
+    fn update_task_after_run<'a>(
+        &'a self,
+        id: &'a str,
+        next_run: Option<&'a str>,
+        last_result: &'a str,
+    ) -> StorageFuture<'a, ()>;
+    fn log_task_run<'a>(&'a self, log: &'a TaskRunLog) -> StorageFuture<'a, ()>;
+    /// A task's most recent runs, newest first.
+    fn get_task_runs<'a>(
+        &'a self,
+        task_id: &'a str,
+        limit: i64,
+        offset: i64,
+    ) -> StorageFuture<'a, Vec<TaskRunLog>>;
+    fn get_task_run_stats<'a>(&'a self, task_id: &'a str) -> StorageFuture<'a, TaskRunStats>;
+
+    // Task templates
+    fn upsert_task_template<'a>(&'a self, template: &'a TaskTemplate) -> StorageFuture<'a, ()>;
+    fn get_task_template<'a>(&'a self, name: &'a str) -> StorageFuture<'a, Option<TaskTemplate>>;
+    fn list_task_templates(&self) -> StorageFuture<'_, Vec<TaskTemplate>>;
+    /// `false` when there was no such template.
+    fn delete_task_template<'a>(&'a self, name: &'a str) -> StorageFuture<'a, bool>;
+
+    // Container runs
+    fn record_container_run<'a>(&'a self, run: &'a ContainerRun) -> StorageFuture<'a, ()>;
+    fn list_container_runs<'a>(
+        &'a self,
+        group_folder: Option<&'a str>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<ContainerRun>>;
+    /// Runs started since `since`, summed.
+    fn usage_totals<'a>(
+        &'a self,
+        group_folder: Option<&'a str>,
+        since: &'a str,
+    ) -> StorageFuture<'a, UsageTotals>;
+
+    // Demarch audit
+    fn record_demarch_write<'a>(&'a self, entry: &'a DemarchAuditEntry) -> StorageFuture<'a, ()>;
+    /// Newest entries first.
+    fn list_demarch_audit<'a>(
+        &'a self,
+        filter: &'a DemarchAuditFilter,
+    ) -> StorageFuture<'a, Vec<DemarchAuditEntry>>;
+
+    // Router state
+    fn get_router_state<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>>;
+    fn set_router_state<'a>(&'a self, key: &'a str, value: &'a str) -> StorageFuture<'a, ()>;
+
+    // Sessions
+    fn get_session<'a>(&'a self, group_folder: &'a str) -> StorageFuture<'a, Option<String>>;
+    fn set_session<'a>(
+        &'a self,
+        group_folder: &'a str,
+        session_id: &'a str,
+    ) -> StorageFuture<'a, ()>;
+    fn get_all_sessions(&self) -> StorageFuture<'_, HashMap<String, String>>;
+    fn delete_session<'a>(&'a self, group_folder: &'a str) -> StorageFuture<'a, ()>;
+
+    // Registered groups
+    fn get_registered_group<'a>(
+        &'a self,
+        jid: &'a str,
+    ) -> StorageFuture<'a, Option<RegisteredGroup>>;
+    fn set_registered_group<'a>(&'a self, group: &'a RegisteredGroup) -> StorageFuture<'a, ()>;
+    fn get_all_registered_groups(&self) -> StorageFuture<'_, HashMap<String, RegisteredGroup>>;
+    /// Remove a group's registration; its messages, tasks and folder stay.
+    fn delete_registered_group<'a>(&'a self, jid: &'a str) -> StorageFuture<'a, bool>;
+}
+
+// ---------------------------------------------------------------------------
+// Postgres — delegate to the inherent PgPool methods
+// ---------------------------------------------------------------------------
+
+impl Storage for PgPool {
+    fn backend(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn connect(&self) -> StorageFuture<'_, ()> {
+        Box::pin(PgPool::connect(self))
+    }
+
+    fn health_check(&self) -> StorageFuture<'_, ()> {
+        Box::pin(PgPool::health_check(self))
+    }
+
+    fn circuit(&self) -> Option<CircuitSnapshot> {
+        Some(PgPool::circuit(self))
+    }
+
+    fn listen<'a>(
+        &'a self,
+        channel: &'a str,
+    ) -> StorageFuture<'a, mpsc::UnboundedReceiver<String>> {
+        Box::pin(PgPool::listen(self, channel))
+    }
+
+    fn store_chat_metadata<'a>(
+        &'a self,
+        jid: &'a str,
+        timestamp: &'a str,
+        name: Option<&'a str>,
+        channel: Option<&'a str>,
+        is_group: Option<bool>,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::store_chat_metadata(
+            self, jid, timestamp, name, channel, is_group,
+        ))
+    }
+
+    fn update_chat_name<'a>(&'a self, jid: &'a str, name: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::update_chat_name(self, jid, name))
+    }
+
+    fn get_all_chats(&self) -> StorageFuture<'_, Vec<ChatInfo>> {
+        Box::pin(PgPool::get_all_chats(self))
+    }
+
+    fn get_chat_stats<'a>(&'a self, chat_jid: &'a str) -> StorageFuture<'a, ChatStats> {
+        Box::pin(PgPool::get_chat_stats(self, chat_jid))
+    }
+
+    fn run_read_query<'a>(
+        &'a self,
+        sql: &'a str,
+        params: &'a [Option<String>],
+        max_rows: usize,
+        timeout_ms: u64,
+    ) -> StorageFuture<'a, QueryResult> {
+        Box::pin(PgPool::run_read_query(
+            self, sql, params, max_rows, timeout_ms,
+        ))
+    }
+
+    fn store_message<'a>(&'a self, msg: &'a NewMessage) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::store_message(self, msg))
+    }
+
+    fn get_recent_conversation<'a>(
+        &'a self,
+        chat_jid: &'a str,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<ConversationMessage>> {
+        Box::pin(PgPool::get_recent_conversation(self, chat_jid, limit))
+    }
+
+    fn get_new_messages<'a>(
+        &'a self,
+        jids: &'a [String],
+        last_timestamp: &'a str,
+        bot_prefix: &'a str,
+    ) -> StorageFuture<'a, (Vec<NewMessage>, String)> {
+        Box::pin(PgPool::get_new_messages(
+            self,
+            jids,
+            last_timestamp,
+            bot_prefix,
+        ))
+    }
+
+    fn get_messages_since<'a>(
+        &'a self,
+        chat_jid: &'a str,
+        since_timestamp: &'a str,
+        bot_prefix: &'a str,
+    ) -> StorageFuture<'a, Vec<NewMessage>> {
+        Box::pin(PgPool::get_messages_since(
+            self,
+            chat_jid,
+            since_timestamp,
+            bot_prefix,
+        ))
+    }
+
+    fn list_messages<'a>(
+        &'a self,
+        filter: &'a MessageFilter,
+    ) -> StorageFuture<'a, Vec<NewMessage>> {
+        Box::pin(PgPool::list_messages(self, filter))
+    }
+
+    fn get_replay_batch<'a>(
+        &'a self,
+        chat_jid: &'a str,
+        message_id: &'a str,
+        bot_prefix: &'a str,
+    ) -> StorageFuture<'a, Vec<NewMessage>> {
+        Box::pin(PgPool::get_replay_batch(
+            self, chat_jid, message_id, bot_prefix,
+        ))
+    }
+
+    fn get_correlation_trace<'a>(
+        &'a self,
+        correlation_id: &'a str,
+    ) -> StorageFuture<'a, CorrelationTrace> {
+        Box::pin(PgPool::get_correlation_trace(self, correlation_id))
+    }
+
+    fn edit_message<'a>(
+        &'a self,
+        id: &'a str,
+        chat_jid: &'a str,
+        content: &'a str,
+        edited_at: &'a str,
+    ) -> StorageFuture<'a, bool> {
+        Box::pin(PgPool::edit_message(self, id, chat_jid, content, edited_at))
+    }
+
+    fn delete_message<'a>(
+        &'a self,
+        id: &'a str,
+        chat_jid: &'a str,
+        deleted_at: &'a str,
+    ) -> StorageFuture<'a, bool> {
+        Box::pin(PgPool::delete_message(self, id, chat_jid, deleted_at))
+    }
+
+    fn get_message_edits<'a>(
+        &'a self,
+        id: &'a str,
+        chat_jid: &'a str,
+    ) -> StorageFuture<'a, Vec<MessageEdit>> {
+        Box::pin(PgPool::get_message_edits(self, id, chat_jid))
+    }
+
+    fn store_attachment<'a>(&'a self, attachment: &'a Attachment) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::store_attachment(self, attachment))
+    }
+
+    fn get_attachments<'a>(
+        &'a self,
+        message_id: &'a str,
+        chat_jid: &'a str,
+    ) -> StorageFuture<'a, Vec<Attachment>> {
+        Box::pin(PgPool::get_attachments(self, message_id, chat_jid))
+    }
+
+    fn create_task<'a>(&'a self, task: &'a ScheduledTask) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::create_task(self, task))
+    }
+
+    fn get_task_by_id<'a>(&'a self, id: &'a str) -> StorageFuture<'a, Option<ScheduledTask>> {
+        Box::pin(PgPool::get_task_by_id(self, id))
+    }
+
+    fn get_tasks_for_group<'a>(
+        &'a self,
+        group_folder: &'a str,
+    ) -> StorageFuture<'a, Vec<ScheduledTask>> {
+        Box::pin(PgPool::get_tasks_for_group(self, group_folder))
+    }
+
+    fn get_all_tasks(&self) -> StorageFuture<'_, Vec<ScheduledTask>> {
+        Box::pin(PgPool::get_all_tasks(self))
+    }
+
+    fn list_tasks<'a>(&'a self, filter: &'a TaskFilter) -> StorageFuture<'a, Vec<ScheduledTask>> {
+        Box::pin(PgPool::list_tasks(self, filter))
+    }
+
+    fn update_task<'a>(&'a self, id: &'a str, updates: &'a TaskUpdate) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::update_task(self, id, updates))
+    }
+
+    fn delete_task<'a>(&'a self, id: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::delete_task(self, id))
+    }
+
+    fn get_due_tasks(&self) -> StorageFuture<'_, Vec<ScheduledTask>> {
+        Box::pin(PgPool::get_due_tasks(self))
+    }
+
+    fn set_task_status<'a>(
+        &'a self,
+        id: &'a str,
+        status: &'a str,
+        next_run: Option<&'a str>,
+    ) -> StorageFuture<'a, Option<ScheduledTask>> {
+        Box::pin(PgPool::set_task_status(self, id, status, next_run))
+    }
+
+    fn update_task_after_run<'a>(
+        &'a self,
+        id: &'a str,
+        next_run: Option<&'a str>,
+        last_result: &'a str,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::update_task_after_run(
+            self,
+            id,
+            next_run,
+            last_result,
+        ))
+    }
+
+    fn log_task_run<'a>(&'a self, log: &'a TaskRunLog) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::log_task_run(self, log))
+    }
+
+    fn get_task_runs<'a>(
+        &'a self,
+        task_id: &'a str,
+        limit: i64,
+        offset: i64,
+    ) -> StorageFuture<'a, Vec<TaskRunLog>> {
+        Box::pin(PgPool::get_task_runs(self, task_id, limit, offset))
+    }
+
+    fn get_task_run_stats<'a>(&'a self, task_id: &'a str) -> StorageFuture<'a, TaskRunStats> {
+        Box::pin(PgPool::get_task_run_stats(self, task_id))
+    }
+
+    fn upsert_task_template<'a>(&'a self, template: &'a TaskTemplate) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::upsert_task_template(self, template))
+    }
+
+    fn get_task_template<'a>(&'a self, name: &'a str) -> StorageFuture<'a, Option<TaskTemplate>> {
+        Box::pin(PgPool::get_task_template(self, name))
+    }
+
+    fn list_task_templates(&self) -> StorageFuture<'_, Vec<TaskTemplate>> {
+        Box::pin(PgPool::list_task_templates(self))
+    }
+
+    fn delete_task_template<'a>(&'a self, name: &'a str) -> StorageFuture<'a, bool> {
+        Box::pin(PgPool::delete_task_template(self, name))
+    }
+
+    fn record_container_run<'a>(&'a self, run: &'a ContainerRun) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::record_container_run(self, run))
+    }
+
+    fn list_container_runs<'a>(
+        &'a self,
+        group_folder: Option<&'a str>,
+        limit: i64,
+    ) -> StorageFuture<'a, Vec<ContainerRun>> {
+        Box::pin(PgPool::list_container_runs(self, group_folder, limit))
+    }
+
+    fn usage_totals<'a>(
+        &'a self,
+        group_folder: Option<&'a str>,
+        since: &'a str,
+    ) -> StorageFuture<'a, UsageTotals> {
+        Box::pin(PgPool::usage_totals(self, group_folder, since))
+    }
+
+    fn record_demarch_write<'a>(&'a self, entry: &'a DemarchAuditEntry) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::record_demarch_write(self, entry))
+    }
+
+    fn list_demarch_audit<'a>(
+        &'a self,
+        filter: &'a DemarchAuditFilter,
+    ) -> StorageFuture<'a, Vec<DemarchAuditEntry>> {
+        Box::pin(PgPool::list_demarch_audit(self, filter))
+    }
+
+    fn get_router_state<'a>(&'a self, key: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(PgPool::get_router_state(self, key))
+    }
+
+    fn set_router_state<'a>(&'a self, key: &'a str, value: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::set_router_state(self, key, value))
+    }
+
+    fn get_session<'a>(&'a self, group_folder: &'a str) -> StorageFuture<'a, Option<String>> {
+        Box::pin(PgPool::get_session(self, group_folder))
+    }
+
+    fn set_session<'a>(
+        &'a self,
+        group_folder: &'a str,
+        session_id: &'a str,
+    ) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::set_session(self, group_folder, session_id))
+    }
+
+    fn get_all_sessions(&self) -> StorageFuture<'_, HashMap<String, String>> {
+        Box::pin(PgPool::get_all_sessions(self))
+    }
+
+    fn delete_session<'a>(&'a self, group_folder: &'a str) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::delete_session(self, group_folder))
+    }
+
+    fn get_registered_group<'a>(
+        &'a self,
+        jid: &'a str,
+    ) -> StorageFuture<'a, Option<RegisteredGroup>> {
+        Box::pin(PgPool::get_registered_group(self, jid))
+    }
+
+    fn set_registered_group<'a>(&'a self, group: &'a RegisteredGroup) -> StorageFuture<'a, ()> {
+        Box::pin(PgPool::set_registered_group(self, group))
+    }
+
+    fn get_all_registered_groups(&self) -> StorageFuture<'_, HashMap<String, RegisteredGroup>> {
+        Box::pin(PgPool::get_all_registered_groups(self))
+    }
+
+    fn delete_registered_group<'a>(&'a self, jid: &'a str) -> StorageFuture<'a, bool> {
+        Box::pin(PgPool::delete_registered_group(self, jid))
+    }
+}
@@ -0,0 +1,927 @@
+//! Calendar feeds — a group's iCal feed materialized as one-shot tasks.
+//!
+//! A group whose container config has `calendar: {"url": ...}` is
+//! subscribed to that feed. Every refresh the feed is fetched, events
+//! starting within the horizon are expanded (recurring ones through their
+//! `RRULE`, minus `EXDATE`s and moved or cancelled instances), and each
+//! occurrence becomes a `once` task whose prompt is the event description,
+//! else its summary.
+//!
+//! Feed tasks have ids derived from the group, event UID and start, so a
+//! refresh finds the tasks it made before: new occurrences are created,
+//! edited prompts are updated, and pending tasks whose occurrence left the
+//! feed (deleted or moved events) are removed. Tasks that already ran are
+//! left for their history. A feed that cannot be fetched leaves the group's
+//! tasks as they are.
+//!
+//! Supported recurrence: `FREQ` daily, weekly, monthly or yearly with
+//! `INTERVAL`, `COUNT`, `UNTIL` and `BYDAY` (ordinal days such as `1MO`
+//! only for monthly rules). Events with other rule parts are skipped with a
+//! warning. Times with a `TZID` follow that zone's wall clock; floating
+//! times and all-day events use the scheduler's timezone, all-day events
+//! starting at midnight.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, anyhow};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use intercom_core::{RegisteredGroup, ScheduledTask, SharedStorage, TaskUpdate};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{RwLock, watch};
+use tracing::{debug, info, warn};
+
+use crate::container::security::ContainerConfig;
+
+/// Id prefix of tasks made from calendar feeds.
+pub const TASK_ID_PREFIX: &str = "cal-";
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_FEED_BYTES: usize = 5 * 1024 * 1024;
+/// How far past its start a rule is followed, in days.
+const MAX_RULE_DAYS: i64 = 366 * 50;
+
+/// Feed configured on a group's `containerConfig.calendar`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalendarFeed {
+    /// `http(s)://` or `webcal://` URL of an iCal feed.
+    pub url: String,
+}
+
+impl CalendarFeed {
+    /// The URL to fetch; `webcal://` is served over https.
+    pub fn fetch_url(&self) -> Option<String> {
+        let url = self.url.trim();
+        if let Some(rest) = url.strip_prefix("webcal://") {
+            Some(format!("https://{rest}"))
+        } else if url.starts_with("https://") || url.starts_with("http://") {
+            Some(url.to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// The `calendar` of a registered group's container config.
+pub fn group_calendar(group: &RegisteredGroup) -> Option<CalendarFeed> {
+    let config = group.container_config.clone()?;
+    serde_json::from_value::<ContainerConfig>(config)
+        .ok()?
+        .calendar
+}
+
+/// A wall-clock time in a zone; UTC times use [`Tz::UTC`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventTime {
+    pub local: NaiveDateTime,
+    pub tz: Tz,
+}
+
+impl EventTime {
+    /// The instant, with times skipped by a spring-forward moved to the end
+    /// of the gap and repeated ones taken at their first occurrence.
+    pub fn instant(&self) -> Option<DateTime<Utc>> {
+        (0..=24 * 60)
+            .find_map(|minutes| {
+                let shifted = self.local + chrono::Duration::minutes(minutes);
+                self.tz.from_local_datetime(&shifted).earliest()
+            })
+            .map(|t| t.with_timezone(&Utc))
+    }
+}
+
+/// One `VEVENT`, as far as scheduling needs it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CalendarEvent {
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub start: Option<EventTime>,
+    /// The raw `RRULE` value.
+    pub rrule: Option<String>,
+    pub exdates: Vec<EventTime>,
+    /// Set on an instance that replaces one occurrence of a recurring event.
+    pub recurrence_id: Option<EventTime>,
+    pub cancelled: bool,
+}
+
+impl CalendarEvent {
+    fn prompt(&self) -> &str {
+        if self.description.trim().is_empty() {
+            self.summary.trim()
+        } else {
+            self.description.trim()
+        }
+    }
+}
+
+/// A single run an event asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub uid: String,
+    pub start: DateTime<Utc>,
+    pub prompt: String,
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Split `NAME;PARAM=x:value` into the upper-cased name, its parameters and
+/// the value. Quoted parameter values may hold `:` and `;`.
+fn split_property(line: &str) -> Option<(String, HashMap<String, String>, &str)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| {
+            (
+                k.trim().to_ascii_uppercase(),
+                v.trim().trim_matches('"').to_string(),
+            )
+        })
+        .collect();
+    Some((name, params, value))
+}
+
+/// Parse `20261020T090000Z`, `20261020T090000` or `20261020`. A `TZID`
+/// the zone database does not know falls back to `default_tz`.
+fn parse_time(value: &str, params: &HashMap<String, String>, default_tz: Tz) -> Option<EventTime> {
+    let value = value.trim();
+    if let Some(utc) = value.strip_suffix('Z') {
+        let local = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(EventTime { local, tz: Tz::UTC });
+    }
+    let tz = match params.get("TZID") {
+        Some(id) => id.parse().unwrap_or_else(|_| {
+            debug!(tzid = %id, "unknown calendar timezone, using the scheduler's");
+            default_tz
+        }),
+        None => default_tz,
+    };
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .or_else(|| {
+            Some(
+                NaiveDate::parse_from_str(value, "%Y%m%d")
+                    .ok()?
+                    .and_time(NaiveTime::MIN),
+            )
+        })?;
+    Some(EventTime { local, tz })
+}
+
+/// The events of an iCal document. Floating and all-day times are read in
+/// `default_tz`.
+pub fn parse_ics(text: &str, default_tz: Tz) -> Vec<CalendarEvent> {
+    // Unfold: a line starting with a space or tab continues the previous one
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut current: Option<CalendarEvent> = None;
+    // Depth of components nested in the event, such as VALARM
+    let mut nested = 0;
+    for line in &lines {
+        let Some((name, params, value)) = split_property(line) else {
+            continue;
+        };
+        match (name.as_str(), value.trim().to_ascii_uppercase().as_str()) {
+            ("BEGIN", "VEVENT") => {
+                current = Some(CalendarEvent::default());
+                nested = 0;
+                continue;
+            }
+            ("END", "VEVENT") => {
+                events.extend(current.take());
+                continue;
+            }
+            ("BEGIN", _) if current.is_some() => nested += 1,
+            ("END", _) if current.is_some() => nested -= 1,
+            _ => {}
+        }
+        let Some(event) = current.as_mut().filter(|_| nested == 0) else {
+            continue;
+        };
+        match name.as_str() {
+            "UID" => event.uid = value.trim().to_string(),
+            "SUMMARY" => event.summary = unescape(value),
+            "DESCRIPTION" => event.description = unescape(value),
+            "DTSTART" => event.start = parse_time(value, &params, default_tz),
+            "RRULE" => event.rrule = Some(value.trim().to_string()),
+            "EXDATE" => event.exdates.extend(
+                value
+                    .split(',')
+                    .filter_map(|v| parse_time(v, &params, default_tz)),
+            ),
+            "RECURRENCE-ID" => event.recurrence_id = parse_time(value, &params, default_tz),
+            "STATUS" => event.cancelled = value.trim().eq_ignore_ascii_case("CANCELLED"),
+            _ => {}
+        }
+    }
+    events
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A parsed `RRULE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecurrenceRule {
+    frequency: Frequency,
+    interval: i64,
+    count: Option<usize>,
+    until: Option<DateTime<Utc>>,
+    /// Weekdays with their ordinal in the month; 0 is every one.
+    by_day: Vec<(i32, Weekday)>,
+}
+
+fn parse_weekday(code: &str) -> Option<Weekday> {
+    Some(match code {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+impl RecurrenceRule {
+    fn parse(rule: &str, start: &EventTime) -> Result<Self, String> {
+        let mut frequency = None;
+        let mut parsed = Self {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+        };
+        for part in rule.split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("bad rule part `{part}`"))?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        other => return Err(format!("unsupported FREQ `{other}`")),
+                    })
+                }
+                "INTERVAL" => match value.parse() {
+                    Ok(n) if n > 0 => parsed.interval = n,
+                    _ => return Err(format!("bad INTERVAL `{value}`")),
+                },
+                "COUNT" => {
+                    parsed.count = Some(value.parse().map_err(|_| format!("bad COUNT `{value}`"))?)
+                }
+                "UNTIL" => {
+                    // A date-only UNTIL includes that whole day
+                    let mut until = parse_time(value, &HashMap::new(), start.tz)
+                        .ok_or_else(|| format!("bad UNTIL `{value}`"))?;
+                    if value.trim().len() == 8 {
+                        until.local += chrono::Duration::days(1) - chrono::Duration::seconds(1);
+                    }
+                    parsed.until = until.instant();
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        let day = day.trim().to_ascii_uppercase();
+                        let (ordinal, code) = day.split_at(day.len().saturating_sub(2));
+                        let weekday =
+                            parse_weekday(code).ok_or_else(|| format!("bad BYDAY `{day}`"))?;
+                        let ordinal = if ordinal.is_empty() {
+                            0
+                        } else {
+                            ordinal
+                                .trim_start_matches('+')
+                                .parse()
+                                .map_err(|_| format!("bad BYDAY `{day}`"))?
+                        };
+                        parsed.by_day.push((ordinal, weekday));
+                    }
+                }
+                "WKST" => {}
+                other => return Err(format!("unsupported rule part `{other}`")),
+            }
+        }
+        parsed.frequency = frequency.ok_or("missing FREQ")?;
+        if parsed.frequency != Frequency::Monthly
+            && parsed.by_day.iter().any(|(ordinal, _)| *ordinal != 0)
+        {
+            return Err("ordinal BYDAY is only supported for monthly rules".into());
+        }
+        Ok(parsed)
+    }
+
+    fn weekday_matches(&self, date: NaiveDate) -> bool {
+        self.by_day.iter().any(|(ordinal, weekday)| {
+            if date.weekday() != *weekday {
+                return false;
+            }
+            let from_start = (date.day() as i32 - 1) / 7 + 1;
+            let days_in_month = last_day_of_month(date) as i32;
+            let from_end = (days_in_month - date.day() as i32) / 7 + 1;
+            *ordinal == 0 || *ordinal == from_start || *ordinal == -from_end
+        })
+    }
+
+    /// Whether the rule generates `date`, for an event first on `start`.
+    fn matches(&self, start: NaiveDate, date: NaiveDate) -> bool {
+        match self.frequency {
+            Frequency::Daily => {
+                (date - start).num_days() % self.interval == 0
+                    && (self.by_day.is_empty() || self.weekday_matches(date))
+            }
+            Frequency::Weekly => {
+                let monday = |d: NaiveDate| {
+                    d - chrono::Duration::days(i64::from(d.weekday().num_days_from_monday()))
+                };
+                let weeks = (monday(date) - monday(start)).num_days() / 7;
+                let weekday = if self.by_day.is_empty() {
+                    date.weekday() == start.weekday()
+                } else {
+                    self.weekday_matches(date)
+                };
+                weeks % self.interval == 0 && weekday
+            }
+            Frequency::Monthly => {
+                let months = i64::from(
+                    date.year() * 12 + date.month() as i32
+                        - start.year() * 12
+                        - start.month() as i32,
+                );
+                let day = if self.by_day.is_empty() {
+                    date.day() == start.day()
+                } else {
+                    self.weekday_matches(date)
+                };
+                months % self.interval == 0 && day
+            }
+            Frequency::Yearly => {
+                i64::from(date.year() - start.year()) % self.interval == 0
+                    && date.month() == start.month()
+                    && date.day() == start.day()
+            }
+        }
+    }
+}
+
+fn last_day_of_month(date: NaiveDate) -> u32 {
+    let (year, month) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|first| first.pred_opt())
+        .map_or(31, |last| last.day())
+}
+
+/// Start times of `event`'s instances up to `to`, on its own wall clock.
+fn event_starts(
+    event: &CalendarEvent,
+    start: &EventTime,
+    to: DateTime<Utc>,
+) -> Result<Vec<DateTime<Utc>>, String> {
+    let Some(rule) = event.rrule.as_deref() else {
+        return Ok(start.instant().into_iter().collect());
+    };
+    let rule = RecurrenceRule::parse(rule, start)?;
+    let first = start.local.date();
+    let last = to.with_timezone(&start.tz).date_naive();
+    let mut starts = Vec::new();
+    let mut generated = 0;
+    for date in first
+        .iter_days()
+        .take(MAX_RULE_DAYS as usize)
+        .take_while(|d| *d <= last)
+    {
+        // DTSTART is always the first instance
+        if date != first && !rule.matches(first, date) {
+            continue;
+        }
+        let Some(at) = (EventTime {
+            local: date.and_time(start.local.time()),
+            tz: start.tz,
+        })
+        .instant() else {
+            continue;
+        };
+        if rule.until.is_some_and(|until| at > until)
+            || rule.count.is_some_and(|count| generated >= count)
+        {
+            break;
+        }
+        generated += 1;
+        starts.push(at);
+    }
+    Ok(starts)
+}
+
+/// Occurrences starting after `from` and no later than `to`.
+pub fn expand(events: &[CalendarEvent], from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Occurrence> {
+    // Instances replaced by a RECURRENCE-ID event, per UID
+    let replaced: HashSet<(&str, DateTime<Utc>)> = events
+        .iter()
+        .filter_map(|e| Some((e.uid.as_str(), e.recurrence_id?.instant()?)))
+        .collect();
+    let mut occurrences = Vec::new();
+    for event in events {
+        let Some(start) = event.start.as_ref() else {
+            continue;
+        };
+        if event.cancelled || event.uid.is_empty() || event.prompt().is_empty() {
+            continue;
+        }
+        let starts = match event_starts(event, start, to) {
+            Ok(starts) => starts,
+            Err(e) => {
+                warn!(uid = %event.uid, err = %e, "skipping calendar event with unsupported recurrence");
+                continue;
+            }
+        };
+        let excluded: HashSet<DateTime<Utc>> = event
+            .exdates
+            .iter()
+            .filter_map(EventTime::instant)
+            .collect();
+        occurrences.extend(
+            starts
+                .into_iter()
+                .filter(|at| *at > from && *at <= to && !excluded.contains(at))
+                .filter(|at| {
+                    event.recurrence_id.is_some() || !replaced.contains(&(event.uid.as_str(), *at))
+                })
+                .map(|at| Occurrence {
+                    uid: event.uid.clone(),
+                    start: at,
+                    prompt: event.prompt().to_string(),
+                }),
+        );
+    }
+    occurrences.sort_by_key(|o| o.start);
+    occurrences
+}
+
+/// Stable id of the task for one occurrence in one group.
+pub fn task_id(group_folder: &str, occurrence: &Occurrence) -> String {
+    let digest = Sha256::digest(format!(
+        "{group_folder}\n{}\n{}",
+        occurrence.uid,
+        occurrence.start.timestamp()
+    ));
+    format!("{TASK_ID_PREFIX}{}", &hex::encode(digest)[..16])
+}
+
+/// What a refresh changed for one group.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub created: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+/// Make the group's feed tasks match `occurrences`: create the missing
+/// ones, update changed prompts and remove pending tasks no longer in it.
+pub async fn sync_group(
+    pool: &SharedStorage,
+    chat_jid: &str,
+    group_folder: &str,
+    occurrences: &[Occurrence],
+    now: DateTime<Utc>,
+) -> anyhow::Result<SyncReport> {
+    let existing: HashMap<String, ScheduledTask> = pool
+        .get_tasks_for_group(group_folder)
+        .await?
+        .into_iter()
+        .filter(|t| t.id.starts_with(TASK_ID_PREFIX))
+        .map(|t| (t.id.clone(), t))
+        .collect();
+    let mut report = SyncReport::default();
+    let mut wanted = HashSet::new();
+    for occurrence in occurrences {
+        let id = task_id(group_folder, occurrence);
+        wanted.insert(id.clone());
+        match existing.get(&id) {
+            Some(task) if task.status == "active" && task.prompt != occurrence.prompt => {
+                let update = TaskUpdate {
+                    prompt: Some(occurrence.prompt.clone()),
+                    schedule_type: None,
+                    schedule_value: None,
+                    next_run: None,
+                    status: None,
+                    task_config: None,
+                };
+                pool.update_task(&id, &update).await?;
+                report.updated += 1;
+            }
+            Some(_) => {}
+            None => {
+                let at = occurrence.start.to_rfc3339();
+                pool.create_task(&ScheduledTask {
+                    id,
+                    group_folder: group_folder.to_string(),
+                    chat_jid: chat_jid.to_string(),
+                    prompt: occurrence.prompt.clone(),
+                    schedule_type: "once".into(),
+                    schedule_value: at.clone(),
+                    context_mode: "isolated".into(),
+                    next_run: Some(at),
+                    last_run: None,
+                    last_result: None,
+                    status: "active".into(),
+                    created_at: now.to_rfc3339(),
+                    task_config: None,
+                })
+                .await?;
+                report.created += 1;
+            }
+        }
+    }
+    for (id, task) in &existing {
+        let pending = task.last_run.is_none()
+            && task
+                .next_run
+                .as_deref()
+                .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+                .is_some_and(|at| at > now);
+        if pending && !wanted.contains(id) {
+            pool.delete_task(id).await?;
+            report.removed += 1;
+        }
+    }
+    Ok(report)
+}
+
+async fn fetch_feed(client: &reqwest::Client, feed: &CalendarFeed) -> anyhow::Result<String> {
+    let url = feed
+        .fetch_url()
+        .ok_or_else(|| anyhow!("not an http(s) or webcal url"))?;
+    let body = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .context("fetch calendar feed")?
+        .bytes()
+        .await
+        .context("read calendar feed")?;
+    if body.len() > MAX_FEED_BYTES {
+        return Err(anyhow!(
+            "calendar feed is larger than {MAX_FEED_BYTES} bytes"
+        ));
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Refresh settings for [`run_calendar_loop`].
+#[derive(Debug, Clone)]
+pub struct CalendarSyncConfig {
+    pub refresh: Duration,
+    /// How far ahead occurrences become tasks.
+    pub horizon: Duration,
+    /// Zone for floating and all-day event times.
+    pub timezone: String,
+}
+
+/// Refresh every group's feed on an interval, starting now. Groups without
+/// a feed lose the pending tasks an earlier feed made.
+pub async fn run_calendar_loop(
+    config: CalendarSyncConfig,
+    pool: SharedStorage,
+    groups: Arc<RwLock<HashMap<String, RegisteredGroup>>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let client = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let tz: Tz = config.timezone.parse().unwrap_or(Tz::UTC);
+    let horizon = chrono::Duration::from_std(config.horizon).unwrap_or(chrono::Duration::days(7));
+    let mut ticker = tokio::time::interval(config.refresh);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.changed() => break,
+        }
+        let subscriptions: Vec<(String, String, Option<CalendarFeed>)> = groups
+            .read()
+            .await
+            .iter()
+            .map(|(jid, group)| (jid.clone(), group.folder.clone(), group_calendar(group)))
+            .collect();
+        for (jid, folder, feed) in subscriptions {
+            let now = Utc::now();
+            let occurrences = match &feed {
+                Some(feed) => match fetch_feed(&client, feed).await {
+                    Ok(text) => expand(&parse_ics(&text, tz), now, now + horizon),
+                    Err(e) => {
+                        warn!(group = %folder, url = %feed.url, err = %format!("{e:#}"), "calendar refresh failed");
+                        continue;
+                    }
+                },
+                None => Vec::new(),
+            };
+            match sync_group(&pool, &jid, &folder, &occurrences, now).await {
+                Ok(report) if report != SyncReport::default() => info!(
+                    group = %folder,
+                    created = report.created,
+                    updated = report.updated,
+                    removed = report.removed,
+                    "calendar tasks refreshed"
+                ),
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(group = %folder, err = %format!("{e:#}"), "calendar task sync failed")
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    const FEED: &str = "BEGIN:VCALENDAR\r\n\
+        VERSION:2.0\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:standup@example.com\r\n\
+        SUMMARY:Standup\r\n\
+        DESCRIPTION:Collect yesterday's merged PRs\\, then post a\r\n  \
+         summary.\\nKeep it short.\r\n\
+        DTSTART;TZID=Europe/Berlin:20261019T090000\r\n\
+        RRULE:FREQ=WEEKLY;BYDAY=MO,WE,FR\r\n\
+        EXDATE;TZID=Europe/Berlin:20261023T090000\r\n\
+        BEGIN:VALARM\r\n\
+        DESCRIPTION:Reminder\r\n\
+        END:VALARM\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:standup@example.com\r\n\
+        RECURRENCE-ID;TZID=Europe/Berlin:20261026T090000\r\n\
+        DTSTART;TZID=Europe/Berlin:20261026T110000\r\n\
+        SUMMARY:Standup (late)\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:retro@example.com\r\n\
+        SUMMARY:Retro\r\n\
+        DTSTART:20261020T150000Z\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:offsite@example.com\r\n\
+        SUMMARY:Offsite\r\n\
+        STATUS:CANCELLED\r\n\
+        DTSTART;VALUE=DATE:20261021\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    #[test]
+    fn events_are_unfolded_and_unescaped() {
+        let events = parse_ics(FEED, Tz::UTC);
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0].description,
+            "Collect yesterday's merged PRs, then post a summary.\nKeep it short."
+        );
+        assert_eq!(events[0].start.unwrap().tz, chrono_tz::Europe::Berlin);
+        assert_eq!(events[0].exdates.len(), 1);
+        assert!(events[3].cancelled);
+        assert_eq!(
+            events[3].start.unwrap().local.to_string(),
+            "2026-10-21 00:00:00"
+        );
+    }
+
+    #[test]
+    fn recurring_events_expand_within_the_window() {
+        let events = parse_ics(FEED, Tz::UTC);
+        let found = expand(
+            &events,
+            utc("2026-10-19T00:00:00Z"),
+            utc("2026-10-29T00:00:00Z"),
+        );
+        let starts: Vec<(String, &str)> = found
+            .iter()
+            .map(|o| (o.start.to_rfc3339(), o.prompt.lines().next().unwrap()))
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                (
+                    "2026-10-19T07:00:00+00:00".into(),
+                    "Collect yesterday's merged PRs, then post a summary."
+                ),
+                ("2026-10-20T15:00:00+00:00".into(), "Retro"),
+                (
+                    "2026-10-21T07:00:00+00:00".into(),
+                    "Collect yesterday's merged PRs, then post a summary."
+                ),
+                // Friday the 23rd is excluded; Berlin leaves summer time on the 25th
+                ("2026-10-26T10:00:00+00:00".into(), "Standup (late)"),
+                (
+                    "2026-10-28T08:00:00+00:00".into(),
+                    "Collect yesterday's merged PRs, then post a summary."
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rules_follow_interval_count_until_and_ordinal_days() {
+        let start = EventTime {
+            local: NaiveDateTime::parse_from_str("2026-01-05T10:00:00", "%Y-%m-%dT%H:%M:%S")
+                .unwrap(),
+            tz: Tz::UTC,
+        };
+        let starts = |rule: &str| {
+            let event = CalendarEvent {
+                rrule: Some(rule.into()),
+                ..Default::default()
+            };
+            event_starts(&event, &start, utc("2026-12-31T00:00:00Z")).map(|s| {
+                s.iter()
+                    .map(|t| t.format("%m-%d").to_string())
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            starts("FREQ=WEEKLY;INTERVAL=2;COUNT=3").unwrap(),
+            ["01-05", "01-19", "02-02"]
+        );
+        assert_eq!(
+            starts("FREQ=DAILY;UNTIL=20260107").unwrap(),
+            ["01-05", "01-06", "01-07"]
+        );
+        // First Monday and last Friday of each month
+        assert_eq!(
+            starts("FREQ=MONTHLY;BYDAY=1MO,-1FR;COUNT=4").unwrap(),
+            ["01-05", "01-30", "02-02", "02-27"]
+        );
+        assert_eq!(starts("FREQ=YEARLY").unwrap(), ["01-05"]);
+        assert!(starts("FREQ=HOURLY").is_err());
+        assert!(starts("FREQ=MONTHLY;BYSETPOS=-1").is_err());
+        assert!(starts("FREQ=WEEKLY;BYDAY=2TU").is_err());
+    }
+
+    #[test]
+    fn webcal_feeds_are_fetched_over_https() {
+        let feed = |url: &str| CalendarFeed { url: url.into() }.fetch_url();
+        assert_eq!(
+            feed("webcal://cal.example.com/team.ics").as_deref(),
+            Some("https://cal.example.com/team.ics")
+        );
+        assert_eq!(
+            feed("https://cal.example.com/a.ics").as_deref(),
+            Some("https://cal.example.com/a.ics")
+        );
+        assert_eq!(feed("file:///etc/passwd"), None);
+    }
+
+    #[tokio::test]
+    async fn refreshes_create_update_and_remove_feed_tasks() {
+        let pool: SharedStorage = Arc::new(intercom_core::SqliteStore::new(":memory:"));
+        let now = utc("2026-10-19T00:00:00Z");
+        let occurrence = |uid: &str, at: &str, prompt: &str| Occurrence {
+            uid: uid.into(),
+            start: utc(at),
+            prompt: prompt.into(),
+        };
+        let standup = occurrence("standup", "2026-10-19T07:00:00Z", "Post the standup");
+        let retro = occurrence("retro", "2026-10-20T15:00:00Z", "Run the retro");
+        let report = sync_group(
+            &pool,
+            "tg:1",
+            "team",
+            &[standup.clone(), retro.clone()],
+            now,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            report,
+            SyncReport {
+                created: 2,
+                updated: 0,
+                removed: 0
+            }
+        );
+        let task = pool
+            .get_task_by_id(&task_id("team", &standup))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (task.schedule_type.as_str(), task.chat_jid.as_str()),
+            ("once", "tg:1")
+        );
+        assert_eq!(task.next_run.as_deref(), Some("2026-10-19T07:00:00.000Z"));
+
+        // The standup prompt changes and the retro moves an hour later
+        let standup = occurrence(
+            "standup",
+            "2026-10-19T07:00:00Z",
+            "Post the standup, briefly",
+        );
+        let moved = occurrence("retro", "2026-10-20T16:00:00Z", "Run the retro");
+        let report = sync_group(
+            &pool,
+            "tg:1",
+            "team",
+            &[standup.clone(), moved.clone()],
+            now,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            report,
+            SyncReport {
+                created: 1,
+                updated: 1,
+                removed: 1
+            }
+        );
+        assert!(
+            pool.get_task_by_id(&task_id("team", &retro))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let task = pool
+            .get_task_by_id(&task_id("team", &standup))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(task.prompt, "Post the standup, briefly");
+
+        // Once the feed goes away, only tasks still to come are removed
+        let later = utc("2026-10-20T00:00:00Z");
+        let report = sync_group(&pool, "tg:1", "team", &[], later).await.unwrap();
+        assert_eq!(
+            report,
+            SyncReport {
+                created: 0,
+                updated: 0,
+                removed: 1
+            }
+        );
+        assert!(
+            pool.get_task_by_id(&task_id("team", &standup))
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert_ne!(task_id("team", &standup), task_id("other", &standup));
+    }
+}
@@ -0,0 +1,316 @@
+//! Transport-neutral channel interface.
+//!
+//! The orchestrator (process_group, scheduler_wiring, replay) delivers agent
+//! output through a [`ChannelRegistry`], which picks the [`ChannelBridge`]
+//! for a chat by JID prefix (`tg:`, `wa:`, `dc:`). A new transport
+//! implements the trait and is registered in `serve`; nothing in the
+//! orchestrator changes.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use intercom_core::{Attachment, ChannelProfile, NewMessage, SharedStorage};
+use tracing::warn;
+
+use crate::event_stream::{self, LiveEvent, LiveEventKind};
+use crate::health;
+
+/// Boxed future returned by `ChannelBridge` methods (keeps the trait object-safe).
+pub type ChannelFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// Shared handle to one registered bridge.
+pub type SharedChannel = Arc<dyn ChannelBridge>;
+
+/// Text to deliver to one chat.
+#[derive(Debug, Clone, Default)]
+pub struct OutboundMessage {
+    pub jid: String,
+    pub text: String,
+    /// Channel message id to thread the first chunk under.
+    pub reply_to: Option<String>,
+    pub correlation_id: Option<String>,
+}
+
+impl OutboundMessage {
+    pub fn text(jid: &str, text: &str) -> Self {
+        Self {
+            jid: jid.to_string(),
+            text: text.to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+pub trait ChannelBridge: Send + Sync {
+    /// Channel name: its `[channels.profiles]` key and health subsystem.
+    fn name(&self) -> &'static str;
+
+    fn profile(&self) -> &ChannelProfile;
+
+    /// JIDs starting with this prefix belong to the channel.
+    fn jid_prefix(&self) -> &str {
+        &self.profile().jid_prefix
+    }
+
+    /// Longest single message the channel accepts, in characters.
+    fn max_chars(&self) -> usize {
+        self.profile().max_chars.max(1)
+    }
+
+    /// Send text, split to the channel's limit. Returns the ids of the sent
+    /// messages, first chunk first.
+    fn send<'a>(&'a self, message: &'a OutboundMessage) -> ChannelFuture<'a, Vec<String>>;
+
+    /// Replace the text of a message the bridge sent earlier.
+    fn edit<'a>(
+        &'a self,
+        jid: &'a str,
+        message_id: &'a str,
+        text: &'a str,
+    ) -> ChannelFuture<'a, ()>;
+
+    /// Inbound message text as the agent should see it, with placeholders
+    /// for media the channel delivered.
+    fn normalize_ingress(&self, content: &str, attachments: &[Attachment]) -> String;
+
+    /// Gap between edits of a streamed draft reply, or `None` if the
+    /// channel only gets the final text.
+    fn draft_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Registered bridges, looked up by JID prefix.
+pub struct ChannelRegistry {
+    bridges: Vec<SharedChannel>,
+    /// Bridge for JIDs no prefix matches (legacy unprefixed Telegram ids).
+    default_channel: String,
+}
+
+impl ChannelRegistry {
+    pub fn new(default_channel: impl Into<String>) -> Self {
+        Self {
+            bridges: Vec::new(),
+            default_channel: default_channel.into(),
+        }
+    }
+
+    /// Add a bridge. A bridge with the same name replaces the earlier one.
+    pub fn register(mut self, bridge: SharedChannel) -> Self {
+        self.bridges.retain(|b| b.name() != bridge.name());
+        self.bridges.push(bridge);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SharedChannel> {
+        self.bridges.iter().find(|b| b.name() == name)
+    }
+
+    /// Bridge for `jid`: the longest matching prefix, else the default.
+    pub fn for_jid(&self, jid: &str) -> Option<&SharedChannel> {
+        self.bridges
+            .iter()
+            .filter(|b| !b.jid_prefix().is_empty() && jid.starts_with(b.jid_prefix()))
+            .max_by_key(|b| b.jid_prefix().len())
+            .or_else(|| self.get(&self.default_channel))
+    }
+
+    #[cfg(test)]
+    pub fn names(&self) -> Vec<&'static str> {
+        self.bridges.iter().map(|b| b.name()).collect()
+    }
+
+    /// Send through the chat's bridge. Failures are also recorded against
+    /// the channel's health subsystem and published as `send_failed`.
+    pub async fn send(&self, message: &OutboundMessage) -> anyhow::Result<Vec<String>> {
+        let failed = |channel: Option<&str>, e: &anyhow::Error| {
+            let mut event = LiveEvent::new(LiveEventKind::SendFailed, &message.jid)
+                .with_correlation_id(message.correlation_id.as_deref())
+                .with_error(e);
+            event.channel = channel.map(str::to_string);
+            event_stream::publish(event);
+        };
+        let Some(bridge) = self.for_jid(&message.jid) else {
+            let e = anyhow!("no channel registered for {}", message.jid);
+            failed(None, &e);
+            return Err(e);
+        };
+        let result = bridge.send(message).await;
+        if let Err(e) = &result {
+            health::record_error(bridge.name(), e);
+            failed(Some(bridge.name()), e);
+        }
+        result
+    }
+
+    pub async fn send_text(&self, jid: &str, text: &str) -> anyhow::Result<()> {
+        self.send(&OutboundMessage::text(jid, text))
+            .await
+            .map(|_| ())
+    }
+}
+
+/// Store one chunk a bridge has sent as a bot message from `sender_name`.
+/// Failures are logged, never returned: the message has already been
+/// delivered.
+pub async fn record_sent(
+    store: Option<&SharedStorage>,
+    channel: &str,
+    sender_name: &str,
+    jid: &str,
+    message_id: &str,
+    text: &str,
+    correlation_id: Option<&str>,
+) {
+    let Some(store) = store else {
+        return;
+    };
+    let message = NewMessage {
+        id: message_id.to_string(),
+        chat_jid: jid.to_string(),
+        sender: "bot".into(),
+        sender_name: sender_name.to_string(),
+        content: text.to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        is_from_me: true,
+        is_bot_message: true,
+        edited: false,
+        attachments: Vec::new(),
+        correlation_id: correlation_id.map(str::to_string),
+    };
+    if let Err(e) = store.store_message(&message).await {
+        warn!(channel, jid, message_id, err = %e, "failed to store sent message");
+        health::record_error(health::SUBSYSTEM_DB, &e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeBridge {
+        name: &'static str,
+        profile: ChannelProfile,
+    }
+
+    impl FakeBridge {
+        fn shared(name: &'static str, prefix: &str) -> SharedChannel {
+            Arc::new(Self {
+                name,
+                profile: ChannelProfile {
+                    jid_prefix: prefix.to_string(),
+                    ..ChannelProfile::default()
+                },
+            })
+        }
+    }
+
+    impl ChannelBridge for FakeBridge {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn profile(&self) -> &ChannelProfile {
+            &self.profile
+        }
+
+        fn send<'a>(&'a self, message: &'a OutboundMessage) -> ChannelFuture<'a, Vec<String>> {
+            Box::pin(async move { Ok(vec![format!("{}:{}", self.name, message.jid)]) })
+        }
+
+        fn edit<'a>(
+            &'a self,
+            _jid: &'a str,
+            _message_id: &'a str,
+            _text: &'a str,
+        ) -> ChannelFuture<'a, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn normalize_ingress(&self, content: &str, _attachments: &[Attachment]) -> String {
+            content.to_string()
+        }
+    }
+
+    fn registry() -> ChannelRegistry {
+        ChannelRegistry::new("telegram")
+            .register(FakeBridge::shared("telegram", "tg:"))
+            .register(FakeBridge::shared("whatsapp", "wa:"))
+            .register(FakeBridge::shared("whatsapp_business", "wa:biz:"))
+    }
+
+    #[test]
+    fn for_jid_picks_longest_prefix() {
+        let channels = registry();
+        assert_eq!(channels.for_jid("tg:-100").unwrap().name(), "telegram");
+        assert_eq!(channels.for_jid("wa:123").unwrap().name(), "whatsapp");
+        assert_eq!(
+            channels.for_jid("wa:biz:1").unwrap().name(),
+            "whatsapp_business"
+        );
+    }
+
+    #[test]
+    fn for_jid_falls_back_to_default_channel() {
+        assert_eq!(
+            registry().for_jid("120363@g.us").unwrap().name(),
+            "telegram"
+        );
+        assert!(ChannelRegistry::new("telegram").for_jid("tg:1").is_none());
+    }
+
+    #[test]
+    fn register_replaces_same_name() {
+        let channels = registry().register(FakeBridge::shared("whatsapp", "wa2:"));
+        assert_eq!(
+            channels.names(),
+            ["telegram", "whatsapp_business", "whatsapp"]
+        );
+        assert_eq!(channels.for_jid("wa2:1").unwrap().name(), "whatsapp");
+    }
+
+    #[tokio::test]
+    async fn send_routes_to_bridge() {
+        let ids = registry()
+            .send(&OutboundMessage::text("wa:9", "hi"))
+            .await
+            .unwrap();
+        assert_eq!(ids, ["whatsapp:wa:9"]);
+    }
+
+    #[tokio::test]
+    async fn sent_chunks_are_stored_as_bot_messages() {
+        let tmp = tempfile::TempDir::new().expect("create tempdir");
+        let store: SharedStorage = Arc::new(intercom_core::SqliteStore::new(
+            tmp.path().join("intercom.db"),
+        ));
+
+        for (id, text) in [("41", "first half"), ("42", "second half")] {
+            record_sent(
+                Some(&store),
+                "telegram",
+                "Amtiskaw",
+                "tg:5",
+                id,
+                text,
+                Some("corr-1"),
+            )
+            .await;
+        }
+        // No store configured: nothing to do, and nothing to fail.
+        record_sent(None, "telegram", "Amtiskaw", "tg:5", "43", "dropped", None).await;
+
+        let history = store.get_recent_conversation("tg:5", 10).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(
+            history
+                .iter()
+                .all(|m| m.is_bot_message && m.sender_name == "Amtiskaw")
+        );
+        let contents: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+        assert!(contents.contains(&"first half") && contents.contains(&"second half"));
+    }
+}
@@ -1,12 +1,29 @@
 //! Slash command handler for Telegram/WhatsApp commands.
 //!
 //! Port of the command handlers from `src/index.ts`.
-//! Commands: /help, /status, /model, /reset (/new alias).
-
-use std::time::Instant;
-
+//! Commands: /help, /status, /model, /reset (/new alias), /tasks,
+//! /schedule, /cancel, /queue, /ps, /logs, /usage, /stats, /mute, /wake, /trigger,
+//! /language, plus the
+//! prompt template commands from `[commands.custom]`, all looked up in a
+//! [`CommandRegistry`]. Replies come from the group's locale catalog (see
+//! [`crate::i18n`]).
+//! `/health` and `/revert-last` touch live state and are handled by the HTTP
+//! endpoint in main.rs.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use intercom_core::{CommandPermission, CommandsConfig, PromptCommand, ScheduledTask, UsageTotals};
 use serde::{Deserialize, Serialize};
 
+use crate::i18n;
+use crate::message_loop;
+use crate::queue::GroupQueueStatus;
+use crate::scheduler;
+use crate::telegram::{InlineKeyboardButton, InlineKeyboardMarkup};
+
 // ---------------------------------------------------------------------------
 // Model catalog
 // ---------------------------------------------------------------------------
@@ -122,15 +139,33 @@ pub fn resolve_model(args: &str) -> ModelEntry {
 /// Keeps command handlers pure and testable — no async, no shared state.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum CommandEffect {
+    /// Drop the group's queued message checks and tasks.
+    CancelQueued,
     /// Stop the active container for this group.
     KillContainer,
     /// Delete the session for this group (both in-memory and Postgres).
     ClearSession,
     /// Switch the group to a new model and runtime.
-    SwitchModel {
-        model_id: String,
-        runtime: String,
+    SwitchModel { model_id: String, runtime: String },
+    /// Create a scheduled task for this group.
+    CreateTask {
+        prompt: String,
+        schedule_type: String,
+        schedule_value: String,
+        next_run: Option<String>,
     },
+    /// Delete one of this group's scheduled tasks.
+    CancelTask { task_id: String },
+    /// Send one of this group's container run logs as a file.
+    SendLog { file_name: String },
+    /// Mute the group until the given time, or unmute it.
+    SetMute { until: Option<String> },
+    /// Store `prompt` as a message from the sender, for the agent to answer.
+    InjectPrompt { prompt: String },
+    /// Change the group's trigger pattern and whether messages need it.
+    SetTrigger { pattern: String, required: bool },
+    /// Change the language of the group's command replies and notices.
+    SetLocale { locale: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -141,6 +176,9 @@ pub struct CommandResult {
     /// Side effects to apply. Empty for read-only commands.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub effects: Vec<CommandEffect>,
+    /// Tap-to-select options to send with `text`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
 }
 
 // ---------------------------------------------------------------------------
@@ -151,6 +189,273 @@ pub struct CommandResult {
 pub struct CommandContext {
     pub assistant_name: String,
     pub started_at: Instant,
+    /// The group folder's size against its quota, once scanned.
+    pub disk_usage: Option<String>,
+    /// The group's scheduled tasks, loaded for the task commands; `None`
+    /// when there is no storage to hold tasks.
+    pub tasks: Option<Vec<ScheduledTask>>,
+    /// Scheduler timezone, for reading and showing task times.
+    pub timezone: String,
+    pub now: DateTime<Utc>,
+    /// This group's place in the container queue, for `/queue`.
+    pub queue: Option<GroupQueueStatus>,
+    /// Every running container, for `/ps`; `None` outside the main group.
+    pub containers: Option<Vec<GroupQueueStatus>>,
+    /// The group's most recent container run log, for `/logs`.
+    pub last_log: Option<RunLog>,
+    /// The group's runs over the last day, week and month, for `/usage`;
+    /// `None` without storage.
+    pub usage: Option<[UsageTotals; 3]>,
+    /// The group's runs over the last 24 hours, for `/stats`; `None`
+    /// without storage.
+    pub stats: Option<UsageTotals>,
+    /// When the group's current `/mute` ends, if it has one.
+    pub muted_until: Option<String>,
+    /// The group's trigger settings, for `/trigger`.
+    pub trigger: Option<GroupTrigger>,
+    pub registry: Arc<CommandRegistry>,
+    /// Who sent the command, for admin-only commands.
+    pub sender: Option<String>,
+    /// Whether the command came from the main group.
+    pub is_main: bool,
+    /// Catalog the replies are written from, e.g. `de`.
+    pub locale: &'static str,
+}
+
+impl CommandContext {
+    fn text(&self, key: &str) -> String {
+        i18n::text(self.locale, key).to_string()
+    }
+
+    fn fill(&self, key: &str, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+        i18n::fill(self.locale, key, args)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GroupTrigger {
+    pub pattern: String,
+    pub required: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunLog {
+    pub file_name: String,
+    pub text: String,
+}
+
+/// What a command was invoked with, beyond the shared [`CommandContext`].
+pub struct Invocation<'a> {
+    pub args: &'a str,
+    pub group_name: Option<&'a str>,
+    pub group_folder: Option<&'a str>,
+    pub current_model: Option<&'a str>,
+    pub session_id: Option<&'a str>,
+    pub container_active: bool,
+}
+
+pub type CommandFn = fn(&Invocation<'_>, &CommandContext) -> CommandResult;
+
+#[derive(Clone)]
+pub enum CommandHandler {
+    Builtin(CommandFn),
+    /// Post the prompt into the chat as the sender (see [`prompt_text`]).
+    Prompt(String),
+    /// Answered before [`handle_command`] (`/health`, `/revert_last` in
+    /// main.rs) or by the channel itself (`/ping`, `/chatid`), which also
+    /// check their own permissions. Listed here for `/help`.
+    External,
+}
+
+/// A command as `/help` lists it and [`handle_command`] runs it.
+#[derive(Clone)]
+pub struct CommandSpec {
+    pub name: String,
+    pub aliases: Vec<String>,
+    /// Arguments as shown in `/help`, e.g. `[n]`.
+    pub usage: String,
+    /// The `/help` line; empty for the catalog's `help.<name>`.
+    pub help: String,
+    pub permission: CommandPermission,
+    pub handler: CommandHandler,
+}
+
+impl CommandSpec {
+    fn builtin(name: &str, usage: &str, handler: CommandFn) -> Self {
+        Self {
+            name: name.into(),
+            aliases: Vec::new(),
+            usage: usage.into(),
+            help: String::new(),
+            permission: CommandPermission::Anyone,
+            handler: CommandHandler::Builtin(handler),
+        }
+    }
+
+    fn external(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            aliases: Vec::new(),
+            usage: String::new(),
+            help: String::new(),
+            permission: CommandPermission::Anyone,
+            handler: CommandHandler::External,
+        }
+    }
+
+    fn alias(mut self, alias: &str) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    fn permission(mut self, permission: CommandPermission) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    fn answers_to(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+}
+
+/// Every command this deployment answers: the built-ins plus the prompt
+/// template commands from `[commands.custom]`.
+#[derive(Clone)]
+pub struct CommandRegistry {
+    commands: Vec<CommandSpec>,
+    admins: Vec<String>,
+}
+
+impl CommandRegistry {
+    pub fn builtin() -> Self {
+        use CommandPermission::{Admin, Main};
+        let commands = vec![
+            CommandSpec::builtin("help", "", |_, ctx| handle_help(ctx)),
+            CommandSpec::builtin("status", "", |inv, ctx| {
+                handle_status(
+                    inv.group_name,
+                    inv.group_folder,
+                    inv.current_model,
+                    inv.session_id,
+                    inv.container_active,
+                    ctx,
+                )
+            }),
+            CommandSpec::builtin("model", "[# or name]", |inv, ctx| {
+                handle_model(inv.args, inv.current_model, inv.group_name, ctx)
+            }),
+            CommandSpec::builtin("reset", "", |inv, ctx| {
+                handle_reset(inv.group_name, inv.container_active, ctx)
+            })
+            .alias("new"),
+            CommandSpec::builtin("tasks", "", |inv, ctx| handle_tasks(inv.group_folder, ctx)),
+            CommandSpec::builtin("schedule", "<when> <prompt>", |inv, ctx| {
+                handle_schedule(inv.args, inv.group_folder, ctx)
+            }),
+            CommandSpec::builtin("cancel", "<id>", |inv, ctx| {
+                handle_cancel(inv.args, inv.group_folder, ctx)
+            }),
+            CommandSpec::builtin("queue", "", |inv, ctx| handle_queue(inv.group_folder, ctx)),
+            CommandSpec::builtin("ps", "", |_, ctx| handle_ps(ctx)).permission(Main),
+            CommandSpec::builtin("logs", "[n]", |inv, ctx| {
+                handle_logs(inv.args, inv.group_folder, ctx)
+            }),
+            CommandSpec::builtin("usage", "", |inv, ctx| {
+                handle_usage(inv.group_name, inv.group_folder, ctx)
+            }),
+            CommandSpec::builtin("stats", "", |inv, ctx| {
+                handle_stats(inv.group_name, inv.group_folder, inv.current_model, ctx)
+            }),
+            CommandSpec::builtin("mute", "[duration]", |inv, ctx| {
+                handle_mute(inv.args, inv.group_folder, ctx)
+            }),
+            CommandSpec::builtin("wake", "", |inv, ctx| handle_wake(inv.group_folder, ctx)),
+            CommandSpec::builtin("trigger", "[show|set <pattern>|off]", |inv, ctx| {
+                handle_trigger(inv.args, inv.group_folder, ctx)
+            }),
+            CommandSpec::builtin("language", "[code]", |inv, ctx| {
+                handle_language(inv.args, inv.group_folder, ctx)
+            }),
+            CommandSpec::external("health").permission(Main),
+            CommandSpec::external("revert_last")
+                .alias("revert-last")
+                .permission(Admin),
+            CommandSpec::external("ping"),
+            CommandSpec::external("chatid"),
+        ];
+        Self {
+            commands,
+            admins: Vec::new(),
+        }
+    }
+
+    /// The built-ins plus `config`'s prompt commands. A custom command with
+    /// a name Telegram wouldn't accept, or one already taken, is skipped with
+    /// a warning.
+    pub fn from_config(config: &CommandsConfig) -> Self {
+        let mut registry = Self::builtin();
+        registry.admins = config.admins.clone();
+        for (name, command) in &config.custom {
+            if let Err(e) = registry.register_prompt(name, command) {
+                tracing::warn!(command = name.as_str(), "skipping custom command: {e}");
+            }
+        }
+        registry
+    }
+
+    fn register_prompt(&mut self, name: &str, command: &PromptCommand) -> Result<(), String> {
+        let valid = !name.is_empty()
+            && name.len() <= 32
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid {
+            return Err("names are 1-32 lowercase letters, digits or underscores".into());
+        }
+        if self.get(name).is_some() {
+            return Err(format!("/{name} is already a command"));
+        }
+        if command.prompt.trim().is_empty() {
+            return Err("the prompt is empty".into());
+        }
+        self.commands.push(CommandSpec {
+            name: name.into(),
+            aliases: Vec::new(),
+            usage: if command.prompt.contains("{args}") {
+                "[text]".into()
+            } else {
+                String::new()
+            },
+            help: command.description.clone(),
+            permission: command.permission,
+            handler: CommandHandler::Prompt(command.prompt.clone()),
+        });
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        self.commands.iter().find(|spec| spec.answers_to(name))
+    }
+
+    pub fn commands(&self) -> &[CommandSpec] {
+        &self.commands
+    }
+
+    pub fn is_admin(&self, sender: Option<&str>) -> bool {
+        sender.is_some_and(|s| self.admins.iter().any(|a| a == s))
+    }
+
+    /// Why the sender can't run `spec` here, if they can't.
+    fn denied(&self, spec: &CommandSpec, ctx: &CommandContext) -> Option<String> {
+        let key = match spec.permission {
+            CommandPermission::Anyone => return None,
+            CommandPermission::Main if ctx.is_main => return None,
+            CommandPermission::Main => "denied.main",
+            CommandPermission::Admin if self.is_admin(ctx.sender.as_deref()) => return None,
+            CommandPermission::Admin => "denied.admin",
+        };
+        Some(ctx.fill(key, &[("command", &spec.name)]))
+    }
 }
 
 pub fn handle_command(
@@ -163,43 +468,91 @@ pub fn handle_command(
     container_active: bool,
     ctx: &CommandContext,
 ) -> CommandResult {
-    match command {
-        "help" => handle_help(&ctx.assistant_name),
-        "status" => handle_status(
-            group_name,
-            group_folder,
-            current_model,
-            session_id,
-            container_active,
-            ctx,
-        ),
-        "model" => handle_model(args, current_model, group_name),
-        "reset" | "new" => handle_reset(group_name, container_active),
-        _ => CommandResult {
-            text: format!("Unknown command: /{command}"),
-            parse_mode: None,
-            effects: vec![],
-        },
+    let unknown = || plain(ctx.fill("unknown_command", &[("command", &command)]));
+    let Some(spec) = ctx.registry.get(command) else {
+        return unknown();
+    };
+    if let Some(reason) = ctx.registry.denied(spec, ctx) {
+        return plain(reason);
+    }
+    let invocation = Invocation {
+        args,
+        group_name,
+        group_folder,
+        current_model,
+        session_id,
+        container_active,
+    };
+    match &spec.handler {
+        CommandHandler::Builtin(handler) => handler(&invocation, ctx),
+        CommandHandler::Prompt(template) => {
+            handle_prompt(&spec.name, template, args, group_folder, ctx)
+        }
+        CommandHandler::External => unknown(),
     }
 }
 
-fn handle_help(assistant_name: &str) -> CommandResult {
+fn handle_help(ctx: &CommandContext) -> CommandResult {
+    let mut lines = vec![ctx.fill("help.title", &[("assistant", &ctx.assistant_name)])];
+    for spec in ctx.registry.commands() {
+        let usage = if spec.usage.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", spec.usage)
+        };
+        let help = match &spec.handler {
+            _ if !spec.help.is_empty() => spec.help.clone(),
+            CommandHandler::Prompt(_) => ctx.text("help.prompt"),
+            _ => ctx.text(&format!("help.{}", spec.name)),
+        };
+        let only = match spec.permission {
+            CommandPermission::Anyone => String::new(),
+            CommandPermission::Main => ctx.text("help.main_only"),
+            CommandPermission::Admin => ctx.text("help.admins_only"),
+        };
+        lines.push(format!(
+            "/{}{usage} — {help}{only}",
+            spec.name.replace('_', "\\_")
+        ));
+    }
     CommandResult {
-        text: format!(
-            "*{assistant_name} Commands*\n\
-             \n\
-             /help — Show this command list\n\
-             /status — Show runtime, session, and container status\n\
-             /model — Show available models\n\
-             /model <#> — Switch model by number\n\
-             /model <name> — Switch model by name\n\
-             /reset — Clear session and stop running container\n\
-             /new — Start a fresh chat (alias for /reset)\n\
-             /ping — Check if bot is online\n\
-             /chatid — Show this chat's registration ID"
-        ),
+        text: lines.join("\n"),
         parse_mode: Some("Markdown".into()),
         effects: vec![],
+        reply_markup: None,
+    }
+}
+
+/// A prompt command's message: `{args}` filled in, or the arguments
+/// appended when the template doesn't place them.
+pub fn prompt_text(template: &str, args: &str) -> String {
+    let args = args.trim();
+    if template.contains("{args}") {
+        template.replace("{args}", args).trim().to_string()
+    } else if args.is_empty() {
+        template.trim().to_string()
+    } else {
+        format!("{}\n\n{args}", template.trim())
+    }
+}
+
+fn handle_prompt(
+    name: &str,
+    template: &str,
+    args: &str,
+    group_folder: Option<&str>,
+    ctx: &CommandContext,
+) -> CommandResult {
+    if group_folder.is_none() {
+        return plain(ctx.text("not_registered"));
+    }
+    CommandResult {
+        text: ctx.fill("prompt.running", &[("command", &name)]),
+        parse_mode: None,
+        effects: vec![CommandEffect::InjectPrompt {
+            prompt: prompt_text(template, args),
+        }],
+        reply_markup: None,
     }
 }
 
@@ -211,13 +564,9 @@ fn handle_status(
     container_active: bool,
     ctx: &CommandContext,
 ) -> CommandResult {
-    let name = group_name.unwrap_or("Unknown");
+    let name = group_name.map_or_else(|| ctx.text("unknown_group"), str::to_string);
     if group_folder.is_none() {
-        return CommandResult {
-            text: "This chat is not registered.".into(),
-            parse_mode: None,
-            effects: vec![],
-        };
+        return plain(ctx.text("not_registered"));
     }
 
     let model_id = current_model.unwrap_or(DEFAULT_MODEL);
@@ -228,34 +577,38 @@ fn handle_status(
     let session_display = match session_id {
         Some(sid) if sid.len() > 12 => format!("`{}...`", &sid[..12]),
         Some(sid) => format!("`{sid}`"),
-        None => "_none_".into(),
+        None => ctx.text("status.no_session"),
     };
 
-    let elapsed = ctx.started_at.elapsed();
-    let total_min = elapsed.as_secs() / 60;
-    let hours = total_min / 60;
-    let minutes = total_min % 60;
-    let uptime = if hours > 0 {
-        format!("{hours}h {minutes}m")
-    } else {
-        format!("{minutes}m")
-    };
+    let uptime = elapsed_label(ctx.started_at.elapsed());
 
-    let container_status = if container_active { "active" } else { "idle" };
+    let container_status = ctx.text(if container_active {
+        "status.active"
+    } else {
+        "status.idle"
+    });
+    let disk = ctx
+        .disk_usage
+        .as_deref()
+        .map(|usage| ctx.fill("status.disk", &[("usage", &usage)]))
+        .unwrap_or_default();
 
     CommandResult {
-        text: format!(
-            "*Status for {name}*\n\
-             \n\
-             Model: `{model_display}`\n\
-             Session: {session_display}\n\
-             Container: {container_status}\n\
-             Assistant: {}\n\
-             Uptime: {uptime}",
-            ctx.assistant_name
+        text: ctx.fill(
+            "status.body",
+            &[
+                ("name", &name),
+                ("model", &model_display),
+                ("session", &session_display),
+                ("container", &container_status),
+                ("disk", &disk),
+                ("assistant", &ctx.assistant_name),
+                ("uptime", &uptime),
+            ],
         ),
         parse_mode: Some("Markdown".into()),
         effects: vec![],
+        reply_markup: None,
     }
 }
 
@@ -263,13 +616,10 @@ fn handle_model(
     args: &str,
     current_model: Option<&str>,
     group_name: Option<&str>,
+    ctx: &CommandContext,
 ) -> CommandResult {
     if group_name.is_none() {
-        return CommandResult {
-            text: "This chat is not registered.".into(),
-            parse_mode: None,
-            effects: vec![],
-        };
+        return plain(ctx.text("not_registered"));
     }
 
     let current_id = current_model.unwrap_or(DEFAULT_MODEL);
@@ -285,22 +635,33 @@ fn handle_model(
             .iter()
             .enumerate()
             .map(|(i, m)| {
-                let active = if m.id == current_id { " (active)" } else { "" };
+                let active = if m.id == current_id {
+                    ctx.text("model.active")
+                } else {
+                    String::new()
+                };
                 format!(" {}. `{}` — {}{}", i + 1, m.id, m.display_name, active)
             })
             .collect();
+        let buttons: Vec<InlineKeyboardButton> = catalog
+            .iter()
+            .filter(|m| m.id != current_id)
+            .map(|m| InlineKeyboardButton::command(&m.display_name, &format!("model {}", m.id)))
+            .collect();
 
         return CommandResult {
-            text: format!(
-                "*Current model:* {current_display}\n\
-                 \n\
-                 {}\n\
-                 \n\
-                 Switch: `/model <name>` or `/model <#>`",
-                catalog_lines.join("\n")
+            text: ctx.fill(
+                "model.list",
+                &[
+                    ("model", &current_display),
+                    ("catalog", &catalog_lines.join("\n")),
+                ],
             ),
             parse_mode: Some("Markdown".into()),
             effects: vec![],
+            reply_markup: Some(InlineKeyboardMarkup {
+                inline_keyboard: buttons.chunks(2).map(<[_]>::to_vec).collect(),
+            }),
         };
     }
 
@@ -309,9 +670,10 @@ fn handle_model(
 
     if new_model.id == current_id {
         return CommandResult {
-            text: format!("Already using `{}`.", new_model.display_name),
+            text: ctx.fill("model.already", &[("model", &new_model.display_name)]),
             parse_mode: Some("Markdown".into()),
             effects: vec![],
+            reply_markup: None,
         };
     }
 
@@ -320,10 +682,12 @@ fn handle_model(
         .unwrap_or_else(|| current_id.to_string());
 
     CommandResult {
-        text: format!(
-            "Switched from {prev_display} to *{}*.\n\
-             Conversation context will carry over.",
-            new_model.display_name
+        text: ctx.fill(
+            "model.switched",
+            &[
+                ("previous", &prev_display),
+                ("model", &new_model.display_name),
+            ],
         ),
         parse_mode: Some("Markdown".into()),
         effects: vec![
@@ -334,160 +698,829 @@ fn handle_model(
                 runtime: new_model.runtime,
             },
         ],
+        reply_markup: None,
     }
 }
 
-fn handle_reset(group_name: Option<&str>, was_active: bool) -> CommandResult {
+fn handle_reset(group_name: Option<&str>, was_active: bool, ctx: &CommandContext) -> CommandResult {
     if group_name.is_none() {
-        return CommandResult {
-            text: "This chat is not registered.".into(),
-            parse_mode: None,
-            effects: vec![],
-        };
+        return plain(ctx.text("not_registered"));
     }
 
-    let mut parts = vec!["Session cleared.".to_string()];
+    let mut parts = vec![ctx.text("reset.cleared")];
     if was_active {
-        parts.push("Running container stopped.".to_string());
+        parts.push(ctx.text("reset.stopped"));
     }
-    parts.push("Next message will start a fresh session.".to_string());
+    parts.push(ctx.text("reset.fresh"));
 
-    let mut effects = vec![CommandEffect::ClearSession];
+    // Cancel first, so queued work does not take the killed container's slot
+    let mut effects = vec![CommandEffect::CancelQueued, CommandEffect::ClearSession];
     if was_active {
-        effects.insert(0, CommandEffect::KillContainer);
+        effects.insert(1, CommandEffect::KillContainer);
     }
 
     CommandResult {
         text: parts.join(" "),
         parse_mode: None,
         effects,
+        reply_markup: None,
     }
 }
 
-// ---------------------------------------------------------------------------
-// HTTP endpoint for commands
-// ---------------------------------------------------------------------------
+fn plain(text: impl Into<String>) -> CommandResult {
+    CommandResult {
+        text: text.into(),
+        parse_mode: None,
+        effects: vec![],
+        reply_markup: None,
+    }
+}
 
-#[derive(Debug, Deserialize)]
-pub struct CommandRequest {
-    pub chat_jid: String,
-    pub command: String,
-    #[serde(default)]
-    pub args: String,
-    pub group_name: Option<String>,
-    pub group_folder: Option<String>,
-    pub current_model: Option<String>,
-    pub session_id: Option<String>,
-    #[serde(default)]
-    pub container_active: bool,
+/// The group's tasks, or the reply to send when there are none to look at.
+fn group_tasks<'a>(
+    group_folder: Option<&str>,
+    ctx: &'a CommandContext,
+) -> Result<&'a [ScheduledTask], CommandResult> {
+    if group_folder.is_none() {
+        return Err(plain(ctx.text("not_registered")));
+    }
+    ctx.tasks
+        .as_deref()
+        .ok_or_else(|| plain(ctx.text("tasks.no_storage")))
 }
 
-// ---------------------------------------------------------------------------
-// Tests
-// ---------------------------------------------------------------------------
+/// An RFC 3339 time as `2026-10-18 09:00` in the scheduler's timezone.
+fn local_time(at: &str, timezone: &str) -> String {
+    let tz: Tz = timezone.parse().unwrap_or(Tz::UTC);
+    DateTime::parse_from_rfc3339(at)
+        .map(|at| at.with_timezone(&tz).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| at.to_string())
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+fn handle_tasks(group_folder: Option<&str>, ctx: &CommandContext) -> CommandResult {
+    let tasks = match group_tasks(group_folder, ctx) {
+        Ok(tasks) => tasks,
+        Err(reply) => return reply,
+    };
+    if tasks.is_empty() {
+        return plain(ctx.text("tasks.none"));
+    }
 
-    fn test_ctx() -> CommandContext {
-        CommandContext {
-            assistant_name: "TestBot".into(),
-            started_at: Instant::now(),
+    let mut lines = vec![ctx.fill("tasks.title", &[("timezone", &ctx.timezone)])];
+    for task in tasks {
+        let next = match (task.status.as_str(), task.next_run.as_deref()) {
+            ("active", Some(next)) => {
+                ctx.fill("tasks.next", &[("time", &local_time(next, &ctx.timezone))])
+            }
+            (status, _) => status.to_string(),
+        };
+        let prompt: String = task.prompt.chars().take(60).collect();
+        let ellipsis = if task.prompt.chars().count() > 60 {
+            "…"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "\n{} — {} {}, {next}\n  {prompt}{ellipsis}",
+            task.id, task.schedule_type, task.schedule_value
+        ));
+    }
+    plain(lines.join("\n"))
+}
+
+fn handle_schedule(args: &str, group_folder: Option<&str>, ctx: &CommandContext) -> CommandResult {
+    if let Err(reply) = group_tasks(group_folder, ctx) {
+        return reply;
+    }
+    let usage = ctx.text("schedule.usage");
+    if args.trim().is_empty() {
+        return plain(usage);
+    }
+    let spoken = match scheduler::parse_spoken_schedule(args, &ctx.timezone, ctx.now) {
+        Ok(spoken) => spoken,
+        Err(e) => {
+            return plain(format!(
+                "{}\n\n{usage}",
+                ctx.fill("schedule.unreadable", &[("error", &e)])
+            ));
         }
+    };
+    if spoken.rest.is_empty() {
+        return plain(format!("{}\n\n{usage}", ctx.text("schedule.no_prompt")));
     }
+    let schedule = match scheduler::validate_schedule(
+        spoken.schedule_type,
+        &spoken.schedule_value,
+        &ctx.timezone,
+    ) {
+        Ok(schedule) => schedule,
+        Err(e) => return plain(ctx.fill("schedule.unreadable", &[("error", &e)])),
+    };
+    let next_run = scheduler::first_run(&schedule, &ctx.timezone, ctx.now);
+    let first = next_run
+        .as_deref()
+        .map(|at| {
+            ctx.fill(
+                "schedule.first_run",
+                &[
+                    ("time", &local_time(at, &ctx.timezone)),
+                    ("timezone", &ctx.timezone),
+                ],
+            )
+        })
+        .unwrap_or_default();
 
-    #[test]
-    fn help_command() {
-        let result = handle_command("help", "", None, None, None, None, false, &test_ctx());
-        assert!(result.text.contains("TestBot Commands"));
-        assert_eq!(result.parse_mode, Some("Markdown".into()));
+    CommandResult {
+        text: ctx.fill(
+            "schedule.done",
+            &[
+                (
+                    "schedule",
+                    &format!("{} {}", spoken.schedule_type, spoken.schedule_value),
+                ),
+                ("first", &first),
+            ],
+        ),
+        parse_mode: None,
+        effects: vec![CommandEffect::CreateTask {
+            prompt: spoken.rest,
+            schedule_type: spoken.schedule_type.to_string(),
+            schedule_value: spoken.schedule_value,
+            next_run,
+        }],
+        reply_markup: None,
     }
+}
 
-    #[test]
-    fn status_unregistered() {
-        let result = handle_command("status", "", None, None, None, None, false, &test_ctx());
-        assert!(result.text.contains("not registered"));
+fn handle_cancel(args: &str, group_folder: Option<&str>, ctx: &CommandContext) -> CommandResult {
+    let tasks = match group_tasks(group_folder, ctx) {
+        Ok(tasks) => tasks,
+        Err(reply) => return reply,
+    };
+    let task_id = args.trim();
+    if task_id.is_empty() {
+        return plain(ctx.text("cancel.usage"));
+    }
+    if !tasks.iter().any(|task| task.id == task_id) {
+        return plain(ctx.fill("cancel.unknown", &[("id", &task_id)]));
+    }
+    CommandResult {
+        text: ctx.fill("cancel.done", &[("id", &task_id)]),
+        parse_mode: None,
+        effects: vec![CommandEffect::CancelTask {
+            task_id: task_id.to_string(),
+        }],
+        reply_markup: None,
     }
+}
 
-    #[test]
-    fn status_registered() {
-        let result = handle_command(
-            "status",
-            "",
-            Some("Test Group"),
-            Some("test-group"),
-            Some("claude-opus-4-6"),
-            Some("sess-abc123def456"),
-            true,
-            &test_ctx(),
-        );
-        assert!(result.text.contains("Test Group"));
-        assert!(result.text.contains("Claude Opus 4.6"));
-        assert!(result.text.contains("active"));
-        assert!(result.text.contains("sess-abc123d"));
+/// `1h 5m`, or `5m` under an hour.
+fn elapsed_label(elapsed: Duration) -> String {
+    let total_min = elapsed.as_secs() / 60;
+    let hours = total_min / 60;
+    let minutes = total_min % 60;
+    if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m")
     }
+}
 
-    #[test]
-    fn model_catalog_display() {
-        let result = handle_command(
-            "model",
-            "",
-            Some("Test"),
-            Some("test"),
-            Some("claude-opus-4-6"),
-            None,
-            false,
-            &test_ctx(),
-        );
-        assert!(result.text.contains("Claude Opus 4.6"));
-        assert!(result.text.contains("(active)"));
-        assert!(result.text.contains("Gemini"));
+/// What a running container is doing: `messages` or its tasks, and whether
+/// it is idle between turns.
+fn container_work(status: &GroupQueueStatus, ctx: &CommandContext) -> String {
+    let mut work = Vec::new();
+    if status.active && !status.is_task_container {
+        work.push(ctx.text("queue.messages"));
+    }
+    match status.running_tasks.as_slice() {
+        [] => {}
+        [task] => work.push(ctx.fill("queue.task", &[("task", task)])),
+        tasks => work.push(ctx.fill("queue.tasks", &[("tasks", &tasks.join(", "))])),
     }
+    let work = work.join(" + ");
+    if status.idle_waiting {
+        ctx.fill("queue.idle", &[("work", &work)])
+    } else {
+        work
+    }
+}
 
-    #[test]
-    fn model_switch_by_number() {
-        let model = resolve_model("2");
-        assert_eq!(model.id, "claude-sonnet-4-6");
+fn handle_queue(group_folder: Option<&str>, ctx: &CommandContext) -> CommandResult {
+    if group_folder.is_none() {
+        return plain(ctx.text("not_registered"));
     }
+    let Some(status) = &ctx.queue else {
+        return plain(ctx.text("queue.unavailable"));
+    };
 
-    #[test]
-    fn model_switch_by_name() {
-        let model = resolve_model("gemini-3.1-pro");
-        assert_eq!(model.id, "gemini-3.1-pro");
-        assert_eq!(model.runtime, "gemini");
+    let work = container_work(status, ctx);
+    let container = match status.uptime {
+        Some(uptime) => ctx.fill(
+            "queue.running_for",
+            &[("work", &work), ("uptime", &elapsed_label(uptime))],
+        ),
+        None if !status.running_tasks.is_empty() => ctx.fill("queue.running", &[("work", &work)]),
+        None => ctx.text("queue.no_container"),
+    };
+    let mut lines = vec![ctx.fill("queue.container", &[("container", &container)])];
+    if let Some(name) = &status.container_name {
+        lines.push(ctx.fill("queue.name", &[("name", name)]));
     }
+    lines.push(match (status.pending_messages, status.pending_for) {
+        (true, Some(waited)) => ctx.fill(
+            "queue.pending_waiting",
+            &[("waited", &elapsed_label(waited))],
+        ),
+        (true, None) => ctx.text("queue.pending"),
+        (false, _) => ctx.text("queue.no_pending"),
+    });
+    lines.push(if status.pending_tasks.is_empty() {
+        ctx.text("queue.no_pending_tasks")
+    } else {
+        ctx.fill(
+            "queue.pending_tasks",
+            &[
+                ("count", &status.pending_tasks.len()),
+                ("tasks", &status.pending_tasks.join(", ")),
+            ],
+        )
+    });
+    if let Some(position) = status.waiting_position {
+        lines.push(ctx.fill("queue.waiting", &[("position", &position)]));
+    }
+    if status.retry_count > 0 {
+        lines.push(ctx.fill("queue.retries", &[("count", &status.retry_count)]));
+    }
+    plain(lines.join("\n"))
+}
 
-    #[test]
-    fn model_switch_substring() {
-        let model = resolve_model("codex");
-        assert_eq!(model.id, "gpt-5.3-codex");
+fn handle_ps(ctx: &CommandContext) -> CommandResult {
+    let Some(containers) = &ctx.containers else {
+        return plain(ctx.fill("denied.main", &[("command", &"ps")]));
+    };
+    if containers.is_empty() {
+        return plain(ctx.text("ps.none"));
     }
 
-    #[test]
-    fn model_unknown_infers_runtime() {
-        let model = resolve_model("claude-haiku-4-5");
-        assert_eq!(model.runtime, "claude");
-        assert_eq!(model.id, "claude-haiku-4-5");
+    let mut lines = vec![ctx.fill("ps.title", &[("count", &containers.len())])];
+    for status in containers {
+        let group = status.group_folder.as_deref().unwrap_or(&status.group_jid);
+        let uptime = status
+            .uptime
+            .map(|up| ctx.fill("ps.up", &[("uptime", &elapsed_label(up))]))
+            .unwrap_or_default();
+        let name = status
+            .container_name
+            .as_deref()
+            .map(|name| format!("\n  {name}"))
+            .unwrap_or_default();
+        lines.push(format!(
+            "\n{group} — {}{uptime}{name}",
+            container_work(status, ctx)
+        ));
     }
+    plain(lines.join("\n"))
+}
 
-    #[test]
-    fn model_already_active() {
-        let result = handle_command(
-            "model",
-            "claude-opus-4-6",
-            Some("Test"),
-            Some("test"),
-            Some("claude-opus-4-6"),
-            None,
-            false,
-            &test_ctx(),
-        );
-        assert!(result.text.contains("Already using"));
+const LOG_LINES: usize = 30;
+const MAX_LOG_LINES: usize = 200;
+/// Room left in a Telegram message once the header and code fence are in.
+const MAX_LOG_CHARS: usize = 3500;
+
+/// The part of a run log worth reading first: its stderr section when a
+/// failed run wrote one, else the whole log.
+fn log_section(text: &str) -> (&'static str, &str) {
+    let stderr = text
+        .split_once("\n=== Stderr")
+        .and_then(|(_, rest)| rest.split_once('\n'))
+        .map(|(_, body)| body.split_once("\n=== ").map_or(body, |(body, _)| body))
+        .filter(|body| !body.trim().is_empty());
+    match stderr {
+        Some(body) => ("stderr", body),
+        None => ("log", text),
     }
+}
 
-    #[test]
-    fn reset_with_active_container() {
+fn handle_logs(args: &str, group_folder: Option<&str>, ctx: &CommandContext) -> CommandResult {
+    if group_folder.is_none() {
+        return plain(ctx.text("not_registered"));
+    }
+    let Some(log) = &ctx.last_log else {
+        return plain(ctx.text("logs.none"));
+    };
+    let lines = match args.trim() {
+        "" => LOG_LINES,
+        "full" => {
+            return CommandResult {
+                text: ctx.fill("logs.sending", &[("file", &log.file_name)]),
+                parse_mode: None,
+                effects: vec![CommandEffect::SendLog {
+                    file_name: log.file_name.clone(),
+                }],
+                reply_markup: None,
+            };
+        }
+        n => match n.parse::<usize>() {
+            Ok(n) => n.clamp(1, MAX_LOG_LINES),
+            Err(_) => return plain(ctx.text("logs.usage")),
+        },
+    };
+
+    let (section, body) = log_section(&log.text);
+    let all: Vec<&str> = body.trim_end().lines().collect();
+    let mut tail = all[all.len().saturating_sub(lines)..].join("\n");
+    let chars = tail.chars().count();
+    if chars > MAX_LOG_CHARS {
+        tail = format!(
+            "…{}",
+            tail.chars().skip(chars - MAX_LOG_CHARS).collect::<String>()
+        );
+    }
+    let shown = lines.min(all.len());
+    let title = ctx.fill(
+        if shown == 1 {
+            "logs.title_one"
+        } else {
+            "logs.title_other"
+        },
+        &[
+            ("file", &log.file_name),
+            ("count", &shown),
+            ("section", &section),
+        ],
+    );
+    CommandResult {
+        text: format!("{title}\n```\n{tail}\n```"),
+        parse_mode: Some("Markdown".into()),
+        effects: vec![],
+        reply_markup: Some(InlineKeyboardMarkup {
+            inline_keyboard: vec![vec![InlineKeyboardButton::command(
+                ctx.text("logs.full"),
+                "logs full",
+            )]],
+        }),
+    }
+}
+
+/// Periods `/usage` reports on, matching `CommandContext::usage`: the
+/// catalog key for each label and its length in days.
+pub const USAGE_PERIODS: [(&str, i64); 3] =
+    [("usage.day", 1), ("usage.week", 7), ("usage.month", 30)];
+
+/// `950`, `12.3k`, `4.1M`.
+fn token_count(tokens: i64) -> String {
+    if tokens < 1_000 {
+        tokens.to_string()
+    } else if tokens < 1_000_000 {
+        format!("{:.1}k", tokens as f64 / 1e3)
+    } else {
+        format!("{:.1}M", tokens as f64 / 1e6)
+    }
+}
+
+fn handle_usage(
+    group_name: Option<&str>,
+    group_folder: Option<&str>,
+    ctx: &CommandContext,
+) -> CommandResult {
+    if group_folder.is_none() {
+        return plain(ctx.text("not_registered"));
+    }
+    let Some(usage) = &ctx.usage else {
+        return plain(ctx.text("usage.no_storage"));
+    };
+
+    let name = group_name.map_or_else(|| ctx.text("unknown_group"), str::to_string);
+    let mut lines = vec![ctx.fill("usage.title", &[("name", &name)])];
+    for ((label, _), totals) in USAGE_PERIODS.iter().zip(usage) {
+        let period = ctx.text(label);
+        if totals.runs == 0 {
+            lines.push(ctx.fill("usage.no_runs", &[("period", &period)]));
+            continue;
+        }
+        let input = totals.input_tokens + totals.cache_read_tokens + totals.cache_write_tokens;
+        let cached = if totals.cache_read_tokens > 0 {
+            ctx.fill(
+                "usage.cached",
+                &[("tokens", &token_count(totals.cache_read_tokens))],
+            )
+        } else {
+            String::new()
+        };
+        let unpriced = match totals.unpriced_runs {
+            0 => String::new(),
+            n if n == totals.runs => ctx.text("usage.cost_unknown"),
+            n => ctx.fill("usage.unpriced", &[("count", &n)]),
+        };
+        lines.push(ctx.fill(
+            if totals.runs == 1 {
+                "usage.line_one"
+            } else {
+                "usage.line_other"
+            },
+            &[
+                ("period", &period),
+                ("runs", &totals.runs),
+                ("input", &token_count(input)),
+                ("cached", &cached),
+                ("output", &token_count(totals.output_tokens)),
+                ("cost", &format!("{:.2}", totals.cost_usd)),
+                ("unpriced", &unpriced),
+            ],
+        ));
+    }
+    CommandResult {
+        text: lines.join("\n"),
+        parse_mode: Some("Markdown".into()),
+        effects: vec![],
+        reply_markup: None,
+    }
+}
+
+/// A run's length as `42s` or `3m 05s`.
+fn run_duration_label(ms: i64) -> String {
+    let secs = (ms + 500) / 1000;
+    if secs < 60 {
+        format!("{secs}s")
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+fn handle_stats(
+    group_name: Option<&str>,
+    group_folder: Option<&str>,
+    current_model: Option<&str>,
+    ctx: &CommandContext,
+) -> CommandResult {
+    if group_folder.is_none() {
+        return plain(ctx.text("not_registered"));
+    }
+    let Some(stats) = &ctx.stats else {
+        return plain(ctx.text("stats.no_storage"));
+    };
+
+    let model_id = current_model.unwrap_or(DEFAULT_MODEL);
+    let model = find_model(model_id)
+        .map(|m| m.display_name)
+        .unwrap_or_else(|| model_id.to_string());
+    let name = group_name.map_or_else(|| ctx.text("unknown_group"), str::to_string);
+    let mut lines = vec![
+        ctx.fill("stats.title", &[("name", &name)]),
+        ctx.fill("stats.runs", &[("count", &stats.runs)]),
+    ];
+    if stats.runs > 0 {
+        lines.push(ctx.fill(
+            "stats.success",
+            &[
+                (
+                    "percent",
+                    &format!(
+                        "{:.0}",
+                        stats.succeeded_runs as f64 * 100.0 / stats.runs as f64
+                    ),
+                ),
+                ("succeeded", &stats.succeeded_runs),
+                ("runs", &stats.runs),
+            ],
+        ));
+        lines.push(ctx.fill(
+            "stats.duration",
+            &[(
+                "duration",
+                &run_duration_label(stats.duration_ms / stats.runs),
+            )],
+        ));
+    }
+    lines.push(ctx.fill("stats.model", &[("model", &model)]));
+    CommandResult {
+        text: lines.join("\n"),
+        parse_mode: Some("Markdown".into()),
+        effects: vec![],
+        reply_markup: None,
+    }
+}
+
+/// How long `/mute` lasts when no duration is given.
+const DEFAULT_MUTE_HOURS: i64 = 1;
+
+fn handle_mute(args: &str, group_folder: Option<&str>, ctx: &CommandContext) -> CommandResult {
+    if group_folder.is_none() {
+        return plain(ctx.text("not_registered"));
+    }
+    let duration = if args.trim().is_empty() {
+        chrono::Duration::hours(DEFAULT_MUTE_HOURS)
+    } else {
+        match scheduler::parse_spoken_duration(args) {
+            Some(duration) => duration,
+            None => return plain(ctx.text("mute.usage")),
+        }
+    };
+    let until = (ctx.now + duration).to_rfc3339();
+    CommandResult {
+        text: ctx.fill(
+            "mute.done",
+            &[
+                ("time", &local_time(&until, &ctx.timezone)),
+                ("timezone", &ctx.timezone),
+            ],
+        ),
+        parse_mode: None,
+        effects: vec![CommandEffect::SetMute { until: Some(until) }],
+        reply_markup: Some(InlineKeyboardMarkup {
+            inline_keyboard: vec![vec![InlineKeyboardButton::command(
+                ctx.text("mute.wake_now"),
+                "wake",
+            )]],
+        }),
+    }
+}
+
+fn handle_wake(group_folder: Option<&str>, ctx: &CommandContext) -> CommandResult {
+    if group_folder.is_none() {
+        return plain(ctx.text("not_registered"));
+    }
+    if !message_loop::is_muted(ctx.muted_until.as_deref(), ctx.now) {
+        return plain(ctx.text("wake.not_muted"));
+    }
+    CommandResult {
+        text: ctx.text("wake.done"),
+        parse_mode: None,
+        effects: vec![CommandEffect::SetMute { until: None }],
+        reply_markup: None,
+    }
+}
+
+fn handle_trigger(args: &str, group_folder: Option<&str>, ctx: &CommandContext) -> CommandResult {
+    let Some(trigger) = group_folder.and(ctx.trigger.as_ref()) else {
+        return plain(ctx.text("not_registered"));
+    };
+    let (action, pattern) = args
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((args.trim(), ""));
+    let pattern = pattern.trim();
+    let effect = match action.to_lowercase().as_str() {
+        "" | "show" => {
+            let mention = format!("@{}", ctx.assistant_name);
+            let text = if ctx.is_main {
+                ctx.text("trigger.main")
+            } else if !trigger.required {
+                ctx.fill("trigger.off_kept", &[("pattern", &trigger.pattern)])
+            } else {
+                ctx.fill(
+                    "trigger.required",
+                    &[("pattern", &trigger.pattern), ("mention", &mention)],
+                )
+            };
+            return plain(text);
+        }
+        "set" if pattern.is_empty() || pattern.chars().count() > 64 || pattern.contains('\n') => {
+            return plain(ctx.text("trigger.invalid"));
+        }
+        "set" => CommandEffect::SetTrigger {
+            pattern: pattern.to_string(),
+            required: true,
+        },
+        "off" => CommandEffect::SetTrigger {
+            pattern: trigger.pattern.clone(),
+            required: false,
+        },
+        _ => return plain(ctx.text("trigger.usage")),
+    };
+    if !ctx.registry.is_admin(ctx.sender.as_deref()) {
+        return plain(ctx.text("trigger.admins"));
+    }
+    let text = match &effect {
+        CommandEffect::SetTrigger {
+            pattern,
+            required: true,
+        } => ctx.fill("trigger.set", &[("pattern", pattern)]),
+        _ => ctx.text("trigger.off"),
+    };
+    CommandResult {
+        text,
+        parse_mode: None,
+        effects: vec![effect],
+        reply_markup: None,
+    }
+}
+
+fn handle_language(args: &str, group_folder: Option<&str>, ctx: &CommandContext) -> CommandResult {
+    if group_folder.is_none() {
+        return plain(ctx.text("not_registered"));
+    }
+    let available = i18n::supported().collect::<Vec<_>>().join(", ");
+    let code = args.trim();
+    if code.is_empty() {
+        return plain(ctx.fill(
+            "language.current",
+            &[
+                ("name", &ctx.text("language.name")),
+                ("code", &ctx.locale),
+                ("available", &available),
+            ],
+        ));
+    }
+    let Some(locale) = i18n::resolve(code) else {
+        return plain(ctx.fill(
+            "language.unsupported",
+            &[("code", &code), ("available", &available)],
+        ));
+    };
+    // Confirm in the new language
+    CommandResult {
+        text: i18n::fill(
+            locale,
+            "language.set",
+            &[("name", &i18n::text(locale, "language.name"))],
+        ),
+        parse_mode: None,
+        effects: vec![CommandEffect::SetLocale {
+            locale: locale.to_string(),
+        }],
+        reply_markup: None,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// HTTP endpoint for commands
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct CommandRequest {
+    pub chat_jid: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: String,
+    pub group_name: Option<String>,
+    pub group_folder: Option<String>,
+    pub current_model: Option<String>,
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub container_active: bool,
+    /// Channel-specific ID of the user who sent the command.
+    #[serde(default)]
+    pub sender: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_ctx() -> CommandContext {
+        CommandContext {
+            assistant_name: "TestBot".into(),
+            started_at: Instant::now(),
+            disk_usage: None,
+            tasks: None,
+            timezone: "UTC".into(),
+            now: "2026-10-17T08:00:00Z".parse().unwrap(),
+            queue: None,
+            containers: None,
+            last_log: None,
+            usage: None,
+            stats: None,
+            muted_until: None,
+            trigger: None,
+            registry: Arc::new(CommandRegistry::builtin()),
+            sender: None,
+            is_main: true,
+            locale: "en",
+        }
+    }
+
+    #[test]
+    fn help_command() {
+        let result = handle_command("help", "", None, None, None, None, false, &test_ctx());
+        assert!(result.text.contains("TestBot Commands"));
+        assert_eq!(result.parse_mode, Some("Markdown".into()));
+    }
+
+    #[test]
+    fn status_unregistered() {
+        let result = handle_command("status", "", None, None, None, None, false, &test_ctx());
+        assert!(result.text.contains("not registered"));
+    }
+
+    #[test]
+    fn status_registered() {
+        let result = handle_command(
+            "status",
+            "",
+            Some("Test Group"),
+            Some("test-group"),
+            Some("claude-opus-4-6"),
+            Some("sess-abc123def456"),
+            true,
+            &test_ctx(),
+        );
+        assert!(result.text.contains("Test Group"));
+        assert!(result.text.contains("Claude Opus 4.6"));
+        assert!(result.text.contains("active"));
+        assert!(result.text.contains("sess-abc123d"));
+        assert!(!result.text.contains("Disk:"));
+    }
+
+    #[test]
+    fn status_shows_disk_usage() {
+        let ctx = CommandContext {
+            disk_usage: Some("1.5 MB of 2.0 MB (over soft quota)".into()),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "status",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert!(
+            result
+                .text
+                .contains("Container: idle\nDisk: 1.5 MB of 2.0 MB (over soft quota)\n")
+        );
+    }
+
+    #[test]
+    fn model_catalog_display() {
+        let result = handle_command(
+            "model",
+            "",
+            Some("Test"),
+            Some("test"),
+            Some("claude-opus-4-6"),
+            None,
+            false,
+            &test_ctx(),
+        );
+        assert!(result.text.contains("Claude Opus 4.6"));
+        assert!(result.text.contains("(active)"));
+        assert!(result.text.contains("Gemini"));
+
+        let markup = result.reply_markup.expect("model keyboard");
+        markup.validate().unwrap();
+        let buttons: Vec<_> = markup.inline_keyboard.iter().flatten().collect();
+        assert_eq!(buttons.len(), model_catalog().len() - 1);
+        assert!(
+            buttons
+                .iter()
+                .all(|b| b.callback_data != "cmd:model claude-opus-4-6")
+        );
+        assert_eq!(buttons[0].callback_data, "cmd:model claude-sonnet-4-6");
+    }
+
+    #[test]
+    fn model_switch_by_number() {
+        let model = resolve_model("2");
+        assert_eq!(model.id, "claude-sonnet-4-6");
+    }
+
+    #[test]
+    fn model_switch_by_name() {
+        let model = resolve_model("gemini-3.1-pro");
+        assert_eq!(model.id, "gemini-3.1-pro");
+        assert_eq!(model.runtime, "gemini");
+    }
+
+    #[test]
+    fn model_switch_substring() {
+        let model = resolve_model("codex");
+        assert_eq!(model.id, "gpt-5.3-codex");
+    }
+
+    #[test]
+    fn model_unknown_infers_runtime() {
+        let model = resolve_model("claude-haiku-4-5");
+        assert_eq!(model.runtime, "claude");
+        assert_eq!(model.id, "claude-haiku-4-5");
+    }
+
+    #[test]
+    fn model_already_active() {
+        let result = handle_command(
+            "model",
+            "claude-opus-4-6",
+            Some("Test"),
+            Some("test"),
+            Some("claude-opus-4-6"),
+            None,
+            false,
+            &test_ctx(),
+        );
+        assert!(result.text.contains("Already using"));
+    }
+
+    #[test]
+    fn reset_with_active_container() {
         let result = handle_command(
             "reset",
             "",
@@ -565,20 +1598,41 @@ mod tests {
     #[test]
     fn reset_effects_with_active_container() {
         let result = handle_command(
-            "reset", "", Some("Test"), Some("test"), None, None, true, &test_ctx(),
+            "reset",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            true,
+            &test_ctx(),
+        );
+        assert_eq!(
+            result.effects,
+            vec![
+                CommandEffect::CancelQueued,
+                CommandEffect::KillContainer,
+                CommandEffect::ClearSession,
+            ]
         );
-        assert_eq!(result.effects, vec![
-            CommandEffect::KillContainer,
-            CommandEffect::ClearSession,
-        ]);
     }
 
     #[test]
     fn reset_effects_without_active_container() {
         let result = handle_command(
-            "reset", "", Some("Test"), Some("test"), None, None, false, &test_ctx(),
+            "reset",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &test_ctx(),
+        );
+        assert_eq!(
+            result.effects,
+            vec![CommandEffect::CancelQueued, CommandEffect::ClearSession]
         );
-        assert_eq!(result.effects, vec![CommandEffect::ClearSession]);
     }
 
     #[test]
@@ -628,4 +1682,749 @@ mod tests {
         let result = handle_command("reset", "", None, None, None, None, false, &test_ctx());
         assert!(result.effects.is_empty());
     }
+
+    fn task(id: &str, prompt: &str) -> ScheduledTask {
+        ScheduledTask {
+            id: id.into(),
+            group_folder: "test".into(),
+            chat_jid: "tg:1".into(),
+            prompt: prompt.into(),
+            schedule_type: "cron".into(),
+            schedule_value: "0 9 * * *".into(),
+            context_mode: "isolated".into(),
+            next_run: Some("2026-10-18T09:00:00Z".into()),
+            last_run: None,
+            last_result: None,
+            status: "active".into(),
+            created_at: "2026-10-01T00:00:00Z".into(),
+            task_config: None,
+        }
+    }
+
+    #[test]
+    fn tasks_lists_next_runs() {
+        let ctx = CommandContext {
+            tasks: Some(vec![task("task-1", "Morning digest")]),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "tasks",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert!(
+            result
+                .text
+                .contains("task-1 — cron 0 9 * * *, next 2026-10-18 09:00")
+        );
+        assert!(result.text.contains("Morning digest"));
+
+        let none = CommandContext {
+            tasks: Some(vec![]),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "tasks",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &none,
+        );
+        assert!(result.text.starts_with("No scheduled tasks"));
+
+        // Without storage there is nothing to list.
+        let result = handle_command(
+            "tasks",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &test_ctx(),
+        );
+        assert!(result.text.contains("storage"));
+    }
+
+    #[test]
+    fn schedule_creates_task() {
+        let ctx = CommandContext {
+            tasks: Some(vec![]),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "schedule",
+            "every weekday at 9am Summarize the inbox",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert_eq!(
+            result.effects,
+            vec![CommandEffect::CreateTask {
+                prompt: "Summarize the inbox".into(),
+                schedule_type: "cron".into(),
+                schedule_value: "0 9 * * 1-5".into(),
+                next_run: Some("2026-10-19T09:00:00+00:00".into()),
+            }]
+        );
+
+        for args in ["", "whenever works", "in 10m"] {
+            let result = handle_command(
+                "schedule",
+                args,
+                Some("Test"),
+                Some("test"),
+                None,
+                None,
+                false,
+                &ctx,
+            );
+            assert!(result.effects.is_empty(), "{args}");
+            assert!(result.text.contains("Usage: /schedule"), "{args}");
+        }
+    }
+
+    #[test]
+    fn cancel_only_own_tasks() {
+        let ctx = CommandContext {
+            tasks: Some(vec![task("task-1", "Morning digest")]),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "cancel",
+            "task-1",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert_eq!(
+            result.effects,
+            vec![CommandEffect::CancelTask {
+                task_id: "task-1".into()
+            }]
+        );
+
+        let result = handle_command(
+            "cancel",
+            "task-9",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert!(result.effects.is_empty());
+        assert!(result.text.starts_with("No task task-9"));
+
+        let result = handle_command("cancel", "task-1", None, None, None, None, false, &ctx);
+        assert_eq!(result.text, "This chat is not registered.");
+    }
+
+    #[test]
+    fn queue_shows_group_state() {
+        let ctx = CommandContext {
+            queue: Some(GroupQueueStatus {
+                group_jid: "tg:1".into(),
+                active: true,
+                container_name: Some("nanoclaw-test-1".into()),
+                uptime: Some(Duration::from_secs(3_900)),
+                pending_messages: true,
+                pending_tasks: vec!["task-1".into()],
+                retry_count: 2,
+                ..Default::default()
+            }),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "queue",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            true,
+            &ctx,
+        );
+        assert_eq!(
+            result.text,
+            "Container: running messages for 1h 5m\n\
+             Name: nanoclaw-test-1\n\
+             Pending messages: yes\n\
+             Pending tasks: 1 (task-1)\n\
+             Retries: 2"
+        );
+        assert!(result.effects.is_empty());
+    }
+
+    #[test]
+    fn ps_is_main_only() {
+        let result = handle_command(
+            "ps",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &test_ctx(),
+        );
+        assert_eq!(result.text, "/ps is only available in the main group.");
+
+        let ctx = CommandContext {
+            containers: Some(vec![GroupQueueStatus {
+                group_jid: "tg:2".into(),
+                active: true,
+                is_task_container: true,
+                group_folder: Some("family".into()),
+                uptime: Some(Duration::from_secs(120)),
+                running_tasks: vec!["task-7".into()],
+                ..Default::default()
+            }]),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "ps",
+            "",
+            Some("Main"),
+            Some("main"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert_eq!(
+            result.text,
+            "Running containers (1):\n\nfamily — task task-7, up 2m"
+        );
+
+        let ctx = CommandContext {
+            containers: Some(vec![]),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "ps",
+            "",
+            Some("Main"),
+            Some("main"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert_eq!(result.text, "No containers running.");
+    }
+
+    #[test]
+    fn logs_prefer_stderr_tail() {
+        let ctx = CommandContext {
+            last_log: Some(RunLog {
+                file_name: "container-1.error.log".into(),
+                text:
+                    "=== Container Run Log ===\nExit Code: Some(1)\n\n=== Mounts ===\n/a -> /b\n\n\
+                       === Stderr ===\nboot\npanic: boom\n\n=== Stdout ===\nhello"
+                        .into(),
+            }),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "logs",
+            "1",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert_eq!(
+            result.text,
+            "*container-1.error.log* — last 1 line of stderr\n```\npanic: boom\n```"
+        );
+        assert_eq!(
+            result.reply_markup.unwrap().inline_keyboard[0][0].callback_data,
+            "cmd:logs full"
+        );
+
+        let result = handle_command(
+            "logs",
+            "full",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert_eq!(
+            result.effects,
+            vec![CommandEffect::SendLog {
+                file_name: "container-1.error.log".into()
+            }]
+        );
+
+        let result = handle_command(
+            "logs",
+            "lots",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert!(result.text.starts_with("Usage: /logs"));
+    }
+
+    #[test]
+    fn logs_without_stderr_show_the_whole_log() {
+        let ctx = CommandContext {
+            last_log: Some(RunLog {
+                file_name: "container-2.log".into(),
+                text: format!("=== Container Run Log ===\n{}", "x".repeat(5000)),
+            }),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "logs",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert!(
+            result
+                .text
+                .starts_with("*container-2.log* — last 2 lines of log\n```\n…x")
+        );
+        assert!(result.text.chars().count() < 4096);
+
+        let result = handle_command(
+            "logs",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &test_ctx(),
+        );
+        assert_eq!(result.text, "No container runs logged for this group yet.");
+    }
+
+    #[test]
+    fn usage_summarizes_periods() {
+        let day = UsageTotals {
+            runs: 1,
+            input_tokens: 800,
+            output_tokens: 1_200,
+            cost_usd: 0.031,
+            ..UsageTotals::default()
+        };
+        let month = UsageTotals {
+            runs: 12,
+            input_tokens: 400_000,
+            cache_read_tokens: 2_100_000,
+            output_tokens: 95_000,
+            cost_usd: 4.5,
+            unpriced_runs: 2,
+            ..UsageTotals::default()
+        };
+        let ctx = CommandContext {
+            usage: Some([day, UsageTotals::default(), month]),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "usage",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert_eq!(
+            result.text,
+            "*Usage for Test*\n\n\
+             Last 24h: 1 run, 800 in / 1.2k out tokens, ~$0.03\n\
+             Last 7 days: no runs\n\
+             Last 30 days: 12 runs, 2.5M in (2.1M cached) / 95.0k out tokens, ~$4.50 (2 unpriced)"
+        );
+
+        let result = handle_command(
+            "usage",
+            "",
+            Some("Test"),
+            Some("test"),
+            None,
+            None,
+            false,
+            &test_ctx(),
+        );
+        assert!(result.text.contains("storage"));
+    }
+
+    #[test]
+    fn mute_and_wake() {
+        let ctx = test_ctx();
+        let mute = |args: &str| {
+            handle_command(
+                "mute",
+                args,
+                Some("Ops"),
+                Some("ops"),
+                None,
+                None,
+                false,
+                &ctx,
+            )
+        };
+
+        let result = mute("");
+        assert!(
+            result
+                .text
+                .starts_with("Muted until 2026-10-17 09:00 (UTC)"),
+            "{}",
+            result.text
+        );
+        assert_eq!(
+            result.effects,
+            vec![CommandEffect::SetMute {
+                until: Some("2026-10-17T09:00:00+00:00".into())
+            }]
+        );
+        assert_eq!(
+            mute("for 2 days").effects,
+            vec![CommandEffect::SetMute {
+                until: Some("2026-10-19T08:00:00+00:00".into())
+            }]
+        );
+        assert!(mute("30m").text.starts_with("Muted until 2026-10-17 08:30"));
+        let bad = mute("until lunch");
+        assert!(bad.text.starts_with("Usage: /mute"));
+        assert!(bad.effects.is_empty());
+
+        let wake = |muted_until: Option<&str>| {
+            let ctx = CommandContext {
+                muted_until: muted_until.map(str::to_string),
+                ..test_ctx()
+            };
+            handle_command(
+                "wake",
+                "",
+                Some("Ops"),
+                Some("ops"),
+                None,
+                None,
+                false,
+                &ctx,
+            )
+        };
+        assert_eq!(
+            wake(Some("2026-10-17T09:00:00+00:00")).effects,
+            vec![CommandEffect::SetMute { until: None }]
+        );
+        let expired = wake(Some("2026-10-17T07:00:00+00:00"));
+        assert_eq!(expired.text, "Not muted.");
+        assert!(expired.effects.is_empty());
+        assert_eq!(wake(None).text, "Not muted.");
+    }
+
+    #[test]
+    fn registry_runs_prompt_commands_with_permissions() {
+        let prompt = |prompt: &str, permission: CommandPermission| PromptCommand {
+            description: String::new(),
+            prompt: prompt.into(),
+            permission,
+        };
+        let config = CommandsConfig {
+            admins: vec!["42".into()],
+            custom: [
+                (
+                    "standup".into(),
+                    prompt(
+                        "Summarize yesterday's commits. {args}",
+                        CommandPermission::Anyone,
+                    ),
+                ),
+                (
+                    "deploy".into(),
+                    prompt("Deploy staging.", CommandPermission::Admin),
+                ),
+                (
+                    "digest".into(),
+                    prompt("Write the weekly digest.", CommandPermission::Main),
+                ),
+                (
+                    "status".into(),
+                    prompt("Shadows a built-in.", CommandPermission::Anyone),
+                ),
+                (
+                    "Bad-Name".into(),
+                    prompt("Not a Telegram command.", CommandPermission::Anyone),
+                ),
+            ]
+            .into(),
+        };
+        let registry = Arc::new(CommandRegistry::from_config(&config));
+        assert!(registry.get("bad-name").is_none() && registry.get("Bad-Name").is_none());
+        assert!(matches!(
+            registry.get("status").unwrap().handler,
+            CommandHandler::Builtin(_)
+        ));
+        assert!(registry.get("new").is_some_and(|spec| spec.name == "reset"));
+
+        let run = |command: &str, args: &str, sender: Option<&str>, is_main: bool| {
+            let ctx = CommandContext {
+                registry: registry.clone(),
+                sender: sender.map(str::to_string),
+                is_main,
+                ..test_ctx()
+            };
+            handle_command(
+                command,
+                args,
+                Some("Ops"),
+                Some("ops"),
+                None,
+                None,
+                false,
+                &ctx,
+            )
+        };
+
+        let standup = run("standup", "focus on the API", None, false);
+        assert_eq!(standup.text, "Running /standup.");
+        assert_eq!(
+            standup.effects,
+            vec![CommandEffect::InjectPrompt {
+                prompt: "Summarize yesterday's commits. focus on the API".into()
+            }]
+        );
+        assert_eq!(
+            run("deploy", "", Some("7"), true).text,
+            "/deploy is restricted to admins."
+        );
+        assert_eq!(
+            run("deploy", "now", Some("42"), false).effects,
+            vec![CommandEffect::InjectPrompt {
+                prompt: "Deploy staging.\n\nnow".into()
+            }]
+        );
+        assert_eq!(
+            run("digest", "", None, false).text,
+            "/digest is only available in the main group."
+        );
+        assert_eq!(
+            run("ps", "", None, false).text,
+            "/ps is only available in the main group."
+        );
+        assert_eq!(run("ping", "", None, true).text, "Unknown command: /ping");
+
+        let help = run("help", "", None, true).text;
+        assert!(
+            help.ends_with("/standup [text] — Run a saved prompt"),
+            "{help}"
+        );
+        assert!(help.contains("/deploy — Run a saved prompt (admins only)"));
+        assert!(help.contains("/revert\\_last — Undo the last run's file changes (admins only)"));
+    }
+
+    #[test]
+    fn trigger_shows_and_changes_settings() {
+        let config = CommandsConfig {
+            admins: vec!["42".into()],
+            ..CommandsConfig::default()
+        };
+        let run = |args: &str, sender: Option<&str>, required: bool| {
+            let ctx = CommandContext {
+                trigger: Some(GroupTrigger {
+                    pattern: "@Andy".into(),
+                    required,
+                }),
+                registry: Arc::new(CommandRegistry::from_config(&config)),
+                sender: sender.map(str::to_string),
+                is_main: false,
+                ..test_ctx()
+            };
+            handle_command(
+                "trigger",
+                args,
+                Some("Ops"),
+                Some("ops"),
+                None,
+                None,
+                false,
+                &ctx,
+            )
+        };
+
+        assert_eq!(
+            run("", None, true).text,
+            "Messages must start with @Andy or @TestBot."
+        );
+        assert!(run("show", None, false).text.starts_with("Trigger off"));
+        assert_eq!(
+            run("set @Ops", Some("7"), true).text,
+            "Changing the trigger is restricted to admins."
+        );
+        assert!(run("set @Ops", Some("7"), true).effects.is_empty());
+
+        let set = run("set  hey bot ", Some("42"), false);
+        assert_eq!(set.text, "Messages must now start with hey bot.");
+        assert_eq!(
+            set.effects,
+            vec![CommandEffect::SetTrigger {
+                pattern: "hey bot".into(),
+                required: true
+            }]
+        );
+        assert_eq!(
+            run("off", Some("42"), true).effects,
+            vec![CommandEffect::SetTrigger {
+                pattern: "@Andy".into(),
+                required: false
+            }]
+        );
+        assert!(
+            run("set", Some("42"), true)
+                .text
+                .starts_with("The pattern must be")
+        );
+        assert_eq!(
+            run("sometimes", Some("42"), true).text,
+            "Usage: /trigger [show], /trigger set <pattern> or /trigger off."
+        );
+    }
+
+    #[test]
+    fn replies_follow_the_group_language() {
+        let ctx = CommandContext {
+            locale: "de",
+            ..test_ctx()
+        };
+        let run = |command: &str, args: &str| {
+            handle_command(
+                command,
+                args,
+                Some("Ops"),
+                Some("ops"),
+                None,
+                None,
+                false,
+                &ctx,
+            )
+        };
+
+        assert_eq!(
+            run("language", "").text,
+            "Antworten sind auf Deutsch (de). Verfügbar: en, de, es. Ändern mit /language <Code>."
+        );
+        assert_eq!(
+            run("reset", "").text,
+            "Sitzung gelöscht. Die nächste Nachricht beginnt eine neue Sitzung."
+        );
+        assert_eq!(run("nope", "").text, "Unbekannter Befehl: /nope");
+        let help = run("help", "").text;
+        assert!(help.starts_with("*TestBot – Befehle*\n"));
+        assert!(help.contains("/ps — Laufende Container auflisten (nur Hauptgruppe)"));
+
+        let switch = run("language", "ES-mx");
+        assert_eq!(switch.text, "Ahora las respuestas están en Español.");
+        assert_eq!(
+            switch.effects,
+            vec![CommandEffect::SetLocale {
+                locale: "es".into()
+            }]
+        );
+        assert!(
+            run("language", "fr")
+                .text
+                .starts_with("Noch keine Antworten auf fr.")
+        );
+        assert!(run("language", "fr").effects.is_empty());
+    }
+
+    #[test]
+    fn stats_show_the_last_day() {
+        let stats = UsageTotals {
+            runs: 4,
+            succeeded_runs: 3,
+            duration_ms: 250_000,
+            ..UsageTotals::default()
+        };
+        let ctx = CommandContext {
+            stats: Some(stats),
+            ..test_ctx()
+        };
+        let result = handle_command(
+            "stats",
+            "",
+            Some("Ops"),
+            Some("ops"),
+            Some("gemini-2.5-flash"),
+            None,
+            false,
+            &ctx,
+        );
+        assert_eq!(
+            result.text,
+            "*Stats for Ops* (last 24h)\n\nRuns: 4\nSuccess rate: 75% (3 of 4)\n\
+             Average duration: 1m 03s\nModel: `Gemini 2.5 Flash`"
+        );
+
+        let ctx = CommandContext {
+            stats: Some(UsageTotals::default()),
+            ..test_ctx()
+        };
+        let idle = handle_command(
+            "stats",
+            "",
+            Some("Ops"),
+            Some("ops"),
+            None,
+            None,
+            false,
+            &ctx,
+        );
+        assert!(
+            idle.text.ends_with("Runs: 0\nModel: `Claude Opus 4.6`"),
+            "{}",
+            idle.text
+        );
+        let no_storage = handle_command(
+            "stats",
+            "",
+            Some("Ops"),
+            Some("ops"),
+            None,
+            None,
+            false,
+            &test_ctx(),
+        );
+        assert_eq!(
+            no_storage.text,
+            "Run stats need storage, which is not configured."
+        );
+    }
 }
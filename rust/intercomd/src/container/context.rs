@@ -0,0 +1,363 @@
+//! Pre-run `context.json` for runner images.
+//!
+//! Written next to `current_tasks.json` in the group's IPC directory before
+//! every container run. It gathers what a runner would otherwise have to
+//! reconstruct: the group's registration, the runtime and model actually in
+//! use, the channel's formatting limits, the group's active reminders, and
+//! its quiet-hours state.
+//!
+//! The layout is versioned by `schema`. Fields are only ever added within a
+//! version; renames or removals bump `CONTEXT_SCHEMA_VERSION`.
+
+use std::path::Path;
+
+use chrono::{DateTime, NaiveTime, Timelike, Utc};
+use intercom_core::{MarkdownDialect, RegisteredGroup, RuntimeKind, ScheduledTask};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::runner::RunConfig;
+use super::security::ContainerConfig;
+
+pub const CONTEXT_SCHEMA_VERSION: u32 = 2;
+pub const CONTEXT_FILE: &str = "context.json";
+
+/// Daily window, in the scheduler timezone, during which the group prefers
+/// not to be messaged. Set as `containerConfig.quietHours`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// "HH:MM", inclusive.
+    pub start: String,
+    /// "HH:MM", exclusive. May be earlier than `start` for overnight windows.
+    pub end: String,
+}
+
+impl QuietHours {
+    /// Whether `time` falls inside the window; `None` if either bound is not
+    /// a valid "HH:MM".
+    pub fn contains(&self, time: NaiveTime) -> Option<bool> {
+        let start = NaiveTime::parse_from_str(&self.start, "%H:%M").ok()?;
+        let end = NaiveTime::parse_from_str(&self.end, "%H:%M").ok()?;
+        let time = time.with_second(0)?.with_nanosecond(0)?;
+        Some(if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupContext {
+    pub schema: u32,
+    pub generated_at: String,
+    pub group: GroupSection,
+    pub runtime: RuntimeSection,
+    pub channel: ChannelSection,
+    pub reminders: Vec<Reminder>,
+    pub quiet_hours: QuietHoursState,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupSection {
+    pub jid: String,
+    pub name: String,
+    pub folder: String,
+    pub trigger: String,
+    pub requires_trigger: bool,
+    pub is_main: bool,
+    pub added_at: String,
+    /// Registered container config with secrets removed.
+    pub container_config: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeSection {
+    pub runtime: String,
+    /// Group override, else the runtime profile's default.
+    pub model: Option<String>,
+    pub protocol: String,
+    pub is_scheduled_task: bool,
+    pub timezone: String,
+}
+
+/// What the group's chat can render, so the agent can format for it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelSection {
+    pub name: String,
+    /// Longer replies are split into several messages.
+    pub max_chars: usize,
+    pub markdown: MarkdownDialect,
+    pub supports_edit: bool,
+    pub supports_files: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reminder {
+    pub id: String,
+    pub prompt: String,
+    pub schedule_type: String,
+    pub schedule_value: String,
+    pub next_run: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietHoursState {
+    pub configured: bool,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    /// Whether quiet hours are in effect right now.
+    pub active: bool,
+}
+
+/// `containerConfig` keys passed through to `context.json`. Anything else
+/// is left out: webhooks carry secrets, and webhook or calendar URLs often
+/// embed access tokens.
+const EXPOSED_CONTAINER_CONFIG_KEYS: &[&str] = &[
+    "additionalMounts",
+    "timeout",
+    "quietHours",
+    "cpus",
+    "memory",
+    "pidsLimit",
+    "network",
+    "maxOutputBytes",
+    "diskSoftMb",
+    "diskHardMb",
+    "maxConcurrent",
+    "maxRunsPerHour",
+    "acceptMailFrom",
+];
+
+/// Registered container config as JSON, limited to the keys above.
+fn redacted_container_config(group: &RegisteredGroup) -> Option<serde_json::Value> {
+    let config = group.container_config.as_ref()?.as_object()?;
+    let exposed = config
+        .iter()
+        .filter(|(key, _)| EXPOSED_CONTAINER_CONFIG_KEYS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    Some(serde_json::Value::Object(exposed))
+}
+
+pub struct ContextInputs<'a> {
+    pub group: &'a RegisteredGroup,
+    pub container_config: Option<&'a ContainerConfig>,
+    pub is_main: bool,
+    pub runtime: RuntimeKind,
+    pub is_scheduled_task: bool,
+    /// Any task list; only this group's active tasks are kept.
+    pub tasks: &'a [ScheduledTask],
+}
+
+impl GroupContext {
+    pub fn build(inputs: &ContextInputs<'_>, run_config: &RunConfig, now: DateTime<Utc>) -> Self {
+        let group = inputs.group;
+        let tz: chrono_tz::Tz = run_config.timezone.parse().unwrap_or(chrono_tz::Tz::UTC);
+        let local_time = now.with_timezone(&tz).time();
+
+        let quiet = inputs.container_config.and_then(|c| c.quiet_hours.as_ref());
+        let quiet_hours = QuietHoursState {
+            configured: quiet.is_some(),
+            start: quiet.map(|q| q.start.clone()),
+            end: quiet.map(|q| q.end.clone()),
+            active: quiet.and_then(|q| q.contains(local_time)).unwrap_or(false),
+        };
+
+        let reminders = inputs
+            .tasks
+            .iter()
+            .filter(|t| t.group_folder == group.folder && t.status == "active")
+            .map(|t| Reminder {
+                id: t.id.clone(),
+                prompt: t.prompt.clone(),
+                schedule_type: t.schedule_type.clone(),
+                schedule_value: t.schedule_value.clone(),
+                next_run: t.next_run.clone(),
+            })
+            .collect();
+
+        let channel_name = run_config.channels.channel_for_jid(&group.jid);
+        let channel = run_config.channels.profile(channel_name);
+
+        Self {
+            schema: CONTEXT_SCHEMA_VERSION,
+            generated_at: now.to_rfc3339(),
+            group: GroupSection {
+                jid: group.jid.clone(),
+                name: group.name.clone(),
+                folder: group.folder.clone(),
+                trigger: group.trigger.clone(),
+                requires_trigger: group.requires_trigger.unwrap_or(true),
+                is_main: inputs.is_main,
+                added_at: group.added_at.clone(),
+                container_config: redacted_container_config(group),
+            },
+            runtime: RuntimeSection {
+                runtime: inputs.runtime.as_str().to_string(),
+                model: run_config.resolve_model(inputs.runtime, group.model.as_deref()),
+                protocol: run_config.protocol_for(inputs.runtime).as_str().to_string(),
+                is_scheduled_task: inputs.is_scheduled_task,
+                timezone: tz.name().to_string(),
+            },
+            channel: ChannelSection {
+                name: channel_name.to_string(),
+                max_chars: channel.max_chars,
+                markdown: channel.markdown,
+                supports_edit: channel.supports_edit,
+                supports_files: channel.supports_files,
+            },
+            reminders,
+            quiet_hours,
+        }
+    }
+}
+
+/// Write `context.json` into the group's IPC directory. Failures are logged;
+/// a missing context file never blocks the run.
+pub async fn write_group_context(data_dir: &Path, group_folder: &str, context: &GroupContext) {
+    let ipc_dir = data_dir.join("ipc").join(group_folder);
+    tokio::fs::create_dir_all(&ipc_dir).await.ok();
+
+    let json = match serde_json::to_string_pretty(context) {
+        Ok(json) => json,
+        Err(e) => {
+            warn!(error = %e, "Failed to serialize group context");
+            return;
+        }
+    };
+    if let Err(e) = tokio::fs::write(ipc_dir.join(CONTEXT_FILE), json).await {
+        warn!(error = %e, "Failed to write group context");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hh: u32, mm: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hh, mm, 30).unwrap()
+    }
+
+    fn task(id: &str, folder: &str, status: &str) -> ScheduledTask {
+        ScheduledTask {
+            id: id.into(),
+            group_folder: folder.into(),
+            chat_jid: "tg:1".into(),
+            prompt: "water the plants".into(),
+            schedule_type: "cron".into(),
+            schedule_value: "0 9 * * *".into(),
+            context_mode: "isolated".into(),
+            next_run: Some("2026-10-17T09:00:00.000Z".into()),
+            last_run: None,
+            last_result: None,
+            status: status.into(),
+            created_at: String::new(),
+            task_config: None,
+        }
+    }
+
+    #[test]
+    fn quiet_hours_handle_overnight_windows() {
+        let night = QuietHours {
+            start: "22:00".into(),
+            end: "07:00".into(),
+        };
+        assert_eq!(night.contains(time(23, 15)), Some(true));
+        assert_eq!(night.contains(time(6, 59)), Some(true));
+        assert_eq!(night.contains(time(7, 0)), Some(false));
+        assert_eq!(night.contains(time(12, 0)), Some(false));
+
+        let lunch = QuietHours {
+            start: "12:00".into(),
+            end: "13:00".into(),
+        };
+        assert_eq!(lunch.contains(time(12, 0)), Some(true));
+        assert_eq!(lunch.contains(time(13, 0)), Some(false));
+
+        let bad = QuietHours {
+            start: "noon".into(),
+            end: "13:00".into(),
+        };
+        assert_eq!(bad.contains(time(12, 0)), None);
+    }
+
+    #[test]
+    fn context_collects_group_runtime_and_reminders() {
+        let group = RegisteredGroup {
+            jid: "tg:1".into(),
+            name: "Team".into(),
+            folder: "team".into(),
+            trigger: "@Amtiskaw".into(),
+            added_at: "2026-01-01T00:00:00Z".into(),
+            container_config: Some(serde_json::json!({
+                "quietHours": {"start": "22:00", "end": "07:00"},
+                "webhook": {"url": "https://example.com/h", "secret": "hunter2"},
+                "channelWebhook": {"url": "https://example.com/c?token=t0k", "secret": "hunter3"},
+                "calendar": {"url": "https://example.com/cal.ics?token=t0k"},
+                "timeout": 600000,
+            })),
+            requires_trigger: None,
+            runtime: Some("gemini".into()),
+            model: None,
+            muted_until: None,
+            locale: None,
+        };
+        let container_config: ContainerConfig =
+            serde_json::from_value(group.container_config.clone().unwrap()).unwrap();
+        let run_config = RunConfig {
+            timezone: "Europe/Berlin".into(),
+            runtime_profiles: intercom_core::IntercomConfig::default().runtimes.profiles,
+            ..Default::default()
+        };
+        let tasks = [
+            task("t1", "team", "active"),
+            task("t2", "team", "paused"),
+            task("t3", "other", "active"),
+        ];
+
+        // 21:30 UTC is 23:30 in Berlin (CEST).
+        let now = "2026-07-01T21:30:00Z".parse::<DateTime<Utc>>().unwrap();
+        let context = GroupContext::build(
+            &ContextInputs {
+                group: &group,
+                container_config: Some(&container_config),
+                is_main: false,
+                runtime: RuntimeKind::Gemini,
+                is_scheduled_task: true,
+                tasks: &tasks,
+            },
+            &run_config,
+            now,
+        );
+
+        let json = serde_json::to_value(&context).unwrap();
+        assert_eq!(json["schema"], CONTEXT_SCHEMA_VERSION);
+        assert_eq!(json["group"]["requiresTrigger"], true);
+        let exposed = &json["group"]["containerConfig"];
+        assert_eq!(exposed["timeout"], 600000);
+        assert_eq!(exposed["quietHours"]["start"], "22:00");
+        for key in ["webhook", "channelWebhook", "calendar"] {
+            assert!(exposed.get(key).is_none(), "{key} leaked into context.json");
+        }
+        let text = json.to_string();
+        assert!(!text.contains("hunter") && !text.contains("t0k"), "{text}");
+        assert_eq!(json["runtime"]["runtime"], "gemini");
+        assert_eq!(json["runtime"]["model"], "gemini-3.1-pro");
+        assert_eq!(json["runtime"]["protocol"], "marker-json");
+        assert_eq!(json["runtime"]["isScheduledTask"], true);
+        assert_eq!(json["channel"]["name"], "telegram");
+        assert_eq!(json["channel"]["maxChars"], 4096);
+        assert_eq!(json["channel"]["markdown"], "markdown_v2");
+        assert_eq!(json["reminders"].as_array().unwrap().len(), 1);
+        assert_eq!(json["reminders"][0]["id"], "t1");
+        assert_eq!(json["quietHours"]["configured"], true);
+        assert_eq!(json["quietHours"]["active"], true);
+    }
+}
@@ -0,0 +1,260 @@
+//! Disk usage accounting and quotas for group folders.
+//!
+//! Group folders are mounted read-write and can grow without bound. Every
+//! `[container.quota] scan_interval_secs` each registered group's
+//! `groups/{folder}` is measured (apparent size, symlinks not followed).
+//! Usage past the soft quota is logged and flagged in `/status`; past the
+//! hard quota new runs are refused until the folder shrinks and the next
+//! scan sees it. A group's container config can override both limits.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use intercom_core::{DiskQuotaConfig, RegisteredGroup};
+use tokio::sync::watch;
+use tracing::warn;
+
+use super::security::ContainerConfig;
+
+const MB: u64 = 1024 * 1024;
+
+/// A group's limits in bytes; `None` is unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaStatus {
+    Within,
+    OverSoft,
+    OverHard,
+}
+
+/// Last measured size of each group folder, shared by the scanner, the
+/// runner and `/status`.
+#[derive(Clone, Default)]
+pub struct DiskQuotas {
+    config: DiskQuotaConfig,
+    usage: Arc<RwLock<HashMap<String, u64>>>,
+}
+
+fn limit(mb: u64) -> Option<u64> {
+    (mb > 0).then(|| mb * MB)
+}
+
+pub fn format_mb(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / MB as f64)
+}
+
+impl DiskQuotas {
+    pub fn new(config: DiskQuotaConfig) -> Self {
+        Self {
+            config,
+            usage: Arc::default(),
+        }
+    }
+
+    pub fn limits(&self, group: Option<&ContainerConfig>) -> Quota {
+        let soft = group
+            .and_then(|c| c.disk_soft_mb)
+            .unwrap_or(self.config.soft_mb);
+        let hard = group
+            .and_then(|c| c.disk_hard_mb)
+            .unwrap_or(self.config.hard_mb);
+        Quota {
+            soft: limit(soft),
+            hard: limit(hard),
+        }
+    }
+
+    /// Bytes the folder held at the last scan, if it was scanned.
+    pub fn usage(&self, folder: &str) -> Option<u64> {
+        self.usage.read().unwrap().get(folder).copied()
+    }
+
+    pub fn record(&self, folder: &str, bytes: u64) {
+        self.usage
+            .write()
+            .unwrap()
+            .insert(folder.to_string(), bytes);
+    }
+
+    pub fn status(&self, folder: &str, group: Option<&ContainerConfig>) -> QuotaStatus {
+        let Some(used) = self.usage(folder) else {
+            return QuotaStatus::Within;
+        };
+        let quota = self.limits(group);
+        if quota.hard.is_some_and(|hard| used > hard) {
+            QuotaStatus::OverHard
+        } else if quota.soft.is_some_and(|soft| used > soft) {
+            QuotaStatus::OverSoft
+        } else {
+            QuotaStatus::Within
+        }
+    }
+
+    /// Why a new run for the group is refused: it is over its hard quota.
+    pub fn refusal(&self, folder: &str, group: Option<&ContainerConfig>) -> Option<String> {
+        if self.status(folder, group) != QuotaStatus::OverHard {
+            return None;
+        }
+        let used = self.usage(folder).unwrap_or_default();
+        let hard = self.limits(group).hard.unwrap_or_default();
+        Some(format!(
+            "Workspace for {folder} is over its disk quota ({} of {}); free up space to run again",
+            format_mb(used),
+            format_mb(hard)
+        ))
+    }
+
+    /// One line for `/status`, once the folder has been scanned.
+    pub fn summary(&self, folder: &str, group: Option<&ContainerConfig>) -> Option<String> {
+        let used = self.usage(folder)?;
+        let quota = self.limits(group);
+        let mut line = format_mb(used);
+        if let Some(limit) = quota.hard.or(quota.soft) {
+            line.push_str(&format!(" of {}", format_mb(limit)));
+        }
+        match self.status(folder, group) {
+            QuotaStatus::Within => {}
+            QuotaStatus::OverSoft => line.push_str(" (over soft quota)"),
+            QuotaStatus::OverHard => line.push_str(" (over quota, runs paused)"),
+        }
+        Some(line)
+    }
+}
+
+/// Apparent size of everything under `path`, without following symlinks.
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let meta = entry.path().symlink_metadata()?;
+            if meta.is_dir() {
+                pending.push(entry.path());
+            } else {
+                total += meta.len();
+            }
+        }
+    }
+    Ok(total)
+}
+
+/// Measure every registered group's folder on an interval.
+pub async fn run_scanner(
+    quotas: DiskQuotas,
+    groups_dir: PathBuf,
+    groups: Arc<tokio::sync::RwLock<HashMap<String, RegisteredGroup>>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let interval = Duration::from_secs(quotas.config.scan_interval_secs.max(10));
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.changed() => break,
+        }
+        let folders: Vec<(String, Option<ContainerConfig>)> = groups
+            .read()
+            .await
+            .values()
+            .map(|g| {
+                let config = g
+                    .container_config
+                    .as_ref()
+                    .and_then(|v| serde_json::from_value(v.clone()).ok());
+                (g.folder.clone(), config)
+            })
+            .collect();
+        for (folder, config) in folders {
+            let path = groups_dir.join(&folder);
+            match tokio::task::spawn_blocking(move || dir_size(&path)).await {
+                Ok(Ok(bytes)) => quotas.record(&folder, bytes),
+                Ok(Err(e)) => {
+                    warn!(group = %folder, error = %e, "Disk usage scan failed");
+                    continue;
+                }
+                Err(_) => continue,
+            }
+            let status = quotas.status(&folder, config.as_ref());
+            if status != QuotaStatus::Within {
+                warn!(
+                    group = %folder,
+                    usage = %quotas.summary(&folder, config.as_ref()).unwrap_or_default(),
+                    hard = status == QuotaStatus::OverHard,
+                    "Group folder over its disk quota"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dir_size_counts_files_recursively() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), vec![0u8; 1000]).unwrap();
+        std::fs::create_dir_all(dir.path().join("sub/deeper")).unwrap();
+        std::fs::write(dir.path().join("sub/deeper/b.bin"), vec![0u8; 2500]).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/", dir.path().join("sub/root")).unwrap();
+
+        let size = dir_size(dir.path()).unwrap();
+        assert!((3500..3600).contains(&size));
+        assert_eq!(dir_size(&dir.path().join("missing")).unwrap(), 0);
+    }
+
+    #[test]
+    fn group_overrides_and_status() {
+        let quotas = DiskQuotas::new(DiskQuotaConfig {
+            soft_mb: 1,
+            hard_mb: 2,
+            scan_interval_secs: 60,
+        });
+        assert_eq!(quotas.status("team", None), QuotaStatus::Within);
+        assert!(quotas.summary("team", None).is_none());
+
+        quotas.record("team", 3 * MB / 2);
+        assert_eq!(quotas.status("team", None), QuotaStatus::OverSoft);
+        assert_eq!(
+            quotas.summary("team", None).as_deref(),
+            Some("1.5 MB of 2.0 MB (over soft quota)")
+        );
+        assert!(quotas.refusal("team", None).is_none());
+
+        quotas.record("team", 3 * MB);
+        assert!(
+            quotas
+                .refusal("team", None)
+                .unwrap()
+                .contains("3.0 MB of 2.0 MB")
+        );
+
+        let roomy = ContainerConfig {
+            disk_hard_mb: Some(10),
+            disk_soft_mb: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(quotas.status("team", Some(&roomy)), QuotaStatus::Within);
+        assert_eq!(
+            quotas.summary("team", Some(&roomy)).as_deref(),
+            Some("3.0 MB of 10.0 MB")
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//! Container engine CLI: which binary runs agents, and the few flags that
+//! differ between docker, podman and nerdctl.
+//!
+//! The engine is chosen once at startup by [`detect`] and used by every
+//! container command afterwards. Before detection (and in tests) it is
+//! plain `docker`. On macOS, when plain `docker` has no engine behind it,
+//! the sockets of Colima, Lima and Docker Desktop VMs are tried as well.
+
+use std::sync::OnceLock;
+
+use intercom_core::{ContainerEngine, ContainerRuntimeConfig};
+use tokio::process::Command;
+use tracing::{debug, info};
+
+use super::host::{self, HostMounts, VmSocket};
+
+/// Prefix of every agent container name (see `mounts::container_name`).
+pub const CONTAINER_NAME_PREFIX: &str = "intercom-";
+
+/// Labels on every agent container. Orphan cleanup selects on
+/// [`LABEL_GROUP`]; external monitoring can use all four.
+pub const LABEL_GROUP: &str = "intercom.group";
+pub const LABEL_RUN_ID: &str = "intercom.run_id";
+pub const LABEL_RUNTIME: &str = "intercom.runtime";
+pub const LABEL_TRIGGER: &str = "intercom.trigger";
+
+/// Engines tried, in order, when `container.engine = "auto"`.
+const AUTO_ORDER: [ContainerEngine; 3] = [
+    ContainerEngine::Docker,
+    ContainerEngine::Podman,
+    ContainerEngine::Nerdctl,
+];
+
+static ACTIVE: OnceLock<ContainerCli> = OnceLock::new();
+
+/// A resolved engine and the binary that speaks its dialect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerCli {
+    /// Never [`ContainerEngine::Auto`].
+    pub engine: ContainerEngine,
+    pub binary: String,
+    /// macOS VM socket the CLI is pointed at through `DOCKER_HOST`.
+    pub socket: Option<VmSocket>,
+}
+
+impl ContainerCli {
+    pub fn new(engine: ContainerEngine, binary: Option<&str>) -> Self {
+        let engine = match engine {
+            ContainerEngine::Auto => ContainerEngine::Docker,
+            engine => engine,
+        };
+        let binary = binary
+            .map(str::trim)
+            .filter(|b| !b.is_empty())
+            .unwrap_or(engine.as_str())
+            .to_string();
+        Self {
+            engine,
+            binary,
+            socket: None,
+        }
+    }
+
+    /// Reach the engine through a VM's docker socket.
+    fn with_socket(mut self, socket: VmSocket) -> Self {
+        self.socket = Some(socket);
+        self
+    }
+
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.binary);
+        if let Some(socket) = &self.socket {
+            command.env("DOCKER_HOST", socket.docker_host());
+        }
+        command
+    }
+
+    /// How mount paths reach this engine from the host.
+    pub fn host_mounts(&self) -> HostMounts {
+        HostMounts::current(self.socket.as_ref())
+    }
+
+    /// `run` flags that keep bind-mounted files owned by the host user.
+    ///
+    /// Docker and nerdctl run as the host uid unless it is root or already
+    /// the image's `node` user (1000). Rootless podman maps container uids
+    /// into a subuid range, so it keeps the host uid with `--userns=keep-id`
+    /// instead.
+    pub fn user_args(&self, uid: u32, gid: u32) -> Vec<String> {
+        let home = ["-e".to_string(), "HOME=/home/node".to_string()];
+        match self.engine {
+            _ if uid == 0 => Vec::new(),
+            ContainerEngine::Podman => {
+                let mut args = vec!["--userns=keep-id".to_string()];
+                args.extend(home);
+                args
+            }
+            _ if uid == 1000 => Vec::new(),
+            _ => {
+                let mut args = vec!["--user".to_string(), format!("{uid}:{gid}")];
+                args.extend(home);
+                args
+            }
+        }
+    }
+
+    /// `run` flags tagging the container with `labels`. Podman also
+    /// writes them as OCI annotations, which reach the runtime spec.
+    pub fn label_args(&self, labels: &[(&str, &str)]) -> Vec<String> {
+        let mut args = Vec::new();
+        for (key, value) in labels {
+            args.extend(["--label".to_string(), format!("{key}={value}")]);
+        }
+        if self.engine == ContainerEngine::Podman {
+            for (key, value) in labels {
+                args.extend(["--annotation".to_string(), format!("{key}={value}")]);
+            }
+        }
+        args
+    }
+
+    /// `ps` arguments listing running agent containers: those carrying
+    /// the [`LABEL_GROUP`] label, whatever their name.
+    pub fn list_agents_args(&self) -> Vec<String> {
+        vec![
+            "ps".to_string(),
+            "--filter".to_string(),
+            format!("label={LABEL_GROUP}"),
+            "--format".to_string(),
+            "{{.Names}}".to_string(),
+        ]
+    }
+}
+
+/// The engine in use: the detected one, else `docker`.
+pub fn active() -> &'static ContainerCli {
+    ACTIVE.get_or_init(|| ContainerCli::new(ContainerEngine::Docker, None))
+}
+
+/// Run `<binary> info` to check the engine is installed and running.
+pub async fn probe(cli: &ContainerCli) -> anyhow::Result<()> {
+    let output = cli
+        .command()
+        .arg("info")
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("Container runtime `{}` not found: {}", cli.binary, e))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Container runtime `{}` is not running. Ensure {} is installed and started.",
+            cli.binary,
+            cli.engine.as_str()
+        );
+    }
+    Ok(())
+}
+
+/// Candidate CLIs for the configured engine, in probe order. Each docker
+/// candidate is followed by the same CLI on every VM socket found.
+fn candidates(config: &ContainerRuntimeConfig, sockets: &[VmSocket]) -> Vec<ContainerCli> {
+    let binary = config.binary.as_deref();
+    let clis = match config.engine {
+        // An explicit binary under `auto` still needs a dialect; take it
+        // from the file name (`/usr/bin/podman` speaks podman).
+        ContainerEngine::Auto => match binary {
+            Some(path) => {
+                let name = std::path::Path::new(path.trim())
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or_default();
+                let engine = AUTO_ORDER
+                    .into_iter()
+                    .find(|e| name.starts_with(e.as_str()))
+                    .unwrap_or(ContainerEngine::Docker);
+                vec![ContainerCli::new(engine, binary)]
+            }
+            None => AUTO_ORDER
+                .into_iter()
+                .map(|engine| ContainerCli::new(engine, None))
+                .collect(),
+        },
+        engine => vec![ContainerCli::new(engine, binary)],
+    };
+    clis.into_iter()
+        .flat_map(|cli| {
+            let on_sockets: Vec<_> = match cli.engine {
+                ContainerEngine::Docker => sockets
+                    .iter()
+                    .map(|s| cli.clone().with_socket(s.clone()))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            std::iter::once(cli).chain(on_sockets)
+        })
+        .collect()
+}
+
+/// Pick the container engine at startup: the configured one, or with
+/// `auto` the first available. The choice holds for the process lifetime.
+pub async fn detect(config: &ContainerRuntimeConfig) -> anyhow::Result<&'static ContainerCli> {
+    let mut errors = Vec::new();
+    for cli in candidates(config, &host::find_vm_sockets()) {
+        match probe(&cli).await {
+            Ok(()) => {
+                if ACTIVE.set(cli).is_err() {
+                    debug!("container engine already selected");
+                }
+                let active = active();
+                info!(
+                    engine = active.engine.as_str(),
+                    binary = %active.binary,
+                    vm = active.socket.as_ref().map(|s| s.vm.as_str()),
+                    "Container runtime selected"
+                );
+                return Ok(active);
+            }
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+    anyhow::bail!("no container runtime available: {}", errors.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(engine: ContainerEngine, binary: Option<&str>) -> ContainerRuntimeConfig {
+        ContainerRuntimeConfig {
+            engine,
+            binary: binary.map(str::to_string),
+            ..ContainerRuntimeConfig::default()
+        }
+    }
+
+    #[test]
+    fn auto_probes_every_engine_in_order() {
+        let engines: Vec<_> = candidates(&config(ContainerEngine::Auto, None), &[])
+            .into_iter()
+            .map(|cli| cli.binary)
+            .collect();
+        assert_eq!(engines, ["docker", "podman", "nerdctl"]);
+    }
+
+    #[test]
+    fn binary_override_keeps_or_infers_dialect() {
+        let explicit = candidates(&config(ContainerEngine::Podman, Some("/opt/bin/pd")), &[]);
+        assert_eq!(
+            explicit,
+            [ContainerCli {
+                engine: ContainerEngine::Podman,
+                binary: "/opt/bin/pd".into(),
+                socket: None
+            }]
+        );
+
+        let inferred = candidates(
+            &config(ContainerEngine::Auto, Some("/usr/local/bin/nerdctl")),
+            &[],
+        );
+        assert_eq!(inferred[0].engine, ContainerEngine::Nerdctl);
+    }
+
+    #[test]
+    fn docker_is_retried_on_each_vm_socket() {
+        let sockets =
+            host::vm_socket_candidates(std::path::Path::new("/Users/sam"), None, Some("docker"));
+        let clis = candidates(&config(ContainerEngine::Auto, None), &sockets[..2]);
+        let order: Vec<_> = clis
+            .iter()
+            .map(|cli| {
+                (
+                    cli.binary.as_str(),
+                    cli.socket.as_ref().map(|s| s.vm.as_str()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("docker", None),
+                ("docker", Some("colima")),
+                ("docker", Some("lima")),
+                ("podman", None),
+                ("nerdctl", None),
+            ]
+        );
+        let command = clis[1].command();
+        let (key, value) = command.as_std().get_envs().next().unwrap();
+        assert_eq!(key, "DOCKER_HOST");
+        assert_eq!(
+            value.unwrap(),
+            "unix:///Users/sam/.colima/default/docker.sock"
+        );
+    }
+
+    #[test]
+    fn podman_keeps_host_uid_with_userns() {
+        let podman = ContainerCli::new(ContainerEngine::Podman, None);
+        assert_eq!(podman.user_args(1000, 1000)[0], "--userns=keep-id");
+        assert!(podman.user_args(0, 0).is_empty());
+
+        let docker = ContainerCli::new(ContainerEngine::Docker, None);
+        assert_eq!(docker.user_args(1001, 100)[..2], ["--user", "1001:100"]);
+        assert!(docker.user_args(1000, 1000).is_empty());
+    }
+
+    #[test]
+    fn agents_are_listed_and_tagged_by_label() {
+        for engine in AUTO_ORDER {
+            let args = ContainerCli::new(engine, None).list_agents_args();
+            assert_eq!(args[1..3], ["--filter", "label=intercom.group"]);
+        }
+
+        let labels = [(LABEL_GROUP, "team"), (LABEL_TRIGGER, "task")];
+        let docker = ContainerCli::new(ContainerEngine::Docker, None).label_args(&labels);
+        assert_eq!(
+            docker,
+            [
+                "--label",
+                "intercom.group=team",
+                "--label",
+                "intercom.trigger=task"
+            ]
+        );
+        let podman = ContainerCli::new(ContainerEngine::Podman, None).label_args(&labels);
+        assert_eq!(podman.len(), 8);
+        assert_eq!(podman[4..6], ["--annotation", "intercom.group=team"]);
+    }
+}
@@ -0,0 +1,438 @@
+//! Container executors: where and how an agent container actually runs.
+//!
+//! The runner describes a run as a [`ContainerSpec`] and talks to the
+//! resulting [`AgentProcess`] through its stdio streams; everything
+//! backend-specific sits behind [`ContainerExecutor`]. [`CliExecutor`]
+//! drives the local docker/podman/nerdctl CLI (with the optional warm
+//! pool) and `kubernetes::KubernetesExecutor` drives `kubectl`.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use intercom_core::{EgressProxyConfig, VolumeMount};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::process::{Child, Command};
+use tracing::{debug, info, warn};
+
+use super::engine::{self, LABEL_GROUP, LABEL_RUN_ID, LABEL_RUNTIME, LABEL_TRIGGER};
+use super::images;
+use super::limits::ResourceLimits;
+use super::network::{NetworkMode, network_args};
+use super::secrets::build_container_args;
+use super::warm_pool::WarmPool;
+
+pub type ExecutorFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+pub type AgentStdin = Box<dyn AsyncWrite + Send + Unpin>;
+pub type AgentOutput = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Everything needed to start one agent container.
+pub struct ContainerSpec<'a> {
+    /// Also the run id.
+    pub name: &'a str,
+    pub group_folder: &'a str,
+    pub runtime: &'a str,
+    /// What started the run: `message`, `task` or `replay`.
+    pub trigger: &'a str,
+    pub image: &'a str,
+    /// The runtime profile's pinned digest, if any.
+    pub image_digest: Option<&'a str>,
+    /// Refuse to start when the image does not match `image_digest`.
+    pub verify_image: bool,
+    pub timezone: &'a str,
+    pub mounts: &'a [VolumeMount],
+    pub limits: &'a ResourceLimits,
+    pub network: NetworkMode,
+    pub egress: &'a EgressProxyConfig,
+}
+
+impl ContainerSpec<'_> {
+    /// The labels the container is tagged with.
+    pub fn labels(&self) -> [(&'static str, &str); 4] {
+        [
+            (LABEL_GROUP, self.group_folder),
+            (LABEL_RUN_ID, self.name),
+            (LABEL_RUNTIME, self.runtime),
+            (LABEL_TRIGGER, self.trigger),
+        ]
+    }
+}
+
+/// A started agent: its stdio and a future for its exit code.
+pub struct AgentProcess {
+    /// The name to stop it by, which the executor may have changed (a warm
+    /// container or a pod brings its own).
+    pub name: String,
+    pub stdin: Option<AgentStdin>,
+    pub stdout: AgentOutput,
+    pub stderr: AgentOutput,
+    /// Resolves once the process ends; `None` if it was killed by a signal.
+    pub exit: ExecutorFuture<'static, std::io::Result<Option<i32>>>,
+}
+
+impl AgentProcess {
+    /// Wrap a spawned CLI process with piped stdio.
+    pub fn from_child(name: String, mut child: Child) -> anyhow::Result<Self> {
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("container stdout not piped"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("container stderr not piped"))?;
+        Ok(Self {
+            name,
+            stdin: child
+                .stdin
+                .take()
+                .map(|stdin| Box::new(stdin) as AgentStdin),
+            stdout: Box::new(stdout),
+            stderr: Box::new(stderr),
+            exit: Box::pin(async move { child.wait().await.map(|status| status.code()) }),
+        })
+    }
+}
+
+/// How a stop request ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopOutcome {
+    /// Exited within the grace period after SIGTERM.
+    Stopped,
+    /// Ignored SIGTERM for the grace period and was killed.
+    Killed,
+    /// Neither worked; the container may still be running.
+    Failed,
+}
+
+impl StopOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stopped => "stopped",
+            Self::Killed => "killed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+pub trait ContainerExecutor: Send + Sync {
+    /// Short backend name for logs.
+    fn kind(&self) -> &'static str;
+
+    /// Start a container. It waits on stdin for its input.
+    fn spawn<'a>(
+        &'a self,
+        spec: &'a ContainerSpec<'a>,
+    ) -> ExecutorFuture<'a, anyhow::Result<AgentProcess>>;
+
+    /// Stop a running container by name: SIGTERM, then kill once `grace`
+    /// has passed.
+    fn stop<'a>(&'a self, name: &'a str, grace: Duration) -> ExecutorFuture<'a, StopOutcome>;
+
+    /// Stop agent containers left over from a previous run of intercomd.
+    #[allow(dead_code)] // orphan cleanup at startup is still done by the Node host
+    fn cleanup(&self) -> ExecutorFuture<'_, ()>;
+
+    /// Check the backend is reachable.
+    fn probe(&self) -> ExecutorFuture<'_, anyhow::Result<()>>;
+}
+
+/// Spawn `<engine> <args>` with piped stdio.
+pub fn spawn_cli(args: &[String]) -> anyhow::Result<Child> {
+    engine::active()
+        .command()
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to spawn container: {}", e))
+}
+
+/// Run a stop-style command and log the outcome.
+pub async fn run_stop(mut command: Command, container_name: &str) -> bool {
+    match command.output().await {
+        Ok(output) if output.status.success() => {
+            info!(container_name, "Container stopped");
+            true
+        }
+        Ok(output) => {
+            warn!(
+                container_name,
+                stderr = String::from_utf8_lossy(&output.stderr).as_ref(),
+                "Failed to stop container"
+            );
+            false
+        }
+        Err(e) => {
+            warn!(container_name, error = %e, "Failed to execute container stop");
+            false
+        }
+    }
+}
+
+/// Give `graceful` up to `grace` to see the container gone; when it fails
+/// or runs out of time, run `kill`.
+pub async fn stop_or_kill(
+    graceful: impl Future<Output = bool>,
+    kill: Command,
+    container_name: &str,
+    grace: Duration,
+) -> StopOutcome {
+    match tokio::time::timeout(grace, graceful).await {
+        Ok(true) => return StopOutcome::Stopped,
+        Ok(false) => warn!(container_name, "Graceful stop failed, killing container"),
+        Err(_) => warn!(
+            container_name,
+            grace_secs = grace.as_secs(),
+            "Container ignored SIGTERM, killing"
+        ),
+    }
+    match run_stop(kill, container_name).await {
+        true => StopOutcome::Killed,
+        false => StopOutcome::Failed,
+    }
+}
+
+/// SIGTERM through the local engine, wait up to `grace` for the container
+/// to exit, then `kill` it.
+pub async fn stop_cli(container_name: &str, grace: Duration) -> StopOutcome {
+    let cli = engine::active();
+    let mut signal = cli.command();
+    signal.args(["kill", "--signal", "SIGTERM", container_name]);
+    if !run_stop(signal, container_name).await {
+        return StopOutcome::Failed;
+    }
+    let mut wait = cli.command();
+    wait.args(["wait", container_name]).kill_on_drop(true);
+    // `wait` also fails once an `--rm` container is removed, which is gone too
+    let exited = async move { wait.output().await.is_ok() };
+    let mut kill = cli.command();
+    kill.args(["kill", container_name]);
+    stop_or_kill(exited, kill, container_name, grace).await
+}
+
+/// The local docker/podman/nerdctl CLI. The engine is read at call time,
+/// so the executor can be built before [`engine::detect`] runs.
+#[derive(Default)]
+pub struct CliExecutor {
+    warm_pool: Option<Arc<WarmPool>>,
+}
+
+impl CliExecutor {
+    pub fn new(warm_pool: Option<Arc<WarmPool>>) -> Self {
+        Self { warm_pool }
+    }
+}
+
+impl ContainerExecutor for CliExecutor {
+    fn kind(&self) -> &'static str {
+        engine::active().engine.as_str()
+    }
+
+    fn spawn<'a>(
+        &'a self,
+        spec: &'a ContainerSpec<'a>,
+    ) -> ExecutorFuture<'a, anyhow::Result<AgentProcess>> {
+        Box::pin(async move {
+            if let (true, Some(pin)) = (spec.verify_image, spec.image_digest) {
+                images::verify(spec.image, pin).await?;
+            }
+            let network = network_args(spec.network, spec.egress)?;
+            let cli = engine::active();
+            let host = cli.host_mounts();
+            let mounts: Vec<_> = spec
+                .mounts
+                .iter()
+                .map(|m| VolumeMount {
+                    host_path: host.host_path(&m.host_path),
+                    ..m.clone()
+                })
+                .collect();
+            let args = build_container_args(
+                cli,
+                spec.limits,
+                &network,
+                &mounts,
+                &spec.labels(),
+                spec.name,
+                spec.image,
+                spec.timezone,
+            );
+
+            // Claim a warm container started with the same args, else spawn one
+            let warm = match &self.warm_pool {
+                Some(pool) => pool.claim(&args).await,
+                None => None,
+            };
+            let (name, child) = match warm {
+                Some(warm) => {
+                    debug!(container_name = %warm.name, "Claimed warm container");
+                    (warm.name, warm.child)
+                }
+                None => (spec.name.to_string(), spawn_cli(&args)?),
+            };
+            let mut process = AgentProcess::from_child(name, child)?;
+
+            // Start the next run's container once this one exits
+            if let Some(pool) = self.warm_pool.clone() {
+                let exit = process.exit;
+                process.exit = Box::pin(async move {
+                    let code = exit.await;
+                    pool.refill(&args);
+                    code
+                });
+            }
+            Ok(process)
+        })
+    }
+
+    fn stop<'a>(&'a self, name: &'a str, grace: Duration) -> ExecutorFuture<'a, StopOutcome> {
+        Box::pin(stop_cli(name, grace))
+    }
+
+    fn cleanup(&self) -> ExecutorFuture<'_, ()> {
+        Box::pin(async move {
+            let cli = engine::active();
+            let output = match cli.command().args(cli.list_agents_args()).output().await {
+                Ok(o) => o,
+                Err(e) => {
+                    warn!(error = %e, "Failed to list orphaned containers");
+                    return;
+                }
+            };
+            let names: Vec<&str> = std::str::from_utf8(&output.stdout)
+                .unwrap_or("")
+                .lines()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .collect();
+            for name in &names {
+                let _ = cli.command().args(["stop", name]).output().await;
+            }
+            if !names.is_empty() {
+                info!(count = names.len(), "Stopped orphaned containers");
+            }
+        })
+    }
+
+    fn probe(&self) -> ExecutorFuture<'_, anyhow::Result<()>> {
+        Box::pin(engine::probe(engine::active()))
+    }
+}
+
+/// Scripted executor for runner tests: every spawn prints `stdout` (and
+/// `stderr`), exits with `exit_code`, and records the stdin it was given. A `silent` one
+/// prints nothing and keeps stdout open until it is stopped.
+#[cfg(test)]
+pub struct FakeExecutor {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+    pub silent: bool,
+    pub spawned: std::sync::Mutex<Vec<String>>,
+    pub stopped: std::sync::Mutex<Vec<String>>,
+    stdin: std::sync::Mutex<Vec<tokio::io::DuplexStream>>,
+    open_stdout: std::sync::Mutex<Vec<tokio::io::DuplexStream>>,
+}
+
+#[cfg(test)]
+impl FakeExecutor {
+    pub fn new(stdout: &str, exit_code: i32) -> Self {
+        Self {
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code,
+            silent: false,
+            spawned: Default::default(),
+            stopped: Default::default(),
+            stdin: Default::default(),
+            open_stdout: Default::default(),
+        }
+    }
+
+    pub fn silent(exit_code: i32) -> Self {
+        Self {
+            silent: true,
+            ..Self::new("", exit_code)
+        }
+    }
+
+    /// Everything written to the last spawned container's stdin.
+    pub async fn last_stdin(&self) -> String {
+        use tokio::io::AsyncReadExt;
+        let mut reader = self.stdin.lock().unwrap().pop().expect("nothing spawned");
+        let mut input = String::new();
+        reader.read_to_string(&mut input).await.unwrap();
+        input
+    }
+}
+
+#[cfg(test)]
+impl ContainerExecutor for FakeExecutor {
+    fn kind(&self) -> &'static str {
+        "fake"
+    }
+
+    fn spawn<'a>(
+        &'a self,
+        spec: &'a ContainerSpec<'a>,
+    ) -> ExecutorFuture<'a, anyhow::Result<AgentProcess>> {
+        Box::pin(async move {
+            let (stdin, reader) = tokio::io::duplex(1 << 20);
+            self.stdin.lock().unwrap().push(reader);
+            self.spawned.lock().unwrap().push(spec.name.to_string());
+            let code = self.exit_code;
+            let stdout: AgentOutput = if self.silent {
+                let (writer, reader) = tokio::io::duplex(64);
+                self.open_stdout.lock().unwrap().push(writer);
+                Box::new(reader)
+            } else {
+                Box::new(std::io::Cursor::new(self.stdout.clone().into_bytes()))
+            };
+            Ok(AgentProcess {
+                name: spec.name.to_string(),
+                stdin: Some(Box::new(stdin)),
+                stdout,
+                stderr: Box::new(std::io::Cursor::new(self.stderr.clone().into_bytes())),
+                exit: Box::pin(async move { Ok(Some(code)) }),
+            })
+        })
+    }
+
+    fn stop<'a>(&'a self, name: &'a str, _grace: Duration) -> ExecutorFuture<'a, StopOutcome> {
+        self.stopped.lock().unwrap().push(name.to_string());
+        self.open_stdout.lock().unwrap().clear();
+        Box::pin(async { StopOutcome::Stopped })
+    }
+
+    fn cleanup(&self) -> ExecutorFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    fn probe(&self) -> ExecutorFuture<'_, anyhow::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stop_escalates_to_kill() {
+        let grace = Duration::from_millis(50);
+        let exited = stop_or_kill(async { true }, Command::new("false"), "c1", grace).await;
+        assert_eq!(exited, StopOutcome::Stopped);
+
+        let hung = stop_or_kill(std::future::pending(), Command::new("true"), "c1", grace).await;
+        assert_eq!(hung, StopOutcome::Killed);
+
+        let stuck = stop_or_kill(async { false }, Command::new("false"), "c1", grace).await;
+        assert_eq!(stuck, StopOutcome::Failed);
+    }
+}
@@ -0,0 +1,337 @@
+//! Host platform differences for the local container engines.
+//!
+//! On Linux the engine shares the host filesystem, so mount paths pass
+//! through unchanged. On macOS the engine runs in a Linux VM (Docker
+//! Desktop, Colima or Lima) that sees only the host directories it shares:
+//! Docker Desktop shares `/Users`, `/Volumes`, `/private` and `/tmp`, while
+//! Colima and Lima share the home directory (and their own `/tmp` folder).
+//! `/tmp`, `/var` and `/etc` are symlinks into `/private` there, so they
+//! are resolved before the path reaches the VM. Homes live under `/Users`,
+//! and there is no `/wm`.
+
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostOs {
+    Linux,
+    MacOs,
+}
+
+impl HostOs {
+    pub fn current() -> Self {
+        if cfg!(target_os = "macos") {
+            Self::MacOs
+        } else {
+            Self::Linux
+        }
+    }
+}
+
+/// The VM a macOS engine runs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostVm {
+    DockerDesktop,
+    Colima,
+    Lima,
+}
+
+impl HostVm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::DockerDesktop => "docker-desktop",
+            Self::Colima => "colima",
+            Self::Lima => "lima",
+        }
+    }
+
+    /// Which VM serves a docker socket, from where the socket lives.
+    pub fn from_socket(path: &str) -> Option<Self> {
+        let path = path.strip_prefix("unix://").unwrap_or(path);
+        if path.contains("/.colima/") {
+            Some(Self::Colima)
+        } else if path.contains("/.lima/") {
+            Some(Self::Lima)
+        } else if path.contains("/.docker/run/")
+            || path.contains("/Library/Containers/com.docker.docker/")
+        {
+            Some(Self::DockerDesktop)
+        } else {
+            None
+        }
+    }
+
+    /// Host directories the VM shares with its containers by default.
+    pub fn shared_roots(self, home: &Path) -> Vec<PathBuf> {
+        match self {
+            Self::DockerDesktop => ["/Users", "/Volumes", "/private", "/tmp"]
+                .into_iter()
+                .map(PathBuf::from)
+                .collect(),
+            Self::Colima => vec![home.to_path_buf(), PathBuf::from("/private/tmp/colima")],
+            Self::Lima => vec![home.to_path_buf(), PathBuf::from("/private/tmp/lima")],
+        }
+    }
+}
+
+/// A docker socket published by a macOS VM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmSocket {
+    pub vm: HostVm,
+    pub path: PathBuf,
+}
+
+impl VmSocket {
+    /// The `DOCKER_HOST` value that reaches this socket.
+    pub fn docker_host(&self) -> String {
+        format!("unix://{}", self.path.display())
+    }
+}
+
+/// The home directory: `$HOME`, else the platform default for `$USER`.
+pub fn home_dir() -> PathBuf {
+    match std::env::var("HOME") {
+        Ok(home) if !home.is_empty() => PathBuf::from(home),
+        _ => match (HostOs::current(), std::env::var("USER")) {
+            (HostOs::MacOs, Ok(user)) => PathBuf::from("/Users").join(user),
+            _ => PathBuf::from("/root"),
+        },
+    }
+}
+
+/// Expand a leading `~` against `home`.
+pub fn expand_home(path: &str, home: &Path) -> PathBuf {
+    if path == "~" {
+        home.to_path_buf()
+    } else if let Some(rest) = path.strip_prefix("~/") {
+        home.join(rest)
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+/// Docker sockets the macOS VMs would publish, in probe order: Colima
+/// (`COLIMA_PROFILE`, else `default`), Lima (`LIMA_INSTANCE`, else
+/// `docker` and `default`), then Docker Desktop.
+pub fn vm_socket_candidates(
+    home: &Path,
+    colima_profile: Option<&str>,
+    lima_instance: Option<&str>,
+) -> Vec<VmSocket> {
+    let mut sockets = vec![VmSocket {
+        vm: HostVm::Colima,
+        path: home
+            .join(".colima")
+            .join(colima_profile.unwrap_or("default"))
+            .join("docker.sock"),
+    }];
+    let lima = match lima_instance {
+        Some(instance) => vec![instance],
+        None => vec!["docker", "default"],
+    };
+    sockets.extend(lima.into_iter().map(|instance| {
+        VmSocket {
+            vm: HostVm::Lima,
+            path: home
+                .join(".lima")
+                .join(instance)
+                .join("sock")
+                .join("docker.sock"),
+        }
+    }));
+    sockets.push(VmSocket {
+        vm: HostVm::DockerDesktop,
+        path: home.join(".docker").join("run").join("docker.sock"),
+    });
+    sockets
+}
+
+/// The VM sockets present on this host. Empty off macOS, and when
+/// `DOCKER_HOST` already names the engine.
+pub fn find_vm_sockets() -> Vec<VmSocket> {
+    if HostOs::current() != HostOs::MacOs || std::env::var_os("DOCKER_HOST").is_some() {
+        return Vec::new();
+    }
+    let colima = std::env::var("COLIMA_PROFILE").ok();
+    let lima = std::env::var("LIMA_INSTANCE").ok();
+    vm_socket_candidates(&home_dir(), colima.as_deref(), lima.as_deref())
+        .into_iter()
+        .filter(|socket| socket.path.exists())
+        .collect()
+}
+
+/// How host paths reach the engine on this host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostMounts {
+    pub os: HostOs,
+    pub home: PathBuf,
+    /// The VM behind the engine, when it is known.
+    pub vm: Option<HostVm>,
+}
+
+impl HostMounts {
+    /// This host, with the VM from `socket` or else from `DOCKER_HOST`.
+    pub fn current(socket: Option<&VmSocket>) -> Self {
+        let vm = socket.map(|s| s.vm).or_else(|| {
+            std::env::var("DOCKER_HOST")
+                .ok()
+                .and_then(|host| HostVm::from_socket(&host))
+        });
+        Self {
+            os: HostOs::current(),
+            home: home_dir(),
+            vm,
+        }
+    }
+
+    /// The path the engine should bind for `host_path`. On macOS the
+    /// `/private` symlinks are resolved; a path the VM does not share is
+    /// passed on with a warning, since the VM's own mounts may still
+    /// cover it.
+    pub fn host_path(&self, host_path: &str) -> String {
+        if self.os != HostOs::MacOs {
+            return host_path.to_string();
+        }
+        let path = expand_home(host_path, &self.home);
+        let path = ["/tmp", "/var", "/etc"]
+            .into_iter()
+            .find_map(|link| {
+                let rest = path.strip_prefix(link).ok()?;
+                Some(
+                    Path::new("/private")
+                        .join(link.trim_start_matches('/'))
+                        .join(rest),
+                )
+            })
+            .unwrap_or(path);
+        if let Some(vm) = self.vm {
+            if !vm
+                .shared_roots(&self.home)
+                .iter()
+                .any(|root| path.starts_with(root))
+            {
+                warn!(
+                    path = %path.display(),
+                    vm = vm.as_str(),
+                    "Mount is outside the VM's shared directories and may be empty in the container"
+                );
+            }
+        }
+        path.to_string_lossy().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn macos(vm: Option<HostVm>) -> HostMounts {
+        HostMounts {
+            os: HostOs::MacOs,
+            home: PathBuf::from("/Users/sam"),
+            vm,
+        }
+    }
+
+    #[test]
+    fn tilde_expands_to_the_platform_home() {
+        assert_eq!(
+            expand_home("~/code", Path::new("/Users/sam")),
+            Path::new("/Users/sam/code")
+        );
+        assert_eq!(
+            expand_home("~/code", Path::new("/home/sam")),
+            Path::new("/home/sam/code")
+        );
+        assert_eq!(
+            expand_home("~", Path::new("/Users/sam")),
+            Path::new("/Users/sam")
+        );
+        assert_eq!(
+            expand_home("/srv/~x", Path::new("/Users/sam")),
+            Path::new("/srv/~x")
+        );
+    }
+
+    #[test]
+    fn macos_paths_resolve_private_symlinks() {
+        let mounts = macos(Some(HostVm::DockerDesktop));
+        assert_eq!(
+            mounts.host_path("/tmp/intercom/ipc"),
+            "/private/tmp/intercom/ipc"
+        );
+        assert_eq!(
+            mounts.host_path("/var/folders/xy/T/groups/main"),
+            "/private/var/folders/xy/T/groups/main"
+        );
+        assert_eq!(
+            mounts.host_path("/Users/sam/intercom/groups/main"),
+            "/Users/sam/intercom/groups/main"
+        );
+        assert_eq!(mounts.host_path("~/intercom"), "/Users/sam/intercom");
+        // Only whole components are symlinks.
+        assert_eq!(mounts.host_path("/tmpfs/x"), "/tmpfs/x");
+    }
+
+    #[test]
+    fn linux_paths_pass_through() {
+        let linux = HostMounts {
+            os: HostOs::Linux,
+            home: PathBuf::from("/home/sam"),
+            vm: None,
+        };
+        assert_eq!(linux.host_path("/tmp/intercom/ipc"), "/tmp/intercom/ipc");
+        assert_eq!(linux.host_path("/var/lib/intercom"), "/var/lib/intercom");
+    }
+
+    #[test]
+    fn vms_share_different_roots() {
+        let home = Path::new("/Users/sam");
+        let shared = |vm: HostVm, path: &str| {
+            vm.shared_roots(home)
+                .iter()
+                .any(|r| Path::new(path).starts_with(r))
+        };
+        assert!(shared(HostVm::DockerDesktop, "/Volumes/data/project"));
+        assert!(!shared(HostVm::Colima, "/Volumes/data/project"));
+        assert!(shared(HostVm::Colima, "/Users/sam/project"));
+        assert!(!shared(HostVm::Lima, "/Users/other/project"));
+        assert!(shared(HostVm::Lima, "/private/tmp/lima/x"));
+    }
+
+    #[test]
+    fn sockets_are_probed_colima_lima_then_desktop() {
+        let home = Path::new("/Users/sam");
+        let sockets = vm_socket_candidates(home, None, None);
+        let paths: Vec<_> = sockets
+            .iter()
+            .map(|s| s.path.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "/Users/sam/.colima/default/docker.sock",
+                "/Users/sam/.lima/docker/sock/docker.sock",
+                "/Users/sam/.lima/default/sock/docker.sock",
+                "/Users/sam/.docker/run/docker.sock",
+            ]
+        );
+        let pinned = vm_socket_candidates(home, Some("work"), Some("dev"));
+        assert_eq!(
+            pinned[0].path,
+            Path::new("/Users/sam/.colima/work/docker.sock")
+        );
+        assert_eq!(
+            pinned[1].docker_host(),
+            "unix:///Users/sam/.lima/dev/sock/docker.sock"
+        );
+        assert_eq!(pinned.len(), 3);
+
+        assert_eq!(
+            HostVm::from_socket(&pinned[0].docker_host()),
+            Some(HostVm::Colima)
+        );
+        assert_eq!(HostVm::from_socket("unix:///var/run/docker.sock"), None);
+    }
+}
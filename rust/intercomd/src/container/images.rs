@@ -0,0 +1,237 @@
+//! Runtime image management: pull, pin and verify.
+//!
+//! Each runtime profile may name its own `image` and pin it with
+//! `image_digest`. A pin matches either the local image id (locally built
+//! images) or one of its registry digests (pulled images). With
+//! `[container.images] verify_digests = true` a run whose image does not
+//! match its pin is refused. Pinned images are never re-pulled by the update
+//! check; change the pin to upgrade.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use intercom_core::{ImageConfig, RuntimeKind, RuntimeProfile, container_image};
+use serde::Deserialize;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use super::engine;
+
+const RUNTIMES: [RuntimeKind; 3] = [RuntimeKind::Claude, RuntimeKind::Gemini, RuntimeKind::Codex];
+
+/// The image a runtime runs, and its pin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeImage {
+    pub image: String,
+    pub digest: Option<String>,
+}
+
+/// Identity of a local image, from `image inspect`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageDigests {
+    pub id: String,
+    #[serde(default)]
+    pub repo_digests: Option<Vec<String>>,
+}
+
+impl ImageDigests {
+    /// Whether `pin` is this image's id or one of its registry digests.
+    /// Podman reports ids without the `sha256:` prefix.
+    pub fn matches(&self, pin: &str) -> bool {
+        let bare = |d: &str| d.trim().trim_start_matches("sha256:").to_ascii_lowercase();
+        let pin = bare(pin);
+        !pin.is_empty()
+            && (bare(&self.id) == pin
+                || self
+                    .repo_digests
+                    .iter()
+                    .flatten()
+                    .filter_map(|d| d.rsplit_once('@'))
+                    .any(|(_, digest)| bare(digest) == pin))
+    }
+
+    /// The digest to report: the first registry digest, else the id.
+    pub fn current(&self) -> &str {
+        self.repo_digests
+            .iter()
+            .flatten()
+            .find_map(|d| d.rsplit_once('@').map(|(_, digest)| digest))
+            .unwrap_or(&self.id)
+    }
+}
+
+/// The profile's image and pin for `runtime`, defaulting to the built-in
+/// image.
+pub fn runtime_image(
+    profiles: &BTreeMap<String, RuntimeProfile>,
+    runtime: RuntimeKind,
+) -> RuntimeImage {
+    let profile = profiles.get(runtime.as_str());
+    let image = profile
+        .map(|p| p.image.trim())
+        .filter(|image| !image.is_empty())
+        .unwrap_or(container_image(runtime))
+        .to_string();
+    let digest = profile
+        .map(|p| p.image_digest.trim().to_string())
+        .filter(|digest| !digest.is_empty());
+    RuntimeImage { image, digest }
+}
+
+/// `image inspect` the local image; `None` when it is not present.
+pub async fn inspect(image: &str) -> anyhow::Result<Option<ImageDigests>> {
+    let output = engine::active()
+        .command()
+        .args(["image", "inspect", image])
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to inspect image {image}: {e}"))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let found: Vec<ImageDigests> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Unreadable inspect output for {image}: {e}"))?;
+    Ok(found.into_iter().next())
+}
+
+pub async fn pull(image: &str) -> anyhow::Result<()> {
+    let output = engine::active()
+        .command()
+        .args(["pull", "--quiet", image])
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to pull {image}: {e}"))?;
+    if !output.status.success() {
+        bail!(
+            "pull {image} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Refuse an image that is missing or does not match its pin.
+pub async fn verify(image: &str, pin: &str) -> anyhow::Result<()> {
+    match inspect(image).await? {
+        None => bail!("image {image} is not present locally"),
+        Some(found) if found.matches(pin) => Ok(()),
+        Some(found) => bail!(
+            "image {image} has digest {}, but its runtime profile pins {pin}",
+            found.current()
+        ),
+    }
+}
+
+/// Pull every runtime image. A pinned image already matching its pin is
+/// left alone; one that still mismatches after the pull is reported.
+pub async fn pull_all(profiles: &BTreeMap<String, RuntimeProfile>) {
+    for runtime in RUNTIMES {
+        let RuntimeImage { image, digest } = runtime_image(profiles, runtime);
+        if let Some(pin) = &digest {
+            if matches!(inspect(&image).await, Ok(Some(found)) if found.matches(pin)) {
+                continue;
+            }
+        }
+        match pull(&image).await {
+            Ok(()) => info!(image = %image, "Pulled runtime image"),
+            // Locally built images have no registry to pull from.
+            Err(e) => warn!(image = %image, err = %e, "Runtime image pull failed"),
+        }
+        if let Some(pin) = &digest {
+            if let Err(e) = verify(&image, pin).await {
+                warn!(image = %image, err = %e, "Runtime image does not match its pin");
+            }
+        }
+    }
+}
+
+/// Re-pull unpinned runtime images on an interval and log new digests.
+pub async fn run_update_check(
+    profiles: BTreeMap<String, RuntimeProfile>,
+    config: ImageConfig,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(
+        config.update_check_interval_secs.max(60),
+    ));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.changed() => break,
+        }
+        for runtime in RUNTIMES {
+            let RuntimeImage { image, digest } = runtime_image(&profiles, runtime);
+            if digest.is_some() {
+                continue;
+            }
+            let before = inspect(&image)
+                .await
+                .ok()
+                .flatten()
+                .map(|d| d.current().to_string());
+            if let Err(e) = pull(&image).await {
+                warn!(image = %image, err = %e, "Image update check failed");
+                continue;
+            }
+            let after = inspect(&image)
+                .await
+                .ok()
+                .flatten()
+                .map(|d| d.current().to_string());
+            if after != before {
+                info!(image = %image, digest = after.as_deref().unwrap_or(""), "Runtime image updated");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_match_ids_and_registry_digests() {
+        let local: Vec<ImageDigests> =
+            serde_json::from_str(r#"[{"Id": "sha256:aaa111", "RepoDigests": [], "Size": 1}]"#)
+                .unwrap();
+        assert!(local[0].matches("sha256:AAA111"));
+        assert!(!local[0].matches("sha256:bbb222"));
+
+        let podman = ImageDigests {
+            id: "aaa111".into(),
+            repo_digests: None,
+        };
+        assert!(podman.matches("sha256:aaa111"));
+
+        let pulled = ImageDigests {
+            id: "sha256:aaa111".into(),
+            repo_digests: Some(vec!["ghcr.io/x/intercom-agent@sha256:ccc333".into()]),
+        };
+        assert!(pulled.matches("sha256:ccc333"));
+        assert_eq!(pulled.current(), "sha256:ccc333");
+        assert!(!pulled.matches(""));
+    }
+
+    #[test]
+    fn profile_overrides_image_and_pin() {
+        let mut profiles = intercom_core::IntercomConfig::default().runtimes.profiles;
+        assert_eq!(
+            runtime_image(&profiles, RuntimeKind::Gemini),
+            RuntimeImage {
+                image: "intercom-agent-gemini:latest".into(),
+                digest: None
+            }
+        );
+
+        let codex = profiles.get_mut("codex").unwrap();
+        codex.image = "ghcr.io/x/codex:2".into();
+        codex.image_digest = " sha256:ddd444 ".into();
+        let resolved = runtime_image(&profiles, RuntimeKind::Codex);
+        assert_eq!(resolved.image, "ghcr.io/x/codex:2");
+        assert_eq!(resolved.digest.as_deref(), Some("sha256:ddd444"));
+    }
+}
@@ -0,0 +1,483 @@
+//! Kubernetes executor: agents run as pods instead of local containers.
+//!
+//! Selected with `[container] executor = "kubernetes"`. Each run is a
+//! `kubectl run -i --rm --restart=Never` pod, so the runner keeps its usual
+//! shape: input goes to the pod's stdin and output streams back from the
+//! API server on kubectl's stdout. `kubectl` must already be authenticated,
+//! either by kubeconfig or by the in-cluster service account.
+//!
+//! Host bind mounts become sub-paths of `container.kubernetes.volume_claim`,
+//! a PVC holding the project root that intercomd itself also mounts. Mounts
+//! outside the project root cannot be expressed and fail the run. Network
+//! modes are not enforced by the pod spec; pods carry an
+//! `intercom.network` label for a cluster NetworkPolicy to select on.
+//! A pinned runtime image runs as `image@digest`, so the kubelet enforces
+//! the pin itself.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use intercom_core::KubernetesConfig;
+use serde_json::{Value, json};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use super::engine::{LABEL_GROUP, LABEL_RUN_ID, LABEL_RUNTIME, LABEL_TRIGGER};
+use super::executor::{
+    AgentProcess, ContainerExecutor, ContainerSpec, ExecutorFuture, StopOutcome, run_stop,
+    stop_or_kill,
+};
+use super::network::{NetworkMode, proxy_env};
+
+/// Label selecting every agent pod intercomd started.
+pub const MANAGED_LABEL: &str = "app.kubernetes.io/managed-by=intercomd";
+
+/// Longest pod (and container) name Kubernetes accepts.
+const MAX_NAME_LEN: usize = 63;
+
+pub struct KubernetesExecutor {
+    config: KubernetesConfig,
+    project_root: PathBuf,
+}
+
+/// `intercom-{folder}-{millis}` as a valid pod name: lowercase DNS label,
+/// folder shortened to fit while the timestamp suffix is kept.
+pub fn pod_name(container_name: &str) -> String {
+    let name: String = container_name
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if name.len() <= MAX_NAME_LEN {
+        return name;
+    }
+    let (head, suffix) = name.rsplit_once('-').unwrap_or((&name, ""));
+    let keep = MAX_NAME_LEN.saturating_sub(suffix.len() + 1);
+    format!(
+        "{}-{suffix}",
+        head[..keep.min(head.len())].trim_end_matches('-')
+    )
+}
+
+/// The image reference to run, with the pin appended when there is one.
+fn image_ref(pod: &ContainerSpec<'_>) -> String {
+    match pod.image_digest {
+        Some(digest) => format!("{}@{digest}", pod.image),
+        None => pod.image.to_string(),
+    }
+}
+
+/// Label values are at most 63 characters of `[a-z0-9A-Z._-]`.
+fn label_value(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._-".contains(c) {
+                c
+            } else {
+                '-'
+            }
+        })
+        .take(MAX_NAME_LEN)
+        .collect();
+    value
+        .trim_matches(|c: char| !c.is_ascii_alphanumeric())
+        .to_string()
+}
+
+impl KubernetesExecutor {
+    pub fn new(config: &KubernetesConfig, project_root: &Path) -> Self {
+        Self {
+            config: config.clone(),
+            project_root: project_root.to_path_buf(),
+        }
+    }
+
+    pub fn command(&self) -> Command {
+        Command::new(&self.config.kubectl)
+    }
+
+    /// Namespace and context flags shared by every call.
+    fn scope_args(&self) -> Vec<String> {
+        let mut args = vec![format!("--namespace={}", self.config.namespace)];
+        if !self.config.context.trim().is_empty() {
+            args.push(format!("--context={}", self.config.context.trim()));
+        }
+        args
+    }
+
+    /// Sub-path of the project volume for a host path.
+    fn volume_sub_path(&self, host_path: &str) -> anyhow::Result<String> {
+        let relative = Path::new(host_path).strip_prefix(&self.project_root).map_err(|_| {
+            anyhow!("mount {host_path} is outside the project root and cannot come from the volume claim")
+        })?;
+        Ok(relative.to_string_lossy().into_owned())
+    }
+
+    /// The pod spec merged over kubectl's generated one.
+    fn overrides(&self, pod_name: &str, pod: &ContainerSpec<'_>) -> anyhow::Result<Value> {
+        let image = image_ref(pod);
+        let claim = self.config.volume_claim.trim();
+        if claim.is_empty() {
+            bail!("container.kubernetes.volume_claim is not set");
+        }
+
+        let mut volumes =
+            vec![json!({ "name": "project", "persistentVolumeClaim": { "claimName": claim } })];
+        let mut volume_mounts = Vec::new();
+        for mount in pod.mounts {
+            let mut entry = json!({
+                "name": "project",
+                "mountPath": mount.container_path,
+                "readOnly": mount.readonly,
+            });
+            let sub_path = self.volume_sub_path(&mount.host_path)?;
+            if !sub_path.is_empty() {
+                entry["subPath"] = json!(sub_path);
+            }
+            volume_mounts.push(entry);
+            // Excluded subdirectories are hidden under empty volumes, as
+            // the local runner does with tmpfs overlays.
+            for subdir in &mount.exclude {
+                let name = format!("exclude-{}", volumes.len());
+                volumes.push(json!({ "name": name, "emptyDir": {} }));
+                volume_mounts.push(json!({
+                    "name": name,
+                    "mountPath": format!("{}/{}", mount.container_path, subdir),
+                }));
+            }
+        }
+
+        let mut env = vec![json!({ "name": "TZ", "value": pod.timezone })];
+        if pod.network == NetworkMode::EgressAllowlist {
+            for (var, value) in proxy_env(pod.egress)? {
+                env.push(json!({ "name": var, "value": value }));
+            }
+        }
+
+        // pidsLimit is a node-level setting in Kubernetes and is not applied.
+        let mut limits = serde_json::Map::new();
+        if let Some(cpus) = pod.limits.cpus {
+            limits.insert("cpu".into(), json!(cpus.to_string()));
+        }
+        if let Some(bytes) = pod.limits.memory_bytes {
+            limits.insert("memory".into(), json!(bytes.to_string()));
+        }
+
+        let mut spec = json!({
+            "restartPolicy": "Never",
+            "volumes": volumes,
+            "containers": [{
+                "name": pod_name,
+                "image": image,
+                "imagePullPolicy": self.config.image_pull_policy,
+                "stdin": true,
+                "stdinOnce": true,
+                "tty": false,
+                "env": env,
+                "resources": { "limits": limits },
+                "volumeMounts": volume_mounts,
+            }],
+        });
+        if !self.config.service_account.trim().is_empty() {
+            spec["serviceAccountName"] = json!(self.config.service_account.trim());
+        }
+        if !self.config.node_selector.is_empty() {
+            spec["nodeSelector"] = json!(self.config.node_selector);
+        }
+        Ok(json!({ "apiVersion": "v1", "spec": spec }))
+    }
+
+    /// `kubectl run` arguments for one agent pod. `pod_name` must already
+    /// be a valid pod name (see [`pod_name`]).
+    pub fn run_args(&self, pod_name: &str, pod: &ContainerSpec<'_>) -> anyhow::Result<Vec<String>> {
+        let overrides = self.overrides(pod_name, pod)?;
+        let mut args = vec![
+            "run".to_string(),
+            pod_name.to_string(),
+            "-i".to_string(),
+            "--rm".to_string(),
+            // Keep kubectl's own status lines out of the agent's stdout.
+            "--quiet".to_string(),
+            "--restart=Never".to_string(),
+            format!("--image={}", image_ref(pod)),
+            format!(
+                "--labels={MANAGED_LABEL},{LABEL_GROUP}={},{LABEL_RUN_ID}={},{LABEL_RUNTIME}={},{LABEL_TRIGGER}={},intercom.network={}",
+                label_value(pod.group_folder),
+                label_value(pod.name),
+                label_value(pod.runtime),
+                label_value(pod.trigger),
+                pod.network.as_str()
+            ),
+            format!(
+                "--pod-running-timeout={}s",
+                self.config.start_timeout_secs.max(1)
+            ),
+            format!("--overrides={overrides}"),
+        ];
+        args.extend(self.scope_args());
+        Ok(args)
+    }
+
+    /// `delete pod` arguments; callers add the grace period and wait mode.
+    pub fn stop_args(&self, name: &str) -> Vec<String> {
+        let mut args = vec![
+            "delete".to_string(),
+            "pod".to_string(),
+            name.to_string(),
+            "--ignore-not-found".to_string(),
+        ];
+        args.extend(self.scope_args());
+        args
+    }
+
+    /// `get pods` arguments printing agent pods as `pod/<name>`.
+    pub fn list_agents_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "get".to_string(),
+            "pods".to_string(),
+            format!("--selector={MANAGED_LABEL}"),
+            "--output=name".to_string(),
+        ];
+        args.extend(self.scope_args());
+        args
+    }
+
+    /// Check kubectl can reach the cluster and create pods.
+    async fn can_create_pods(&self) -> anyhow::Result<()> {
+        let output = self
+            .command()
+            .args(["auth", "can-i", "create", "pods"])
+            .args(self.scope_args())
+            .output()
+            .await
+            .map_err(|e| anyhow!("kubectl `{}` not found: {}", self.config.kubectl, e))?;
+        if !output.status.success() || !String::from_utf8_lossy(&output.stdout).trim().eq("yes") {
+            bail!(
+                "kubectl cannot create pods in namespace {}: {}",
+                self.config.namespace,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl ContainerExecutor for KubernetesExecutor {
+    fn kind(&self) -> &'static str {
+        "kubernetes"
+    }
+
+    fn spawn<'a>(
+        &'a self,
+        spec: &'a ContainerSpec<'a>,
+    ) -> ExecutorFuture<'a, anyhow::Result<AgentProcess>> {
+        Box::pin(async move {
+            let name = pod_name(spec.name);
+            let args = self.run_args(&name, spec)?;
+            let child = self
+                .command()
+                .args(&args)
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+                .map_err(|e| anyhow!("Failed to run kubectl: {}", e))?;
+            AgentProcess::from_child(name, child)
+        })
+    }
+
+    fn stop<'a>(&'a self, name: &'a str, grace: Duration) -> ExecutorFuture<'a, StopOutcome> {
+        // The kubelet sends SIGTERM and waits out the grace period; kill
+        // with a forced delete if the pod is still there after it
+        let mut delete = self.command();
+        delete.args(self.stop_args(name));
+        delete.args([
+            format!("--grace-period={}", grace.as_secs().max(1)),
+            "--wait=true".into(),
+        ]);
+        delete.kill_on_drop(true);
+        let mut kill = self.command();
+        kill.args(self.stop_args(name));
+        kill.args(["--grace-period=0", "--force", "--wait=false"]);
+        // Leave kubectl a few seconds beyond the grace period to report back
+        Box::pin(stop_or_kill(
+            run_stop(delete, name),
+            kill,
+            name,
+            grace + Duration::from_secs(5),
+        ))
+    }
+
+    fn cleanup(&self) -> ExecutorFuture<'_, ()> {
+        Box::pin(async move {
+            let output = match self.command().args(self.list_agents_args()).output().await {
+                Ok(o) => o,
+                Err(e) => {
+                    warn!(error = %e, "Failed to list orphaned pods");
+                    return;
+                }
+            };
+            let names: Vec<&str> = std::str::from_utf8(&output.stdout)
+                .unwrap_or("")
+                .lines()
+                .map(|s| s.trim().trim_start_matches("pod/"))
+                .filter(|s| !s.is_empty())
+                .collect();
+            for name in &names {
+                let _ = self
+                    .command()
+                    .args(self.stop_args(name))
+                    .arg("--wait=false")
+                    .output()
+                    .await;
+            }
+            if !names.is_empty() {
+                info!(count = names.len(), "Deleted orphaned pods");
+            }
+        })
+    }
+
+    fn probe(&self) -> ExecutorFuture<'_, anyhow::Result<()>> {
+        Box::pin(self.can_create_pods())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::limits::ResourceLimits;
+    use intercom_core::{EgressProxyConfig, VolumeMount};
+
+    fn executor() -> KubernetesExecutor {
+        let config = KubernetesConfig {
+            volume_claim: "intercom-data".into(),
+            ..KubernetesConfig::default()
+        };
+        KubernetesExecutor::new(&config, Path::new("/srv/intercom"))
+    }
+
+    fn mount(host: &str, container: &str, readonly: bool) -> VolumeMount {
+        VolumeMount {
+            host_path: host.into(),
+            container_path: container.into(),
+            readonly,
+            exclude: vec![],
+        }
+    }
+
+    fn request<'a>(
+        mounts: &'a [VolumeMount],
+        limits: &'a ResourceLimits,
+        egress: &'a EgressProxyConfig,
+    ) -> ContainerSpec<'a> {
+        ContainerSpec {
+            name: "intercom-team-1",
+            group_folder: "team",
+            runtime: "claude",
+            trigger: "message",
+            image: "intercom-agent:latest",
+            image_digest: None,
+            verify_image: false,
+            timezone: "UTC",
+            mounts,
+            limits,
+            network: NetworkMode::Full,
+            egress,
+        }
+    }
+
+    #[test]
+    fn mounts_become_volume_sub_paths() {
+        let mut project = mount("/srv/intercom", "/workspace/project", true);
+        project.exclude = vec!["store".into()];
+        let mounts = [
+            project,
+            mount("/srv/intercom/groups/team", "/workspace/group", false),
+        ];
+        let limits = ResourceLimits {
+            memory_bytes: Some(1024 * 1024 * 1024),
+            ..ResourceLimits::default()
+        };
+        let egress = EgressProxyConfig::default();
+        let spec = executor()
+            .overrides("intercom-team-1", &request(&mounts, &limits, &egress))
+            .unwrap();
+
+        let container = &spec["spec"]["containers"][0];
+        assert_eq!(container["name"], "intercom-team-1");
+        assert_eq!(container["stdinOnce"], true);
+        assert_eq!(container["resources"]["limits"]["memory"], "1073741824");
+        let volume_mounts = container["volumeMounts"].as_array().unwrap();
+        assert!(volume_mounts[0].get("subPath").is_none());
+        assert_eq!(volume_mounts[1]["mountPath"], "/workspace/project/store");
+        assert_eq!(volume_mounts[2]["subPath"], "groups/team");
+        assert_eq!(
+            spec["spec"]["volumes"][0]["persistentVolumeClaim"]["claimName"],
+            "intercom-data"
+        );
+    }
+
+    #[test]
+    fn rejects_mounts_outside_the_project_and_missing_claim() {
+        let limits = ResourceLimits::default();
+        let egress = EgressProxyConfig::default();
+        let outside = [mount("/home/me/notes", "/workspace/extra/notes", true)];
+        assert!(
+            executor()
+                .run_args("intercom-team-1", &request(&outside, &limits, &egress))
+                .is_err()
+        );
+
+        let unclaimed =
+            KubernetesExecutor::new(&KubernetesConfig::default(), Path::new("/srv/intercom"));
+        assert!(
+            unclaimed
+                .run_args("intercom-team-1", &request(&[], &limits, &egress))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn run_args_attach_stdin_and_label_the_pod() {
+        let limits = ResourceLimits::default();
+        let egress = EgressProxyConfig::default();
+        let args = executor()
+            .run_args("intercom-team-1", &request(&[], &limits, &egress))
+            .unwrap();
+        assert_eq!(args[..4], ["run", "intercom-team-1", "-i", "--rm"]);
+        assert!(args.contains(&"--quiet".to_string()));
+        assert!(args.iter().any(|a| {
+            a.starts_with("--labels=app.kubernetes.io/managed-by=intercomd,intercom.group=team")
+        }));
+        assert!(args.iter().any(|a| a.contains(
+            ",intercom.run_id=intercom-team-1,intercom.runtime=claude,intercom.trigger=message,"
+        )));
+        assert!(args.contains(&"--namespace=intercom".to_string()));
+
+        let pinned = ContainerSpec {
+            image_digest: Some("sha256:abc"),
+            ..request(&[], &limits, &egress)
+        };
+        let args = executor().run_args("intercom-team-1", &pinned).unwrap();
+        assert!(args.contains(&"--image=intercom-agent:latest@sha256:abc".to_string()));
+    }
+
+    #[test]
+    fn pod_names_are_dns_labels() {
+        assert_eq!(
+            pod_name("intercom-Team_Eng-1719000000000"),
+            "intercom-team-eng-1719000000000"
+        );
+        let long = pod_name(&format!("intercom-{}-1719000000000", "x".repeat(80)));
+        assert_eq!(long.len(), MAX_NAME_LEN);
+        assert!(long.ends_with("-1719000000000"));
+    }
+}
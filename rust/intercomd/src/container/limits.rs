@@ -0,0 +1,148 @@
+//! Per-group container resource limits.
+//!
+//! `cpus`, `memory` and `pidsLimit` in a group's `containerConfig` become
+//! `--cpus`, `--memory` and `--pids-limit`. Limits are validated before the
+//! container starts; a group with an invalid limit fails its run instead of
+//! running uncapped.
+
+use anyhow::{anyhow, bail};
+
+use super::security::ContainerConfig;
+
+/// Smallest memory cap the engines accept (6 MiB).
+const MIN_MEMORY_BYTES: u64 = 6 * 1024 * 1024;
+
+/// Fewest processes an agent needs to start its runtime at all.
+const MIN_PIDS: u32 = 16;
+
+/// Validated limits for one container run. Empty means uncapped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    pub cpus: Option<f64>,
+    pub memory_bytes: Option<u64>,
+    pub pids_limit: Option<u32>,
+}
+
+impl ResourceLimits {
+    /// Validate the limits in a group's container config.
+    pub fn from_config(config: Option<&ContainerConfig>) -> anyhow::Result<Self> {
+        let Some(config) = config else {
+            return Ok(Self::default());
+        };
+        if let Some(cpus) = config.cpus {
+            let host_cpus = std::thread::available_parallelism().map_or(1, |n| n.get()) as f64;
+            if !cpus.is_finite() || cpus <= 0.0 {
+                bail!("containerConfig.cpus must be a positive number, got {cpus}");
+            }
+            if cpus > host_cpus {
+                bail!("containerConfig.cpus {cpus} exceeds the host's {host_cpus} CPUs");
+            }
+        }
+        let memory_bytes = config.memory.as_deref().map(parse_memory).transpose()?;
+        if let Some(pids) = config.pids_limit {
+            if pids < MIN_PIDS {
+                bail!("containerConfig.pidsLimit must be at least {MIN_PIDS}, got {pids}");
+            }
+        }
+        Ok(Self {
+            cpus: config.cpus,
+            memory_bytes,
+            pids_limit: config.pids_limit,
+        })
+    }
+
+    /// `run` flags for these limits.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(cpus) = self.cpus {
+            args.push(format!("--cpus={cpus}"));
+        }
+        if let Some(bytes) = self.memory_bytes {
+            args.push(format!("--memory={bytes}b"));
+        }
+        if let Some(pids) = self.pids_limit {
+            args.push(format!("--pids-limit={pids}"));
+        }
+        args
+    }
+}
+
+/// `"512m"` → bytes. Accepts `b`, `k`, `m` and `g` suffixes (binary units,
+/// as the engines use), with an optional trailing `b` as in `"2gb"`.
+pub fn parse_memory(value: &str) -> anyhow::Result<u64> {
+    let invalid =
+        || anyhow!("containerConfig.memory `{value}` is not a size like \"512m\" or \"2g\"");
+    let lower = value.trim().to_ascii_lowercase();
+    let unit_at = lower
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(lower.len());
+    let (digits, unit) = lower.split_at(unit_at);
+    let number: u64 = digits.parse().map_err(|_| invalid())?;
+    let multiplier: u64 = match unit
+        .strip_suffix('b')
+        .filter(|u| !u.is_empty())
+        .unwrap_or(unit)
+    {
+        "" | "b" => 1,
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        "g" => 1024 * 1024 * 1024,
+        _ => return Err(invalid()),
+    };
+    let bytes = number.checked_mul(multiplier).ok_or_else(invalid)?;
+    if bytes < MIN_MEMORY_BYTES {
+        bail!("containerConfig.memory `{value}` is below the 6m minimum");
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(value: serde_json::Value) -> ContainerConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn parses_memory_units() {
+        assert_eq!(parse_memory("512m").unwrap(), 512 * 1024 * 1024);
+        assert_eq!(parse_memory("2G").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(parse_memory("2gb").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(parse_memory("7340032").unwrap(), 7 * 1024 * 1024);
+        assert!(parse_memory("1m").is_err());
+        assert!(parse_memory("lots").is_err());
+        assert!(parse_memory("1.5g").is_err());
+    }
+
+    #[test]
+    fn limits_become_run_flags() {
+        let limits = ResourceLimits::from_config(Some(&config(serde_json::json!({
+            "cpus": 1,
+            "memory": "1g",
+            "pidsLimit": 256
+        }))))
+        .unwrap();
+        assert_eq!(
+            limits.args(),
+            ["--cpus=1", "--memory=1073741824b", "--pids-limit=256"]
+        );
+        assert!(ResourceLimits::from_config(None).unwrap().args().is_empty());
+    }
+
+    #[test]
+    fn invalid_limits_are_rejected() {
+        for bad in [
+            serde_json::json!({ "cpus": 0 }),
+            serde_json::json!({ "cpus": -1.5 }),
+            serde_json::json!({ "cpus": 100_000 }),
+            serde_json::json!({ "memory": "10k" }),
+            serde_json::json!({ "pidsLimit": 1 }),
+        ] {
+            assert!(
+                ResourceLimits::from_config(Some(&config(bad.clone()))).is_err(),
+                "{bad}"
+            );
+        }
+    }
+}
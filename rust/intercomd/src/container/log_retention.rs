@@ -0,0 +1,339 @@
+//! Retention for per-run container logs.
+//!
+//! Each run writes `container-{ts}.log`, or `container-{ts}.error.log` when
+//! it failed or timed out, into `groups/{folder}/logs/`. After every run
+//! the directory is pruned in order: logs past `max_age_days`, then all but
+//! the newest `keep_success` / `keep_error`, then logs older than
+//! `compress_after_hours` are gzipped in place (`.log.gz`, same mtime), and
+//! finally the oldest are removed until the directory fits `max_total_mb`.
+//! Output overflow files (`container-{ts}.stdout.overflow`) go once their
+//! run's log is gone.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use intercom_core::ContainerLogConfig;
+
+use super::output_spool::OVERFLOW_SUFFIX;
+
+/// Marks a failed run's log: `container-{ts}.error.log`.
+pub const ERROR_LOG_SUFFIX: &str = ".error.log";
+
+struct LogFile {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+    is_error: bool,
+}
+
+impl LogFile {
+    fn is_compressed(&self) -> bool {
+        self.path.extension().is_some_and(|ext| ext == "gz")
+    }
+}
+
+/// What a prune removed or compressed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PruneStats {
+    pub removed: usize,
+    pub compressed: usize,
+}
+
+/// File name for a run's log.
+pub fn log_file_name(timestamp: &str, is_error: bool) -> String {
+    if is_error {
+        format!("container-{timestamp}{ERROR_LOG_SUFFIX}")
+    } else {
+        format!("container-{timestamp}.log")
+    }
+}
+
+/// The `container-{ts}` part shared by a run's log and overflow files.
+fn run_key(name: &str) -> &str {
+    name.split('.').next().unwrap_or(name)
+}
+
+/// Whether a file name is a run log, plain or gzipped.
+pub fn is_run_log(name: &str) -> bool {
+    name.starts_with("container-") && (name.ends_with(".log") || name.ends_with(".log.gz"))
+}
+
+fn list(logs_dir: &Path) -> io::Result<Vec<LogFile>> {
+    let mut logs = Vec::new();
+    for entry in fs::read_dir(logs_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !is_run_log(&name) {
+            continue;
+        }
+        let meta = entry.metadata()?;
+        logs.push(LogFile {
+            path: entry.path(),
+            modified: meta.modified()?,
+            size: meta.len(),
+            is_error: name.trim_end_matches(".gz").ends_with(ERROR_LOG_SUFFIX),
+        });
+    }
+    // Newest first.
+    logs.sort_by(|a, b| {
+        b.modified
+            .cmp(&a.modified)
+            .then_with(|| b.path.cmp(&a.path))
+    });
+    Ok(logs)
+}
+
+fn older_than(log: &LogFile, now: SystemTime, age: Duration) -> bool {
+    now.duration_since(log.modified)
+        .is_ok_and(|elapsed| elapsed > age)
+}
+
+/// Gzip `log` next to itself and remove the original.
+fn compress(log: &mut LogFile) -> io::Result<()> {
+    let mut target = log.path.clone().into_os_string();
+    target.push(".gz");
+    let target = PathBuf::from(target);
+    let mut encoder = GzEncoder::new(fs::File::create(&target)?, Compression::default());
+    encoder.write_all(&fs::read(&log.path)?)?;
+    let file = encoder.finish()?;
+    // Keep the run time, so age limits still count from the run.
+    file.set_modified(log.modified)?;
+    log.size = file.metadata()?.len();
+    fs::remove_file(&log.path)?;
+    log.path = target;
+    Ok(())
+}
+
+/// Apply `config` to one group's log directory.
+pub fn prune(logs_dir: &Path, config: &ContainerLogConfig) -> io::Result<PruneStats> {
+    let now = SystemTime::now();
+    let mut stats = PruneStats::default();
+    let mut kept = Vec::new();
+    let (mut success_seen, mut error_seen) = (0, 0);
+    for log in list(logs_dir)? {
+        let (seen, keep) = if log.is_error {
+            (&mut error_seen, config.keep_error)
+        } else {
+            (&mut success_seen, config.keep_success)
+        };
+        *seen += 1;
+        let expired = config.max_age_days > 0
+            && older_than(&log, now, Duration::from_secs(config.max_age_days * 86_400));
+        if expired || (keep > 0 && *seen > keep) {
+            fs::remove_file(&log.path)?;
+            stats.removed += 1;
+        } else {
+            kept.push(log);
+        }
+    }
+
+    if config.compress_after_hours > 0 {
+        let age = Duration::from_secs(config.compress_after_hours * 3600);
+        for log in kept.iter_mut() {
+            if !log.is_compressed() && older_than(log, now, age) {
+                compress(log)?;
+                stats.compressed += 1;
+            }
+        }
+    }
+
+    if config.max_total_mb > 0 {
+        let cap = config.max_total_mb * 1024 * 1024;
+        let mut total: u64 = kept.iter().map(|log| log.size).sum();
+        // Never remove the newest log, however large.
+        while total > cap && kept.len() > 1 {
+            let oldest = kept.pop().expect("more than one log");
+            fs::remove_file(&oldest.path)?;
+            total -= oldest.size;
+            stats.removed += 1;
+        }
+    }
+
+    let runs: Vec<String> = list(logs_dir)?
+        .iter()
+        .filter_map(|log| log.path.file_name())
+        .map(|name| run_key(&name.to_string_lossy()).to_string())
+        .collect();
+    for entry in fs::read_dir(logs_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with("container-")
+            && name.ends_with(OVERFLOW_SUFFIX)
+            && !runs.iter().any(|run| run == run_key(&name))
+        {
+            fs::remove_file(entry.path())?;
+            stats.removed += 1;
+        }
+    }
+    Ok(stats)
+}
+
+/// The newest run log in `logs_dir`, if any run has logged.
+pub fn latest(logs_dir: &Path) -> io::Result<Option<PathBuf>> {
+    match list(logs_dir) {
+        Ok(logs) => Ok(logs.into_iter().next().map(|log| log.path)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// A run log's text, gunzipping it if retention has compressed it.
+pub fn read(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut text = String::new();
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        GzDecoder::new(file).read_to_string(&mut text)?;
+    } else {
+        file.read_to_string(&mut text)?;
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_log(dir: &Path, name: &str, age_hours: u64, bytes: usize) {
+        let path = dir.join(name);
+        fs::write(&path, "x".repeat(bytes)).unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(age_hours * 3600);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn config() -> ContainerLogConfig {
+        ContainerLogConfig {
+            keep_success: 2,
+            keep_error: 1,
+            max_age_days: 0,
+            max_total_mb: 0,
+            compress_after_hours: 0,
+            ..ContainerLogConfig::default()
+        }
+    }
+
+    #[test]
+    fn keeps_newest_per_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        for (i, name) in ["container-1.log", "container-2.log", "container-3.log"]
+            .iter()
+            .enumerate()
+        {
+            write_log(dir.path(), name, 10 - i as u64, 10);
+        }
+        write_log(dir.path(), "container-4.error.log", 5, 10);
+        write_log(dir.path(), "container-4.stdout.overflow", 5, 10);
+        write_log(dir.path(), "container-5.error.log", 4, 10);
+        write_log(dir.path(), "container-5.stderr.overflow", 4, 10);
+        write_log(dir.path(), "notes.txt", 100, 10);
+
+        let stats = prune(dir.path(), &config()).unwrap();
+        assert_eq!(
+            stats,
+            PruneStats {
+                removed: 3,
+                compressed: 0
+            }
+        );
+        assert_eq!(
+            names(dir.path()),
+            [
+                "container-2.log",
+                "container-3.log",
+                "container-5.error.log",
+                "container-5.stderr.overflow",
+                "notes.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn expires_compresses_and_caps_size() {
+        let dir = tempfile::tempdir().unwrap();
+        write_log(dir.path(), "container-1.log", 24 * 40, 10);
+        write_log(dir.path(), "container-2.error.log", 48, 4096);
+        write_log(dir.path(), "container-3.log", 1, 10);
+        let config = ContainerLogConfig {
+            keep_success: 0,
+            keep_error: 0,
+            max_age_days: 30,
+            compress_after_hours: 24,
+            ..config()
+        };
+
+        let stats = prune(dir.path(), &config).unwrap();
+        assert_eq!(
+            stats,
+            PruneStats {
+                removed: 1,
+                compressed: 1
+            }
+        );
+        assert_eq!(
+            names(dir.path()),
+            ["container-2.error.log.gz", "container-3.log"]
+        );
+        let gz = fs::metadata(dir.path().join("container-2.error.log.gz")).unwrap();
+        assert!(gz.len() < 4096);
+        assert!(
+            SystemTime::now()
+                .duration_since(gz.modified().unwrap())
+                .unwrap()
+                > Duration::from_secs(47 * 3600)
+        );
+
+        // A second pass leaves the compressed log alone.
+        assert_eq!(prune(dir.path(), &config).unwrap(), PruneStats::default());
+    }
+
+    #[test]
+    fn size_cap_spares_the_newest_log() {
+        let dir = tempfile::tempdir().unwrap();
+        write_log(dir.path(), "container-1.log", 2, 700 * 1024);
+        write_log(dir.path(), "container-2.log", 1, 2 * 1024 * 1024);
+        let config = ContainerLogConfig {
+            keep_success: 0,
+            max_total_mb: 1,
+            ..config()
+        };
+        prune(dir.path(), &config).unwrap();
+        assert_eq!(names(dir.path()), ["container-2.log"]);
+    }
+
+    #[test]
+    fn latest_reads_compressed_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(latest(&dir.path().join("missing")).unwrap(), None);
+        write_log(dir.path(), "container-1.log", 1, 10);
+        write_log(dir.path(), "container-2.error.log", 0, 10);
+        let newest = latest(dir.path()).unwrap().unwrap();
+        assert!(newest.ends_with("container-2.error.log"));
+
+        let mut log = LogFile {
+            path: newest,
+            modified: SystemTime::now(),
+            size: 10,
+            is_error: true,
+        };
+        compress(&mut log).unwrap();
+        assert_eq!(read(&log.path).unwrap(), "x".repeat(10));
+    }
+}
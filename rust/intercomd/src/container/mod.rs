@@ -1,4 +1,20 @@
+pub mod context;
+pub mod disk_quota;
+pub mod engine;
+pub mod executor;
+pub mod host;
+pub mod images;
+pub mod kubernetes;
+pub mod limits;
+pub mod log_retention;
 pub mod mounts;
+pub mod network;
+pub mod output_spool;
+pub mod output_stream;
+pub mod pricing;
+pub mod run_events;
 pub mod runner;
 pub mod secrets;
 pub mod security;
+pub mod stdin_channel;
+pub mod warm_pool;
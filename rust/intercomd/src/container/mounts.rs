@@ -126,7 +126,14 @@ pub fn build_volume_mounts(
 
     // Per-group IPC namespace.
     let ipc_dir = data_dir.join("ipc").join(&group.folder);
-    for sub in &["messages", "tasks", "input", "queries", "responses"] {
+    for sub in &[
+        "messages",
+        "tasks",
+        "input",
+        "queries",
+        "responses",
+        "inbox",
+    ] {
         fs::create_dir_all(ipc_dir.join(sub)).ok();
     }
     mounts.push(VolumeMount {
@@ -383,6 +390,7 @@ mod tests {
         assert!(ipc_base.join("input").exists());
         assert!(ipc_base.join("queries").exists());
         assert!(ipc_base.join("responses").exists());
+        assert!(ipc_base.join("inbox").exists());
     }
 
     #[test]
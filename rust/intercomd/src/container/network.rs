@@ -0,0 +1,106 @@
+//! Per-group network policy.
+//!
+//! A group's `containerConfig.network` picks how much of the network its
+//! agent sees:
+//!
+//! - `full` (default): the engine's default bridge network.
+//! - `none`: `--network=none`, no network at all. The agent cannot reach a
+//!   hosted model API either, so this suits local runtimes or work that
+//!   only touches mounted files.
+//! - `egress-allowlist`: the container joins `container.egress.network`,
+//!   an internal network whose only way out is the proxy at
+//!   `container.egress.proxy_url`, which enforces the allowlist.
+
+use anyhow::bail;
+use intercom_core::EgressProxyConfig;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NetworkMode {
+    #[default]
+    Full,
+    None,
+    EgressAllowlist,
+}
+
+impl NetworkMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::None => "none",
+            Self::EgressAllowlist => "egress-allowlist",
+        }
+    }
+}
+
+/// `run` flags enforcing `mode`. Fails when the egress proxy is requested
+/// but not configured, so the group never silently gets full access.
+pub fn network_args(mode: NetworkMode, egress: &EgressProxyConfig) -> anyhow::Result<Vec<String>> {
+    match mode {
+        NetworkMode::Full => Ok(Vec::new()),
+        NetworkMode::None => Ok(vec!["--network=none".to_string()]),
+        NetworkMode::EgressAllowlist => {
+            let mut args = vec![format!("--network={}", egress.network.trim())];
+            for (var, value) in proxy_env(egress)? {
+                args.extend(["-e".to_string(), format!("{var}={value}")]);
+            }
+            Ok(args)
+        }
+    }
+}
+
+/// Proxy variables for `egress-allowlist`, or an error when the proxy is
+/// not configured.
+pub fn proxy_env(egress: &EgressProxyConfig) -> anyhow::Result<Vec<(&'static str, String)>> {
+    let proxy = egress.proxy_url.trim();
+    if proxy.is_empty() || egress.network.trim().is_empty() {
+        bail!("network \"egress-allowlist\" needs container.egress.network and proxy_url");
+    }
+    // Tools disagree on the case they read.
+    let mut env: Vec<_> = ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"]
+        .into_iter()
+        .map(|var| (var, proxy.to_string()))
+        .collect();
+    for var in ["NO_PROXY", "no_proxy"] {
+        env.push((var, egress.no_proxy.trim().to_string()));
+    }
+    Ok(env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::security::ContainerConfig;
+
+    #[test]
+    fn parses_modes_from_container_config() {
+        let cfg: ContainerConfig =
+            serde_json::from_value(serde_json::json!({ "network": "egress-allowlist" })).unwrap();
+        assert_eq!(cfg.network, Some(NetworkMode::EgressAllowlist));
+        let cfg: ContainerConfig = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(cfg.network.unwrap_or_default(), NetworkMode::Full);
+    }
+
+    #[test]
+    fn none_and_full_need_no_proxy() {
+        let egress = EgressProxyConfig::default();
+        assert!(network_args(NetworkMode::Full, &egress).unwrap().is_empty());
+        assert_eq!(
+            network_args(NetworkMode::None, &egress).unwrap(),
+            ["--network=none"]
+        );
+    }
+
+    #[test]
+    fn egress_mode_requires_a_proxy() {
+        let mut egress = EgressProxyConfig::default();
+        assert!(network_args(NetworkMode::EgressAllowlist, &egress).is_err());
+
+        egress.proxy_url = "http://intercom-egress-proxy:3128".into();
+        let args = network_args(NetworkMode::EgressAllowlist, &egress).unwrap();
+        assert_eq!(args[0], "--network=intercom-egress");
+        assert!(args.contains(&"HTTPS_PROXY=http://intercom-egress-proxy:3128".to_string()));
+        assert!(args.contains(&"NO_PROXY=localhost,127.0.0.1".to_string()));
+    }
+}
@@ -0,0 +1,145 @@
+//! Capped container output with overflow to disk.
+//!
+//! The runner keeps up to `max_output_bytes` of a run's stdout and stderr in
+//! memory for its log and for decoding. Past the cap, with `spool_overflow`,
+//! the whole stream (what was buffered plus everything after) goes to
+//! `groups/{folder}/logs/container-{ts}.{stream}.overflow`, which the run log
+//! names. Overflow files are pruned along with their run log.
+
+use std::path::{Path, PathBuf};
+
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// Marks a spooled stream: `container-{ts}.stdout.overflow`.
+pub const OVERFLOW_SUFFIX: &str = ".overflow";
+
+pub fn overflow_file_name(timestamp: &str, stream: &str) -> String {
+    format!("container-{timestamp}.{stream}{OVERFLOW_SUFFIX}")
+}
+
+/// One output stream of a run, buffered up to a cap.
+pub struct CappedOutput {
+    cap: usize,
+    /// What fits under the cap.
+    pub text: String,
+    pub truncated: bool,
+    /// Bytes read, including any past the cap.
+    pub total_bytes: u64,
+    /// Where the full stream goes once it passes the cap; `None` drops it.
+    spool_path: Option<PathBuf>,
+    spool: Option<tokio::fs::File>,
+}
+
+impl CappedOutput {
+    pub fn new(cap: usize, spool_path: Option<PathBuf>) -> Self {
+        Self {
+            cap,
+            text: String::new(),
+            truncated: false,
+            total_bytes: 0,
+            spool_path,
+            spool: None,
+        }
+    }
+
+    /// Append a chunk. Returns `true` on the chunk that passes the cap.
+    pub async fn push(&mut self, chunk: &str) -> bool {
+        self.total_bytes += chunk.len() as u64;
+        if let Some(spool) = self.spool.as_mut() {
+            if let Err(e) = spool.write_all(chunk.as_bytes()).await {
+                self.spool_failed(&e);
+            }
+            return false;
+        }
+        if self.truncated {
+            return false;
+        }
+        let remaining = self.cap.saturating_sub(self.text.len());
+        if chunk.len() <= remaining {
+            self.text.push_str(chunk);
+            return false;
+        }
+        let mut end = remaining;
+        while !chunk.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.text.push_str(&chunk[..end]);
+        self.truncated = true;
+        self.open_spool(&chunk[end..]).await;
+        true
+    }
+
+    async fn open_spool(&mut self, rest: &str) {
+        let Some(path) = self.spool_path.as_ref() else {
+            return;
+        };
+        let result = async {
+            let mut file = tokio::fs::File::create(path).await?;
+            file.write_all(self.text.as_bytes()).await?;
+            file.write_all(rest.as_bytes()).await?;
+            Ok::<_, std::io::Error>(file)
+        }
+        .await;
+        match result {
+            Ok(file) => self.spool = Some(file),
+            Err(e) => self.spool_failed(&e),
+        }
+    }
+
+    fn spool_failed(&mut self, error: &std::io::Error) {
+        if let Some(path) = self.spool_path.take() {
+            warn!(path = %path.display(), error = %error, "Output overflow spool failed");
+        }
+        self.spool = None;
+    }
+
+    /// The overflow file holding the full stream, once there is one.
+    pub fn spooled_to(&self) -> Option<&Path> {
+        self.spool.as_ref().and(self.spool_path.as_deref())
+    }
+
+    pub async fn finish(&mut self) {
+        if let Some(spool) = self.spool.as_mut() {
+            if let Err(e) = spool.flush().await {
+                self.spool_failed(&e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn overflow_spools_the_whole_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(overflow_file_name("1-000", "stdout"));
+        let mut out = CappedOutput::new(8, Some(path.clone()));
+        assert!(!out.push("hello\n").await);
+        assert!(out.spooled_to().is_none());
+        assert!(out.push("wörld\n").await);
+        assert!(!out.push("more\n").await);
+        out.finish().await;
+
+        // "ö" straddles the cap, so the buffer stops short of it.
+        assert_eq!(out.text, "hello\nw");
+        assert!(out.truncated);
+        assert_eq!(out.total_bytes, 18);
+        assert_eq!(out.spooled_to(), Some(path.as_path()));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "hello\nwörld\nmore\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn without_a_spool_overflow_is_dropped() {
+        let mut out = CappedOutput::new(4, None);
+        assert!(out.push("abcdef").await);
+        assert_eq!(out.text, "abcd");
+        assert_eq!(out.total_bytes, 6);
+        assert!(out.spooled_to().is_none());
+    }
+}
@@ -0,0 +1,167 @@
+//! Concurrent stdout/stderr readers for a running container.
+//!
+//! Each stream is read line by line on its own task, and the lines reach the
+//! runner through one bounded channel, so a container flooding stderr never
+//! holds up OUTPUT markers on stdout (or the other way round). When the
+//! runner falls behind, the channel fills and the readers stop reading: the
+//! container then blocks on its pipe instead of intercomd buffering without
+//! limit.
+
+use std::io;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use super::executor::AgentOutput;
+
+/// Lines in flight between the readers and the runner.
+pub const LINE_BUFFER: usize = 256;
+
+#[derive(Debug)]
+pub enum StreamLine {
+    Stdout(String),
+    Stderr(String),
+    /// Stdout ended, at EOF or on a read error. Ends the run.
+    StdoutClosed(Option<io::Error>),
+}
+
+pub struct OutputStreams {
+    lines: mpsc::Receiver<StreamLine>,
+    readers: [JoinHandle<()>; 2],
+}
+
+impl OutputStreams {
+    pub fn spawn(stdout: AgentOutput, stderr: AgentOutput, capacity: usize) -> Self {
+        let (tx, lines) = mpsc::channel(capacity);
+        let stdout_tx = tx.clone();
+        let stdout_reader = tokio::spawn(async move {
+            let mut reader = BufReader::new(stdout);
+            loop {
+                let mut line = String::new();
+                let closed = match reader.read_line(&mut line).await {
+                    Ok(0) => StreamLine::StdoutClosed(None),
+                    Ok(_) => StreamLine::Stdout(line),
+                    Err(e) => StreamLine::StdoutClosed(Some(e)),
+                };
+                let done = matches!(closed, StreamLine::StdoutClosed(_));
+                if stdout_tx.send(closed).await.is_err() || done {
+                    break;
+                }
+            }
+        });
+        let stderr_reader = tokio::spawn(async move {
+            let mut reader = BufReader::new(stderr);
+            loop {
+                let mut line = String::new();
+                match reader.read_line(&mut line).await {
+                    Ok(n) if n > 0 => {
+                        if tx.send(StreamLine::Stderr(line)).await.is_err() {
+                            break;
+                        }
+                    }
+                    // Stderr errors are non-fatal; stdout decides when the run ends
+                    _ => break,
+                }
+            }
+        });
+        Self {
+            lines,
+            readers: [stdout_reader, stderr_reader],
+        }
+    }
+
+    /// The next line from either stream; `None` once both have ended.
+    pub async fn next(&mut self) -> Option<StreamLine> {
+        self.lines.recv().await
+    }
+
+    /// After stdout closed, the stderr lines still to come, waiting up to
+    /// `grace` for the stream to end.
+    pub async fn drain_stderr(&mut self, grace: Duration) -> Vec<String> {
+        let deadline = tokio::time::Instant::now() + grace;
+        let mut drained = Vec::new();
+        while let Ok(Some(line)) = tokio::time::timeout_at(deadline, self.lines.recv()).await {
+            if let StreamLine::Stderr(line) = line {
+                drained.push(line);
+            }
+        }
+        drained
+    }
+}
+
+impl Drop for OutputStreams {
+    fn drop(&mut self) {
+        for reader in &self.readers {
+            reader.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intercom_core::{OUTPUT_END_MARKER, OUTPUT_START_MARKER};
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn interleaved_floods_lose_nothing() {
+        const LINES: usize = 5_000;
+        // Pipes smaller than a single burst: a reader that waited on the
+        // other stream would deadlock the writer.
+        let (mut out_w, out_r) = tokio::io::duplex(256);
+        let (mut err_w, err_r) = tokio::io::duplex(256);
+        let writer = tokio::spawn(async move {
+            for i in 0..LINES {
+                out_w
+                    .write_all(
+                        format!("{OUTPUT_START_MARKER}\n{{\"n\":{i}}}\n{OUTPUT_END_MARKER}\n")
+                            .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+                err_w
+                    .write_all(format!("debug {i} {}\n", "x".repeat(200)).as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let mut streams = OutputStreams::spawn(Box::new(out_r), Box::new(err_r), 8);
+        let (mut stdout, mut stderr) = (Vec::new(), 0);
+        loop {
+            match streams.next().await.unwrap() {
+                StreamLine::Stdout(line) => stdout.push(line),
+                StreamLine::Stderr(_) => stderr += 1,
+                StreamLine::StdoutClosed(err) => {
+                    assert!(err.is_none());
+                    break;
+                }
+            }
+            // A slow consumer: the bounded channel pushes back on both readers.
+            if stdout.len() % 500 == 0 {
+                tokio::task::yield_now().await;
+            }
+        }
+        stderr += streams.drain_stderr(Duration::from_secs(1)).await.len();
+        writer.await.unwrap();
+
+        assert_eq!(stderr, LINES);
+        let markers: Vec<_> = stdout.iter().filter(|l| l.starts_with("{\"n\":")).collect();
+        assert_eq!(markers.len(), LINES);
+        assert!(
+            markers
+                .iter()
+                .enumerate()
+                .all(|(i, l)| **l == format!("{{\"n\":{i}}}\n"))
+        );
+        assert_eq!(
+            stdout
+                .iter()
+                .filter(|l| l.trim_end() == OUTPUT_END_MARKER)
+                .count(),
+            LINES
+        );
+    }
+}
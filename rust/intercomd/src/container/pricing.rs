@@ -0,0 +1,71 @@
+//! Estimated cost of a run's tokens.
+//!
+//! The Claude runner reports what each turn cost; the Gemini runner only
+//! reports token counts and the Codex CLI reports neither. Runs without a
+//! reported cost are priced from list prices per million tokens, matched
+//! on the model id's prefix. Cache reads bill at a tenth of the input
+//! price and cache writes at a quarter more. Models not in the table stay
+//! unpriced rather than guessed.
+
+use intercom_core::TokenUsage;
+
+/// USD per million input and output tokens, by model id prefix. Longer
+/// prefixes come first so they win.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("claude-opus", 15.0, 75.0),
+    ("claude-sonnet", 3.0, 15.0),
+    ("claude-haiku", 1.0, 5.0),
+    ("gemini-2.5-flash", 0.30, 2.50),
+    ("gemini-2.5-pro", 1.25, 10.0),
+    ("gemini-3", 2.0, 12.0),
+    ("gpt-5", 1.25, 10.0),
+];
+
+const CACHE_READ_FACTOR: f64 = 0.1;
+const CACHE_WRITE_FACTOR: f64 = 1.25;
+
+/// The run's cost: as reported, else estimated for `model`.
+pub fn cost_usd(model: Option<&str>, usage: &TokenUsage) -> Option<f64> {
+    if usage.cost_usd.is_some() {
+        return usage.cost_usd;
+    }
+    let model = model?.to_ascii_lowercase();
+    let (_, input, output) = PRICES
+        .iter()
+        .find(|(prefix, ..)| model.starts_with(prefix))?;
+    let input_tokens = usage.input_tokens as f64
+        + usage.cache_read_tokens as f64 * CACHE_READ_FACTOR
+        + usage.cache_write_tokens as f64 * CACHE_WRITE_FACTOR;
+    Some((input_tokens * input + usage.output_tokens as f64 * output) / 1_000_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reported_cost_wins_over_prices() {
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            ..TokenUsage::default()
+        };
+        assert_eq!(cost_usd(Some("claude-sonnet-4-6"), &usage), Some(4.5));
+        assert_eq!(cost_usd(Some("gemini-2.5-flash"), &usage), Some(0.55));
+        assert_eq!(cost_usd(Some("llama-3"), &usage), None);
+        assert_eq!(cost_usd(None, &usage), None);
+
+        let reported = TokenUsage {
+            cost_usd: Some(0.12),
+            ..usage
+        };
+        assert_eq!(cost_usd(Some("llama-3"), &reported), Some(0.12));
+
+        let cached = TokenUsage {
+            cache_read_tokens: 1_000_000,
+            cache_write_tokens: 1_000_000,
+            ..TokenUsage::default()
+        };
+        assert_eq!(cost_usd(Some("claude-opus-4-6"), &cached), Some(20.25));
+    }
+}
@@ -0,0 +1,226 @@
+//! Per-run event transcripts (`data/runs/{run_id}.jsonl`).
+//!
+//! Every output the runner decodes from a container (tool starts, text
+//! deltas, results) is appended as one JSON line, between a `start` and an
+//! `exit` record, so operators can see what an agent actually did.
+//! `GET /v1/runs/{id}/events` serves the file back. The run id is the name
+//! generated for the run's container. Transcripts follow the container log
+//! `max_age_days`.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use intercom_core::ContainerOutput;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tracing::warn;
+
+use super::executor::StopOutcome;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RunEventKind {
+    Start {
+        group: String,
+        runtime: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+    },
+    Output {
+        output: ContainerOutput,
+    },
+    Exit {
+        exit_code: Option<i32>,
+        timed_out: bool,
+        /// How intercomd stopped the container, if it had to.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        stop: Option<StopOutcome>,
+        duration_ms: u64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunEvent {
+    pub seq: u64,
+    /// RFC 3339 time the event was recorded.
+    pub ts: String,
+    #[serde(flatten)]
+    pub kind: RunEventKind,
+}
+
+/// Appends a run's events to its transcript. Write errors are logged once
+/// and further events dropped; a transcript never fails a run.
+pub struct RunEventLog {
+    path: PathBuf,
+    writer: Option<BufWriter<tokio::fs::File>>,
+    seq: u64,
+}
+
+/// Run ids are container names; anything else could escape `runs_dir`.
+pub fn valid_run_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+pub fn transcript_path(runs_dir: &Path, run_id: &str) -> PathBuf {
+    runs_dir.join(format!("{run_id}.jsonl"))
+}
+
+impl RunEventLog {
+    pub async fn create(runs_dir: &Path, run_id: &str) -> Self {
+        let path = transcript_path(runs_dir, run_id);
+        let file = match tokio::fs::create_dir_all(runs_dir).await {
+            Ok(()) => tokio::fs::File::create(&path).await,
+            Err(e) => Err(e),
+        };
+        let writer = match file {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Cannot create run transcript");
+                None
+            }
+        };
+        Self {
+            path,
+            writer,
+            seq: 0,
+        }
+    }
+
+    pub async fn record(&mut self, kind: RunEventKind) {
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+        self.seq += 1;
+        let event = RunEvent {
+            seq: self.seq,
+            ts: chrono::Utc::now().to_rfc3339(),
+            kind,
+        };
+        let mut line = serde_json::to_vec(&event).unwrap_or_default();
+        line.push(b'\n');
+        if let Err(e) = writer.write_all(&line).await {
+            warn!(path = %self.path.display(), error = %e, "Run transcript write failed");
+            self.writer = None;
+        }
+    }
+
+    pub async fn finish(mut self) {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush().await.ok();
+        }
+    }
+}
+
+/// A run's events, or `None` when there is no transcript for it.
+pub async fn read_events(runs_dir: &Path, run_id: &str) -> io::Result<Option<Vec<RunEvent>>> {
+    let content = match tokio::fs::read_to_string(transcript_path(runs_dir, run_id)).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    // A torn last line (run still writing, or a crash) is skipped.
+    Ok(Some(
+        content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect(),
+    ))
+}
+
+/// Remove transcripts older than `max_age_days` (0 keeps them).
+pub fn prune(runs_dir: &Path, max_age_days: u64) -> io::Result<usize> {
+    if max_age_days == 0 {
+        return Ok(0);
+    }
+    let max_age = Duration::from_secs(max_age_days * 86_400);
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for entry in std::fs::read_dir(runs_dir)? {
+        let entry = entry?;
+        let expired = entry
+            .metadata()?
+            .modified()
+            .is_ok_and(|modified| now.duration_since(modified).is_ok_and(|age| age > max_age));
+        if expired && entry.path().extension().is_some_and(|ext| ext == "jsonl") {
+            std::fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use intercom_core::{ContainerStatus, StreamEvent};
+
+    #[tokio::test]
+    async fn transcript_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = RunEventLog::create(dir.path(), "intercom-team-1").await;
+        log.record(RunEventKind::Start {
+            group: "Team".into(),
+            runtime: "claude".into(),
+            correlation_id: Some("c1".into()),
+        })
+        .await;
+        log.record(RunEventKind::Output {
+            output: ContainerOutput {
+                status: ContainerStatus::Success,
+                result: None,
+                new_session_id: None,
+                error: None,
+                model: None,
+                event: Some(StreamEvent::ToolStart {
+                    tool_name: Some("Bash".into()),
+                    tool_input: Some("ls".into()),
+                }),
+                usage: None,
+            },
+        })
+        .await;
+        log.record(RunEventKind::Exit {
+            exit_code: Some(0),
+            timed_out: false,
+            stop: None,
+            duration_ms: 12,
+        })
+        .await;
+        log.finish().await;
+
+        let raw = std::fs::read_to_string(dir.path().join("intercom-team-1.jsonl")).unwrap();
+        assert!(raw.lines().nth(1).unwrap().contains(r#""kind":"output""#));
+
+        let events = read_events(dir.path(), "intercom-team-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(events.iter().map(|e| e.seq).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(matches!(
+            events[2].kind,
+            RunEventKind::Exit {
+                exit_code: Some(0),
+                ..
+            }
+        ));
+        assert!(
+            read_events(dir.path(), "intercom-none-2")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn run_ids_cannot_escape_the_directory() {
+        assert!(valid_run_id("intercom-team-eng-1719000000000"));
+        assert!(!valid_run_id("../secrets"));
+        assert!(!valid_run_id("a/b"));
+        assert!(!valid_run_id(""));
+    }
+}
@@ -1,33 +1,42 @@
-//! Async container runner: spawns Docker containers and manages their lifecycle.
+//! Async container runner: spawns agent containers and manages their lifecycle.
 //!
 //! Port of `runContainerAgent()` from container-runner.ts.
 //!
-//! Uses tokio::process for async spawning, streams stdout for OUTPUT marker
-//! pairs, manages activity-based timeouts, and handles graceful stop.
+//! Uses tokio::process for async spawning, decodes stdout with the runtime
+//! profile's protocol adapter (OUTPUT markers by default), manages
+//! activity-based timeouts, and handles graceful stop.
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use intercom_core::{
-    ContainerInput, ContainerOutput, ContainerStatus, RuntimeKind, VolumeMount,
-    container_image, extract_output_markers,
+    ChannelsConfig, ContainerInput, ContainerLogConfig, ContainerOutput, ContainerRun,
+    ContainerStatus, EgressProxyConfig, OutputDecoder, RuntimeKind, RuntimeProfile,
+    RuntimeProtocol, SharedStorage, SnapshotConfig, TokenUsage, VolumeMount,
 };
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::process::Command;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::{Mutex, watch};
-use tracing::{debug, error, info, warn};
+use tracing::{Instrument, debug, error, info, warn};
 
+use super::disk_quota::DiskQuotas;
+use super::executor::{AgentProcess, CliExecutor, ContainerExecutor, ContainerSpec, StopOutcome};
+use super::images;
+use super::limits::ResourceLimits;
+use super::log_retention;
 use super::mounts::{GroupInfo, build_volume_mounts, container_name};
-use super::secrets::{build_container_args, read_secrets};
+use super::output_spool::{CappedOutput, overflow_file_name};
+use super::output_stream::{LINE_BUFFER, OutputStreams, StreamLine};
+use super::pricing;
+use super::run_events::{self, RunEventKind, RunEventLog};
+use super::secrets::read_secrets;
 use super::security::MountAllowlist;
+use super::stdin_channel::{self, StdinChannels};
+use crate::health;
 
-/// Container runtime binary name.
-const CONTAINER_RUNTIME_BIN: &str = "docker";
-
-/// Maximum output buffer size (1 MiB) before truncation.
-const MAX_OUTPUT_SIZE: usize = 1_048_576;
+/// How long stderr may trail stdout once the container's output ends.
+const STDERR_DRAIN: Duration = Duration::from_millis(500);
 
 /// Default container timeout (5 minutes).
 const DEFAULT_TIMEOUT_MS: u64 = 300_000;
@@ -44,6 +53,66 @@ pub struct RunConfig {
     pub timezone: String,
     pub idle_timeout_ms: u64,
     pub allowlist: Option<MountAllowlist>,
+    /// Runtime profiles by name (stdin/stdout protocol, default model).
+    /// Runtimes without a profile use `marker-json`.
+    pub runtime_profiles: BTreeMap<String, RuntimeProfile>,
+    /// Git snapshots of the group folder around each run.
+    pub snapshots: SnapshotConfig,
+    /// Channel profiles, surfaced to runners in `context.json`.
+    pub channels: ChannelsConfig,
+    /// Proxy for groups with `network: "egress-allowlist"`.
+    pub egress: EgressProxyConfig,
+    /// Retention for per-run logs in each group's `logs/`.
+    pub logs: ContainerLogConfig,
+    /// Refuse images that do not match their runtime profile's pin.
+    pub verify_image_digests: bool,
+    /// Backend that starts, stops and cleans up agent containers.
+    pub executor: Arc<dyn ContainerExecutor>,
+    /// How long a timed-out container gets after SIGTERM before it is killed.
+    pub stop_timeout: Duration,
+    /// How long a container may go without any stdout after it starts
+    /// before it is stopped as failed to start. `None` disables the probe.
+    pub startup_timeout: Option<Duration>,
+    /// Where runs with `stdin_keepalive` publish their open stdin, shared
+    /// with the group queue.
+    pub stdin_channels: StdinChannels,
+    /// Group folder sizes; runs over the hard quota are refused.
+    pub disk_quotas: DiskQuotas,
+    /// Hard limit for this run, in place of the group's timeout and the
+    /// idle grace; set for scheduled tasks with their own `timeoutMs`.
+    pub run_timeout: Option<Duration>,
+}
+
+impl RunConfig {
+    pub fn protocol_for(&self, runtime: RuntimeKind) -> RuntimeProtocol {
+        self.runtime_profiles
+            .get(runtime.as_str())
+            .map(|profile| profile.protocol)
+            .unwrap_or_default()
+    }
+
+    /// Whether follow-ups go over the runtime's stdin. `plain-text` runners
+    /// take the raw prompt, so they always use IPC files.
+    pub fn stdin_keepalive(&self, runtime: RuntimeKind) -> bool {
+        self.runtime_profiles
+            .get(runtime.as_str())
+            .is_some_and(|profile| {
+                profile.stdin_keepalive && profile.protocol != RuntimeProtocol::PlainText
+            })
+    }
+
+    /// The group's model override, else the runtime profile's default.
+    pub fn resolve_model(&self, runtime: RuntimeKind, group_model: Option<&str>) -> Option<String> {
+        group_model
+            .filter(|m| !m.is_empty())
+            .map(str::to_string)
+            .or_else(|| {
+                self.runtime_profiles
+                    .get(runtime.as_str())
+                    .map(|profile| profile.default_model.clone())
+                    .filter(|m| !m.is_empty())
+            })
+    }
 }
 
 impl Default for RunConfig {
@@ -55,15 +124,34 @@ impl Default for RunConfig {
             timezone: "UTC".to_string(),
             idle_timeout_ms: DEFAULT_IDLE_TIMEOUT_MS,
             allowlist: None,
+            runtime_profiles: BTreeMap::new(),
+            snapshots: SnapshotConfig::default(),
+            channels: ChannelsConfig::default(),
+            egress: EgressProxyConfig::default(),
+            logs: ContainerLogConfig::default(),
+            verify_image_digests: false,
+            executor: Arc::new(CliExecutor::default()),
+            stop_timeout: Duration::from_secs(10),
+            startup_timeout: Some(Duration::from_secs(120)),
+            stdin_channels: StdinChannels::default(),
+            disk_quotas: DiskQuotas::default(),
+            run_timeout: None,
         }
     }
 }
 
 /// Result of a container run.
 pub struct RunResult {
+    /// Names the run's transcript (`/v1/runs/{id}/events`).
+    pub run_id: String,
     pub output: ContainerOutput,
-    pub container_name: String,
     pub duration: Duration,
+    /// How the container was stopped, if intercomd had to stop it.
+    pub stop: Option<StopOutcome>,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    /// Bytes read from the container's stdout, including any past the cap.
+    pub output_bytes: u64,
 }
 
 /// Callback for streaming container output as it arrives.
@@ -78,16 +166,24 @@ pub async fn run_container_agent(
     input: &ContainerInput,
     runtime: RuntimeKind,
     is_main: bool,
+    trigger: &str,
     config: &RunConfig,
     on_output: Option<Arc<OutputCallback>>,
 ) -> anyhow::Result<RunResult> {
     let start = Instant::now();
+    if let Some(reason) = config
+        .disk_quotas
+        .refusal(&group.folder, group.container_config.as_ref())
+    {
+        anyhow::bail!(reason);
+    }
 
     // Ensure group directory exists
     let group_dir = config.groups_dir.join(&group.folder);
     tokio::fs::create_dir_all(&group_dir).await.ok();
     let logs_dir = group_dir.join("logs");
     tokio::fs::create_dir_all(&logs_dir).await.ok();
+    let log_timestamp = chrono_timestamp();
 
     // Build mounts and container args
     let mounts = build_volume_mounts(
@@ -101,8 +197,30 @@ pub async fn run_container_agent(
     );
 
     let name = container_name(&group.folder);
-    let image = container_image(runtime);
-    let container_args = build_container_args(&mounts, &name, image, &config.timezone);
+    let run_id = name.clone();
+    let image = images::runtime_image(&config.runtime_profiles, runtime);
+    let protocol = config.protocol_for(runtime);
+    let limits = ResourceLimits::from_config(group.container_config.as_ref())
+        .map_err(|e| anyhow::anyhow!("Invalid resource limits for {}: {e}", group.name))?;
+    let network_mode = group
+        .container_config
+        .as_ref()
+        .and_then(|c| c.network)
+        .unwrap_or_default();
+    let spec = ContainerSpec {
+        name: &name,
+        group_folder: &group.folder,
+        runtime: runtime.as_str(),
+        trigger,
+        image: &image.image,
+        image_digest: image.digest.as_deref(),
+        verify_image: config.verify_image_digests,
+        timezone: &config.timezone,
+        mounts: &mounts,
+        limits: &limits,
+        network: network_mode,
+        egress: &config.egress,
+    };
 
     info!(
         group = %group.name,
@@ -110,28 +228,47 @@ pub async fn run_container_agent(
         mount_count = mounts.len(),
         is_main,
         runtime = runtime.as_str(),
+        protocol = protocol.as_str(),
+        network = ?network_mode,
+        executor = config.executor.kind(),
         "Spawning container agent"
     );
 
-    // Spawn the container process
-    let mut child = Command::new(CONTAINER_RUNTIME_BIN)
-        .args(&container_args)
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()
-        .map_err(|e| anyhow::anyhow!("Failed to spawn container: {}", e))?;
+    let AgentProcess {
+        name,
+        stdin,
+        stdout,
+        stderr,
+        exit,
+    } = config
+        .executor
+        .spawn(&spec)
+        .await
+        .map_err(|e| anyhow::anyhow!("Cannot start container for {}: {e:#}", group.name))?;
 
     // Write input + secrets to stdin
+    let keepalive = config.stdin_keepalive(runtime) && stdin.is_some();
     let mut stdin_input = input.clone();
     stdin_input.secrets = Some(read_secrets(&config.project_root));
-    let input_json = serde_json::to_string(&stdin_input)?;
+    stdin_input.stdin_keepalive = keepalive.then_some(true);
+    let mut input_json = protocol.encode_input(&stdin_input)?;
     // Zero secrets from our copy
     drop(stdin_input);
 
-    if let Some(mut stdin) = child.stdin.take() {
+    // With keep-alive the input is the first line and stdin stays open for
+    // follow-ups until the container's stdout ends
+    let mut stdin_forwarder = None;
+    if let Some(mut stdin) = stdin {
+        if keepalive && !input_json.ends_with('\n') {
+            input_json.push('\n');
+        }
         stdin.write_all(input_json.as_bytes()).await?;
-        stdin.shutdown().await.ok();
+        if keepalive {
+            let follow_ups = config.stdin_channels.open(&input.chat_jid);
+            stdin_forwarder = Some(tokio::spawn(stdin_channel::forward(stdin, follow_ups)));
+        } else {
+            stdin.shutdown().await.ok();
+        }
     }
 
     // Set up timeout management
@@ -142,60 +279,81 @@ pub async fn run_container_agent(
         .unwrap_or(DEFAULT_TIMEOUT_MS);
     // Grace period: hard timeout must be at least idle_timeout + 30s
     let timeout_ms = container_timeout.max(config.idle_timeout_ms + 30_000);
-    let timeout_duration = Duration::from_millis(timeout_ms);
+    let timeout_duration = config
+        .run_timeout
+        .unwrap_or(Duration::from_millis(timeout_ms));
 
     let (activity_tx, mut activity_rx) = watch::channel(Instant::now());
     let timed_out = Arc::new(Mutex::new(false));
+    let stop_outcome: Arc<Mutex<Option<StopOutcome>>> = Arc::new(Mutex::new(None));
     let had_streaming_output = Arc::new(Mutex::new(false));
     let new_session_id: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
 
     // Timeout watchdog task
     let timeout_name = name.clone();
     let timeout_flag = timed_out.clone();
-    let timeout_handle = tokio::spawn(async move {
-        loop {
-            let last_activity = *activity_rx.borrow();
-            let elapsed = last_activity.elapsed();
-            if elapsed >= timeout_duration {
-                *timeout_flag.lock().await = true;
-                error!(
-                    container_name = %timeout_name,
-                    "Container timeout, stopping"
-                );
-                // Graceful stop
-                let stop_result = Command::new(CONTAINER_RUNTIME_BIN)
-                    .args(["stop", &timeout_name])
-                    .output()
-                    .await;
-                if let Err(e) = stop_result {
-                    warn!(
+    let timeout_executor = config.executor.clone();
+    let timeout_outcome = stop_outcome.clone();
+    let stop_grace = config.stop_timeout;
+    let timeout_handle = tokio::spawn(
+        async move {
+            loop {
+                let last_activity = *activity_rx.borrow();
+                let elapsed = last_activity.elapsed();
+                if elapsed >= timeout_duration {
+                    *timeout_flag.lock().await = true;
+                    error!(
                         container_name = %timeout_name,
-                        error = %e,
-                        "Graceful stop failed"
+                        "Container timeout, stopping"
                     );
+                    // SIGTERM, then kill after the grace period
+                    let outcome = timeout_executor.stop(&timeout_name, stop_grace).await;
+                    *timeout_outcome.lock().await = Some(outcome);
+                    break;
+                }
+                let remaining = timeout_duration - elapsed;
+                tokio::select! {
+                    _ = tokio::time::sleep(remaining) => {}
+                    _ = activity_rx.changed() => {}
                 }
-                break;
-            }
-            let remaining = timeout_duration - elapsed;
-            tokio::select! {
-                _ = tokio::time::sleep(remaining) => {}
-                _ = activity_rx.changed() => {}
             }
         }
-    });
+        // Keep the run's span, so a timeout is logged with its request and correlation ids
+        .instrument(tracing::Span::current()),
+    );
+
+    // Stream stdout through the protocol decoder
+    let mut streams = OutputStreams::spawn(stdout, stderr, LINE_BUFFER);
+    let mut decoder = OutputDecoder::new(protocol);
+    let output_cap = group
+        .container_config
+        .as_ref()
+        .and_then(|c| c.max_output_bytes)
+        .unwrap_or(config.logs.max_output_bytes);
+    let spool_path = |stream: &str| {
+        config
+            .logs
+            .spool_overflow
+            .then(|| logs_dir.join(overflow_file_name(&log_timestamp, stream)))
+    };
+    let mut stdout_total = CappedOutput::new(output_cap, spool_path("stdout"));
 
-    // Stream stdout for OUTPUT markers
-    let stdout = child.stdout.take().unwrap();
-    let mut stdout_reader = BufReader::new(stdout);
-    let mut stdout_buf = String::new();
-    let mut stdout_total = String::new();
-    let mut stdout_truncated = false;
+    let mut stderr_total = CappedOutput::new(output_cap, spool_path("stderr"));
 
-    let stderr = child.stderr.take().unwrap();
-    let mut stderr_reader = BufReader::new(stderr);
-    let mut stderr_buf = String::new();
-    let mut stderr_total = String::new();
-    let mut stderr_truncated = false;
+    let mut events = match config.logs.run_events {
+        true => Some(RunEventLog::create(&config.data_dir.join("runs"), &run_id).await),
+        false => None,
+    };
+    if let Some(events) = events.as_mut() {
+        events
+            .record(RunEventKind::Start {
+                group: group.name.clone(),
+                runtime: runtime.as_str().to_string(),
+                correlation_id: input.correlation_id.clone(),
+            })
+            .await;
+    }
+    let decode = on_output.is_some() || events.is_some();
 
     // Process stdout and stderr concurrently
     let on_output_ref = on_output.clone();
@@ -203,118 +361,191 @@ pub async fn run_container_agent(
     let session_ref = new_session_id.clone();
     let activity_tx_ref = activity_tx.clone();
 
+    // Liveness probe: every bundled runner prints as soon as it is up, so a
+    // container with no stdout at all by the deadline never started.
+    // Plain-text runtimes only print their answer and are exempt.
+    let startup_timeout = config
+        .startup_timeout
+        .filter(|_| protocol != RuntimeProtocol::PlainText);
+    let startup_deadline = tokio::time::sleep(startup_timeout.unwrap_or(Duration::MAX));
+    tokio::pin!(startup_deadline);
+    let mut failed_to_start = false;
+    let mut run_usage: Option<TokenUsage> = None;
+
     loop {
         tokio::select! {
-            result = stdout_reader.read_line(&mut stdout_buf) => {
-                match result {
-                    Ok(0) => break, // EOF
-                    Ok(_) => {
-                        // Accumulate for logging
-                        if !stdout_truncated {
-                            let remaining = MAX_OUTPUT_SIZE - stdout_total.len();
-                            if stdout_buf.len() > remaining {
-                                stdout_total.push_str(&stdout_buf[..remaining]);
-                                stdout_truncated = true;
-                                warn!(group = %group.name, "Container stdout truncated");
-                            } else {
-                                stdout_total.push_str(&stdout_buf);
-                            }
-                        }
-
-                        // Parse OUTPUT markers
-                        if on_output_ref.is_some() {
-                            let (results, consumed) = extract_output_markers(&stdout_buf);
-                            if consumed > 0 {
-                                stdout_buf = stdout_buf[consumed..].to_string();
-                            }
-                            for json_str in results {
-                                match serde_json::from_str::<ContainerOutput>(&json_str) {
-                                    Ok(parsed) => {
-                                        if let Some(ref sid) = parsed.new_session_id {
-                                            *session_ref.lock().await = Some(sid.clone());
-                                        }
-                                        *had_output_ref.lock().await = true;
-                                        // Reset activity timer
-                                        activity_tx_ref.send(Instant::now()).ok();
-
-                                        if let Some(ref cb) = on_output_ref {
-                                            cb(parsed).await;
-                                        }
-                                    }
-                                    Err(e) => {
-                                        warn!(
-                                            group = %group.name,
-                                            error = %e,
-                                            "Failed to parse streamed output chunk"
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                        if consumed_none(&stdout_buf) {
-                            stdout_buf.clear();
-                        }
+            _ = &mut startup_deadline,
+                if startup_timeout.is_some() && !failed_to_start && stdout_total.total_bytes == 0 =>
+            {
+                // Keep reading: the stop ends stdout, and stderr may say why.
+                failed_to_start = true;
+                error!(
+                    group = %group.name,
+                    container_name = %name,
+                    startup_timeout_ms = startup_timeout.unwrap_or_default().as_millis(),
+                    "Container produced no output, stopping"
+                );
+                let outcome = config.executor.stop(&name, config.stop_timeout).await;
+                *stop_outcome.lock().await = Some(outcome);
+            }
+            line = streams.next() => match line {
+                Some(StreamLine::Stdout(line)) => {
+                    // Accumulate for logging
+                    if stdout_total.push(&line).await {
+                        warn!(
+                            group = %group.name,
+                            spool = ?stdout_total.spooled_to(),
+                            "Container stdout passed the output cap"
+                        );
                     }
-                    Err(e) => {
-                        warn!(group = %group.name, error = %e, "Error reading stdout");
-                        break;
+
+                    // Decode streamed outputs
+                    if decode {
+                        let decoded = decoder.push(&line);
+                        add_usage(&mut run_usage, &decoded);
+                        dispatch_outputs(
+                            decoded,
+                            &group.name,
+                            &session_ref,
+                            &had_output_ref,
+                            &activity_tx_ref,
+                            on_output_ref.as_ref(),
+                            events.as_mut(),
+                        )
+                        .await;
                     }
                 }
-            }
-            result = stderr_reader.read_line(&mut stderr_buf) => {
-                match result {
-                    Ok(0) => {} // stderr EOF, keep reading stdout
-                    Ok(_) => {
-                        let line = stderr_buf.trim();
-                        if !line.is_empty() {
-                            debug!(container = %group.folder, "{}", line);
-                        }
-                        if !stderr_truncated {
-                            let remaining = MAX_OUTPUT_SIZE - stderr_total.len();
-                            if stderr_buf.len() > remaining {
-                                stderr_total.push_str(&stderr_buf[..remaining]);
-                                stderr_truncated = true;
-                                warn!(group = %group.name, "Container stderr truncated");
-                            } else {
-                                stderr_total.push_str(&stderr_buf);
-                            }
-                        }
-                        stderr_buf.clear();
+                Some(StreamLine::Stderr(line)) => record_stderr(group, &mut stderr_total, &line).await,
+                Some(StreamLine::StdoutClosed(None)) => {
+                    // EOF: flush protocols that only emit on exit
+                    if decode {
+                        let flushed = decoder.finish();
+                        add_usage(&mut run_usage, &flushed);
+                        dispatch_outputs(
+                            flushed,
+                            &group.name,
+                            &session_ref,
+                            &had_output_ref,
+                            &activity_tx_ref,
+                            on_output_ref.as_ref(),
+                            events.as_mut(),
+                        )
+                        .await;
                     }
-                    Err(_) => {} // stderr error, non-fatal
+                    break;
                 }
-            }
+                Some(StreamLine::StdoutClosed(Some(e))) => {
+                    warn!(group = %group.name, error = %e, "Error reading stdout");
+                    break;
+                }
+                None => break,
+            },
         }
     }
+    // Stderr usually ends with stdout; keep what it still has to say
+    for line in streams.drain_stderr(STDERR_DRAIN).await {
+        record_stderr(group, &mut stderr_total, &line).await;
+    }
+    drop(streams);
+
+    stdout_total.finish().await;
+    stderr_total.finish().await;
+
+    if let Some(forwarder) = stdin_forwarder {
+        forwarder.abort();
+        forwarder.await.ok();
+        config.stdin_channels.release(&input.chat_jid);
+    }
 
     // Wait for process exit
-    let status = child.wait().await?;
+    let exit_code = exit.await?;
     let duration = start.elapsed();
 
-    // Cancel timeout watchdog
-    timeout_handle.abort();
-
+    // Cancel timeout watchdog, or let a stop it started finish
     let was_timed_out = *timed_out.lock().await;
+    if was_timed_out {
+        timeout_handle.await.ok();
+    } else {
+        timeout_handle.abort();
+    }
+    let stop = *stop_outcome.lock().await;
     let had_output = *had_streaming_output.lock().await;
     let session_id = new_session_id.lock().await.clone();
-    let exit_code = status.code();
+    let stdout_bytes = stdout_total.total_bytes;
+    let finish = move |mut output: ContainerOutput| {
+        output.usage = output.usage.or(run_usage);
+        RunResult {
+            run_id,
+            output,
+            duration,
+            stop,
+            exit_code,
+            timed_out: was_timed_out,
+            output_bytes: stdout_bytes,
+        }
+    };
 
     // Write container log
     write_container_log(
         &logs_dir,
-        &group.name,
-        &name,
+        &LogHeader {
+            timestamp: &log_timestamp,
+            group_name: &group.name,
+            container_name: &name,
+            correlation_id: input.correlation_id.as_deref(),
+        },
         duration,
         exit_code,
         was_timed_out,
         had_output,
         &mounts,
         &stdout_total,
-        stdout_truncated,
         &stderr_total,
-        stderr_truncated,
     )
     .await;
+    if let Some(events) = events.as_mut() {
+        events
+            .record(RunEventKind::Exit {
+                exit_code,
+                timed_out: was_timed_out,
+                stop,
+                duration_ms: duration.as_millis() as u64,
+            })
+            .await;
+    }
+    if let Some(events) = events {
+        events.finish().await;
+    }
+    let retention = config.logs.clone();
+    let runs_dir = config.data_dir.join("runs");
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = log_retention::prune(&logs_dir, &retention) {
+            warn!(logs_dir = %logs_dir.display(), error = %e, "Failed to prune container logs");
+        }
+        if let Err(e) = run_events::prune(&runs_dir, retention.max_age_days) {
+            warn!(runs_dir = %runs_dir.display(), error = %e, "Failed to prune run transcripts");
+        }
+    });
+
+    // Liveness probe fired: the runtime never came up
+    if failed_to_start {
+        let tail = stderr_total.text.trim();
+        let tail = &tail[tail.char_indices().rev().nth(199).map_or(0, |(i, _)| i)..];
+        let window_ms = startup_timeout.unwrap_or_default().as_millis();
+        return Ok(finish(ContainerOutput {
+            status: ContainerStatus::Error,
+            result: None,
+            new_session_id: None,
+            error: Some(if tail.is_empty() {
+                format!("Runtime failed to start: no output within {window_ms}ms")
+            } else {
+                format!("Runtime failed to start: no output within {window_ms}ms: {tail}")
+            }),
+            model: None,
+            event: None,
+            usage: None,
+        }));
+    }
 
     // Handle timeout cases
     if was_timed_out {
@@ -323,71 +554,72 @@ pub async fn run_container_agent(
                 group = %group.name,
                 container_name = %name,
                 duration_ms = duration.as_millis(),
+                stop = stop.map(StopOutcome::as_str),
                 "Container timed out after output (idle cleanup)"
             );
-            return Ok(RunResult {
-                output: ContainerOutput {
-                    status: ContainerStatus::Success,
-                    result: None,
-                    new_session_id: session_id,
-                    error: None,
-                    model: None,
-                    event: None,
-                },
-                container_name: name,
-                duration,
-            });
+            return Ok(finish(ContainerOutput {
+                status: ContainerStatus::Success,
+                result: None,
+                new_session_id: session_id,
+                error: None,
+                model: None,
+                event: None,
+                usage: None,
+            }));
         }
 
         error!(
             group = %group.name,
             container_name = %name,
             duration_ms = duration.as_millis(),
+            stop = stop.map(StopOutcome::as_str),
             "Container timed out with no output"
         );
-        return Ok(RunResult {
-            output: ContainerOutput {
-                status: ContainerStatus::Error,
-                result: None,
-                new_session_id: None,
-                error: Some(format!("Container timed out after {}ms", container_timeout)),
-                model: None,
-                event: None,
-            },
-            container_name: name,
-            duration,
-        });
+        return Ok(finish(ContainerOutput {
+            status: ContainerStatus::Error,
+            result: None,
+            new_session_id: None,
+            error: Some(match stop {
+                Some(StopOutcome::Killed) => format!(
+                    "Container timed out after {}ms and was killed after ignoring SIGTERM for {}s",
+                    container_timeout,
+                    config.stop_timeout.as_secs()
+                ),
+                _ => format!("Container timed out after {}ms", container_timeout),
+            }),
+            model: None,
+            event: None,
+            usage: None,
+        }));
     }
 
     // Handle error exit
-    if !status.success() {
+    if exit_code != Some(0) {
         error!(
             group = %group.name,
             exit_code = ?exit_code,
             duration_ms = duration.as_millis(),
             "Container exited with error"
         );
-        let tail = if stderr_total.len() > 200 {
-            &stderr_total[stderr_total.len() - 200..]
+        let stderr_text = &stderr_total.text;
+        let tail = if stderr_text.len() > 200 {
+            &stderr_text[stderr_text.len() - 200..]
         } else {
-            &stderr_total
+            stderr_text
         };
-        return Ok(RunResult {
-            output: ContainerOutput {
-                status: ContainerStatus::Error,
-                result: None,
-                new_session_id: None,
-                error: Some(format!(
-                    "Container exited with code {}: {}",
-                    exit_code.unwrap_or(-1),
-                    tail
-                )),
-                model: None,
-                event: None,
-            },
-            container_name: name,
-            duration,
-        });
+        return Ok(finish(ContainerOutput {
+            status: ContainerStatus::Error,
+            result: None,
+            new_session_id: None,
+            error: Some(format!(
+                "Container exited with code {}: {}",
+                exit_code.unwrap_or(-1),
+                tail
+            )),
+            model: None,
+            event: None,
+            usage: None,
+        }));
     }
 
     // Streaming mode: output was already dispatched via callbacks
@@ -397,36 +629,34 @@ pub async fn run_container_agent(
             duration_ms = duration.as_millis(),
             "Container completed (streaming mode)"
         );
-        return Ok(RunResult {
-            output: ContainerOutput {
-                status: ContainerStatus::Success,
-                result: None,
-                new_session_id: session_id,
-                error: None,
-                model: None,
-                event: None,
-            },
-            container_name: name,
-            duration,
-        });
+        return Ok(finish(ContainerOutput {
+            status: ContainerStatus::Success,
+            result: None,
+            new_session_id: session_id,
+            error: None,
+            model: None,
+            event: None,
+            usage: None,
+        }));
     }
 
-    // Legacy mode: parse the last output marker pair from accumulated stdout
-    let (results, _) = extract_output_markers(&stdout_total);
-    if let Some(last_json) = results.last() {
-        match serde_json::from_str::<ContainerOutput>(last_json) {
-            Ok(output) => {
+    // Legacy mode: decode accumulated stdout and keep the last output
+    let mut decoder = OutputDecoder::new(protocol);
+    let mut decoded = decoder.push(&stdout_total.text);
+    decoded.extend(decoder.finish());
+    let mut usage = None;
+    add_usage(&mut usage, &decoded);
+    if let Some(last) = decoded.pop() {
+        match last {
+            Ok(mut output) => {
+                output.usage = usage;
                 info!(
                     group = %group.name,
                     duration_ms = duration.as_millis(),
                     status = ?output.status,
                     "Container completed"
                 );
-                Ok(RunResult {
-                    output,
-                    container_name: name,
-                    duration,
-                })
+                Ok(finish(output))
             }
             Err(e) => {
                 error!(
@@ -434,71 +664,204 @@ pub async fn run_container_agent(
                     error = %e,
                     "Failed to parse container output"
                 );
-                Ok(RunResult {
-                    output: ContainerOutput {
-                        status: ContainerStatus::Error,
-                        result: None,
-                        new_session_id: None,
-                        error: Some(format!("Failed to parse container output: {}", e)),
-                        model: None,
-                        event: None,
-                    },
-                    container_name: name,
-                    duration,
-                })
-            }
-        }
-    } else {
-        // Fallback: try parsing last non-empty line
-        let last_line = stdout_total.trim().lines().last().unwrap_or("");
-        match serde_json::from_str::<ContainerOutput>(last_line) {
-            Ok(output) => Ok(RunResult {
-                output,
-                container_name: name,
-                duration,
-            }),
-            Err(e) => Ok(RunResult {
-                output: ContainerOutput {
+                Ok(finish(ContainerOutput {
                     status: ContainerStatus::Error,
                     result: None,
                     new_session_id: None,
-                    error: Some(format!(
-                        "No OUTPUT markers found and failed to parse last line: {}",
-                        e
-                    )),
+                    error: Some(format!("Failed to parse container output: {}", e)),
                     model: None,
                     event: None,
-                },
-                container_name: name,
-                duration,
-            }),
+                    usage: None,
+                }))
+            }
+        }
+    } else {
+        // Fallback: try parsing last non-empty line
+        let last_line = stdout_total.text.trim().lines().last().unwrap_or("");
+        match serde_json::from_str::<ContainerOutput>(last_line) {
+            Ok(output) => Ok(finish(output)),
+            Err(e) => Ok(finish(ContainerOutput {
+                status: ContainerStatus::Error,
+                result: None,
+                new_session_id: None,
+                error: Some(format!(
+                    "No OUTPUT markers found and failed to parse last line: {}",
+                    e
+                )),
+                model: None,
+                event: None,
+                usage: None,
+            })),
         }
     }
 }
 
-/// Helper: check if the buffer contains no OUTPUT markers (nothing was consumed).
-fn consumed_none(buf: &str) -> bool {
-    !buf.contains(intercom_core::OUTPUT_START_MARKER)
+/// Record a run in `container_runs`. `trigger` is `message`, `task` or
+/// `replay`; a run that failed before its container started is recorded
+/// as an error without a run id. Storage failures are only logged.
+pub async fn record_run(
+    pool: &SharedStorage,
+    trigger: &str,
+    input: &ContainerInput,
+    started_at: chrono::DateTime<chrono::Utc>,
+    result: &anyhow::Result<RunResult>,
+) {
+    let finished_at = chrono::Utc::now();
+    let mut run = ContainerRun {
+        group_folder: input.group_folder.clone(),
+        chat_jid: input.chat_jid.clone(),
+        trigger: trigger.to_string(),
+        started_at: started_at.to_rfc3339(),
+        finished_at: finished_at.to_rfc3339(),
+        duration_ms: (finished_at - started_at).num_milliseconds(),
+        status: "error".into(),
+        model: input.model.clone(),
+        session_id: input.session_id.clone(),
+        correlation_id: input.correlation_id.clone(),
+        ..ContainerRun::default()
+    };
+    match result {
+        Ok(r) => {
+            run.run_id = Some(r.run_id.clone());
+            if r.stop == Some(StopOutcome::Failed) {
+                // It may still be running, holding a slot's worth of resources
+                health::record_error(
+                    health::SUBSYSTEM_DOCKER,
+                    format!("could not stop container {}", r.run_id),
+                );
+            }
+            run.duration_ms = r.duration.as_millis() as i64;
+            run.exit_code = r.exit_code;
+            run.timed_out = r.timed_out;
+            run.output_bytes = r.output_bytes as i64;
+            run.error = r.output.error.clone();
+            if r.output.status == ContainerStatus::Success {
+                run.status = "success".into();
+            }
+            if r.output.model.is_some() {
+                run.model = r.output.model.clone();
+            }
+            if r.output.new_session_id.is_some() {
+                run.session_id = r.output.new_session_id.clone();
+            }
+            if let Some(usage) = &r.output.usage {
+                run.input_tokens = usage.input_tokens as i64;
+                run.output_tokens = usage.output_tokens as i64;
+                run.cache_read_tokens = usage.cache_read_tokens as i64;
+                run.cache_write_tokens = usage.cache_write_tokens as i64;
+                run.cost_usd = pricing::cost_usd(run.model.as_deref(), usage);
+            }
+        }
+        Err(e) => run.error = Some(e.to_string()),
+    }
+    if let Err(e) = pool.record_container_run(&run).await {
+        warn!(group = %run.group_folder, err = %e, "Failed to record container run");
+    }
 }
 
+/// Log a container stderr line and keep it for the run log.
+async fn record_stderr(group: &GroupInfo, stderr: &mut CappedOutput, line: &str) {
+    let trimmed = line.trim();
+    if !trimmed.is_empty() {
+        debug!(container = %group.folder, "{}", trimmed);
+    }
+    if stderr.push(line).await {
+        warn!(
+            group = %group.name,
+            spool = ?stderr.spooled_to(),
+            "Container stderr passed the output cap"
+        );
+    }
+}
+
+/// Add the token usage of decoded outputs to the run's total.
+fn add_usage(total: &mut Option<TokenUsage>, decoded: &[serde_json::Result<ContainerOutput>]) {
+    for usage in decoded
+        .iter()
+        .filter_map(|output| output.as_ref().ok()?.usage.as_ref())
+    {
+        match total {
+            Some(total) => total.add(usage),
+            None => *total = Some(*usage),
+        }
+    }
+}
+
+/// Hand decoded outputs to the streaming callback, tracking session id and
+/// activity along the way.
+async fn dispatch_outputs(
+    decoded: Vec<serde_json::Result<ContainerOutput>>,
+    group_name: &str,
+    session: &Mutex<Option<String>>,
+    had_output: &Mutex<bool>,
+    activity_tx: &watch::Sender<Instant>,
+    on_output: Option<&Arc<OutputCallback>>,
+    mut events: Option<&mut RunEventLog>,
+) {
+    for item in decoded {
+        match item {
+            Ok(parsed) => {
+                if let Some(events) = events.as_deref_mut() {
+                    events
+                        .record(RunEventKind::Output {
+                            output: parsed.clone(),
+                        })
+                        .await;
+                }
+                // Without a callback, outputs are only decoded for the
+                // transcript; the final result comes from the full stdout.
+                if on_output.is_none() {
+                    continue;
+                }
+                if let Some(ref sid) = parsed.new_session_id {
+                    *session.lock().await = Some(sid.clone());
+                }
+                *had_output.lock().await = true;
+                // Reset activity timer
+                activity_tx.send(Instant::now()).ok();
+
+                if let Some(cb) = on_output {
+                    cb(parsed).await;
+                }
+            }
+            Err(e) => {
+                warn!(
+                    group = %group_name,
+                    error = %e,
+                    "Failed to parse streamed output chunk"
+                );
+            }
+        }
+    }
+}
+
+/// Identifies the run at the top of its container log.
+struct LogHeader<'a> {
+    /// Names the log file and any overflow files.
+    timestamp: &'a str,
+    group_name: &'a str,
+    container_name: &'a str,
+    correlation_id: Option<&'a str>,
+}
+
+/// Line in the container log header naming the run's correlation id.
+pub(crate) const CORRELATION_LOG_PREFIX: &str = "Correlation ID: ";
+
 /// Write a container run log to the logs directory.
 async fn write_container_log(
     logs_dir: &Path,
-    group_name: &str,
-    container_name: &str,
+    header: &LogHeader<'_>,
     duration: Duration,
     exit_code: Option<i32>,
     timed_out: bool,
     had_output: bool,
     mounts: &[VolumeMount],
-    stdout: &str,
-    stdout_truncated: bool,
-    stderr: &str,
-    stderr_truncated: bool,
+    stdout: &CappedOutput,
+    stderr: &CappedOutput,
 ) {
-    let timestamp = chrono_timestamp();
-    let log_file = logs_dir.join(format!("container-{}.log", timestamp));
+    let timestamp = header.timestamp;
     let is_error = exit_code.unwrap_or(0) != 0 || timed_out;
+    let log_file = logs_dir.join(log_retention::log_file_name(timestamp, is_error));
 
     let mut lines = vec![
         format!(
@@ -506,13 +869,27 @@ async fn write_container_log(
             if timed_out { " (TIMEOUT)" } else { "" }
         ),
         format!("Timestamp: {}", timestamp),
-        format!("Group: {}", group_name),
-        format!("Container: {}", container_name),
+        format!("Group: {}", header.group_name),
+        format!("Container: {}", header.container_name),
+    ];
+    if let Some(id) = header.correlation_id {
+        lines.push(format!("{CORRELATION_LOG_PREFIX}{id}"));
+    }
+    lines.extend([
         format!("Duration: {}ms", duration.as_millis()),
         format!("Exit Code: {:?}", exit_code),
         format!("Had Streaming Output: {}", had_output),
-        String::new(),
-    ];
+    ]);
+    for (label, output) in [("Stdout", stdout), ("Stderr", stderr)] {
+        if let Some(path) = output.spooled_to() {
+            lines.push(format!(
+                "{label} Overflow: {} ({} bytes)",
+                path.display(),
+                output.total_bytes
+            ));
+        }
+    }
+    lines.push(String::new());
 
     if is_error {
         lines.push("=== Mounts ===".to_string());
@@ -527,15 +904,15 @@ async fn write_container_log(
         lines.push(String::new());
         lines.push(format!(
             "=== Stderr{} ===",
-            if stderr_truncated { " (TRUNCATED)" } else { "" }
+            if stderr.truncated { " (TRUNCATED)" } else { "" }
         ));
-        lines.push(stderr.to_string());
+        lines.push(stderr.text.clone());
         lines.push(String::new());
         lines.push(format!(
             "=== Stdout{} ===",
-            if stdout_truncated { " (TRUNCATED)" } else { "" }
+            if stdout.truncated { " (TRUNCATED)" } else { "" }
         ));
-        lines.push(stdout.to_string());
+        lines.push(stdout.text.clone());
     } else {
         lines.push("=== Mounts ===".to_string());
         for m in mounts {
@@ -573,7 +950,7 @@ fn chrono_timestamp() -> String {
 pub async fn write_snapshots(
     data_dir: &Path,
     group_folder: &str,
-    is_main: bool,
+    _is_main: bool,
     tasks_json: &str,
     groups_json: &str,
 ) {
@@ -588,99 +965,385 @@ pub async fn write_snapshots(
     }
 }
 
-/// Stop a container by name (graceful docker stop).
-pub async fn stop_container(container_name: &str) -> bool {
-    match Command::new(CONTAINER_RUNTIME_BIN)
-        .args(["stop", container_name])
-        .output()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chrono_timestamp_format() {
+        let ts = chrono_timestamp();
+        // Should be "{seconds}-{millis}" format
+        assert!(ts.contains('-'));
+        let parts: Vec<&str> = ts.split('-').collect();
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0].parse::<u64>().is_ok());
+        assert!(parts[1].parse::<u32>().is_ok());
+    }
+
+    #[test]
+    fn marker_decoder_keeps_partial_pairs_only() {
+        let mut decoder = OutputDecoder::new(RuntimeProtocol::MarkerJson);
+        assert!(decoder.push("just some output\n").is_empty());
+        assert!(
+            decoder
+                .push(&format!("{}\n", intercom_core::OUTPUT_START_MARKER))
+                .is_empty()
+        );
+        let out = decoder.push(&format!(
+            "{{\"status\":\"success\",\"result\":\"ok\"}}{}",
+            intercom_core::OUTPUT_END_MARKER
+        ));
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].as_ref().unwrap().result.as_deref(), Some("ok"));
+    }
+
+    #[test]
+    fn usage_sums_over_a_runs_turns() {
+        let mut decoder = OutputDecoder::new(RuntimeProtocol::MarkerJson);
+        let turn = |usage: &str| {
+            format!(
+                "{}\n{{\"status\":\"success\",\"result\":\"ok\",\"usage\":{usage}}}\n{}\n",
+                intercom_core::OUTPUT_START_MARKER,
+                intercom_core::OUTPUT_END_MARKER
+            )
+        };
+        let mut total = None;
+        add_usage(
+            &mut total,
+            &decoder.push(&turn(
+                r#"{"inputTokens":100,"outputTokens":20,"costUsd":0.5}"#,
+            )),
+        );
+        add_usage(
+            &mut total,
+            &decoder.push("{\"status\":\"success\",\"result\":null}\n"),
+        );
+        assert_eq!(total.unwrap().cost_usd, Some(0.5));
+
+        add_usage(
+            &mut total,
+            &decoder.push(&turn(
+                r#"{"inputTokens":5,"outputTokens":1,"cacheReadTokens":40}"#,
+            )),
+        );
+        let total = total.unwrap();
+        assert_eq!(
+            (
+                total.input_tokens,
+                total.output_tokens,
+                total.cache_read_tokens
+            ),
+            (105, 21, 40)
+        );
+        // One turn without a cost leaves the run's cost to be estimated.
+        assert_eq!(total.cost_usd, None);
+    }
+
+    #[test]
+    fn protocol_for_falls_back_to_marker_json() {
+        let mut config = RunConfig::default();
+        let mut codex = intercom_core::IntercomConfig::default().runtimes.profiles["codex"].clone();
+        codex.protocol = RuntimeProtocol::JsonlStream;
+        config.runtime_profiles.insert("codex".into(), codex);
+        assert_eq!(
+            config.protocol_for(RuntimeKind::Codex),
+            RuntimeProtocol::JsonlStream
+        );
+        assert_eq!(
+            config.protocol_for(RuntimeKind::Claude),
+            RuntimeProtocol::MarkerJson
+        );
+        assert_eq!(
+            config.resolve_model(RuntimeKind::Codex, None).as_deref(),
+            Some("gpt-5.3-codex")
+        );
+        assert_eq!(
+            config
+                .resolve_model(RuntimeKind::Codex, Some("o3-pro"))
+                .as_deref(),
+            Some("o3-pro")
+        );
+        assert_eq!(config.resolve_model(RuntimeKind::Claude, None), None);
+    }
+
+    fn fake_run(
+        dir: &std::path::Path,
+        executor: Arc<super::super::executor::FakeExecutor>,
+    ) -> (GroupInfo, ContainerInput, RunConfig) {
+        let group = GroupInfo {
+            folder: "team".into(),
+            name: "Team".into(),
+            container_config: None,
+        };
+        let input = ContainerInput {
+            prompt: "hello agent".into(),
+            session_id: None,
+            group_folder: "team".into(),
+            chat_jid: "tg:1".into(),
+            is_main: false,
+            is_scheduled_task: None,
+            assistant_name: None,
+            model: None,
+            secrets: None,
+            attachments: vec![],
+            correlation_id: None,
+            reply_to_message_id: None,
+            stdin_keepalive: None,
+        };
+        let config = RunConfig {
+            project_root: dir.to_path_buf(),
+            groups_dir: dir.join("groups"),
+            data_dir: dir.join("data"),
+            executor,
+            ..RunConfig::default()
+        };
+        (group, input, config)
+    }
+
+    #[tokio::test]
+    async fn runs_through_a_fake_executor() {
+        let dir = tempfile::tempdir().unwrap();
+        let stdout = format!(
+            "booting\n{}\n{{\"status\":\"success\",\"result\":\"hi there\"}}\n{}\n",
+            intercom_core::OUTPUT_START_MARKER,
+            intercom_core::OUTPUT_END_MARKER
+        );
+        let fake = Arc::new(super::super::executor::FakeExecutor::new(&stdout, 0));
+        let (group, input, config) = fake_run(dir.path(), fake.clone());
+
+        let result = run_container_agent(
+            &group,
+            &input,
+            RuntimeKind::Claude,
+            false,
+            "message",
+            &config,
+            None,
+        )
         .await
-    {
-        Ok(output) if output.status.success() => {
-            info!(container_name, "Container stopped");
-            true
-        }
-        Ok(output) => {
-            warn!(
-                container_name,
-                stderr = String::from_utf8_lossy(&output.stderr).as_ref(),
-                "Failed to stop container"
-            );
-            false
-        }
-        Err(e) => {
-            warn!(container_name, error = %e, "Failed to execute docker stop");
-            false
-        }
+        .unwrap();
+        assert!(matches!(result.output.status, ContainerStatus::Success));
+        assert_eq!(result.output.result.as_deref(), Some("hi there"));
+        assert!(result.run_id.starts_with("intercom-team-"));
+        assert_eq!(
+            fake.spawned.lock().unwrap().as_slice(),
+            std::slice::from_ref(&result.run_id)
+        );
+        assert!(fake.last_stdin().await.contains("hello agent"));
+
+        let events = run_events::read_events(&dir.path().join("data/runs"), &result.run_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(events[0].kind, RunEventKind::Start { .. }));
+        assert!(
+            matches!(&events[1].kind, RunEventKind::Output { output } if output.result.as_deref() == Some("hi there"))
+        );
+        assert!(matches!(
+            events[2].kind,
+            RunEventKind::Exit {
+                exit_code: Some(0),
+                ..
+            }
+        ));
     }
-}
 
-/// Check if the container runtime is available.
-pub async fn ensure_runtime_available() -> anyhow::Result<()> {
-    let output = Command::new(CONTAINER_RUNTIME_BIN)
-        .args(["info"])
-        .output()
+    #[tokio::test]
+    async fn keepalive_leaves_stdin_open_for_follow_ups() {
+        let dir = tempfile::tempdir().unwrap();
+        let fake = Arc::new(super::super::executor::FakeExecutor::new("", 0));
+        let (group, input, mut config) = fake_run(dir.path(), fake.clone());
+        let profile = RuntimeProfile {
+            stdin_keepalive: true,
+            ..RuntimeProfile::default()
+        };
+        config.runtime_profiles.insert("claude".into(), profile);
+
+        run_container_agent(
+            &group,
+            &input,
+            RuntimeKind::Claude,
+            false,
+            "message",
+            &config,
+            None,
+        )
         .await
-        .map_err(|e| anyhow::anyhow!("Container runtime not found: {}", e))?;
+        .unwrap();
+        let stdin = fake.last_stdin().await;
+        assert!(stdin.ends_with("\n"));
+        assert!(stdin.contains(r#""stdinKeepalive":true"#));
+        // Released once the run ended: follow-ups fall back to IPC files.
+        assert!(
+            !config
+                .stdin_channels
+                .send("tg:1", super::super::stdin_channel::FollowUp::Close)
+        );
 
-    if !output.status.success() {
-        anyhow::bail!("Container runtime is not running. Ensure Docker is installed and started.");
+        config.runtime_profiles.get_mut("claude").unwrap().protocol = RuntimeProtocol::PlainText;
+        assert!(!config.stdin_keepalive(RuntimeKind::Claude));
     }
 
-    debug!("Container runtime available");
-    Ok(())
-}
+    #[tokio::test]
+    async fn output_past_the_group_cap_is_spooled() {
+        let dir = tempfile::tempdir().unwrap();
+        let stdout = "0123456789\n".repeat(4);
+        let fake = Arc::new(super::super::executor::FakeExecutor::new(&stdout, 0));
+        let (mut group, input, config) = fake_run(dir.path(), fake);
+        group.container_config = Some(super::super::security::ContainerConfig {
+            max_output_bytes: Some(16),
+            ..Default::default()
+        });
 
-/// Kill orphaned intercom containers from previous runs.
-pub async fn cleanup_orphans() {
-    let output = match Command::new(CONTAINER_RUNTIME_BIN)
-        .args(["ps", "--filter", "name=intercom-", "--format", "{{.Names}}"])
-        .output()
+        let result = run_container_agent(
+            &group,
+            &input,
+            RuntimeKind::Claude,
+            false,
+            "message",
+            &config,
+            None,
+        )
         .await
-    {
-        Ok(o) => o,
-        Err(e) => {
-            warn!(error = %e, "Failed to list orphaned containers");
-            return;
-        }
-    };
+        .unwrap();
+        assert_eq!(result.output_bytes, 44);
 
-    let names: Vec<&str> = std::str::from_utf8(&output.stdout)
-        .unwrap_or("")
-        .trim()
-        .split('\n')
-        .filter(|s| !s.is_empty())
-        .collect();
-
-    for name in &names {
-        let _ = Command::new(CONTAINER_RUNTIME_BIN)
-            .args(["stop", name])
-            .output()
-            .await;
+        let logs_dir = dir.path().join("groups/team/logs");
+        let file = |suffix: &str| {
+            std::fs::read_dir(&logs_dir)
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .find(|p| p.to_string_lossy().ends_with(suffix))
+                .unwrap()
+        };
+        let overflow = file(".stdout.overflow");
+        assert_eq!(std::fs::read_to_string(&overflow).unwrap(), stdout);
+        let log = std::fs::read_to_string(file(".log")).unwrap();
+        assert!(log.contains(&format!(
+            "Stdout Overflow: {} (44 bytes)",
+            overflow.display()
+        )));
     }
 
-    if !names.is_empty() {
-        info!(count = names.len(), "Stopped orphaned containers");
+    #[tokio::test]
+    async fn silent_container_fails_to_start() {
+        let dir = tempfile::tempdir().unwrap();
+        let fake = Arc::new(super::super::executor::FakeExecutor::silent(143));
+        let (group, input, mut config) = fake_run(dir.path(), fake.clone());
+        config.startup_timeout = Some(Duration::from_millis(50));
+
+        let result = run_container_agent(
+            &group,
+            &input,
+            RuntimeKind::Claude,
+            false,
+            "message",
+            &config,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(result.output.status, ContainerStatus::Error));
+        assert_eq!(
+            result.output.error.as_deref(),
+            Some("Runtime failed to start: no output within 50ms")
+        );
+        assert!(!result.timed_out);
+        assert_eq!(result.stop, Some(StopOutcome::Stopped));
+        assert_eq!(
+            fake.stopped.lock().unwrap().as_slice(),
+            std::slice::from_ref(&result.run_id)
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn run_timeout_replaces_the_group_timeout_and_idle_grace() {
+        let dir = tempfile::tempdir().unwrap();
+        let fake = Arc::new(super::super::executor::FakeExecutor::silent(143));
+        let (group, input, mut config) = fake_run(dir.path(), fake.clone());
+        config.startup_timeout = None;
+        config.run_timeout = Some(Duration::from_millis(50));
 
-    #[test]
-    fn chrono_timestamp_format() {
-        let ts = chrono_timestamp();
-        // Should be "{seconds}-{millis}" format
-        assert!(ts.contains('-'));
-        let parts: Vec<&str> = ts.split('-').collect();
-        assert_eq!(parts.len(), 2);
-        assert!(parts[0].parse::<u64>().is_ok());
-        assert!(parts[1].parse::<u32>().is_ok());
+        let started = Instant::now();
+        let result = run_container_agent(
+            &group,
+            &input,
+            RuntimeKind::Claude,
+            true,
+            "task",
+            &config,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(result.timed_out);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            fake.stopped.lock().unwrap().as_slice(),
+            std::slice::from_ref(&result.run_id)
+        );
     }
 
-    #[test]
-    fn consumed_none_detects_no_markers() {
-        assert!(consumed_none("just some output"));
-        assert!(!consumed_none(&format!("prefix{}suffix", intercom_core::OUTPUT_START_MARKER)));
+    #[tokio::test]
+    async fn streams_every_output_under_a_stderr_flood() {
+        let dir = tempfile::tempdir().unwrap();
+        let stdout: String = (0..1_000)
+            .map(|i| {
+                format!(
+                    "{}\n{{\"status\":\"success\",\"result\":\"{i}\"}}\n{}\n",
+                    intercom_core::OUTPUT_START_MARKER,
+                    intercom_core::OUTPUT_END_MARKER
+                )
+            })
+            .collect();
+        let mut fake = super::super::executor::FakeExecutor::new(&stdout, 0);
+        fake.stderr = format!("{}\n", "e".repeat(100)).repeat(20_000);
+        let (group, input, config) = fake_run(dir.path(), Arc::new(fake));
+
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen_cb = seen.clone();
+        let on_output: Arc<OutputCallback> = Arc::new(Box::new(move |output: ContainerOutput| {
+            seen_cb
+                .lock()
+                .unwrap()
+                .push(output.result.unwrap_or_default());
+            Box::pin(async {})
+        }));
+        let result = run_container_agent(
+            &group,
+            &input,
+            RuntimeKind::Claude,
+            false,
+            "message",
+            &config,
+            Some(on_output),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(result.output.status, ContainerStatus::Success));
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1_000);
+        assert!(seen.iter().enumerate().all(|(i, r)| *r == i.to_string()));
+    }
+
+    #[tokio::test]
+    async fn nonzero_exit_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let fake = Arc::new(super::super::executor::FakeExecutor::new("", 137));
+        let (group, input, config) = fake_run(dir.path(), fake);
+
+        let result = run_container_agent(
+            &group,
+            &input,
+            RuntimeKind::Claude,
+            false,
+            "message",
+            &config,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(result.output.status, ContainerStatus::Error));
+        assert!(result.output.error.unwrap_or_default().contains("137"));
     }
 }
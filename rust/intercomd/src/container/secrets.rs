@@ -8,6 +8,9 @@ use std::path::Path;
 
 use tracing::debug;
 
+use super::engine::ContainerCli;
+use super::limits::ResourceLimits;
+
 /// Secret key names for each runtime.
 const SECRET_KEYS: &[&str] = &[
     // Claude
@@ -105,11 +108,16 @@ pub fn read_secrets(project_root: &Path) -> HashMap<String, String> {
     secrets
 }
 
-/// Build the Docker CLI args for running a container.
+/// Build the container CLI args for running a container.
 ///
-/// Constructs `docker run -i --rm --name {name} -e TZ=... --user ... -v ... {image}`.
+/// Constructs `run -i --rm --name {name} --label ... -e TZ=... --user ... --cpus ... --network ... -v ... {image}`
+/// in `cli`'s dialect.
 pub fn build_container_args(
+    cli: &ContainerCli,
+    limits: &ResourceLimits,
+    network: &[String],
     mounts: &[intercom_core::VolumeMount],
+    labels: &[(&str, &str)],
     container_name: &str,
     image: &str,
     timezone: &str,
@@ -121,24 +129,18 @@ pub fn build_container_args(
         "--name".to_string(),
         container_name.to_string(),
     ];
+    args.extend(cli.label_args(labels));
 
     // Pass host timezone
     args.push("-e".to_string());
     args.push(format!("TZ={}", timezone));
 
     // Run as host user so bind-mounted files are accessible.
-    // Skip when running as root (uid 0) or the container's node user (uid 1000).
     #[cfg(unix)]
-    {
-        let uid = nix_uid();
-        let gid = nix_gid();
-        if uid != 0 && uid != 1000 {
-            args.push("--user".to_string());
-            args.push(format!("{}:{}", uid, gid));
-            args.push("-e".to_string());
-            args.push("HOME=/home/node".to_string());
-        }
-    }
+    args.extend(cli.user_args(nix_uid(), nix_gid()));
+
+    args.extend(limits.args());
+    args.extend_from_slice(network);
 
     for mount in mounts {
         if mount.readonly {
@@ -242,16 +244,31 @@ mod tests {
             },
         ];
 
-        let args = build_container_args(&mounts, "test-container", "intercom-agent:latest", "UTC");
+        let args = build_container_args(
+            &ContainerCli::new(intercom_core::ContainerEngine::Docker, None),
+            &ResourceLimits {
+                pids_limit: Some(64),
+                ..ResourceLimits::default()
+            },
+            &["--network=none".to_string()],
+            &mounts,
+            &[("intercom.group", "team")],
+            "test-container",
+            "intercom-agent:latest",
+            "UTC",
+        );
 
         assert!(args.contains(&"-i".to_string()));
         assert!(args.contains(&"--rm".to_string()));
         assert!(args.contains(&"--name".to_string()));
         assert!(args.contains(&"test-container".to_string()));
         assert!(args.contains(&"TZ=UTC".to_string()));
+        assert!(args.contains(&"intercom.group=team".to_string()));
         assert!(args.contains(&"/home/mk/project:/workspace/project:ro".to_string()));
         assert!(args.contains(&"/home/mk/data:/workspace/group".to_string()));
         assert!(args.contains(&"type=tmpfs,destination=/workspace/project/node_modules,tmpfs-size=0".to_string()));
+        assert!(args.contains(&"--pids-limit=64".to_string()));
+        assert!(args.contains(&"--network=none".to_string()));
         assert!(args.last() == Some(&"intercom-agent:latest".to_string()));
     }
 }
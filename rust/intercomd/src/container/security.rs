@@ -10,7 +10,10 @@ use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
 
+use super::host::{expand_home, home_dir};
+
 /// Default blocked patterns — paths that should never be mounted.
+#[allow(dead_code)] // allowlist loading still lives in the Node host
 const DEFAULT_BLOCKED_PATTERNS: &[&str] = &[
     ".ssh",
     ".gnupg",
@@ -78,6 +81,54 @@ pub struct ContainerConfig {
     #[serde(default)]
     pub additional_mounts: Vec<AdditionalMount>,
     pub timeout: Option<u64>,
+    /// Outbound lifecycle webhook scoped to this group.
+    #[serde(default)]
+    pub webhook: Option<crate::webhooks::GroupWebhook>,
+    /// Reported to runners in `context.json`.
+    #[serde(default)]
+    pub quiet_hours: Option<super::context::QuietHours>,
+    /// Environment variable holding this group's own Telegram bot token.
+    /// Must start with [`crate::telegram::BOT_TOKEN_ENV_PREFIX`].
+    #[serde(default)]
+    pub bot_token_env: Option<String>,
+    /// Callback for `hook:` chats: agent replies are POSTed to its url, and
+    /// frontend posts are verified with its secret.
+    #[serde(default)]
+    pub channel_webhook: Option<crate::webhooks::GroupWebhook>,
+    /// CPU cap in cores, e.g. `1.5` (`--cpus`).
+    #[serde(default)]
+    pub cpus: Option<f64>,
+    /// Memory cap with an optional `k`/`m`/`g` suffix, e.g. `"2g"` (`--memory`).
+    #[serde(default)]
+    pub memory: Option<String>,
+    /// Process cap inside the container (`--pids-limit`).
+    #[serde(default)]
+    pub pids_limit: Option<u32>,
+    /// `full` (default), `none` or `egress-allowlist`.
+    #[serde(default)]
+    pub network: Option<super::network::NetworkMode>,
+    /// Overrides `[container.logs] max_output_bytes` for this group.
+    #[serde(default)]
+    pub max_output_bytes: Option<usize>,
+    /// Override `[container.quota] soft_mb` / `hard_mb`; 0 disables.
+    #[serde(default)]
+    pub disk_soft_mb: Option<u64>,
+    #[serde(default)]
+    pub disk_hard_mb: Option<u64>,
+    /// Containers the group may run at once; past the first, only
+    /// scheduled tasks. Default 1.
+    #[serde(default)]
+    pub max_concurrent: Option<usize>,
+    /// Runs the group may start per hour; 0 or unset is unlimited.
+    #[serde(default)]
+    pub max_runs_per_hour: Option<u32>,
+    /// iCal feed whose events become one-shot tasks for the group.
+    #[serde(default)]
+    pub calendar: Option<crate::calendar::CalendarFeed>,
+    /// Group folders whose agents may leave mail in this group's inbox;
+    /// `*` admits every group. The main group may always.
+    #[serde(default)]
+    pub accept_mail_from: Vec<String>,
 }
 
 /// Result of validating a single mount.
@@ -100,12 +151,13 @@ pub struct ValidatedMount {
 }
 
 /// Default allowlist path.
+#[allow(dead_code)] // allowlist loading still lives in the Node host
 pub fn default_allowlist_path() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| "/root".to_string());
-    PathBuf::from(home).join(".config/intercom/mount-allowlist.json")
+    home_dir().join(".config/intercom/mount-allowlist.json")
 }
 
 /// Load the mount allowlist from the external config location.
+#[allow(dead_code)] // allowlist loading still lives in the Node host
 pub fn load_allowlist(path: &Path) -> Option<MountAllowlist> {
     if !path.exists() {
         warn!(
@@ -163,11 +215,8 @@ pub fn load_allowlist(path: &Path) -> Option<MountAllowlist> {
 
 /// Expand `~` to home directory and resolve to absolute path.
 fn expand_path(p: &str) -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| "/root".to_string());
-    if p == "~" {
-        PathBuf::from(&home)
-    } else if let Some(rest) = p.strip_prefix("~/") {
-        PathBuf::from(&home).join(rest)
+    if p == "~" || p.starts_with("~/") {
+        expand_home(p, &home_dir())
     } else {
         PathBuf::from(p).canonicalize().unwrap_or_else(|_| PathBuf::from(p))
     }
@@ -0,0 +1,118 @@
+//! Follow-up input over a container's open stdin.
+//!
+//! With a runtime profile's `stdin_keepalive`, the runner writes the
+//! `ContainerInput` as one line and leaves stdin open. Follow-ups the queue
+//! would otherwise drop into `ipc/{folder}/input/` are sent through
+//! [`StdinChannels`] and written as JSON lines: `{"type":"message",...}`,
+//! the same document as an IPC input file, or `{"type":"close"}` in place
+//! of the `_close` sentinel. Groups without an open channel keep using the
+//! files.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+use super::executor::AgentStdin;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FollowUp {
+    Message {
+        text: String,
+        #[serde(rename = "correlationId", skip_serializing_if = "Option::is_none")]
+        correlation_id: Option<String>,
+    },
+    Close,
+}
+
+/// Open stdin channels of running containers, by chat JID.
+#[derive(Clone, Default)]
+pub struct StdinChannels {
+    senders: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<FollowUp>>>>,
+}
+
+impl StdinChannels {
+    /// Register a run's stdin for `chat_jid`, replacing any earlier one.
+    pub fn open(&self, chat_jid: &str) -> mpsc::UnboundedReceiver<FollowUp> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.senders
+            .lock()
+            .unwrap()
+            .insert(chat_jid.to_string(), tx);
+        rx
+    }
+
+    /// Forget `chat_jid`'s channel once its receiver is gone. A channel
+    /// opened since by a newer run is left alone.
+    pub fn release(&self, chat_jid: &str) {
+        let mut senders = self.senders.lock().unwrap();
+        if senders.get(chat_jid).is_some_and(|tx| tx.is_closed()) {
+            senders.remove(chat_jid);
+        }
+    }
+
+    /// Send to the container's stdin. `false` when it has none open, so the
+    /// caller falls back to IPC files.
+    pub fn send(&self, chat_jid: &str, follow_up: FollowUp) -> bool {
+        let senders = self.senders.lock().unwrap();
+        senders
+            .get(chat_jid)
+            .is_some_and(|tx| tx.send(follow_up).is_ok())
+    }
+}
+
+/// Write follow-ups to `stdin` until a close, the channel is dropped, or
+/// the container stops reading. Stdin is closed on the way out.
+pub async fn forward(mut stdin: AgentStdin, mut follow_ups: mpsc::UnboundedReceiver<FollowUp>) {
+    while let Some(follow_up) = follow_ups.recv().await {
+        let mut line = serde_json::to_vec(&follow_up).unwrap_or_default();
+        line.push(b'\n');
+        if stdin.write_all(&line).await.is_err() || stdin.flush().await.is_err() {
+            break;
+        }
+        if follow_up == FollowUp::Close {
+            break;
+        }
+    }
+    stdin.shutdown().await.ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn follow_ups_stream_as_jsonl_until_close() {
+        let channels = StdinChannels::default();
+        assert!(!channels.send("tg:1", FollowUp::Close));
+
+        let (stdin, mut reader) = tokio::io::duplex(1024);
+        let rx = channels.open("tg:1");
+        let task = tokio::spawn(forward(Box::new(stdin), rx));
+        assert!(channels.send(
+            "tg:1",
+            FollowUp::Message {
+                text: "also this".into(),
+                correlation_id: Some("c2".into())
+            }
+        ));
+        assert!(channels.send("tg:1", FollowUp::Close));
+        task.await.unwrap();
+
+        let mut written = String::new();
+        reader.read_to_string(&mut written).await.unwrap();
+        assert_eq!(
+            written,
+            "{\"type\":\"message\",\"text\":\"also this\",\"correlationId\":\"c2\"}\n{\"type\":\"close\"}\n"
+        );
+
+        // The forwarder is gone: later follow-ups fall back to files.
+        assert!(!channels.send("tg:1", FollowUp::Close));
+        channels.release("tg:1");
+        assert!(channels.senders.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,327 @@
+//! Opt-in warm container pool.
+//!
+//! Engine start-up plus the agent runtime's boot dominate the latency of
+//! short replies. With `[container.warm_pool] enabled = true`, once a run
+//! finishes intercomd starts the next container for the same group right
+//! away: same image, mounts and flags, stdin open and waiting for input.
+//! The next run with identical arguments claims it and only has to write
+//! its input. Runs are one-shot (`--rm`), so a claimed container is never
+//! reused; returning it to the pool means starting its replacement.
+//!
+//! Containers are keyed by their full `run` arguments minus the name and
+//! the per-run labels, so a change to mounts, limits, network or image
+//! never hands out a stale container. A warm container is labelled with its
+//! own name as run id and `warm` as trigger. A container idle for longer
+//! than `ttl_secs` is stopped.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use intercom_core::WarmPoolConfig;
+use serde::Serialize;
+use tokio::process::Child;
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use super::engine::{LABEL_RUN_ID, LABEL_TRIGGER};
+use super::executor::{spawn_cli, stop_cli};
+use super::mounts::container_name;
+
+/// An idle container has no work to lose; don't wait long for it.
+const RECYCLE_STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// A started container waiting for its input on stdin.
+pub struct WarmContainer {
+    pub name: String,
+    pub child: Child,
+    started: Instant,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WarmPoolStats {
+    pub idle: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Containers stopped unused after their TTL (or found dead).
+    pub recycled: u64,
+    /// Share of runs that claimed a warm container.
+    pub hit_rate: f64,
+}
+
+pub struct WarmPool {
+    ttl: Duration,
+    max_idle: usize,
+    idle: Mutex<HashMap<String, WarmContainer>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    recycled: AtomicU64,
+}
+
+/// The value of a per-run label (or annotation) argument.
+fn per_run_label(arg: &str) -> Option<&'static str> {
+    [LABEL_RUN_ID, LABEL_TRIGGER].into_iter().find(|label| {
+        arg.strip_prefix(label)
+            .is_some_and(|rest| rest.starts_with('='))
+    })
+}
+
+/// Pool key: the `run` arguments with the container name and per-run
+/// labels left out.
+fn pool_key(args: &[String]) -> String {
+    let mut key = Vec::with_capacity(args.len());
+    let mut skip_next = false;
+    for arg in args {
+        if std::mem::take(&mut skip_next) {
+            continue;
+        }
+        if arg == "--name" {
+            skip_next = true;
+            continue;
+        }
+        key.push(per_run_label(arg).unwrap_or(arg));
+    }
+    key.join("\0")
+}
+
+/// `args` with the `--name` value replaced and the per-run labels set for
+/// a warm container of that name.
+fn with_name(args: &[String], name: &str) -> Vec<String> {
+    let mut renamed = args.to_vec();
+    if let Some(i) = renamed.iter().position(|arg| arg == "--name") {
+        if let Some(value) = renamed.get_mut(i + 1) {
+            *value = name.to_string();
+        }
+    }
+    for arg in &mut renamed {
+        match per_run_label(arg) {
+            Some(LABEL_RUN_ID) => *arg = format!("{LABEL_RUN_ID}={name}"),
+            Some(_) => *arg = format!("{LABEL_TRIGGER}=warm"),
+            None => {}
+        }
+    }
+    renamed
+}
+
+/// Folder part of an `intercom-{folder}-{millis}` container name.
+fn folder_of(name: &str) -> &str {
+    let rest = name
+        .strip_prefix(super::engine::CONTAINER_NAME_PREFIX)
+        .unwrap_or(name);
+    rest.rsplit_once('-').map_or(rest, |(folder, _)| folder)
+}
+
+impl WarmPool {
+    pub fn new(config: &WarmPoolConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_secs.max(1)),
+            max_idle: config.max_idle,
+            idle: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            recycled: AtomicU64::new(0),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, WarmContainer>> {
+        self.idle.lock().expect("warm pool lock poisoned")
+    }
+
+    /// Take the idle container started with `args`, if one is still fresh
+    /// and running. Counts a hit or a miss.
+    pub async fn claim(&self, args: &[String]) -> Option<WarmContainer> {
+        let entry = self.lock().remove(&pool_key(args));
+        let usable = match entry {
+            Some(mut warm) => {
+                let alive = matches!(warm.child.try_wait(), Ok(None));
+                if alive && warm.started.elapsed() < self.ttl {
+                    Some(warm)
+                } else {
+                    self.recycle(warm).await;
+                    None
+                }
+            }
+            None => None,
+        };
+        let counter = if usable.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        usable
+    }
+
+    /// Start a container with `args` (under a fresh name) for the next run,
+    /// unless one is already waiting or the pool is full.
+    pub fn refill(self: &Arc<Self>, args: &[String]) {
+        let key = pool_key(args);
+        {
+            let idle = self.lock();
+            if idle.contains_key(&key) || idle.len() >= self.max_idle {
+                return;
+            }
+        }
+        let Some(old_name) = args.iter().skip_while(|a| *a != "--name").nth(1) else {
+            return;
+        };
+        let name = container_name(folder_of(old_name));
+        let args = with_name(args, &name);
+        let pool = Arc::clone(self);
+        tokio::spawn(async move {
+            let child = match spawn_cli(&args) {
+                Ok(child) => child,
+                Err(e) => {
+                    warn!(container_name = %name, err = %e, "failed to start warm container");
+                    return;
+                }
+            };
+            let warm = WarmContainer {
+                name,
+                child,
+                started: Instant::now(),
+            };
+            debug!(container_name = %warm.name, "warm container started");
+            let displaced = pool.lock().insert(key, warm);
+            if let Some(old) = displaced {
+                pool.recycle(old).await;
+            }
+        });
+    }
+
+    async fn recycle(&self, mut warm: WarmContainer) {
+        self.recycled.fetch_add(1, Ordering::Relaxed);
+        if matches!(warm.child.try_wait(), Ok(None)) {
+            stop_cli(&warm.name, RECYCLE_STOP_GRACE).await;
+        }
+        warm.child.start_kill().ok();
+        warm.child.wait().await.ok();
+    }
+
+    /// Stop containers idle for longer than the TTL.
+    pub async fn reap_expired(&self) {
+        let expired: Vec<WarmContainer> = {
+            let mut idle = self.lock();
+            let keys: Vec<String> = idle
+                .iter()
+                .filter(|(_, warm)| warm.started.elapsed() >= self.ttl)
+                .map(|(key, _)| key.clone())
+                .collect();
+            keys.iter().filter_map(|key| idle.remove(key)).collect()
+        };
+        for warm in expired {
+            debug!(container_name = %warm.name, "warm container expired");
+            self.recycle(warm).await;
+        }
+    }
+
+    /// Stop every idle container (shutdown).
+    pub async fn drain(&self) {
+        let all: Vec<WarmContainer> = self.lock().drain().map(|(_, warm)| warm).collect();
+        for warm in all {
+            self.recycle(warm).await;
+        }
+    }
+
+    pub fn stats(&self) -> WarmPoolStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let total = hits + misses;
+        WarmPoolStats {
+            idle: self.lock().len(),
+            hits,
+            misses,
+            recycled: self.recycled.load(Ordering::Relaxed),
+            hit_rate: if total == 0 {
+                0.0
+            } else {
+                hits as f64 / total as f64
+            },
+        }
+    }
+}
+
+/// Recycle expired containers until shutdown, then stop the rest.
+pub async fn run_reaper(pool: Arc<WarmPool>, mut shutdown: watch::Receiver<bool>) {
+    let mut ticker = tokio::time::interval((pool.ttl / 4).max(Duration::from_secs(1)));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => pool.reap_expired().await,
+            _ = shutdown.changed() => break,
+        }
+    }
+    let stats = pool.stats();
+    pool.drain().await;
+    info!(
+        hits = stats.hits,
+        misses = stats.misses,
+        "warm pool drained"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labelled(name: &str, trigger: &str, image: &str) -> Vec<String> {
+        let run_id = format!("intercom.run_id={name}");
+        let trigger = format!("intercom.trigger={trigger}");
+        [
+            "run", "-i", "--rm", "--name", name, "--label", &run_id, "--label", &trigger, "-e",
+            "TZ=UTC", image,
+        ]
+        .map(str::to_string)
+        .to_vec()
+    }
+
+    fn args(name: &str, image: &str) -> Vec<String> {
+        labelled(name, "message", image)
+    }
+
+    #[test]
+    fn key_ignores_only_the_name_and_run_labels() {
+        let a = args("intercom-team-1", "intercom-agent:latest");
+        let b = labelled("intercom-team-2", "task", "intercom-agent:latest");
+        let c = args("intercom-team-3", "intercom-agent-codex:latest");
+        assert_eq!(pool_key(&a), pool_key(&b));
+        assert_ne!(pool_key(&a), pool_key(&c));
+    }
+
+    #[test]
+    fn rename_relabels_and_keeps_other_args() {
+        let renamed = with_name(&args("intercom-team-1", "img"), "intercom-team-9");
+        assert_eq!(renamed, labelled("intercom-team-9", "warm", "img"));
+        assert_eq!(folder_of("intercom-dev-team-1719000000000"), "dev-team");
+    }
+
+    #[tokio::test]
+    async fn claims_count_hits_and_misses() {
+        let pool = WarmPool::new(&WarmPoolConfig {
+            enabled: true,
+            ..WarmPoolConfig::default()
+        });
+        assert!(pool.claim(&args("intercom-team-1", "img")).await.is_none());
+
+        let child = tokio::process::Command::new("sleep")
+            .arg("30")
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        pool.lock().insert(
+            pool_key(&args("intercom-team-1", "img")),
+            WarmContainer {
+                name: "intercom-team-2".into(),
+                child,
+                started: Instant::now(),
+            },
+        );
+        let warm = pool.claim(&args("intercom-team-3", "img")).await.unwrap();
+        assert_eq!(warm.name, "intercom-team-2");
+
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.misses, stats.idle), (1, 1, 0));
+        assert_eq!(stats.hit_rate, 0.5);
+    }
+}
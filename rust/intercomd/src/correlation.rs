@@ -0,0 +1,182 @@
+//! Look up everything recorded under a correlation id
+//! (`POST /v1/admin/correlation`).
+//!
+//! Ingress assigns each inbound message a correlation id. The message loop
+//! tags the run it triggers with the newest id in the batch: the
+//! `ContainerInput`, the container log header, lifecycle webhooks, the stored
+//! reply and the `process_group` tracing span all carry it. Scheduled task
+//! runs get a fresh id recorded in `task_run_logs`, and replays use their
+//! replay id. This gathers the stored side of that trail in one response.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use intercom_core::{NewMessage, RegisteredGroup, SharedStorage, TaskRunLog};
+use serde::{Deserialize, Serialize};
+
+use crate::container::log_retention;
+use crate::container::runner::CORRELATION_LOG_PREFIX;
+
+/// Header lines read from each container log when searching for an id.
+const LOG_HEADER_LINES: usize = 8;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CorrelationRequest {
+    pub correlation_id: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CorrelationResponse {
+    pub ok: bool,
+    pub correlation_id: String,
+    /// Inbound messages and stored replies, oldest first.
+    pub messages: Vec<NewMessage>,
+    pub task_runs: Vec<TaskRunLog>,
+    /// Container logs whose header names the id, relative to the groups dir.
+    pub container_logs: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl CorrelationResponse {
+    pub fn from_error(err: impl Into<String>) -> Self {
+        Self {
+            error: Some(err.into()),
+            ..Default::default()
+        }
+    }
+}
+
+pub async fn lookup(
+    correlation_id: &str,
+    pool: &SharedStorage,
+    groups: &HashMap<String, RegisteredGroup>,
+    groups_dir: &Path,
+) -> anyhow::Result<CorrelationResponse> {
+    let correlation_id = correlation_id.trim();
+    if correlation_id.is_empty() {
+        anyhow::bail!("correlation_id is required");
+    }
+    let trace = pool.get_correlation_trace(correlation_id).await?;
+
+    // Only the folders of groups the trail touched are searched for logs.
+    let mut folders = BTreeSet::new();
+    for msg in &trace.messages {
+        if let Some(group) = groups.get(&msg.chat_jid) {
+            folders.insert(group.folder.clone());
+        }
+    }
+    for run in &trace.task_runs {
+        if let Some(task) = pool.get_task_by_id(&run.task_id).await? {
+            folders.insert(task.group_folder);
+        }
+    }
+    if correlation_id.starts_with("replay-") {
+        folders.extend(groups.values().map(|g| g.folder.clone()));
+    }
+
+    let groups_dir = groups_dir.to_path_buf();
+    let id = correlation_id.to_string();
+    let container_logs = tokio::task::spawn_blocking(move || {
+        find_container_logs(&groups_dir, folders.iter().map(String::as_str), &id)
+    })
+    .await?;
+
+    Ok(CorrelationResponse {
+        ok: true,
+        correlation_id: trace.correlation_id,
+        messages: trace.messages,
+        task_runs: trace.task_runs,
+        container_logs,
+        error: None,
+    })
+}
+
+/// Container logs under `{groups_dir}/{folder}/logs/` whose header carries
+/// `correlation_id`, sorted by path.
+fn find_container_logs<'a>(
+    groups_dir: &Path,
+    folders: impl Iterator<Item = &'a str>,
+    correlation_id: &str,
+) -> Vec<String> {
+    let needle = format!("{CORRELATION_LOG_PREFIX}{correlation_id}");
+    let mut found: Vec<PathBuf> = Vec::new();
+    for folder in folders {
+        let Ok(entries) = std::fs::read_dir(groups_dir.join(folder).join("logs")) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !log_retention::is_run_log(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            let Ok(file) = std::fs::File::open(&path) else {
+                continue;
+            };
+            // Older logs are gzipped by retention.
+            let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "gz") {
+                Box::new(GzDecoder::new(file))
+            } else {
+                Box::new(file)
+            };
+            let matches = BufReader::new(reader)
+                .lines()
+                .take(LOG_HEADER_LINES)
+                .map_while(Result::ok)
+                .any(|line| line == needle);
+            if matches {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    found
+        .iter()
+        .map(|p| {
+            p.strip_prefix(groups_dir)
+                .unwrap_or(p)
+                .display()
+                .to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn container_logs_match_on_header_line_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let logs = tmp.path().join("team/logs");
+        std::fs::create_dir_all(&logs).unwrap();
+        std::fs::write(
+            logs.join("container-1.log"),
+            "=== Container Run Log ===\nGroup: Team\nCorrelation ID: abc\nDuration: 5ms\n",
+        )
+        .unwrap();
+        std::fs::write(
+            logs.join("container-2.log"),
+            "=== Container Run Log ===\nCorrelation ID: abcd\n",
+        )
+        .unwrap();
+        std::fs::write(logs.join("notes.txt"), "Correlation ID: abc\n").unwrap();
+        let mut gz = flate2::write::GzEncoder::new(
+            std::fs::File::create(logs.join("container-0.error.log.gz")).unwrap(),
+            flate2::Compression::default(),
+        );
+        std::io::Write::write_all(&mut gz, b"=== Container Run Log ===\nCorrelation ID: abc\n")
+            .unwrap();
+        gz.finish().unwrap();
+
+        let found = find_container_logs(tmp.path(), ["team", "missing"].into_iter(), "abc");
+        assert_eq!(
+            found,
+            [
+                "team/logs/container-0.error.log.gz",
+                "team/logs/container-1.log"
+            ]
+        );
+    }
+}
@@ -2,18 +2,22 @@
 //!
 //! These endpoints let the Node host dual-write to Postgres through
 //! intercomd during the migration period. Once Node is retired, the
-//! Rust message loop will call PgPool directly.
+//! Rust message loop will call the storage backend directly.
+
+use std::sync::Arc;
 
-use axum::extract::State;
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
 use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
 use intercom_core::persistence::{
-    ChatInfo, NewMessage, RegisteredGroup, ScheduledTask, TaskRunLog, TaskUpdate,
+    Attachment, MessageFilter, NewMessage, QueryResult, RegisteredGroup, ScheduledTask, SortOrder,
+    TaskFilter, TaskRunLog, TaskUpdate,
 };
-use intercom_core::PgPool;
+use intercom_core::{NamedQuery, QueryConfig, SharedStorage, new_correlation_id};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+
+use crate::event_stream::{self, LiveEvent, LiveEventKind};
 
 /// Wrapper for error responses from the DB endpoints.
 #[derive(Serialize)]
@@ -28,17 +32,157 @@ fn db_error(msg: String) -> (StatusCode, Json<DbError>) {
     )
 }
 
-fn require_pool(pool: &Option<PgPool>) -> Result<&PgPool, (StatusCode, Json<DbError>)> {
+fn require_pool(
+    pool: &Option<SharedStorage>,
+) -> Result<&SharedStorage, (StatusCode, Json<DbError>)> {
     pool.as_ref().ok_or_else(|| {
         (
             StatusCode::SERVICE_UNAVAILABLE,
             Json(DbError {
-                error: "postgres not configured".to_string(),
+                error: "storage not configured".to_string(),
             }),
         )
     })
 }
 
+// ---------------------------------------------------------------------------
+// Paging
+// ---------------------------------------------------------------------------
+
+/// Most rows one message or task listing returns. `?limit=` defaults to
+/// this and is clamped to it.
+pub const MAX_PAGE: i64 = 500;
+
+/// Set on a listing that has more rows; pass it back as `?cursor=`.
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+#[derive(Deserialize, Default)]
+pub struct MessagePageQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    pub sender: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct TaskPageQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    pub status: Option<String>,
+    pub group_folder: Option<String>,
+    pub chat_jid: Option<String>,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+fn page_limit(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(MAX_PAGE).clamp(1, MAX_PAGE)
+}
+
+/// A cursor is the sort key of the last row returned, as hex-encoded JSON.
+/// Callers treat it as opaque.
+fn encode_cursor(key: &[&str]) -> String {
+    hex::encode(serde_json::to_vec(key).expect("strings serialize"))
+}
+
+/// Decode a cursor whose key is a timestamp followed by `N - 1` ids.
+fn decode_cursor<const N: usize>(cursor: &str) -> Result<[String; N], (StatusCode, Json<DbError>)> {
+    hex::decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Vec<String>>(&bytes).ok())
+        .and_then(|key| <[String; N]>::try_from(key).ok())
+        .filter(|key| chrono::DateTime::parse_from_rfc3339(&key[0]).is_ok())
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(DbError {
+                    error: "invalid cursor".to_string(),
+                }),
+            )
+        })
+}
+
+/// Cut a fetch of `limit + 1` rows down to `limit`, returning the cursor
+/// past the last row kept when there were more.
+fn split_page<T>(rows: &mut Vec<T>, limit: i64, key: impl Fn(&T) -> String) -> Option<String> {
+    if rows.len() as i64 <= limit {
+        return None;
+    }
+    rows.truncate(limit as usize);
+    rows.last().map(key)
+}
+
+fn paged<T: Serialize>(body: T, next_cursor: Option<String>) -> Response {
+    let mut response = (StatusCode::OK, Json(body)).into_response();
+    if let Some(value) = next_cursor.and_then(|c| HeaderValue::from_str(&c).ok()) {
+        response.headers_mut().insert(NEXT_CURSOR_HEADER, value);
+    }
+    response
+}
+
+fn message_cursor(m: &NewMessage) -> String {
+    encode_cursor(&[&m.timestamp, &m.chat_jid, &m.id])
+}
+
+fn task_cursor(t: &ScheduledTask) -> String {
+    encode_cursor(&[&t.created_at, &t.id])
+}
+
+/// One page of user messages in `chat_jids` after `since`.
+async fn message_page(
+    pool: &SharedStorage,
+    chat_jids: Vec<String>,
+    since: &str,
+    bot_prefix: &str,
+    page: MessagePageQuery,
+) -> Result<(Vec<NewMessage>, Option<String>), (StatusCode, Json<DbError>)> {
+    let after = match page.cursor.as_deref() {
+        Some(cursor) => Some(decode_cursor::<3>(cursor)?.into()),
+        None => None,
+    };
+    let limit = page_limit(page.limit);
+    let filter = MessageFilter {
+        chat_jids,
+        since: since.to_string(),
+        bot_prefix: bot_prefix.to_string(),
+        sender: page.sender,
+        after,
+        limit: limit + 1,
+    };
+    let mut messages = pool
+        .list_messages(&filter)
+        .await
+        .map_err(|e| db_error(e.to_string()))?;
+    let next = split_page(&mut messages, limit, message_cursor);
+    Ok((messages, next))
+}
+
+/// One page of tasks; `group_folder` wins over the query's.
+async fn task_page(
+    pool: &SharedStorage,
+    group_folder: Option<String>,
+    page: TaskPageQuery,
+) -> Result<(Vec<ScheduledTask>, Option<String>), (StatusCode, Json<DbError>)> {
+    let after = match page.cursor.as_deref() {
+        Some(cursor) => Some(decode_cursor::<2>(cursor)?.into()),
+        None => None,
+    };
+    let limit = page_limit(page.limit);
+    let filter = TaskFilter {
+        group_folder: group_folder.or(page.group_folder),
+        chat_jid: page.chat_jid,
+        status: page.status,
+        order: page.order,
+        after,
+        limit: limit + 1,
+    };
+    let mut tasks = pool
+        .list_tasks(&filter)
+        .await
+        .map_err(|e| db_error(e.to_string()))?;
+    let next = split_page(&mut tasks, limit, task_cursor);
+    Ok((tasks, next))
+}
+
 // ---------------------------------------------------------------------------
 // Chat endpoints
 // ---------------------------------------------------------------------------
@@ -53,7 +197,7 @@ pub struct StoreChatMetadataRequest {
 }
 
 pub async fn store_chat_metadata(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<StoreChatMetadataRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -82,7 +226,7 @@ pub struct UpdateChatNameRequest {
 }
 
 pub async fn update_chat_name(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<UpdateChatNameRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -95,7 +239,7 @@ pub async fn update_chat_name(
     }
 }
 
-pub async fn get_all_chats(State(pool): State<Option<PgPool>>) -> impl IntoResponse {
+pub async fn get_all_chats(State(pool): State<Option<SharedStorage>>) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
         Ok(p) => p,
         Err(e) => return e.into_response(),
@@ -106,20 +250,58 @@ pub async fn get_all_chats(State(pool): State<Option<PgPool>>) -> impl IntoRespo
     }
 }
 
+#[derive(Deserialize)]
+pub struct ChatStatsRequest {
+    pub chat_jid: String,
+}
+
+pub async fn get_chat_stats(
+    State(pool): State<Option<SharedStorage>>,
+    Json(req): Json<ChatStatsRequest>,
+) -> impl IntoResponse {
+    let pool = match require_pool(&pool) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    match pool.get_chat_stats(&req.chat_jid).await {
+        Ok(stats) => (StatusCode::OK, Json(stats)).into_response(),
+        Err(e) => db_error(e.to_string()).into_response(),
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Message endpoints
 // ---------------------------------------------------------------------------
 
+/// Host ingress: inbound messages without a correlation id are assigned one,
+/// returned so the caller can log it.
 pub async fn store_message(
-    State(pool): State<Option<PgPool>>,
-    Json(msg): Json<NewMessage>,
+    State(pool): State<Option<SharedStorage>>,
+    Json(mut msg): Json<NewMessage>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
         Ok(p) => p,
         Err(e) => return e.into_response(),
     };
+    if msg.correlation_id.is_none() && !msg.is_bot_message {
+        msg.correlation_id = Some(new_correlation_id());
+    }
     match pool.store_message(&msg).await {
-        Ok(()) => (StatusCode::OK, Json(serde_json::json!({"ok": true}))).into_response(),
+        Ok(()) => {
+            if !msg.is_bot_message {
+                crate::request_id::note_message(&msg.chat_jid);
+                event_stream::publish(
+                    LiveEvent::new(LiveEventKind::MessageIngested, &msg.chat_jid)
+                        .with_message(&msg.id)
+                        .with_correlation_id(msg.correlation_id.as_deref()),
+                );
+            }
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({"ok": true, "correlation_id": msg.correlation_id})),
+            )
+                .into_response()
+        }
         Err(e) => db_error(e.to_string()).into_response(),
     }
 }
@@ -137,24 +319,87 @@ pub struct GetNewMessagesResponse {
     pub new_timestamp: String,
 }
 
+/// `new_timestamp` is the last returned message's, so polling with it
+/// picks up where a cut-off page stopped.
 pub async fn get_new_messages(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
+    Query(page): Query<MessagePageQuery>,
     Json(req): Json<GetNewMessagesRequest>,
+) -> impl IntoResponse {
+    let pool = match require_pool(&pool) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    match message_page(pool, req.jids, &req.last_timestamp, &req.bot_prefix, page).await {
+        Ok((messages, next)) => {
+            let new_timestamp = messages
+                .last()
+                .map_or(req.last_timestamp, |m| m.timestamp.clone());
+            paged(
+                GetNewMessagesResponse {
+                    messages,
+                    new_timestamp,
+                },
+                next,
+            )
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GetMessagesSinceRequest {
+    pub chat_jid: String,
+    pub since_timestamp: String,
+    pub bot_prefix: String,
+}
+
+pub async fn get_messages_since(
+    State(pool): State<Option<SharedStorage>>,
+    Query(page): Query<MessagePageQuery>,
+    Json(req): Json<GetMessagesSinceRequest>,
+) -> impl IntoResponse {
+    let pool = match require_pool(&pool) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    match message_page(
+        pool,
+        vec![req.chat_jid],
+        &req.since_timestamp,
+        &req.bot_prefix,
+        page,
+    )
+    .await
+    {
+        Ok((msgs, next)) => paged(msgs, next),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EditMessageRequest {
+    pub id: String,
+    pub chat_jid: String,
+    pub content: String,
+    pub edited_at: String,
+}
+
+pub async fn edit_message(
+    State(pool): State<Option<SharedStorage>>,
+    Json(req): Json<EditMessageRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
         Ok(p) => p,
         Err(e) => return e.into_response(),
     };
     match pool
-        .get_new_messages(&req.jids, &req.last_timestamp, &req.bot_prefix)
+        .edit_message(&req.id, &req.chat_jid, &req.content, &req.edited_at)
         .await
     {
-        Ok((messages, new_timestamp)) => (
+        Ok(found) => (
             StatusCode::OK,
-            Json(GetNewMessagesResponse {
-                messages,
-                new_timestamp,
-            }),
+            Json(serde_json::json!({"ok": true, "found": found})),
         )
             .into_response(),
         Err(e) => db_error(e.to_string()).into_response(),
@@ -162,25 +407,83 @@ pub async fn get_new_messages(
 }
 
 #[derive(Deserialize)]
-pub struct GetMessagesSinceRequest {
+pub struct DeleteMessageRequest {
+    pub id: String,
     pub chat_jid: String,
-    pub since_timestamp: String,
-    pub bot_prefix: String,
+    pub deleted_at: String,
 }
 
-pub async fn get_messages_since(
-    State(pool): State<Option<PgPool>>,
-    Json(req): Json<GetMessagesSinceRequest>,
+pub async fn delete_message(
+    State(pool): State<Option<SharedStorage>>,
+    Json(req): Json<DeleteMessageRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
         Ok(p) => p,
         Err(e) => return e.into_response(),
     };
     match pool
-        .get_messages_since(&req.chat_jid, &req.since_timestamp, &req.bot_prefix)
+        .delete_message(&req.id, &req.chat_jid, &req.deleted_at)
         .await
     {
-        Ok(msgs) => (StatusCode::OK, Json(msgs)).into_response(),
+        Ok(found) => (
+            StatusCode::OK,
+            Json(serde_json::json!({"ok": true, "found": found})),
+        )
+            .into_response(),
+        Err(e) => db_error(e.to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GetMessageEditsRequest {
+    pub id: String,
+    pub chat_jid: String,
+}
+
+pub async fn get_message_edits(
+    State(pool): State<Option<SharedStorage>>,
+    Json(req): Json<GetMessageEditsRequest>,
+) -> impl IntoResponse {
+    let pool = match require_pool(&pool) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    match pool.get_message_edits(&req.id, &req.chat_jid).await {
+        Ok(edits) => (StatusCode::OK, Json(edits)).into_response(),
+        Err(e) => db_error(e.to_string()).into_response(),
+    }
+}
+
+pub async fn store_attachment(
+    State(pool): State<Option<SharedStorage>>,
+    Json(attachment): Json<Attachment>,
+) -> impl IntoResponse {
+    let pool = match require_pool(&pool) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    match pool.store_attachment(&attachment).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({"ok": true}))).into_response(),
+        Err(e) => db_error(e.to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GetAttachmentsRequest {
+    pub message_id: String,
+    pub chat_jid: String,
+}
+
+pub async fn get_attachments(
+    State(pool): State<Option<SharedStorage>>,
+    Json(req): Json<GetAttachmentsRequest>,
+) -> impl IntoResponse {
+    let pool = match require_pool(&pool) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    match pool.get_attachments(&req.message_id, &req.chat_jid).await {
+        Ok(attachments) => (StatusCode::OK, Json(attachments)).into_response(),
         Err(e) => db_error(e.to_string()).into_response(),
     }
 }
@@ -197,7 +500,7 @@ fn default_conversation_limit() -> i64 {
 }
 
 pub async fn get_recent_conversation(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<GetRecentConversationRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -205,7 +508,7 @@ pub async fn get_recent_conversation(
         Err(e) => return e.into_response(),
     };
     match pool
-        .get_recent_conversation(&req.chat_jid, req.limit)
+        .get_recent_conversation(&req.chat_jid, req.limit.clamp(1, MAX_PAGE))
         .await
     {
         Ok(msgs) => (StatusCode::OK, Json(msgs)).into_response(),
@@ -218,7 +521,7 @@ pub async fn get_recent_conversation(
 // ---------------------------------------------------------------------------
 
 pub async fn create_task(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(task): Json<ScheduledTask>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -237,7 +540,7 @@ pub struct GetTaskByIdRequest {
 }
 
 pub async fn get_task_by_id(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<GetTaskByIdRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -256,27 +559,31 @@ pub struct GetTasksForGroupRequest {
 }
 
 pub async fn get_tasks_for_group(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
+    Query(page): Query<TaskPageQuery>,
     Json(req): Json<GetTasksForGroupRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
         Ok(p) => p,
         Err(e) => return e.into_response(),
     };
-    match pool.get_tasks_for_group(&req.group_folder).await {
-        Ok(tasks) => (StatusCode::OK, Json(tasks)).into_response(),
-        Err(e) => db_error(e.to_string()).into_response(),
+    match task_page(pool, Some(req.group_folder), page).await {
+        Ok((tasks, next)) => paged(tasks, next),
+        Err(e) => e.into_response(),
     }
 }
 
-pub async fn get_all_tasks(State(pool): State<Option<PgPool>>) -> impl IntoResponse {
+pub async fn get_all_tasks(
+    State(pool): State<Option<SharedStorage>>,
+    Query(page): Query<TaskPageQuery>,
+) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
         Ok(p) => p,
         Err(e) => return e.into_response(),
     };
-    match pool.get_all_tasks().await {
-        Ok(tasks) => (StatusCode::OK, Json(tasks)).into_response(),
-        Err(e) => db_error(e.to_string()).into_response(),
+    match task_page(pool, None, page).await {
+        Ok((tasks, next)) => paged(tasks, next),
+        Err(e) => e.into_response(),
     }
 }
 
@@ -288,7 +595,7 @@ pub struct UpdateTaskRequest {
 }
 
 pub async fn update_task(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<UpdateTaskRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -307,7 +614,7 @@ pub struct DeleteTaskRequest {
 }
 
 pub async fn delete_task(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<DeleteTaskRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -320,7 +627,7 @@ pub async fn delete_task(
     }
 }
 
-pub async fn get_due_tasks(State(pool): State<Option<PgPool>>) -> impl IntoResponse {
+pub async fn get_due_tasks(State(pool): State<Option<SharedStorage>>) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
         Ok(p) => p,
         Err(e) => return e.into_response(),
@@ -339,7 +646,7 @@ pub struct UpdateTaskAfterRunRequest {
 }
 
 pub async fn update_task_after_run(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<UpdateTaskAfterRunRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -356,7 +663,7 @@ pub async fn update_task_after_run(
 }
 
 pub async fn log_task_run(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(log): Json<TaskRunLog>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -379,7 +686,7 @@ pub struct GetRouterStateRequest {
 }
 
 pub async fn get_router_state(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<GetRouterStateRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -399,7 +706,7 @@ pub struct SetRouterStateRequest {
 }
 
 pub async fn set_router_state(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<SetRouterStateRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -422,7 +729,7 @@ pub struct GetSessionRequest {
 }
 
 pub async fn get_session(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<GetSessionRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -442,7 +749,7 @@ pub struct SetSessionRequest {
 }
 
 pub async fn set_session(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<SetSessionRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -458,7 +765,7 @@ pub async fn set_session(
     }
 }
 
-pub async fn get_all_sessions(State(pool): State<Option<PgPool>>) -> impl IntoResponse {
+pub async fn get_all_sessions(State(pool): State<Option<SharedStorage>>) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
         Ok(p) => p,
         Err(e) => return e.into_response(),
@@ -475,7 +782,7 @@ pub struct DeleteSessionRequest {
 }
 
 pub async fn delete_session(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<DeleteSessionRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -498,7 +805,7 @@ pub struct GetRegisteredGroupRequest {
 }
 
 pub async fn get_registered_group(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(req): Json<GetRegisteredGroupRequest>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -512,7 +819,7 @@ pub async fn get_registered_group(
 }
 
 pub async fn set_registered_group(
-    State(pool): State<Option<PgPool>>,
+    State(pool): State<Option<SharedStorage>>,
     Json(group): Json<RegisteredGroup>,
 ) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
@@ -525,7 +832,9 @@ pub async fn set_registered_group(
     }
 }
 
-pub async fn get_all_registered_groups(State(pool): State<Option<PgPool>>) -> impl IntoResponse {
+pub async fn get_all_registered_groups(
+    State(pool): State<Option<SharedStorage>>,
+) -> impl IntoResponse {
     let pool = match require_pool(&pool) {
         Ok(p) => p,
         Err(e) => return e.into_response(),
@@ -535,3 +844,204 @@ pub async fn get_all_registered_groups(State(pool): State<Option<PgPool>>) -> im
         Err(e) => db_error(e.to_string()).into_response(),
     }
 }
+
+// ---------------------------------------------------------------------------
+// Named report queries
+// ---------------------------------------------------------------------------
+
+/// State for `/v1/db/query`: storage plus the configured query whitelist.
+#[derive(Clone)]
+pub struct QueryState {
+    pub db: Option<SharedStorage>,
+    pub queries: Arc<QueryConfig>,
+}
+
+#[derive(Deserialize)]
+pub struct NamedQueryRequest {
+    pub name: String,
+    #[serde(default)]
+    pub params: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct NamedQueryResponse {
+    name: String,
+    #[serde(flatten)]
+    result: QueryResult,
+}
+
+#[derive(Serialize)]
+struct NamedQueryInfo<'a> {
+    name: &'a str,
+    description: &'a str,
+    params: &'a [String],
+}
+
+fn query_error(status: StatusCode, msg: String) -> (StatusCode, Json<DbError>) {
+    (status, Json(DbError { error: msg }))
+}
+
+/// Order the request's parameters as the query declares them. Every
+/// declared parameter must be present and no others are accepted; scalars
+/// are bound as text, null as SQL NULL.
+fn bind_params(
+    query: &NamedQuery,
+    params: &serde_json::Map<String, serde_json::Value>,
+) -> Result<Vec<Option<String>>, String> {
+    if let Some(unknown) = params.keys().find(|k| !query.params.contains(k)) {
+        return Err(format!("unknown parameter: {unknown}"));
+    }
+    query
+        .params
+        .iter()
+        .map(|name| match params.get(name) {
+            None => Err(format!("missing parameter: {name}")),
+            Some(serde_json::Value::Null) => Ok(None),
+            Some(serde_json::Value::String(s)) => Ok(Some(s.clone())),
+            Some(v @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => {
+                Ok(Some(v.to_string()))
+            }
+            Some(_) => Err(format!("parameter {name} must be a scalar")),
+        })
+        .collect()
+}
+
+pub async fn run_named_query(
+    State(state): State<QueryState>,
+    Json(req): Json<NamedQueryRequest>,
+) -> impl IntoResponse {
+    let pool = match require_pool(&state.db) {
+        Ok(p) => p,
+        Err(e) => return e.into_response(),
+    };
+    let Some(query) = state.queries.named.get(&req.name) else {
+        return query_error(
+            StatusCode::NOT_FOUND,
+            format!("unknown query: {}", req.name),
+        )
+        .into_response();
+    };
+    let params = match bind_params(query, &req.params) {
+        Ok(p) => p,
+        Err(msg) => return query_error(StatusCode::BAD_REQUEST, msg).into_response(),
+    };
+    let max_rows = query.max_rows.unwrap_or(state.queries.max_rows);
+    match pool
+        .run_read_query(&query.sql, &params, max_rows, state.queries.timeout_ms)
+        .await
+    {
+        Ok(result) => (
+            StatusCode::OK,
+            Json(NamedQueryResponse {
+                name: req.name,
+                result,
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::warn!(query = %req.name, err = %e, "named query failed");
+            db_error(format!("{e:#}")).into_response()
+        }
+    }
+}
+
+/// List the configured queries so dashboards can discover them.
+pub async fn list_named_queries(State(state): State<QueryState>) -> impl IntoResponse {
+    let queries: Vec<NamedQueryInfo<'_>> = state
+        .queries
+        .named
+        .iter()
+        .map(|(name, q)| NamedQueryInfo {
+            name,
+            description: &q.description,
+            params: &q.params,
+        })
+        .collect();
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "queries": queries })),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(params: &[&str]) -> NamedQuery {
+        NamedQuery {
+            description: String::new(),
+            sql: "SELECT 1".into(),
+            params: params.iter().map(|p| p.to_string()).collect(),
+            max_rows: None,
+        }
+    }
+
+    fn map(value: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn bind_params_orders_by_declaration() {
+        let bound = bind_params(
+            &query(&["since", "limit", "chat"]),
+            &map(serde_json::json!({"chat": null, "limit": 10, "since": "2026-01-01"})),
+        )
+        .unwrap();
+        assert_eq!(
+            bound,
+            [Some("2026-01-01".to_string()), Some("10".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn bind_params_rejects_missing_unknown_and_nested() {
+        let q = query(&["since"]);
+        assert!(
+            bind_params(&q, &map(serde_json::json!({})))
+                .unwrap_err()
+                .contains("missing")
+        );
+        assert!(
+            bind_params(&q, &map(serde_json::json!({"since": "x", "sql": "DROP"})))
+                .unwrap_err()
+                .contains("unknown")
+        );
+        assert!(
+            bind_params(&q, &map(serde_json::json!({"since": ["x"]})))
+                .unwrap_err()
+                .contains("scalar")
+        );
+    }
+
+    #[test]
+    fn cursors_round_trip_and_reject_garbage() {
+        let cursor = encode_cursor(&["2024-01-15T12:00:00.000Z", "tg:1", "m1"]);
+        let Ok([ts, jid, id]) = decode_cursor::<3>(&cursor) else {
+            panic!("cursor should decode")
+        };
+        assert_eq!(
+            (ts.as_str(), jid.as_str(), id.as_str()),
+            ("2024-01-15T12:00:00.000Z", "tg:1", "m1")
+        );
+
+        assert!(decode_cursor::<2>(&cursor).is_err());
+        assert!(decode_cursor::<1>("zz").is_err());
+        assert!(decode_cursor::<2>(&encode_cursor(&["yesterday", "t1"])).is_err());
+    }
+
+    #[test]
+    fn split_page_only_cursors_when_rows_remain() {
+        let mut rows = vec![1, 2, 3];
+        assert_eq!(split_page(&mut rows, 3, i32::to_string), None);
+        assert_eq!(rows, [1, 2, 3]);
+        assert_eq!(
+            split_page(&mut rows, 2, i32::to_string).as_deref(),
+            Some("2")
+        );
+        assert_eq!(rows, [1, 2]);
+        assert_eq!(page_limit(None), MAX_PAGE);
+        assert_eq!(page_limit(Some(10_000)), MAX_PAGE);
+        assert_eq!(page_limit(Some(0)), 1);
+    }
+}
@@ -0,0 +1,191 @@
+//! Draft replies: streamed agent output shown as one message that is edited
+//! in place until the final text replaces it.
+//!
+//! Port of `StreamAccumulator` from `src/stream-accumulator.ts`, limited to
+//! text deltas. Edits are throttled to the bridge's draft interval on top of
+//! its own send pacing, so a chatty runner cannot trip the channel's rate
+//! limits.
+
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::channels::{ChannelBridge, OutboundMessage};
+use crate::health;
+use crate::outbound::{strip_internal_blocks, truncate_text};
+
+/// One in-progress reply to a chat.
+#[derive(Debug)]
+pub struct DraftReply {
+    jid: String,
+    interval: Duration,
+    max_chars: usize,
+    reply_to: Option<String>,
+    correlation_id: Option<String>,
+    /// Raw text deltas received so far.
+    text: String,
+    message_id: Option<String>,
+    /// Text the draft message currently shows.
+    shown: String,
+    last_flush: Option<Instant>,
+    /// Set once posting or editing fails; later deltas are then ignored and
+    /// the final text is sent normally.
+    failed: bool,
+}
+
+impl DraftReply {
+    pub fn new(
+        jid: &str,
+        interval: Duration,
+        max_chars: usize,
+        reply_to: Option<String>,
+        correlation_id: Option<String>,
+    ) -> Self {
+        Self {
+            jid: jid.to_string(),
+            interval,
+            max_chars: max_chars.max(1),
+            reply_to,
+            correlation_id,
+            text: String::new(),
+            message_id: None,
+            shown: String::new(),
+            last_flush: None,
+            failed: false,
+        }
+    }
+
+    /// Add a streamed delta and return the preview to show now, if the
+    /// draft is due for an update and the preview changed.
+    fn push(&mut self, delta: &str, now: Instant) -> Option<String> {
+        if self.failed {
+            return None;
+        }
+        self.text.push_str(delta);
+        if self
+            .last_flush
+            .is_some_and(|at| now.duration_since(at) < self.interval)
+        {
+            return None;
+        }
+        let (preview, _) = truncate_text(&strip_internal_blocks(&self.text), self.max_chars);
+        (!preview.is_empty() && preview != self.shown).then_some(preview)
+    }
+
+    /// Add a streamed delta, posting or editing the draft message if due.
+    pub async fn on_delta(&mut self, channel: &dyn ChannelBridge, delta: &str) {
+        let now = Instant::now();
+        let Some(preview) = self.push(delta, now) else {
+            return;
+        };
+        self.last_flush = Some(now);
+        let result = match &self.message_id {
+            Some(message_id) => channel.edit(&self.jid, message_id, &preview).await,
+            None => self.post(channel, &preview).await,
+        };
+        match result {
+            Ok(()) => self.shown = preview,
+            Err(e) => {
+                warn!(jid = %self.jid, err = %e, "failed to update draft reply, waiting for final text");
+                self.failed = true;
+            }
+        }
+    }
+
+    async fn post(&mut self, channel: &dyn ChannelBridge, text: &str) -> anyhow::Result<()> {
+        let message_ids = channel
+            .send(&self.message(text, self.reply_to.clone()))
+            .await?;
+        self.message_id = message_ids.into_iter().next();
+        Ok(())
+    }
+
+    fn message(&self, text: &str, reply_to: Option<String>) -> OutboundMessage {
+        OutboundMessage {
+            jid: self.jid.clone(),
+            text: text.to_string(),
+            reply_to,
+            correlation_id: self.correlation_id.clone(),
+        }
+    }
+
+    /// Replace the draft with the final text. Text beyond one message goes
+    /// out as follow-up messages; without a draft message the whole text is
+    /// sent as an ordinary reply.
+    pub async fn finish(self, channel: &dyn ChannelBridge, text: &str) -> anyhow::Result<()> {
+        let Some(message_id) = self.message_id.as_deref().filter(|_| !self.failed) else {
+            channel
+                .send(&self.message(text, self.reply_to.clone()))
+                .await?;
+            return Ok(());
+        };
+        let (head, truncated) = truncate_text(text, self.max_chars);
+        if head != self.shown {
+            if let Err(e) = channel.edit(&self.jid, message_id, &head).await {
+                // The draft is stuck on a partial preview; say it in full.
+                health::record_error(channel.name(), &e);
+                channel.send(&self.message(text, None)).await?;
+                return Ok(());
+            }
+        }
+        if truncated {
+            let rest: String = text.chars().skip(head.chars().count()).collect();
+            channel.send(&self.message(&rest, None)).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draft(interval_ms: u64, max_chars: usize) -> DraftReply {
+        DraftReply::new(
+            "tg:1",
+            Duration::from_millis(interval_ms),
+            max_chars,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn first_delta_is_shown_immediately() {
+        let mut d = draft(1_000, 100);
+        assert_eq!(d.push("Hello", Instant::now()).as_deref(), Some("Hello"));
+    }
+
+    #[test]
+    fn deltas_within_interval_accumulate() {
+        let mut d = draft(1_000, 100);
+        let start = Instant::now();
+        d.last_flush = Some(start);
+        d.shown = "Hello".into();
+        d.text = "Hello".into();
+        assert_eq!(d.push(" wor", start + Duration::from_millis(200)), None);
+        assert_eq!(
+            d.push("ld", start + Duration::from_millis(1_200))
+                .as_deref(),
+            Some("Hello world")
+        );
+    }
+
+    #[test]
+    fn preview_hides_internal_blocks_and_respects_limit() {
+        let mut d = draft(0, 5);
+        assert_eq!(d.push("<internal>thinking", Instant::now()), None);
+        assert_eq!(
+            d.push("</internal>Answer is long", Instant::now())
+                .as_deref(),
+            Some("Answe")
+        );
+    }
+
+    #[test]
+    fn failed_draft_ignores_deltas() {
+        let mut d = draft(0, 100);
+        d.failed = true;
+        assert_eq!(d.push("Hello", Instant::now()), None);
+    }
+}
@@ -0,0 +1,527 @@
+//! Email gateway: inbound mail relayed by a provider webhook, replies sent
+//! over SMTP.
+//!
+//! Each correspondent is one chat, `email:<address>`, registered like any
+//! other group. intercomd runs no IMAP client: the mail provider's inbound
+//! route (Mailgun, Postmark, SES + Lambda, ...) posts a small JSON body to
+//! `/v1/email/inbound`, signed with `email.inbound_secret` the same way
+//! group webhooks are (`X-Intercom-Signature: sha256=<hex>`).
+//!
+//! Replies keep the conversation threaded in the correspondent's client:
+//! the bridge remembers each chat's subject and `References` chain from the
+//! last inbound mail and sends `Re:` replies with `In-Reply-To` and
+//! `References` set. The chain lives in memory, so the first reply after a
+//! restart starts a new thread under `email.default_subject`.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Context, anyhow};
+use intercom_core::{
+    Attachment, ChannelProfile, IntercomConfig, NewMessage, SharedStorage, SmtpSecurity,
+};
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::channels::{ChannelBridge, ChannelFuture, OutboundMessage};
+use crate::health::{SUBSYSTEM_DB, record_error};
+use crate::markdown;
+use crate::outbound::split_text;
+use crate::telegram::{self, TelegramIngressRequest, TelegramUpdateKind};
+use crate::webhooks;
+
+/// Name of the email profile under `[channels.profiles]`, also its health
+/// subsystem.
+pub const EMAIL_CHANNEL: &str = "email";
+/// JID prefix for email correspondents.
+pub const EMAIL_JID_PREFIX: &str = "email:";
+/// Message-IDs kept in a thread's `References` header.
+const MAX_REFERENCES: usize = 20;
+
+/// One inbound email, as posted by the provider's inbound route.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmailInboundRequest {
+    /// `From` header, e.g. `Ana <ana@example.org>`.
+    pub from: String,
+    #[serde(default)]
+    pub subject: String,
+    /// Plain-text body.
+    pub text: String,
+    /// `Message-ID` header, with or without angle brackets.
+    pub message_id: String,
+    /// `References` header: whitespace-separated Message-IDs.
+    #[serde(default)]
+    pub references: String,
+    /// `Date` header (RFC 2822) or RFC 3339; the receive time if absent.
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+/// Subject and `References` chain of one chat's conversation.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct EmailThread {
+    subject: String,
+    /// Oldest first; the last entry is the newest message.
+    references: Vec<String>,
+}
+
+impl EmailThread {
+    fn push(&mut self, message_id: String) {
+        self.references.retain(|id| *id != message_id);
+        self.references.push(message_id);
+        let excess = self.references.len().saturating_sub(MAX_REFERENCES);
+        self.references.drain(..excess);
+    }
+}
+
+pub struct EmailBridge {
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+    from: Option<Mailbox>,
+    default_subject: String,
+    profile: ChannelProfile,
+    threads: Mutex<HashMap<String, EmailThread>>,
+    /// Message-IDs must be globally unique; the start time keeps them
+    /// unique across restarts.
+    id_prefix: String,
+    id_counter: AtomicU64,
+    store: Option<SharedStorage>,
+    sender_name: String,
+}
+
+/// JID for an email address. Addresses are compared case-insensitively.
+pub fn address_jid(address: &str) -> String {
+    format!("{EMAIL_JID_PREFIX}{}", address.trim().to_lowercase())
+}
+
+fn address(jid: &str) -> &str {
+    jid.strip_prefix(EMAIL_JID_PREFIX).unwrap_or(jid)
+}
+
+/// `abc@host` → `<abc@host>`.
+fn angle_id(id: &str) -> String {
+    let id = id.trim().trim_start_matches('<').trim_end_matches('>');
+    format!("<{id}>")
+}
+
+fn reply_subject(subject: &str) -> String {
+    let subject = subject.trim();
+    if subject
+        .get(..3)
+        .is_some_and(|re| re.eq_ignore_ascii_case("re:"))
+    {
+        subject.to_string()
+    } else {
+        format!("Re: {subject}")
+    }
+}
+
+fn parse_date(date: Option<&str>) -> String {
+    let parsed = date.map(str::trim).and_then(|date| {
+        chrono::DateTime::parse_from_rfc2822(date)
+            .or_else(|_| chrono::DateTime::parse_from_rfc3339(date))
+            .ok()
+    });
+    parsed
+        .map(|date| date.with_timezone(&chrono::Utc))
+        .unwrap_or_else(chrono::Utc::now)
+        .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// Constant-time check of an inbound post's `X-Intercom-Signature`.
+pub fn signature_matches(secret: &str, body: &[u8], provided: &str) -> bool {
+    telegram::secret_matches(&webhooks::sign(secret, body), provided.trim())
+}
+
+/// The body without quoted history: `>` lines, the `On ... wrote:`
+/// attribution, forwarded originals and the signature.
+pub fn strip_quoted(text: &str) -> String {
+    let mut kept = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_end();
+        if trimmed == "--"
+            || trimmed == "-- "
+            || trimmed.starts_with("-----Original Message-----")
+            || (trimmed.starts_with("On ") && trimmed.ends_with("wrote:"))
+        {
+            break;
+        }
+        if !trimmed.trim_start().starts_with('>') {
+            kept.push(trimmed);
+        }
+    }
+    kept.join("\n").trim().to_string()
+}
+
+fn build_transport(
+    config: &intercom_core::EmailConfig,
+    password: Option<String>,
+) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
+    let host = config.smtp_host.trim();
+    let mut builder = match config.smtp_security {
+        SmtpSecurity::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+        SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+    }
+    .port(config.smtp_port);
+    if !config.smtp_username.is_empty() {
+        builder = builder.credentials(Credentials::new(
+            config.smtp_username.clone(),
+            password.unwrap_or_default(),
+        ));
+    }
+    Ok(builder.build())
+}
+
+impl EmailBridge {
+    pub fn new(config: &IntercomConfig) -> Self {
+        let email = &config.email;
+        let password = std::env::var("EMAIL_SMTP_PASSWORD")
+            .ok()
+            .filter(|value| !value.is_empty());
+        let transport = if email.smtp_host.trim().is_empty() {
+            None
+        } else {
+            build_transport(email, password)
+                .inspect_err(|e| warn!(err = %e, "invalid email SMTP settings"))
+                .ok()
+        };
+        let from = if email.from_address.trim().is_empty() {
+            None
+        } else {
+            email
+                .from_address
+                .parse::<Mailbox>()
+                .inspect_err(|e| warn!(err = %e, "invalid email.from_address"))
+                .ok()
+        };
+
+        Self {
+            transport,
+            from,
+            default_subject: email.default_subject.clone(),
+            profile: config.channels.profile(EMAIL_CHANNEL),
+            threads: Mutex::new(HashMap::new()),
+            id_prefix: format!("intercom.{}", chrono::Utc::now().timestamp_millis()),
+            id_counter: AtomicU64::new(0),
+            store: None,
+            sender_name: String::new(),
+        }
+    }
+
+    /// Record every sent message in `store` as a bot message from
+    /// `sender_name`, keyed by its Message-ID.
+    pub fn with_storage(
+        mut self,
+        store: Option<SharedStorage>,
+        sender_name: impl Into<String>,
+    ) -> Self {
+        self.store = store;
+        self.sender_name = sender_name.into();
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.transport.is_some() && self.from.is_some()
+    }
+
+    fn next_message_id(&self, from: &Mailbox) -> String {
+        let n = self.id_counter.fetch_add(1, Ordering::Relaxed);
+        format!("<{}.{n}@{}>", self.id_prefix, from.email.domain())
+    }
+
+    fn thread(&self, jid: &str) -> EmailThread {
+        let threads = self.threads.lock().expect("email thread lock poisoned");
+        threads.get(jid).cloned().unwrap_or_else(|| EmailThread {
+            subject: self.default_subject.clone(),
+            references: Vec::new(),
+        })
+    }
+
+    /// Add a sent mail to the chat's thread so the next one follows it.
+    fn extend_thread(&self, jid: &str, message_id: &str) {
+        let mut threads = self.threads.lock().expect("email thread lock poisoned");
+        threads
+            .entry(jid.to_string())
+            .or_insert_with(|| EmailThread {
+                subject: self.default_subject.clone(),
+                references: Vec::new(),
+            })
+            .push(message_id.to_string());
+    }
+
+    /// Convert an inbound email to an ingress request for its sender's chat
+    /// and remember its thread for the replies.
+    pub fn ingest(&self, request: &EmailInboundRequest) -> anyhow::Result<TelegramIngressRequest> {
+        let sender: Mailbox = request
+            .from
+            .parse()
+            .with_context(|| format!("invalid From address `{}`", request.from))?;
+        if request.message_id.trim().is_empty() {
+            return Err(anyhow!("inbound email has no Message-ID"));
+        }
+        let chat_jid = address_jid(sender.email.as_ref());
+        let message_id = angle_id(&request.message_id);
+
+        let mut thread = EmailThread {
+            subject: request.subject.trim().to_string(),
+            references: request
+                .references
+                .split_whitespace()
+                .map(angle_id)
+                .collect(),
+        };
+        thread.push(message_id.clone());
+        self.threads
+            .lock()
+            .expect("email thread lock poisoned")
+            .insert(chat_jid.clone(), thread);
+
+        let body = self.normalize_ingress(&request.text, &[]);
+        let content = match request.subject.trim() {
+            "" => body,
+            subject => format!("Subject: {subject}\n\n{body}"),
+        };
+        let sender_name = sender
+            .name
+            .clone()
+            .unwrap_or_else(|| sender.email.user().to_string());
+        Ok(TelegramIngressRequest {
+            chat_jid,
+            chat_name: Some(sender_name.clone()),
+            chat_type: Some("private".to_string()),
+            message_id,
+            sender_id: Some(sender.email.to_string()),
+            sender_name: Some(sender_name),
+            content,
+            timestamp: parse_date(request.date.as_deref()),
+            persist: true,
+            kind: TelegramUpdateKind::Message,
+            attachments: Vec::new(),
+        })
+    }
+
+    /// A reply mail in the chat's thread. `reply_to` overrides which
+    /// message it answers.
+    fn compose(
+        &self,
+        from: &Mailbox,
+        jid: &str,
+        text: &str,
+        reply_to: Option<&str>,
+        message_id: &str,
+    ) -> anyhow::Result<Message> {
+        let to: Mailbox = address(jid)
+            .parse()
+            .with_context(|| format!("invalid email JID `{jid}`"))?;
+        let mut thread = self.thread(jid);
+        if let Some(reply_to) = reply_to {
+            thread.push(angle_id(reply_to));
+        }
+        let mut builder = Message::builder()
+            .from(from.clone())
+            .to(to)
+            .message_id(Some(message_id.to_string()))
+            .header(ContentType::TEXT_PLAIN);
+        builder = match thread.references.last() {
+            Some(parent) => builder
+                .subject(reply_subject(&thread.subject))
+                .in_reply_to(parent.clone())
+                .references(thread.references.join(" ")),
+            None => builder.subject(thread.subject.clone()),
+        };
+        builder
+            .body(markdown::render(text, self.profile.markdown))
+            .context("failed to build email")
+    }
+
+    /// Send `text` to the chat's address, split to the profile limit. Each
+    /// mail continues the thread. Returns the sent Message-IDs.
+    pub async fn send_message(&self, message: &OutboundMessage) -> anyhow::Result<Vec<String>> {
+        if message.text.trim().is_empty() {
+            return Err(anyhow!("cannot send an empty email"));
+        }
+        let (Some(transport), Some(from)) = (&self.transport, &self.from) else {
+            return Err(anyhow!(
+                "email.smtp_host and email.from_address must be set to send email"
+            ));
+        };
+        let mut message_ids = Vec::new();
+        for (index, chunk) in split_text(&message.text, self.max_chars())
+            .iter()
+            .enumerate()
+        {
+            let message_id = self.next_message_id(from);
+            let reply_to = message.reply_to.as_deref().filter(|_| index == 0);
+            let mail = self.compose(from, &message.jid, chunk, reply_to, &message_id)?;
+            transport.send(mail).await.context("SMTP send failed")?;
+            self.extend_thread(&message.jid, &message_id);
+            self.record_sent(
+                &message.jid,
+                &message_id,
+                chunk,
+                message.correlation_id.as_deref(),
+            )
+            .await;
+            message_ids.push(message_id);
+        }
+        Ok(message_ids)
+    }
+
+    /// Store one sent mail as a bot message. Failures are logged, never
+    /// returned: the mail has already been delivered.
+    async fn record_sent(
+        &self,
+        jid: &str,
+        message_id: &str,
+        text: &str,
+        correlation_id: Option<&str>,
+    ) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let message = NewMessage {
+            id: message_id.to_string(),
+            chat_jid: jid.to_string(),
+            sender: "bot".into(),
+            sender_name: self.sender_name.clone(),
+            content: text.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            is_from_me: true,
+            is_bot_message: true,
+            edited: false,
+            attachments: Vec::new(),
+            correlation_id: correlation_id.map(str::to_string),
+        };
+        if let Err(e) = store.store_message(&message).await {
+            warn!(jid, message_id, err = %e, "failed to store sent email");
+            record_error(SUBSYSTEM_DB, &e);
+        }
+    }
+}
+
+impl ChannelBridge for EmailBridge {
+    fn name(&self) -> &'static str {
+        EMAIL_CHANNEL
+    }
+
+    fn profile(&self) -> &ChannelProfile {
+        &self.profile
+    }
+
+    fn send<'a>(&'a self, message: &'a OutboundMessage) -> ChannelFuture<'a, Vec<String>> {
+        Box::pin(self.send_message(message))
+    }
+
+    fn edit<'a>(
+        &'a self,
+        _jid: &'a str,
+        _message_id: &'a str,
+        _text: &'a str,
+    ) -> ChannelFuture<'a, ()> {
+        Box::pin(async { Err(anyhow!("sent email cannot be edited")) })
+    }
+
+    fn normalize_ingress(&self, content: &str, _attachments: &[Attachment]) -> String {
+        strip_quoted(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bridge() -> EmailBridge {
+        let mut config = IntercomConfig::default();
+        config.email.from_address = "Amtiskaw <amtiskaw@example.org>".into();
+        EmailBridge::new(&config)
+    }
+
+    fn inbound(subject: &str, references: &str) -> EmailInboundRequest {
+        EmailInboundRequest {
+            from: "Ana Lima <Ana@Example.org>".into(),
+            subject: subject.into(),
+            text: "Please summarise the report.\n\nOn Mon, Ana wrote:\n> old text".into(),
+            message_id: "m2@mail.example.org".into(),
+            references: references.into(),
+            date: Some("Tue, 1 Jul 2025 10:00:00 +0200".into()),
+        }
+    }
+
+    #[test]
+    fn ingest_maps_sender_to_chat() {
+        let request = bridge().ingest(&inbound("Report", "")).unwrap();
+        assert_eq!(request.chat_jid, "email:ana@example.org");
+        assert_eq!(request.message_id, "<m2@mail.example.org>");
+        assert_eq!(request.sender_name.as_deref(), Some("Ana Lima"));
+        assert_eq!(
+            request.content,
+            "Subject: Report\n\nPlease summarise the report."
+        );
+        assert_eq!(request.timestamp, "2025-07-01T08:00:00.000Z");
+    }
+
+    #[test]
+    fn strip_quoted_drops_history_and_signature() {
+        let text = "Yes, go ahead.\n> earlier\n>> older\nThanks\n-- \nAna\n";
+        assert_eq!(strip_quoted(text), "Yes, go ahead.\nThanks");
+        assert_eq!(
+            strip_quoted("Hi\n-----Original Message-----\nFrom: x"),
+            "Hi"
+        );
+    }
+
+    #[test]
+    fn replies_continue_the_inbound_thread() {
+        let bridge = bridge();
+        bridge
+            .ingest(&inbound("Report", "<m1@mail.example.org>"))
+            .unwrap();
+        let from = bridge.from.clone().unwrap();
+        let mail = bridge
+            .compose(
+                &from,
+                "email:ana@example.org",
+                "Done.",
+                None,
+                "<r1@example.org>",
+            )
+            .unwrap();
+        let headers = String::from_utf8(mail.formatted()).unwrap();
+        assert!(headers.contains("Subject: Re: Report\r\n"));
+        assert!(headers.contains("In-Reply-To: <m2@mail.example.org>\r\n"));
+        assert!(headers.contains("References: <m1@mail.example.org> <m2@mail.example.org>\r\n"));
+        assert!(headers.contains("Message-ID: <r1@example.org>\r\n"));
+    }
+
+    #[test]
+    fn reply_without_thread_uses_default_subject() {
+        let bridge = bridge();
+        let from = bridge.from.clone().unwrap();
+        let mail = bridge
+            .compose(
+                &from,
+                "email:bo@example.org",
+                "Hello",
+                None,
+                "<r1@example.org>",
+            )
+            .unwrap();
+        let headers = String::from_utf8(mail.formatted()).unwrap();
+        assert!(headers.contains("Subject: Message from your assistant\r\n"));
+        assert!(!headers.contains("In-Reply-To"));
+        assert_eq!(reply_subject("RE: Report"), "RE: Report");
+    }
+
+    #[test]
+    fn signature_uses_webhook_scheme() {
+        let body = br#"{"from":"ana@example.org"}"#;
+        let signature = webhooks::sign("s3cret", body);
+        assert!(signature_matches("s3cret", body, &signature));
+        assert!(!signature_matches("other", body, &signature));
+    }
+}
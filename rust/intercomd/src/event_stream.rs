@@ -0,0 +1,280 @@
+//! Live event stream for dashboards and the Node host.
+//!
+//! `GET /v1/events/stream` is a Server-Sent Events feed of what the daemon
+//! is doing: messages ingested, containers started and finished, scheduled
+//! tasks fired and sends that failed. Each SSE message is named after the
+//! event kind, carries the event's sequence number as its `id`, and has the
+//! JSON [`LiveEvent`] as data:
+//!
+//! ```text
+//! event: container_finished
+//! id: 42
+//! data: {"id":42,"event":"container_finished","chatJid":"tg:-100…","status":"success",…}
+//! ```
+//!
+//! `?types=a,b` limits the feed to those kinds and `?chat_jid=` to one chat.
+//! Events are not stored: a subscriber only sees what happens while it is
+//! connected. One that falls more than [`CAPACITY`] events behind gets a
+//! `lagged` event with the number it missed, and should re-read the db
+//! routes to catch up.
+
+use std::convert::Infallible;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axum::extract::Query;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::webhooks::{LifecycleEvent, LifecycleEventKind};
+
+/// Events buffered per subscriber before it starts missing them.
+pub const CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveEventKind {
+    MessageIngested,
+    ContainerStarted,
+    ContainerFinished,
+    TaskFired,
+    SendFailed,
+}
+
+impl LiveEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MessageIngested => "message_ingested",
+            Self::ContainerStarted => "container_started",
+            Self::ContainerFinished => "container_finished",
+            Self::TaskFired => "task_fired",
+            Self::SendFailed => "send_failed",
+        }
+    }
+}
+
+/// One event on the stream.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveEvent {
+    /// Sequence number, assigned by [`publish`]; increases by one per event
+    /// for the life of the daemon.
+    pub id: u64,
+    pub event: LiveEventKind,
+    pub timestamp: String,
+    pub chat_jid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_folder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// Channel the message came in on or the send went out through.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// "success" or "error" once a container has finished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl LiveEvent {
+    pub fn new(event: LiveEventKind, chat_jid: &str) -> Self {
+        Self {
+            id: 0,
+            event,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            chat_jid: chat_jid.to_string(),
+            group_folder: None,
+            task_id: None,
+            correlation_id: None,
+            message_id: None,
+            channel: None,
+            status: None,
+            duration_ms: None,
+            error: None,
+        }
+    }
+
+    pub fn with_group(mut self, group_folder: &str) -> Self {
+        self.group_folder = Some(group_folder.to_string());
+        self
+    }
+
+    pub fn with_task(mut self, task_id: &str) -> Self {
+        self.task_id = Some(task_id.to_string());
+        self
+    }
+
+    pub fn with_correlation_id(mut self, correlation_id: Option<&str>) -> Self {
+        self.correlation_id = correlation_id.map(str::to_string);
+        self
+    }
+
+    pub fn with_message(mut self, message_id: &str) -> Self {
+        self.message_id = Some(message_id.to_string());
+        self
+    }
+
+    pub fn with_channel(mut self, channel: &str) -> Self {
+        self.channel = Some(channel.to_string());
+        self
+    }
+
+    pub fn with_error(mut self, error: impl std::fmt::Display) -> Self {
+        self.error = Some(error.to_string());
+        self
+    }
+
+    /// The stream's view of a group webhook event: run start and finish.
+    /// Task results are left to the webhook, as `container_finished`
+    /// already carries the task id and outcome.
+    pub fn from_lifecycle(event: &LifecycleEvent) -> Option<Self> {
+        let kind = match event.event {
+            LifecycleEventKind::RunStarted => LiveEventKind::ContainerStarted,
+            LifecycleEventKind::RunFinished => LiveEventKind::ContainerFinished,
+            LifecycleEventKind::TaskResult => return None,
+        };
+        Some(Self {
+            timestamp: event.timestamp.clone(),
+            group_folder: Some(event.group_folder.clone()),
+            task_id: event.task_id.clone(),
+            correlation_id: event.correlation_id.clone(),
+            status: event.status.clone(),
+            duration_ms: event.duration_ms,
+            error: event.error.clone(),
+            ..Self::new(kind, &event.chat_jid)
+        })
+    }
+}
+
+fn bus() -> &'static broadcast::Sender<LiveEvent> {
+    static BUS: OnceLock<broadcast::Sender<LiveEvent>> = OnceLock::new();
+    BUS.get_or_init(|| broadcast::channel(CAPACITY).0)
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Send `event` to every connected subscriber. A no-op when nobody is
+/// listening, so callers never wait on the stream.
+pub fn publish(mut event: LiveEvent) {
+    event.id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let _ = bus().send(event);
+}
+
+pub fn subscribe() -> broadcast::Receiver<LiveEvent> {
+    bus().subscribe()
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct StreamQuery {
+    /// Comma-separated event kinds; every kind when absent.
+    #[serde(default)]
+    pub types: Option<String>,
+    #[serde(default)]
+    pub chat_jid: Option<String>,
+}
+
+impl StreamQuery {
+    pub fn matches(&self, event: &LiveEvent) -> bool {
+        let kind_ok = self.types.as_deref().is_none_or(|types| {
+            types
+                .split(',')
+                .map(str::trim)
+                .any(|kind| kind == event.event.as_str())
+        });
+        let chat_ok = self
+            .chat_jid
+            .as_deref()
+            .is_none_or(|jid| jid == event.chat_jid);
+        kind_ok && chat_ok
+    }
+}
+
+fn sse_event(event: &LiveEvent) -> Event {
+    Event::default()
+        .event(event.event.as_str())
+        .id(event.id.to_string())
+        .data(serde_json::to_string(event).unwrap_or_default())
+}
+
+/// `GET /v1/events/stream`.
+pub async fn stream_events(
+    Query(query): Query<StreamQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = futures::stream::unfold((subscribe(), query), |(mut rx, query)| async move {
+        loop {
+            let next = match rx.recv().await {
+                Ok(event) if query.matches(&event) => sse_event(&event),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => Event::default()
+                    .event("lagged")
+                    .data(serde_json::json!({ "skipped": skipped }).to_string()),
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((Ok(next), (rx, query)));
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn subscribers_see_published_events_in_order() {
+        let mut rx = subscribe();
+        publish(
+            LiveEvent::new(LiveEventKind::TaskFired, "tg:1")
+                .with_group("ops")
+                .with_task("digest"),
+        );
+        let finished = LifecycleEvent::new(LifecycleEventKind::RunFinished, "ops", "tg:1")
+            .with_task("digest")
+            .with_outcome(1200, None, Some("boom"));
+        publish(LiveEvent::from_lifecycle(&finished).unwrap());
+
+        // Other tests publish on the same bus; keep to this chat's events.
+        let mut seen = Vec::new();
+        while seen.len() < 2 {
+            let event = rx.recv().await.unwrap();
+            if event.chat_jid == "tg:1" {
+                seen.push(event);
+            }
+        }
+        assert!(seen[1].id > seen[0].id);
+        assert_eq!(seen[0].event, LiveEventKind::TaskFired);
+        let json = serde_json::to_value(&seen[1]).unwrap();
+        assert_eq!(json["event"], "container_finished");
+        assert_eq!(json["taskId"], "digest");
+        assert_eq!(json["status"], "error");
+        assert_eq!(json["error"], "boom");
+        assert!(json.get("messageId").is_none());
+
+        let result = LifecycleEvent::new(LifecycleEventKind::TaskResult, "ops", "tg:1");
+        assert!(LiveEvent::from_lifecycle(&result).is_none());
+    }
+
+    #[test]
+    fn queries_filter_by_kind_and_chat() {
+        let sent = LiveEvent::new(LiveEventKind::SendFailed, "wa:2").with_error("timeout");
+        assert!(StreamQuery::default().matches(&sent));
+        let query = |types: Option<&str>, chat_jid: Option<&str>| StreamQuery {
+            types: types.map(str::to_string),
+            chat_jid: chat_jid.map(str::to_string),
+        };
+        assert!(query(Some("task_fired, send_failed"), None).matches(&sent));
+        assert!(!query(Some("task_fired"), None).matches(&sent));
+        assert!(query(None, Some("wa:2")).matches(&sent));
+        assert!(!query(Some("send_failed"), Some("tg:1")).matches(&sent));
+    }
+}
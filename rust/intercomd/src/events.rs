@@ -1,5 +1,6 @@
-//! Event consumer loop — polls `ic events tail --consumer=intercom` and
-//! routes relevant kernel events to the Telegram bridge as push notifications.
+//! Event consumer loop — polls `ic events tail --consumer=intercom` (or, in
+//! follow mode, reads a long-lived `ic events follow`) and routes relevant
+//! kernel events to the Telegram bridge as push notifications.
 //!
 //! Event types handled:
 //! - `gate.pending`    → send approval request with inline buttons
@@ -10,9 +11,9 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use intercom_core::{DemarchAdapter, ReadOperation};
+use intercom_core::{DemarchAdapter, EventsMode, ReadOperation};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, info, warn};
 
 use crate::ipc::IpcDelegate;
 use crate::telegram::{InlineKeyboardButton, InlineKeyboardMarkup};
@@ -28,6 +29,10 @@ pub struct EventConsumerConfig {
     pub notification_jid: Option<String>,
     /// Enable/disable the event consumer.
     pub enabled: bool,
+    /// Poll `ic events tail` or follow `ic events follow`.
+    pub mode: EventsMode,
+    /// Wait before respawning the follower after it exits or fails to start.
+    pub follow_restart: Duration,
 }
 
 impl Default for EventConsumerConfig {
@@ -37,6 +42,8 @@ impl Default for EventConsumerConfig {
             batch_size: 20,
             notification_jid: None,
             enabled: false,
+            mode: EventsMode::Poll,
+            follow_restart: Duration::from_secs(5),
         }
     }
 }
@@ -118,15 +125,60 @@ impl EventConsumer {
 
         info!(
             jid = %jid,
+            mode = ?self.config.mode,
             poll_interval_ms = %self.config.poll_interval.as_millis(),
             "Event consumer started"
         );
 
+        if self.config.mode == EventsMode::Follow {
+            return self.follow(&jid, shutdown).await;
+        }
+
         loop {
             tokio::select! {
                 _ = tokio::time::sleep(self.config.poll_interval) => {
-                    self.poll_events(&jid);
+                    self.poll_events(&jid).await;
+                }
+                _ = shutdown.changed() => {
+                    if *shutdown.borrow() {
+                        info!("Event consumer shutting down");
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Follow mode: stream events from `ic events follow`, respawning it from
+    /// the last seen event whenever it exits.
+    async fn follow(&mut self, jid: &str, mut shutdown: tokio::sync::watch::Receiver<bool>) {
+        loop {
+            match self.demarch.follow_events(self.last_event_id.clone()).await {
+                Ok(mut lines) => {
+                    debug!(since = ?self.last_event_id, "Following kernel events");
+                    loop {
+                        tokio::select! {
+                            line = lines.recv() => match line {
+                                Some(line) => self.handle_line(jid, &line),
+                                None => break,
+                            },
+                            _ = shutdown.changed() => {
+                                if *shutdown.borrow() {
+                                    info!("Event consumer shutting down");
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                    warn!("ic events follow exited — restarting");
                 }
+                Err(err) => {
+                    debug!(err = %format!("{err:#}"), "Could not follow kernel events");
+                }
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(self.config.follow_restart) => {}
                 _ = shutdown.changed() => {
                     if *shutdown.borrow() {
                         info!("Event consumer shutting down");
@@ -137,12 +189,23 @@ impl EventConsumer {
         }
     }
 
+    /// Dispatch one line of `ic events follow` output.
+    fn handle_line(&mut self, notification_jid: &str, line: &str) {
+        match serde_json::from_str::<KernelEvent>(line) {
+            Ok(event) => self.dispatch(notification_jid, &event),
+            Err(err) => debug!(err = %err, "Failed to parse followed event"),
+        }
+    }
+
     /// Poll for new events and dispatch notifications.
-    fn poll_events(&mut self, notification_jid: &str) {
-        let response = self.demarch.execute_read(ReadOperation::RunEvents {
-            limit: Some(self.config.batch_size),
-            since: self.last_event_id.clone(),
-        });
+    async fn poll_events(&mut self, notification_jid: &str) {
+        let response = self
+            .demarch
+            .execute_read(ReadOperation::RunEvents {
+                limit: Some(self.config.batch_size),
+                since: self.last_event_id.clone(),
+            })
+            .await;
 
         if response.status != intercom_core::DemarchStatus::Ok {
             debug!(
@@ -168,25 +231,30 @@ impl EventConsumer {
         debug!(count = events.len(), "Processing kernel events");
 
         for event in &events {
-            if let Some(notif) = self.format_notification(event) {
-                if notif.buttons.is_some() {
-                    self.delegate.send_message_with_buttons(
-                        notification_jid,
-                        &notif.text,
-                        Some("Intercom"),
-                        notif.buttons,
-                    );
-                } else {
-                    self.delegate
-                        .send_message(notification_jid, &notif.text, Some("Intercom"));
-                }
-            }
+            self.dispatch(notification_jid, event);
+        }
+    }
 
-            // Advance cursor
-            if let Some(id) = &event.id {
-                self.last_event_id = Some(id.clone());
+    /// Send the notification for one event, if any, and advance the cursor.
+    fn dispatch(&mut self, notification_jid: &str, event: &KernelEvent) {
+        if let Some(notif) = self.format_notification(event) {
+            if notif.buttons.is_some() {
+                self.delegate.send_message_with_buttons(
+                    notification_jid,
+                    &notif.text,
+                    Some("Intercom"),
+                    notif.buttons,
+                );
+            } else {
+                self.delegate
+                    .send_message(notification_jid, &notif.text, Some("Intercom"));
             }
         }
+
+        // Advance cursor
+        if let Some(id) = &event.id {
+            self.last_event_id = Some(id.clone());
+        }
     }
 
     /// Format a kernel event into a notification with optional inline buttons.
@@ -356,6 +424,25 @@ mod tests {
             .is_none());
     }
 
+    #[test]
+    fn followed_lines_advance_the_cursor() {
+        let mut consumer = EventConsumer::new(
+            EventConsumerConfig::default(),
+            Arc::new(DemarchAdapter::new(
+                intercom_core::config::DemarchConfig::default(),
+                ".",
+            )),
+            Arc::new(crate::ipc::LogOnlyDelegate),
+        );
+
+        consumer.handle_line("tg:1", r#"{"id": "evt-7", "kind": "run.completed"}"#);
+        assert_eq!(consumer.last_event_id.as_deref(), Some("evt-7"));
+
+        consumer.handle_line("tg:1", "not json");
+        consumer.handle_line("tg:1", r#"{"kind": "phase.changed"}"#);
+        assert_eq!(consumer.last_event_id.as_deref(), Some("evt-7"));
+    }
+
     #[test]
     fn gate_buttons_have_correct_callback_data() {
         let buttons = gate_approval_buttons("gate-review");
@@ -0,0 +1,416 @@
+//! Group management for the `/v1/admin/groups` routes.
+//!
+//! Every change is made under the groups map's write lock: storage is
+//! written first and the map only updated once that succeeded, so the
+//! daemon's view and the `registered_groups` table never disagree and two
+//! admin calls can't interleave.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use intercom_core::{RegisteredGroup, SharedStorage};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::info;
+
+use crate::commands;
+use crate::message_loop;
+use crate::queue::GroupQueueStatus;
+
+/// Why an admin change was refused, mapped to an HTTP status by the route.
+#[derive(Debug)]
+pub enum AdminError {
+    Invalid(String),
+    NotFound(String),
+    Conflict(String),
+    Storage(anyhow::Error),
+}
+
+impl std::fmt::Display for AdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(msg) | Self::NotFound(msg) | Self::Conflict(msg) => f.write_str(msg),
+            Self::Storage(e) => write!(f, "{e:#}"),
+        }
+    }
+}
+
+/// Same rule as the host's `isValidGroupFolder`: a short name of letters,
+/// digits, `_` and `-` that can't leave `groups/`, and not `global`.
+pub fn valid_folder(folder: &str) -> bool {
+    let mut chars = folder.chars();
+    let first_ok = chars.next().is_some_and(|c| c.is_ascii_alphanumeric());
+    first_ok
+        && folder.len() <= 64
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !folder.eq_ignore_ascii_case("global")
+}
+
+/// The same limits `/trigger set` applies.
+fn check_trigger(trigger: &str) -> Result<(), AdminError> {
+    if trigger.trim().is_empty() || trigger.chars().count() > 64 || trigger.contains('\n') {
+        return Err(AdminError::Invalid(
+            "trigger must be one line of at most 64 characters".into(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterGroupRequest {
+    pub jid: String,
+    pub name: String,
+    pub folder: String,
+    pub trigger: String,
+    #[serde(default)]
+    pub requires_trigger: Option<bool>,
+    #[serde(default)]
+    pub runtime: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub container_config: Option<serde_json::Value>,
+}
+
+/// Fields to change; absent ones are kept. An empty `model` or `runtime`
+/// clears it back to the default.
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateGroupRequest {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub trigger: Option<String>,
+    #[serde(default)]
+    pub requires_trigger: Option<bool>,
+    #[serde(default)]
+    pub runtime: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// A registered group with what it is doing right now.
+#[derive(Debug, Serialize)]
+pub struct GroupListing {
+    #[serde(flatten)]
+    pub group: RegisteredGroup,
+    pub is_main: bool,
+    pub muted: bool,
+    pub status: GroupActivity,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct GroupActivity {
+    pub container_active: bool,
+    pub idle_waiting: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uptime_secs: Option<u64>,
+    pub running_tasks: Vec<String>,
+    pub pending_messages: bool,
+    pub pending_tasks: usize,
+}
+
+impl From<&GroupQueueStatus> for GroupActivity {
+    fn from(status: &GroupQueueStatus) -> Self {
+        Self {
+            container_active: status.active,
+            idle_waiting: status.idle_waiting,
+            container_name: status.container_name.clone(),
+            uptime_secs: status.uptime.map(|up| up.as_secs()),
+            running_tasks: status.running_tasks.clone(),
+            pending_messages: status.pending_messages,
+            pending_tasks: status.pending_tasks.len(),
+        }
+    }
+}
+
+pub fn listing(
+    group: RegisteredGroup,
+    status: &GroupQueueStatus,
+    main_folder: &str,
+) -> GroupListing {
+    GroupListing {
+        is_main: group.folder == main_folder,
+        muted: message_loop::is_muted(group.muted_until.as_deref(), chrono::Utc::now()),
+        status: GroupActivity::from(status),
+        group,
+    }
+}
+
+/// Runtime for a group's model: `runtime` when given, else inferred from
+/// the model as `/model` does. Must be a configured profile.
+fn resolve_runtime(
+    runtime: Option<String>,
+    model: Option<&str>,
+    profiles: &[&str],
+) -> Result<Option<String>, AdminError> {
+    let runtime = runtime
+        .filter(|r| !r.trim().is_empty())
+        .or_else(|| model.map(commands::runtime_for_model));
+    match runtime {
+        Some(r) if !profiles.contains(&r.as_str()) => Err(AdminError::Invalid(format!(
+            "no runtime profile {r}; configured: {}",
+            profiles.join(", ")
+        ))),
+        runtime => Ok(runtime),
+    }
+}
+
+/// Register a new group and create its folder.
+pub async fn register(
+    pool: &SharedStorage,
+    groups: &RwLock<HashMap<String, RegisteredGroup>>,
+    groups_dir: &Path,
+    profiles: &[&str],
+    request: RegisterGroupRequest,
+) -> Result<RegisteredGroup, AdminError> {
+    if request.jid.trim().is_empty() || request.name.trim().is_empty() {
+        return Err(AdminError::Invalid("jid and name are required".into()));
+    }
+    if !valid_folder(&request.folder) {
+        return Err(AdminError::Invalid(format!(
+            "invalid group folder {:?}",
+            request.folder
+        )));
+    }
+    check_trigger(&request.trigger)?;
+    let model = request.model.filter(|m| !m.trim().is_empty());
+    let runtime = resolve_runtime(request.runtime, model.as_deref(), profiles)?;
+
+    let mut groups = groups.write().await;
+    if groups.contains_key(&request.jid) {
+        return Err(AdminError::Conflict(format!(
+            "{} is already registered",
+            request.jid
+        )));
+    }
+    if let Some(other) = groups.values().find(|g| g.folder == request.folder) {
+        return Err(AdminError::Conflict(format!(
+            "folder {} belongs to {}",
+            request.folder, other.jid
+        )));
+    }
+
+    std::fs::create_dir_all(groups_dir.join(&request.folder).join("logs"))
+        .map_err(|e| AdminError::Storage(anyhow::anyhow!("failed to create group folder: {e}")))?;
+    let group = RegisteredGroup {
+        jid: request.jid,
+        name: request.name,
+        folder: request.folder,
+        trigger: request.trigger,
+        added_at: chrono::Utc::now().to_rfc3339(),
+        container_config: request.container_config,
+        requires_trigger: request.requires_trigger,
+        runtime,
+        model,
+        muted_until: None,
+        locale: None,
+    };
+    pool.set_registered_group(&group)
+        .await
+        .map_err(AdminError::Storage)?;
+    groups.insert(group.jid.clone(), group.clone());
+    info!(
+        jid = group.jid.as_str(),
+        folder = group.folder.as_str(),
+        "group registered via admin API"
+    );
+    Ok(group)
+}
+
+/// Change a registered group's name, trigger, runtime or model.
+pub async fn update(
+    pool: &SharedStorage,
+    groups: &RwLock<HashMap<String, RegisteredGroup>>,
+    profiles: &[&str],
+    jid: &str,
+    request: UpdateGroupRequest,
+) -> Result<RegisteredGroup, AdminError> {
+    let mut groups = groups.write().await;
+    let Some(current) = groups.get(jid) else {
+        return Err(AdminError::NotFound(format!("{jid} is not registered")));
+    };
+    let mut group = current.clone();
+    if let Some(name) = request.name {
+        if name.trim().is_empty() {
+            return Err(AdminError::Invalid("name is empty".into()));
+        }
+        group.name = name;
+    }
+    if let Some(trigger) = request.trigger {
+        check_trigger(&trigger)?;
+        group.trigger = trigger;
+    }
+    if let Some(required) = request.requires_trigger {
+        group.requires_trigger = Some(required);
+    }
+    if request.model.is_some() || request.runtime.is_some() {
+        if let Some(model) = request.model {
+            group.model = Some(model).filter(|m| !m.trim().is_empty());
+        }
+        // A new model without a runtime brings its own runtime
+        let runtime = match request.runtime {
+            Some(runtime) => Some(runtime),
+            None => group.model.as_deref().map(commands::runtime_for_model),
+        };
+        group.runtime = resolve_runtime(runtime, group.model.as_deref(), profiles)?;
+    }
+
+    pool.set_registered_group(&group)
+        .await
+        .map_err(AdminError::Storage)?;
+    groups.insert(jid.to_string(), group.clone());
+    info!(
+        jid,
+        folder = group.folder.as_str(),
+        "group updated via admin API"
+    );
+    Ok(group)
+}
+
+/// Remove a group's registration. Its folder, messages, tasks and session
+/// are kept, so registering the folder again picks up where it left off.
+pub async fn deactivate(
+    pool: &SharedStorage,
+    groups: &RwLock<HashMap<String, RegisteredGroup>>,
+    main_folder: &str,
+    jid: &str,
+) -> Result<RegisteredGroup, AdminError> {
+    let mut groups = groups.write().await;
+    let Some(group) = groups.get(jid) else {
+        return Err(AdminError::NotFound(format!("{jid} is not registered")));
+    };
+    if group.folder == main_folder {
+        return Err(AdminError::Conflict(
+            "the main group can't be deactivated".into(),
+        ));
+    }
+    pool.delete_registered_group(jid)
+        .await
+        .map_err(AdminError::Storage)?;
+    let group = groups.remove(jid).expect("checked above");
+    info!(
+        jid,
+        folder = group.folder.as_str(),
+        "group deactivated via admin API"
+    );
+    Ok(group)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const PROFILES: &[&str] = &["claude", "gemini", "codex"];
+
+    fn request(jid: &str, folder: &str) -> RegisterGroupRequest {
+        RegisterGroupRequest {
+            jid: jid.into(),
+            name: "Ops".into(),
+            folder: folder.into(),
+            trigger: "@Andy".into(),
+            requires_trigger: None,
+            runtime: None,
+            model: None,
+            container_config: None,
+        }
+    }
+
+    #[test]
+    fn folders_stay_inside_groups() {
+        assert!(valid_folder("ops-team_2"));
+        for bad in [
+            "",
+            "-ops",
+            "../ops",
+            "a/b",
+            "ops ",
+            "Global",
+            &"x".repeat(65),
+        ] {
+            assert!(!valid_folder(bad), "{bad:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn changes_reach_storage_and_the_map_together() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool: SharedStorage = Arc::new(intercom_core::SqliteStore::new(":memory:"));
+        let groups = RwLock::new(HashMap::new());
+
+        let group = register(&pool, &groups, dir.path(), PROFILES, request("tg:1", "ops"))
+            .await
+            .unwrap();
+        assert!(dir.path().join("ops/logs").is_dir());
+        assert_eq!(
+            pool.get_registered_group("tg:1")
+                .await
+                .unwrap()
+                .unwrap()
+                .folder,
+            "ops"
+        );
+        assert_eq!(groups.read().await["tg:1"].added_at, group.added_at);
+        assert!(matches!(
+            register(&pool, &groups, dir.path(), PROFILES, request("tg:2", "ops")).await,
+            Err(AdminError::Conflict(_))
+        ));
+        assert!(matches!(
+            register(
+                &pool,
+                &groups,
+                dir.path(),
+                PROFILES,
+                request("tg:2", "../x")
+            )
+            .await,
+            Err(AdminError::Invalid(_))
+        ));
+
+        let switch = UpdateGroupRequest {
+            model: Some("gemini-3.1-pro".into()),
+            ..Default::default()
+        };
+        let updated = update(&pool, &groups, PROFILES, "tg:1", switch)
+            .await
+            .unwrap();
+        assert_eq!(
+            (updated.runtime.as_deref(), updated.model.as_deref()),
+            (Some("gemini"), Some("gemini-3.1-pro"))
+        );
+        assert_eq!(
+            groups.read().await["tg:1"].runtime.as_deref(),
+            Some("gemini")
+        );
+        let stored = pool.get_registered_group("tg:1").await.unwrap().unwrap();
+        assert_eq!(stored.model.as_deref(), Some("gemini-3.1-pro"));
+
+        let unknown = UpdateGroupRequest {
+            runtime: Some("llama".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            update(&pool, &groups, PROFILES, "tg:1", unknown).await,
+            Err(AdminError::Invalid(_))
+        ));
+        assert_eq!(
+            groups.read().await["tg:1"].runtime.as_deref(),
+            Some("gemini")
+        );
+
+        assert!(matches!(
+            deactivate(&pool, &groups, "ops", "tg:1").await,
+            Err(AdminError::Conflict(_))
+        ));
+        deactivate(&pool, &groups, "main", "tg:1").await.unwrap();
+        assert!(groups.read().await.is_empty());
+        assert!(pool.get_registered_group("tg:1").await.unwrap().is_none());
+        assert!(dir.path().join("ops").is_dir());
+        assert!(matches!(
+            deactivate(&pool, &groups, "main", "tg:1").await,
+            Err(AdminError::NotFound(_))
+        ));
+    }
+}
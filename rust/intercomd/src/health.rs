@@ -0,0 +1,630 @@
+//! In-process doctor checks behind the `/health` chat command and `/readyz`.
+//!
+//! Probes each subsystem (storage, container runtime, Telegram, Demarch,
+//! the IPC directory), snapshots queue depth, and pairs every check with
+//! the last error that subsystem reported at runtime. The report is
+//! rendered as a compact Markdown message so operators can check on the
+//! daemon from a phone; `/readyz` reports the same checks per dependency.
+//!
+//! Runtime errors are collected through [`record_error`], which any module
+//! can call without holding a handle to shared state.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use intercom_core::{CircuitState, DemarchAdapter, SharedStorage};
+use serde::Serialize;
+use tokio::sync::watch;
+use tracing::debug;
+
+use crate::container::executor::ContainerExecutor;
+use crate::queue::QueueSnapshot;
+use crate::telegram::TelegramBridge;
+
+/// Upper bound for any single probe so a hung dependency can't stall the reply.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest error text shown per subsystem in chat.
+const MAX_ERROR_CHARS: usize = 120;
+
+/// Storage answering slower than this is a warning.
+const SLOW_STORAGE: Duration = Duration::from_millis(500);
+
+pub const SUBSYSTEM_DB: &str = "db";
+pub const SUBSYSTEM_DOCKER: &str = "docker";
+pub const SUBSYSTEM_TELEGRAM: &str = "telegram";
+pub const SUBSYSTEM_DEMARCH: &str = "demarch";
+pub const SUBSYSTEM_IPC: &str = "ipc";
+
+// ---------------------------------------------------------------------------
+// Last-error registry
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+pub struct LastError {
+    pub message: String,
+    pub at: Instant,
+}
+
+fn registry() -> &'static Mutex<HashMap<&'static str, LastError>> {
+    static LAST_ERRORS: OnceLock<Mutex<HashMap<&'static str, LastError>>> = OnceLock::new();
+    LAST_ERRORS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Remember the most recent failure for a subsystem.
+pub fn record_error(subsystem: &'static str, err: impl Display) {
+    let entry = LastError {
+        message: err.to_string(),
+        at: Instant::now(),
+    };
+    if let Ok(mut map) = registry().lock() {
+        map.insert(subsystem, entry);
+    }
+}
+
+pub fn last_error(subsystem: &str) -> Option<LastError> {
+    registry().lock().ok()?.get(subsystem).cloned()
+}
+
+// ---------------------------------------------------------------------------
+// Checks
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+    /// Subsystem is not configured.
+    Off,
+}
+
+impl CheckStatus {
+    fn icon(self) -> &'static str {
+        match self {
+            Self::Ok => "✅",
+            Self::Warn => "⚠️",
+            Self::Fail => "❌",
+            Self::Off => "⏸",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub subsystem: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// How long the probe took, for probes where that matters.
+    pub latency: Option<Duration>,
+    pub last_error: Option<LastError>,
+}
+
+impl CheckResult {
+    fn new(subsystem: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            subsystem,
+            status,
+            detail: detail.into(),
+            latency: None,
+            last_error: last_error(subsystem),
+        }
+    }
+
+    /// Build a result from a probe, recording failures in the registry.
+    fn from_probe(
+        subsystem: &'static str,
+        outcome: anyhow::Result<String>,
+        fail_status: CheckStatus,
+    ) -> Self {
+        match outcome {
+            Ok(detail) => Self::new(subsystem, CheckStatus::Ok, detail),
+            Err(e) => {
+                record_error(subsystem, &e);
+                Self::new(subsystem, fail_status, "unreachable")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub checks: Vec<CheckResult>,
+    pub queue: QueueSnapshot,
+    pub uptime: Duration,
+}
+
+impl HealthReport {
+    pub fn overall(&self) -> CheckStatus {
+        if self.checks.iter().any(|c| c.status == CheckStatus::Fail) {
+            CheckStatus::Fail
+        } else if self.checks.iter().any(|c| c.status == CheckStatus::Warn) {
+            CheckStatus::Warn
+        } else {
+            CheckStatus::Ok
+        }
+    }
+}
+
+async fn with_timeout<F>(probe: F) -> anyhow::Result<String>
+where
+    F: Future<Output = anyhow::Result<String>>,
+{
+    tokio::time::timeout(CHECK_TIMEOUT, probe)
+        .await
+        .unwrap_or_else(|_| {
+            Err(anyhow::anyhow!(
+                "timed out after {}s",
+                CHECK_TIMEOUT.as_secs()
+            ))
+        })
+}
+
+async fn check_db(db: Option<&SharedStorage>) -> CheckResult {
+    let Some(db) = db else {
+        return CheckResult::new(SUBSYSTEM_DB, CheckStatus::Off, "not configured");
+    };
+    let backend = db.backend();
+    let started = Instant::now();
+    let outcome = with_timeout(async {
+        db.get_router_state("last_timestamp").await?;
+        Ok(backend.to_string())
+    })
+    .await;
+    let latency = started.elapsed();
+    let mut result = CheckResult::from_probe(SUBSYSTEM_DB, outcome, CheckStatus::Fail);
+    if result.status == CheckStatus::Ok {
+        result.latency = Some(latency);
+        if latency > SLOW_STORAGE {
+            result.status = CheckStatus::Warn;
+            result.detail = format!("{backend}, slow ({}ms)", latency.as_millis());
+        }
+    }
+    if let Some(circuit) = db.circuit().filter(|c| c.state != CircuitState::Closed) {
+        if result.status == CheckStatus::Ok {
+            result.status = CheckStatus::Warn;
+        }
+        result.detail = match circuit.retry_in_ms {
+            Some(ms) => format!(
+                "circuit {}, retry in {}s",
+                circuit.state.as_str(),
+                ms.div_ceil(1000)
+            ),
+            None => format!("circuit {}", circuit.state.as_str()),
+        };
+    }
+    result
+}
+
+/// Periodically probe storage so a dropped connection is re-established in
+/// the background instead of on the next caller's request.
+pub async fn storage_monitor_loop(
+    db: SharedStorage,
+    interval: Duration,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.changed() => break,
+        }
+        if let Err(e) = db.health_check().await {
+            debug!(err = %format!("{e:#}"), "storage health check failed");
+            record_error(SUBSYSTEM_DB, format!("{e:#}"));
+        }
+    }
+}
+
+async fn check_docker(executor: &dyn ContainerExecutor) -> CheckResult {
+    let outcome = with_timeout(async {
+        executor.probe().await?;
+        Ok("running".to_string())
+    })
+    .await;
+    CheckResult::from_probe(SUBSYSTEM_DOCKER, outcome, CheckStatus::Fail)
+}
+
+async fn check_telegram(telegram: &TelegramBridge) -> CheckResult {
+    if !telegram.is_enabled() {
+        return CheckResult::new(SUBSYSTEM_TELEGRAM, CheckStatus::Off, "no bot token");
+    }
+    let outcome = with_timeout(async { Ok(format!("@{}", telegram.get_me().await?)) }).await;
+    CheckResult::from_probe(SUBSYSTEM_TELEGRAM, outcome, CheckStatus::Fail)
+}
+
+async fn check_demarch(demarch: &DemarchAdapter) -> CheckResult {
+    if !demarch.is_enabled() {
+        return CheckResult::new(SUBSYSTEM_DEMARCH, CheckStatus::Off, "disabled");
+    }
+    if demarch.backend_kind() == "mock" {
+        return CheckResult::new(
+            SUBSYSTEM_DEMARCH,
+            CheckStatus::Warn,
+            "mock backend (canned data)",
+        );
+    }
+    let demarch = demarch.clone();
+    let available = tokio::task::spawn_blocking(move || demarch.kernel_available())
+        .await
+        .unwrap_or(false);
+    if available {
+        CheckResult::new(SUBSYSTEM_DEMARCH, CheckStatus::Ok, "kernel available")
+    } else {
+        // Standalone mode is supported, so a missing kernel is not a failure.
+        CheckResult::new(
+            SUBSYSTEM_DEMARCH,
+            CheckStatus::Warn,
+            "standalone (no ic CLI)",
+        )
+    }
+}
+
+/// Containers read and write their requests under the IPC directory, so
+/// it must take a new file.
+async fn check_ipc(dir: &Path) -> CheckResult {
+    let dir = dir.to_path_buf();
+    let outcome = tokio::task::spawn_blocking(move || {
+        let probe = dir.join(format!(".health-probe-{}", std::process::id()));
+        std::fs::write(&probe, b"ok")
+            .with_context(|| format!("{} is not writable", dir.display()))?;
+        let _ = std::fs::remove_file(&probe);
+        Ok("writable".to_string())
+    })
+    .await
+    .unwrap_or_else(|e| Err(anyhow::anyhow!("probe panicked: {e}")));
+    let mut result = CheckResult::from_probe(SUBSYSTEM_IPC, outcome, CheckStatus::Fail);
+    if result.status == CheckStatus::Fail {
+        result.detail = "not writable".into();
+    }
+    result
+}
+
+/// Run all checks concurrently.
+pub async fn run_checks(
+    db: Option<&SharedStorage>,
+    telegram: &TelegramBridge,
+    demarch: &DemarchAdapter,
+    executor: &dyn ContainerExecutor,
+    ipc_dir: &Path,
+    queue: QueueSnapshot,
+    started_at: Instant,
+) -> HealthReport {
+    let (db, docker, ipc, telegram, demarch) = tokio::join!(
+        check_db(db),
+        check_docker(executor),
+        check_ipc(ipc_dir),
+        check_telegram(telegram),
+        check_demarch(demarch),
+    );
+    HealthReport {
+        checks: vec![db, docker, ipc, telegram, demarch],
+        queue,
+        uptime: started_at.elapsed(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Readiness
+// ---------------------------------------------------------------------------
+
+/// How long `/readyz` reuses a round of probes. Orchestrators poll it every
+/// few seconds, and each round calls Telegram and the container CLI.
+const READINESS_TTL: Duration = Duration::from_secs(10);
+
+/// A dependency's state as `/readyz` reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Readiness {
+    Ok,
+    Degraded,
+    Down,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyHealth {
+    pub status: Readiness,
+    /// The daemon is down while a required dependency is; any other
+    /// dependency being down only degrades it.
+    pub required: bool,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl DependencyHealth {
+    /// `None` for a subsystem that is not configured.
+    fn from_check(check: &CheckResult, required: bool) -> Option<Self> {
+        let status = match check.status {
+            CheckStatus::Ok => Readiness::Ok,
+            CheckStatus::Warn => Readiness::Degraded,
+            CheckStatus::Fail => Readiness::Down,
+            CheckStatus::Off => return None,
+        };
+        Some(Self {
+            status,
+            required,
+            detail: check.detail.clone(),
+            latency_ms: check.latency.map(|l| l.as_millis() as u64),
+            last_error: check.last_error.as_ref().map(|e| e.message.clone()),
+        })
+    }
+}
+
+pub type Dependencies = BTreeMap<&'static str, DependencyHealth>;
+
+/// The worst dependency, counting a down one that is not required as
+/// degraded.
+pub fn overall_readiness(dependencies: &Dependencies) -> Readiness {
+    dependencies
+        .values()
+        .map(|dep| match dep.status {
+            Readiness::Down if !dep.required => Readiness::Degraded,
+            status => status,
+        })
+        .max()
+        .unwrap_or(Readiness::Ok)
+}
+
+/// What `/readyz` probes. Without an orchestrator no container runs, so
+/// the container engine and IPC directory are left out.
+pub struct ReadinessTargets<'a> {
+    pub db: Option<&'a SharedStorage>,
+    pub telegram: &'a TelegramBridge,
+    pub demarch: &'a DemarchAdapter,
+    pub containers: Option<(&'a dyn ContainerExecutor, PathBuf)>,
+}
+
+/// The last round of readiness probes, reused for [`READINESS_TTL`].
+#[derive(Default)]
+pub struct ReadinessProbe {
+    last: tokio::sync::Mutex<Option<(Instant, Dependencies)>>,
+}
+
+impl ReadinessProbe {
+    /// Probe the dependencies, or return the last round if it is recent.
+    /// Concurrent callers wait for one round rather than each probing.
+    pub async fn check(&self, targets: ReadinessTargets<'_>) -> Dependencies {
+        let mut last = self.last.lock().await;
+        if let Some((at, dependencies)) = last.as_ref()
+            && at.elapsed() < READINESS_TTL
+        {
+            return dependencies.clone();
+        }
+        let containers = async {
+            match &targets.containers {
+                Some((executor, ipc_dir)) => {
+                    let (docker, ipc) = tokio::join!(check_docker(*executor), check_ipc(ipc_dir));
+                    vec![docker, ipc]
+                }
+                None => vec![],
+            }
+        };
+        let (db, telegram, demarch, containers) = tokio::join!(
+            check_db(targets.db),
+            check_telegram(targets.telegram),
+            check_demarch(targets.demarch),
+            containers,
+        );
+        let checks = [(db, true), (telegram, false), (demarch, false)]
+            .into_iter()
+            .chain(containers.into_iter().map(|check| (check, true)));
+        let dependencies: Dependencies = checks
+            .filter_map(|(check, required)| {
+                DependencyHealth::from_check(&check, required).map(|dep| (check.subsystem, dep))
+            })
+            .collect();
+        *last = Some((Instant::now(), dependencies.clone()));
+        dependencies
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Formatting
+// ---------------------------------------------------------------------------
+
+fn format_ago(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs < 60 {
+        format!("{secs}s ago")
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 86_400 {
+        format!("{}h ago", secs / 3600)
+    } else {
+        format!("{}d ago", secs / 86_400)
+    }
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    let single_line = text.replace('\n', " ");
+    if single_line.chars().count() <= max {
+        return single_line;
+    }
+    let mut out: String = single_line.chars().take(max).collect();
+    out.push('…');
+    out
+}
+
+/// Render the report as a Telegram Markdown message.
+pub fn format_report(report: &HealthReport, now: Instant) -> String {
+    let overall = match report.overall() {
+        CheckStatus::Fail => "degraded",
+        CheckStatus::Warn => "ok (warnings)",
+        _ => "ok",
+    };
+    let uptime_min = report.uptime.as_secs() / 60;
+    let mut lines = vec![
+        format!("*Health: {overall}*"),
+        format!("Uptime: {}h {}m", uptime_min / 60, uptime_min % 60),
+        String::new(),
+    ];
+
+    for check in &report.checks {
+        lines.push(format!(
+            "{} {}: {}",
+            check.status.icon(),
+            check.subsystem,
+            check.detail
+        ));
+        if let Some(ref err) = check.last_error {
+            lines.push(format!(
+                "    last error {}: `{}`",
+                format_ago(now.saturating_duration_since(err.at)),
+                truncate_chars(&err.message, MAX_ERROR_CHARS).replace('`', "'")
+            ));
+        }
+    }
+
+    let q = &report.queue;
+    lines.push(String::new());
+    lines.push(format!(
+        "Queue: {}/{} active, {} waiting, {} tasks pending",
+        q.active, q.max_concurrent, q.waiting_groups, q.pending_tasks
+    ));
+    if q.paused {
+        lines.push("Queue paused: no new containers start until resumed".to_string());
+    }
+    if !q.interactive_wait.is_zero() {
+        lines.push(format!(
+            "Longest message wait: {}s",
+            q.interactive_wait.as_secs()
+        ));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(checks: Vec<CheckResult>) -> HealthReport {
+        HealthReport {
+            checks,
+            queue: QueueSnapshot {
+                active: 1,
+                max_concurrent: 3,
+                paused: false,
+                waiting_groups: 2,
+                pending_tasks: 4,
+                interactive_wait: Duration::from_secs(42),
+            },
+            uptime: Duration::from_secs(3_660),
+        }
+    }
+
+    fn check(subsystem: &'static str, status: CheckStatus) -> CheckResult {
+        CheckResult {
+            subsystem,
+            status,
+            detail: "detail".into(),
+            latency: None,
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn overall_is_worst_check() {
+        let ok = report(vec![
+            check("db", CheckStatus::Ok),
+            check("demarch", CheckStatus::Off),
+        ]);
+        assert_eq!(ok.overall(), CheckStatus::Ok);
+        let warn = report(vec![
+            check("db", CheckStatus::Ok),
+            check("demarch", CheckStatus::Warn),
+        ]);
+        assert_eq!(warn.overall(), CheckStatus::Warn);
+        let fail = report(vec![
+            check("db", CheckStatus::Fail),
+            check("demarch", CheckStatus::Warn),
+        ]);
+        assert_eq!(fail.overall(), CheckStatus::Fail);
+    }
+
+    #[test]
+    fn format_report_includes_queue_and_last_error() {
+        let now = Instant::now();
+        let mut db = check("db", CheckStatus::Fail);
+        db.last_error = Some(LastError {
+            message: "connection `refused`\nretrying".into(),
+            at: now - Duration::from_secs(300),
+        });
+        let text = format_report(&report(vec![db, check("docker", CheckStatus::Ok)]), now);
+        assert!(text.starts_with("*Health: degraded*"));
+        assert!(text.contains("Uptime: 1h 1m"));
+        assert!(text.contains("❌ db: detail"));
+        assert!(text.contains("last error 5m ago: `connection 'refused' retrying`"));
+        assert!(text.contains("✅ docker: detail"));
+        assert!(text.contains("Queue: 1/3 active, 2 waiting, 4 tasks pending"));
+        assert!(text.contains("Longest message wait: 42s"));
+    }
+
+    #[test]
+    fn record_error_is_visible_to_checks() {
+        record_error("health-test", "boom");
+        let result = CheckResult::new("health-test", CheckStatus::Ok, "fine");
+        assert_eq!(result.last_error.unwrap().message, "boom");
+    }
+
+    #[test]
+    fn readiness_counts_optional_dependencies_as_degraded() {
+        let deps = |checks: &[(&'static str, CheckStatus, bool)]| -> Dependencies {
+            checks
+                .iter()
+                .filter_map(|&(name, status, required)| {
+                    DependencyHealth::from_check(&check(name, status), required)
+                        .map(|dep| (name, dep))
+                })
+                .collect()
+        };
+        assert_eq!(overall_readiness(&Dependencies::new()), Readiness::Ok);
+        let off = deps(&[
+            ("db", CheckStatus::Ok, true),
+            ("telegram", CheckStatus::Off, false),
+        ]);
+        assert_eq!(off.len(), 1);
+        assert_eq!(overall_readiness(&off), Readiness::Ok);
+        let optional = deps(&[
+            ("db", CheckStatus::Ok, true),
+            ("telegram", CheckStatus::Fail, false),
+        ]);
+        assert_eq!(overall_readiness(&optional), Readiness::Degraded);
+        let slow = deps(&[
+            ("db", CheckStatus::Warn, true),
+            ("demarch", CheckStatus::Ok, false),
+        ]);
+        assert_eq!(overall_readiness(&slow), Readiness::Degraded);
+        let down = deps(&[
+            ("db", CheckStatus::Ok, true),
+            ("ipc", CheckStatus::Fail, true),
+        ]);
+        assert_eq!(overall_readiness(&down), Readiness::Down);
+    }
+
+    #[tokio::test]
+    async fn ipc_check_needs_a_writable_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let ok = check_ipc(dir.path()).await;
+        assert_eq!(ok.status, CheckStatus::Ok);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+        let missing = check_ipc(&dir.path().join("missing")).await;
+        assert_eq!(missing.status, CheckStatus::Fail);
+        assert!(missing.last_error.unwrap().message.contains("not writable"));
+    }
+
+    #[test]
+    fn truncate_chars_limits_long_errors() {
+        assert_eq!(truncate_chars("short", 10), "short");
+        assert_eq!(truncate_chars("abcdefghijkl", 5), "abcde…");
+    }
+}
@@ -0,0 +1,131 @@
+//! Message catalogs for command replies and chat notices.
+//!
+//! Each locale is a flat JSON object of `key -> text` under `locales/`,
+//! embedded in the binary. Text may hold `{name}` placeholders, filled in by
+//! [`fill`]. A key missing from a locale falls back to English, so a partial
+//! catalog still reads as a whole reply.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+const CATALOGS: [(&str, &str); 3] = [
+    ("en", include_str!("../locales/en.json")),
+    ("de", include_str!("../locales/de.json")),
+    ("es", include_str!("../locales/es.json")),
+];
+
+type Catalog = HashMap<String, String>;
+
+fn catalogs() -> &'static HashMap<&'static str, Catalog> {
+    static PARSED: OnceLock<HashMap<&'static str, Catalog>> = OnceLock::new();
+    PARSED.get_or_init(|| {
+        CATALOGS
+            .iter()
+            .map(|(code, json)| {
+                let catalog = serde_json::from_str(json)
+                    .unwrap_or_else(|e| panic!("locales/{code}.json: {e}"));
+                (*code, catalog)
+            })
+            .collect()
+    })
+}
+
+/// Locale codes with a catalog, English first.
+pub fn supported() -> impl Iterator<Item = &'static str> {
+    CATALOGS.iter().map(|(code, _)| *code)
+}
+
+/// The catalog for `code`, matching `de`, `DE` or `de-AT` to `de`.
+pub fn resolve(code: &str) -> Option<&'static str> {
+    let language = code
+        .trim()
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    supported().find(|supported| *supported == language)
+}
+
+/// The catalog locale for a group's stored setting; English when unset or
+/// no longer supported.
+pub fn locale_or_default(code: Option<&str>) -> &'static str {
+    code.and_then(resolve).unwrap_or(DEFAULT_LOCALE)
+}
+
+/// `key` in `locale`, else in English.
+pub fn lookup(locale: &str, key: &str) -> Option<&'static str> {
+    let catalogs = catalogs();
+    [locale, DEFAULT_LOCALE]
+        .into_iter()
+        .find_map(|code| catalogs.get(code)?.get(key))
+        .map(String::as_str)
+}
+
+/// `key` in `locale`, or the key itself when no catalog has it.
+pub fn text<'a>(locale: &str, key: &'a str) -> &'a str {
+    lookup(locale, key).unwrap_or(key)
+}
+
+/// [`text`] with each `{name}` replaced by its value.
+pub fn fill(locale: &str, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut text = text(locale, key).to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = text
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn catalogs_match_english() {
+        let english = &catalogs()[DEFAULT_LOCALE];
+        for code in supported() {
+            for (key, text) in &catalogs()[code] {
+                let Some(source) = english.get(key) else {
+                    panic!("{code}: {key} is not in the English catalog");
+                };
+                assert_eq!(placeholders(text), placeholders(source), "{code}: {key}");
+            }
+            assert_eq!(
+                catalogs()[code].len(),
+                english.len(),
+                "{code} is missing keys"
+            );
+        }
+    }
+
+    #[test]
+    fn lookups_fall_back_to_english() {
+        assert_eq!(resolve("de-AT"), Some("de"));
+        assert_eq!(resolve(" ES "), Some("es"));
+        assert_eq!(resolve("fr"), None);
+        assert_eq!(locale_or_default(Some("fr")), "en");
+        assert_eq!(locale_or_default(None), "en");
+
+        assert_eq!(
+            fill("de", "cancel.done", &[("id", &"t-1")]),
+            "Aufgabe t-1 abgebrochen."
+        );
+        assert_eq!(
+            fill("fr", "cancel.done", &[("id", &"t-1")]),
+            "Cancelled task t-1."
+        );
+        assert_eq!(text("de", "no.such.key"), "no.such.key");
+    }
+}
@@ -4,32 +4,122 @@
 //! Processes files atomically (read → act → unlink), moving failures to an
 //! `errors/` directory for debugging.
 //!
+//! Scaling: groups are hashed onto `shards` queues, each drained by its own
+//! worker on a staggered timer. A worker that empties its queue steals from
+//! the longest other one, so one slow group never stalls the rest. A
+//! subdirectory whose mtime has not changed since it was last drained is not
+//! listed again; a full rescan every `rescan_interval` covers anything that
+//! slips past the mtime check.
+//!
+//! Successful answers to Demarch read queries are cached for
+//! `query_cache_ttl`, keyed by query type and params, so a container asking
+//! the same thing repeatedly does not shell out to `ic`/`bd` each time.
+//! A write query drops the cache. Answers over `response_chunk_bytes` are
+//! written as a chain of part files, and cut at `response_max_bytes`.
+//!
+//! Task commands (schedule, pause, resume, cancel) are applied to storage
+//! by [`NativeTasks`] when it is set, and the outcome is written to
+//! `{group}/responses/{task file name}` as an ack. Without it, and for group
+//! registration commands, they are forwarded to the Node host.
+//!
+//! A `broadcast` message goes to every registered group or to a list of
+//! folders. Each target is authorized on its own, and what was delivered
+//! and refused is written to `{group}/responses/{message file name}`.
+//!
+//! A `mail` message is left in another group's `{group}/inbox/` for its
+//! agent to read, if that group's `acceptMailFrom` lists the sender; the
+//! sender gets an ack in its `responses/`.
+//!
 //! Authorization model:
 //! - Main group can send messages to any chat and manage any task.
-//! - Non-main groups can only send to their own registered chat JID.
+//! - Non-main groups can only send to their own registered chat JID, and
+//!   only schedule and manage their own tasks.
 //! - Demarch query authorization delegated to DemarchAdapter (allowlist + is_main).
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
+use anyhow::{anyhow, bail};
 use intercom_core::{
-    DemarchAdapter, IpcGroupContext, IpcMessage, IpcQuery, IpcQueryResponse, IpcTask,
-    ReadOperation, WriteOperation,
+    BroadcastTarget, CAPABILITIES_FILE, DemarchAdapter, IPC_PROTOCOL_VERSION, IpcCapabilities,
+    IpcGroupContext, IpcMessage, IpcQuery, IpcQueryResponse, IpcTask, ReadOperation,
+    RegisteredGroup, ScheduledTask, SharedStorage, WriteOperation,
 };
 use tracing::{debug, error, info, warn};
 
+use crate::scheduler::{self, TaskControl};
+
 const MAIN_GROUP_FOLDER: &str = "main";
 
+/// Read-only Demarch queries whose answers may be cached.
+const CACHED_QUERIES: &[&str] = &[
+    "run_status",
+    "sprint_phase",
+    "search_beads",
+    "spec_lookup",
+    "review_summary",
+    "next_work",
+    "run_events",
+    "list_gates",
+    "issue_comments",
+    "issue_history",
+    "run_diff",
+    "batch",
+];
+
+/// Demarch queries that change state; main group only.
+const WRITE_QUERIES: &[&str] = &[
+    "create_issue",
+    "update_issue",
+    "close_issue",
+    "start_run",
+    "approve_gate",
+];
+
+const MESSAGE_TYPES: &[&str] = &["message", "broadcast", "mail"];
+
+const TASK_TYPES: &[&str] = &[
+    "schedule_task",
+    "pause_task",
+    "resume_task",
+    "cancel_task",
+    "refresh_groups",
+    "register_group",
+];
+
+/// Error text for a payload type this host does not know, pointing newer
+/// agents at the capabilities file.
+fn unsupported(kind: &str, name: &str, version: Option<u32>) -> String {
+    let sent = version
+        .map(|v| format!("; sent as protocol {v}"))
+        .unwrap_or_default();
+    format!(
+        "Unknown {kind} type: {name} (intercomd speaks IPC protocol {IPC_PROTOCOL_VERSION}{sent}; \
+         supported types are listed in {CAPABILITIES_FILE})"
+    )
+}
+
 /// Configuration for the IPC watcher.
 #[derive(Debug, Clone)]
 pub struct IpcWatcherConfig {
     /// Base directory for IPC files (e.g., `data/ipc`).
     pub ipc_base_dir: PathBuf,
-    /// Poll interval.
+    /// Poll interval, per shard.
     pub poll_interval: Duration,
+    /// Number of shard workers.
+    pub shards: usize,
+    /// How often the mtime cache is dropped and every directory listed.
+    pub rescan_interval: Duration,
+    /// How long a read query's answer is reused; zero disables caching.
+    pub query_cache_ttl: Duration,
+    /// Split query results larger than this into parts; zero never splits.
+    pub response_chunk_bytes: usize,
+    /// Cut query results to this size; zero for no limit.
+    pub response_max_bytes: usize,
 }
 
 impl Default for IpcWatcherConfig {
@@ -37,10 +127,50 @@ impl Default for IpcWatcherConfig {
         Self {
             ipc_base_dir: PathBuf::from("data/ipc"),
             poll_interval: Duration::from_secs(1),
+            shards: 4,
+            rescan_interval: Duration::from_secs(60),
+            query_cache_ttl: Duration::ZERO,
+            response_chunk_bytes: 0,
+            response_max_bytes: 0,
         }
     }
 }
 
+impl IpcWatcherConfig {
+    pub fn from_config(ipc_base_dir: PathBuf, config: &intercom_core::IpcConfig) -> Self {
+        Self {
+            ipc_base_dir,
+            poll_interval: Duration::from_millis(config.poll_interval_ms.max(10)),
+            shards: config.shards.max(1),
+            rescan_interval: Duration::from_millis(config.rescan_interval_ms),
+            query_cache_ttl: Duration::from_millis(config.query_cache_ttl_ms),
+            response_chunk_bytes: config.response_chunk_bytes,
+            response_max_bytes: config.response_max_bytes,
+        }
+    }
+}
+
+/// A directory's mtime is only trusted once it is this old, so a write in the
+/// same timestamp tick as the last listing is never missed on filesystems
+/// with coarse mtimes.
+const MTIME_SETTLE: Duration = Duration::from_secs(2);
+
+/// Per-shard queues of group folders waiting to be processed.
+#[derive(Default)]
+struct ShardQueues {
+    queues: Vec<VecDeque<String>>,
+    /// Folders queued or in progress, so no group is handled twice at once.
+    claimed: HashSet<String>,
+}
+
+/// Stable shard for a group folder (FNV-1a).
+fn shard_for(folder: &str, shards: usize) -> usize {
+    let hash = folder.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    (hash % shards.max(1) as u64) as usize
+}
+
 /// Callback trait for IPC actions that need the Node host.
 ///
 /// During the strangler-fig migration, some IPC actions (sending messages,
@@ -68,6 +198,7 @@ pub trait IpcDelegate: Send + Sync {
 
 /// No-op delegate that logs actions without forwarding to Node.
 /// Used when intercomd runs standalone (no Node host).
+#[allow(dead_code)] // `serve` always has a host to forward to for now
 pub struct LogOnlyDelegate;
 
 impl IpcDelegate for LogOnlyDelegate {
@@ -111,12 +242,25 @@ impl HttpDelegate {
 
 impl IpcDelegate for HttpDelegate {
     fn send_message(&self, chat_jid: &str, text: &str, sender: Option<&str>) {
+        self.send_message_with_buttons(chat_jid, text, sender, None);
+    }
+
+    fn send_message_with_buttons(
+        &self,
+        chat_jid: &str,
+        text: &str,
+        sender: Option<&str>,
+        reply_markup: Option<crate::telegram::InlineKeyboardMarkup>,
+    ) {
         let url = format!("{}/v1/ipc/send-message", self.base_url);
-        let body = serde_json::json!({
+        let mut body = serde_json::json!({
             "chat_jid": chat_jid,
             "text": text,
             "sender": sender,
         });
+        if let Some(markup) = reply_markup {
+            body["reply_markup"] = serde_json::to_value(markup).unwrap_or_default();
+        }
 
         // Fire-and-forget via blocking spawn — IPC delegate is called from sync code.
         // The HTTP call is best-effort; if Node is down, message is lost (same as Node IPC).
@@ -162,15 +306,208 @@ impl IpcDelegate for HttpDelegate {
     }
 }
 
+/// Applies task commands to storage in place of the Node host.
+pub struct NativeTasks {
+    pool: SharedStorage,
+    groups: Arc<tokio::sync::RwLock<HashMap<String, RegisteredGroup>>>,
+    timezone: String,
+    /// The watcher runs on blocking threads; storage calls run here.
+    runtime: tokio::runtime::Handle,
+    next_id: AtomicU64,
+}
+
+impl NativeTasks {
+    /// Must be called from within the runtime.
+    pub fn new(
+        pool: SharedStorage,
+        groups: Arc<tokio::sync::RwLock<HashMap<String, RegisteredGroup>>>,
+        timezone: impl Into<String>,
+    ) -> Self {
+        Self {
+            pool,
+            groups,
+            timezone: timezone.into(),
+            runtime: tokio::runtime::Handle::current(),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Carry out `task` for the group in `ctx`. `None` for commands that
+    /// are left to the host.
+    fn handle(&self, task: &IpcTask, ctx: &IpcGroupContext) -> Option<IpcQueryResponse> {
+        if matches!(
+            task,
+            IpcTask::RefreshGroups { .. } | IpcTask::RegisterGroup { .. }
+        ) {
+            return None;
+        }
+        Some(match self.runtime.block_on(self.apply(task, ctx)) {
+            Ok(done) => IpcQueryResponse::ok(done),
+            Err(e) => IpcQueryResponse::error(format!("{e:#}")),
+        })
+    }
+
+    async fn apply(&self, task: &IpcTask, ctx: &IpcGroupContext) -> anyhow::Result<String> {
+        let (task_id, control) = match task {
+            IpcTask::ScheduleTask {
+                prompt,
+                schedule_type,
+                schedule_value,
+                context_mode,
+                target_jid,
+                ..
+            } => {
+                return self
+                    .schedule(
+                        ctx,
+                        prompt,
+                        schedule_type,
+                        schedule_value,
+                        context_mode,
+                        target_jid.as_deref(),
+                    )
+                    .await;
+            }
+            IpcTask::PauseTask { task_id, .. } => {
+                self.owned_task(task_id, ctx).await?;
+                (task_id, scheduler::pause_task(&self.pool, task_id).await?)
+            }
+            IpcTask::ResumeTask { task_id, .. } => {
+                self.owned_task(task_id, ctx).await?;
+                (
+                    task_id,
+                    scheduler::resume_task(&self.pool, task_id, &self.timezone).await?,
+                )
+            }
+            IpcTask::CancelTask { task_id, .. } => {
+                self.owned_task(task_id, ctx).await?;
+                self.pool.delete_task(task_id).await?;
+                info!(task_id = %task_id, group = %ctx.group_folder, "Task cancelled via IPC");
+                return Ok(format!("Cancelled task {task_id}"));
+            }
+            IpcTask::RefreshGroups { .. } | IpcTask::RegisterGroup { .. } => {
+                bail!("group commands are handled by the host")
+            }
+        };
+        match control {
+            TaskControl::Done(task) => {
+                info!(task_id = %task.id, status = %task.status, group = %ctx.group_folder, "Task updated via IPC");
+                Ok(format!("Task {} is {}", task.id, task.status))
+            }
+            TaskControl::NotFound => Err(anyhow!("no task {task_id}")),
+            TaskControl::Completed => Err(anyhow!("task {task_id} has completed")),
+        }
+    }
+
+    /// The task, if the group may manage it: main manages any task, other
+    /// groups only their own.
+    async fn owned_task(
+        &self,
+        task_id: &str,
+        ctx: &IpcGroupContext,
+    ) -> anyhow::Result<ScheduledTask> {
+        let task = self
+            .pool
+            .get_task_by_id(task_id)
+            .await?
+            .ok_or_else(|| anyhow!("no task {task_id}"))?;
+        if !ctx.is_main && task.group_folder != ctx.group_folder {
+            warn!(task_id, group = %ctx.group_folder, "Unauthorized IPC task command blocked");
+            bail!("task {task_id} belongs to another group");
+        }
+        Ok(task)
+    }
+
+    async fn schedule(
+        &self,
+        ctx: &IpcGroupContext,
+        prompt: &str,
+        schedule_type: &str,
+        schedule_value: &str,
+        context_mode: &str,
+        target_jid: Option<&str>,
+    ) -> anyhow::Result<String> {
+        let (chat_jid, folder) = {
+            let groups = self.groups.read().await;
+            let target = match target_jid {
+                Some(jid) => groups.get_key_value(jid),
+                None => groups.iter().find(|(_, g)| g.folder == ctx.group_folder),
+            };
+            let (jid, group) = target.ok_or_else(|| {
+                anyhow!(
+                    "{} is not a registered group",
+                    target_jid.unwrap_or(&ctx.group_folder)
+                )
+            })?;
+            (jid.clone(), group.folder.clone())
+        };
+        if !ctx.is_main && folder != ctx.group_folder {
+            warn!(target = %chat_jid, group = %ctx.group_folder, "Unauthorized IPC schedule_task blocked");
+            bail!("{} may only schedule tasks for itself", ctx.group_folder);
+        }
+        if prompt.trim().is_empty() {
+            bail!("prompt is empty");
+        }
+        let schedule = scheduler::validate_schedule(schedule_type, schedule_value, &self.timezone)
+            .map_err(|e| anyhow!(e))?;
+        let now = chrono::Utc::now();
+        let task = ScheduledTask {
+            id: format!(
+                "task-{}-{}",
+                now.timestamp_millis(),
+                self.next_id.fetch_add(1, Ordering::Relaxed)
+            ),
+            group_folder: folder,
+            chat_jid,
+            prompt: prompt.to_string(),
+            schedule_type: schedule_type.to_string(),
+            schedule_value: schedule_value.to_string(),
+            context_mode: if context_mode == "group" {
+                "group"
+            } else {
+                "isolated"
+            }
+            .to_string(),
+            next_run: scheduler::first_run(&schedule, &self.timezone, now),
+            last_run: None,
+            last_result: None,
+            status: "active".into(),
+            created_at: now.to_rfc3339(),
+            task_config: None,
+        };
+        self.pool.create_task(&task).await?;
+        info!(task_id = %task.id, group = %task.group_folder, next_run = ?task.next_run, "Task scheduled via IPC");
+        Ok(match &task.next_run {
+            Some(next) => format!("Scheduled task {}; next run {next}", task.id),
+            None => format!("Scheduled task {}", task.id),
+        })
+    }
+}
+
 /// The IPC watcher. Owns polling state and dispatches to DemarchAdapter + delegate.
 pub struct IpcWatcher {
     config: IpcWatcherConfig,
     demarch: Arc<DemarchAdapter>,
     delegate: Arc<dyn IpcDelegate>,
+    /// Applies task commands itself instead of forwarding them.
+    native_tasks: Option<NativeTasks>,
+    /// Registered groups, for mail allowlists; mail is refused without it.
+    groups: Option<Arc<tokio::sync::RwLock<HashMap<String, RegisteredGroup>>>>,
+    /// Keeps inbox file names unique within a millisecond.
+    mail_seq: AtomicU64,
+    /// Groups whose capabilities file was written since the last rescan.
+    advertised: Mutex<HashSet<String>>,
     registry: GroupRegistry,
+    shards: Mutex<ShardQueues>,
+    /// mtime of each subdirectory as of when it was last drained.
+    drained: Mutex<HashMap<PathBuf, SystemTime>>,
+    last_rescan: Mutex<Instant>,
+    /// Read query answers by query key, with when they were computed.
+    query_cache: Mutex<HashMap<String, (Instant, IpcQueryResponse)>>,
 }
 
 impl IpcWatcher {
+    #[cfg(test)]
     pub fn new(
         config: IpcWatcherConfig,
         demarch: Arc<DemarchAdapter>,
@@ -185,36 +522,155 @@ impl IpcWatcher {
         delegate: Arc<dyn IpcDelegate>,
         registry: GroupRegistry,
     ) -> Self {
+        let shard_count = config.shards.max(1);
         Self {
             config,
             demarch,
             delegate,
+            native_tasks: None,
+            groups: None,
+            mail_seq: AtomicU64::new(0),
+            advertised: Mutex::new(HashSet::new()),
             registry,
+            shards: Mutex::new(ShardQueues {
+                queues: vec![VecDeque::new(); shard_count],
+                claimed: HashSet::new(),
+            }),
+            drained: Mutex::new(HashMap::new()),
+            last_rescan: Mutex::new(Instant::now()),
+            query_cache: Mutex::new(HashMap::new()),
         }
     }
 
-    /// Run the IPC polling loop. Call from a tokio::spawn.
-    pub async fn run(&self, mut shutdown: tokio::sync::watch::Receiver<bool>) {
+    /// Apply task commands with `native` rather than forwarding them.
+    pub fn with_native_tasks(mut self, native: NativeTasks) -> Self {
+        self.native_tasks = Some(native);
+        self
+    }
+
+    /// Check mail against the allowlists of `groups`.
+    pub fn with_groups(
+        mut self,
+        groups: Arc<tokio::sync::RwLock<HashMap<String, RegisteredGroup>>>,
+    ) -> Self {
+        self.groups = Some(groups);
+        self
+    }
+
+    /// What this watcher accepts, as advertised to containers.
+    pub fn capabilities(&self) -> IpcCapabilities {
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        let mut message_types: Vec<String> = names(MESSAGE_TYPES);
+        if self.groups.is_none() {
+            message_types.retain(|name| name != "mail");
+        }
+        IpcCapabilities {
+            version: IPC_PROTOCOL_VERSION,
+            min_version: 1,
+            message_types,
+            task_types: names(TASK_TYPES),
+            query_types: CACHED_QUERIES
+                .iter()
+                .chain(WRITE_QUERIES)
+                .map(|name| name.to_string())
+                .collect(),
+            task_acks: self.native_tasks.is_some(),
+            response_chunk_bytes: self.config.response_chunk_bytes,
+            max_response_bytes: self.config.response_max_bytes,
+            socket: false,
+        }
+    }
+
+    /// Run the shard workers until shutdown. Call from a tokio::spawn.
+    pub async fn run(self: Arc<Self>, shutdown: tokio::sync::watch::Receiver<bool>) {
         fs::create_dir_all(&self.config.ipc_base_dir).ok();
-        info!(dir = %self.config.ipc_base_dir.display(), "IPC watcher started");
+        let shard_count = self.config.shards.max(1);
+        info!(
+            dir = %self.config.ipc_base_dir.display(),
+            shards = shard_count,
+            "IPC watcher started"
+        );
 
-        loop {
-            tokio::select! {
-                _ = tokio::time::sleep(self.config.poll_interval) => {
-                    self.poll_once();
-                }
-                _ = shutdown.changed() => {
-                    if *shutdown.borrow() {
-                        info!("IPC watcher shutting down");
-                        return;
+        let mut workers = tokio::task::JoinSet::new();
+        for shard in 0..shard_count {
+            let watcher = self.clone();
+            let mut shutdown = shutdown.clone();
+            // Stagger the shard timers so passes spread across the interval.
+            let offset = self.config.poll_interval * shard as u32 / shard_count as u32;
+            workers.spawn(async move {
+                let mut next = tokio::time::Instant::now() + watcher.config.poll_interval + offset;
+                loop {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(next) => {
+                            let pass = watcher.clone();
+                            if let Err(err) = tokio::task::spawn_blocking(move || pass.run_shard(shard)).await {
+                                error!(shard, err = %err, "IPC shard pass panicked");
+                            }
+                            next = tokio::time::Instant::now() + watcher.config.poll_interval;
+                        }
+                        _ = shutdown.changed() => {
+                            if *shutdown.borrow() {
+                                return;
+                            }
+                        }
                     }
                 }
-            }
+            });
         }
+        while workers.join_next().await.is_some() {}
+        info!("IPC watcher shutting down");
     }
 
-    /// Process one polling cycle across all group directories.
+    /// Process every group directory in one pass.
+    #[cfg(test)]
     fn poll_once(&self) {
+        self.run_shard(0);
+    }
+
+    /// One pass for `shard`: refill its queue if empty, then drain it and
+    /// steal from the other shards until there is nothing left.
+    fn run_shard(&self, shard: usize) {
+        let started = Instant::now();
+        let needs_refill = self
+            .shards
+            .lock()
+            .unwrap()
+            .queues
+            .get(shard)
+            .is_none_or(VecDeque::is_empty);
+        if needs_refill {
+            self.refill();
+        }
+
+        let mut processed = 0_usize;
+        while let Some(group_folder) = self.next_group(shard) {
+            self.process_group(&group_folder);
+            self.shards.lock().unwrap().claimed.remove(&group_folder);
+            processed += 1;
+        }
+
+        let elapsed = started.elapsed();
+        if elapsed > self.config.poll_interval {
+            warn!(
+                shard,
+                groups = processed,
+                elapsed_ms = elapsed.as_millis() as u64,
+                "IPC pass took longer than the poll interval"
+            );
+        }
+    }
+
+    /// Queue every group directory that is not already queued or in progress.
+    fn refill(&self) {
+        {
+            let mut last_rescan = self.last_rescan.lock().unwrap();
+            if last_rescan.elapsed() >= self.config.rescan_interval {
+                self.drained.lock().unwrap().clear();
+                self.advertised.lock().unwrap().clear();
+                *last_rescan = Instant::now();
+            }
+        }
+
         let group_folders = match fs::read_dir(&self.config.ipc_base_dir) {
             Ok(entries) => entries
                 .flatten()
@@ -230,13 +686,85 @@ impl IpcWatcher {
             }
         };
 
+        let mut shards = self.shards.lock().unwrap();
+        let shard_count = shards.queues.len();
         for group_folder in group_folders {
-            let ctx = IpcGroupContext::new(&group_folder, MAIN_GROUP_FOLDER);
-            let group_dir = self.config.ipc_base_dir.join(&group_folder);
+            if shards.claimed.insert(group_folder.clone()) {
+                shards.queues[shard_for(&group_folder, shard_count)].push_back(group_folder);
+            }
+        }
+    }
 
+    /// Next folder from `shard`'s own queue, else the back of the longest
+    /// other queue.
+    fn next_group(&self, shard: usize) -> Option<String> {
+        let mut shards = self.shards.lock().unwrap();
+        if let Some(folder) = shards.queues.get_mut(shard).and_then(VecDeque::pop_front) {
+            return Some(folder);
+        }
+        shards
+            .queues
+            .iter_mut()
+            .max_by_key(|queue| queue.len())
+            .and_then(VecDeque::pop_back)
+    }
+
+    fn process_group(&self, group_folder: &str) {
+        let ctx = IpcGroupContext::new(group_folder, MAIN_GROUP_FOLDER);
+        let group_dir = self.config.ipc_base_dir.join(group_folder);
+
+        if self
+            .advertised
+            .lock()
+            .unwrap()
+            .insert(group_folder.to_string())
+            && let Err(err) =
+                write_json_file(&group_dir.join(CAPABILITIES_FILE), &self.capabilities())
+        {
+            warn!(group = %group_folder, err = %err, "Failed to write IPC capabilities");
+        }
+
+        let messages_dir = group_dir.join("messages");
+        if self.needs_scan(&messages_dir) {
             self.process_messages(&group_dir, &ctx);
+            self.mark_drained(&messages_dir);
+        }
+        let tasks_dir = group_dir.join("tasks");
+        if self.needs_scan(&tasks_dir) {
             self.process_tasks(&group_dir, &ctx);
+            self.mark_drained(&tasks_dir);
+        }
+        let queries_dir = group_dir.join("queries");
+        if self.needs_scan(&queries_dir) {
             self.process_queries(&group_dir, &ctx);
+            self.mark_drained(&queries_dir);
+        }
+    }
+
+    /// Whether `dir` may hold files we have not seen: it exists and its
+    /// mtime differs from when it was last drained.
+    fn needs_scan(&self, dir: &Path) -> bool {
+        let Ok(mtime) = fs::metadata(dir).and_then(|m| m.modified()) else {
+            return false;
+        };
+        self.drained.lock().unwrap().get(dir) != Some(&mtime)
+    }
+
+    /// Remember `dir`'s mtime after draining it, once that mtime has settled.
+    fn mark_drained(&self, dir: &Path) {
+        let mtime = fs::metadata(dir).and_then(|m| m.modified()).ok();
+        let mut drained = self.drained.lock().unwrap();
+        match mtime {
+            Some(mtime)
+                if SystemTime::now()
+                    .duration_since(mtime)
+                    .is_ok_and(|age| age >= MTIME_SETTLE) =>
+            {
+                drained.insert(dir.to_path_buf(), mtime);
+            }
+            _ => {
+                drained.remove(dir);
+            }
         }
     }
 
@@ -250,20 +778,37 @@ impl IpcWatcher {
 
         for file_path in files {
             match read_and_parse::<IpcMessage>(&file_path) {
+                Ok(msg) if msg.msg_type == "broadcast" || msg.msg_type == "mail" => {
+                    let report = match msg.msg_type.as_str() {
+                        "mail" => self.mail(&msg, ctx),
+                        _ => self.broadcast(&msg, ctx),
+                    };
+                    let ack = file_path.file_stem().unwrap_or_default().to_string_lossy();
+                    if let Err(err) = write_response(&group_dir.join("responses"), &ack, &report) {
+                        error!(ack = %ack, err = %err, "Failed to write message report");
+                    }
+                    remove_file(&file_path);
+                }
                 Ok(msg) => {
                     if msg.msg_type != "message" || msg.chat_jid.is_empty() || msg.text.is_empty() {
                         warn!(path = %file_path.display(), "Invalid IPC message — missing fields");
-                        move_to_errors(&self.config.ipc_base_dir, &file_path, &ctx.group_folder);
+                        let reason = if MESSAGE_TYPES.contains(&msg.msg_type.as_str()) {
+                            "message is missing type, chatJid or text".to_string()
+                        } else {
+                            unsupported("message", &msg.msg_type, msg.version)
+                        };
+                        move_to_errors(
+                            &self.config.ipc_base_dir,
+                            &file_path,
+                            &ctx.group_folder,
+                            &reason,
+                        );
                         continue;
                     }
 
                     // Authorization: main can send anywhere, others only to their own chat
                     if ctx.is_main || self.is_authorized_target(&msg.chat_jid, &ctx.group_folder) {
-                        self.delegate.send_message(
-                            &msg.chat_jid,
-                            &msg.text,
-                            msg.sender.as_deref(),
-                        );
+                        self.deliver(&msg.chat_jid, &msg);
                         debug!(
                             chat_jid = %msg.chat_jid,
                             group = %ctx.group_folder,
@@ -281,7 +826,13 @@ impl IpcWatcher {
                 }
                 Err(err) => {
                     error!(path = %file_path.display(), err = %err, "Failed to parse IPC message");
-                    move_to_errors(&self.config.ipc_base_dir, &file_path, &ctx.group_folder);
+                    let reason = format!("{err:#}");
+                    move_to_errors(
+                        &self.config.ipc_base_dir,
+                        &file_path,
+                        &ctx.group_folder,
+                        &reason,
+                    );
                 }
             }
         }
@@ -298,13 +849,41 @@ impl IpcWatcher {
         for file_path in files {
             match read_and_parse::<IpcTask>(&file_path) {
                 Ok(task) => {
-                    self.delegate
-                        .forward_task(&task, &ctx.group_folder, ctx.is_main);
+                    match self
+                        .native_tasks
+                        .as_ref()
+                        .and_then(|native| native.handle(&task, ctx))
+                    {
+                        Some(response) => {
+                            let ack = file_path.file_stem().unwrap_or_default().to_string_lossy();
+                            if let Err(err) =
+                                write_response(&group_dir.join("responses"), &ack, &response)
+                            {
+                                error!(ack = %ack, err = %err, "Failed to write task ack");
+                            }
+                            if response.status != "ok" {
+                                warn!(
+                                    group = %ctx.group_folder,
+                                    err = %response.result,
+                                    "IPC task command rejected"
+                                );
+                            }
+                        }
+                        None => self
+                            .delegate
+                            .forward_task(&task, &ctx.group_folder, ctx.is_main),
+                    }
                     remove_file(&file_path);
                 }
                 Err(err) => {
                     error!(path = %file_path.display(), err = %err, "Failed to parse IPC task");
-                    move_to_errors(&self.config.ipc_base_dir, &file_path, &ctx.group_folder);
+                    let reason = format!("{err:#}");
+                    move_to_errors(
+                        &self.config.ipc_base_dir,
+                        &file_path,
+                        &ctx.group_folder,
+                        &reason,
+                    );
                 }
             }
         }
@@ -333,10 +912,15 @@ impl IpcWatcher {
                         continue;
                     }
 
-                    let response = self.handle_query(&query, ctx);
+                    let response = self.answer_query(&query, ctx);
 
-                    // Write response atomically: write to .tmp then rename
-                    if let Err(err) = write_response(&responses_dir, &query.uuid, &response) {
+                    if let Err(err) = write_query_response(
+                        &responses_dir,
+                        &query.uuid,
+                        &response,
+                        self.config.response_chunk_bytes,
+                        self.config.response_max_bytes,
+                    ) {
                         error!(
                             uuid = %query.uuid,
                             err = %err,
@@ -359,70 +943,66 @@ impl IpcWatcher {
                         err = %err,
                         "Failed to parse Demarch query"
                     );
-                    move_to_errors(&self.config.ipc_base_dir, &file_path, &ctx.group_folder);
+                    let reason = format!("{err:#}");
+                    move_to_errors(
+                        &self.config.ipc_base_dir,
+                        &file_path,
+                        &ctx.group_folder,
+                        &reason,
+                    );
                 }
             }
         }
     }
 
     /// Route a query to the appropriate DemarchAdapter operation.
+    /// Answer `query`, from the cache when it is a read asked recently.
+    fn answer_query(&self, query: &IpcQuery, ctx: &IpcGroupContext) -> IpcQueryResponse {
+        let ttl = self.config.query_cache_ttl;
+        if !CACHED_QUERIES.contains(&query.query_type.as_str()) {
+            let response = self.handle_query(query, ctx);
+            if !ttl.is_zero() {
+                // A write may change what any read returns.
+                self.query_cache.lock().unwrap().clear();
+            }
+            return response;
+        }
+        if ttl.is_zero() {
+            return self.handle_query(query, ctx);
+        }
+
+        // serde_json objects keep their keys sorted, so equal params render alike.
+        let key = format!("{}\n{}", query.query_type, query.params);
+        if let Some((at, response)) = self.query_cache.lock().unwrap().get(&key)
+            && at.elapsed() < ttl
+        {
+            debug!(query_type = %query.query_type, group = %ctx.group_folder, "Demarch query answered from cache");
+            return response.clone();
+        }
+        let response = self.handle_query(query, ctx);
+        if response.status == "ok" {
+            let mut cache = self.query_cache.lock().unwrap();
+            cache.retain(|_, (at, _)| at.elapsed() < ttl);
+            cache.insert(key, (Instant::now(), response.clone()));
+        }
+        response
+    }
+
     fn handle_query(&self, query: &IpcQuery, ctx: &IpcGroupContext) -> IpcQueryResponse {
         let params = &query.params;
 
-        match query.query_type.as_str() {
-            "run_status" => {
-                let run_id = params.get("runId").and_then(|v| v.as_str()).map(String::from);
-                let resp = self.demarch.execute_read(ReadOperation::RunStatus { run_id });
-                response_from_demarch(resp)
-            }
-            "sprint_phase" => {
-                let resp = self.demarch.execute_read(ReadOperation::SprintPhase);
-                response_from_demarch(resp)
-            }
-            "search_beads" => {
-                let id = params.get("id").and_then(|v| v.as_str()).map(String::from);
-                let query_str = params.get("query").and_then(|v| v.as_str()).map(String::from);
-                let status = params.get("status").and_then(|v| v.as_str()).map(String::from);
-                let resp = self.demarch.execute_read(ReadOperation::SearchBeads {
-                    id,
-                    query: query_str,
-                    status,
-                });
-                response_from_demarch(resp)
-            }
-            "spec_lookup" => {
-                let artifact_id = params
-                    .get("artifactId")
-                    .and_then(|v| v.as_str())
-                    .map(String::from);
-                let resp = self
-                    .demarch
-                    .execute_read(ReadOperation::SpecLookup { artifact_id });
-                response_from_demarch(resp)
-            }
-            "review_summary" => {
-                let resp = self.demarch.execute_read(ReadOperation::ReviewSummary);
-                response_from_demarch(resp)
-            }
-            "next_work" => {
-                let resp = self.demarch.execute_read(ReadOperation::NextWork);
-                response_from_demarch(resp)
-            }
-            "run_events" => {
-                let limit = params
-                    .get("limit")
-                    .and_then(|v| v.as_u64())
-                    .map(|v| v as u32);
-                let since = params
-                    .get("since")
-                    .and_then(|v| v.as_str())
-                    .map(String::from);
-                let resp = self
-                    .demarch
-                    .execute_read(ReadOperation::RunEvents { limit, since });
-                response_from_demarch(resp)
-            }
+        let read = match query.query_type.as_str() {
+            "batch" => Some(batch_operation(params)),
+            query_type => read_operation(query_type, params),
+        };
+        if let Some(op) = read {
+            return match op {
+                Ok(op) => response_from_demarch(self.demarch.execute_read_blocking(op)),
+                Err(err) => IpcQueryResponse::error(err),
+            };
+        }
 
+        match query.query_type.as_str() {
             // Write operations (require main group check)
             "create_issue" => {
                 let title = params
@@ -433,7 +1013,7 @@ impl IpcWatcher {
                 if title.is_empty() {
                     return IpcQueryResponse::error("create_issue requires a title");
                 }
-                let resp = self.demarch.execute_write(
+                let resp = self.demarch.execute_write_blocking(
                     WriteOperation::CreateIssue {
                         title,
                         description: params
@@ -456,6 +1036,7 @@ impl IpcWatcher {
                             })
                         }),
                     },
+                    Some(&ctx.group_folder),
                     ctx.is_main,
                 );
                 response_from_demarch(resp)
@@ -469,7 +1050,7 @@ impl IpcWatcher {
                 if id.is_empty() {
                     return IpcQueryResponse::error("update_issue requires an id");
                 }
-                let resp = self.demarch.execute_write(
+                let resp = self.demarch.execute_write_blocking(
                     WriteOperation::UpdateIssue {
                         id,
                         status: params
@@ -493,6 +1074,7 @@ impl IpcWatcher {
                             .and_then(|v| v.as_str())
                             .map(String::from),
                     },
+                    Some(&ctx.group_folder),
                     ctx.is_main,
                 );
                 response_from_demarch(resp)
@@ -506,7 +1088,7 @@ impl IpcWatcher {
                 if id.is_empty() {
                     return IpcQueryResponse::error("close_issue requires an id");
                 }
-                let resp = self.demarch.execute_write(
+                let resp = self.demarch.execute_write_blocking(
                     WriteOperation::CloseIssue {
                         id,
                         reason: params
@@ -514,12 +1096,13 @@ impl IpcWatcher {
                             .and_then(|v| v.as_str())
                             .map(String::from),
                     },
+                    Some(&ctx.group_folder),
                     ctx.is_main,
                 );
                 response_from_demarch(resp)
             }
             "start_run" => {
-                let resp = self.demarch.execute_write(
+                let resp = self.demarch.execute_write_blocking(
                     WriteOperation::StartRun {
                         title: params
                             .get("title")
@@ -530,12 +1113,13 @@ impl IpcWatcher {
                             .and_then(|v| v.as_str())
                             .map(String::from),
                     },
+                    Some(&ctx.group_folder),
                     ctx.is_main,
                 );
                 response_from_demarch(resp)
             }
             "approve_gate" => {
-                let resp = self.demarch.execute_write(
+                let resp = self.demarch.execute_write_blocking(
                     WriteOperation::ApproveGate {
                         gate_id: params
                             .get("gate_id")
@@ -546,12 +1130,139 @@ impl IpcWatcher {
                             .and_then(|v| v.as_str())
                             .map(String::from),
                     },
+                    Some(&ctx.group_folder),
                     ctx.is_main,
                 );
                 response_from_demarch(resp)
             }
 
-            unknown => IpcQueryResponse::error(format!("Unknown query type: {unknown}")),
+            unknown => IpcQueryResponse::error(unsupported("query", unknown, query.version)),
+        }
+    }
+
+    /// Send `msg`'s text, with its options as buttons, to `chat_jid`.
+    fn deliver(&self, chat_jid: &str, msg: &IpcMessage) {
+        match crate::telegram::options_keyboard(&msg.options) {
+            Some(keyboard) => self.delegate.send_message_with_buttons(
+                chat_jid,
+                &msg.text,
+                msg.sender.as_deref(),
+                Some(keyboard),
+            ),
+            None => self
+                .delegate
+                .send_message(chat_jid, &msg.text, msg.sender.as_deref()),
+        }
+    }
+
+    /// Send a broadcast to each of its target groups the sender may reach:
+    /// any group for main, only its own for other groups. The report lists
+    /// `delivered` chats and `failed` folders with the reason.
+    fn broadcast(&self, msg: &IpcMessage, ctx: &IpcGroupContext) -> IpcQueryResponse {
+        if msg.text.is_empty() {
+            return IpcQueryResponse::error("broadcast has no text");
+        }
+        let chats = self.registry.chats_by_folder();
+        let folders: Vec<String> = match &msg.target {
+            Some(BroadcastTarget::Keyword(keyword)) if keyword == "all" => {
+                chats.keys().cloned().collect()
+            }
+            Some(BroadcastTarget::Folders(folders)) if !folders.is_empty() => {
+                let mut unique = folders.clone();
+                unique.sort();
+                unique.dedup();
+                unique
+            }
+            _ => {
+                return IpcQueryResponse::error(
+                    "broadcast target must be \"all\" or a list of group folders",
+                );
+            }
+        };
+
+        let mut delivered = Vec::new();
+        let mut failed = Vec::new();
+        for folder in folders {
+            let Some(jids) = chats.get(&folder) else {
+                failed.push(
+                    serde_json::json!({ "folder": folder, "error": "not a registered group" }),
+                );
+                continue;
+            };
+            if !ctx.is_main && folder != ctx.group_folder {
+                warn!(target = %folder, group = %ctx.group_folder, "Unauthorized IPC broadcast target blocked");
+                failed.push(serde_json::json!({ "folder": folder, "error": "not allowed" }));
+                continue;
+            }
+            for jid in jids {
+                self.deliver(jid, msg);
+                delivered.push(serde_json::json!({ "folder": folder, "chatJid": jid }));
+            }
+        }
+        info!(
+            group = %ctx.group_folder,
+            delivered = delivered.len(),
+            failed = failed.len(),
+            "IPC broadcast dispatched"
+        );
+        let report = serde_json::json!({ "delivered": delivered, "failed": failed }).to_string();
+        if delivered.is_empty() {
+            IpcQueryResponse::error(report)
+        } else {
+            IpcQueryResponse::ok(report)
+        }
+    }
+
+    /// Leave `msg` in the inbox of the group named by `to`, if it accepts
+    /// mail from the sender.
+    fn mail(&self, msg: &IpcMessage, ctx: &IpcGroupContext) -> IpcQueryResponse {
+        let Some(to) = msg.to.as_deref().filter(|to| !to.is_empty()) else {
+            return IpcQueryResponse::error("mail needs a `to` group folder");
+        };
+        if msg.text.is_empty() {
+            return IpcQueryResponse::error("mail has no text");
+        }
+        let Some(groups) = &self.groups else {
+            return IpcQueryResponse::error("mail is not available");
+        };
+        let allowed = {
+            let groups = groups.blocking_read();
+            let Some(group) = groups.values().find(|group| group.folder == to) else {
+                return IpcQueryResponse::error(format!("{to} is not a registered group"));
+            };
+            ctx.is_main || accepts_mail_from(group, &ctx.group_folder)
+        };
+        if !allowed {
+            warn!(to, group = %ctx.group_folder, "Unauthorized IPC mail blocked");
+            return IpcQueryResponse::error(format!(
+                "{to} does not accept mail from {}",
+                ctx.group_folder
+            ));
+        }
+
+        let name = format!(
+            "{}-{}-{}",
+            chrono::Utc::now().timestamp_millis(),
+            ctx.group_folder,
+            self.mail_seq.fetch_add(1, Ordering::Relaxed)
+        );
+        let mail = serde_json::json!({
+            "from": ctx.group_folder,
+            "subject": msg.subject,
+            "text": msg.text,
+            "sender": msg.sender,
+            "sentAt": chrono::Utc::now().to_rfc3339(),
+        });
+        match write_json(
+            &self.config.ipc_base_dir.join(to).join("inbox"),
+            &name,
+            &mail,
+        ) {
+            Ok(()) => {
+                info!(to, group = %ctx.group_folder, mail = %name, "IPC mail delivered");
+                IpcQueryResponse::ok(format!("Delivered to {to} as {name}.json"))
+            }
+            Err(err) => IpcQueryResponse::error(format!("{err:#}")),
         }
     }
 
@@ -565,10 +1276,92 @@ impl IpcWatcher {
     }
 }
 
+/// The Demarch read a query type names, built from its params; `None` when
+/// the type is not a read.
+fn read_operation(
+    query_type: &str,
+    params: &serde_json::Value,
+) -> Option<Result<ReadOperation, String>> {
+    let str_param = |key: &str| params.get(key).and_then(|v| v.as_str()).map(String::from);
+
+    let op = match query_type {
+        "run_status" => ReadOperation::RunStatus {
+            run_id: str_param("runId"),
+        },
+        "sprint_phase" => ReadOperation::SprintPhase,
+        "search_beads" => ReadOperation::SearchBeads {
+            id: str_param("id"),
+            query: str_param("query"),
+            status: str_param("status"),
+        },
+        "spec_lookup" => ReadOperation::SpecLookup {
+            artifact_id: str_param("artifactId"),
+        },
+        "review_summary" => ReadOperation::ReviewSummary,
+        "next_work" => ReadOperation::NextWork,
+        "run_events" => ReadOperation::RunEvents {
+            limit: params
+                .get("limit")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32),
+            since: str_param("since"),
+        },
+        "list_gates" => ReadOperation::ListGates {
+            run_id: str_param("runId"),
+            status: str_param("status"),
+        },
+        "issue_comments" | "issue_history" => {
+            let Some(id) = str_param("id") else {
+                return Some(Err(format!("{query_type} requires an id")));
+            };
+            if query_type == "issue_comments" {
+                ReadOperation::IssueComments { id }
+            } else {
+                ReadOperation::IssueHistory { id }
+            }
+        }
+        "run_diff" => ReadOperation::RunDiff {
+            run_id: str_param("runId"),
+        },
+        _ => return None,
+    };
+    Some(Ok(op))
+}
+
+/// A `batch` query: `params.queries` lists `{type, params}` reads, answered
+/// together as a JSON array in the same order.
+fn batch_operation(params: &serde_json::Value) -> Result<ReadOperation, String> {
+    let Some(queries) = params.get("queries").and_then(|v| v.as_array()) else {
+        return Err("batch requires a queries array".to_string());
+    };
+    let empty = serde_json::Value::Object(Default::default());
+    let ops = queries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let query_type = entry
+                .get("type")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            let params = entry.get("params").unwrap_or(&empty);
+            read_operation(query_type, params).unwrap_or_else(|| {
+                Err(format!(
+                    "batch entry {index}: {query_type:?} is not a read query"
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ReadOperation::Batch { ops })
+}
+
 fn response_from_demarch(resp: intercom_core::DemarchResponse) -> IpcQueryResponse {
-    match resp.status {
+    let response = match resp.status {
         intercom_core::DemarchStatus::Ok => IpcQueryResponse::ok(resp.result),
         intercom_core::DemarchStatus::Error => IpcQueryResponse::error(resp.result),
+    };
+    IpcQueryResponse {
+        data: resp.data,
+        ..response
     }
 }
 
@@ -610,29 +1403,138 @@ fn write_response(
     uuid: &str,
     response: &IpcQueryResponse,
 ) -> anyhow::Result<()> {
-    fs::create_dir_all(responses_dir)?;
-    let response_path = responses_dir.join(format!("{uuid}.json"));
-    let temp_path = responses_dir.join(format!("{uuid}.json.tmp"));
-    let content = serde_json::to_string_pretty(response)?;
+    write_json(responses_dir, uuid, response)
+}
+
+/// Write `{dir}/{stem}.json` atomically (write .tmp then rename).
+fn write_json(dir: &Path, stem: &str, value: &impl serde::Serialize) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    write_json_file(&dir.join(format!("{stem}.json")), value)
+}
+
+fn write_json_file(path: &Path, value: &impl serde::Serialize) -> anyhow::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let content = serde_json::to_string_pretty(value)?;
     fs::write(&temp_path, content)?;
-    fs::rename(&temp_path, &response_path)?;
+    fs::rename(&temp_path, path)?;
     Ok(())
 }
 
-/// Move a failed file to the errors directory for debugging.
-fn move_to_errors(ipc_base: &Path, file_path: &Path, group_folder: &str) {
-    let error_dir = ipc_base.join("errors");
-    fs::create_dir_all(&error_dir).ok();
-
-    if let Some(filename) = file_path.file_name() {
-        let dest = error_dir.join(format!("{group_folder}-{}", filename.to_string_lossy()));
-        if let Err(err) = fs::rename(file_path, &dest) {
-            error!(
-                path = %file_path.display(),
-                err = %err,
-                "Failed to move error file"
-            );
+/// Whether `group`'s `acceptMailFrom` admits mail from `folder`.
+fn accepts_mail_from(group: &RegisteredGroup, folder: &str) -> bool {
+    group
+        .container_config
+        .clone()
+        .and_then(|config| {
+            serde_json::from_value::<crate::container::security::ContainerConfig>(config).ok()
+        })
+        .is_some_and(|config| {
+            config
+                .accept_mail_from
+                .iter()
+                .any(|allowed| allowed == "*" || allowed == folder)
+        })
+}
+
+/// The largest char boundary in `s` at or below `max`.
+fn boundary_below(s: &str, max: usize) -> usize {
+    let mut at = max.min(s.len());
+    while !s.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+/// Write a query's answer, cut to `max_bytes` and split into parts of at
+/// most `chunk_bytes` (zero disables either). Part 1 is `{uuid}.json` and
+/// each part names the next in `next`; later parts are written first so
+/// the chain is complete once `{uuid}.json` appears. Parsed `data` is only
+/// kept when the whole result goes out in one file.
+fn write_query_response(
+    responses_dir: &Path,
+    uuid: &str,
+    response: &IpcQueryResponse,
+    chunk_bytes: usize,
+    max_bytes: usize,
+) -> anyhow::Result<()> {
+    let whole = response.result.len();
+    let truncated = max_bytes > 0 && whole > max_bytes;
+    let result = if truncated {
+        &response.result[..boundary_below(&response.result, max_bytes)]
+    } else {
+        response.result.as_str()
+    };
+    let base = IpcQueryResponse {
+        status: response.status.clone(),
+        result: String::new(),
+        data: None,
+        part: None,
+        parts: None,
+        next: None,
+        truncated,
+        total_bytes: truncated.then_some(whole),
+    };
+    if chunk_bytes == 0 || result.len() <= chunk_bytes {
+        let data = if truncated {
+            None
+        } else {
+            response.data.clone()
+        };
+        return write_response(
+            responses_dir,
+            uuid,
+            &IpcQueryResponse {
+                result: result.to_string(),
+                data,
+                ..base
+            },
+        );
+    }
+
+    let mut chunks = Vec::new();
+    let mut rest = result;
+    while !rest.is_empty() {
+        // A chunk smaller than one char still takes that char.
+        let cut = match boundary_below(rest, chunk_bytes) {
+            0 => rest.chars().next().map_or(rest.len(), char::len_utf8),
+            cut => cut,
+        };
+        let (chunk, tail) = rest.split_at(cut);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    let parts = chunks.len() as u32;
+    let stem = |part: u32| {
+        if part == 1 {
+            uuid.to_string()
+        } else {
+            format!("{uuid}.part-{part}")
         }
+    };
+    for (index, chunk) in chunks.iter().enumerate().rev() {
+        let part = index as u32 + 1;
+        let piece = IpcQueryResponse {
+            result: chunk.to_string(),
+            part: Some(part),
+            parts: Some(parts),
+            next: (part < parts).then(|| format!("{}.json", stem(part + 1))),
+            ..base.clone()
+        };
+        write_response(responses_dir, &stem(part), &piece)?;
+    }
+    Ok(())
+}
+
+/// Move a failed file to the errors directory, recording why, so it can be
+/// fixed and retried (see [`crate::ipc_errors`]).
+fn move_to_errors(ipc_base: &Path, file_path: &Path, group_folder: &str, reason: &str) {
+    if let Err(err) = crate::ipc_errors::dead_letter(ipc_base, file_path, group_folder, reason) {
+        error!(
+            path = %file_path.display(),
+            err = %err,
+            "Failed to move error file"
+        );
     }
 }
 
@@ -668,6 +1570,17 @@ impl GroupRegistry {
         map.get(chat_jid).cloned()
     }
 
+    /// Registered chat JIDs by group folder, both sorted.
+    pub fn chats_by_folder(&self) -> std::collections::BTreeMap<String, Vec<String>> {
+        let mut chats = std::collections::BTreeMap::<String, Vec<String>>::new();
+        for (jid, folder) in self.jid_to_folder.read().unwrap().iter() {
+            chats.entry(folder.clone()).or_default().push(jid.clone());
+        }
+        chats.values_mut().for_each(|jids| jids.sort());
+        chats
+    }
+
+    #[cfg(test)]
     pub fn len(&self) -> usize {
         self.jid_to_folder.read().unwrap().len()
     }
@@ -735,7 +1648,7 @@ pub async fn sync_registry_loop(
 mod tests {
     use std::fs;
 
-    use intercom_core::{DemarchResponse, DemarchStatus, IpcQueryResponse};
+    use intercom_core::{DemarchResponse, IpcQueryResponse};
 
     use super::*;
 
@@ -756,6 +1669,21 @@ mod tests {
         assert_eq!(ipc.result, "test result");
     }
 
+    #[test]
+    fn json_results_carry_parsed_data_unless_split() {
+        let ipc = super::response_from_demarch(DemarchResponse::ok(r#"[{"id":"iv-1"}]"#));
+        assert_eq!(ipc.data, Some(serde_json::json!([{ "id": "iv-1" }])));
+
+        let tmp = tempfile::tempdir().unwrap();
+        write_query_response(tmp.path(), "whole", &ipc, 0, 0).unwrap();
+        write_query_response(tmp.path(), "split", &ipc, 4, 0).unwrap();
+        let read = |name: &str| -> IpcQueryResponse {
+            serde_json::from_str(&fs::read_to_string(tmp.path().join(name)).unwrap()).unwrap()
+        };
+        assert_eq!(read("whole.json").data, ipc.data);
+        assert_eq!(read("split.json").data, None);
+    }
+
     #[test]
     fn response_from_demarch_error() {
         let demarch = DemarchResponse::error("test error");
@@ -788,7 +1716,7 @@ mod tests {
         let file_path = ipc_base.join("test-query.json");
         fs::write(&file_path, "bad json").unwrap();
 
-        move_to_errors(ipc_base, &file_path, "team-eng");
+        move_to_errors(ipc_base, &file_path, "team-eng", "bad json");
 
         assert!(!file_path.exists());
         assert!(ipc_base.join("errors/team-eng-test-query.json").exists());
@@ -862,6 +1790,13 @@ mod tests {
         assert_eq!(msg.chat_jid, "tg:1108701034");
         assert_eq!(msg.text, "Hello from agent");
         assert_eq!(msg.sender.as_deref(), Some("Amtiskaw"));
+        assert!(msg.options.is_empty());
+
+        let with_options: IpcMessage = serde_json::from_str(
+            r#"{"type": "message", "chatJid": "tg:1", "text": "Deploy?", "options": ["Yes", "No"]}"#,
+        )
+        .unwrap();
+        assert_eq!(with_options.options, vec!["Yes", "No"]);
     }
 
     #[test]
@@ -956,6 +1891,103 @@ mod tests {
         assert_eq!(response.status, "error");
     }
 
+    #[test]
+    fn mock_backend_answers_queries_successfully() {
+        use intercom_core::DemarchBackendKind;
+        use intercom_core::config::DemarchConfig;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let queries_dir = tmp.path().join("main/queries");
+        fs::create_dir_all(&queries_dir).unwrap();
+        for (uuid, kind) in [("q1", "next_work"), ("q2", "approve_gate")] {
+            let query = serde_json::json!({ "uuid": uuid, "type": kind, "params": {} });
+            fs::write(queries_dir.join(format!("{uuid}.json")), query.to_string()).unwrap();
+        }
+
+        let config = DemarchConfig {
+            backend: DemarchBackendKind::Mock,
+            ..Default::default()
+        };
+        let watcher = IpcWatcher::new(
+            IpcWatcherConfig {
+                ipc_base_dir: tmp.path().to_path_buf(),
+                ..Default::default()
+            },
+            Arc::new(DemarchAdapter::new(config, ".")),
+            Arc::new(LogOnlyDelegate),
+        );
+        watcher.poll_once();
+
+        let read = |uuid: &str| -> IpcQueryResponse {
+            let path = tmp.path().join(format!("main/responses/{uuid}.json"));
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+        };
+        let next_work = read("q1");
+        assert_eq!(next_work.status, "ok");
+        assert!(next_work.result.contains("mock-1"));
+        let approved = read("q2");
+        assert_eq!(approved.status, "ok");
+        assert!(approved.result.contains("approved"));
+    }
+
+    #[test]
+    fn batch_query_answers_each_read_by_index() {
+        use intercom_core::DemarchBackendKind;
+        use intercom_core::config::DemarchConfig;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let queries_dir = tmp.path().join("main/queries");
+        fs::create_dir_all(&queries_dir).unwrap();
+        let batches = [
+            (
+                "briefing",
+                serde_json::json!([{ "type": "run_status" }, { "type": "next_work" }]),
+            ),
+            (
+                "no-id",
+                serde_json::json!([{ "type": "issue_history", "params": {} }]),
+            ),
+            (
+                "write",
+                serde_json::json!([{ "type": "close_issue", "params": { "id": "mock-1" } }]),
+            ),
+        ];
+        for (uuid, entries) in batches {
+            let query = serde_json::json!({ "uuid": uuid, "type": "batch", "params": { "queries": entries } });
+            fs::write(queries_dir.join(format!("{uuid}.json")), query.to_string()).unwrap();
+        }
+
+        let config = DemarchConfig {
+            backend: DemarchBackendKind::Mock,
+            ..Default::default()
+        };
+        let watcher = IpcWatcher::new(
+            IpcWatcherConfig {
+                ipc_base_dir: tmp.path().to_path_buf(),
+                ..Default::default()
+            },
+            Arc::new(DemarchAdapter::new(config, ".")),
+            Arc::new(LogOnlyDelegate),
+        );
+        watcher.poll_once();
+
+        let read = |uuid: &str| -> IpcQueryResponse {
+            let path = tmp.path().join(format!("main/responses/{uuid}.json"));
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+        };
+        let briefing = read("briefing");
+        assert_eq!(briefing.status, "ok");
+        let answers = briefing.data.unwrap();
+        assert_eq!(answers[0]["data"]["id"], "run-mock");
+        assert_eq!(answers[1]["data"][0]["id"], "mock-1");
+        assert!(
+            read("no-id")
+                .result
+                .contains("issue_history requires an id")
+        );
+        assert!(read("write").result.contains("not a read query"));
+    }
+
     #[test]
     fn poll_once_moves_bad_json_to_errors() {
         use intercom_core::config::DemarchConfig;
@@ -1107,4 +2139,501 @@ mod tests {
         let messages = delegate.messages.lock().unwrap();
         assert_eq!(messages.len(), 0);
     }
+
+    fn write_test_message(messages_dir: &Path, name: &str) {
+        fs::create_dir_all(messages_dir).unwrap();
+        let msg = serde_json::json!({
+            "type": "message",
+            "chatJid": "tg:99999",
+            "text": "hi",
+            "timestamp": "2026-02-25T12:00:00Z"
+        });
+        fs::write(
+            messages_dir.join(name),
+            serde_json::to_string(&msg).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn shard_pass_steals_groups_from_other_shards() {
+        use intercom_core::config::DemarchConfig;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let ipc_base = tmp.path().to_path_buf();
+        let folders: Vec<String> = (0..8).map(|i| format!("team-{i}")).collect();
+        for folder in &folders {
+            write_test_message(&ipc_base.join(folder).join("messages"), "001-msg.json");
+        }
+        assert!(
+            folders.iter().any(|f| shard_for(f, 3) != 1),
+            "test needs groups hashed to other shards"
+        );
+
+        let demarch = Arc::new(DemarchAdapter::new(DemarchConfig::default(), "."));
+        let watcher = IpcWatcher::new(
+            IpcWatcherConfig {
+                ipc_base_dir: ipc_base.clone(),
+                shards: 3,
+                ..Default::default()
+            },
+            demarch,
+            Arc::new(LogOnlyDelegate),
+        );
+
+        watcher.run_shard(1);
+
+        for folder in &folders {
+            assert!(!ipc_base.join(folder).join("messages/001-msg.json").exists());
+        }
+        let shards = watcher.shards.lock().unwrap();
+        assert!(shards.claimed.is_empty());
+        assert!(shards.queues.iter().all(VecDeque::is_empty));
+    }
+
+    #[test]
+    fn poll_once_skips_directories_with_unchanged_mtime() {
+        use intercom_core::config::DemarchConfig;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let ipc_base = tmp.path().to_path_buf();
+        let messages_dir = ipc_base.join("main/messages");
+        fs::create_dir_all(&messages_dir).unwrap();
+        let set_mtime = |at: SystemTime| {
+            fs::File::open(&messages_dir)
+                .unwrap()
+                .set_modified(at)
+                .unwrap();
+        };
+        let old = SystemTime::now() - Duration::from_secs(60);
+        set_mtime(old);
+
+        let demarch = Arc::new(DemarchAdapter::new(DemarchConfig::default(), "."));
+        let watcher = IpcWatcher::new(
+            IpcWatcherConfig {
+                ipc_base_dir: ipc_base.clone(),
+                ..Default::default()
+            },
+            demarch,
+            Arc::new(LogOnlyDelegate),
+        );
+        watcher.poll_once();
+
+        // A file whose directory mtime still matches the drained one is not seen.
+        write_test_message(&messages_dir, "001-msg.json");
+        set_mtime(old);
+        watcher.poll_once();
+        assert!(messages_dir.join("001-msg.json").exists());
+
+        // Once the mtime moves the directory is listed again.
+        set_mtime(SystemTime::now());
+        watcher.poll_once();
+        assert!(!messages_dir.join("001-msg.json").exists());
+    }
+
+    #[test]
+    fn broadcasts_report_each_target() {
+        use intercom_core::config::DemarchConfig;
+
+        #[derive(Default)]
+        struct RecordingDelegate {
+            sent: Mutex<Vec<String>>,
+        }
+
+        impl IpcDelegate for RecordingDelegate {
+            fn send_message(&self, chat_jid: &str, _text: &str, _sender: Option<&str>) {
+                self.sent.lock().unwrap().push(chat_jid.to_string());
+            }
+
+            fn forward_task(&self, _task: &IpcTask, _group_folder: &str, _is_main: bool) {}
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let registry = GroupRegistry::new();
+        registry.update_from_map(
+            [
+                ("tg:main", "main"),
+                ("tg:eng", "team-eng"),
+                ("tg:ops", "ops"),
+            ]
+            .into_iter()
+            .map(|(jid, folder)| (jid.to_string(), folder.to_string()))
+            .collect(),
+        );
+        let delegate = Arc::new(RecordingDelegate::default());
+        let watcher = IpcWatcher::with_registry(
+            IpcWatcherConfig {
+                ipc_base_dir: tmp.path().to_path_buf(),
+                ..Default::default()
+            },
+            Arc::new(DemarchAdapter::new(DemarchConfig::default(), ".")),
+            delegate.clone(),
+            registry,
+        );
+        let send = |folder: &str, target: serde_json::Value| -> serde_json::Value {
+            let dir = tmp.path().join(folder).join("messages");
+            fs::create_dir_all(&dir).unwrap();
+            let msg =
+                serde_json::json!({ "type": "broadcast", "text": "Deploy at 5", "target": target });
+            fs::write(dir.join("001-all.json"), msg.to_string()).unwrap();
+            watcher.process_messages(
+                &tmp.path().join(folder),
+                &IpcGroupContext::new(folder, "main"),
+            );
+            let path = tmp.path().join(folder).join("responses/001-all.json");
+            let report: IpcQueryResponse =
+                serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+            serde_json::from_str(&report.result).unwrap()
+        };
+
+        let all = send("main", serde_json::json!("all"));
+        assert_eq!(all["delivered"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            *delegate.sent.lock().unwrap(),
+            vec!["tg:main", "tg:ops", "tg:eng"]
+        );
+
+        let scoped = send(
+            "team-eng",
+            serde_json::json!(["ops", "team-eng", "nowhere"]),
+        );
+        assert_eq!(
+            scoped["delivered"],
+            serde_json::json!([{ "folder": "team-eng", "chatJid": "tg:eng" }])
+        );
+        assert_eq!(
+            scoped["failed"],
+            serde_json::json!([
+                { "folder": "nowhere", "error": "not a registered group" },
+                { "folder": "ops", "error": "not allowed" },
+            ])
+        );
+        assert_eq!(delegate.sent.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn mail_lands_in_inboxes_that_accept_the_sender() {
+        use intercom_core::config::DemarchConfig;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let eng_config = serde_json::json!({ "acceptMailFrom": ["ops"] });
+        let groups: HashMap<String, RegisteredGroup> = [
+            ("tg:main", "main", None),
+            ("tg:eng", "team-eng", Some(eng_config)),
+            ("tg:ops", "ops", None),
+        ]
+        .into_iter()
+        .map(|(jid, folder, container_config)| {
+            let group = RegisteredGroup {
+                jid: jid.into(),
+                name: folder.into(),
+                folder: folder.into(),
+                trigger: "@Andy".into(),
+                added_at: "2024-01-01T00:00:00Z".into(),
+                container_config,
+                requires_trigger: None,
+                runtime: None,
+                model: None,
+                muted_until: None,
+                locale: None,
+            };
+            (jid.to_string(), group)
+        })
+        .collect();
+        let watcher = IpcWatcher::new(
+            IpcWatcherConfig {
+                ipc_base_dir: tmp.path().to_path_buf(),
+                ..Default::default()
+            },
+            Arc::new(DemarchAdapter::new(DemarchConfig::default(), ".")),
+            Arc::new(LogOnlyDelegate),
+        )
+        .with_groups(Arc::new(tokio::sync::RwLock::new(groups)));
+        let send = |from: &str, to: &str| -> IpcQueryResponse {
+            let dir = tmp.path().join(from).join("messages");
+            fs::create_dir_all(&dir).unwrap();
+            let msg = serde_json::json!({ "type": "mail", "to": to, "subject": "Handoff", "text": "Please review" });
+            fs::write(dir.join("001-mail.json"), msg.to_string()).unwrap();
+            watcher.process_messages(&tmp.path().join(from), &IpcGroupContext::new(from, "main"));
+            let path = tmp.path().join(from).join("responses/001-mail.json");
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+        };
+        let inbox = |folder: &str| -> Vec<serde_json::Value> {
+            read_json_files(&tmp.path().join(folder).join("inbox"))
+                .unwrap_or_default()
+                .iter()
+                .map(|path| serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap())
+                .collect()
+        };
+
+        assert_eq!(send("ops", "team-eng").status, "ok");
+        assert_eq!(send("team-eng", "ops").status, "error");
+        assert_eq!(send("main", "ops").status, "ok");
+        assert_eq!(send("ops", "nowhere").status, "error");
+
+        let eng = inbox("team-eng");
+        assert_eq!(eng.len(), 1);
+        assert_eq!(eng[0]["from"], "ops");
+        assert_eq!(eng[0]["subject"], "Handoff");
+        assert_eq!(eng[0]["text"], "Please review");
+        assert_eq!(inbox("ops").len(), 1);
+    }
+
+    #[test]
+    fn groups_are_told_what_the_watcher_supports() {
+        use intercom_core::config::DemarchConfig;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let queries_dir = tmp.path().join("team-eng/queries");
+        fs::create_dir_all(&queries_dir).unwrap();
+        let query = serde_json::json!({ "uuid": "q1", "type": "future_query", "version": 3 });
+        fs::write(queries_dir.join("q1.json"), query.to_string()).unwrap();
+        let watcher = IpcWatcher::new(
+            IpcWatcherConfig {
+                ipc_base_dir: tmp.path().to_path_buf(),
+                ..Default::default()
+            },
+            Arc::new(DemarchAdapter::new(DemarchConfig::default(), ".")),
+            Arc::new(LogOnlyDelegate),
+        );
+        watcher.poll_once();
+
+        let written =
+            fs::read_to_string(tmp.path().join("team-eng").join(CAPABILITIES_FILE)).unwrap();
+        let capabilities: IpcCapabilities = serde_json::from_str(&written).unwrap();
+        assert_eq!(capabilities, watcher.capabilities());
+        assert_eq!(capabilities.version, IPC_PROTOCOL_VERSION);
+        assert!(capabilities.query_types.contains(&"next_work".to_string()));
+        assert!(!capabilities.message_types.contains(&"mail".to_string()));
+        assert!(!capabilities.task_acks);
+
+        let response = fs::read_to_string(tmp.path().join("team-eng/responses/q1.json")).unwrap();
+        let response: IpcQueryResponse = serde_json::from_str(&response).unwrap();
+        assert_eq!(response.status, "error");
+        assert!(
+            response.result.contains("sent as protocol 3"),
+            "{}",
+            response.result
+        );
+    }
+
+    #[test]
+    fn large_query_results_are_chunked_and_capped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let read = |name: &str| -> IpcQueryResponse {
+            serde_json::from_str(&fs::read_to_string(dir.join(name)).unwrap()).unwrap()
+        };
+
+        write_query_response(dir, "small", &IpcQueryResponse::ok("tiny"), 4, 0).unwrap();
+        let small = read("small.json");
+        assert_eq!(
+            (small.result.as_str(), small.part, small.truncated),
+            ("tiny", None, false)
+        );
+
+        // "é" is two bytes and never split.
+        write_query_response(dir, "big", &IpcQueryResponse::ok("abcdéfghij-cut"), 4, 11).unwrap();
+        let mut parts = Vec::new();
+        let mut next = Some("big.json".to_string());
+        while let Some(name) = next {
+            let part = read(&name);
+            next = part.next.clone();
+            parts.push(part);
+        }
+        let text: Vec<&str> = parts.iter().map(|part| part.result.as_str()).collect();
+        assert_eq!(text, vec!["abcd", "éfg", "hij"]);
+        assert_eq!(parts[1].part, Some(2));
+        assert!(
+            parts
+                .iter()
+                .all(|part| part.parts == Some(3) && part.truncated)
+        );
+        assert_eq!(parts[0].total_bytes, Some(15));
+        assert!(parts[2].next.is_none());
+    }
+
+    #[test]
+    fn read_queries_are_cached_until_a_write() {
+        use intercom_core::config::DemarchConfig;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let queries_dir = tmp.path().join("main/queries");
+        let demarch = DemarchConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let watcher = IpcWatcher::new(
+            IpcWatcherConfig {
+                ipc_base_dir: tmp.path().to_path_buf(),
+                query_cache_ttl: Duration::from_secs(60),
+                ..Default::default()
+            },
+            Arc::new(DemarchAdapter::new(demarch, ".")),
+            Arc::new(LogOnlyDelegate),
+        );
+        watcher.query_cache.lock().unwrap().insert(
+            "next_work\n{}".into(),
+            (Instant::now(), IpcQueryResponse::ok("cached")),
+        );
+
+        let ask = |uuid: &str, query: serde_json::Value| -> IpcQueryResponse {
+            fs::create_dir_all(&queries_dir).unwrap();
+            fs::write(queries_dir.join(format!("{uuid}.json")), query.to_string()).unwrap();
+            watcher.process_queries(
+                &tmp.path().join("main"),
+                &IpcGroupContext::new("main", "main"),
+            );
+            let path = tmp.path().join(format!("main/responses/{uuid}.json"));
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+        };
+        let next_work =
+            |uuid: &str| serde_json::json!({ "uuid": uuid, "type": "next_work", "params": {} });
+
+        assert_eq!(ask("q1", next_work("q1")).result, "cached");
+        // Another query type misses the cache.
+        let phase = ask(
+            "q2",
+            serde_json::json!({ "uuid": "q2", "type": "sprint_phase" }),
+        );
+        assert_eq!(phase.status, "error");
+
+        ask(
+            "w1",
+            serde_json::json!({ "uuid": "w1", "type": "create_issue", "params": {} }),
+        );
+        let after_write = ask("q3", next_work("q3"));
+        assert_eq!(after_write.status, "error");
+        // Failures are not cached.
+        assert!(watcher.query_cache.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn native_tasks_are_acked_and_scoped_to_the_group() {
+        use intercom_core::config::DemarchConfig;
+
+        #[derive(Default)]
+        struct RecordingDelegate {
+            tasks: Mutex<Vec<String>>,
+        }
+
+        impl IpcDelegate for RecordingDelegate {
+            fn send_message(&self, _chat_jid: &str, _text: &str, _sender: Option<&str>) {}
+
+            fn forward_task(&self, _task: &IpcTask, group_folder: &str, _is_main: bool) {
+                self.tasks.lock().unwrap().push(group_folder.to_string());
+            }
+        }
+
+        let pool: SharedStorage = Arc::new(intercom_core::SqliteStore::new(":memory:"));
+        let groups: HashMap<String, RegisteredGroup> =
+            [("tg:main", "main"), ("tg:eng", "team-eng")]
+                .into_iter()
+                .map(|(jid, folder)| {
+                    let group = RegisteredGroup {
+                        jid: jid.into(),
+                        name: folder.into(),
+                        folder: folder.into(),
+                        trigger: "@Andy".into(),
+                        added_at: "2024-01-01T00:00:00Z".into(),
+                        container_config: None,
+                        requires_trigger: None,
+                        runtime: None,
+                        model: None,
+                        muted_until: None,
+                        locale: None,
+                    };
+                    (jid.to_string(), group)
+                })
+                .collect();
+        let main_task = ScheduledTask {
+            id: "task-main".into(),
+            group_folder: "main".into(),
+            chat_jid: "tg:main".into(),
+            prompt: "Daily digest".into(),
+            schedule_type: "once".into(),
+            schedule_value: "2099-01-01T00:00:00Z".into(),
+            context_mode: "isolated".into(),
+            next_run: Some("2099-01-01T00:00:00Z".into()),
+            last_run: None,
+            last_result: None,
+            status: "active".into(),
+            created_at: "2026-01-01T00:00:00Z".into(),
+            task_config: None,
+        };
+        pool.create_task(&main_task).await.unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let write = |name: &str, body: serde_json::Value| {
+            let dir = tmp.path().join("team-eng/tasks");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("{name}.json")), body.to_string()).unwrap();
+        };
+        write(
+            "001-own",
+            serde_json::json!({
+                "type": "schedule_task", "prompt": "Standup summary",
+                "schedule_type": "cron", "schedule_value": "0 9 * * 1-5",
+            }),
+        );
+        write(
+            "002-other",
+            serde_json::json!({
+                "type": "schedule_task", "prompt": "Sneaky", "targetJid": "tg:main",
+                "schedule_type": "interval", "schedule_value": "60000",
+            }),
+        );
+        write(
+            "003-cancel",
+            serde_json::json!({ "type": "cancel_task", "taskId": "task-main" }),
+        );
+        write(
+            "004-register",
+            serde_json::json!({
+                "type": "register_group", "jid": "tg:new", "name": "New", "folder": "new", "trigger": "@Andy",
+            }),
+        );
+
+        let delegate = Arc::new(RecordingDelegate::default());
+        let watcher = IpcWatcher::new(
+            IpcWatcherConfig {
+                ipc_base_dir: tmp.path().to_path_buf(),
+                ..Default::default()
+            },
+            Arc::new(DemarchAdapter::new(DemarchConfig::default(), ".")),
+            delegate.clone(),
+        )
+        .with_native_tasks(NativeTasks::new(
+            pool.clone(),
+            Arc::new(tokio::sync::RwLock::new(groups)),
+            "UTC",
+        ));
+        tokio::task::spawn_blocking(move || watcher.poll_once())
+            .await
+            .unwrap();
+
+        let ack = |name: &str| -> IpcQueryResponse {
+            let path = tmp.path().join(format!("team-eng/responses/{name}.json"));
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+        };
+        assert_eq!(ack("001-own").status, "ok");
+        assert_eq!(ack("002-other").status, "error");
+        assert_eq!(ack("003-cancel").status, "error");
+        assert!(
+            !tmp.path()
+                .join("team-eng/responses/004-register.json")
+                .exists()
+        );
+        assert_eq!(
+            *delegate.tasks.lock().unwrap(),
+            vec!["team-eng".to_string()]
+        );
+
+        let eng = pool.get_tasks_for_group("team-eng").await.unwrap();
+        assert_eq!(eng.len(), 1);
+        assert_eq!(eng[0].chat_jid, "tg:eng");
+        assert_eq!(eng[0].status, "active");
+        assert!(eng[0].next_run.is_some());
+        assert!(pool.get_task_by_id("task-main").await.unwrap().is_some());
+    }
 }
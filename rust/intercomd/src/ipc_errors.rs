@@ -0,0 +1,378 @@
+//! Dead-letter tooling for IPC files that failed to process.
+//!
+//! The watcher moves a file it cannot handle to `{ipc}/errors/{group}-{file}`
+//! and records where it came from and why in a `{name}.meta` sidecar. From
+//! here a file can be listed, inspected, fixed and requeued into the owning
+//! group's directory, or purged. Files dead-lettered before sidecars existed
+//! have their group matched against the IPC group directories and their
+//! directory inferred from their contents.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use intercom_core::{IpcMessage, IpcQuery, IpcTask};
+use serde::{Deserialize, Serialize};
+
+/// Directories a dead-lettered file can be requeued into.
+pub const KINDS: &[&str] = &["messages", "tasks", "queries"];
+
+/// Why and from where a file was dead-lettered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorMeta {
+    pub group: String,
+    pub kind: String,
+    /// Original file name in the group's directory.
+    pub file: String,
+    pub error: String,
+    pub failed_at: String,
+}
+
+/// A file in the errors directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub name: String,
+    pub group: Option<String>,
+    pub kind: Option<String>,
+    pub file: String,
+    pub error: Option<String>,
+    pub failed_at: Option<String>,
+    pub size: u64,
+}
+
+/// Outcome of a retry.
+#[derive(Debug)]
+pub enum Retry {
+    /// Where the file was requeued.
+    Requeued(PathBuf),
+    NotFound,
+    /// The file still would not be accepted, or its owner is unknown.
+    Invalid(String),
+}
+
+fn errors_dir(ipc_base: &Path) -> PathBuf {
+    ipc_base.join("errors")
+}
+
+fn meta_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".meta");
+    PathBuf::from(name)
+}
+
+/// The dead letter's path, if `name` is a plain file name that exists.
+fn locate(ipc_base: &Path, name: &str) -> Option<PathBuf> {
+    if name.is_empty()
+        || Path::new(name).file_name().is_none_or(|f| f != name)
+        || name.ends_with(".meta")
+    {
+        return None;
+    }
+    let path = errors_dir(ipc_base).join(name);
+    path.is_file().then_some(path)
+}
+
+/// Move `file_path`, from `group`'s IPC directory, to the errors directory
+/// with a sidecar recording `reason`.
+pub fn dead_letter(
+    ipc_base: &Path,
+    file_path: &Path,
+    group: &str,
+    reason: &str,
+) -> anyhow::Result<PathBuf> {
+    let dir = errors_dir(ipc_base);
+    fs::create_dir_all(&dir)?;
+    let file = file_path
+        .file_name()
+        .context("IPC file has no name")?
+        .to_string_lossy()
+        .into_owned();
+    let dest = dir.join(format!("{group}-{file}"));
+    fs::rename(file_path, &dest)?;
+    let meta = ErrorMeta {
+        group: group.to_string(),
+        kind: file_path
+            .parent()
+            .and_then(Path::file_name)
+            .map(|kind| kind.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        file,
+        error: reason.to_string(),
+        failed_at: Utc::now().to_rfc3339(),
+    };
+    fs::write(meta_path(&dest), serde_json::to_vec_pretty(&meta)?)?;
+    Ok(dest)
+}
+
+fn read_meta(path: &Path) -> Option<ErrorMeta> {
+    serde_json::from_slice(&fs::read(meta_path(path)).ok()?).ok()
+}
+
+/// The longest group directory name that prefixes `name`, for files without
+/// a sidecar.
+fn guess_group(ipc_base: &Path, name: &str) -> Option<String> {
+    fs::read_dir(ipc_base)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|group| {
+            group != "errors"
+                && name.len() > group.len() + 1
+                && name.starts_with(&format!("{group}-"))
+        })
+        .max_by_key(String::len)
+}
+
+/// The directory `content` belongs in, judged by which IPC type it parses as.
+fn guess_kind(content: &str) -> Option<&'static str> {
+    if serde_json::from_str::<IpcMessage>(content).is_ok_and(|msg| msg.msg_type == "message") {
+        Some("messages")
+    } else if serde_json::from_str::<IpcTask>(content).is_ok() {
+        Some("tasks")
+    } else if serde_json::from_str::<IpcQuery>(content).is_ok() {
+        Some("queries")
+    } else {
+        None
+    }
+}
+
+fn describe(ipc_base: &Path, path: &Path) -> anyhow::Result<DeadLetter> {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let size = fs::metadata(path)?.len();
+    Ok(match read_meta(path) {
+        Some(meta) => DeadLetter {
+            name,
+            group: Some(meta.group),
+            kind: Some(meta.kind).filter(|kind| KINDS.contains(&kind.as_str())),
+            file: meta.file,
+            error: Some(meta.error),
+            failed_at: Some(meta.failed_at),
+            size,
+        },
+        None => {
+            let group = guess_group(ipc_base, &name);
+            let file = match &group {
+                Some(group) => name[group.len() + 1..].to_string(),
+                None => name.clone(),
+            };
+            let kind = fs::read_to_string(path)
+                .ok()
+                .and_then(|content| guess_kind(&content));
+            let failed_at = fs::metadata(path)?
+                .modified()
+                .ok()
+                .map(|at| DateTime::<Utc>::from(at).to_rfc3339());
+            DeadLetter {
+                name,
+                group,
+                kind: kind.map(str::to_string),
+                file,
+                error: None,
+                failed_at,
+                size,
+            }
+        }
+    })
+}
+
+/// Every dead letter, oldest name first.
+pub fn list(ipc_base: &Path) -> anyhow::Result<Vec<DeadLetter>> {
+    let dir = errors_dir(ipc_base);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_none_or(|ext| ext != "meta"))
+        .collect();
+    paths.sort();
+    paths.iter().map(|path| describe(ipc_base, path)).collect()
+}
+
+/// A dead letter and its contents.
+pub fn show(ipc_base: &Path, name: &str) -> anyhow::Result<Option<(DeadLetter, String)>> {
+    let Some(path) = locate(ipc_base, name) else {
+        return Ok(None);
+    };
+    let content = String::from_utf8_lossy(&fs::read(&path)?).into_owned();
+    Ok(Some((describe(ipc_base, &path)?, content)))
+}
+
+fn parses_as(kind: &str, content: &str) -> Result<(), String> {
+    let parsed = match kind {
+        "messages" => serde_json::from_str::<IpcMessage>(content).map(drop),
+        "tasks" => serde_json::from_str::<IpcTask>(content).map(drop),
+        "queries" => serde_json::from_str::<IpcQuery>(content).map(drop),
+        _ => return Err(format!("unknown IPC directory {kind}")),
+    };
+    parsed.map_err(|e| format!("does not parse as {kind}: {e}"))
+}
+
+/// Requeue a dead letter into its group's directory, replacing its contents
+/// with `fixed` if given. The file must parse as what its directory expects;
+/// otherwise it would only be dead-lettered again.
+pub fn retry(ipc_base: &Path, name: &str, fixed: Option<&str>) -> anyhow::Result<Retry> {
+    let Some(path) = locate(ipc_base, name) else {
+        return Ok(Retry::NotFound);
+    };
+    let letter = describe(ipc_base, &path)?;
+    let content = match fixed {
+        Some(fixed) => fixed.to_string(),
+        None => fs::read_to_string(&path)?,
+    };
+    let Some(group) = letter.group else {
+        return Ok(Retry::Invalid(format!(
+            "cannot tell which group {name} belongs to"
+        )));
+    };
+    let Some(kind) = letter
+        .kind
+        .or_else(|| guess_kind(&content).map(str::to_string))
+    else {
+        return Ok(Retry::Invalid(format!(
+            "cannot tell which IPC directory {name} belongs in"
+        )));
+    };
+    if let Err(e) = parses_as(&kind, &content) {
+        return Ok(Retry::Invalid(e));
+    }
+
+    let dir = ipc_base.join(&group).join(&kind);
+    fs::create_dir_all(&dir)?;
+    let dest = dir.join(&letter.file);
+    // Written aside and renamed so the watcher never reads a partial file.
+    let temp = dir.join(format!("{}.tmp", letter.file));
+    fs::write(&temp, &content)?;
+    fs::rename(&temp, &dest)?;
+    fs::remove_file(&path)?;
+    let _ = fs::remove_file(meta_path(&path));
+    Ok(Retry::Requeued(dest))
+}
+
+/// Delete the named dead letters, or all of them when `names` is empty,
+/// sparing those modified within `older_than`. Returns the names removed.
+pub fn purge(
+    ipc_base: &Path,
+    names: &[String],
+    older_than: Option<Duration>,
+) -> anyhow::Result<Vec<String>> {
+    let paths: Vec<PathBuf> = if names.is_empty() {
+        list(ipc_base)?
+            .into_iter()
+            .map(|letter| errors_dir(ipc_base).join(letter.name))
+            .collect()
+    } else {
+        names
+            .iter()
+            .filter_map(|name| locate(ipc_base, name))
+            .collect()
+    };
+    let now = SystemTime::now();
+    let mut removed = Vec::new();
+    for path in paths {
+        if let Some(older_than) = older_than {
+            let modified = fs::metadata(&path)?.modified()?;
+            if now.duration_since(modified).unwrap_or_default() < older_than {
+                continue;
+            }
+        }
+        fs::remove_file(&path)?;
+        let _ = fs::remove_file(meta_path(&path));
+        removed.push(
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        );
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_files_are_requeued_into_their_group() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ipc = tmp.path();
+        let tasks = ipc.join("team-eng/tasks");
+        fs::create_dir_all(&tasks).unwrap();
+        fs::write(tasks.join("001-task.json"), "{not json").unwrap();
+        dead_letter(
+            ipc,
+            &tasks.join("001-task.json"),
+            "team-eng",
+            "expected value",
+        )
+        .unwrap();
+
+        let letters = list(ipc).unwrap();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].name, "team-eng-001-task.json");
+        assert_eq!(letters[0].group.as_deref(), Some("team-eng"));
+        assert_eq!(letters[0].kind.as_deref(), Some("tasks"));
+        assert_eq!(letters[0].error.as_deref(), Some("expected value"));
+
+        assert!(matches!(
+            retry(ipc, "team-eng-001-task.json", None).unwrap(),
+            Retry::Invalid(_)
+        ));
+        assert!(matches!(
+            retry(ipc, "../team-eng-001-task.json", None).unwrap(),
+            Retry::NotFound
+        ));
+
+        let fixed = r#"{"type":"pause_task","taskId":"task-1"}"#;
+        let Retry::Requeued(dest) = retry(ipc, "team-eng-001-task.json", Some(fixed)).unwrap()
+        else {
+            panic!("expected the fixed file to be requeued");
+        };
+        assert_eq!(dest, tasks.join("001-task.json"));
+        assert_eq!(fs::read_to_string(dest).unwrap(), fixed);
+        assert!(list(ipc).unwrap().is_empty());
+        assert_eq!(fs::read_dir(ipc.join("errors")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn files_without_a_sidecar_are_matched_to_a_group() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ipc = tmp.path();
+        fs::create_dir_all(ipc.join("team/messages")).unwrap();
+        fs::create_dir_all(ipc.join("team-eng/messages")).unwrap();
+        fs::create_dir_all(ipc.join("errors")).unwrap();
+        let message = r#"{"type":"message","chatJid":"tg:1","text":"hi"}"#;
+        fs::write(ipc.join("errors/team-eng-001-msg.json"), message).unwrap();
+        fs::write(ipc.join("errors/stray.json"), "{}").unwrap();
+
+        let letters = list(ipc).unwrap();
+        assert_eq!(letters[1].name, "team-eng-001-msg.json");
+        assert_eq!(letters[1].group.as_deref(), Some("team-eng"));
+        assert_eq!(letters[1].kind.as_deref(), Some("messages"));
+        assert_eq!(letters[1].file, "001-msg.json");
+
+        assert!(matches!(
+            retry(ipc, "team-eng-001-msg.json", None).unwrap(),
+            Retry::Requeued(_)
+        ));
+        assert!(ipc.join("team-eng/messages/001-msg.json").exists());
+
+        assert!(
+            purge(ipc, &[], Some(Duration::from_secs(3600)))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            purge(ipc, &[], None).unwrap(),
+            vec!["stray.json".to_string()]
+        );
+    }
+}
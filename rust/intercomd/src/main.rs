@@ -1,14 +1,36 @@
+mod calendar;
+mod channels;
 mod commands;
 mod container;
+mod correlation;
 mod db;
+mod draft;
+mod email;
+mod event_stream;
 mod events;
+mod group_admin;
+mod health;
+mod i18n;
 mod ipc;
+mod ipc_errors;
+mod markdown;
+mod matrix;
 mod message_loop;
+mod metrics;
+mod outbound;
 mod process_group;
 mod queue;
+mod rate_limit;
+mod replay;
+mod request_id;
 mod scheduler;
 mod scheduler_wiring;
+mod shutdown;
 mod telegram;
+mod webhook_channel;
+mod webhooks;
+mod whatsapp;
+mod workspace_git;
 
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -16,17 +38,22 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use anyhow::{Context, anyhow};
-use axum::extract::State;
-use axum::routing::{get, post};
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, patch, post, put};
 use axum::{Json, Router};
 use clap::{Parser, Subcommand};
+use event_stream::{LiveEvent, LiveEventKind};
 use intercom_compat::{
     LegacyLayout, LegacySnapshot, MigrationOptions, inspect_legacy_layout, inspect_legacy_sqlite,
     migrate_legacy_to_postgres, verify_migration_parity,
 };
 use intercom_core::{
-    DemarchAdapter, DemarchResponse, IntercomConfig, PgPool, ReadOperation, RegisteredGroup,
-    WriteOperation, load_config,
+    CircuitSnapshot, ContainerExecutorKind, DemarchAdapter, DemarchResponse, IngressGroupSource,
+    IntercomConfig, IpcTaskHandling, NewMessage, PgPool, ProvisionOptions, ReadOperation,
+    RegisteredGroup, SharedStorage, SqliteStore, StorageBackend, TelegramIngest, WriteOperation,
+    load_config, new_correlation_id, provision_database,
 };
 use serde::{Deserialize, Serialize};
 use telegram::{
@@ -35,7 +62,7 @@ use telegram::{
     TelegramSendResponse,
 };
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{info, warn};
 
 #[derive(Parser, Debug)]
 #[command(name = "intercomd", version, about = "Intercom Rust daemon skeleton")]
@@ -56,6 +83,10 @@ enum Command {
     MigrateLegacy(MigrateLegacyArgs),
     /// Compare legacy SQLite counts against migrated Postgres tables.
     VerifyMigration(VerifyMigrationArgs),
+    /// Create a least-privilege Postgres role and schema for the daemon.
+    ProvisionDb(ProvisionDbArgs),
+    /// Inspect and retry IPC files that failed to process.
+    Ipc(IpcArgs),
 }
 
 #[derive(clap::Args, Debug)]
@@ -104,6 +135,65 @@ struct VerifyMigrationArgs {
     config: PathBuf,
 }
 
+#[derive(clap::Args, Debug)]
+struct ProvisionDbArgs {
+    /// Admin DSN allowed to create roles and schemas. Used once, not stored.
+    #[arg(long, env = "INTERCOM_ADMIN_DSN")]
+    dsn: String,
+    #[arg(long, default_value = intercom_core::provision::DEFAULT_APP_ROLE)]
+    role: String,
+    #[arg(long, default_value = intercom_core::provision::DEFAULT_APP_SCHEMA)]
+    schema: String,
+    /// Role password. Generated for a new role if omitted; an existing
+    /// role's password is left unchanged.
+    #[arg(long, env = "INTERCOM_APP_PASSWORD")]
+    password: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct IpcArgs {
+    #[command(subcommand)]
+    command: IpcCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum IpcCommand {
+    /// Dead-lettered files in the IPC errors directory.
+    Errors(IpcErrorsArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct IpcErrorsArgs {
+    #[arg(long, default_value = "data/ipc")]
+    ipc_dir: PathBuf,
+    #[command(subcommand)]
+    command: IpcErrorsCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum IpcErrorsCommand {
+    /// List dead-lettered files with their group and error.
+    List,
+    /// Print a dead-lettered file and its contents.
+    Show { name: String },
+    /// Requeue a file into its group's IPC directory.
+    Retry {
+        name: String,
+        /// Replace the file's contents with this file's first.
+        #[arg(long)]
+        from: Option<PathBuf>,
+    },
+    /// Delete the named files, or every file with --all.
+    Purge {
+        names: Vec<String>,
+        #[arg(long, conflicts_with = "names")]
+        all: bool,
+        /// Only files at least this many hours old.
+        #[arg(long)]
+        older_than_hours: Option<u64>,
+    },
+}
+
 /// Shared orchestrator state: registered groups indexed by JID.
 type Groups = HashMap<String, RegisteredGroup>;
 /// Shared session state: group folder → session ID.
@@ -115,11 +205,24 @@ struct AppState {
     config: Arc<IntercomConfig>,
     demarch: Arc<DemarchAdapter>,
     telegram: Arc<TelegramBridge>,
-    db: Option<PgPool>,
+    email: Arc<email::EmailBridge>,
+    webhook_channel: Arc<webhook_channel::WebhookChannel>,
+    /// Every outbound channel, the Telegram bridge included, by JID prefix.
+    channels: Arc<channels::ChannelRegistry>,
+    db: Option<SharedStorage>,
     queue: Arc<queue::GroupQueue>,
     groups: Arc<RwLock<Groups>>,
     sessions: Arc<RwLock<Sessions>>,
     agent_timestamps: Arc<RwLock<message_loop::AgentTimestamps>>,
+    run_config: container::runner::RunConfig,
+    warm_pool: Option<Arc<container::warm_pool::WarmPool>>,
+    /// Enqueues a task as the scheduler does; set once the orchestrator
+    /// is wired.
+    run_task: Option<scheduler::TaskCallback>,
+    /// Built-in and `[commands.custom]` slash commands.
+    commands: Arc<commands::CommandRegistry>,
+    shutdown: shutdown::Shutdown,
+    readiness: Arc<health::ReadinessProbe>,
 }
 
 #[derive(Serialize)]
@@ -133,14 +236,26 @@ struct HealthResponse {
 
 #[derive(Serialize)]
 struct ReadyResponse {
+    /// `ready`, `degraded`, `down` or `shutting_down`; the last two are
+    /// answered 503.
     status: &'static str,
     runtime_profiles: usize,
     demarch_writes_restricted_to_main: bool,
     telegram_bridge_enabled: bool,
     postgres_connected: bool,
+    storage_backend: Option<&'static str>,
+    /// Postgres circuit-breaker state; absent for SQLite or no storage.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    storage_circuit: Option<CircuitSnapshot>,
     orchestrator_enabled: bool,
     registered_groups: usize,
     active_containers: usize,
+    queue_paused: bool,
+    /// Warm container pool metrics; absent when the pool is disabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warm_pool: Option<container::warm_pool::WarmPoolStats>,
+    /// Each configured dependency's probe.
+    dependencies: health::Dependencies,
 }
 
 #[derive(Serialize)]
@@ -188,6 +303,10 @@ async fn main() -> anyhow::Result<()> {
         Command::InspectLegacy(args) => inspect_legacy(args),
         Command::MigrateLegacy(args) => migrate_legacy(args).await,
         Command::VerifyMigration(args) => verify_migration(args).await,
+        Command::ProvisionDb(args) => provision_db(args).await,
+        Command::Ipc(IpcArgs {
+            command: IpcCommand::Errors(args),
+        }) => ipc_errors_cli(args),
     }
 }
 
@@ -213,35 +332,27 @@ async fn serve(args: ServeArgs) -> anyhow::Result<()> {
     let host_callback_url = config.server.host_callback_url.clone();
     let project_root =
         std::env::current_dir().context("failed to resolve current working directory")?;
-    let demarch = Arc::new(DemarchAdapter::new(config.demarch.clone(), &project_root));
-    let telegram = TelegramBridge::new(&config);
-
-    // Connect to Postgres if DSN is configured
-    let db = if let Some(ref dsn) = config.storage.postgres_dsn {
-        if !dsn.trim().is_empty() {
-            let pool = PgPool::new(dsn.clone());
-            match pool.connect().await {
-                Ok(()) => {
-                    info!("postgres persistence layer connected");
-                    Some(pool)
-                }
-                Err(e) => {
-                    tracing::warn!(err = %e, "postgres connection failed, DB endpoints disabled");
-                    None
-                }
-            }
-        } else {
-            None
-        }
-    } else {
-        None
-    };
+    let db = open_storage(&config).await;
+    let mut demarch = DemarchAdapter::new(config.demarch.clone(), &project_root);
+    if let Some(ref pool) = db {
+        demarch = demarch.with_audit(pool.clone());
+    }
+    let demarch = Arc::new(demarch);
 
     // Initialize orchestrator state
     let queue = Arc::new(queue::GroupQueue::new(
         config.orchestrator.max_concurrent_containers,
         project_root.join("data"),
     ));
+    // Read before anything is enqueued: the first enqueue rewrites it.
+    let restored_queue = queue::load_journal(&project_root.join("data"));
+    if !restored_queue.is_empty() {
+        info!(
+            messages = restored_queue.messages.len(),
+            tasks = restored_queue.tasks.len(),
+            "restoring queued work from the last run"
+        );
+    }
 
     // Load registered groups and sessions from Postgres (if available)
     let (groups, sessions) = if let Some(ref pool) = db {
@@ -269,9 +380,34 @@ async fn serve(args: ServeArgs) -> anyhow::Result<()> {
     } else {
         (HashMap::new(), HashMap::new())
     };
+    let main_jid = groups
+        .iter()
+        .find(|(_, g)| g.folder == config.orchestrator.main_group_folder)
+        .map(|(jid, _)| jid.as_str());
+    queue.set_main_group(main_jid).await;
+    let group_limits = groups
+        .iter()
+        .map(|(jid, g)| {
+            let config: Option<container::security::ContainerConfig> = g
+                .container_config
+                .as_ref()
+                .and_then(|v| serde_json::from_value(v.clone()).ok());
+            (
+                jid.clone(),
+                queue::GroupLimits::from_config(config.as_ref()),
+            )
+        })
+        .collect();
+    queue.set_group_limits(group_limits).await;
 
     let groups = Arc::new(RwLock::new(groups));
     let sessions = Arc::new(RwLock::new(sessions));
+    let telegram = TelegramBridge::new(&config)
+        .with_storage(
+            db.clone(),
+            std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into()),
+        )
+        .with_groups(groups.clone());
 
     // Load agent timestamps from Postgres (or start empty)
     let agent_timestamps = if let Some(ref pool) = db {
@@ -280,32 +416,136 @@ async fn serve(args: ServeArgs) -> anyhow::Result<()> {
         Arc::new(RwLock::new(message_loop::AgentTimestamps::default()))
     };
 
-    let state = AppState {
+    // Pods cannot be pre-started and claimed like local containers.
+    let warm_pool = (config.container.warm_pool.enabled
+        && config.container.executor == ContainerExecutorKind::Local)
+        .then(|| {
+            Arc::new(container::warm_pool::WarmPool::new(
+                &config.container.warm_pool,
+            ))
+        });
+
+    let run_config = container::runner::RunConfig {
+        project_root: project_root.clone(),
+        groups_dir: project_root.join("groups"),
+        data_dir: project_root.join("data"),
+        timezone: config.scheduler.timezone.clone(),
+        idle_timeout_ms: config.orchestrator.idle_timeout_ms,
+        allowlist: None,
+        runtime_profiles: config.runtimes.profiles.clone(),
+        snapshots: config.snapshots.clone(),
+        channels: config.channels.clone(),
+        egress: config.container.egress.clone(),
+        logs: config.container.logs.clone(),
+        verify_image_digests: config.container.images.verify_digests,
+        executor: match config.container.executor {
+            ContainerExecutorKind::Local => {
+                Arc::new(container::executor::CliExecutor::new(warm_pool.clone()))
+            }
+            ContainerExecutorKind::Kubernetes => {
+                Arc::new(container::kubernetes::KubernetesExecutor::new(
+                    &config.container.kubernetes,
+                    &project_root,
+                ))
+            }
+        },
+        stop_timeout: std::time::Duration::from_secs(config.container.stop_timeout_secs),
+        startup_timeout: (config.container.startup_timeout_secs > 0)
+            .then(|| std::time::Duration::from_secs(config.container.startup_timeout_secs)),
+        stdin_channels: queue.stdin_channels(),
+        disk_quotas: container::disk_quota::DiskQuotas::new(config.container.quota.clone()),
+        run_timeout: None,
+    };
+    let after_ms = |ms: u64| (ms > 0).then(|| std::time::Duration::from_millis(ms));
+    queue
+        .set_preemption(
+            queue::PreemptionPolicy {
+                wind_down_after: after_ms(config.orchestrator.task_preempt_after_ms),
+                hard_stop_after: after_ms(config.orchestrator.critical_task_stop_after_ms),
+            },
+            run_config.executor.clone(),
+            run_config.stop_timeout,
+        )
+        .await;
+
+    let telegram = Arc::new(telegram);
+    let matrix = Arc::new(matrix::MatrixBridge::new(&config).with_storage(
+        db.clone(),
+        std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into()),
+    ));
+    let email = Arc::new(email::EmailBridge::new(&config).with_storage(
+        db.clone(),
+        std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into()),
+    ));
+    let webhook_channel = Arc::new(
+        webhook_channel::WebhookChannel::new(&config, groups.clone()).with_storage(
+            db.clone(),
+            std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into()),
+        ),
+    );
+    let mut channels = channels::ChannelRegistry::new(config.channels.default_channel.clone())
+        .register(telegram.clone())
+        .register(matrix.clone())
+        .register(webhook_channel.clone());
+    if config.email.enabled {
+        if !email.is_enabled() {
+            tracing::warn!("email.enabled but email.smtp_host or email.from_address is not set");
+        }
+        channels = channels.register(email.clone());
+    }
+
+    let command_registry = Arc::new(commands::CommandRegistry::from_config(&config.commands));
+    let mut state = AppState {
         started_at: Instant::now(),
         config: Arc::new(config),
         demarch: demarch.clone(),
-        telegram: Arc::new(telegram),
+        telegram,
+        email,
+        webhook_channel,
+        channels: Arc::new(channels),
         db,
         queue,
         groups,
         sessions,
         agent_timestamps,
+        run_config,
+        warm_pool,
+        run_task: None,
+        commands: command_registry,
+        shutdown: shutdown::Shutdown::default(),
+        readiness: Arc::default(),
     };
 
     // IPC watcher — polls data/ipc/ directories for container messages/queries
-    let ipc_config = ipc::IpcWatcherConfig {
-        ipc_base_dir: project_root.join("data/ipc"),
-        ..Default::default()
-    };
-    let delegate: Arc<dyn ipc::IpcDelegate> =
-        Arc::new(ipc::HttpDelegate::new(&host_callback_url));
+    let ipc_config =
+        ipc::IpcWatcherConfig::from_config(project_root.join("data/ipc"), &state.config.ipc);
+    let delegate: Arc<dyn ipc::IpcDelegate> = Arc::new(ipc::HttpDelegate::new(&host_callback_url));
     let registry = ipc::GroupRegistry::new();
-    info!(
-        host_callback_url = %host_callback_url,
-        "IPC delegate: forwarding messages/tasks to Node host"
-    );
-    let ipc_watcher =
-        ipc::IpcWatcher::with_registry(ipc_config, demarch, delegate, registry.clone());
+    let mut ipc_watcher =
+        ipc::IpcWatcher::with_registry(ipc_config, demarch, delegate, registry.clone())
+            .with_groups(state.groups.clone());
+    match (&state.config.ipc.tasks, &state.db) {
+        (IpcTaskHandling::Native, Some(pool)) => {
+            ipc_watcher = ipc_watcher.with_native_tasks(ipc::NativeTasks::new(
+                pool.clone(),
+                state.groups.clone(),
+                state.config.scheduler.timezone.clone(),
+            ));
+            info!(
+                host_callback_url = %host_callback_url,
+                "IPC delegate: forwarding messages to Node host, handling tasks natively"
+            );
+        }
+        (IpcTaskHandling::Native, None) => warn!(
+            host_callback_url = %host_callback_url,
+            "IPC tasks configured as native but storage is not configured; forwarding messages/tasks to Node host"
+        ),
+        (IpcTaskHandling::Host, _) => info!(
+            host_callback_url = %host_callback_url,
+            "IPC delegate: forwarding messages/tasks to Node host"
+        ),
+    }
+    let ipc_watcher = Arc::new(ipc_watcher);
     let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
 
     let ipc_shutdown_rx = shutdown_rx.clone();
@@ -320,7 +560,8 @@ async fn serve(args: ServeArgs) -> anyhow::Result<()> {
         ipc::sync_registry_loop(registry, registry_url, registry_shutdown_rx).await;
     });
 
-    // Event consumer — polls ic events tail and sends push notifications
+    // Event consumer — polls ic events tail (or follows ic events follow) and sends
+    // push notifications
     let events_config = events::EventConsumerConfig {
         poll_interval: std::time::Duration::from_millis(
             state.config.events.poll_interval_ms,
@@ -328,6 +569,8 @@ async fn serve(args: ServeArgs) -> anyhow::Result<()> {
         batch_size: state.config.events.batch_size,
         notification_jid: state.config.events.notification_jid.clone(),
         enabled: state.config.events.enabled,
+        mode: state.config.events.mode,
+        follow_restart: std::time::Duration::from_millis(state.config.events.follow_restart_ms),
     };
     let events_demarch = state.demarch.clone();
     let events_delegate: Arc<dyn ipc::IpcDelegate> =
@@ -339,20 +582,133 @@ async fn serve(args: ServeArgs) -> anyhow::Result<()> {
         consumer.run(events_shutdown_rx).await;
     });
 
+    // Storage monitor — probes the database and reconnects with backoff
+    let storage_monitor_handle = match state.db.clone() {
+        Some(db) if state.config.storage.health_check_interval_ms > 0 => {
+            let interval =
+                std::time::Duration::from_millis(state.config.storage.health_check_interval_ms);
+            let monitor_shutdown_rx = shutdown_rx.clone();
+            Some(tokio::spawn(async move {
+                health::storage_monitor_loop(db, interval, monitor_shutdown_rx).await;
+            }))
+        }
+        _ => None,
+    };
+
+    // Disk usage scans of group folders, for quotas and /status
+    let disk_scan_handle = (state.config.container.quota.scan_interval_secs > 0).then(|| {
+        tokio::spawn(container::disk_quota::run_scanner(
+            state.run_config.disk_quotas.clone(),
+            state.run_config.groups_dir.clone(),
+            state.groups.clone(),
+            shutdown_rx.clone(),
+        ))
+    });
+
+    // Warm pool reaper — recycles idle containers past their TTL
+    let warm_pool_handle = state.warm_pool.clone().map(|pool| {
+        let reaper_shutdown_rx = shutdown_rx.clone();
+        tokio::spawn(container::warm_pool::run_reaper(pool, reaper_shutdown_rx))
+    });
+
+    // Telegram ingestion — intercomd receives updates instead of the host
+    let telegram_ingest_handle = if state.config.telegram.ingest == TelegramIngest::Poll {
+        if state.telegram.is_enabled() {
+            let poll_state = state.clone();
+            let poll_shutdown_rx = shutdown_rx.clone();
+            let assistant_name =
+                std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into());
+            Some(tokio::spawn(async move {
+                let bridge = (*poll_state.telegram).clone();
+                let config = poll_state.config.telegram.clone();
+                telegram::run_poll_loop(
+                    bridge,
+                    config,
+                    assistant_name,
+                    poll_shutdown_rx,
+                    |update| {
+                        let state = poll_state.clone();
+                        async move { handle_inbound_update(&state, update).await }
+                    },
+                )
+                .await;
+            }))
+        } else {
+            tracing::warn!("telegram.ingest = \"poll\" but TELEGRAM_BOT_TOKEN is not set");
+            None
+        }
+    } else if state.config.telegram.ingest == TelegramIngest::Webhook {
+        if state.telegram.is_enabled() {
+            let bridge = (*state.telegram).clone();
+            let config = state.config.telegram.clone();
+            let webhook_shutdown_rx = shutdown_rx.clone();
+            Some(tokio::spawn(async move {
+                telegram::register_webhook(bridge, config, webhook_shutdown_rx).await;
+            }))
+        } else {
+            tracing::warn!("telegram.ingest = \"webhook\" but TELEGRAM_BOT_TOKEN is not set");
+            None
+        }
+    } else {
+        None
+    };
+
     // Orchestrator loops (message poll + scheduler) — behind feature flag
+    // Matrix ingestion — /sync loop for rooms registered as mx: groups
+    let matrix_sync_handle = if !state.config.matrix.enabled {
+        None
+    } else if matrix.is_enabled() {
+        let sync_state = state.clone();
+        let bridge = (*matrix).clone();
+        let config = state.config.matrix.clone();
+        let sync_shutdown_rx = shutdown_rx.clone();
+        Some(tokio::spawn(async move {
+            matrix::run_sync_loop(bridge, config, sync_shutdown_rx, |request| {
+                let state = sync_state.clone();
+                async move { store_inbound_message(&state, request, matrix::MATRIX_CHANNEL).await }
+            })
+            .await;
+        }))
+    } else {
+        tracing::warn!(
+            "matrix.enabled but MATRIX_ACCESS_TOKEN or matrix.homeserver_url is not set"
+        );
+        None
+    };
+
     let mut scheduler_handle: Option<tokio::task::JoinHandle<()>> = None;
+    let mut calendar_handle: Option<tokio::task::JoinHandle<()>> = None;
     let mut message_loop_handle: Option<tokio::task::JoinHandle<()>> = None;
+    let mut unmute_sweep_handle: Option<tokio::task::JoinHandle<()>> = None;
+    let mut image_update_handle: Option<tokio::task::JoinHandle<()>> = None;
 
     if state.config.orchestrator.enabled {
+        // Pick Kubernetes, or docker, podman or nerdctl, before the first
+        // container runs.
+        if state.config.container.executor == ContainerExecutorKind::Kubernetes {
+            if let Err(e) = state.run_config.executor.probe().await {
+                tracing::warn!(err = %e, "Kubernetes executor is not ready");
+            }
+        } else {
+            if let Err(e) = container::engine::detect(&state.config.container).await {
+                tracing::warn!(err = %e, "container runtime detection failed, using {}", container::engine::active().binary);
+            }
+            if state.config.container.images.pull_on_startup {
+                container::images::pull_all(&state.config.runtimes.profiles).await;
+            }
+            if state.config.container.images.update_check_interval_secs > 0 {
+                let profiles = state.config.runtimes.profiles.clone();
+                let images = state.config.container.images.clone();
+                let images_shutdown = shutdown_rx.clone();
+                image_update_handle = Some(tokio::spawn(container::images::run_update_check(
+                    profiles,
+                    images,
+                    images_shutdown,
+                )));
+            }
+        }
         if let Some(ref pool) = state.db {
-            let run_config = container::runner::RunConfig {
-                project_root: project_root.clone(),
-                groups_dir: project_root.join("groups"),
-                data_dir: project_root.join("data"),
-                timezone: state.config.scheduler.timezone.clone(),
-                idle_timeout_ms: state.config.orchestrator.idle_timeout_ms,
-                allowlist: None,
-            };
+            let run_config = state.run_config.clone();
 
             let assistant_name = std::env::var("ASSISTANT_NAME")
                 .unwrap_or_else(|_| "Amtiskaw".into());
@@ -364,18 +720,49 @@ async fn serve(args: ServeArgs) -> anyhow::Result<()> {
                 state.groups.clone(),
                 state.sessions.clone(),
                 state.agent_timestamps.clone(),
-                state.telegram.clone(),
+                state.channels.clone(),
                 assistant_name.clone(),
                 state.config.orchestrator.main_group_folder.clone(),
                 run_config.clone(),
             );
             state.queue.set_process_messages_fn(process_fn).await;
+            let deadline_ms = state.config.orchestrator.queue_deadline_ms;
+            if deadline_ms > 0 {
+                state
+                    .queue
+                    .set_message_deadline(Some(std::time::Duration::from_millis(deadline_ms)))
+                    .await;
+                let channels = state.channels.clone();
+                let groups = state.groups.clone();
+                state
+                    .queue
+                    .set_expired_fn(Arc::new(move |chat_jid, waited| {
+                        let channels = channels.clone();
+                        let groups = groups.clone();
+                        Box::pin(async move {
+                            let locale = groups.read().await.get(&chat_jid).and_then(|g| g.locale.clone());
+                            let text = i18n::fill(
+                                i18n::locale_or_default(locale.as_deref()),
+                                "notice.queue_expired",
+                                &[("minutes", &waited.as_secs().div_ceil(60))],
+                            );
+                            if let Err(e) = channels.send_text(&chat_jid, &text).await {
+                                tracing::warn!(chat_jid, err = %e, "failed to send queue deadline notice");
+                            }
+                        })
+                    }))
+                    .await;
+            }
+            for chat_jid in &restored_queue.messages {
+                state.queue.enqueue_message_check(chat_jid).await;
+            }
 
             // Message poll loop
             let ml_config = message_loop::MessageLoopConfig {
                 poll_interval_ms: state.config.orchestrator.poll_interval_ms,
                 assistant_name: assistant_name.clone(),
                 main_group_folder: state.config.orchestrator.main_group_folder.clone(),
+                listen_notify: state.config.orchestrator.listen_notify,
             };
             let ml_pool = pool.clone();
             let ml_queue = state.queue.clone();
@@ -388,6 +775,11 @@ async fn serve(args: ServeArgs) -> anyhow::Result<()> {
                 )
                 .await;
             }));
+            unmute_sweep_handle = Some(tokio::spawn(message_loop::run_unmute_sweep(
+                pool.clone(),
+                state.groups.clone(),
+                shutdown_rx.clone(),
+            )));
 
             // Scheduler loop
             let sched_config = scheduler::SchedulerConfig {
@@ -396,41 +788,94 @@ async fn serve(args: ServeArgs) -> anyhow::Result<()> {
                 ),
                 timezone: state.config.scheduler.timezone.clone(),
                 enabled: state.config.scheduler.enabled,
+                jitter: std::time::Duration::from_millis(state.config.scheduler.jitter_ms),
             };
             let task_callback = scheduler_wiring::build_task_callback(
                 pool.clone(),
                 state.queue.clone(),
                 state.groups.clone(),
                 state.sessions.clone(),
-                state.telegram.clone(),
+                state.channels.clone(),
                 run_config,
                 state.config.scheduler.timezone.clone(),
+                state.config.orchestrator.main_group_folder.clone(),
             );
+            let admission = (state.config.scheduler.max_interactive_wait_ms > 0).then(|| {
+                scheduler::TaskAdmission::new(
+                    state.queue.clone(),
+                    std::time::Duration::from_millis(
+                        state.config.scheduler.max_interactive_wait_ms,
+                    ),
+                    state.config.orchestrator.main_group_folder.clone(),
+                )
+            });
+            state.run_task = Some(task_callback.clone());
             let sched_pool = pool.clone();
+            let sched_queue = state.queue.clone();
             let sched_shutdown = shutdown_rx.clone();
+            let restored_tasks = restored_queue
+                .tasks
+                .iter()
+                .map(|t| t.task_id.clone())
+                .collect();
             scheduler_handle = Some(tokio::spawn(async move {
                 scheduler::run_scheduler_loop(
-                    sched_config, sched_pool, task_callback, sched_shutdown,
+                    sched_config,
+                    sched_pool,
+                    sched_queue,
+                    task_callback,
+                    admission,
+                    restored_tasks,
+                    sched_shutdown,
                 )
                 .await;
             }));
 
+            if state.config.scheduler.enabled && state.config.scheduler.calendar_refresh_ms > 0 {
+                let calendar_config = calendar::CalendarSyncConfig {
+                    refresh: std::time::Duration::from_millis(
+                        state.config.scheduler.calendar_refresh_ms,
+                    ),
+                    horizon: std::time::Duration::from_secs(
+                        u64::from(state.config.scheduler.calendar_horizon_days) * 86_400,
+                    ),
+                    timezone: state.config.scheduler.timezone.clone(),
+                };
+                let calendar_pool = pool.clone();
+                let calendar_groups = state.groups.clone();
+                let calendar_shutdown = shutdown_rx.clone();
+                calendar_handle = Some(tokio::spawn(calendar::run_calendar_loop(
+                    calendar_config,
+                    calendar_pool,
+                    calendar_groups,
+                    calendar_shutdown,
+                )));
+            }
+
             info!("orchestrator enabled: message loop + scheduler wired");
         } else {
-            tracing::warn!("orchestrator.enabled=true but no Postgres connection — orchestrator disabled");
+            tracing::warn!(
+                "orchestrator.enabled=true but no storage backend — orchestrator disabled"
+            );
         }
     }
 
-    // DB routes use Option<PgPool> state — nested router avoids exposing
+    // DB routes use Option<SharedStorage> state — nested router avoids exposing
     // full AppState to the db module.
     let db_routes = Router::new()
         .route("/chats", post(db::store_chat_metadata))
         .route("/chats/name", post(db::update_chat_name))
         .route("/chats/all", post(db::get_all_chats))
+        .route("/chats/stats", post(db::get_chat_stats))
         .route("/messages", post(db::store_message))
         .route("/messages/new", post(db::get_new_messages))
         .route("/messages/since", post(db::get_messages_since))
         .route("/messages/conversation", post(db::get_recent_conversation))
+        .route("/messages/edit", post(db::edit_message))
+        .route("/messages/delete", post(db::delete_message))
+        .route("/messages/edits", post(db::get_message_edits))
+        .route("/attachments", post(db::store_attachment))
+        .route("/attachments/get", post(db::get_attachments))
         .route("/tasks", post(db::create_task))
         .route("/tasks/get", post(db::get_task_by_id))
         .route("/tasks/group", post(db::get_tasks_for_group))
@@ -449,42 +894,164 @@ async fn serve(args: ServeArgs) -> anyhow::Result<()> {
         .route("/groups/get", post(db::get_registered_group))
         .route("/groups/set", post(db::set_registered_group))
         .route("/groups/all", post(db::get_all_registered_groups))
-        .with_state(state.db.clone());
+        .with_state(state.db.clone())
+        .merge(
+            Router::new()
+                .route("/query", post(db::run_named_query))
+                .route("/query/list", post(db::list_named_queries))
+                .with_state(db::QueryState {
+                    db: state.db.clone(),
+                    queries: Arc::new(state.config.queries.clone()),
+                }),
+        );
+
+    // Routes reachable from outside, behind [server.rate_limit]
+    let rate_limiter = Arc::new(rate_limit::RateLimiter::from_config(
+        &state.config.server.rate_limit,
+    ));
+    let limited_routes = Router::new()
+        .route("/v1/telegram/ingress", post(telegram_ingress))
+        .route("/v1/telegram/send", post(telegram_send))
+        .route("/v1/telegram/edit", post(telegram_edit))
+        .route("/v1/telegram/callback", post(telegram_callback))
+        .route("/v1/telegram/webhook", post(telegram_webhook))
+        .route("/v1/commands", post(handle_slash_command))
+        .route_layer(axum::middleware::from_fn_with_state(
+            rate_limiter,
+            rate_limit::enforce,
+        ));
 
     let app = Router::new()
         .route("/healthz", get(healthz))
         .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
         .route("/v1/runtime/profiles", get(runtime_profiles))
         .route("/v1/demarch/read", post(demarch_read))
         .route("/v1/demarch/write", post(demarch_write))
-        .route("/v1/telegram/ingress", post(telegram_ingress))
-        .route("/v1/telegram/send", post(telegram_send))
-        .route("/v1/telegram/edit", post(telegram_edit))
-        .route("/v1/telegram/callback", post(telegram_callback))
-        .route("/v1/commands", post(handle_slash_command))
+        .route("/v1/demarch/audit", get(demarch_audit))
+        .route("/v1/whatsapp/ingress", post(whatsapp_ingress))
+        .route("/v1/whatsapp/send", post(whatsapp_send))
+        .route("/v1/email/inbound", post(email_inbound))
+        .route("/v1/channel/webhook/ingress", post(channel_webhook_ingress))
+        .route("/v1/admin/replay", post(admin_replay))
+        .route("/v1/admin/correlation", post(admin_correlation))
+        .route("/v1/admin/shutdown", post(admin_shutdown))
+        .route(
+            "/v1/admin/groups",
+            get(admin_list_groups).post(admin_register_group),
+        )
+        .route(
+            "/v1/admin/groups/{jid}",
+            patch(admin_update_group).delete(admin_deactivate_group),
+        )
+        .route("/v1/queue/pause", post(queue_pause))
+        .route("/v1/queue/resume", post(queue_resume))
+        .route("/v1/queue/drain", post(queue_drain))
+        .route("/v1/tasks/{id}/pause", post(task_pause))
+        .route("/v1/tasks/{id}/resume", post(task_resume))
+        .route("/v1/tasks/{id}/run-now", post(task_run_now))
+        .route("/v1/tasks/{id}/history", get(task_history))
+        .route("/v1/tasks/bulk", post(tasks_bulk))
+        .route("/v1/task-templates", get(list_task_templates))
+        .route("/v1/scheduler/preview", get(scheduler_preview))
+        .route(
+            "/v1/task-templates/{name}",
+            put(put_task_template).delete(delete_task_template),
+        )
+        .route(
+            "/v1/ipc/errors",
+            get(list_ipc_errors).delete(purge_ipc_errors),
+        )
+        .route(
+            "/v1/ipc/errors/{name}",
+            get(show_ipc_error).delete(delete_ipc_error),
+        )
+        .route("/v1/ipc/errors/{name}/retry", post(retry_ipc_error))
+        .route("/v1/runs", get(list_runs))
+        .route("/v1/runs/{id}/events", get(run_events))
+        .route("/v1/events/stream", get(event_stream::stream_events))
+        .merge(limited_routes)
         .nest("/v1/db", db_routes)
-        .with_state(state);
+        .layer(axum::middleware::from_fn(request_id::propagate))
+        .with_state(state.clone());
 
     let listener = tokio::net::TcpListener::bind(&bind)
         .await
         .with_context(|| format!("failed to bind listener on {bind}"))?;
 
+    let grace = std::time::Duration::from_millis(state.config.server.shutdown_grace_ms);
+    tokio::spawn(shutdown::watch_signals(state.shutdown.clone(), grace));
+    let (http_stop_tx, mut http_stop_rx) = tokio::sync::watch::channel(false);
+    let mut server = tokio::spawn(
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+        )
+        .with_graceful_shutdown(async move {
+            let _ = http_stop_rx.wait_for(|stop| *stop).await;
+        })
+        .into_future(),
+    );
+
     info!(bind = %bind, "intercomd listening (IPC watcher active)");
-    let result = axum::serve(listener, app)
-        .await
-        .context("server exited unexpectedly");
+    let result = tokio::select! {
+        joined = &mut server => match joined {
+            Ok(served) => served.context("server exited unexpectedly"),
+            Err(e) => Err(anyhow!("server task failed: {e}")),
+        },
+        request = state.shutdown.requested() => {
+            info!(reason = %request.reason, grace_secs = request.grace.as_secs(), "shutting down");
+            // HTTP stays up while containers finish, for probes and the host
+            let remaining = state.queue.drain(request.grace).await;
+            state.queue.shutdown().await;
+            if let Some(ref db) = state.db {
+                message_loop::save_agent_timestamps_pub(db, &*state.agent_timestamps.read().await).await;
+            }
+            info!(detached_containers = remaining, "queue stopped, closing the HTTP server");
+            let _ = http_stop_tx.send(true);
+            // Event streams never end by themselves; don't wait on them long
+            if tokio::time::timeout(std::time::Duration::from_secs(5), &mut server).await.is_err() {
+                server.abort();
+            }
+            Ok(())
+        }
+    };
 
     // Signal background tasks to stop on server exit
     let _ = shutdown_tx.send(true);
     let _ = ipc_handle.await;
     let _ = registry_handle.await;
     let _ = events_handle.await;
+    if let Some(h) = storage_monitor_handle {
+        let _ = h.await;
+    }
+    if let Some(h) = telegram_ingest_handle {
+        let _ = h.await;
+    }
+    if let Some(h) = matrix_sync_handle {
+        let _ = h.await;
+    }
     if let Some(h) = message_loop_handle {
         let _ = h.await;
     }
+    if let Some(h) = unmute_sweep_handle {
+        let _ = h.await;
+    }
     if let Some(h) = scheduler_handle {
         let _ = h.await;
     }
+    if let Some(h) = calendar_handle {
+        let _ = h.await;
+    }
+    if let Some(h) = warm_pool_handle {
+        let _ = h.await;
+    }
+    if let Some(h) = image_update_handle {
+        let _ = h.await;
+    }
+    if let Some(h) = disk_scan_handle {
+        let _ = h.await;
+    }
 
     result
 }
@@ -496,6 +1063,52 @@ fn print_config(args: PrintConfigArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn ipc_errors_cli(args: IpcErrorsArgs) -> anyhow::Result<()> {
+    let ipc = &args.ipc_dir;
+    match args.command {
+        IpcErrorsCommand::List => {
+            println!("{}", serde_json::to_string_pretty(&ipc_errors::list(ipc)?)?);
+        }
+        IpcErrorsCommand::Show { name } => {
+            let (letter, content) = ipc_errors::show(ipc, &name)?
+                .with_context(|| format!("no IPC error file {name}"))?;
+            println!("{}", serde_json::to_string_pretty(&letter)?);
+            println!("{content}");
+        }
+        IpcErrorsCommand::Retry { name, from } => {
+            let fixed = match &from {
+                Some(path) => Some(
+                    std::fs::read_to_string(path)
+                        .with_context(|| format!("failed to read {}", path.display()))?,
+                ),
+                None => None,
+            };
+            match ipc_errors::retry(ipc, &name, fixed.as_deref())? {
+                ipc_errors::Retry::Requeued(dest) => println!("requeued {}", dest.display()),
+                ipc_errors::Retry::NotFound => anyhow::bail!("no IPC error file {name}"),
+                ipc_errors::Retry::Invalid(reason) => {
+                    anyhow::bail!("{name} was not requeued: {reason}")
+                }
+            }
+        }
+        IpcErrorsCommand::Purge {
+            names,
+            all,
+            older_than_hours,
+        } => {
+            if names.is_empty() && !all {
+                anyhow::bail!("name the files to purge, or pass --all");
+            }
+            let older_than =
+                older_than_hours.map(|hours| std::time::Duration::from_secs(hours * 3600));
+            for name in ipc_errors::purge(ipc, &names, older_than)? {
+                println!("purged {name}");
+            }
+        }
+    }
+    Ok(())
+}
+
 fn inspect_legacy(args: InspectLegacyArgs) -> anyhow::Result<()> {
     let snapshot = inspect_legacy_sqlite(&args.sqlite)
         .with_context(|| format!("failed to inspect sqlite file {}", args.sqlite.display()))?;
@@ -536,6 +1149,20 @@ async fn verify_migration(args: VerifyMigrationArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn provision_db(args: ProvisionDbArgs) -> anyhow::Result<()> {
+    let report = provision_database(
+        &args.dsn,
+        &ProvisionOptions {
+            role: args.role,
+            password: args.password,
+            schema: args.schema,
+        },
+    )
+    .await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
 fn resolve_postgres_dsn(explicit: Option<String>, config_path: &PathBuf) -> anyhow::Result<String> {
     if let Some(dsn) = explicit {
         if !dsn.trim().is_empty() {
@@ -566,19 +1193,106 @@ async fn healthz(State(state): State<AppState>) -> Json<HealthResponse> {
     })
 }
 
-async fn readyz(State(state): State<AppState>) -> Json<ReadyResponse> {
+/// Open the configured storage backend. Postgres stays optional (no DSN
+/// means the DB endpoints are disabled); SQLite always opens.
+async fn open_storage(config: &IntercomConfig) -> Option<SharedStorage> {
+    let storage: SharedStorage = match config.storage.backend {
+        StorageBackend::Postgres => {
+            let dsn = config.storage.postgres_dsn.as_deref().unwrap_or("").trim();
+            if dsn.is_empty() {
+                return None;
+            }
+            Arc::new(
+                PgPool::new(dsn.to_string())
+                    .with_clock_skew_warn_ms(config.storage.clock_skew_warn_ms)
+                    .with_reconnect_policy(config.storage.reconnect_policy()),
+            )
+        }
+        StorageBackend::Sqlite => Arc::new(
+            SqliteStore::new(&config.storage.sqlite_path)
+                .with_clock_skew_warn_ms(config.storage.clock_skew_warn_ms),
+        ),
+    };
+
+    match storage.connect().await {
+        Ok(()) => {
+            info!(backend = storage.backend(), "persistence layer connected");
+            Some(storage)
+        }
+        Err(e) => {
+            tracing::warn!(
+                backend = storage.backend(),
+                err = %e,
+                "storage connection failed, DB endpoints disabled"
+            );
+            None
+        }
+    }
+}
+
+async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<ReadyResponse>) {
     let groups_count = state.groups.read().await.len();
     let active = state.queue.active_count().await;
-    Json(ReadyResponse {
-        status: "ready",
-        runtime_profiles: state.config.runtimes.profiles.len(),
-        demarch_writes_restricted_to_main: state.config.demarch.require_main_group_for_writes,
-        telegram_bridge_enabled: state.telegram.is_enabled(),
-        postgres_connected: state.db.is_some(),
-        orchestrator_enabled: state.config.orchestrator.enabled,
-        registered_groups: groups_count,
-        active_containers: active,
-    })
+    let circuit = state.db.as_ref().and_then(|db| db.circuit());
+    let containers = state.config.orchestrator.enabled.then(|| {
+        (
+            state.run_config.executor.as_ref(),
+            state.run_config.data_dir.join("ipc"),
+        )
+    });
+    let dependencies = state
+        .readiness
+        .check(health::ReadinessTargets {
+            db: state.db.as_ref(),
+            telegram: &state.telegram,
+            demarch: &state.demarch,
+            containers,
+        })
+        .await;
+    let status = if state.shutdown.is_requested() {
+        "shutting_down"
+    } else {
+        match health::overall_readiness(&dependencies) {
+            health::Readiness::Ok => "ready",
+            health::Readiness::Degraded => "degraded",
+            health::Readiness::Down => "down",
+        }
+    };
+    let code = match status {
+        "down" | "shutting_down" => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+    (
+        code,
+        Json(ReadyResponse {
+            status,
+            runtime_profiles: state.config.runtimes.profiles.len(),
+            demarch_writes_restricted_to_main: state.config.demarch.require_main_group_for_writes,
+            telegram_bridge_enabled: state.telegram.is_enabled(),
+            postgres_connected: circuit.as_ref().is_some_and(|c| c.connected),
+            storage_backend: state.db.as_ref().map(|db| db.backend()),
+            storage_circuit: circuit,
+            orchestrator_enabled: state.config.orchestrator.enabled,
+            registered_groups: groups_count,
+            active_containers: active,
+            queue_paused: state.queue.is_paused().await,
+            warm_pool: state.warm_pool.as_ref().map(|pool| pool.stats()),
+            dependencies,
+        }),
+    )
+}
+
+/// `GET /metrics`: queue series in the Prometheus text format.
+async fn metrics(
+    State(state): State<AppState>,
+) -> ([(axum::http::header::HeaderName, &'static str); 1], String) {
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        state.queue.render_metrics().await,
+    )
 }
 
 async fn runtime_profiles(State(state): State<AppState>) -> Json<RuntimeProfilesResponse> {
@@ -603,27 +1317,88 @@ async fn demarch_read(
 ) -> Json<DemarchResponse> {
     let _ = request.source_group;
     let _ = request.is_main;
-    Json(state.demarch.execute_read(request.operation))
+    Json(state.demarch.execute_read(request.operation).await)
 }
 
 async fn demarch_write(
     State(state): State<AppState>,
     Json(request): Json<DemarchWriteRequest>,
 ) -> Json<DemarchResponse> {
-    let _ = request.source_group;
     Json(
         state
             .demarch
-            .execute_write(request.operation, request.is_main),
+            .execute_write(
+                request.operation,
+                request.source_group.as_deref(),
+                request.is_main,
+            )
+            .await,
     )
 }
 
+#[derive(Deserialize)]
+struct DemarchAuditQuery {
+    group: Option<String>,
+    op: Option<String>,
+    status: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct DemarchAuditResponse {
+    entries: Vec<intercom_core::DemarchAuditEntry>,
+}
+
+/// `GET /v1/demarch/audit?group=&op=&status=&since=&until=&limit=`: recorded
+/// Demarch writes, newest first.
+async fn demarch_audit(
+    State(state): State<AppState>,
+    Query(query): Query<DemarchAuditQuery>,
+) -> Result<Json<DemarchAuditResponse>, StatusCode> {
+    let Some(ref pool) = state.db else {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    let filter = intercom_core::DemarchAuditFilter {
+        source_group: query.group,
+        operation: query.op,
+        status: query.status,
+        since: query.since,
+        until: query.until,
+        limit: query.limit.unwrap_or(50).clamp(1, 500),
+    };
+    match pool.list_demarch_audit(&filter).await {
+        Ok(entries) => Ok(Json(DemarchAuditResponse { entries })),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to list Demarch audit entries");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 async fn telegram_ingress(
     State(state): State<AppState>,
     Json(request): Json<TelegramIngressRequest>,
 ) -> Json<TelegramIngressResponse> {
-    match state.telegram.route_ingress(&state.config, request) {
-        Ok(response) => Json(response),
+    let live = state.config.telegram.ingress_groups == IngressGroupSource::Live;
+    let live_group = if live {
+        live_ingress_group(&state, &request.chat_jid).await
+    } else {
+        None
+    };
+    let stored = (live && request.persist).then(|| request.clone());
+    match state
+        .telegram
+        .route_ingress(&state.config, request, live_group.as_ref())
+    {
+        Ok(response) => {
+            if let Some(mut stored) = stored {
+                stored.content = response.normalized_content.clone();
+                store_inbound_message(&state, stored, telegram::TELEGRAM_CHANNEL).await;
+            }
+            Json(response)
+        }
         Err(err) => Json(TelegramIngressResponse {
             accepted: false,
             reason: Some(format!("routing_error: {err}")),
@@ -643,6 +1418,132 @@ async fn telegram_ingress(
     }
 }
 
+/// `POST /v1/whatsapp/ingress`: routing decision for a message the host's
+/// WhatsApp socket received. With `persist`, accepted messages are stored.
+async fn whatsapp_ingress(
+    State(state): State<AppState>,
+    Json(request): Json<whatsapp::WhatsAppIngressRequest>,
+) -> Json<whatsapp::WhatsAppIngressResponse> {
+    let group = live_ingress_group(&state, &request.chat_jid).await;
+    let response = whatsapp::route_ingress(
+        &state.config,
+        &whatsapp::WhatsAppIdentity::from_env(),
+        &request,
+        group.as_ref(),
+    );
+    if request.persist && response.accepted {
+        let chat_type = if request.is_group() {
+            "group"
+        } else {
+            "private"
+        };
+        let stored = TelegramIngressRequest {
+            chat_jid: request.chat_jid,
+            chat_name: response.group_name.clone(),
+            chat_type: Some(chat_type.to_string()),
+            message_id: request.message_id,
+            sender_id: Some(request.sender),
+            sender_name: request.sender_name,
+            content: response.normalized_content.clone(),
+            timestamp: request.timestamp,
+            persist: true,
+            kind: telegram::TelegramUpdateKind::Message,
+            attachments: Vec::new(),
+        };
+        store_inbound_message(&state, stored, whatsapp::WHATSAPP_CHANNEL).await;
+    }
+    Json(response)
+}
+
+/// `POST /v1/whatsapp/send`: the prefixed, formatted chunks the host should
+/// send for a reply.
+async fn whatsapp_send(
+    State(state): State<AppState>,
+    Json(request): Json<whatsapp::WhatsAppSendRequest>,
+) -> Json<whatsapp::WhatsAppSendResponse> {
+    let profile = state.config.channels.profile(whatsapp::WHATSAPP_CHANNEL);
+    Json(whatsapp::plan_send(
+        &profile,
+        &whatsapp::WhatsAppIdentity::from_env(),
+        &request,
+    ))
+}
+
+/// `POST /v1/email/inbound`: one email relayed by the mail provider's
+/// inbound route, signed with `email.inbound_secret`. Mail from registered
+/// addresses is stored; a non-2xx asks the provider to retry.
+async fn email_inbound(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    if !state.config.email.enabled {
+        return StatusCode::NOT_FOUND;
+    }
+    let Some(secret) = state.config.email.inbound_secret.as_deref() else {
+        return StatusCode::SERVICE_UNAVAILABLE;
+    };
+    let provided = headers
+        .get(webhooks::SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !email::signature_matches(secret, &body, provided) {
+        warn!("rejected inbound email with a bad signature");
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let request = match serde_json::from_slice::<email::EmailInboundRequest>(&body) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!(err = %e, "rejected malformed inbound email");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    match state.email.ingest(&request) {
+        Ok(ingress) => {
+            store_inbound_message(&state, ingress, email::EMAIL_CHANNEL).await;
+            StatusCode::OK
+        }
+        Err(e) => {
+            warn!(err = %format!("{e:#}"), "rejected inbound email");
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
+
+/// `POST /v1/channel/webhook/ingress`: a user message from a custom
+/// frontend, signed with its `hook:` group's `channelWebhook` secret.
+async fn channel_webhook_ingress(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<webhook_channel::WebhookIngressResponse>, StatusCode> {
+    let request =
+        serde_json::from_slice::<webhook_channel::WebhookIngressRequest>(&body).map_err(|e| {
+            warn!(err = %e, "rejected malformed webhook channel message");
+            StatusCode::BAD_REQUEST
+        })?;
+    if !request.jid.starts_with(webhook_channel::WEBHOOK_JID_PREFIX) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let group = live_ingress_group(&state, &request.jid)
+        .await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let provided = headers
+        .get(webhooks::SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if let Err(e) = webhook_channel::verify_ingress(&group, &body, provided) {
+        warn!(err = %e, "rejected webhook channel message");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let ingress = state.webhook_channel.ingest(request);
+    let message_id = ingress.message_id.clone();
+    store_inbound_message(&state, ingress, webhook_channel::WEBHOOK_CHANNEL).await;
+    Ok(Json(webhook_channel::WebhookIngressResponse { message_id }))
+}
+
 async fn telegram_send(
     State(state): State<AppState>,
     Json(request): Json<TelegramSendRequest>,
@@ -667,7 +1568,7 @@ async fn telegram_callback(
     State(state): State<AppState>,
     Json(request): Json<TelegramCallbackRequest>,
 ) -> Json<TelegramCallbackResponse> {
-    match state.telegram.handle_callback(request, &state.demarch).await {
+    match route_callback(&state, request).await {
         Ok(response) => Json(response),
         Err(err) => Json(TelegramCallbackResponse {
             ok: false,
@@ -679,39 +1580,1470 @@ async fn telegram_callback(
     }
 }
 
-async fn handle_slash_command(
+/// `POST /v1/admin/replay`: re-run a stored message and deliver the reply
+/// to an operator chat. See `replay.rs`.
+async fn admin_replay(
     State(state): State<AppState>,
-    Json(request): Json<commands::CommandRequest>,
-) -> Json<commands::CommandResult> {
-    let assistant_name = std::env::var("ASSISTANT_NAME")
-        .unwrap_or_else(|_| "Amtiskaw".into());
-    let ctx = commands::CommandContext {
-        assistant_name,
-        started_at: state.started_at,
+    Json(request): Json<replay::ReplayRequest>,
+) -> Json<replay::ReplayResponse> {
+    let Some(ref pool) = state.db else {
+        return Json(replay::ReplayResponse::from_error(
+            "storage is not configured",
+        ));
     };
-    let result = commands::handle_command(
-        &request.command,
-        &request.args,
-        request.group_name.as_deref(),
-        request.group_folder.as_deref(),
-        request.current_model.as_deref(),
-        request.session_id.as_deref(),
-        request.container_active,
-        &ctx,
-    );
-
+    let assistant_name = std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into());
+    match replay::start_replay(
+        request,
+        pool,
+        &state.queue,
+        &state.groups,
+        &state.channels,
+        &state.run_config,
+        &assistant_name,
+        &state.config.orchestrator.main_group_folder,
+    )
+    .await
+    {
+        Ok(response) => Json(response),
+        Err(err) => Json(replay::ReplayResponse::from_error(format!("{err:#}"))),
+    }
+}
+
+/// `POST /v1/admin/correlation`: messages, task runs and container logs
+/// recorded under one correlation id.
+async fn admin_correlation(
+    State(state): State<AppState>,
+    Json(request): Json<correlation::CorrelationRequest>,
+) -> Json<correlation::CorrelationResponse> {
+    let Some(ref pool) = state.db else {
+        return Json(correlation::CorrelationResponse::from_error(
+            "storage is not configured",
+        ));
+    };
+    let groups = state.groups.read().await.clone();
+    match correlation::lookup(
+        &request.correlation_id,
+        pool,
+        &groups,
+        &state.run_config.groups_dir,
+    )
+    .await
+    {
+        Ok(response) => Json(response),
+        Err(err) => Json(correlation::CorrelationResponse::from_error(format!(
+            "{err:#}"
+        ))),
+    }
+}
+
+/// Reply to a group admin request: the group, or why it was refused.
+fn group_admin_response<T: Serialize>(
+    result: Result<T, group_admin::AdminError>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    use group_admin::AdminError;
+    let error = match result {
+        Ok(body) => return (StatusCode::OK, Json(serde_json::json!(body))),
+        Err(e) => e,
+    };
+    let status = match error {
+        AdminError::Invalid(_) => StatusCode::BAD_REQUEST,
+        AdminError::NotFound(_) => StatusCode::NOT_FOUND,
+        AdminError::Conflict(_) => StatusCode::CONFLICT,
+        AdminError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(serde_json::json!({ "error": error.to_string() })),
+    )
+}
+
+fn runtime_profile_names(config: &IntercomConfig) -> Vec<&str> {
+    config
+        .runtimes
+        .profiles
+        .keys()
+        .map(String::as_str)
+        .collect()
+}
+
+/// `GET /v1/admin/groups`: every registered group with its queue status.
+async fn admin_list_groups(State(state): State<AppState>) -> Json<Vec<group_admin::GroupListing>> {
+    let mut groups: Vec<RegisteredGroup> = state.groups.read().await.values().cloned().collect();
+    groups.sort_by(|a, b| a.folder.cmp(&b.folder));
+    let main_folder = &state.config.orchestrator.main_group_folder;
+    let mut listings = Vec::with_capacity(groups.len());
+    for group in groups {
+        let status = state.queue.group_status(&group.jid).await;
+        listings.push(group_admin::listing(group, &status, main_folder));
+    }
+    Json(listings)
+}
+
+/// `POST /v1/admin/groups`: register a group and create its folder.
+async fn admin_register_group(
+    State(state): State<AppState>,
+    Json(request): Json<group_admin::RegisterGroupRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    let profiles = runtime_profile_names(&state.config);
+    let result = group_admin::register(
+        db,
+        &state.groups,
+        &state.run_config.groups_dir,
+        &profiles,
+        request,
+    )
+    .await;
+    group_admin_response(result)
+}
+
+/// `PATCH /v1/admin/groups/{jid}`: change a group's name, trigger, runtime
+/// or model.
+async fn admin_update_group(
+    State(state): State<AppState>,
+    Path(jid): Path<String>,
+    Json(request): Json<group_admin::UpdateGroupRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    let profiles = runtime_profile_names(&state.config);
+    group_admin_response(group_admin::update(db, &state.groups, &profiles, &jid, request).await)
+}
+
+/// `DELETE /v1/admin/groups/{jid}`: unregister a group, keeping its data.
+async fn admin_deactivate_group(
+    State(state): State<AppState>,
+    Path(jid): Path<String>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    let main_folder = &state.config.orchestrator.main_group_folder;
+    group_admin_response(group_admin::deactivate(db, &state.groups, main_folder, &jid).await)
+}
+
+#[derive(Serialize)]
+struct QueueStateResponse {
+    paused: bool,
+    active: usize,
+    waiting_groups: usize,
+    pending_tasks: usize,
+}
+
+impl QueueStateResponse {
+    async fn of(queue: &queue::GroupQueue) -> Self {
+        let snapshot = queue.snapshot().await;
+        Self {
+            paused: snapshot.paused,
+            active: snapshot.active,
+            waiting_groups: snapshot.waiting_groups,
+            pending_tasks: snapshot.pending_tasks,
+        }
+    }
+}
+
+/// `POST /v1/queue/pause`: stop starting containers; running ones finish.
+async fn queue_pause(State(state): State<AppState>) -> Json<QueueStateResponse> {
+    state.queue.pause().await;
+    Json(QueueStateResponse::of(&state.queue).await)
+}
+
+/// `POST /v1/queue/resume`: start containers again.
+async fn queue_resume(State(state): State<AppState>) -> Json<QueueStateResponse> {
+    state.queue.resume().await;
+    Json(QueueStateResponse::of(&state.queue).await)
+}
+
+#[derive(Deserialize, Default)]
+struct DrainRequest {
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct DrainResponse {
+    drained: bool,
+    #[serde(flatten)]
+    queue: QueueStateResponse,
+}
+
+/// `POST /v1/queue/drain`: pause, then wait for the running containers to
+/// finish (`timeout_secs`, default 600). `drained` is false when some are
+/// still running at the timeout.
+async fn queue_drain(
+    State(state): State<AppState>,
+    request: Option<Json<DrainRequest>>,
+) -> Json<DrainResponse> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let timeout = std::time::Duration::from_secs(request.timeout_secs.unwrap_or(600));
+    let remaining = state.queue.drain(timeout).await;
+    Json(DrainResponse {
+        drained: remaining == 0,
+        queue: QueueStateResponse::of(&state.queue).await,
+    })
+}
+
+#[derive(Deserialize, Default)]
+struct ShutdownBody {
+    timeout_secs: Option<u64>,
+}
+
+/// `POST /v1/admin/shutdown`: stop as on SIGTERM, waiting up to
+/// `timeout_secs` (default `server.shutdown_grace_ms`) for running
+/// containers. Needs `Authorization: Bearer <server.admin_token>`.
+async fn admin_shutdown(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    request: Option<Json<ShutdownBody>>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(expected) = state
+        .config
+        .server
+        .admin_token
+        .as_deref()
+        .filter(|t| !t.is_empty())
+    else {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": "server.admin_token is not set" })),
+        );
+    };
+    let provided = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !telegram::secret_matches(expected, provided.trim()) {
+        warn!("rejected shutdown call with a bad admin token");
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "error": "invalid admin token" })),
+        );
+    }
+
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let grace = match request.timeout_secs {
+        Some(secs) => std::time::Duration::from_secs(secs),
+        None => std::time::Duration::from_millis(state.config.server.shutdown_grace_ms),
+    };
+    if !state.shutdown.request("admin API", grace) {
+        return (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({ "error": "already shutting down" })),
+        );
+    }
+    info!(
+        grace_secs = grace.as_secs(),
+        "shutdown requested via admin API"
+    );
+    (
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "shutting_down": true,
+            "grace_secs": grace.as_secs(),
+            "queue": QueueStateResponse::of(&state.queue).await,
+        })),
+    )
+}
+
+/// Reply to a task control request: the task, or why it was refused.
+fn task_control_response(
+    id: &str,
+    result: anyhow::Result<scheduler::TaskControl>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    match result {
+        Ok(scheduler::TaskControl::Done(task)) => (StatusCode::OK, Json(serde_json::json!(task))),
+        Ok(scheduler::TaskControl::NotFound) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": format!("no task {id}") })),
+        ),
+        Ok(scheduler::TaskControl::Completed) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({ "error": format!("task {id} has completed") })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{e:#}") })),
+        ),
+    }
+}
+
+fn storage_unavailable() -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({ "error": "storage is not configured" })),
+    )
+}
+
+/// `POST /v1/tasks/{id}/pause`: stop the task coming due.
+async fn task_pause(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    task_control_response(&id, scheduler::pause_task(db, &id).await)
+}
+
+/// `POST /v1/tasks/{id}/resume`: make a paused task active again.
+async fn task_resume(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    let result = scheduler::resume_task(db, &id, &state.config.scheduler.timezone).await;
+    task_control_response(&id, result)
+}
+
+/// `POST /v1/tasks/{id}/run-now`: queue the task for its group as if it
+/// had come due.
+async fn task_run_now(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    let Some(run_task) = state.run_task.as_ref() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "error": "orchestrator not running" })),
+        );
+    };
+    task_control_response(&id, scheduler::run_task_now(db, run_task, &id).await)
+}
+
+#[derive(Deserialize)]
+struct TaskHistoryQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct TaskHistoryResponse {
+    task_id: String,
+    stats: intercom_core::TaskRunStats,
+    runs: Vec<intercom_core::TaskRunLog>,
+    limit: i64,
+    offset: i64,
+}
+
+/// `GET /v1/tasks/{id}/history?limit=&offset=`: a page of the task's runs,
+/// newest first, with aggregates over all of them.
+async fn task_history(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<TaskHistoryQuery>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    let limit = query.limit.unwrap_or(20).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let history = async {
+        if db.get_task_by_id(&id).await?.is_none() {
+            return Ok(None);
+        }
+        let stats = db.get_task_run_stats(&id).await?;
+        let runs = db.get_task_runs(&id, limit, offset).await?;
+        anyhow::Ok(Some(TaskHistoryResponse {
+            task_id: id.clone(),
+            stats,
+            runs,
+            limit,
+            offset,
+        }))
+    };
+    match history.await {
+        Ok(Some(history)) => (StatusCode::OK, Json(serde_json::json!(history))),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": format!("no task {id}") })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{e:#}") })),
+        ),
+    }
+}
+
+/// `POST /v1/tasks/bulk`: create the same task for several groups, from a
+/// template and/or explicit fields.
+async fn tasks_bulk(
+    State(state): State<AppState>,
+    Json(request): Json<scheduler::BulkTaskRequest>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    let template = request.template.clone().unwrap_or_default();
+    let groups = state.groups.read().await.clone();
+    match scheduler::create_bulk_tasks(db, &groups, request, &state.config.scheduler.timezone).await
+    {
+        Ok(scheduler::BulkTasks::Done { created, failed }) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "created": created, "failed": failed })),
+        ),
+        Ok(scheduler::BulkTasks::UnknownTemplate) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": format!("no task template {template}") })),
+        ),
+        Ok(scheduler::BulkTasks::Invalid(e)) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{e:#}") })),
+        ),
+    }
+}
+
+/// `GET /v1/task-templates`: every template, by name.
+async fn list_task_templates(
+    State(state): State<AppState>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    match db.list_task_templates().await {
+        Ok(templates) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "templates": templates })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{e:#}") })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct TaskTemplateBody {
+    prompt: String,
+    schedule_type: String,
+    schedule_value: String,
+    context_mode: Option<String>,
+    task_config: Option<serde_json::Value>,
+}
+
+/// `PUT /v1/task-templates/{name}`: create or replace a template.
+async fn put_task_template(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(body): Json<TaskTemplateBody>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    if body.prompt.trim().is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "prompt is empty" })),
+        );
+    }
+    if let Err(e) = scheduler::validate_schedule(
+        &body.schedule_type,
+        &body.schedule_value,
+        &state.config.scheduler.timezone,
+    ) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e })),
+        );
+    }
+    let template = intercom_core::TaskTemplate {
+        name: name.clone(),
+        prompt: body.prompt,
+        schedule_type: body.schedule_type,
+        schedule_value: body.schedule_value,
+        context_mode: body.context_mode.unwrap_or_else(|| "isolated".into()),
+        task_config: body.task_config,
+        created_at: String::new(),
+        updated_at: String::new(),
+    };
+    let stored = async {
+        db.upsert_task_template(&template).await?;
+        db.get_task_template(&name).await
+    };
+    match stored.await {
+        Ok(template) => (StatusCode::OK, Json(serde_json::json!(template))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{e:#}") })),
+        ),
+    }
+}
+
+/// `DELETE /v1/task-templates/{name}`: tasks created from it are kept.
+async fn delete_task_template(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(db) = state.db.as_ref() else {
+        return storage_unavailable();
+    };
+    match db.delete_task_template(&name).await {
+        Ok(true) => (StatusCode::OK, Json(serde_json::json!({ "deleted": name }))),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": format!("no task template {name}") })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": format!("{e:#}") })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct PreviewQuery {
+    schedule_type: String,
+    schedule_value: String,
+    timezone: Option<String>,
+    count: Option<usize>,
+}
+
+/// `GET /v1/scheduler/preview?schedule_type=&schedule_value=&timezone=&count=`:
+/// the next fire times of a schedule, in UTC and on its wall clock.
+async fn scheduler_preview(
+    State(state): State<AppState>,
+    Query(query): Query<PreviewQuery>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let timezone = query
+        .timezone
+        .unwrap_or_else(|| state.config.scheduler.timezone.clone());
+    let count = query.count.unwrap_or(5).clamp(1, 50);
+    match scheduler::preview(
+        &query.schedule_type,
+        &query.schedule_value,
+        &timezone,
+        chrono::Utc::now(),
+        count,
+    ) {
+        Ok(times) => {
+            let tz: chrono_tz::Tz = timezone.parse().unwrap_or(chrono_tz::UTC);
+            let next: Vec<_> = times
+                .iter()
+                .map(|at| serde_json::json!({ "at": at.to_rfc3339(), "local": at.with_timezone(&tz).to_rfc3339() }))
+                .collect();
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "timezone": timezone, "next": next })),
+            )
+        }
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e })),
+        ),
+    }
+}
+
+fn ipc_error_failure(e: anyhow::Error) -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({ "error": format!("{e:#}") })),
+    )
+}
+
+fn no_ipc_error(name: &str) -> (StatusCode, Json<serde_json::Value>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(serde_json::json!({ "error": format!("no IPC error file {name}") })),
+    )
+}
+
+/// `GET /v1/ipc/errors`: dead-lettered IPC files.
+async fn list_ipc_errors(State(state): State<AppState>) -> (StatusCode, Json<serde_json::Value>) {
+    match ipc_errors::list(&state.run_config.data_dir.join("ipc")) {
+        Ok(errors) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "errors": errors })),
+        ),
+        Err(e) => ipc_error_failure(e),
+    }
+}
+
+/// `GET /v1/ipc/errors/{name}`: a dead-lettered file and its contents.
+async fn show_ipc_error(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    match ipc_errors::show(&state.run_config.data_dir.join("ipc"), &name) {
+        Ok(Some((letter, content))) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "file": letter, "content": content })),
+        ),
+        Ok(None) => no_ipc_error(&name),
+        Err(e) => ipc_error_failure(e),
+    }
+}
+
+/// `POST /v1/ipc/errors/{name}/retry`: requeue the file into its group's
+/// directory. A JSON body replaces the file's contents first.
+async fn retry_ipc_error(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    fixed: Option<Json<serde_json::Value>>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let fixed = fixed.map(|Json(body)| body.to_string());
+    match ipc_errors::retry(
+        &state.run_config.data_dir.join("ipc"),
+        &name,
+        fixed.as_deref(),
+    ) {
+        Ok(ipc_errors::Retry::Requeued(dest)) => {
+            info!(name = %name, dest = %dest.display(), "IPC error file requeued");
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "requeued": dest })),
+            )
+        }
+        Ok(ipc_errors::Retry::NotFound) => no_ipc_error(&name),
+        Ok(ipc_errors::Retry::Invalid(reason)) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": reason })),
+        ),
+        Err(e) => ipc_error_failure(e),
+    }
+}
+
+#[derive(Deserialize)]
+struct PurgeQuery {
+    older_than_hours: Option<u64>,
+}
+
+/// `DELETE /v1/ipc/errors?older_than_hours=`: delete dead-lettered files.
+async fn purge_ipc_errors(
+    State(state): State<AppState>,
+    Query(query): Query<PurgeQuery>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let older_than = query
+        .older_than_hours
+        .map(|hours| std::time::Duration::from_secs(hours * 3600));
+    match ipc_errors::purge(&state.run_config.data_dir.join("ipc"), &[], older_than) {
+        Ok(purged) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "purged": purged })),
+        ),
+        Err(e) => ipc_error_failure(e),
+    }
+}
+
+/// `DELETE /v1/ipc/errors/{name}`: delete one dead-lettered file.
+async fn delete_ipc_error(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    match ipc_errors::purge(
+        &state.run_config.data_dir.join("ipc"),
+        std::slice::from_ref(&name),
+        None,
+    ) {
+        Ok(purged) if purged.is_empty() => no_ipc_error(&name),
+        Ok(purged) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "purged": purged })),
+        ),
+        Err(e) => ipc_error_failure(e),
+    }
+}
+
+#[derive(Deserialize)]
+struct RunsQuery {
+    group: Option<String>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct RunsResponse {
+    runs: Vec<intercom_core::ContainerRun>,
+}
+
+/// `GET /v1/runs?group=&limit=`: recorded container runs, newest first.
+async fn list_runs(
+    State(state): State<AppState>,
+    Query(query): Query<RunsQuery>,
+) -> Result<Json<RunsResponse>, StatusCode> {
+    let Some(ref pool) = state.db else {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    match pool
+        .list_container_runs(query.group.as_deref(), limit)
+        .await
+    {
+        Ok(runs) => Ok(Json(RunsResponse { runs })),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to list container runs");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RunEventsResponse {
+    run_id: String,
+    events: Vec<container::run_events::RunEvent>,
+}
+
+/// `GET /v1/runs/{id}/events`: the JSONL transcript of one container run.
+async fn run_events(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+) -> Result<Json<RunEventsResponse>, StatusCode> {
+    if !container::run_events::valid_run_id(&run_id) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let runs_dir = state.run_config.data_dir.join("runs");
+    match container::run_events::read_events(&runs_dir, &run_id).await {
+        Ok(Some(events)) => Ok(Json(RunEventsResponse { run_id, events })),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::warn!(run_id = %run_id, error = %e, "Failed to read run transcript");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Telegram ingestion (long-polling and webhook)
+// ---------------------------------------------------------------------------
+
+/// `POST /v1/telegram/webhook`: raw Bot API updates pushed by Telegram when
+/// `telegram.ingest = "webhook"`. Telegram retries anything but a 2xx, so
+/// updates we can't use are acknowledged and dropped.
+async fn telegram_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(raw): Json<serde_json::Value>,
+) -> StatusCode {
+    if state.config.telegram.ingest != TelegramIngest::Webhook {
+        return StatusCode::NOT_FOUND;
+    }
+    let Some(expected) = state.telegram.webhook_secret(&state.config.telegram) else {
+        return StatusCode::SERVICE_UNAVAILABLE;
+    };
+    let provided = headers
+        .get(telegram::WEBHOOK_SECRET_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !telegram::secret_matches(&expected, provided) {
+        warn!("rejected Telegram webhook call with a bad secret token");
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let update = match serde_json::from_value::<telegram::TelegramUpdate>(raw) {
+        Ok(update) => update,
+        Err(e) => {
+            warn!(err = %e, "skipping malformed Telegram webhook update");
+            return StatusCode::OK;
+        }
+    };
+    let assistant_name = std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into());
+    let bot_username = state.telegram.cached_bot_username();
+    if let Some(update) = telegram::convert_update(update, bot_username.as_deref(), &assistant_name)
+    {
+        handle_inbound_update(&state, update).await;
+    }
+    StatusCode::OK
+}
+
+/// Handle one update from `telegram::run_poll_loop` or the webhook. Messages go straight to
+/// storage, where the message loop picks them up like host-forwarded ones.
+async fn handle_inbound_update(state: &AppState, update: telegram::InboundUpdate) {
+    match update {
+        telegram::InboundUpdate::Ingress(request) => {
+            store_inbound_message(state, request, telegram::TELEGRAM_CHANNEL).await
+        }
+        telegram::InboundUpdate::Command(command) => {
+            let chat_jid = command.chat_jid.clone();
+            let reply = inbound_command_reply(state, command).await;
+            send_command_reply(state, &chat_jid, reply).await;
+        }
+        telegram::InboundUpdate::Callback(request) => match route_callback(state, request).await {
+            Ok(response) if !response.ok => {
+                warn!(action = %response.action, error = ?response.error, "Telegram callback rejected");
+            }
+            Ok(_) => {}
+            Err(e) => warn!(err = %e, "Telegram callback failed"),
+        },
+    }
+}
+
+async fn send_command_reply(state: &AppState, chat_jid: &str, reply: commands::CommandResult) {
+    let request = TelegramSendRequest {
+        jid: chat_jid.to_string(),
+        text: reply.text,
+        format: None,
+        reply_markup: reply.reply_markup,
+        reply_to_message_id: None,
+        message_thread_id: None,
+        correlation_id: None,
+    };
+    if let Err(e) = state.telegram.send_message(request).await {
+        warn!(chat_jid, err = %e, "failed to send command reply");
+    }
+}
+
+/// Route an inline keyboard press. `cmd:` buttons run a slash command and
+/// `msg:` buttons post their text into the chat as the user who tapped, so
+/// the agent sees the choice like a typed reply. Anything else is a kernel
+/// action for [`TelegramBridge::handle_callback`]. The keyboard is removed
+/// once a choice is made so it can't be pressed twice.
+async fn route_callback(
+    state: &AppState,
+    request: TelegramCallbackRequest,
+) -> anyhow::Result<TelegramCallbackResponse> {
+    let (action, target_id, result) = match telegram::CallbackAction::parse(&request.data) {
+        telegram::CallbackAction::Kernel => {
+            return state
+                .telegram
+                .handle_callback(request, &state.demarch)
+                .await;
+        }
+        telegram::CallbackAction::Command { command, args } => {
+            state
+                .telegram
+                .answer_callback_query(&request.chat_jid, &request.callback_query_id, None)
+                .await?;
+            let reply = group_command_reply(
+                state,
+                &request.chat_jid,
+                command.clone(),
+                args,
+                request.sender_id.clone(),
+            )
+            .await;
+            let text = reply.text.clone();
+            send_command_reply(state, &request.chat_jid, reply).await;
+            ("cmd", command, text)
+        }
+        telegram::CallbackAction::Message(text) => {
+            if let Err(e) = store_button_message(state, &request, &text).await {
+                state
+                    .telegram
+                    .answer_callback_query(
+                        &request.chat_jid,
+                        &request.callback_query_id,
+                        Some(&e.to_string()),
+                    )
+                    .await?;
+                return Ok(TelegramCallbackResponse {
+                    ok: false,
+                    action: "msg".into(),
+                    target_id: request.message_id,
+                    result: None,
+                    error: Some(e.to_string()),
+                });
+            }
+            state
+                .telegram
+                .answer_callback_query(&request.chat_jid, &request.callback_query_id, Some(&text))
+                .await?;
+            ("msg", request.message_id.clone(), text)
+        }
+    };
+    if let Err(e) = state
+        .telegram
+        .clear_keyboard(&request.chat_jid, &request.message_id)
+        .await
+    {
+        warn!(chat_jid = %request.chat_jid, err = %e, "failed to clear inline keyboard");
+    }
+    Ok(TelegramCallbackResponse {
+        ok: true,
+        action: action.into(),
+        target_id,
+        result: Some(result),
+        error: None,
+    })
+}
+
+/// Store a `msg:` button press as an inbound message from the user. Groups
+/// that need a trigger get one prepended: the press answers the assistant.
+async fn store_button_message(
+    state: &AppState,
+    request: &TelegramCallbackRequest,
+    text: &str,
+) -> anyhow::Result<()> {
+    let db = state
+        .db
+        .as_ref()
+        .ok_or_else(|| anyhow!("storage is not configured"))?;
+    let group_jid = group_jid_for(state, &request.chat_jid)
+        .await
+        .ok_or_else(|| anyhow!("this chat is not registered"))?;
+    let group = state
+        .groups
+        .read()
+        .await
+        .get(&group_jid)
+        .cloned()
+        .ok_or_else(|| anyhow!("this chat is not registered"))?;
+    let content = with_trigger(state, &group, text);
+
+    let correlation_id = new_correlation_id();
+    db.store_message(&NewMessage {
+        id: format!("cb-{}", request.callback_query_id),
+        chat_jid: group_jid.clone(),
+        sender: request.sender_id.clone().unwrap_or_default(),
+        sender_name: request.sender_name.clone().unwrap_or_default(),
+        content,
+        timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        is_from_me: false,
+        is_bot_message: false,
+        edited: false,
+        attachments: Vec::new(),
+        correlation_id: Some(correlation_id.clone()),
+    })
+    .await?;
+    info!(
+        chat_jid = %group_jid,
+        correlation_id = correlation_id.as_str(),
+        "Telegram button press stored"
+    );
+    Ok(())
+}
+
+/// `text` as a message that triggers `group`'s agent: prefixed with the
+/// assistant trigger when the group needs one and `text` lacks it.
+fn with_trigger(state: &AppState, group: &RegisteredGroup, text: &str) -> String {
+    let assistant_name = std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into());
+    let is_main = group.folder == state.config.orchestrator.main_group_folder;
+    let custom_trigger = (!group.trigger.is_empty()).then_some(group.trigger.as_str());
+    let trigger = message_loop::build_trigger_regex_pub(&assistant_name, custom_trigger);
+    if !is_main && group.requires_trigger.unwrap_or(true) && !trigger.is_match(text) {
+        format!("@{assistant_name} {text}")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Store a prompt command's text as a message from `sender`, so the message
+/// loop runs it like one they typed.
+async fn store_command_prompt(
+    state: &AppState,
+    chat_jid: &str,
+    sender: Option<&str>,
+    prompt: &str,
+) -> anyhow::Result<()> {
+    let db = state
+        .db
+        .as_ref()
+        .ok_or_else(|| anyhow!("storage is not configured"))?;
+    let group = state
+        .groups
+        .read()
+        .await
+        .get(chat_jid)
+        .cloned()
+        .ok_or_else(|| anyhow!("this chat is not registered"))?;
+    let now = chrono::Utc::now();
+    let correlation_id = new_correlation_id();
+    db.store_message(&NewMessage {
+        id: format!("cmd-{}", now.timestamp_micros()),
+        chat_jid: chat_jid.to_string(),
+        sender: sender.unwrap_or_default().to_string(),
+        sender_name: sender.unwrap_or("Unknown").to_string(),
+        content: with_trigger(state, &group, prompt),
+        timestamp: now.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        is_from_me: false,
+        is_bot_message: false,
+        edited: false,
+        attachments: Vec::new(),
+        correlation_id: Some(correlation_id.clone()),
+    })
+    .await?;
+    info!(
+        chat_jid,
+        correlation_id = correlation_id.as_str(),
+        "command prompt stored"
+    );
+    Ok(())
+}
+
+/// Registered group JID for a Telegram chat or forum topic, see
+/// [`live_ingress_group`].
+async fn group_jid_for(state: &AppState, chat_jid: &str) -> Option<String> {
+    live_ingress_group(state, chat_jid)
+        .await
+        .map(|group| group.jid)
+}
+
+/// Registered group for ingress routing: the daemon's own registry first,
+/// then the live store, which also sees groups registered through
+/// `/v1/db/groups/set` since startup. Topics fall back to their chat's group.
+async fn live_ingress_group(state: &AppState, chat_jid: &str) -> Option<RegisteredGroup> {
+    {
+        let groups = state.groups.read().await;
+        if let Some(jid) = telegram::registered_jid(chat_jid, |jid| groups.contains_key(jid)) {
+            return groups.get(jid).cloned();
+        }
+    }
+    let db = state.db.as_ref()?;
+    let base = telegram::base_jid(chat_jid);
+    let candidates = if base == chat_jid {
+        vec![chat_jid]
+    } else {
+        vec![chat_jid, base]
+    };
+    for jid in candidates {
+        match db.get_registered_group(jid).await {
+            Ok(Some(group)) => return Some(group),
+            Ok(None) => {}
+            Err(e) => {
+                warn!(chat_jid = jid, err = %e, "registered group lookup failed");
+                health::record_error(health::SUBSYSTEM_DB, &e);
+                return None;
+            }
+        }
+    }
+    None
+}
+
+/// Store an inbound message received by one of intercomd's own ingestion
+/// loops (Telegram polling or webhook, Matrix sync, inbound email, webhook
+/// channel) for a registered chat.
+async fn store_inbound_message(
+    state: &AppState,
+    mut request: TelegramIngressRequest,
+    channel: &'static str,
+) {
+    let Some(ref db) = state.db else {
+        warn!(chat_jid = %request.chat_jid, channel, "no storage, dropping inbound message");
+        return;
+    };
+    let is_group = request.chat_type.as_deref().map(|t| t != "private");
+    if let Err(e) = db
+        .store_chat_metadata(
+            &request.chat_jid,
+            &request.timestamp,
+            request.chat_name.as_deref(),
+            Some(channel),
+            is_group,
+        )
+        .await
+    {
+        warn!(chat_jid = %request.chat_jid, err = %e, "failed to store chat metadata");
+    }
+
+    // Only registered chats keep message content, as with host ingestion.
+    // A topic without its own registration is stored under its chat's.
+    let Some(group_jid) = group_jid_for(state, &request.chat_jid).await else {
+        tracing::debug!(chat_jid = %request.chat_jid, channel, "message from unregistered chat");
+        return;
+    };
+    request.chat_jid = group_jid;
+
+    // Edits and deletes change an existing row, which keeps its id.
+    let correlation_id =
+        matches!(request.kind, telegram::TelegramUpdateKind::Message).then(new_correlation_id);
+    let stored = match request.kind {
+        telegram::TelegramUpdateKind::Message => db
+            .store_message(&NewMessage {
+                id: request.message_id.clone(),
+                chat_jid: request.chat_jid.clone(),
+                sender: request.sender_id.clone().unwrap_or_default(),
+                sender_name: request.sender_name.clone().unwrap_or_default(),
+                content: request.content.clone(),
+                timestamp: request.timestamp.clone(),
+                is_from_me: false,
+                is_bot_message: false,
+                edited: false,
+                attachments: request.attachments.clone(),
+                correlation_id: correlation_id.clone(),
+            })
+            .await
+            .map(|()| true),
+        telegram::TelegramUpdateKind::EditedMessage => {
+            db.edit_message(
+                &request.message_id,
+                &request.chat_jid,
+                &request.content,
+                &request.timestamp,
+            )
+            .await
+        }
+        telegram::TelegramUpdateKind::DeletedMessage => {
+            db.delete_message(&request.message_id, &request.chat_jid, &request.timestamp)
+                .await
+        }
+    };
+    match stored {
+        Ok(_) => {
+            info!(
+                chat_jid = %request.chat_jid,
+                kind = ?request.kind,
+                correlation_id = correlation_id.as_deref().unwrap_or_default(),
+                channel,
+                "inbound message stored"
+            );
+            if matches!(request.kind, telegram::TelegramUpdateKind::Message) {
+                request_id::note_message(&request.chat_jid);
+                event_stream::publish(
+                    LiveEvent::new(LiveEventKind::MessageIngested, &request.chat_jid)
+                        .with_message(&request.message_id)
+                        .with_channel(channel)
+                        .with_correlation_id(correlation_id.as_deref()),
+                );
+            }
+        }
+        Err(e) => {
+            warn!(chat_jid = %request.chat_jid, channel, err = %e, "failed to store inbound message");
+            health::record_error(health::SUBSYSTEM_DB, &e);
+        }
+    }
+}
+
+/// `/chatid` and `/ping` are answered here since they need no group; the
+/// rest go through the same path as `/v1/commands`.
+async fn inbound_command_reply(
+    state: &AppState,
+    command: telegram::TelegramCommand,
+) -> commands::CommandResult {
+    let assistant_name = std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into());
+    let text = match command.command.as_str() {
+        "chatid" => format!(
+            "Chat ID: tg:{}\nName: {}\nType: {}",
+            command.chat_jid.trim_start_matches("tg:"),
+            command.chat_name,
+            command.chat_type
+        ),
+        "ping" => format!("{assistant_name} is online."),
+        _ => {
+            return group_command_reply(
+                state,
+                &command.chat_jid,
+                command.command,
+                command.args,
+                command.sender_id,
+            )
+            .await;
+        }
+    };
+    commands::CommandResult {
+        text,
+        parse_mode: None,
+        effects: vec![],
+        reply_markup: None,
+    }
+}
+
+/// Run a command against the chat's registered group, if any.
+async fn group_command_reply(
+    state: &AppState,
+    chat_jid: &str,
+    command: String,
+    args: String,
+    sender: Option<String>,
+) -> commands::CommandResult {
+    let chat_jid = group_jid_for(state, chat_jid)
+        .await
+        .unwrap_or_else(|| chat_jid.to_string());
+    let group = state.groups.read().await.get(&chat_jid).cloned();
+    let session_id = match group {
+        Some(ref g) => state.sessions.read().await.get(&g.folder).cloned(),
+        None => None,
+    };
+    let request = commands::CommandRequest {
+        container_active: state.queue.is_active(&chat_jid).await,
+        chat_jid,
+        command,
+        args,
+        group_name: group.as_ref().map(|g| g.name.clone()),
+        group_folder: group.as_ref().map(|g| g.folder.clone()),
+        current_model: group.and_then(|g| g.model),
+        session_id,
+        sender,
+    };
+    run_command(state, request).await
+}
+
+async fn handle_slash_command(
+    State(state): State<AppState>,
+    Json(request): Json<commands::CommandRequest>,
+) -> Json<commands::CommandResult> {
+    Json(run_command(&state, request).await)
+}
+
+/// Shared by `/v1/commands` and Telegram long-polling.
+async fn run_command(
+    state: &AppState,
+    request: commands::CommandRequest,
+) -> commands::CommandResult {
+    if request.command == "health" {
+        return health_command(state, request.group_folder.as_deref()).await;
+    }
+    if matches!(request.command.as_str(), "revert-last" | "revert_last") {
+        return revert_last_command(state, &request).await;
+    }
+
+    let assistant_name = std::env::var("ASSISTANT_NAME")
+        .unwrap_or_else(|_| "Amtiskaw".into());
+    let disk_usage = match request.group_folder.as_deref() {
+        Some(folder) => {
+            let container_config = state
+                .groups
+                .read()
+                .await
+                .values()
+                .find(|g| g.folder == folder)
+                .and_then(|g| g.container_config.clone())
+                .and_then(|v| serde_json::from_value(v).ok());
+            state
+                .run_config
+                .disk_quotas
+                .summary(folder, container_config.as_ref())
+        }
+        None => None,
+    };
+    let is_main = request.group_folder.as_deref()
+        == Some(state.config.orchestrator.main_group_folder.as_str());
+    let tasks = match (
+        request.command.as_str(),
+        request.group_folder.as_deref(),
+        &state.db,
+    ) {
+        ("tasks" | "schedule" | "cancel", Some(folder), Some(pool)) => {
+            match pool.get_tasks_for_group(folder).await {
+                Ok(tasks) => Some(tasks),
+                Err(e) => {
+                    warn!(folder, err = %e, "failed to load tasks for command");
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    let group = state.groups.read().await.get(&request.chat_jid).cloned();
+    let ctx = commands::CommandContext {
+        assistant_name,
+        started_at: state.started_at,
+        disk_usage,
+        tasks,
+        timezone: state.config.scheduler.timezone.clone(),
+        now: chrono::Utc::now(),
+        queue: match request.command.as_str() {
+            "queue" => Some(state.queue.group_status(&request.chat_jid).await),
+            _ => None,
+        },
+        containers: match request.command.as_str() {
+            "ps" if is_main => Some(state.queue.active_groups().await),
+            _ => None,
+        },
+        last_log: match (request.command.as_str(), request.group_folder.as_deref()) {
+            ("logs", Some(folder)) => latest_run_log(state, folder).await,
+            _ => None,
+        },
+        usage: match (
+            request.command.as_str(),
+            request.group_folder.as_deref(),
+            &state.db,
+        ) {
+            ("usage", Some(folder), Some(pool)) => group_usage(pool, folder).await,
+            _ => None,
+        },
+        stats: match (
+            request.command.as_str(),
+            request.group_folder.as_deref(),
+            &state.db,
+        ) {
+            ("stats", Some(folder), Some(pool)) => {
+                let since = (chrono::Utc::now() - chrono::Duration::days(1)).to_rfc3339();
+                match pool.usage_totals(Some(folder), &since).await {
+                    Ok(stats) => Some(stats),
+                    Err(e) => {
+                        warn!(folder, err = %e, "failed to load run stats");
+                        None
+                    }
+                }
+            }
+            _ => None,
+        },
+        muted_until: group.as_ref().and_then(|g| g.muted_until.clone()),
+        trigger: group.as_ref().map(|g| commands::GroupTrigger {
+            pattern: g.trigger.clone(),
+            required: g.requires_trigger.unwrap_or(true),
+        }),
+        registry: state.commands.clone(),
+        sender: request.sender.clone(),
+        is_main,
+        locale: i18n::locale_or_default(group.as_ref().and_then(|g| g.locale.as_deref())),
+    };
+    let result = commands::handle_command(
+        &request.command,
+        &request.args,
+        request.group_name.as_deref(),
+        request.group_folder.as_deref(),
+        request.current_model.as_deref(),
+        request.session_id.as_deref(),
+        request.container_active,
+        &ctx,
+    );
+
     // Apply side effects
     if !result.effects.is_empty() {
         apply_command_effects(
-            &state,
+            state,
             &request.chat_jid,
             request.group_folder.as_deref(),
+            request.sender.as_deref(),
             &result.effects,
         )
         .await;
     }
 
-    Json(result)
+    result
+}
+
+/// The group's usage over each of `/usage`'s periods.
+async fn group_usage(
+    pool: &SharedStorage,
+    folder: &str,
+) -> Option<[intercom_core::UsageTotals; 3]> {
+    let now = chrono::Utc::now();
+    let mut totals: [intercom_core::UsageTotals; 3] = Default::default();
+    for (slot, (_, days)) in totals.iter_mut().zip(commands::USAGE_PERIODS) {
+        let since = (now - chrono::Duration::days(days)).to_rfc3339();
+        match pool.usage_totals(Some(folder), &since).await {
+            Ok(period) => *slot = period,
+            Err(e) => {
+                warn!(folder, err = %e, "failed to load usage");
+                return None;
+            }
+        }
+    }
+    Some(totals)
+}
+
+/// The group's newest container run log, read off the blocking pool since
+/// an old one may need gunzipping.
+async fn latest_run_log(state: &AppState, folder: &str) -> Option<commands::RunLog> {
+    let logs_dir = state.run_config.groups_dir.join(folder).join("logs");
+    let read = tokio::task::spawn_blocking(move || -> std::io::Result<Option<commands::RunLog>> {
+        let Some(path) = container::log_retention::latest(&logs_dir)? else {
+            return Ok(None);
+        };
+        Ok(Some(commands::RunLog {
+            file_name: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            text: container::log_retention::read(&path)?,
+        }))
+    })
+    .await;
+    match read {
+        Ok(Ok(log)) => log,
+        Ok(Err(e)) => {
+            warn!(folder, err = %e, "failed to read container log");
+            None
+        }
+        Err(e) => {
+            warn!(folder, err = %e, "container log read panicked");
+            None
+        }
+    }
+}
+
+/// `/health` probes live dependencies, so it runs here rather than in the
+/// pure `commands` handlers. Restricted to the main group.
+async fn health_command(state: &AppState, group_folder: Option<&str>) -> commands::CommandResult {
+    if group_folder != Some(state.config.orchestrator.main_group_folder.as_str()) {
+        return commands::CommandResult {
+            text: "/health is only available in the main group.".into(),
+            parse_mode: None,
+            effects: vec![],
+            reply_markup: None,
+        };
+    }
+    let report = health::run_checks(
+        state.db.as_ref(),
+        &state.telegram,
+        &state.demarch,
+        state.run_config.executor.as_ref(),
+        &state.run_config.data_dir.join("ipc"),
+        state.queue.snapshot().await,
+        state.started_at,
+    )
+    .await;
+    commands::CommandResult {
+        text: health::format_report(&report, Instant::now()),
+        parse_mode: Some("Markdown".into()),
+        effects: vec![],
+        reply_markup: None,
+    }
+}
+
+/// `/revert-last` rolls the group folder back to its snapshot from before the
+/// most recent run. Restricted to `snapshots.admins`.
+async fn revert_last_command(
+    state: &AppState,
+    request: &commands::CommandRequest,
+) -> commands::CommandResult {
+    let reply = |text: String| commands::CommandResult {
+        text,
+        parse_mode: None,
+        effects: vec![],
+        reply_markup: None,
+    };
+    let snapshots = &state.config.snapshots;
+    if !snapshots.enabled {
+        return reply("Workspace snapshots are disabled.".into());
+    }
+    let is_admin = request
+        .sender
+        .as_deref()
+        .is_some_and(|sender| snapshots.admins.iter().any(|a| a == sender));
+    if !is_admin {
+        return reply("/revert-last is restricted to admins.".into());
+    }
+    let Some(folder) = request.group_folder.as_deref() else {
+        return reply("This chat is not registered.".into());
+    };
+    if request.container_active || state.queue.is_active(&request.chat_jid).await {
+        return reply(
+            "An agent is still running in this group. Try again once it finishes.".into(),
+        );
+    }
+
+    let repo = workspace_git::WorkspaceRepo::for_group(&state.run_config, folder);
+    match repo.revert_last().await {
+        Ok(Some(reverted)) if reverted.files.is_empty() => reply(format!(
+            "Run {} made no file changes; marked it as reverted.",
+            reverted.run_id
+        )),
+        Ok(Some(reverted)) => {
+            info!(
+                folder,
+                run_id = reverted.run_id.as_str(),
+                files = reverted.files.len(),
+                "reverted run"
+            );
+            let mut lines = vec![format!(
+                "Reverted run {} ({} files):",
+                reverted.run_id,
+                reverted.files.len()
+            )];
+            lines.extend(reverted.files.iter().take(10).map(|f| format!("• {f}")));
+            if reverted.files.len() > 10 {
+                lines.push(format!("…and {} more", reverted.files.len() - 10));
+            }
+            reply(lines.join("\n"))
+        }
+        Ok(None) => reply("No run snapshots to revert.".into()),
+        Err(e) => {
+            warn!(folder, err = %e, "revert-last failed");
+            reply(format!("Revert failed: {e}"))
+        }
+    }
 }
 
 /// Apply side effects from command handlers.
@@ -719,12 +3051,23 @@ async fn apply_command_effects(
     state: &AppState,
     chat_jid: &str,
     group_folder: Option<&str>,
+    sender: Option<&str>,
     effects: &[commands::CommandEffect],
 ) {
     for effect in effects {
         match effect {
+            commands::CommandEffect::CancelQueued => {
+                state.queue.cancel(chat_jid).await;
+            }
             commands::CommandEffect::KillContainer => {
-                state.queue.kill_group(chat_jid).await;
+                state
+                    .queue
+                    .kill_group(
+                        chat_jid,
+                        state.run_config.executor.as_ref(),
+                        state.run_config.stop_timeout,
+                    )
+                    .await;
             }
             commands::CommandEffect::ClearSession => {
                 if let Some(folder) = group_folder {
@@ -758,6 +3101,134 @@ async fn apply_command_effects(
                     }
                 }
             }
+            commands::CommandEffect::SetMute { until } => {
+                let mut groups = state.groups.write().await;
+                if let Some(group) = groups.get_mut(chat_jid) {
+                    group.muted_until = until.clone();
+                    if let Some(ref pool) = state.db {
+                        if let Err(e) = pool.set_registered_group(group).await {
+                            tracing::warn!(err = %e, chat_jid, "failed to persist mute");
+                        }
+                    }
+                }
+            }
+            commands::CommandEffect::SetTrigger { pattern, required } => {
+                let mut groups = state.groups.write().await;
+                if let Some(group) = groups.get_mut(chat_jid) {
+                    group.trigger = pattern.clone();
+                    group.requires_trigger = Some(*required);
+                    info!(
+                        chat_jid,
+                        trigger = pattern.as_str(),
+                        required,
+                        "trigger changed from chat"
+                    );
+                    if let Some(ref pool) = state.db {
+                        if let Err(e) = pool.set_registered_group(group).await {
+                            tracing::warn!(err = %e, chat_jid, "failed to persist trigger change");
+                        }
+                    }
+                }
+            }
+            commands::CommandEffect::SetLocale { locale } => {
+                let mut groups = state.groups.write().await;
+                if let Some(group) = groups.get_mut(chat_jid) {
+                    group.locale = Some(locale.clone());
+                    info!(
+                        chat_jid,
+                        locale = locale.as_str(),
+                        "language changed from chat"
+                    );
+                    if let Some(ref pool) = state.db {
+                        if let Err(e) = pool.set_registered_group(group).await {
+                            tracing::warn!(err = %e, chat_jid, "failed to persist language change");
+                        }
+                    }
+                }
+            }
+            commands::CommandEffect::InjectPrompt { prompt } => {
+                if let Err(e) = store_command_prompt(state, chat_jid, sender, prompt).await {
+                    tracing::warn!(err = %e, chat_jid, "failed to store command prompt");
+                }
+            }
+            commands::CommandEffect::CreateTask {
+                prompt,
+                schedule_type,
+                schedule_value,
+                next_run,
+            } => {
+                if let (Some(folder), Some(pool)) = (group_folder, &state.db) {
+                    let now = chrono::Utc::now();
+                    let task = intercom_core::ScheduledTask {
+                        id: format!("task-{}-{folder}", now.timestamp_millis()),
+                        group_folder: folder.to_string(),
+                        chat_jid: chat_jid.to_string(),
+                        prompt: prompt.clone(),
+                        schedule_type: schedule_type.clone(),
+                        schedule_value: schedule_value.clone(),
+                        context_mode: "isolated".into(),
+                        next_run: next_run.clone(),
+                        last_run: None,
+                        last_result: None,
+                        status: "active".into(),
+                        created_at: now.to_rfc3339(),
+                        task_config: None,
+                    };
+                    match pool.create_task(&task).await {
+                        Ok(()) => info!(task_id = %task.id, folder, "task scheduled from chat"),
+                        Err(e) => {
+                            tracing::warn!(err = %e, folder, "failed to create task from chat")
+                        }
+                    }
+                }
+            }
+            commands::CommandEffect::CancelTask { task_id } => {
+                if let Some(ref pool) = state.db {
+                    match pool.delete_task(task_id).await {
+                        Ok(()) => info!(task_id = %task_id, "task cancelled from chat"),
+                        Err(e) => {
+                            tracing::warn!(err = %e, task_id = %task_id, "failed to cancel task")
+                        }
+                    }
+                }
+            }
+            commands::CommandEffect::SendLog { file_name } => {
+                let Some(folder) = group_folder else { continue };
+                if !container::log_retention::is_run_log(file_name)
+                    || file_name.contains(['/', '\\'])
+                {
+                    continue;
+                }
+                let path = state
+                    .run_config
+                    .groups_dir
+                    .join(folder)
+                    .join("logs")
+                    .join(file_name);
+                let content = match tokio::task::spawn_blocking(move || {
+                    container::log_retention::read(&path)
+                })
+                .await
+                {
+                    Ok(Ok(content)) => content,
+                    Ok(Err(e)) => {
+                        tracing::warn!(err = %e, folder, file_name, "failed to read container log");
+                        continue;
+                    }
+                    Err(e) => {
+                        tracing::warn!(err = %e, folder, "container log read panicked");
+                        continue;
+                    }
+                };
+                let name = file_name.trim_end_matches(".gz");
+                if let Err(e) = state
+                    .telegram
+                    .send_document(chat_jid, name, content.as_bytes(), None)
+                    .await
+                {
+                    tracing::warn!(err = %e, chat_jid, file_name, "failed to send container log");
+                }
+            }
         }
     }
 }
@@ -0,0 +1,358 @@
+//! Render agent markdown for a channel's [`MarkdownDialect`].
+//!
+//! Agents write CommonMark-ish text: `**bold**`, `*italic*`, `~~strike~~`,
+//! inline code, fenced code blocks, `[links](url)` and `#` headings. This
+//! parses that subset into spans and renders them for the target dialect,
+//! escaping everything else so a stray `.` or `(` never makes Telegram
+//! reject the message. Anything not recognised stays literal text.
+
+use intercom_core::MarkdownDialect;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Span {
+    Text(String),
+    Bold(Vec<Span>),
+    Italic(Vec<Span>),
+    Strike(Vec<Span>),
+    Code(String),
+    Pre { lang: String, code: String },
+    Link { label: Vec<Span>, url: String },
+}
+
+/// Render `text` for `dialect`. `Plain` drops the markup, keeping link
+/// targets in parentheses.
+pub fn render(text: &str, dialect: MarkdownDialect) -> String {
+    let spans = parse_blocks(text);
+    let mut out = String::with_capacity(text.len() + text.len() / 8);
+    for span in &spans {
+        render_span(span, dialect, &mut out);
+    }
+    out
+}
+
+fn parse_blocks(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut lines = text.split('\n').peekable();
+    let mut first = true;
+
+    while let Some(line) = lines.next() {
+        if !first {
+            spans.push(Span::Text("\n".into()));
+        }
+        first = false;
+
+        if let Some(lang) = line.trim_start().strip_prefix("```") {
+            let mut code = Vec::new();
+            for inner in lines.by_ref() {
+                if inner.trim_start().starts_with("```") {
+                    break;
+                }
+                code.push(inner);
+            }
+            spans.push(Span::Pre {
+                lang: lang.trim().to_string(),
+                code: code.join("\n"),
+            });
+            continue;
+        }
+
+        let hashes = line.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+            spans.push(Span::Bold(parse_inline(line[hashes..].trim())));
+        } else {
+            spans.extend(parse_inline(line));
+        }
+    }
+    spans
+}
+
+fn is_word(c: Option<char>) -> bool {
+    c.is_some_and(|c| c.is_alphanumeric())
+}
+
+/// Byte index of the closing `delim` in `s`. Single `*` and `_` only count
+/// at word boundaries, so `snake_case` and `2*3*4` stay literal.
+fn find_close(s: &str, delim: &str) -> Option<usize> {
+    let mut from = 0;
+    while let Some(pos) = s[from..].find(delim) {
+        let at = from + pos;
+        let before = s[..at].chars().next_back();
+        let after = s[at + delim.len()..].chars().next();
+        let single = delim.len() == 1;
+        let ok = at > 0
+            && !before.is_some_and(char::is_whitespace)
+            && !(single && (is_word(after) || s[at + 1..].starts_with(delim)));
+        if ok {
+            return Some(at);
+        }
+        from = at + delim.len();
+    }
+    None
+}
+
+fn parse_inline(s: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+
+    let flush = |text: &mut String, spans: &mut Vec<Span>| {
+        if !text.is_empty() {
+            spans.push(Span::Text(std::mem::take(text)));
+        }
+    };
+
+    while i < s.len() {
+        let rest = &s[i..];
+        let prev = s[..i].chars().next_back();
+
+        if let Some(after) = rest.strip_prefix('`') {
+            if let Some(end) = after.find('`') {
+                flush(&mut text, &mut spans);
+                spans.push(Span::Code(after[..end].to_string()));
+                i += end + 2;
+                continue;
+            }
+        }
+
+        let mut matched = false;
+        for (delim, kind) in [("**", 0), ("__", 0), ("~~", 2), ("*", 1), ("_", 1)] {
+            let Some(after) = rest.strip_prefix(delim) else {
+                continue;
+            };
+            if after.starts_with(char::is_whitespace) || (delim.len() == 1 && is_word(prev)) {
+                continue;
+            }
+            if let Some(end) = find_close(after, delim) {
+                flush(&mut text, &mut spans);
+                let inner = parse_inline(&after[..end]);
+                spans.push(match kind {
+                    0 => Span::Bold(inner),
+                    1 => Span::Italic(inner),
+                    _ => Span::Strike(inner),
+                });
+                i += delim.len() * 2 + end;
+                matched = true;
+            }
+            break;
+        }
+        if matched {
+            continue;
+        }
+
+        if let Some(after) = rest.strip_prefix('[') {
+            if let Some(mid) = after.find("](") {
+                if let Some(close) = after[mid + 2..].find(')') {
+                    let url = &after[mid + 2..mid + 2 + close];
+                    if !url.is_empty() && !url.contains(char::is_whitespace) {
+                        flush(&mut text, &mut spans);
+                        spans.push(Span::Link {
+                            label: parse_inline(&after[..mid]),
+                            url: url.to_string(),
+                        });
+                        i += 1 + mid + 2 + close + 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        let ch = rest.chars().next().unwrap_or_default();
+        text.push(ch);
+        i += ch.len_utf8();
+    }
+    flush(&mut text, &mut spans);
+    spans
+}
+
+fn escape_into(out: &mut String, text: &str, specials: &str) {
+    for ch in text.chars() {
+        if specials.contains(ch) {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+}
+
+fn escape_html(out: &mut String, text: &str) {
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+/// Characters MarkdownV2 requires escaping outside entities.
+const V2_SPECIALS: &str = "_*[]()~`>#+-=|{}.!\\";
+
+fn render_children(children: &[Span], dialect: MarkdownDialect, out: &mut String) {
+    for child in children {
+        render_span(child, dialect, out);
+    }
+}
+
+fn wrap(children: &[Span], dialect: MarkdownDialect, open: &str, close: &str, out: &mut String) {
+    out.push_str(open);
+    render_children(children, dialect, out);
+    out.push_str(close);
+}
+
+fn render_span(span: &Span, dialect: MarkdownDialect, out: &mut String) {
+    use MarkdownDialect::*;
+    match (span, dialect) {
+        (Span::Text(t), MarkdownV2) => escape_into(out, t, V2_SPECIALS),
+        (Span::Text(t), Markdown) => escape_into(out, t, "_*`["),
+        (Span::Text(t), Html) => escape_html(out, t),
+        (Span::Text(t), Plain) => out.push_str(t),
+
+        (Span::Bold(c), MarkdownV2 | Markdown) => wrap(c, dialect, "*", "*", out),
+        (Span::Bold(c), Html) => wrap(c, dialect, "<b>", "</b>", out),
+        (Span::Italic(c), MarkdownV2 | Markdown) => wrap(c, dialect, "_", "_", out),
+        (Span::Italic(c), Html) => wrap(c, dialect, "<i>", "</i>", out),
+        (Span::Strike(c), MarkdownV2) => wrap(c, dialect, "~", "~", out),
+        (Span::Strike(c), Html) => wrap(c, dialect, "<s>", "</s>", out),
+        (Span::Bold(c) | Span::Italic(c) | Span::Strike(c), _) => render_children(c, dialect, out),
+
+        (Span::Code(code), MarkdownV2) => {
+            out.push('`');
+            escape_into(out, code, "`\\");
+            out.push('`');
+        }
+        (Span::Code(code), Markdown) => {
+            out.push('`');
+            out.push_str(&code.replace('`', "'"));
+            out.push('`');
+        }
+        (Span::Code(code), Html) => {
+            out.push_str("<code>");
+            escape_html(out, code);
+            out.push_str("</code>");
+        }
+        (Span::Code(code), Plain) => out.push_str(code),
+
+        (Span::Pre { lang, code }, MarkdownV2) => {
+            out.push_str("```");
+            escape_into(out, lang, V2_SPECIALS);
+            out.push('\n');
+            escape_into(out, code, "`\\");
+            out.push_str("\n```");
+        }
+        (Span::Pre { lang, code }, Markdown) => {
+            out.push_str("```");
+            out.push_str(lang);
+            out.push('\n');
+            out.push_str(&code.replace("```", "'''"));
+            out.push_str("\n```");
+        }
+        (Span::Pre { lang, code }, Html) => {
+            if lang.is_empty() {
+                out.push_str("<pre>");
+            } else {
+                out.push_str("<pre><code class=\"language-");
+                escape_html(out, lang);
+                out.push_str("\">");
+            }
+            escape_html(out, code);
+            out.push_str(if lang.is_empty() {
+                "</pre>"
+            } else {
+                "</code></pre>"
+            });
+        }
+        (Span::Pre { code, .. }, Plain) => out.push_str(code),
+
+        (Span::Link { label, url }, MarkdownV2) => {
+            wrap(label, dialect, "[", "](", out);
+            escape_into(out, url, ")\\");
+            out.push(')');
+        }
+        (Span::Link { label, url }, Markdown) => {
+            wrap(label, dialect, "[", "](", out);
+            out.push_str(url);
+            out.push(')');
+        }
+        (Span::Link { label, url }, Html) => {
+            out.push_str("<a href=\"");
+            escape_html(out, url);
+            out.push_str("\">");
+            render_children(label, dialect, out);
+            out.push_str("</a>");
+        }
+        (Span::Link { label, url }, Plain) => {
+            render_children(label, dialect, out);
+            out.push_str(" (");
+            out.push_str(url);
+            out.push(')');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_v2_converts_emphasis_and_escapes_specials() {
+        assert_eq!(
+            render(
+                "**Done.** See *notes* (v1.2) - ok!",
+                MarkdownDialect::MarkdownV2
+            ),
+            "*Done\\.* See _notes_ \\(v1\\.2\\) \\- ok\\!"
+        );
+        assert_eq!(
+            render(
+                "use snake_case_names and 2*3*4",
+                MarkdownDialect::MarkdownV2
+            ),
+            "use snake\\_case\\_names and 2\\*3\\*4"
+        );
+        assert_eq!(
+            render("* item\n# Title", MarkdownDialect::MarkdownV2),
+            "\\* item\n*Title*"
+        );
+    }
+
+    #[test]
+    fn markdown_v2_code_and_links_escape_only_what_they_must() {
+        assert_eq!(
+            render(
+                "run `a_b.sh` or [the docs!](https://x.io/a_b)",
+                MarkdownDialect::MarkdownV2
+            ),
+            "run `a_b.sh` or [the docs\\!](https://x.io/a_b)"
+        );
+        assert_eq!(
+            render(
+                "```rust\nlet x = \"a\\b\";\n```",
+                MarkdownDialect::MarkdownV2
+            ),
+            "```rust\nlet x = \"a\\\\b\";\n```"
+        );
+    }
+
+    #[test]
+    fn html_and_plain_rendering() {
+        let text = "**Hi** <you> & [site](https://e.com?a=1&b=2)\n```\nx < y\n```";
+        assert_eq!(
+            render(text, MarkdownDialect::Html),
+            "<b>Hi</b> &lt;you&gt; &amp; <a href=\"https://e.com?a=1&amp;b=2\">site</a>\n<pre>x &lt; y</pre>"
+        );
+        assert_eq!(
+            render(text, MarkdownDialect::Plain),
+            "Hi <you> & site (https://e.com?a=1&b=2)\nx < y"
+        );
+    }
+
+    #[test]
+    fn unterminated_markup_stays_literal() {
+        assert_eq!(
+            render("**open and `tick", MarkdownDialect::Plain),
+            "**open and `tick"
+        );
+        assert_eq!(render("**open", MarkdownDialect::MarkdownV2), "\\*\\*open");
+    }
+}
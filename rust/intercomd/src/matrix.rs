@@ -0,0 +1,602 @@
+//! Matrix client bridge: `/sync` long-polling for ingress, room sends and
+//! `m.replace` edits for egress.
+//!
+//! Rooms map to `mx:<room id>` JIDs (`mx:!abc123:example.org`). The bridge
+//! logs in with an existing access token and only reads unencrypted rooms;
+//! `m.room.encrypted` events are skipped. Inbound events are converted to
+//! the same ingress requests Telegram produces, so storage and trigger
+//! handling are shared.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use anyhow::{Context, anyhow};
+use intercom_core::{
+    Attachment, ChannelProfile, IntercomConfig, MarkdownDialect, MatrixConfig, SharedStorage,
+};
+use reqwest::{Client, Url};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::channels::{self, ChannelBridge, ChannelFuture, OutboundMessage};
+use crate::health::{SUBSYSTEM_DB, record_error};
+use crate::markdown;
+use crate::outbound::{split_text, truncate_text};
+use crate::telegram::{TelegramIngressRequest, TelegramUpdateKind};
+
+/// Name of the Matrix profile under `[channels.profiles]`, also its health
+/// subsystem.
+pub const MATRIX_CHANNEL: &str = "matrix";
+/// JID prefix for Matrix rooms.
+pub const MATRIX_JID_PREFIX: &str = "mx:";
+const HTML_FORMAT: &str = "org.matrix.custom.html";
+
+#[derive(Clone)]
+pub struct MatrixBridge {
+    client: Client,
+    homeserver_url: String,
+    access_token: Option<String>,
+    user_id: String,
+    profile: ChannelProfile,
+    /// Transaction ids must be unique per access token; the start time keeps
+    /// them unique across restarts.
+    txn_prefix: String,
+    txn_counter: Arc<AtomicU64>,
+    store: Option<SharedStorage>,
+    sender_name: String,
+}
+
+/// One `/sync` response reduced to what the bridge ingests.
+#[derive(Debug, Default)]
+pub struct MatrixSync {
+    pub next_batch: String,
+    pub events: Vec<TelegramIngressRequest>,
+}
+
+/// JID for a room id.
+pub fn room_jid(room_id: &str) -> String {
+    format!("{MATRIX_JID_PREFIX}{room_id}")
+}
+
+fn room_id(jid: &str) -> &str {
+    jid.strip_prefix(MATRIX_JID_PREFIX).unwrap_or(jid)
+}
+
+/// `@alice:example.org` → `alice`.
+fn localpart(user_id: &str) -> &str {
+    let id = user_id.strip_prefix('@').unwrap_or(user_id);
+    id.split_once(':').map_or(id, |(local, _)| local)
+}
+
+fn millis_to_iso(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+impl MatrixBridge {
+    pub fn new(config: &IntercomConfig) -> Self {
+        let access_token = std::env::var("MATRIX_ACCESS_TOKEN")
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty());
+
+        Self {
+            client: Client::new(),
+            homeserver_url: config
+                .matrix
+                .homeserver_url
+                .trim_end_matches('/')
+                .to_string(),
+            access_token,
+            user_id: config.matrix.user_id.clone(),
+            profile: config.channels.profile(MATRIX_CHANNEL),
+            txn_prefix: format!("intercom-{}", chrono::Utc::now().timestamp_millis()),
+            txn_counter: Arc::new(AtomicU64::new(0)),
+            store: None,
+            sender_name: String::new(),
+        }
+    }
+
+    /// Record every sent message in `store` as a bot message from
+    /// `sender_name`, keyed by its event id.
+    pub fn with_storage(
+        mut self,
+        store: Option<SharedStorage>,
+        sender_name: impl Into<String>,
+    ) -> Self {
+        self.store = store;
+        self.sender_name = sender_name.into();
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.access_token.is_some() && !self.homeserver_url.is_empty()
+    }
+
+    fn token(&self) -> anyhow::Result<&str> {
+        self.access_token
+            .as_deref()
+            .ok_or_else(|| anyhow!("MATRIX_ACCESS_TOKEN is not set for intercomd"))
+    }
+
+    /// Client-server API URL with each of `segments` percent-encoded.
+    fn endpoint(&self, segments: &[&str]) -> anyhow::Result<Url> {
+        if self.homeserver_url.is_empty() {
+            return Err(anyhow!("matrix.homeserver_url is not configured"));
+        }
+        let mut url = Url::parse(&self.homeserver_url)
+            .with_context(|| format!("invalid matrix.homeserver_url `{}`", self.homeserver_url))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("matrix.homeserver_url cannot be a base URL"))?
+            .extend(["_matrix", "client", "v3"])
+            .extend(segments);
+        Ok(url)
+    }
+
+    fn next_txn_id(&self) -> String {
+        let n = self.txn_counter.fetch_add(1, Ordering::Relaxed);
+        format!("{}-{n}", self.txn_prefix)
+    }
+
+    /// `m.room.message` content for `text`, with an HTML body when the
+    /// profile asks for it.
+    fn message_content(&self, text: &str) -> serde_json::Value {
+        let mut content = serde_json::json!({
+            "msgtype": "m.text",
+            "body": markdown::render(text, MarkdownDialect::Plain),
+        });
+        if self.profile.markdown == MarkdownDialect::Html {
+            content["format"] = HTML_FORMAT.into();
+            content["formatted_body"] = markdown::render(text, MarkdownDialect::Html).into();
+        }
+        content
+    }
+
+    async fn send_event(&self, jid: &str, content: &serde_json::Value) -> anyhow::Result<String> {
+        let txn_id = self.next_txn_id();
+        let url = self.endpoint(&["rooms", room_id(jid), "send", "m.room.message", &txn_id])?;
+        let body = self
+            .call(self.client.put(url).json(content), "send")
+            .await?;
+        body.get("event_id")
+            .and_then(|id| id.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Matrix send returned no event_id"))
+    }
+
+    async fn call(
+        &self,
+        request: reqwest::RequestBuilder,
+        what: &str,
+    ) -> anyhow::Result<serde_json::Value> {
+        let response = request
+            .bearer_auth(self.token()?)
+            .send()
+            .await
+            .with_context(|| format!("failed to call Matrix {what}"))?;
+        let status = response.status();
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .with_context(|| format!("failed to parse Matrix {what} response"))?;
+        if !status.is_success() {
+            let errcode = body
+                .get("errcode")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown");
+            let error = body
+                .get("error")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            return Err(anyhow!(
+                "Matrix {what} failed ({status}, {errcode}): {error}"
+            ));
+        }
+        Ok(body)
+    }
+
+    /// Send `text` to a room, split to the profile limit. The first chunk
+    /// replies to `reply_to` when given. Returns the sent event ids.
+    pub async fn send_message(&self, message: &OutboundMessage) -> anyhow::Result<Vec<String>> {
+        if message.text.trim().is_empty() {
+            return Err(anyhow!("cannot send an empty Matrix message"));
+        }
+        let mut event_ids = Vec::new();
+        for (index, chunk) in split_text(&message.text, self.max_chars())
+            .iter()
+            .enumerate()
+        {
+            let mut content = self.message_content(chunk);
+            if let Some(reply_to) = message.reply_to.as_deref().filter(|_| index == 0) {
+                content["m.relates_to"] =
+                    serde_json::json!({ "m.in_reply_to": { "event_id": reply_to } });
+            }
+            let event_id = self.send_event(&message.jid, &content).await?;
+            channels::record_sent(
+                self.store.as_ref(),
+                self.name(),
+                &self.sender_name,
+                &message.jid,
+                &event_id,
+                chunk,
+                message.correlation_id.as_deref(),
+            )
+            .await;
+            event_ids.push(event_id);
+        }
+        Ok(event_ids)
+    }
+
+    /// Replace a sent message's text with an `m.replace` edit.
+    pub async fn edit_message(&self, jid: &str, event_id: &str, text: &str) -> anyhow::Result<()> {
+        if !self.profile.supports_edit {
+            return Err(anyhow!("editing is disabled in the matrix channel profile"));
+        }
+        let (text, _) = truncate_text(text, self.max_chars());
+        let new_content = self.message_content(&text);
+        let mut content = new_content.clone();
+        // Clients without edit support show the fallback body.
+        content["body"] = format!("* {}", new_content["body"].as_str().unwrap_or_default()).into();
+        if let Some(html) = new_content.get("formatted_body").and_then(|v| v.as_str()) {
+            content["formatted_body"] = format!("* {html}").into();
+        }
+        content["m.new_content"] = new_content;
+        content["m.relates_to"] =
+            serde_json::json!({ "rel_type": "m.replace", "event_id": event_id });
+        self.send_event(jid, &content).await?;
+
+        if let Some(store) = &self.store {
+            let edited_at = chrono::Utc::now().to_rfc3339();
+            if let Err(e) = store.edit_message(event_id, jid, &text, &edited_at).await {
+                warn!(jid, event_id, err = %e, "failed to store Matrix edit");
+                record_error(SUBSYSTEM_DB, &e);
+            }
+        }
+        Ok(())
+    }
+
+    /// One `/sync` long-poll. Without `since` nothing is returned but the
+    /// batch token, so a restart does not replay room history.
+    pub async fn sync(&self, since: Option<&str>, timeout_secs: u64) -> anyhow::Result<MatrixSync> {
+        let mut url = self.endpoint(&["sync"])?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("timeout", &(timeout_secs * 1000).to_string());
+            match since {
+                Some(since) => query.append_pair("since", since),
+                None => query.append_pair("filter", r#"{"room":{"timeline":{"limit":0}}}"#),
+            };
+        }
+        let body = self
+            .call(
+                self.client
+                    .get(url)
+                    .timeout(Duration::from_secs(timeout_secs + 30)),
+                "sync",
+            )
+            .await?;
+        let mut sync = convert_sync(&body, &self.user_id);
+        if since.is_none() {
+            sync.events.clear();
+        }
+        Ok(sync)
+    }
+}
+
+/// Convert the joined-room timelines of a `/sync` response into ingress
+/// requests. Messages become `Message` (or `EditedMessage` for
+/// `m.replace`), redactions become `DeletedMessage`; the bot's own events
+/// and encrypted rooms are skipped.
+pub fn convert_sync(body: &serde_json::Value, own_user_id: &str) -> MatrixSync {
+    let next_batch = body
+        .get("next_batch")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let mut events = Vec::new();
+    let Some(rooms) = body.pointer("/rooms/join").and_then(|v| v.as_object()) else {
+        return MatrixSync { next_batch, events };
+    };
+    for (room, joined) in rooms {
+        let timeline = joined
+            .pointer("/timeline/events")
+            .and_then(|v| v.as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for event in timeline {
+            if let Some(request) = convert_event(room, event, own_user_id) {
+                events.push(request);
+            }
+        }
+    }
+    MatrixSync { next_batch, events }
+}
+
+fn convert_event(
+    room: &str,
+    event: &serde_json::Value,
+    own_user_id: &str,
+) -> Option<TelegramIngressRequest> {
+    let str_at = |pointer: &str| event.pointer(pointer).and_then(|v| v.as_str());
+    let sender = str_at("/sender")?;
+    if sender == own_user_id {
+        return None;
+    }
+    let timestamp = millis_to_iso(
+        event
+            .get("origin_server_ts")
+            .and_then(|v| v.as_i64())
+            .unwrap_or_default(),
+    );
+    let (message_id, content, kind) = match str_at("/type")? {
+        "m.room.message" if str_at("/content/m.relates_to/rel_type") == Some("m.replace") => (
+            str_at("/content/m.relates_to/event_id")?,
+            message_text(event.pointer("/content/m.new_content")?)?,
+            TelegramUpdateKind::EditedMessage,
+        ),
+        "m.room.message" => (
+            str_at("/event_id")?,
+            message_text(event.get("content")?)?,
+            TelegramUpdateKind::Message,
+        ),
+        // `redacts` moved into content in room version 11.
+        "m.room.redaction" => (
+            str_at("/redacts").or_else(|| str_at("/content/redacts"))?,
+            String::new(),
+            TelegramUpdateKind::DeletedMessage,
+        ),
+        "m.room.encrypted" => {
+            debug!(room, "skipping encrypted Matrix event");
+            return None;
+        }
+        _ => return None,
+    };
+    Some(TelegramIngressRequest {
+        chat_jid: room_jid(room),
+        chat_name: None,
+        chat_type: Some("group".to_string()),
+        message_id: message_id.to_string(),
+        sender_id: Some(sender.to_string()),
+        sender_name: Some(localpart(sender).to_string()),
+        content,
+        timestamp,
+        persist: true,
+        kind,
+        attachments: Vec::new(),
+    })
+}
+
+/// Text of an `m.room.message` content, with placeholders for media.
+fn message_text(content: &serde_json::Value) -> Option<String> {
+    let body = content
+        .get("body")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    let text = match content.get("msgtype").and_then(|v| v.as_str())? {
+        "m.text" | "m.notice" => body.to_string(),
+        "m.emote" => format!("* {body}"),
+        "m.image" => "[Photo]".to_string(),
+        "m.video" => "[Video]".to_string(),
+        "m.audio" => "[Audio]".to_string(),
+        "m.file" => format!("[Document: {body}]"),
+        "m.location" => "[Location]".to_string(),
+        _ => return None,
+    };
+    Some(text)
+}
+
+impl ChannelBridge for MatrixBridge {
+    fn name(&self) -> &'static str {
+        MATRIX_CHANNEL
+    }
+
+    fn profile(&self) -> &ChannelProfile {
+        &self.profile
+    }
+
+    fn send<'a>(&'a self, message: &'a OutboundMessage) -> ChannelFuture<'a, Vec<String>> {
+        Box::pin(self.send_message(message))
+    }
+
+    fn edit<'a>(
+        &'a self,
+        jid: &'a str,
+        message_id: &'a str,
+        text: &'a str,
+    ) -> ChannelFuture<'a, ()> {
+        Box::pin(self.edit_message(jid, message_id, text))
+    }
+
+    fn normalize_ingress(&self, content: &str, _attachments: &[Attachment]) -> String {
+        content.trim().to_string()
+    }
+}
+
+/// Sleep for `delay` unless shutdown is signalled first. Returns false on
+/// shutdown.
+async fn sleep_or_shutdown(delay: Duration, shutdown: &mut watch::Receiver<bool>) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => true,
+        _ = shutdown.changed() => false,
+    }
+}
+
+/// Run `/sync` and hand every converted event to `handle`, in order. Runs
+/// until `shutdown` fires. Used when `matrix.enabled = true`.
+pub async fn run_sync_loop<F, Fut>(
+    bridge: MatrixBridge,
+    config: MatrixConfig,
+    mut shutdown: watch::Receiver<bool>,
+    mut handle: F,
+) where
+    F: FnMut(TelegramIngressRequest) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let retry = Duration::from_millis(config.sync_retry_ms.max(100));
+    let mut since: Option<String> = None;
+    info!(user = %config.user_id, "Matrix sync started");
+    loop {
+        let batch = tokio::select! {
+            batch = bridge.sync(since.as_deref(), config.sync_timeout_secs) => batch,
+            _ = shutdown.changed() => break,
+        };
+        let sync = match batch {
+            Ok(sync) => sync,
+            Err(e) => {
+                warn!(err = %format!("{e:#}"), "Matrix sync failed");
+                record_error(MATRIX_CHANNEL, &e);
+                if !sleep_or_shutdown(retry, &mut shutdown).await {
+                    break;
+                }
+                continue;
+            }
+        };
+        if !sync.next_batch.is_empty() {
+            since = Some(sync.next_batch);
+        }
+        for event in sync.events {
+            handle(event).await;
+        }
+    }
+    info!("Matrix sync stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sync_body(events: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "next_batch": "s72595_4483_1934",
+            "rooms": { "join": { "!room:example.org": { "timeline": { "events": events } } } }
+        })
+    }
+
+    #[test]
+    fn room_jids_round_trip() {
+        assert_eq!(room_jid("!abc:example.org"), "mx:!abc:example.org");
+        assert_eq!(room_id("mx:!abc:example.org"), "!abc:example.org");
+        assert_eq!(localpart("@alice:example.org"), "alice");
+    }
+
+    #[test]
+    fn text_message_becomes_ingress() {
+        let sync = convert_sync(
+            &sync_body(serde_json::json!([{
+                "type": "m.room.message",
+                "event_id": "$ev1",
+                "sender": "@alice:example.org",
+                "origin_server_ts": 1_700_000_000_000_i64,
+                "content": { "msgtype": "m.text", "body": "@Amtiskaw hello" }
+            }])),
+            "@bot:example.org",
+        );
+        assert_eq!(sync.next_batch, "s72595_4483_1934");
+        let [event] = sync.events.as_slice() else {
+            panic!("expected one event");
+        };
+        assert_eq!(event.chat_jid, "mx:!room:example.org");
+        assert_eq!(event.message_id, "$ev1");
+        assert_eq!(event.sender_name.as_deref(), Some("alice"));
+        assert_eq!(event.content, "@Amtiskaw hello");
+        assert_eq!(event.timestamp, "2023-11-14T22:13:20.000Z");
+        assert!(matches!(event.kind, TelegramUpdateKind::Message));
+    }
+
+    #[test]
+    fn edits_and_redactions_target_the_original_event() {
+        let sync = convert_sync(
+            &sync_body(serde_json::json!([
+                {
+                    "type": "m.room.message",
+                    "event_id": "$ev2",
+                    "sender": "@alice:example.org",
+                    "content": {
+                        "msgtype": "m.text",
+                        "body": "* fixed",
+                        "m.new_content": { "msgtype": "m.text", "body": "fixed" },
+                        "m.relates_to": { "rel_type": "m.replace", "event_id": "$ev1" }
+                    }
+                },
+                {
+                    "type": "m.room.redaction",
+                    "event_id": "$ev3",
+                    "sender": "@alice:example.org",
+                    "redacts": "$ev1"
+                }
+            ])),
+            "@bot:example.org",
+        );
+        assert_eq!(sync.events.len(), 2);
+        assert_eq!(sync.events[0].message_id, "$ev1");
+        assert_eq!(sync.events[0].content, "fixed");
+        assert!(matches!(
+            sync.events[0].kind,
+            TelegramUpdateKind::EditedMessage
+        ));
+        assert_eq!(sync.events[1].message_id, "$ev1");
+        assert!(matches!(
+            sync.events[1].kind,
+            TelegramUpdateKind::DeletedMessage
+        ));
+    }
+
+    #[test]
+    fn own_and_encrypted_events_are_skipped() {
+        let sync = convert_sync(
+            &sync_body(serde_json::json!([
+                {
+                    "type": "m.room.message",
+                    "event_id": "$mine",
+                    "sender": "@bot:example.org",
+                    "content": { "msgtype": "m.text", "body": "reply" }
+                },
+                {
+                    "type": "m.room.encrypted",
+                    "event_id": "$secret",
+                    "sender": "@alice:example.org",
+                    "content": { "algorithm": "m.megolm.v1.aes-sha2" }
+                }
+            ])),
+            "@bot:example.org",
+        );
+        assert!(sync.events.is_empty());
+    }
+
+    #[test]
+    fn media_gets_placeholders() {
+        let image = serde_json::json!({ "msgtype": "m.image", "body": "cat.png" });
+        let file = serde_json::json!({ "msgtype": "m.file", "body": "report.pdf" });
+        assert_eq!(message_text(&image).as_deref(), Some("[Photo]"));
+        assert_eq!(
+            message_text(&file).as_deref(),
+            Some("[Document: report.pdf]")
+        );
+    }
+
+    #[test]
+    fn endpoint_encodes_room_ids() {
+        let mut config = IntercomConfig::default();
+        config.matrix.homeserver_url = "https://matrix.example.org/".into();
+        let bridge = MatrixBridge::new(&config);
+        let url = bridge
+            .endpoint(&["rooms", "!abc:example.org", "send", "m.room.message", "t1"])
+            .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://matrix.example.org/_matrix/client/v3/rooms/!abc:example.org/send/m.room.message/t1"
+        );
+    }
+
+    #[test]
+    fn html_profile_adds_formatted_body() {
+        let bridge = MatrixBridge::new(&IntercomConfig::default());
+        let content = bridge.message_content("**hi**");
+        assert_eq!(content["body"], "hi");
+        assert_eq!(content["format"], HTML_FORMAT);
+        assert_eq!(content["formatted_body"], "<b>hi</b>");
+    }
+}
@@ -9,16 +9,21 @@
 //!
 //! On startup, `recover_pending_messages()` re-enqueues groups with unprocessed messages
 //! (handles crash between advancing last_timestamp and agent dispatch).
+//!
+//! When `listen_notify` is set, a dedicated LISTEN connection on
+//! `intercom_new_message` wakes the loop as soon as a message is inserted.
+//! If that connection drops the loop keeps polling and re-listens later.
 
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use intercom_core::{PgPool, RegisteredGroup};
+use intercom_core::{NEW_MESSAGE_CHANNEL, RegisteredGroup, SharedStorage};
 use regex::Regex;
-use tokio::sync::{RwLock, watch};
+use tokio::sync::{RwLock, mpsc, watch};
 use tracing::{debug, error, info, warn};
 
+use crate::health;
 use crate::queue::GroupQueue;
 
 /// Configuration for the message loop.
@@ -30,8 +35,16 @@ pub struct MessageLoopConfig {
     pub assistant_name: String,
     /// Folder name for the main group (e.g., "main"). Main group doesn't require trigger.
     pub main_group_folder: String,
+    /// Wake on store notifications (Postgres NOTIFY) in addition to polling.
+    pub listen_notify: bool,
 }
 
+/// How long to wait before re-opening a dropped LISTEN connection.
+const LISTEN_RETRY: Duration = Duration::from_secs(30);
+
+/// How often expired `/mute`s are cleared.
+const UNMUTE_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Per-group cursor state. Stored in router_state as JSON.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default)]
 pub struct AgentTimestamps(pub HashMap<String, String>);
@@ -39,7 +52,7 @@ pub struct AgentTimestamps(pub HashMap<String, String>);
 /// Run the message poll loop. Exits when shutdown signal fires.
 pub async fn run_message_loop(
     config: MessageLoopConfig,
-    pool: PgPool,
+    pool: SharedStorage,
     queue: Arc<GroupQueue>,
     groups: Arc<RwLock<HashMap<String, RegisteredGroup>>>,
     shared_timestamps: Arc<RwLock<AgentTimestamps>>,
@@ -74,9 +87,23 @@ pub async fn run_message_loop(
         .await;
     }
 
+    let mut listener = if config.listen_notify {
+        open_listener(&pool).await
+    } else {
+        None
+    };
+    let mut last_listen_attempt = Instant::now();
+
     loop {
         tokio::select! {
             _ = tokio::time::sleep(interval) => {}
+            woke = wait_for_notification(&mut listener) => {
+                if !woke {
+                    warn!("message listener dropped, falling back to polling");
+                    listener = None;
+                    last_listen_attempt = Instant::now();
+                }
+            }
             _ = shutdown.changed() => {
                 if *shutdown.borrow() {
                     info!("message loop shutting down");
@@ -85,6 +112,14 @@ pub async fn run_message_loop(
             }
         }
 
+        if config.listen_notify
+            && listener.is_none()
+            && last_listen_attempt.elapsed() >= LISTEN_RETRY
+        {
+            listener = open_listener(&pool).await;
+            last_listen_attempt = Instant::now();
+        }
+
         if let Err(e) = poll_once(
             &config,
             &pool,
@@ -96,14 +131,48 @@ pub async fn run_message_loop(
         .await
         {
             error!(err = %e, "error in message poll");
+            health::record_error(health::SUBSYSTEM_DB, &e);
         }
     }
 }
 
+async fn open_listener(pool: &SharedStorage) -> Option<mpsc::UnboundedReceiver<String>> {
+    match pool.listen(NEW_MESSAGE_CHANNEL).await {
+        Ok(rx) => {
+            info!(
+                channel = NEW_MESSAGE_CHANNEL,
+                "listening for new message notifications"
+            );
+            Some(rx)
+        }
+        Err(e) => {
+            warn!(err = %e, "failed to LISTEN for new messages, polling only");
+            None
+        }
+    }
+}
+
+/// Wait for the next NOTIFY. Returns `false` when the listener has closed;
+/// pends forever when there is no listener so the poll timer wins.
+/// Notifications that piled up meanwhile are drained — one poll covers them.
+async fn wait_for_notification(listener: &mut Option<mpsc::UnboundedReceiver<String>>) -> bool {
+    let Some(rx) = listener.as_mut() else {
+        return std::future::pending().await;
+    };
+    match rx.recv().await {
+        Some(chat_jid) => {
+            debug!(chat_jid = %chat_jid, "woken by new message notification");
+            while rx.try_recv().is_ok() {}
+            true
+        }
+        None => false,
+    }
+}
+
 /// Single poll iteration. Extracted for testability.
 async fn poll_once(
     config: &MessageLoopConfig,
-    pool: &PgPool,
+    pool: &SharedStorage,
     queue: &GroupQueue,
     groups: &RwLock<HashMap<String, RegisteredGroup>>,
     last_timestamp: &mut String,
@@ -148,14 +217,30 @@ async fn poll_once(
             None => continue,
         };
 
+        // Muted groups keep storing messages; they become context once the
+        // group is woken and triggered again.
+        if is_muted(group.muted_until.as_deref(), chrono::Utc::now()) {
+            debug!(chat_jid = chat_jid.as_str(), "group muted, not dispatching");
+            continue;
+        }
+
         let is_main = group.folder == config.main_group_folder;
         let needs_trigger = !is_main && group.requires_trigger.unwrap_or(true);
 
         // For non-main groups, only act on trigger messages.
         // Non-trigger messages accumulate in DB; they'll be pulled as context
         // when a trigger eventually arrives.
-        if needs_trigger {
-            let trigger_pattern = build_trigger_regex(&config.assistant_name, if group.trigger.is_empty() { None } else { Some(group.trigger.as_str()) });
+        let trigger_pattern = needs_trigger.then(|| {
+            build_trigger_regex(
+                &config.assistant_name,
+                if group.trigger.is_empty() {
+                    None
+                } else {
+                    Some(group.trigger.as_str())
+                },
+            )
+        });
+        if let Some(ref trigger_pattern) = trigger_pattern {
             let has_trigger = group_messages
                 .iter()
                 .any(|m| trigger_pattern.is_match(m.content.trim()));
@@ -184,12 +269,22 @@ async fn poll_once(
 
         let formatted = format_messages(messages_to_use);
 
-        if queue.send_message(&chat_jid, &formatted).await {
+        if queue
+            .send_message(&chat_jid, &formatted, batch_correlation_id(messages_to_use))
+            .await
+        {
             debug!(
                 chat_jid = chat_jid.as_str(),
                 count = messages_to_use.len(),
+                request_id = crate::request_id::take_for_chat(&chat_jid).unwrap_or_default(),
                 "piped messages to active container"
             );
+            queue
+                .set_reply_to(
+                    &chat_jid,
+                    reply_target(messages_to_use, trigger_pattern.as_ref()),
+                )
+                .await;
             // Advance per-group cursor
             if let Some(last) = messages_to_use.last() {
                 let mut ts = shared_timestamps.write().await;
@@ -207,7 +302,7 @@ async fn poll_once(
 
 /// Startup recovery: check for unprocessed messages in registered groups.
 async fn recover_pending_messages(
-    pool: &PgPool,
+    pool: &SharedStorage,
     queue: &GroupQueue,
     groups: &RwLock<HashMap<String, RegisteredGroup>>,
     agent_timestamps: &AgentTimestamps,
@@ -216,6 +311,9 @@ async fn recover_pending_messages(
 ) {
     let groups_guard = groups.read().await;
     for (chat_jid, group) in groups_guard.iter() {
+        if is_muted(group.muted_until.as_deref(), chrono::Utc::now()) {
+            continue;
+        }
         let since = agent_timestamps
             .0
             .get(chat_jid)
@@ -258,7 +356,7 @@ async fn recover_pending_messages(
 // Cursor persistence
 // ---------------------------------------------------------------------------
 
-async fn load_cursor(pool: &PgPool, key: &str) -> String {
+async fn load_cursor(pool: &SharedStorage, key: &str) -> String {
     match pool.get_router_state(key).await {
         Ok(Some(v)) => v,
         Ok(None) => String::new(),
@@ -269,19 +367,19 @@ async fn load_cursor(pool: &PgPool, key: &str) -> String {
     }
 }
 
-async fn save_cursor(pool: &PgPool, key: &str, value: &str) {
+async fn save_cursor(pool: &SharedStorage, key: &str, value: &str) {
     if let Err(e) = pool.set_router_state(key, value).await {
         error!(key, err = %e, "failed to save cursor");
     }
 }
 
 /// Public wrapper for loading agent timestamps (used by process_group).
-pub async fn load_agent_timestamps_pub(pool: &PgPool) -> AgentTimestamps {
+pub async fn load_agent_timestamps_pub(pool: &SharedStorage) -> AgentTimestamps {
     load_agent_timestamps(pool).await
 }
 
 /// Public wrapper for saving agent timestamps (used by process_group).
-pub async fn save_agent_timestamps_pub(pool: &PgPool, timestamps: &AgentTimestamps) {
+pub async fn save_agent_timestamps_pub(pool: &SharedStorage, timestamps: &AgentTimestamps) {
     save_agent_timestamps(pool, timestamps).await;
 }
 
@@ -290,12 +388,91 @@ pub fn build_trigger_regex_pub(assistant_name: &str, custom_trigger: Option<&str
     build_trigger_regex(assistant_name, custom_trigger)
 }
 
+/// Whether a group muted with `/mute` is still muted at `now`. An
+/// unreadable timestamp counts as unmuted so a bad row can't silence a group.
+pub(crate) fn is_muted(muted_until: Option<&str>, now: chrono::DateTime<chrono::Utc>) -> bool {
+    muted_until
+        .and_then(|until| chrono::DateTime::parse_from_rfc3339(until).ok())
+        .is_some_and(|until| until > now)
+}
+
+/// Clear `/mute`s that have run out, so a muted group's row doesn't keep a
+/// stale time. Muting is enforced by [`is_muted`] either way; this keeps the
+/// stored state honest. Exits when shutdown fires.
+pub async fn run_unmute_sweep(
+    pool: SharedStorage,
+    groups: Arc<RwLock<HashMap<String, RegisteredGroup>>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut ticker = tokio::time::interval(UNMUTE_SWEEP_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.changed() => break,
+        }
+        unmute_expired(&pool, &groups, chrono::Utc::now()).await;
+    }
+}
+
+/// Clear every mute that has ended by `now`; returns the groups woken.
+async fn unmute_expired(
+    pool: &SharedStorage,
+    groups: &RwLock<HashMap<String, RegisteredGroup>>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<String> {
+    let woken: Vec<RegisteredGroup> = {
+        let mut groups = groups.write().await;
+        groups
+            .values_mut()
+            .filter(|g| g.muted_until.is_some() && !is_muted(g.muted_until.as_deref(), now))
+            .map(|g| {
+                g.muted_until = None;
+                g.clone()
+            })
+            .collect()
+    };
+    for group in &woken {
+        info!(group = group.name.as_str(), "mute expired");
+        if let Err(e) = pool.set_registered_group(group).await {
+            warn!(folder = group.folder.as_str(), err = %e, "failed to persist unmute");
+        }
+    }
+    woken.into_iter().map(|g| g.folder).collect()
+}
+
+/// Correlation id for a batch of messages: the newest one that has an id.
+pub(crate) fn batch_correlation_id(messages: &[intercom_core::NewMessage]) -> Option<&str> {
+    messages
+        .iter()
+        .rev()
+        .find_map(|m| m.correlation_id.as_deref())
+}
+
+/// Message the agent's reply to a batch should thread under: the newest
+/// user message that matches `trigger`, or the newest user message when the
+/// group needs no trigger.
+pub(crate) fn reply_target<'a>(
+    messages: &'a [intercom_core::NewMessage],
+    trigger: Option<&regex::Regex>,
+) -> Option<&'a str> {
+    let mut inbound = messages
+        .iter()
+        .rev()
+        .filter(|m| !m.is_bot_message && !m.is_from_me);
+    match trigger {
+        Some(re) => inbound.find(|m| re.is_match(m.content.trim())),
+        None => inbound.next(),
+    }
+    .map(|m| m.id.as_str())
+}
+
 /// Public wrapper for formatting messages (used by process_group).
 pub fn format_messages_pub(messages: &[intercom_core::NewMessage]) -> String {
     format_messages(messages)
 }
 
-async fn load_agent_timestamps(pool: &PgPool) -> AgentTimestamps {
+async fn load_agent_timestamps(pool: &SharedStorage) -> AgentTimestamps {
     match pool.get_router_state("last_agent_timestamp").await {
         Ok(Some(json)) => serde_json::from_str(&json).unwrap_or_default(),
         Ok(None) => AgentTimestamps::default(),
@@ -306,7 +483,7 @@ async fn load_agent_timestamps(pool: &PgPool) -> AgentTimestamps {
     }
 }
 
-async fn save_agent_timestamps(pool: &PgPool, timestamps: &AgentTimestamps) {
+async fn save_agent_timestamps(pool: &SharedStorage, timestamps: &AgentTimestamps) {
     let json = serde_json::to_string(timestamps).unwrap_or_else(|_| "{}".into());
     if let Err(e) = pool.set_router_state("last_agent_timestamp", &json).await {
         error!(err = %e, "failed to save agent timestamps");
@@ -318,15 +495,24 @@ async fn save_agent_timestamps(pool: &PgPool, timestamps: &AgentTimestamps) {
 // ---------------------------------------------------------------------------
 
 /// Format messages into a prompt string for the container agent.
-/// Matches the `formatMessages()` function in `src/router.ts`.
+/// Matches the `formatMessages()` function in `src/router.ts`. Edited
+/// messages are tagged so the agent knows the text replaced an earlier one,
+/// and attachments are appended as `[kind mime path: caption]` markers.
 fn format_messages(messages: &[intercom_core::NewMessage]) -> String {
     messages
         .iter()
         .map(|m| {
-            if m.is_bot_message {
-                format!("[{}]: {}", m.sender_name, m.content)
+            let mut body = m.content.clone();
+            for attachment in &m.attachments {
+                if !body.is_empty() {
+                    body.push(' ');
+                }
+                body.push_str(&attachment.describe());
+            }
+            if m.edited {
+                format!("[{}] (edited): {}", m.sender_name, body)
             } else {
-                format!("[{}]: {}", m.sender_name, m.content)
+                format!("[{}]: {}", m.sender_name, body)
             }
         })
         .collect::<Vec<_>>()
@@ -370,6 +556,9 @@ mod tests {
                 timestamp: "2024-01-15T12:00:00Z".into(),
                 is_from_me: false,
                 is_bot_message: false,
+                edited: false,
+                attachments: Vec::new(),
+                correlation_id: None,
             },
             intercom_core::NewMessage {
                 id: "2".into(),
@@ -380,6 +569,9 @@ mod tests {
                 timestamp: "2024-01-15T12:01:00Z".into(),
                 is_from_me: true,
                 is_bot_message: true,
+                edited: false,
+                attachments: Vec::new(),
+                correlation_id: None,
             },
         ];
         let result = format_messages(&msgs);
@@ -387,6 +579,93 @@ mod tests {
         assert!(result.contains("[Amtiskaw]: Hi there"));
     }
 
+    #[test]
+    fn format_messages_marks_edits() {
+        let msgs = vec![intercom_core::NewMessage {
+            id: "1".into(),
+            chat_jid: "tg:123".into(),
+            sender: "user1".into(),
+            sender_name: "Alice".into(),
+            content: "Hello, fixed".into(),
+            timestamp: "2024-01-15T12:00:00Z".into(),
+            is_from_me: false,
+            is_bot_message: false,
+            edited: true,
+            attachments: Vec::new(),
+            correlation_id: None,
+        }];
+        assert_eq!(format_messages(&msgs), "[Alice] (edited): Hello, fixed");
+    }
+
+    #[test]
+    fn format_messages_appends_attachments() {
+        let msgs = vec![intercom_core::NewMessage {
+            id: "1".into(),
+            chat_jid: "tg:123".into(),
+            sender: "user1".into(),
+            sender_name: "Alice".into(),
+            content: String::new(),
+            timestamp: "2024-01-15T12:00:00Z".into(),
+            is_from_me: false,
+            is_bot_message: false,
+            edited: false,
+            attachments: vec![intercom_core::Attachment {
+                kind: "document".into(),
+                file_name: Some("report.pdf".into()),
+                ..Default::default()
+            }],
+            correlation_id: None,
+        }];
+        assert_eq!(format_messages(&msgs), "[Alice]: [document report.pdf]");
+    }
+
+    #[test]
+    fn batch_correlation_id_is_newest_tagged_message() {
+        let msg = |id: &str, correlation_id: Option<&str>| intercom_core::NewMessage {
+            id: id.into(),
+            chat_jid: "tg:123".into(),
+            sender: "user1".into(),
+            sender_name: "Alice".into(),
+            content: "hi".into(),
+            timestamp: "2024-01-15T12:00:00Z".into(),
+            is_from_me: false,
+            is_bot_message: false,
+            edited: false,
+            attachments: Vec::new(),
+            correlation_id: correlation_id.map(str::to_string),
+        };
+        let msgs = [msg("1", Some("c1")), msg("2", Some("c2")), msg("3", None)];
+        assert_eq!(batch_correlation_id(&msgs), Some("c2"));
+        assert_eq!(batch_correlation_id(&msgs[2..]), None);
+    }
+
+    #[test]
+    fn reply_target_prefers_newest_trigger_message() {
+        let msg = |id: &str, content: &str, is_bot_message: bool| intercom_core::NewMessage {
+            id: id.into(),
+            chat_jid: "tg:123".into(),
+            sender: "user1".into(),
+            sender_name: "Alice".into(),
+            content: content.into(),
+            timestamp: "2024-01-15T12:00:00Z".into(),
+            is_from_me: false,
+            is_bot_message,
+            edited: false,
+            attachments: Vec::new(),
+            correlation_id: None,
+        };
+        let msgs = [
+            msg("10", "@Amtiskaw first", false),
+            msg("11", "@Amtiskaw deploy?", false),
+            msg("12", "context after", false),
+            msg("bot-1", "@Amtiskaw echo", true),
+        ];
+        let re = build_trigger_regex("Amtiskaw", None);
+        assert_eq!(reply_target(&msgs, Some(&re)), Some("11"));
+        assert_eq!(reply_target(&msgs, None), Some("12"));
+        assert_eq!(reply_target(&msgs[2..], Some(&re)), None);
+    }
+
     #[test]
     fn trigger_regex_matches_at_mention() {
         let re = build_trigger_regex("Amtiskaw", None);
@@ -419,4 +698,78 @@ mod tests {
         let result = format_messages(&[]);
         assert!(result.is_empty());
     }
+
+    #[tokio::test]
+    async fn notification_wakes_and_drains_backlog() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut listener = Some(rx);
+        tx.send("tg:1".to_string()).unwrap();
+        tx.send("tg:2".to_string()).unwrap();
+        assert!(wait_for_notification(&mut listener).await);
+        // Both queued notifications were consumed by the single wake-up.
+        assert!(listener.as_mut().unwrap().try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn closed_listener_reports_fallback() {
+        let (tx, rx) = mpsc::unbounded_channel::<String>();
+        drop(tx);
+        let mut listener = Some(rx);
+        assert!(!wait_for_notification(&mut listener).await);
+    }
+
+    #[tokio::test]
+    async fn missing_listener_never_wakes() {
+        let mut listener = None;
+        let woke = tokio::time::timeout(
+            Duration::from_millis(20),
+            wait_for_notification(&mut listener),
+        )
+        .await;
+        assert!(woke.is_err());
+    }
+
+    #[tokio::test]
+    async fn expired_mutes_are_cleared() {
+        let pool: SharedStorage = Arc::new(intercom_core::SqliteStore::new(":memory:"));
+        let group = |folder: &str, muted_until: Option<&str>| RegisteredGroup {
+            jid: format!("tg:{folder}"),
+            name: folder.to_string(),
+            folder: folder.to_string(),
+            trigger: "@Andy".into(),
+            added_at: "2026-10-01T00:00:00Z".into(),
+            container_config: None,
+            requires_trigger: None,
+            runtime: None,
+            model: None,
+            muted_until: muted_until.map(str::to_string),
+            locale: None,
+        };
+        let now: chrono::DateTime<chrono::Utc> = "2026-10-17T08:00:00Z".parse().unwrap();
+        let groups = RwLock::new(HashMap::new());
+        for g in [
+            group("done", Some("2026-10-17T07:59:00Z")),
+            group("later", Some("2026-10-17T09:00:00Z")),
+            group("never", None),
+        ] {
+            pool.set_registered_group(&g).await.unwrap();
+            groups.write().await.insert(g.jid.clone(), g);
+        }
+
+        assert!(is_muted(Some("2026-10-17T09:00:00+00:00"), now));
+        assert!(!is_muted(Some("2026-10-17T07:59:00Z"), now));
+        assert!(!is_muted(Some("soon"), now));
+
+        assert_eq!(unmute_expired(&pool, &groups, now).await, ["done"]);
+        assert!(groups.read().await["tg:done"].muted_until.is_none());
+        let stored = pool.get_all_registered_groups().await.unwrap();
+        assert!(stored["tg:done"].muted_until.is_none());
+        assert!(
+            stored["tg:later"]
+                .muted_until
+                .as_deref()
+                .is_some_and(|t| t.starts_with("2026-10-17T09:00"))
+        );
+        assert!(unmute_expired(&pool, &groups, now).await.is_empty());
+    }
 }
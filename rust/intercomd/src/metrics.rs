@@ -0,0 +1,157 @@
+//! Prometheus text exposition for `GET /metrics`.
+//!
+//! Just enough of the format for counters, gauges and histograms with
+//! fixed buckets; the queue keeps its own series and renders them here.
+
+use std::fmt::Write;
+
+/// Bucket bounds for durations in seconds, from sub-second to an hour.
+pub const DURATION_BUCKETS: &[f64] = &[
+    0.1, 0.5, 1.0, 5.0, 15.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0,
+];
+
+/// Bucket bounds for small counts, such as retries.
+pub const COUNT_BUCKETS: &[f64] = &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+
+/// A histogram with fixed upper bounds; counts are per bucket, not
+/// cumulative, until rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        if let Some(bucket) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Writes metric families in the text format.
+#[derive(Default)]
+pub struct Exposition {
+    out: String,
+}
+
+fn label_set(labels: &[(&str, &str)], extra: Option<(&str, &str)>) -> String {
+    let pairs: Vec<String> = labels
+        .iter()
+        .copied()
+        .chain(extra)
+        .map(|(name, value)| {
+            format!(
+                "{name}=\"{}\"",
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        })
+        .collect();
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+impl Exposition {
+    /// Start a family: its `HELP` and `TYPE` lines.
+    pub fn family(&mut self, name: &str, kind: &str, help: &str) -> &mut Self {
+        let _ = writeln!(self.out, "# HELP {name} {help}");
+        let _ = writeln!(self.out, "# TYPE {name} {kind}");
+        self
+    }
+
+    /// One counter or gauge sample.
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) -> &mut Self {
+        let _ = writeln!(self.out, "{name}{} {value}", label_set(labels, None));
+        self
+    }
+
+    /// One histogram's buckets, sum and count.
+    pub fn histogram(
+        &mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        histogram: &Histogram,
+    ) -> &mut Self {
+        let mut cumulative = 0;
+        for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+            cumulative += count;
+            let le = bound.to_string();
+            let _ = writeln!(
+                self.out,
+                "{name}_bucket{} {cumulative}",
+                label_set(labels, Some(("le", &le)))
+            );
+        }
+        let _ = writeln!(
+            self.out,
+            "{name}_bucket{} {}",
+            label_set(labels, Some(("le", "+Inf"))),
+            histogram.count
+        );
+        let _ = writeln!(
+            self.out,
+            "{name}_sum{} {}",
+            label_set(labels, None),
+            histogram.sum
+        );
+        let _ = writeln!(
+            self.out,
+            "{name}_count{} {}",
+            label_set(labels, None),
+            histogram.count
+        );
+        self
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms_render_cumulative_buckets() {
+        let mut waits = Histogram::new(&[1.0, 10.0]);
+        waits.observe(0.5);
+        waits.observe(3.0);
+        waits.observe(42.0);
+
+        let mut out = Exposition::default();
+        out.family("wait_seconds", "histogram", "Time in line.")
+            .histogram("wait_seconds", &[("kind", "message")], &waits);
+        out.family("rejected_total", "counter", "Refused.")
+            .sample("rejected_total", &[], 2.0);
+        assert_eq!(
+            out.finish(),
+            "# HELP wait_seconds Time in line.\n\
+             # TYPE wait_seconds histogram\n\
+             wait_seconds_bucket{kind=\"message\",le=\"1\"} 1\n\
+             wait_seconds_bucket{kind=\"message\",le=\"10\"} 2\n\
+             wait_seconds_bucket{kind=\"message\",le=\"+Inf\"} 3\n\
+             wait_seconds_sum{kind=\"message\"} 45.5\n\
+             wait_seconds_count{kind=\"message\"} 3\n\
+             # HELP rejected_total Refused.\n\
+             # TYPE rejected_total counter\n\
+             rejected_total 2\n"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Outbound text pipeline shared by every channel.
+//!
+//! Agent output is cleaned with [`strip_internal_blocks`] before any bridge
+//! sees it, and bridges split or truncate it against their channel's
+//! [`ChannelProfile`](intercom_core::ChannelProfile) from `[channels]`
+//! config, so channel limits stay out of the orchestrator.
+
+/// Strip `<internal>...</internal>` blocks from agent output.
+pub fn strip_internal_blocks(text: &str) -> String {
+    // Simple regex-free approach: find and remove <internal>...</internal> spans
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("<internal>") {
+        result.push_str(&rest[..start]);
+        if let Some(end) = rest[start..].find("</internal>") {
+            rest = &rest[start + end + "</internal>".len()..];
+        } else {
+            // Unclosed tag — strip to end
+            rest = "";
+            break;
+        }
+    }
+    result.push_str(rest);
+    result.trim().to_string()
+}
+
+/// Split text into chunks of at most `max_chars` characters.
+pub fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut chars_in_current = 0_usize;
+
+    for ch in text.chars() {
+        if chars_in_current >= max_chars {
+            chunks.push(current);
+            current = String::new();
+            chars_in_current = 0;
+        }
+        current.push(ch);
+        chars_in_current += 1;
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+/// Cut text to at most `max_chars` characters. The flag is true if anything
+/// was dropped.
+pub fn truncate_text(text: &str, max_chars: usize) -> (String, bool) {
+    let mut output = String::new();
+
+    for (count, ch) in text.chars().enumerate() {
+        if count >= max_chars {
+            return (output, true);
+        }
+        output.push(ch);
+    }
+
+    (output, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_internal_basic() {
+        let input = "Hello <internal>reasoning here</internal> World";
+        assert_eq!(strip_internal_blocks(input), "Hello  World");
+    }
+
+    #[test]
+    fn strip_internal_multiple() {
+        let input = "A <internal>x</internal> B <internal>y</internal> C";
+        assert_eq!(strip_internal_blocks(input), "A  B  C");
+    }
+
+    #[test]
+    fn strip_internal_none() {
+        assert_eq!(strip_internal_blocks("Hello World"), "Hello World");
+    }
+
+    #[test]
+    fn strip_internal_unclosed() {
+        let input = "Hello <internal>never closed";
+        assert_eq!(strip_internal_blocks(input), "Hello");
+    }
+
+    #[test]
+    fn strip_internal_multiline() {
+        let input = "Before\n<internal>\nmulti\nline\n</internal>\nAfter";
+        assert_eq!(strip_internal_blocks(input), "Before\n\nAfter");
+    }
+
+    #[test]
+    fn split_keeps_chunks_within_limit() {
+        let text = "a".repeat(9005);
+        let chunks = split_text(&text, 4096);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 4096));
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.chars().count())
+                .sum::<usize>(),
+            text.chars().count()
+        );
+    }
+
+    #[test]
+    fn truncate_counts_characters_not_bytes() {
+        assert_eq!(truncate_text("héllo", 3), ("hél".to_string(), true));
+        assert_eq!(truncate_text("hé", 3), ("hé".to_string(), false));
+    }
+}
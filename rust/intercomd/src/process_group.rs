@@ -8,37 +8,47 @@
 //! 3. Check trigger for non-main groups
 //! 4. Format prompt from messages
 //! 5. Spawn container via run_container_agent()
-//! 6. Stream output: route results to Telegram
+//! 6. Stream output: route results to the chat's channel (as an edited draft
+//!    if the channel streams drafts)
 //! 7. Store bot responses in Postgres
 //! 8. Advance per-group cursor on success, rollback on error
 
 use std::collections::HashMap;
-use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
 use intercom_core::{
-    ContainerInput, ContainerOutput, ContainerStatus, PgPool, RegisteredGroup, RuntimeKind,
+    ContainerInput, ContainerOutput, ContainerStatus, RegisteredGroup, RuntimeKind, SharedStorage,
+    StreamEvent, new_correlation_id,
 };
-use tokio::sync::RwLock;
-use tracing::{debug, error, info, warn};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{Instrument, error, info, info_span, warn};
 
+use crate::channels::{ChannelRegistry, OutboundMessage};
+use crate::container::context::{ContextInputs, GroupContext, write_group_context};
 use crate::container::mounts::GroupInfo;
-use crate::container::runner::{OutputCallback, RunConfig, run_container_agent, write_snapshots};
+use crate::container::runner::{
+    OutputCallback, RunConfig, record_run, run_container_agent, write_snapshots,
+};
 use crate::container::security::ContainerConfig;
+use crate::draft::DraftReply;
+use crate::health;
 use crate::message_loop::{self, AgentTimestamps};
+use crate::outbound::strip_internal_blocks;
 use crate::queue::{GroupQueue, ProcessMessagesFn};
-use crate::telegram::TelegramBridge;
+use crate::webhooks::{self, LifecycleEvent, LifecycleEventKind};
+use crate::workspace_git::{self, RunPhase};
 
 /// Build the `ProcessMessagesFn` closure that GroupQueue invokes for message processing.
 ///
 /// The returned closure captures all shared state and is `Send + Sync`.
 pub fn build_process_messages_fn(
-    pool: PgPool,
+    pool: SharedStorage,
     queue: Arc<GroupQueue>,
     groups: Arc<RwLock<HashMap<String, RegisteredGroup>>>,
     sessions: Arc<RwLock<HashMap<String, String>>>,
     shared_timestamps: Arc<RwLock<AgentTimestamps>>,
-    telegram: Arc<TelegramBridge>,
+    channels: Arc<ChannelRegistry>,
     assistant_name: String,
     main_group_folder: String,
     run_config: RunConfig,
@@ -49,45 +59,54 @@ pub fn build_process_messages_fn(
         let groups = groups.clone();
         let sessions = sessions.clone();
         let shared_timestamps = shared_timestamps.clone();
-        let telegram = telegram.clone();
+        let channels = channels.clone();
         let assistant_name = assistant_name.clone();
         let main_group_folder = main_group_folder.clone();
         let run_config = run_config.clone();
-
-        Box::pin(async move {
-            match process_group_messages(
-                &chat_jid,
-                &pool,
-                &queue,
-                &groups,
-                &sessions,
-                &shared_timestamps,
-                &telegram,
-                &assistant_name,
-                &main_group_folder,
-                &run_config,
-            )
-            .await
-            {
-                Ok(success) => success,
-                Err(e) => {
-                    error!(chat_jid, err = %e, "processGroupMessages failed");
-                    false
+        // correlation_id is recorded once the pending batch is known.
+        let span = info_span!(
+            "process_group",
+            chat_jid = %chat_jid,
+            correlation_id = tracing::field::Empty
+        );
+
+        Box::pin(
+            async move {
+                match process_group_messages(
+                    &chat_jid,
+                    &pool,
+                    &queue,
+                    &groups,
+                    &sessions,
+                    &shared_timestamps,
+                    &channels,
+                    &assistant_name,
+                    &main_group_folder,
+                    &run_config,
+                )
+                .await
+                {
+                    Ok(success) => success,
+                    Err(e) => {
+                        error!(chat_jid, err = %e, "processGroupMessages failed");
+                        false
+                    }
                 }
             }
-        })
+            .instrument(span),
+        )
     })
 }
 
 /// Core logic for processing messages for a single group.
 async fn process_group_messages(
     chat_jid: &str,
-    pool: &PgPool,
+    pool: &SharedStorage,
     queue: &Arc<GroupQueue>,
     groups: &Arc<RwLock<HashMap<String, RegisteredGroup>>>,
     sessions: &Arc<RwLock<HashMap<String, String>>>,
     shared_timestamps: &Arc<RwLock<AgentTimestamps>>,
-    telegram: &Arc<TelegramBridge>,
+    channels: &Arc<ChannelRegistry>,
     assistant_name: &str,
     main_group_folder: &str,
     run_config: &RunConfig,
@@ -100,6 +119,9 @@ async fn process_group_messages(
             None => return Ok(true), // unknown group — skip, not an error
         }
     };
+    if message_loop::is_muted(group.muted_until.as_deref(), chrono::Utc::now()) {
+        return Ok(true);
+    }
 
     let is_main = group.folder == main_group_folder;
 
@@ -118,6 +140,7 @@ async fn process_group_messages(
     }
 
     // 3. Check trigger for non-main groups
+    let mut trigger_re = None;
     if !is_main && group.requires_trigger.unwrap_or(true) {
         let trigger = if group.trigger.is_empty() {
             None
@@ -129,10 +152,18 @@ async fn process_group_messages(
         if !has_trigger {
             return Ok(true);
         }
+        trigger_re = Some(re);
     }
+    let reply_to = message_loop::reply_target(&pending, trigger_re.as_ref()).map(str::to_string);
+    queue.set_reply_to(chat_jid, reply_to.as_deref()).await;
 
     // 4. Format prompt
     let prompt = message_loop::format_messages_pub(&pending);
+    let correlation_id = message_loop::batch_correlation_id(&pending)
+        .map(str::to_string)
+        .unwrap_or_else(new_correlation_id);
+    tracing::Span::current().record("correlation_id", correlation_id.as_str());
+    queue.set_correlation_id(chat_jid, &correlation_id).await;
 
     // Save cursor position for rollback on error
     let previous_cursor = since.clone();
@@ -171,6 +202,13 @@ async fn process_group_messages(
         assistant_name: Some(assistant_name.to_string()),
         model: group.model.clone(),
         secrets: None, // Secrets injected by runner from env files
+        attachments: pending
+            .iter()
+            .flat_map(|m| m.attachments.iter().cloned())
+            .collect(),
+        correlation_id: Some(correlation_id.clone()),
+        reply_to_message_id: reply_to,
+        stdin_keepalive: None,
     };
 
     let group_info = GroupInfo {
@@ -181,23 +219,34 @@ async fn process_group_messages(
             .as_ref()
             .and_then(|v| serde_json::from_value::<ContainerConfig>(v.clone()).ok()),
     };
+    let webhook = group_info
+        .container_config
+        .as_ref()
+        .and_then(|c| c.webhook.clone());
+
+    // Over the hard disk quota: tell the chat instead of retrying the batch
+    if let Some(reason) = run_config
+        .disk_quotas
+        .refusal(&group.folder, group_info.container_config.as_ref())
+    {
+        warn!(group = group.name.as_str(), "{reason}");
+        if let Err(e) = channels.send_text(chat_jid, &reason).await {
+            error!(err = %e, "failed to send disk quota notice");
+        }
+        return Ok(true);
+    }
 
-    // 5b. Write task/group snapshots for container consumption
+    // 5b. Write task/group snapshots and context.json for container consumption
     {
-        let tasks_json = match pool.get_all_tasks().await {
-            Ok(tasks) => {
-                let filtered: Vec<_> = if is_main {
-                    tasks
-                } else {
-                    tasks.into_iter().filter(|t| t.group_folder == group.folder).collect()
-                };
-                serde_json::to_string(&filtered).unwrap_or_else(|_| "[]".into())
-            }
-            Err(e) => {
-                warn!(err = %e, "failed to load tasks for snapshot");
-                "[]".into()
-            }
-        };
+        let tasks = pool.get_all_tasks().await.unwrap_or_else(|e| {
+            warn!(err = %e, "failed to load tasks for snapshot");
+            Vec::new()
+        });
+        let visible: Vec<_> = tasks
+            .iter()
+            .filter(|t| is_main || t.group_folder == group.folder)
+            .collect();
+        let tasks_json = serde_json::to_string(&visible).unwrap_or_else(|_| "[]".into());
         let groups_json = {
             let g = groups.read().await;
             let entries: Vec<_> = g.values().map(|rg| serde_json::json!({
@@ -208,6 +257,20 @@ async fn process_group_messages(
             serde_json::to_string(&entries).unwrap_or_else(|_| "[]".into())
         };
         write_snapshots(&run_config.data_dir, &group.folder, is_main, &tasks_json, &groups_json).await;
+
+        let context = GroupContext::build(
+            &ContextInputs {
+                group: &group,
+                container_config: group_info.container_config.as_ref(),
+                is_main,
+                runtime,
+                is_scheduled_task: false,
+                tasks: &tasks,
+            },
+            run_config,
+            chrono::Utc::now(),
+        );
+        write_group_context(&run_config.data_dir, &group.folder, &context).await;
     }
 
     // 6. Run container and collect output
@@ -220,9 +283,11 @@ async fn process_group_messages(
     let output_sent = Arc::new(std::sync::atomic::AtomicBool::new(false));
     let output_sent_cb = output_sent.clone();
 
-    let telegram_cb: Arc<TelegramBridge> = telegram.clone();
+    let channels_cb: Arc<ChannelRegistry> = channels.clone();
     let pool_cb = pool.clone();
-    let assistant_name_cb = assistant_name.to_string();
+    let correlation_id_cb = correlation_id.clone();
+    // The reply currently streaming as a draft, if drafts are enabled.
+    let draft: Arc<Mutex<Option<DraftReply>>> = Arc::new(Mutex::new(None));
 
     let on_output: Option<Arc<OutputCallback>> = Some(Arc::new(Box::new(
         move |output: ContainerOutput| {
@@ -230,10 +295,11 @@ async fn process_group_messages(
             let group_folder = group_folder.clone();
             let queue = queue_clone.clone();
             let chat_jid = chat_jid_owned.clone();
-            let telegram = telegram_cb.clone();
+            let channels = channels_cb.clone();
             let pool = pool_cb.clone();
-            let assistant_name = assistant_name_cb.clone();
             let output_sent = output_sent_cb.clone();
+            let run_correlation_id = correlation_id_cb.clone();
+            let draft = draft.clone();
 
             Box::pin(async move {
                 // Track session ID from container
@@ -246,32 +312,73 @@ async fn process_group_messages(
                     }
                 }
 
+                // Show streamed text as a draft until the result replaces it
+                let channel = channels.for_jid(&chat_jid);
+                if let (Some(channel), Some(StreamEvent::TextDelta { text: Some(delta) })) =
+                    (channel, &output.event)
+                    && let Some(interval) = channel.draft_interval()
+                {
+                    let mut draft = draft.lock().await;
+                    if draft.is_none() {
+                        let correlation_id = queue
+                            .correlation_id(&chat_jid)
+                            .await
+                            .unwrap_or(run_correlation_id.clone());
+                        *draft = Some(DraftReply::new(
+                            &chat_jid,
+                            interval,
+                            channel.max_chars(),
+                            queue.take_reply_to(&chat_jid).await,
+                            Some(correlation_id),
+                        ));
+                    }
+                    if let Some(draft) = draft.as_mut() {
+                        draft.on_delta(channel.as_ref(), delta).await;
+                    }
+                }
+
                 // Handle final result
                 if let Some(ref result_text) = output.result {
                     // Strip <internal>...</internal> blocks
                     let text = strip_internal_blocks(result_text);
+                    // The next response starts a draft of its own.
+                    let draft = draft.lock().await.take();
                     if !text.is_empty() {
-                        // Send via Telegram
-                        if let Err(e) = telegram
-                            .send_text_to_jid(&chat_jid, &text)
+                        // Follow-ups piped into the container move the id on.
+                        let correlation_id = queue
+                            .correlation_id(&chat_jid)
                             .await
-                        {
-                            error!(err = %e, "failed to send agent output via Telegram");
-                        }
-
-                        // Store bot response in Postgres
-                        let bot_msg = intercom_core::NewMessage {
-                            id: format!("bot-{}", chrono::Utc::now().timestamp_millis()),
-                            chat_jid: chat_jid.clone(),
-                            sender: "bot".into(),
-                            sender_name: assistant_name.clone(),
-                            content: text,
-                            timestamp: chrono::Utc::now().to_rfc3339(),
-                            is_from_me: true,
-                            is_bot_message: true,
+                            .unwrap_or(run_correlation_id);
+
+                        // Send to the chat, threaded under the question (or
+                        // finish the draft that already is). Bridges store
+                        // what they send as bot messages.
+                        let sent = match (draft, channel) {
+                            (Some(draft), Some(channel)) => {
+                                let finished = draft.finish(channel.as_ref(), &text).await;
+                                if let Err(e) = &finished {
+                                    health::record_error(channel.name(), e);
+                                }
+                                finished
+                            }
+                            _ => {
+                                let reply_to = queue.take_reply_to(&chat_jid).await;
+                                channels
+                                    .send(&OutboundMessage {
+                                        jid: chat_jid.clone(),
+                                        text: text.clone(),
+                                        reply_to,
+                                        correlation_id: Some(correlation_id.clone()),
+                                    })
+                                    .await
+                                    .map(|_| ())
+                            }
                         };
-                        if let Err(e) = pool.store_message(&bot_msg).await {
-                            warn!(err = %e, "failed to store bot response");
+                        match sent {
+                            Ok(()) => info!(correlation_id, "agent output sent"),
+                            Err(e) => {
+                                error!(correlation_id, err = %e, "failed to send agent output")
+                            }
                         }
 
                         output_sent.store(true, std::sync::atomic::Ordering::SeqCst);
@@ -286,15 +393,49 @@ async fn process_group_messages(
         },
     )));
 
+    let run_start = Instant::now();
+    let run_id = workspace_git::new_run_id();
+    workspace_git::snapshot_run(run_config, &group.folder, &run_id, RunPhase::Before, None).await;
+    webhooks::dispatch(
+        webhook.as_ref(),
+        LifecycleEvent::new(LifecycleEventKind::RunStarted, &group.folder, chat_jid)
+            .with_correlation_id(&correlation_id),
+    );
+
+    let started_at = chrono::Utc::now();
     let result = run_container_agent(
         &group_info,
         &input,
         runtime,
         is_main,
+        "message",
         run_config,
         on_output,
     )
     .await;
+    record_run(pool, "message", &input, started_at, &result).await;
+    workspace_git::snapshot_run(run_config, &group.folder, &run_id, RunPhase::After, None).await;
+
+    let run_error = match &result {
+        Ok(r) if r.output.status == ContainerStatus::Error => Some(
+            r.output
+                .error
+                .clone()
+                .unwrap_or_else(|| "Unknown error".into()),
+        ),
+        Ok(_) => None,
+        Err(e) => Some(e.to_string()),
+    };
+    webhooks::dispatch(
+        webhook.as_ref(),
+        LifecycleEvent::new(LifecycleEventKind::RunFinished, &group.folder, chat_jid)
+            .with_correlation_id(&correlation_id)
+            .with_outcome(
+                run_start.elapsed().as_millis() as i64,
+                None,
+                run_error.as_deref(),
+            ),
+    );
 
     // 7. Handle result
     match result {
@@ -335,6 +476,7 @@ async fn process_group_messages(
         }
         Err(e) => {
             error!(group = group.name.as_str(), err = %e, "container agent error");
+            health::record_error(health::SUBSYSTEM_DOCKER, &e);
 
             if output_sent.load(std::sync::atomic::Ordering::SeqCst) {
                 warn!(
@@ -364,59 +506,10 @@ pub(crate) fn resolve_runtime(group: &RegisteredGroup) -> RuntimeKind {
     }
 }
 
-/// Strip `<internal>...</internal>` blocks from agent output.
-fn strip_internal_blocks(text: &str) -> String {
-    // Simple regex-free approach: find and remove <internal>...</internal> spans
-    let mut result = String::with_capacity(text.len());
-    let mut rest = text;
-
-    while let Some(start) = rest.find("<internal>") {
-        result.push_str(&rest[..start]);
-        if let Some(end) = rest[start..].find("</internal>") {
-            rest = &rest[start + end + "</internal>".len()..];
-        } else {
-            // Unclosed tag — strip to end
-            rest = "";
-            break;
-        }
-    }
-    result.push_str(rest);
-    result.trim().to_string()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn strip_internal_basic() {
-        let input = "Hello <internal>reasoning here</internal> World";
-        assert_eq!(strip_internal_blocks(input), "Hello  World");
-    }
-
-    #[test]
-    fn strip_internal_multiple() {
-        let input = "A <internal>x</internal> B <internal>y</internal> C";
-        assert_eq!(strip_internal_blocks(input), "A  B  C");
-    }
-
-    #[test]
-    fn strip_internal_none() {
-        assert_eq!(strip_internal_blocks("Hello World"), "Hello World");
-    }
-
-    #[test]
-    fn strip_internal_unclosed() {
-        let input = "Hello <internal>never closed";
-        assert_eq!(strip_internal_blocks(input), "Hello");
-    }
-
-    #[test]
-    fn strip_internal_multiline() {
-        let input = "Before\n<internal>\nmulti\nline\n</internal>\nAfter";
-        assert_eq!(strip_internal_blocks(input), "Before\n\nAfter");
-    }
-
     #[test]
     fn resolve_runtime_defaults_to_claude() {
         let group = RegisteredGroup {
@@ -429,6 +522,8 @@ mod tests {
             requires_trigger: None,
             runtime: None,
             model: None,
+            muted_until: None,
+            locale: None,
         };
         assert_eq!(resolve_runtime(&group), RuntimeKind::Claude);
     }
@@ -445,6 +540,8 @@ mod tests {
             requires_trigger: None,
             runtime: Some("gemini".into()),
             model: None,
+            muted_until: None,
+            locale: None,
         };
         assert_eq!(resolve_runtime(&group), RuntimeKind::Gemini);
     }
@@ -4,49 +4,236 @@
 //! at a time, with a global limit on total concurrent containers.
 //!
 //! Key semantics:
-//! - Tasks drain before messages (priority ordering)
-//! - Follow-up messages piped to active containers via IPC `input/` directory
+//! - When a slot frees, waiting groups go by priority class: the main group,
+//!   then groups with messages, then groups with only scheduled tasks
+//! - Within a class groups take turns (fair queuing on runs started), so a
+//!   chatty group cannot starve the rest at the concurrency cap
+//! - A group's messages run before its tasks; a container idling on stdin is
+//!   closed as soon as a task is queued behind it, and a busy one once the
+//!   task has waited out the preemption policy
+//! - Per-group limits from `containerConfig`: `maxConcurrent` lets a group
+//!   run tasks alongside its one message container, and `maxRunsPerHour`
+//!   holds further work back until the trailing hour has room
+//! - Follow-up messages piped to active containers via their open stdin
+//!   (`stdin_keepalive` runtimes) or the IPC `input/` directory
 //! - Exponential retry backoff on message processing failure
+//! - Message checks not started by their deadline are dropped and the
+//!   chat notified; `cancel` drops a group's queued work (`/reset`)
+//! - Pause stops new containers from starting while running ones finish;
+//!   drain pauses and then waits for the running ones, for safe deploys
 //! - Graceful shutdown: containers are detached (not killed)
+//! - Wait and run times, retries and rejected work are kept as histograms
+//!   and counters for `GET /metrics`
+//! - Queued and running work is journaled to `data/queue-journal.json` and
+//!   restored on the next start, so a restart loses no triggered run
 
-use std::collections::{HashMap, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use tokio::sync::Mutex;
-use tracing::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, watch};
+use tracing::{Instrument, debug, error, info, info_span, warn};
+
+use crate::container::executor::{ContainerExecutor, StopOutcome};
+use crate::container::security::ContainerConfig;
+use crate::container::stdin_channel::{FollowUp, StdinChannels};
+use crate::metrics::{COUNT_BUCKETS, DURATION_BUCKETS, Exposition, Histogram};
 
 const MAX_RETRIES: u32 = 5;
 const BASE_RETRY_MS: u64 = 5000;
 
+/// Queue intents that outlive a restart, under the queue's `data_dir`.
+pub const JOURNAL_FILE: &str = "queue-journal.json";
+
+/// Window `max_runs_per_hour` counts over.
+const RATE_WINDOW: Duration = Duration::from_secs(3600);
+
+/// How long a task waits behind its group's busy message container
+/// before the container is made to give up the slot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PreemptionPolicy {
+    /// Ask the container to wind down (close its stdin) once a task has
+    /// waited this long.
+    pub wind_down_after: Option<Duration>,
+    /// Stop the container outright once a critical task has waited this
+    /// long.
+    pub hard_stop_after: Option<Duration>,
+}
+
+/// A group's own limits, from its `containerConfig`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GroupLimits {
+    /// Containers the group may run at once; 0 and 1 both mean one. Only
+    /// tasks use the slots past the first.
+    pub max_concurrent: usize,
+    /// Runs the group may start per hour; 0 is unlimited.
+    pub max_runs_per_hour: u32,
+}
+
+impl GroupLimits {
+    pub fn from_config(config: Option<&ContainerConfig>) -> Self {
+        Self {
+            max_concurrent: config.and_then(|c| c.max_concurrent).unwrap_or(1),
+            max_runs_per_hour: config.and_then(|c| c.max_runs_per_hour).unwrap_or(0),
+        }
+    }
+}
+
 /// Callback for processing messages for a group. Returns true on success.
 pub type ProcessMessagesFn =
     Arc<dyn Fn(String) -> Pin<Box<dyn Future<Output = bool> + Send>> + Send + Sync>;
 
+/// Callback told a group's message check passed its deadline unstarted,
+/// with how long it waited.
+pub type ExpiredFn =
+    Arc<dyn Fn(String, Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
 /// Callback for running a queued task.
 pub type TaskFn = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
 
+/// Priority classes for waiting groups, lowest first. A higher class
+/// always goes first; within a class groups take turns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PriorityClass {
+    Task,
+    Interactive,
+    Main,
+}
+
+/// Work started under the lock, to spawn once it is released.
+enum Dispatch {
+    Messages(String),
+    Task(String, QueuedTask),
+}
+
+/// What a drain started, expired and preempted, and when to drain again:
+/// for groups held back by their hourly limit, the next deadline or the
+/// next preemption.
+#[derive(Default)]
+struct Drained {
+    runs: Vec<Dispatch>,
+    /// Groups whose message check passed its deadline, and how long it
+    /// waited.
+    expired: Vec<(String, Duration)>,
+    /// Containers to stop for critical tasks.
+    stops: Vec<String>,
+    wake_at: Option<Instant>,
+}
+
+/// Queued work `cancel` dropped for a group.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cancelled {
+    pub messages: bool,
+    pub tasks: Vec<String>,
+}
+
+impl Cancelled {
+    pub fn is_empty(&self) -> bool {
+        !self.messages && self.tasks.is_empty()
+    }
+}
+
 /// A queued task waiting for execution.
 struct QueuedTask {
     id: String,
     #[allow(dead_code)]
     group_jid: String,
+    queued_at: Instant,
+    /// May hard-stop the container it waits behind.
+    critical: bool,
     task_fn: TaskFn,
 }
 
+/// What the queue has seen since startup, for sizing
+/// `max_concurrent_containers`.
+#[derive(Debug, Clone)]
+struct QueueMetrics {
+    /// Enqueue to start, in seconds.
+    message_wait: Histogram,
+    task_wait: Histogram,
+    /// Start to finish, in seconds.
+    message_run: Histogram,
+    task_run: Histogram,
+    /// Retries a message batch took, counted when it succeeds or gives up.
+    retries: Histogram,
+    /// Work refused or dropped unstarted, by reason.
+    rejected: BTreeMap<&'static str, u64>,
+}
+
+impl Default for QueueMetrics {
+    fn default() -> Self {
+        Self {
+            message_wait: Histogram::new(DURATION_BUCKETS),
+            task_wait: Histogram::new(DURATION_BUCKETS),
+            message_run: Histogram::new(DURATION_BUCKETS),
+            task_run: Histogram::new(DURATION_BUCKETS),
+            retries: Histogram::new(COUNT_BUCKETS),
+            rejected: BTreeMap::new(),
+        }
+    }
+}
+
+impl QueueMetrics {
+    fn reject(&mut self, reason: &'static str) {
+        *self.rejected.entry(reason).or_default() += 1;
+    }
+}
+
 /// Per-group state tracked by the queue.
 #[derive(Default)]
 struct GroupState {
     active: bool,
     idle_waiting: bool,
+    /// The active container was asked to wind down for a waiting task.
+    wound_down: bool,
+    /// The active container was stopped for a critical task.
+    hard_stopped: bool,
     is_task_container: bool,
     pending_messages: bool,
+    /// When `pending_messages` was first set; cleared when the group runs.
+    pending_since: Option<Instant>,
+    /// When pending messages not yet started are dropped.
+    pending_deadline: Option<Instant>,
     pending_tasks: VecDeque<QueuedTask>,
+    /// Task the active container is running, if it is a task container.
+    running_task: Option<String>,
+    /// Tasks running in slots past the first (`maxConcurrent` > 1).
+    extra_tasks: Vec<String>,
+    /// When recent runs started, for `maxRunsPerHour`.
+    run_starts: VecDeque<Instant>,
+    /// When the container in the first slot started.
+    active_since: Option<Instant>,
     container_name: Option<String>,
     group_folder: Option<String>,
+    /// Correlation id of the messages the active container is answering.
+    correlation_id: Option<String>,
+    /// HTTP request that last queued messages; cleared once a run handles them.
+    request_id: Option<String>,
+    /// Message the container's next output should reply to.
+    reply_to: Option<String>,
     retry_count: u32,
+    /// Bumped by `cancel`, so a retry scheduled before it does not fire.
+    cancel_epoch: u64,
+    /// Fair-queuing tag: where the group's last run put it on the clock.
+    finish_tag: u64,
+}
+
+impl GroupState {
+    fn has_work(&self) -> bool {
+        self.pending_messages || !self.pending_tasks.is_empty()
+    }
+
+    /// Whether the task is queued or running in one of the group's slots.
+    fn holds_task(&self, task_id: &str) -> bool {
+        self.running_task.as_deref() == Some(task_id)
+            || self.extra_tasks.iter().any(|id| id == task_id)
+            || self.pending_tasks.iter().any(|t| t.id == task_id)
+    }
 }
 
 /// Shared inner state behind a mutex.
@@ -56,35 +243,559 @@ struct Inner {
     max_concurrent: usize,
     waiting_groups: VecDeque<String>,
     process_messages_fn: Option<ProcessMessagesFn>,
+    expired_fn: Option<ExpiredFn>,
+    /// Deadline for message checks enqueued without their own.
+    message_deadline: Option<Duration>,
+    /// No new containers start; queued work waits for `resume`.
+    paused: bool,
     shutting_down: bool,
     data_dir: PathBuf,
+    /// What the journal file last held.
+    journaled: QueueJournal,
+    /// The main group, whose work goes ahead of every other group's.
+    main_jid: Option<String>,
+    /// Fair-queuing clock: the start tag of the last run dispatched.
+    virtual_time: u64,
+    limits: HashMap<String, GroupLimits>,
+    rate_window: Duration,
+    /// When the pending rate-limit wake-up fires, if one is scheduled.
+    wake_at: Option<Instant>,
+    /// `active_count`, published for `drain` to wait on.
+    active_tx: watch::Sender<usize>,
+    metrics: QueueMetrics,
+    stdin: StdinChannels,
+    preemption: PreemptionPolicy,
+    /// Stops containers for `hard_stop_after`, with the grace it gets.
+    executor: Option<(Arc<dyn ContainerExecutor>, Duration)>,
+}
+
+/// Work the queue owes: groups with messages to process and tasks to run,
+/// queued or running, in the order they would go.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueJournal {
+    pub messages: Vec<String>,
+    pub tasks: Vec<JournaledTask>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournaledTask {
+    pub group_jid: String,
+    pub task_id: String,
+}
+
+impl QueueJournal {
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty() && self.tasks.is_empty()
+    }
+}
+
+/// Read the journal a previous run left, before anything is enqueued.
+/// A missing or unreadable journal is empty.
+pub fn load_journal(data_dir: &Path) -> QueueJournal {
+    let path = data_dir.join(JOURNAL_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!(path = %path.display(), err = %e, "unreadable queue journal, ignoring");
+            QueueJournal::default()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => QueueJournal::default(),
+        Err(e) => {
+            warn!(path = %path.display(), err = %e, "failed to read queue journal");
+            QueueJournal::default()
+        }
+    }
 }
 
 impl Inner {
     fn get_or_insert(&mut self, jid: &str) -> &mut GroupState {
-        self.groups
-            .entry(jid.to_string())
-            .or_insert_with(GroupState::default)
+        self.groups.entry(jid.to_string()).or_default()
+    }
+
+    /// Note messages to process, to be started within `deadline` if given.
+    /// The earliest deadline among the checks a run will answer holds.
+    fn mark_pending_messages(&mut self, jid: &str, deadline: Option<Duration>) {
+        let state = self.get_or_insert(jid);
+        let now = Instant::now();
+        state.pending_messages = true;
+        state.pending_since.get_or_insert(now);
+        if let Some(deadline) = deadline {
+            let at = now + deadline;
+            state.pending_deadline =
+                Some(state.pending_deadline.map_or(at, |current| current.min(at)));
+        }
+    }
+
+    /// Drop message checks whose deadline has passed.
+    fn expire(&mut self, now: Instant) -> Vec<(String, Duration)> {
+        let mut expired = Vec::new();
+        for (jid, state) in &mut self.groups {
+            if !state.pending_messages
+                || state.pending_deadline.is_none_or(|deadline| deadline > now)
+            {
+                continue;
+            }
+            let waited = state
+                .pending_since
+                .map(|since| now.duration_since(since))
+                .unwrap_or_default();
+            state.pending_messages = false;
+            state.pending_since = None;
+            state.pending_deadline = None;
+            warn!(
+                group_jid = jid.as_str(),
+                waited_secs = waited.as_secs(),
+                "message check passed its deadline, dropped"
+            );
+            expired.push((jid.clone(), waited));
+        }
+        for _ in &expired {
+            self.metrics.reject("deadline");
+        }
+        for (jid, _) in &expired {
+            if !self
+                .groups
+                .get(jid)
+                .is_some_and(|state| self.can_start(jid, state))
+            {
+                self.waiting_groups.retain(|w| w != jid);
+            }
+        }
+        expired
+    }
+
+    fn status(&self, jid: &str, now: Instant) -> GroupQueueStatus {
+        let waiting_position = self
+            .waiting_groups
+            .iter()
+            .position(|w| w == jid)
+            .map(|i| i + 1);
+        let Some(state) = self.groups.get(jid) else {
+            return GroupQueueStatus {
+                group_jid: jid.to_string(),
+                waiting_position,
+                ..Default::default()
+            };
+        };
+        GroupQueueStatus {
+            group_jid: jid.to_string(),
+            active: state.active,
+            idle_waiting: state.idle_waiting,
+            is_task_container: state.is_task_container,
+            container_name: state.container_name.clone(),
+            group_folder: state.group_folder.clone(),
+            uptime: state.active_since.map(|since| now.duration_since(since)),
+            running_tasks: state
+                .running_task
+                .iter()
+                .chain(&state.extra_tasks)
+                .cloned()
+                .collect(),
+            pending_messages: state.pending_messages,
+            pending_for: state.pending_since.map(|since| now.duration_since(since)),
+            pending_tasks: state.pending_tasks.iter().map(|t| t.id.clone()).collect(),
+            waiting_position,
+            retry_count: state.retry_count,
+        }
     }
 
     fn reset_group(&mut self, jid: &str) {
         if let Some(state) = self.groups.get_mut(jid) {
             state.active = false;
+            state.wound_down = false;
+            state.hard_stopped = false;
             state.is_task_container = false;
+            state.running_task = None;
+            state.active_since = None;
             state.container_name = None;
             state.group_folder = None;
+            state.correlation_id = None;
+            state.reply_to = None;
+        }
+        self.active_count = self.active_count.saturating_sub(1);
+    }
+
+    fn class(&self, jid: &str, state: &GroupState) -> PriorityClass {
+        if self.main_jid.as_deref() == Some(jid) {
+            PriorityClass::Main
+        } else if state.pending_messages {
+            PriorityClass::Interactive
+        } else {
+            PriorityClass::Task
+        }
+    }
+
+    /// Wind down or stop busy message containers that tasks have waited
+    /// behind past the policy. Returns the containers to stop and when the
+    /// next preemption falls due.
+    fn preempt(&mut self, now: Instant) -> (Vec<String>, Option<Instant>) {
+        let policy = self.preemption;
+        let mut stops = Vec::new();
+        let mut next: Option<Instant> = None;
+        let due = |at: Instant, next: &mut Option<Instant>| {
+            if at > now {
+                *next = Some(next.map_or(at, |n| n.min(at)));
+            }
+            at <= now
+        };
+        for (jid, state) in &mut self.groups {
+            if !state.active || state.is_task_container {
+                continue;
+            }
+            let Some(oldest) = state.pending_tasks.iter().map(|t| t.queued_at).min() else {
+                continue;
+            };
+            if let Some(after) = policy.wind_down_after.filter(|_| !state.wound_down) {
+                if due(oldest + after, &mut next) {
+                    info!(
+                        group_jid = jid.as_str(),
+                        "task waited behind a busy container, asking it to wind down"
+                    );
+                    state.wound_down = true;
+                    close_container(
+                        &self.stdin,
+                        &self.data_dir,
+                        jid,
+                        state.group_folder.as_deref(),
+                    );
+                }
+            }
+            let critical = state
+                .pending_tasks
+                .iter()
+                .filter(|t| t.critical)
+                .map(|t| t.queued_at)
+                .min();
+            if let (Some(after), Some(oldest), false) =
+                (policy.hard_stop_after, critical, state.hard_stopped)
+            {
+                if due(oldest + after, &mut next) {
+                    if let Some(name) = state.container_name.clone() {
+                        warn!(
+                            group_jid = jid.as_str(),
+                            container = name.as_str(),
+                            "critical task overdue, stopping container"
+                        );
+                        state.hard_stopped = true;
+                        stops.push(name);
+                    }
+                }
+            }
+        }
+        (stops, next)
+    }
+
+    /// Put a group in line for a slot.
+    fn wait(&mut self, jid: &str) {
+        if !self.waiting_groups.iter().any(|w| w == jid) {
+            self.waiting_groups.push_back(jid.to_string());
+        }
+    }
+
+    fn limits(&self, jid: &str) -> GroupLimits {
+        self.limits.get(jid).copied().unwrap_or_default()
+    }
+
+    /// Whether the group has work it has a slot for. Its first slot runs
+    /// messages or a task; further slots only run tasks, and a running task
+    /// gives up the first slot when messages arrive.
+    fn can_start(&self, jid: &str, state: &GroupState) -> bool {
+        if !state.active {
+            return state.has_work();
+        }
+        let slots = self.limits(jid).max_concurrent.max(1);
+        if 1 + state.extra_tasks.len() >= slots {
+            return false;
+        }
+        (state.is_task_container && state.pending_messages) || !state.pending_tasks.is_empty()
+    }
+
+    /// When the group's hourly limit next has room, if it is full now.
+    fn rate_limited_until(&self, jid: &str, state: &GroupState) -> Option<Instant> {
+        let limit = self.limits(jid).max_runs_per_hour as usize;
+        if limit == 0 || state.run_starts.len() < limit {
+            return None;
+        }
+        state
+            .run_starts
+            .front()
+            .map(|start| *start + self.rate_window)
+    }
+
+    /// The waiting group to run next: highest class, then the earliest
+    /// fair-queuing tag, then the longest in line.
+    fn next_waiting(&self) -> Option<String> {
+        self.waiting_groups
+            .iter()
+            .enumerate()
+            .filter_map(|(position, jid)| {
+                let state = self.groups.get(jid)?;
+                (self.can_start(jid, state) && self.rate_limited_until(jid, state).is_none()).then(
+                    || {
+                        let tag = state.finish_tag.max(self.virtual_time);
+                        (Reverse(self.class(jid, state)), tag, position, jid)
+                    },
+                )
+            })
+            .min()
+            .map(|(.., jid)| jid.clone())
+    }
+
+    /// Mark the group's next piece of work running: its messages, else
+    /// its first task. A run moves the group one step on the clock, so
+    /// groups that just ran queue behind those that have not.
+    fn start_next(&mut self, jid: &str) -> Option<Dispatch> {
+        let virtual_time = self.virtual_time;
+        let state = self.groups.get_mut(jid)?;
+        if state.active && state.is_task_container && state.pending_messages {
+            // The running task moves to an extra slot; messages take the first
+            state.extra_tasks.extend(state.running_task.take());
+            state.active = false;
+        }
+        let now = Instant::now();
+        let dispatch = if state.active {
+            let task = state.pending_tasks.pop_front()?;
+            state.extra_tasks.push(task.id.clone());
+            self.metrics
+                .task_wait
+                .observe(now.duration_since(task.queued_at).as_secs_f64());
+            Dispatch::Task(jid.to_string(), task)
+        } else {
+            let dispatch = if state.pending_messages {
+                state.is_task_container = false;
+                state.pending_messages = false;
+                let waited = state
+                    .pending_since
+                    .take()
+                    .map(|since| now.duration_since(since));
+                self.metrics
+                    .message_wait
+                    .observe(waited.unwrap_or_default().as_secs_f64());
+                state.pending_deadline = None;
+                Dispatch::Messages(jid.to_string())
+            } else {
+                let task = state.pending_tasks.pop_front()?;
+                state.is_task_container = true;
+                state.running_task = Some(task.id.clone());
+                self.metrics
+                    .task_wait
+                    .observe(now.duration_since(task.queued_at).as_secs_f64());
+                Dispatch::Task(jid.to_string(), task)
+            };
+            state.active = true;
+            state.active_since = Some(now);
+            state.idle_waiting = false;
+            dispatch
+        };
+        state.run_starts.push_back(now);
+        let start = state.finish_tag.max(virtual_time);
+        state.finish_tag = start + 1;
+        self.virtual_time = start;
+        let still_waiting = self.can_start(jid, &self.groups[jid]);
+        if !still_waiting {
+            self.waiting_groups.retain(|w| w != jid);
+        }
+        self.active_count += 1;
+        Some(dispatch)
+    }
+
+    /// Fill free slots from the waiting groups, and note when a group held
+    /// back by its hourly limit gets room again.
+    fn drain(&mut self) -> Drained {
+        let now = Instant::now();
+        let window = self.rate_window;
+        for state in self.groups.values_mut() {
+            while state
+                .run_starts
+                .front()
+                .is_some_and(|start| now.duration_since(*start) >= window)
+            {
+                state.run_starts.pop_front();
+            }
+        }
+        let mut drained = Drained {
+            expired: self.expire(now),
+            ..Drained::default()
+        };
+        let mut preempt_at = None;
+        if !self.paused && !self.shutting_down {
+            (drained.stops, preempt_at) = self.preempt(now);
+        }
+        while !self.shutting_down && !self.paused && self.active_count < self.max_concurrent {
+            let Some(jid) = self.next_waiting() else {
+                break;
+            };
+            drained.runs.extend(self.start_next(&jid));
+        }
+        let reopens = self
+            .waiting_groups
+            .iter()
+            .filter_map(|jid| {
+                let state = self.groups.get(jid)?;
+                if !self.can_start(jid, state) {
+                    return None;
+                }
+                let until = self.rate_limited_until(jid, state)?;
+                debug!(
+                    group_jid = jid.as_str(),
+                    "hourly run limit reached, work waits"
+                );
+                Some(until)
+            })
+            .min()
+            .filter(|_| !self.paused);
+        let deadline = self
+            .groups
+            .values()
+            .filter(|state| state.pending_messages)
+            .filter_map(|state| state.pending_deadline)
+            .min();
+        if let Some(wake) = reopens.into_iter().chain(deadline).chain(preempt_at).min() {
+            if !self.shutting_down && self.wake_at.is_none_or(|scheduled| wake < scheduled) {
+                self.wake_at = Some(wake);
+                drained.wake_at = Some(wake);
+            }
+        }
+        self.persist();
+        self.active_tx.send_replace(self.active_count);
+        drained
+    }
+
+    /// A group's message run, or the task in its first slot, ended: queue
+    /// it again if work arrived meanwhile, and hand the slot on. Past
+    /// shutdown nothing starts and the journal keeps the run.
+    fn finish(&mut self, jid: &str) -> Drained {
+        self.reset_group(jid);
+        self.requeue(jid)
+    }
+
+    /// A task ended, in whichever slot it ran.
+    fn finish_task(&mut self, jid: &str, task_id: &str) -> Drained {
+        let extra = self
+            .groups
+            .get_mut(jid)
+            .and_then(|state| {
+                let position = state.extra_tasks.iter().position(|id| id == task_id)?;
+                Some(state.extra_tasks.remove(position))
+            })
+            .is_some();
+        if !extra {
+            return self.finish(jid);
         }
         self.active_count = self.active_count.saturating_sub(1);
+        self.requeue(jid)
+    }
+
+    fn requeue(&mut self, jid: &str) -> Drained {
+        self.active_tx.send_replace(self.active_count);
+        if self.shutting_down {
+            return Drained::default();
+        }
+        if self
+            .groups
+            .get(jid)
+            .is_some_and(|state| self.can_start(jid, state))
+        {
+            self.wait(jid);
+        }
+        self.drain()
+    }
+
+    /// The intents the queue holds now. Waiting groups come first, in
+    /// order; a running message or task container counts, since a restart
+    /// stops it before it finishes.
+    fn journal(&self) -> QueueJournal {
+        let mut jids: Vec<&String> = self.waiting_groups.iter().collect();
+        let mut rest: Vec<&String> = self
+            .groups
+            .keys()
+            .filter(|jid| !self.waiting_groups.contains(jid))
+            .collect();
+        rest.sort();
+        jids.extend(rest);
+
+        let mut journal = QueueJournal::default();
+        for jid in jids {
+            let Some(state) = self.groups.get(jid) else {
+                continue;
+            };
+            if state.pending_messages || (state.active && !state.is_task_container) {
+                journal.messages.push(jid.clone());
+            }
+            let running = state.running_task.iter().chain(&state.extra_tasks);
+            for task_id in running.chain(state.pending_tasks.iter().map(|t| &t.id)) {
+                journal.tasks.push(JournaledTask {
+                    group_jid: jid.clone(),
+                    task_id: task_id.clone(),
+                });
+            }
+        }
+        journal
+    }
+
+    /// Write the journal if the intents changed. Written to a temp file
+    /// and renamed, so a crash leaves the old or the new journal.
+    fn persist(&mut self) {
+        let journal = self.journal();
+        if journal == self.journaled {
+            return;
+        }
+        let path = self.data_dir.join(JOURNAL_FILE);
+        let temp_path = self.data_dir.join(format!("{JOURNAL_FILE}.tmp"));
+        let result = std::fs::create_dir_all(&self.data_dir)
+            .and_then(|()| {
+                std::fs::write(&temp_path, serde_json::to_vec(&journal).unwrap_or_default())
+            })
+            .and_then(|()| std::fs::rename(&temp_path, &path));
+        match result {
+            Ok(()) => self.journaled = journal,
+            Err(e) => error!(path = %path.display(), err = %e, "failed to write queue journal"),
+        }
     }
 }
 
+/// Point-in-time view of queue depth for health reporting.
+#[derive(Debug, Clone, Default)]
+pub struct QueueSnapshot {
+    pub active: usize,
+    pub max_concurrent: usize,
+    pub paused: bool,
+    pub waiting_groups: usize,
+    pub pending_tasks: usize,
+    pub interactive_wait: Duration,
+}
+
+/// One group's place in the queue, for the `/queue` and `/ps` commands.
+#[derive(Debug, Clone, Default)]
+pub struct GroupQueueStatus {
+    pub group_jid: String,
+    pub active: bool,
+    /// The container is waiting for more input rather than working.
+    pub idle_waiting: bool,
+    pub is_task_container: bool,
+    pub container_name: Option<String>,
+    pub group_folder: Option<String>,
+    /// How long the container in the first slot has been running.
+    pub uptime: Option<Duration>,
+    /// Tasks running in the group's slots.
+    pub running_tasks: Vec<String>,
+    pub pending_messages: bool,
+    /// How long pending messages have waited for a slot.
+    pub pending_for: Option<Duration>,
+    pub pending_tasks: Vec<String>,
+    /// Place among the groups waiting for a slot, from 1.
+    pub waiting_position: Option<usize>,
+    pub retry_count: u32,
+}
+
 /// Group queue managing per-group serialization and global concurrency.
 pub struct GroupQueue {
     inner: Arc<Mutex<Inner>>,
+    stdin: StdinChannels,
 }
 
 impl GroupQueue {
     pub fn new(max_concurrent: usize, data_dir: PathBuf) -> Self {
+        let stdin = StdinChannels::default();
         Self {
             inner: Arc::new(Mutex::new(Inner {
                 groups: HashMap::new(),
@@ -92,146 +803,211 @@ impl GroupQueue {
                 max_concurrent,
                 waiting_groups: VecDeque::new(),
                 process_messages_fn: None,
+                expired_fn: None,
+                message_deadline: None,
+                paused: false,
                 shutting_down: false,
                 data_dir,
+                journaled: QueueJournal::default(),
+                main_jid: None,
+                virtual_time: 0,
+                limits: HashMap::new(),
+                rate_window: RATE_WINDOW,
+                wake_at: None,
+                active_tx: watch::Sender::new(0),
+                metrics: QueueMetrics::default(),
+                stdin: stdin.clone(),
+                preemption: PreemptionPolicy::default(),
+                executor: None,
             })),
+            stdin,
         }
     }
 
+    /// Channels runs register their open stdin in; hand to `RunConfig`.
+    pub fn stdin_channels(&self) -> StdinChannels {
+        self.stdin.clone()
+    }
+
     /// Set the callback invoked to process messages for a group.
     pub async fn set_process_messages_fn(&self, f: ProcessMessagesFn) {
         self.inner.lock().await.process_messages_fn = Some(f);
     }
 
-    /// Enqueue a message check for a group.
+    /// Set the callback told when a message check is dropped at its
+    /// deadline.
+    pub async fn set_expired_fn(&self, f: ExpiredFn) {
+        self.inner.lock().await.expired_fn = Some(f);
+    }
+
+    /// Deadline for `enqueue_message_check`; `None` lets checks wait for a
+    /// slot indefinitely.
+    pub async fn set_message_deadline(&self, deadline: Option<Duration>) {
+        self.inner.lock().await.message_deadline = deadline;
+    }
+
+    /// Set when waiting tasks preempt a busy message container, and the
+    /// executor (with its stop grace) that hard-stops it.
+    pub async fn set_preemption(
+        &self,
+        policy: PreemptionPolicy,
+        executor: Arc<dyn ContainerExecutor>,
+        grace: Duration,
+    ) {
+        let mut inner = self.inner.lock().await;
+        inner.preemption = policy;
+        inner.executor = Some((executor, grace));
+    }
+
+    /// Name the main group, whose work goes ahead of every other group's.
+    pub async fn set_main_group(&self, group_jid: Option<&str>) {
+        self.inner.lock().await.main_jid = group_jid.map(str::to_string);
+    }
+
+    /// Set each group's own limits, by JID. Groups not listed get the
+    /// defaults.
+    pub async fn set_group_limits(&self, limits: HashMap<String, GroupLimits>) {
+        self.inner.lock().await.limits = limits;
+    }
+
+    /// Enqueue a message check for a group, under the queue's deadline.
     pub async fn enqueue_message_check(&self, group_jid: &str) {
-        let should_spawn = {
+        let deadline = self.inner.lock().await.message_deadline;
+        self.enqueue_message_check_within(group_jid, deadline).await;
+    }
+
+    /// Enqueue a message check that is dropped, and the chat notified, if
+    /// it has not started within `deadline`.
+    pub async fn enqueue_message_check_within(&self, group_jid: &str, deadline: Option<Duration>) {
+        let started = {
             let mut inner = self.inner.lock().await;
             if inner.shutting_down {
+                inner.metrics.reject("shutdown");
                 return;
             }
 
-            let state = inner.get_or_insert(group_jid);
-
-            if state.active {
-                state.pending_messages = true;
+            inner.mark_pending_messages(group_jid, deadline);
+            let request_id = crate::request_id::current()
+                .or_else(|| crate::request_id::take_for_chat(group_jid));
+            if let Some(request_id) = request_id {
+                inner.get_or_insert(group_jid).request_id = Some(request_id);
+            }
+            if !inner.can_start(group_jid, &inner.groups[group_jid]) {
                 debug!(group_jid, "container active, message queued");
-                return;
+            } else {
+                inner.wait(group_jid);
             }
-
-            if inner.active_count >= inner.max_concurrent {
-                let state = inner.get_or_insert(group_jid);
-                state.pending_messages = true;
-                let jid = group_jid.to_string();
-                if !inner.waiting_groups.contains(&jid) {
-                    inner.waiting_groups.push_back(jid);
-                }
+            // Drained either way, to arm the deadline's wake-up
+            let started = inner.drain();
+            if inner.waiting_groups.iter().any(|jid| jid == group_jid) {
                 debug!(
                     group_jid,
                     active_count = inner.active_count,
                     "at concurrency limit, message queued"
                 );
-                return;
             }
-
-            // Can run immediately
-            let state = inner.get_or_insert(group_jid);
-            state.active = true;
-            state.idle_waiting = false;
-            state.is_task_container = false;
-            state.pending_messages = false;
-            inner.active_count += 1;
-            true
+            started
         };
-
-        if should_spawn {
-            let queue = self.inner.clone();
-            let jid = group_jid.to_string();
-            tokio::spawn(async move {
-                run_for_group(queue, jid).await;
-            });
-        }
+        spawn_all(&self.inner, started);
     }
 
-    /// Enqueue a task for a group. Tasks have priority over messages.
+    /// Enqueue a task for a group. It runs after the group's messages.
     pub async fn enqueue_task(&self, group_jid: &str, task_id: &str, task_fn: TaskFn) {
-        let task_to_run = {
+        self.enqueue_task_as(group_jid, task_id, false, task_fn)
+            .await;
+    }
+
+    /// Enqueue a task; a `critical` one may hard-stop the busy container it
+    /// waits behind (`PreemptionPolicy::hard_stop_after`).
+    pub async fn enqueue_task_as(
+        &self,
+        group_jid: &str,
+        task_id: &str,
+        critical: bool,
+        task_fn: TaskFn,
+    ) {
+        let started = {
             let mut inner = self.inner.lock().await;
             if inner.shutting_down {
+                inner.metrics.reject("shutdown");
                 return;
             }
 
             let data_dir = inner.data_dir.clone();
             let state = inner.get_or_insert(group_jid);
 
-            // Deduplicate
-            if state.pending_tasks.iter().any(|t| t.id == task_id) {
+            // Deduplicate: the scheduler finds a task due until its run ends
+            if state.holds_task(task_id) {
                 debug!(group_jid, task_id, "task already queued, skipping");
+                inner.metrics.reject("duplicate");
                 return;
             }
+            state.pending_tasks.push_back(QueuedTask {
+                id: task_id.to_string(),
+                group_jid: group_jid.to_string(),
+                queued_at: Instant::now(),
+                critical,
+                task_fn,
+            });
 
-            if state.active {
-                let close_folder = if state.idle_waiting {
-                    state.group_folder.clone()
-                } else {
-                    None
-                };
-                state.pending_tasks.push_back(QueuedTask {
-                    id: task_id.to_string(),
-                    group_jid: group_jid.to_string(),
-                    task_fn,
-                });
-                if let Some(ref folder) = close_folder {
-                    write_close_sentinel(&data_dir, folder);
+            if !inner.can_start(group_jid, &inner.groups[group_jid]) {
+                let state = &inner.groups[group_jid];
+                if state.idle_waiting && !state.wound_down {
+                    close_container(
+                        &self.stdin,
+                        &data_dir,
+                        group_jid,
+                        state.group_folder.as_deref(),
+                    );
+                    inner.get_or_insert(group_jid).wound_down = true;
                 }
                 debug!(group_jid, task_id, "container active, task queued");
-                return;
+            } else {
+                inner.wait(group_jid);
             }
-
-            if inner.active_count >= inner.max_concurrent {
-                let state = inner.get_or_insert(group_jid);
-                state.pending_tasks.push_back(QueuedTask {
-                    id: task_id.to_string(),
-                    group_jid: group_jid.to_string(),
-                    task_fn,
-                });
-                let jid = group_jid.to_string();
-                if !inner.waiting_groups.contains(&jid) {
-                    inner.waiting_groups.push_back(jid);
-                }
+            // Drained either way, to arm the preemption wake-up
+            let started = inner.drain();
+            if inner.waiting_groups.iter().any(|jid| jid == group_jid) {
                 debug!(
                     group_jid,
                     task_id,
                     active_count = inner.active_count,
                     "at concurrency limit, task queued"
                 );
-                return;
             }
-
-            // Run immediately
-            let state = inner.get_or_insert(group_jid);
-            state.active = true;
-            state.idle_waiting = false;
-            state.is_task_container = true;
-            inner.active_count += 1;
-
-            Some(QueuedTask {
-                id: task_id.to_string(),
-                group_jid: group_jid.to_string(),
-                task_fn,
-            })
+            started
         };
+        spawn_all(&self.inner, started);
+    }
 
-        if let Some(task) = task_to_run {
-            let queue = self.inner.clone();
-            let jid = group_jid.to_string();
-            tokio::spawn(async move {
-                run_task(queue, jid, task).await;
-            });
+    /// Drop a group's queued message checks and tasks, and any pending
+    /// retry. A running container is left alone; see `kill_group`.
+    pub async fn cancel(&self, group_jid: &str) -> Cancelled {
+        let mut inner = self.inner.lock().await;
+        let Some(state) = inner.groups.get_mut(group_jid) else {
+            return Cancelled::default();
+        };
+        let cancelled = Cancelled {
+            messages: std::mem::take(&mut state.pending_messages),
+            tasks: state.pending_tasks.drain(..).map(|task| task.id).collect(),
+        };
+        state.pending_since = None;
+        state.pending_deadline = None;
+        state.retry_count = 0;
+        state.cancel_epoch += 1;
+        inner.waiting_groups.retain(|jid| jid != group_jid);
+        for _ in 0..cancelled.tasks.len() + usize::from(cancelled.messages) {
+            inner.metrics.reject("cancelled");
+        }
+        inner.persist();
+        if !cancelled.is_empty() {
+            info!(group_jid, messages = cancelled.messages, tasks = ?cancelled.tasks, "queued work cancelled");
         }
+        cancelled
     }
 
     /// Register a container process for a group.
+    #[allow(dead_code)] // ported from the Node host, which still registers its own containers
     pub async fn register_process(
         &self,
         group_jid: &str,
@@ -254,42 +1030,99 @@ impl GroupQueue {
         {
             let state = inner.get_or_insert(group_jid);
             state.idle_waiting = true;
-            has_tasks = !state.pending_tasks.is_empty();
+            has_tasks = !state.pending_tasks.is_empty() && !state.wound_down;
+            state.wound_down |= has_tasks;
             folder = state.group_folder.clone();
         }
         if has_tasks {
-            if let Some(ref f) = folder {
-                write_close_sentinel(&inner.data_dir, f);
-            }
+            close_container(&self.stdin, &inner.data_dir, group_jid, folder.as_deref());
         }
     }
 
-    /// Send a follow-up message to the active container via IPC input file.
-    pub async fn send_message(&self, group_jid: &str, text: &str) -> bool {
+    /// Record the correlation id of the run now active for a group.
+    pub async fn set_correlation_id(&self, group_jid: &str, correlation_id: &str) {
+        let mut inner = self.inner.lock().await;
+        inner.get_or_insert(group_jid).correlation_id = Some(correlation_id.to_string());
+    }
+
+    /// Correlation id of the messages the group's container is answering.
+    pub async fn correlation_id(&self, group_jid: &str) -> Option<String> {
+        let inner = self.inner.lock().await;
+        inner
+            .groups
+            .get(group_jid)
+            .and_then(|s| s.correlation_id.clone())
+    }
+
+    /// Set the message the group's next agent output replies to.
+    pub async fn set_reply_to(&self, group_jid: &str, message_id: Option<&str>) {
+        let mut inner = self.inner.lock().await;
+        inner.get_or_insert(group_jid).reply_to = message_id.map(str::to_string);
+    }
+
+    /// Take the reply target, so only the first output after a question is
+    /// threaded under it.
+    pub async fn take_reply_to(&self, group_jid: &str) -> Option<String> {
+        let mut inner = self.inner.lock().await;
+        inner
+            .groups
+            .get_mut(group_jid)
+            .and_then(|s| s.reply_to.take())
+    }
+
+    /// Send a follow-up message to the active container, over its stdin if
+    /// it keeps one open, else via IPC input file. Replies after this are
+    /// attributed to `correlation_id`.
+    pub async fn send_message(
+        &self,
+        group_jid: &str,
+        text: &str,
+        correlation_id: Option<&str>,
+    ) -> bool {
         let input_dir = {
-            let inner = self.inner.lock().await;
-            let state = match inner.groups.get(group_jid) {
+            let mut inner = self.inner.lock().await;
+            let data_dir = inner.data_dir.clone();
+            let state = match inner.groups.get_mut(group_jid) {
                 Some(s) => s,
                 None => return false,
             };
-            if !state.active || state.group_folder.is_none() || state.is_task_container {
+            if !state.active || state.is_task_container {
+                return false;
+            }
+            let piped = self.stdin.send(
+                group_jid,
+                FollowUp::Message {
+                    text: text.to_string(),
+                    correlation_id: correlation_id.map(str::to_string),
+                },
+            );
+            if !piped && state.group_folder.is_none() {
                 return false;
             }
+            if let Some(id) = correlation_id {
+                state.correlation_id = Some(id.to_string());
+            }
+            if piped {
+                return true;
+            }
             let folder = state.group_folder.as_ref().unwrap();
-            inner.data_dir.join("ipc").join(folder).join("input")
+            data_dir.join("ipc").join(folder).join("input")
         };
 
-        write_ipc_message(&input_dir, text)
+        write_ipc_message(&input_dir, text, correlation_id)
     }
 
-    /// Signal the active container to wind down via close sentinel.
+    /// Signal the active container to wind down via stdin or close sentinel.
     pub async fn close_stdin(&self, group_jid: &str) {
         let inner = self.inner.lock().await;
         if let Some(state) = inner.groups.get(group_jid) {
             if state.active {
-                if let Some(ref folder) = state.group_folder {
-                    write_close_sentinel(&inner.data_dir, folder);
-                }
+                close_container(
+                    &self.stdin,
+                    &inner.data_dir,
+                    group_jid,
+                    state.group_folder.as_deref(),
+                );
             }
         }
     }
@@ -304,47 +1137,97 @@ impl GroupQueue {
             .unwrap_or(false)
     }
 
-    /// Stop an active container via `docker stop`.
-    pub async fn kill_group(&self, group_jid: &str) -> bool {
+    /// Stop an active container through `executor`: SIGTERM, then kill
+    /// after `grace`. `None` when the group has no running container.
+    pub async fn kill_group(
+        &self,
+        group_jid: &str,
+        executor: &dyn ContainerExecutor,
+        grace: Duration,
+    ) -> Option<StopOutcome> {
         let container_name = {
             let inner = self.inner.lock().await;
             match inner.groups.get(group_jid) {
                 Some(s) if s.active && s.container_name.is_some() => {
                     s.container_name.clone().unwrap()
                 }
-                _ => return false,
+                _ => return None,
             }
         };
 
-        match tokio::process::Command::new("docker")
-            .args(["stop", &container_name])
-            .output()
-            .await
-        {
-            Ok(output) if output.status.success() => {
-                info!(
-                    group_jid,
-                    container = container_name.as_str(),
-                    "container stopped via kill_group"
-                );
-                true
-            }
-            Ok(_) => {
-                warn!(
-                    group_jid,
-                    container = container_name.as_str(),
-                    "failed to stop container"
-                );
-                false
-            }
-            Err(e) => {
-                error!(group_jid, container = container_name.as_str(), err = %e, "docker stop error");
-                false
+        let outcome = executor.stop(&container_name, grace).await;
+        match outcome {
+            StopOutcome::Stopped | StopOutcome::Killed => info!(
+                group_jid,
+                container = container_name.as_str(),
+                outcome = outcome.as_str(),
+                "container stopped via kill_group"
+            ),
+            StopOutcome::Failed => warn!(
+                group_jid,
+                container = container_name.as_str(),
+                "failed to stop container"
+            ),
+        }
+        Some(outcome)
+    }
+
+    /// Stop starting containers. Running ones finish, and work queued
+    /// meanwhile waits (and stays journaled) until `resume`.
+    pub async fn pause(&self) {
+        let mut inner = self.inner.lock().await;
+        if !inner.paused {
+            inner.paused = true;
+            info!(active_count = inner.active_count, "GroupQueue paused");
+        }
+    }
+
+    /// Start containers again, filling the free slots from the waiting
+    /// groups.
+    pub async fn resume(&self) {
+        let started = {
+            let mut inner = self.inner.lock().await;
+            if !inner.paused {
+                return;
             }
+            inner.paused = false;
+            info!(
+                waiting_groups = inner.waiting_groups.len(),
+                "GroupQueue resumed"
+            );
+            inner.drain()
+        };
+        spawn_all(&self.inner, started);
+    }
+
+    pub async fn is_paused(&self) -> bool {
+        self.inner.lock().await.paused
+    }
+
+    /// Pause, then wait up to `timeout` for the running containers to
+    /// finish. Returns the number still running, 0 once drained. The queue
+    /// stays paused either way.
+    pub async fn drain(&self, timeout: Duration) -> usize {
+        let mut active = {
+            let mut inner = self.inner.lock().await;
+            inner.paused = true;
+            info!(active_count = inner.active_count, "GroupQueue draining");
+            inner.active_tx.subscribe()
+        };
+        let drained = tokio::time::timeout(timeout, active.wait_for(|count| *count == 0))
+            .await
+            .is_ok();
+        let remaining = *active.borrow();
+        if drained {
+            info!("GroupQueue drained");
+        } else {
+            warn!(remaining, "GroupQueue drain timed out");
         }
+        remaining
     }
 
-    /// Graceful shutdown — mark as shutting down, detach containers.
+    /// Graceful shutdown — mark as shutting down, detach containers and
+    /// write the journal.
     pub async fn shutdown(&self) {
         let mut inner = self.inner.lock().await;
         inner.shutting_down = true;
@@ -366,6 +1249,170 @@ impl GroupQueue {
             detached_containers = ?active_containers,
             "GroupQueue shutting down (containers detached, not killed)"
         );
+        // Whatever is still queued or running starts again on the next run
+        inner.persist();
+    }
+
+    /// Longest time a group with pending messages has been waiting for a
+    /// container slot. Zero when no interactive work is queued.
+    pub async fn interactive_wait(&self) -> Duration {
+        let inner = self.inner.lock().await;
+        let now = Instant::now();
+        inner
+            .waiting_groups
+            .iter()
+            .filter_map(|jid| inner.groups.get(jid))
+            .filter(|s| s.pending_messages && !s.active)
+            .filter_map(|s| s.pending_since)
+            .map(|since| now.duration_since(since))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Whether a run of the task is queued or in progress, in any group.
+    pub async fn has_task(&self, task_id: &str) -> bool {
+        self.inner
+            .lock()
+            .await
+            .groups
+            .values()
+            .any(|state| state.holds_task(task_id))
+    }
+
+    pub async fn snapshot(&self) -> QueueSnapshot {
+        let interactive_wait = self.interactive_wait().await;
+        let inner = self.inner.lock().await;
+        QueueSnapshot {
+            active: inner.active_count,
+            max_concurrent: inner.max_concurrent,
+            paused: inner.paused,
+            waiting_groups: inner.waiting_groups.len(),
+            pending_tasks: inner.groups.values().map(|s| s.pending_tasks.len()).sum(),
+            interactive_wait,
+        }
+    }
+
+    /// Where the group stands in the queue. An unknown group is idle with
+    /// nothing pending.
+    pub async fn group_status(&self, group_jid: &str) -> GroupQueueStatus {
+        let inner = self.inner.lock().await;
+        inner.status(group_jid, Instant::now())
+    }
+
+    /// Groups with a container running, longest running first.
+    pub async fn active_groups(&self) -> Vec<GroupQueueStatus> {
+        let inner = self.inner.lock().await;
+        let now = Instant::now();
+        let mut active: Vec<_> = inner
+            .groups
+            .iter()
+            .filter(|(_, state)| state.active || !state.extra_tasks.is_empty())
+            .map(|(jid, _)| inner.status(jid, now))
+            .collect();
+        active.sort_by(|a, b| {
+            b.uptime
+                .cmp(&a.uptime)
+                .then_with(|| a.group_jid.cmp(&b.group_jid))
+        });
+        active
+    }
+
+    /// The queue's series in the Prometheus text format.
+    pub async fn render_metrics(&self) -> String {
+        let snapshot = self.snapshot().await;
+        let metrics = self.inner.lock().await.metrics.clone();
+        let mut out = Exposition::default();
+        out.family("intercom_queue_active", "gauge", "Containers running.")
+            .sample("intercom_queue_active", &[], snapshot.active as f64);
+        out.family(
+            "intercom_queue_max_concurrent",
+            "gauge",
+            "The max_concurrent_containers cap.",
+        )
+        .sample(
+            "intercom_queue_max_concurrent",
+            &[],
+            snapshot.max_concurrent as f64,
+        );
+        out.family(
+            "intercom_queue_waiting_groups",
+            "gauge",
+            "Groups waiting for a slot.",
+        )
+        .sample(
+            "intercom_queue_waiting_groups",
+            &[],
+            snapshot.waiting_groups as f64,
+        );
+        out.family(
+            "intercom_queue_pending_tasks",
+            "gauge",
+            "Tasks queued, not yet started.",
+        )
+        .sample(
+            "intercom_queue_pending_tasks",
+            &[],
+            snapshot.pending_tasks as f64,
+        );
+        out.family(
+            "intercom_queue_paused",
+            "gauge",
+            "1 while the queue is paused.",
+        )
+        .sample(
+            "intercom_queue_paused",
+            &[],
+            u8::from(snapshot.paused).into(),
+        );
+        out.family(
+            "intercom_queue_wait_seconds",
+            "histogram",
+            "Time from enqueue to start.",
+        )
+        .histogram(
+            "intercom_queue_wait_seconds",
+            &[("kind", "message")],
+            &metrics.message_wait,
+        )
+        .histogram(
+            "intercom_queue_wait_seconds",
+            &[("kind", "task")],
+            &metrics.task_wait,
+        );
+        out.family(
+            "intercom_queue_run_seconds",
+            "histogram",
+            "Time from start to finish.",
+        )
+        .histogram(
+            "intercom_queue_run_seconds",
+            &[("kind", "message")],
+            &metrics.message_run,
+        )
+        .histogram(
+            "intercom_queue_run_seconds",
+            &[("kind", "task")],
+            &metrics.task_run,
+        );
+        out.family(
+            "intercom_queue_retries",
+            "histogram",
+            "Retries a message batch took.",
+        )
+        .histogram("intercom_queue_retries", &[], &metrics.retries);
+        out.family(
+            "intercom_queue_rejected_total",
+            "counter",
+            "Work refused or dropped unstarted, by reason.",
+        );
+        for (reason, count) in &metrics.rejected {
+            out.sample(
+                "intercom_queue_rejected_total",
+                &[("reason", reason)],
+                *count as f64,
+            );
+        }
+        out.finish()
     }
 
     /// Get the current active container count.
@@ -384,13 +1431,25 @@ async fn run_for_group(queue: Arc<Mutex<Inner>>, group_jid: String) {
         "starting message processing for group"
     );
 
-    let process_fn = {
+    let (process_fn, request_id) = {
         let inner = queue.lock().await;
-        inner.process_messages_fn.clone()
+        let request_id = inner
+            .groups
+            .get(&group_jid)
+            .and_then(|s| s.request_id.clone());
+        (inner.process_messages_fn.clone(), request_id)
     };
+    // The run's spans nest under this one, so its logs carry the request
+    let span = info_span!("queue_run", group_jid = %group_jid, request_id = tracing::field::Empty);
+    if let Some(ref id) = request_id {
+        span.record("request_id", id.as_str());
+    }
 
+    let started_at = Instant::now();
     let success = if let Some(ref f) = process_fn {
-        f(group_jid.clone()).await
+        span.in_scope(|| f(group_jid.clone()))
+            .instrument(span.clone())
+            .await
     } else {
         warn!(
             group_jid = group_jid.as_str(),
@@ -400,10 +1459,19 @@ async fn run_for_group(queue: Arc<Mutex<Inner>>, group_jid: String) {
     };
 
     let mut inner = queue.lock().await;
+    inner
+        .metrics
+        .message_run
+        .observe(started_at.elapsed().as_secs_f64());
 
     if success {
         if let Some(state) = inner.groups.get_mut(&group_jid) {
-            state.retry_count = 0;
+            // Messages queued by a later request keep that request's id
+            if state.request_id == request_id {
+                state.request_id = None;
+            }
+            let retries = std::mem::take(&mut state.retry_count);
+            inner.metrics.retries.observe(retries as f64);
         }
     } else {
         let retry_count = inner
@@ -420,19 +1488,34 @@ async fn run_for_group(queue: Arc<Mutex<Inner>>, group_jid: String) {
             let delay_ms = BASE_RETRY_MS * 2u64.pow(retry_count - 1);
             info!(
                 group_jid = group_jid.as_str(),
-                retry_count,
-                delay_ms,
-                "scheduling retry with backoff"
+                retry_count, delay_ms, "scheduling retry with backoff"
             );
             let queue_clone = queue.clone();
             let jid_clone = group_jid.clone();
+            let epoch = inner
+                .groups
+                .get(&group_jid)
+                .map(|s| s.cancel_epoch)
+                .unwrap_or_default();
             tokio::spawn(async move {
                 tokio::time::sleep(std::time::Duration::from_millis(delay_ms)).await;
-                let mut inner = queue_clone.lock().await;
-                if !inner.shutting_down {
-                    let state = inner.get_or_insert(&jid_clone);
-                    state.pending_messages = true;
-                }
+                let started = {
+                    let mut inner = queue_clone.lock().await;
+                    let cancelled = inner
+                        .groups
+                        .get(&jid_clone)
+                        .is_some_and(|s| s.cancel_epoch != epoch);
+                    if inner.shutting_down || cancelled {
+                        return;
+                    }
+                    let deadline = inner.message_deadline;
+                    inner.mark_pending_messages(&jid_clone, deadline);
+                    if inner.can_start(&jid_clone, &inner.groups[&jid_clone]) {
+                        inner.wait(&jid_clone);
+                    }
+                    inner.drain()
+                };
+                spawn_all(&queue_clone, started);
             });
         } else {
             error!(
@@ -440,14 +1523,18 @@ async fn run_for_group(queue: Arc<Mutex<Inner>>, group_jid: String) {
                 retry_count,
                 "max retries exceeded, dropping (will retry on next incoming message)"
             );
+            inner.metrics.retries.observe(MAX_RETRIES as f64);
+            inner.metrics.reject("retries_exhausted");
             if let Some(state) = inner.groups.get_mut(&group_jid) {
                 state.retry_count = 0;
+                state.request_id = None;
             }
         }
     }
 
-    inner.reset_group(&group_jid);
-    // Drain is handled by the next poll cycle or enqueue call
+    let started = inner.finish(&group_jid);
+    drop(inner);
+    spawn_all(&queue, started);
 }
 
 async fn run_task(queue: Arc<Mutex<Inner>>, group_jid: String, task: QueuedTask) {
@@ -458,17 +1545,97 @@ async fn run_task(queue: Arc<Mutex<Inner>>, group_jid: String, task: QueuedTask)
     );
 
     // Execute the task
+    let started_at = Instant::now();
     (task.task_fn)().await;
 
-    let mut inner = queue.lock().await;
-    inner.reset_group(&group_jid);
+    let started = {
+        let mut inner = queue.lock().await;
+        inner
+            .metrics
+            .task_run
+            .observe(started_at.elapsed().as_secs_f64());
+        inner.finish_task(&group_jid, &task.id)
+    };
+    spawn_all(&queue, started);
+}
+
+/// Spawn the runs `drain` started, the wake-up it asked for, the notices
+/// for checks it expired and the stops it called for.
+fn spawn_all(queue: &Arc<Mutex<Inner>>, drained: Drained) {
+    if !drained.stops.is_empty() {
+        let queue = queue.clone();
+        let stops = drained.stops;
+        tokio::spawn(async move {
+            let Some((executor, grace)) = queue.lock().await.executor.clone() else {
+                return;
+            };
+            for name in stops {
+                let outcome = executor.stop(&name, grace).await;
+                info!(
+                    container = name.as_str(),
+                    outcome = outcome.as_str(),
+                    "container stopped for a critical task"
+                );
+            }
+        });
+    }
+    if !drained.expired.is_empty() {
+        let queue = queue.clone();
+        let expired = drained.expired;
+        tokio::spawn(async move {
+            let Some(on_expired) = queue.lock().await.expired_fn.clone() else {
+                return;
+            };
+            for (jid, waited) in expired {
+                on_expired(jid, waited).await;
+            }
+        });
+    }
+    if let Some(wake_at) = drained.wake_at {
+        let queue = queue.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep_until(wake_at.into()).await;
+            let drained = {
+                let mut inner = queue.lock().await;
+                if inner.wake_at == Some(wake_at) {
+                    inner.wake_at = None;
+                }
+                inner.drain()
+            };
+            spawn_all(&queue, drained);
+        });
+    }
+    for dispatch in drained.runs {
+        let queue = queue.clone();
+        let run: Pin<Box<dyn Future<Output = ()> + Send>> = match dispatch {
+            Dispatch::Messages(jid) => Box::pin(run_for_group(queue, jid)),
+            Dispatch::Task(jid, task) => Box::pin(run_task(queue, jid, task)),
+        };
+        tokio::spawn(run);
+    }
 }
 
 // ---------------------------------------------------------------------------
 // IPC helpers
 // ---------------------------------------------------------------------------
 
-fn write_ipc_message(input_dir: &Path, text: &str) -> bool {
+/// Ask a group's container to wind down: `close` on its stdin, else the
+/// `_close` sentinel.
+fn close_container(
+    stdin: &StdinChannels,
+    data_dir: &Path,
+    group_jid: &str,
+    group_folder: Option<&str>,
+) {
+    if stdin.send(group_jid, FollowUp::Close) {
+        return;
+    }
+    if let Some(folder) = group_folder {
+        write_close_sentinel(data_dir, folder);
+    }
+}
+
+fn write_ipc_message(input_dir: &Path, text: &str, correlation_id: Option<&str>) -> bool {
     if let Err(e) = std::fs::create_dir_all(input_dir) {
         error!(err = %e, "failed to create IPC input dir");
         return false;
@@ -481,7 +1648,10 @@ fn write_ipc_message(input_dir: &Path, text: &str) -> bool {
     let filepath = input_dir.join(&filename);
     let temp_path = input_dir.join(format!("{filename}.tmp"));
 
-    let content = serde_json::json!({"type": "message", "text": text});
+    let mut content = serde_json::json!({"type": "message", "text": text});
+    if let Some(id) = correlation_id {
+        content["correlationId"] = id.into();
+    }
     match std::fs::write(&temp_path, content.to_string()) {
         Ok(()) => match std::fs::rename(&temp_path, &filepath) {
             Ok(()) => true,
@@ -503,12 +1673,16 @@ fn write_close_sentinel(data_dir: &Path, group_folder: &str) {
     let _ = std::fs::write(input_dir.join("_close"), "");
 }
 
-/// Simple pseudo-random u16 for file name uniqueness.
+/// Simple pseudo-random u16 for file name uniqueness. A counter is mixed
+/// in so calls within one clock tick still differ.
 fn rand_u16() -> u16 {
+    static SEQ: std::sync::atomic::AtomicU16 = std::sync::atomic::AtomicU16::new(0);
     let t = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default();
-    (t.subsec_nanos() ^ (t.as_secs() as u32).wrapping_mul(2654435761)) as u16
+    let seq = SEQ.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    ((t.subsec_nanos() ^ (t.as_secs() as u32).wrapping_mul(2654435761)) as u16)
+        .wrapping_add(seq.wrapping_mul(40503))
 }
 
 #[cfg(test)]
@@ -536,10 +1710,576 @@ mod tests {
         assert!(!q.is_active("tg:12345").await);
     }
 
+    #[tokio::test]
+    async fn interactive_wait_tracks_groups_blocked_at_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let q = GroupQueue::new(0, dir.path().to_path_buf());
+        assert_eq!(q.interactive_wait().await, Duration::ZERO);
+
+        q.enqueue_message_check("tg:1").await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let first = q.interactive_wait().await;
+        assert!(first >= Duration::from_millis(20));
+
+        // Re-enqueueing keeps the original wait start.
+        q.enqueue_message_check("tg:1").await;
+        assert!(q.interactive_wait().await >= first);
+    }
+
+    #[tokio::test]
+    async fn queued_work_is_journaled_for_the_next_start() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load_journal(dir.path()).is_empty());
+
+        let q = GroupQueue::new(0, dir.path().to_path_buf());
+        q.enqueue_message_check("tg:2").await;
+        q.enqueue_task("tg:1", "task-a", Box::new(|| Box::pin(async {})))
+            .await;
+        q.enqueue_message_check("tg:1").await;
+        q.enqueue_task("tg:1", "task-b", Box::new(|| Box::pin(async {})))
+            .await;
+
+        let journal = load_journal(dir.path());
+        assert_eq!(journal.messages, ["tg:2", "tg:1"]);
+        let tasks: Vec<_> = journal
+            .tasks
+            .iter()
+            .map(|t| (t.group_jid.as_str(), t.task_id.as_str()))
+            .collect();
+        assert_eq!(tasks, [("tg:1", "task-a"), ("tg:1", "task-b")]);
+
+        std::fs::write(dir.path().join(JOURNAL_FILE), "{not json").unwrap();
+        assert!(load_journal(dir.path()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn finished_task_leaves_the_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let q = GroupQueue::new(1, dir.path().to_path_buf());
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        q.enqueue_task(
+            "tg:1",
+            "task-a",
+            Box::new(move || {
+                Box::pin(async move {
+                    started_tx.send(()).ok();
+                    release_rx.await.ok();
+                })
+            }),
+        )
+        .await;
+        started_rx.await.unwrap();
+        assert_eq!(load_journal(dir.path()).tasks[0].task_id, "task-a");
+
+        release_tx.send(()).unwrap();
+        for _ in 0..100 {
+            if !q.is_active("tg:1").await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert!(load_journal(dir.path()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn status_shows_running_and_waiting_work() {
+        let dir = tempfile::tempdir().unwrap();
+        let q = GroupQueue::new(1, dir.path().to_path_buf());
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        q.enqueue_task(
+            "tg:1",
+            "task-a",
+            Box::new(move || {
+                Box::pin(async move {
+                    started_tx.send(()).ok();
+                    release_rx.await.ok();
+                })
+            }),
+        )
+        .await;
+        started_rx.await.unwrap();
+        q.enqueue_task("tg:1", "task-b", Box::new(|| Box::pin(async {})))
+            .await;
+        q.enqueue_message_check("tg:2").await;
+
+        let first = q.group_status("tg:1").await;
+        assert!(first.active && first.is_task_container);
+        assert!(first.uptime.is_some());
+        assert_eq!(first.running_tasks, ["task-a"]);
+        assert_eq!(first.pending_tasks, ["task-b"]);
+
+        let second = q.group_status("tg:2").await;
+        assert!(!second.active && second.pending_messages);
+        assert_eq!(second.waiting_position, Some(1));
+
+        let active = q.active_groups().await;
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].group_jid, "tg:1");
+
+        let unknown = q.group_status("tg:9").await;
+        assert!(!unknown.active && unknown.pending_tasks.is_empty());
+        release_tx.send(()).unwrap();
+    }
+
+    type Log = Arc<std::sync::Mutex<Vec<String>>>;
+
+    /// A queue whose message runs and tasks note their group in `log`.
+    async fn recording_queue(max_concurrent: usize, dir: &Path) -> (GroupQueue, Log) {
+        let q = GroupQueue::new(max_concurrent, dir.to_path_buf());
+        let log = Log::default();
+        let runs = log.clone();
+        q.set_process_messages_fn(Arc::new(move |jid| {
+            runs.lock().unwrap().push(jid);
+            Box::pin(async { true })
+        }))
+        .await;
+        (q, log)
+    }
+
+    fn logged_task(log: &Log, entry: &str) -> TaskFn {
+        let (log, entry) = (log.clone(), entry.to_string());
+        Box::new(move || Box::pin(async move { log.lock().unwrap().push(entry) }))
+    }
+
+    /// A task holding its slot until the returned sender fires.
+    fn blocking_task() -> (TaskFn, tokio::sync::oneshot::Sender<()>) {
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let task: TaskFn = Box::new(move || {
+            Box::pin(async move {
+                released.await.ok();
+            })
+        });
+        (task, release)
+    }
+
+    async fn until_idle(q: &GroupQueue) {
+        for _ in 0..200 {
+            if q.active_count().await == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("queue never went idle");
+    }
+
+    #[tokio::test]
+    async fn freed_slots_go_to_main_then_messages_then_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let (q, log) = recording_queue(1, dir.path()).await;
+        q.set_main_group(Some("tg:main")).await;
+
+        let (block, release) = blocking_task();
+        q.enqueue_task("tg:busy", "block", block).await;
+        q.enqueue_task("tg:t", "t1", logged_task(&log, "task:t1"))
+            .await;
+        q.enqueue_message_check("tg:b").await;
+        q.enqueue_message_check("tg:main").await;
+        q.enqueue_message_check("tg:c").await;
+        assert!(log.lock().unwrap().is_empty());
+
+        release.send(()).unwrap();
+        until_idle(&q).await;
+        assert_eq!(*log.lock().unwrap(), ["tg:main", "tg:b", "tg:c", "task:t1"]);
+    }
+
+    #[tokio::test]
+    async fn groups_that_ran_less_go_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let (q, log) = recording_queue(1, dir.path()).await;
+        for _ in 0..2 {
+            q.enqueue_message_check("tg:chatty").await;
+            until_idle(&q).await;
+        }
+
+        let (block, release) = blocking_task();
+        q.enqueue_task("tg:busy", "block", block).await;
+        // First in line, but it has already had its turns.
+        q.enqueue_message_check("tg:chatty").await;
+        q.enqueue_message_check("tg:quiet").await;
+        release.send(()).unwrap();
+        until_idle(&q).await;
+        assert_eq!(log.lock().unwrap()[2..], ["tg:quiet", "tg:chatty"]);
+    }
+
+    #[tokio::test]
+    async fn running_task_is_not_queued_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let (q, log) = recording_queue(1, dir.path()).await;
+        let (block, release) = blocking_task();
+        q.enqueue_task("tg:1", "daily", block).await;
+        // The scheduler finds it due again while it runs.
+        q.enqueue_task("tg:1", "daily", logged_task(&log, "again"))
+            .await;
+        assert!(q.has_task("daily").await);
+        assert!(!q.has_task("weekly").await);
+        release.send(()).unwrap();
+        until_idle(&q).await;
+        assert!(log.lock().unwrap().is_empty());
+        assert!(!q.has_task("daily").await);
+    }
+
+    #[tokio::test]
+    async fn extra_slots_run_tasks_and_free_the_first_for_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let (q, log) = recording_queue(5, dir.path()).await;
+        let limits = GroupLimits {
+            max_concurrent: 2,
+            max_runs_per_hour: 0,
+        };
+        q.set_group_limits(HashMap::from([("tg:g".to_string(), limits)]))
+            .await;
+
+        let (first, release_first) = blocking_task();
+        let (second, release_second) = blocking_task();
+        q.enqueue_task("tg:g", "a", first).await;
+        q.enqueue_task("tg:g", "b", second).await;
+        q.enqueue_task("tg:g", "c", logged_task(&log, "task:c"))
+            .await;
+        assert_eq!(q.active_count().await, 2);
+
+        // Both slots taken; the message waits for one.
+        q.enqueue_message_check("tg:g").await;
+        assert!(log.lock().unwrap().is_empty());
+
+        release_second.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // Task `a` moved to the extra slot, so messages went ahead of `c`.
+        assert_eq!(*log.lock().unwrap(), ["tg:g", "task:c"]);
+        assert_eq!(q.active_count().await, 1);
+
+        release_first.send(()).unwrap();
+        until_idle(&q).await;
+        assert!(load_journal(dir.path()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn hourly_limit_holds_runs_until_the_window_has_room() {
+        let dir = tempfile::tempdir().unwrap();
+        let (q, log) = recording_queue(5, dir.path()).await;
+        let limits = GroupLimits {
+            max_concurrent: 1,
+            max_runs_per_hour: 2,
+        };
+        q.set_group_limits(HashMap::from([("tg:g".to_string(), limits)]))
+            .await;
+        q.inner.lock().await.rate_window = Duration::from_millis(300);
+
+        for _ in 0..3 {
+            q.enqueue_message_check("tg:g").await;
+            until_idle(&q).await;
+        }
+        q.enqueue_message_check("tg:other").await;
+        until_idle(&q).await;
+        assert_eq!(*log.lock().unwrap(), ["tg:g", "tg:g", "tg:other"]);
+        assert_eq!(load_journal(dir.path()).messages, ["tg:g"]);
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        until_idle(&q).await;
+        assert_eq!(log.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn finished_runs_promote_waiting_groups_without_passing_the_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let q = GroupQueue::new(2, dir.path().to_path_buf());
+        let running = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let peak = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let log = Log::default();
+        let (counter, high, runs) = (running.clone(), peak.clone(), log.clone());
+        q.set_process_messages_fn(Arc::new(move |jid| {
+            let (counter, high, runs) = (counter.clone(), high.clone(), runs.clone());
+            Box::pin(async move {
+                let now = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                high.fetch_max(now, std::sync::atomic::Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                runs.lock().unwrap().push(jid);
+                counter.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                true
+            })
+        }))
+        .await;
+
+        for n in 0..6 {
+            q.enqueue_message_check(&format!("tg:{n}")).await;
+        }
+        assert_eq!(q.active_count().await, 2);
+        assert_eq!(q.snapshot().await.waiting_groups, 4);
+        // Nothing polls the queue: each finished run starts the next.
+        until_idle(&q).await;
+        assert_eq!(log.lock().unwrap().len(), 6);
+        assert_eq!(peak.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(q.snapshot().await.waiting_groups, 0);
+    }
+
+    #[tokio::test]
+    async fn failed_run_still_hands_its_slot_on() {
+        let dir = tempfile::tempdir().unwrap();
+        let q = GroupQueue::new(1, dir.path().to_path_buf());
+        let log = Log::default();
+        let runs = log.clone();
+        q.set_process_messages_fn(Arc::new(move |jid: String| {
+            let ok = jid != "tg:fail";
+            runs.lock().unwrap().push(jid);
+            Box::pin(async move { ok })
+        }))
+        .await;
+
+        q.enqueue_message_check("tg:fail").await;
+        q.enqueue_message_check("tg:next").await;
+        until_idle(&q).await;
+        // The retry waits on its backoff instead of holding the slot.
+        assert_eq!(*log.lock().unwrap(), ["tg:fail", "tg:next"]);
+    }
+
+    #[tokio::test]
+    async fn group_slots_count_against_the_global_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let (q, log) = recording_queue(2, dir.path()).await;
+        let limits = GroupLimits {
+            max_concurrent: 4,
+            max_runs_per_hour: 0,
+        };
+        q.set_group_limits(HashMap::from([("tg:g".to_string(), limits)]))
+            .await;
+
+        let (first, release_first) = blocking_task();
+        let (second, release_second) = blocking_task();
+        q.enqueue_task("tg:g", "a", first).await;
+        q.enqueue_task("tg:g", "b", second).await;
+        q.enqueue_task("tg:g", "c", logged_task(&log, "task:c"))
+            .await;
+        q.enqueue_message_check("tg:other").await;
+        assert_eq!(q.active_count().await, 2);
+        assert!(log.lock().unwrap().is_empty());
+
+        // The freed slot goes to the waiting messages before the group's
+        // third task, though the group has slots to spare.
+        release_first.send(()).unwrap();
+        release_second.send(()).unwrap();
+        until_idle(&q).await;
+        assert_eq!(*log.lock().unwrap(), ["tg:other", "task:c"]);
+    }
+
+    #[tokio::test]
+    async fn paused_queue_lets_running_work_finish_and_holds_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let (q, log) = recording_queue(3, dir.path()).await;
+        let (block, release) = blocking_task();
+        q.enqueue_task("tg:busy", "block", block).await;
+
+        q.pause().await;
+        q.enqueue_message_check("tg:a").await;
+        assert!(q.is_paused().await);
+        assert_eq!(q.active_count().await, 1);
+
+        // Drain waits on the running task, and times out while it holds.
+        assert_eq!(q.drain(Duration::from_millis(20)).await, 1);
+        let drain = q.drain(Duration::from_secs(5));
+        release.send(()).unwrap();
+        assert_eq!(drain.await, 0);
+        assert!(log.lock().unwrap().is_empty());
+        assert_eq!(load_journal(dir.path()).messages, ["tg:a"]);
+
+        q.resume().await;
+        until_idle(&q).await;
+        assert_eq!(*log.lock().unwrap(), ["tg:a"]);
+    }
+
+    #[tokio::test]
+    async fn message_checks_past_their_deadline_are_dropped_and_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let (q, log) = recording_queue(1, dir.path()).await;
+        let expired = Log::default();
+        let notices = expired.clone();
+        q.set_expired_fn(Arc::new(move |jid, _| {
+            notices.lock().unwrap().push(jid);
+            Box::pin(async {})
+        }))
+        .await;
+
+        let (block, release) = blocking_task();
+        q.enqueue_task("tg:busy", "block", block).await;
+        q.enqueue_message_check_within("tg:late", Some(Duration::from_millis(30)))
+            .await;
+        q.enqueue_message_check_within("tg:patient", None).await;
+        // Nothing frees a slot: the deadline's own wake-up drops the check.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(*expired.lock().unwrap(), ["tg:late"]);
+        assert_eq!(load_journal(dir.path()).messages, ["tg:patient"]);
+
+        release.send(()).unwrap();
+        until_idle(&q).await;
+        assert_eq!(*log.lock().unwrap(), ["tg:patient"]);
+    }
+
+    #[tokio::test]
+    async fn cancel_drops_queued_work_but_not_the_running_container() {
+        let dir = tempfile::tempdir().unwrap();
+        let (q, log) = recording_queue(1, dir.path()).await;
+        let (block, release) = blocking_task();
+        q.enqueue_task("tg:g", "running", block).await;
+        q.enqueue_task("tg:g", "queued", logged_task(&log, "task:queued"))
+            .await;
+        q.enqueue_message_check("tg:g").await;
+
+        let cancelled = q.cancel("tg:g").await;
+        assert_eq!(
+            cancelled,
+            Cancelled {
+                messages: true,
+                tasks: vec!["queued".to_string()]
+            }
+        );
+        assert!(q.is_active("tg:g").await);
+        assert!(q.cancel("tg:unknown").await.is_empty());
+
+        release.send(()).unwrap();
+        until_idle(&q).await;
+        assert!(log.lock().unwrap().is_empty());
+        assert!(load_journal(dir.path()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn metrics_count_runs_waits_and_rejections() {
+        let dir = tempfile::tempdir().unwrap();
+        let (q, _log) = recording_queue(1, dir.path()).await;
+        let (block, release) = blocking_task();
+        q.enqueue_task("tg:1", "daily", block).await;
+        q.enqueue_task("tg:1", "daily", Box::new(|| Box::pin(async {})))
+            .await;
+        q.enqueue_message_check("tg:2").await;
+        release.send(()).unwrap();
+        until_idle(&q).await;
+
+        let text = q.render_metrics().await;
+        assert!(text.contains("intercom_queue_wait_seconds_count{kind=\"message\"} 1\n"));
+        assert!(text.contains("intercom_queue_wait_seconds_count{kind=\"task\"} 1\n"));
+        assert!(text.contains("intercom_queue_run_seconds_count{kind=\"task\"} 1\n"));
+        assert!(text.contains("intercom_queue_retries_bucket{le=\"0\"} 1\n"));
+        assert!(text.contains("intercom_queue_rejected_total{reason=\"duplicate\"} 1\n"));
+        assert!(text.contains("intercom_queue_max_concurrent 1\n"));
+    }
+
+    /// A queue whose message runs hold their slot until `release` fires,
+    /// with the busy group's container registered as `c1` in folder `g`.
+    async fn busy_message_queue(
+        dir: &Path,
+        policy: PreemptionPolicy,
+    ) -> (
+        GroupQueue,
+        Arc<crate::container::executor::FakeExecutor>,
+        Arc<tokio::sync::Notify>,
+    ) {
+        let q = GroupQueue::new(2, dir.to_path_buf());
+        let release = Arc::new(tokio::sync::Notify::new());
+        let released = release.clone();
+        q.set_process_messages_fn(Arc::new(move |_| {
+            let released = released.clone();
+            Box::pin(async move {
+                released.notified().await;
+                true
+            })
+        }))
+        .await;
+        let executor = Arc::new(crate::container::executor::FakeExecutor::silent(0));
+        q.set_preemption(policy, executor.clone(), Duration::from_secs(1))
+            .await;
+        q.enqueue_message_check("tg:g").await;
+        q.register_process("tg:g", "c1", Some("g")).await;
+        (q, executor, release)
+    }
+
+    #[tokio::test]
+    async fn busy_container_winds_down_once_a_task_has_waited() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = PreemptionPolicy {
+            wind_down_after: Some(Duration::from_millis(40)),
+            hard_stop_after: None,
+        };
+        let (q, executor, release) = busy_message_queue(dir.path(), policy).await;
+        let sentinel = dir.path().join("ipc/g/input/_close");
+
+        q.enqueue_task("tg:g", "report", Box::new(|| Box::pin(async {})))
+            .await;
+        assert!(!sentinel.exists(), "a busy container is not closed at once");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(sentinel.exists());
+        assert!(executor.stopped.lock().unwrap().is_empty());
+
+        // Going idle afterwards does not close it a second time.
+        std::fs::remove_file(&sentinel).unwrap();
+        q.notify_idle("tg:g").await;
+        assert!(!sentinel.exists());
+
+        release.notify_one();
+        until_idle(&q).await;
+    }
+
+    #[tokio::test]
+    async fn idle_container_closes_at_once_and_critical_tasks_stop_busy_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let policy = PreemptionPolicy {
+            wind_down_after: None,
+            hard_stop_after: Some(Duration::from_millis(40)),
+        };
+        let (q, executor, release) = busy_message_queue(dir.path(), policy).await;
+        let sentinel = dir.path().join("ipc/g/input/_close");
+
+        // An ordinary task never hard-stops, and without wind_down_after it waits.
+        q.enqueue_task("tg:g", "digest", Box::new(|| Box::pin(async {})))
+            .await;
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(executor.stopped.lock().unwrap().is_empty());
+        assert!(!sentinel.exists());
+
+        q.enqueue_task_as("tg:g", "reminder", true, Box::new(|| Box::pin(async {})))
+            .await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(*executor.stopped.lock().unwrap(), ["c1"]);
+        release.notify_one();
+        until_idle(&q).await;
+
+        // An idle container is closed as soon as a task queues behind it.
+        q.enqueue_message_check("tg:g").await;
+        q.register_process("tg:g", "c2", Some("g")).await;
+        q.notify_idle("tg:g").await;
+        q.enqueue_task("tg:g", "later", Box::new(|| Box::pin(async {})))
+            .await;
+        assert!(sentinel.exists());
+        release.notify_one();
+        until_idle(&q).await;
+    }
+
+    #[test]
+    fn limits_default_to_one_slot_and_no_rate_cap() {
+        assert_eq!(
+            GroupLimits::from_config(None),
+            GroupLimits {
+                max_concurrent: 1,
+                max_runs_per_hour: 0
+            }
+        );
+        let config: ContainerConfig =
+            serde_json::from_value(serde_json::json!({"maxConcurrent": 2, "maxRunsPerHour": 30}))
+                .unwrap();
+        assert_eq!(
+            GroupLimits::from_config(Some(&config)),
+            GroupLimits {
+                max_concurrent: 2,
+                max_runs_per_hour: 30
+            }
+        );
+    }
+
     #[test]
-    fn rand_u16_produces_values() {
-        let a = rand_u16();
-        assert!(a <= u16::MAX);
+    fn ipc_message_files_never_overwrite_each_other() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..64 {
+            assert!(write_ipc_message(dir.path(), &format!("message {i}"), None));
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 64);
     }
 
     #[test]
@@ -559,7 +2299,7 @@ mod tests {
     fn write_ipc_message_creates_file() {
         let dir = tempfile::tempdir().unwrap();
         let input_dir = dir.path().join("input");
-        let result = write_ipc_message(&input_dir, "hello");
+        let result = write_ipc_message(&input_dir, "hello", Some("c1"));
         assert!(result);
         let files: Vec<_> = std::fs::read_dir(&input_dir)
             .unwrap()
@@ -571,5 +2311,8 @@ mod tests {
             })
             .collect();
         assert_eq!(files.len(), 1);
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(files[0].path()).unwrap()).unwrap();
+        assert_eq!(written["correlationId"], "c1");
     }
 }
@@ -0,0 +1,466 @@
+//! Rate limiting for the routes the outside world can reach: the Telegram
+//! routes and `/v1/commands`.
+//!
+//! Each client address and each presented token (`Authorization: Bearer`
+//! or Telegram's webhook secret header) gets a token bucket from
+//! `[server.rate_limit]`. A request must find a token in its address's
+//! bucket and, when it carries one, its token's bucket; otherwise it is
+//! answered 429 with `Retry-After` and neither bucket is charged. Buckets
+//! live in memory and refill continuously, so a restart forgets them.
+//!
+//! The client address is the peer unless the peer is a trusted proxy, in
+//! which case it is the rightmost `X-Forwarded-For` entry the proxies did
+//! not add themselves. Exemptions only ever match the peer.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use axum::Json;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use intercom_core::RateLimitConfig;
+use tracing::{debug, warn};
+
+use crate::telegram;
+
+/// Buckets kept per kind. Full ones are swept out first, since a full
+/// bucket is the same as no bucket; past that the fullest are dropped, which
+/// at worst lets a nearly refilled client through a little early.
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Refill rate and capacity of one kind of bucket.
+#[derive(Debug, Clone, Copy)]
+struct Limit {
+    per_sec: f64,
+    burst: f64,
+}
+
+impl Limit {
+    fn new(per_minute: u32, burst: u32) -> Option<Self> {
+        (per_minute > 0).then(|| Self {
+            per_sec: f64::from(per_minute) / 60.0,
+            burst: f64::from(burst.max(1)),
+        })
+    }
+
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_sec).min(self.burst);
+        bucket.updated = now;
+    }
+}
+
+struct Buckets<K> {
+    limit: Option<Limit>,
+    buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K: std::hash::Hash + Eq + Clone> Buckets<K> {
+    fn new(limit: Option<Limit>) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<K, Bucket>> {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Refill `key`'s bucket and hand it back if it holds a token, or say
+    /// how long until it will. Nothing is taken; `None` means unlimited.
+    fn ready<'a>(
+        &self,
+        buckets: &'a mut HashMap<K, Bucket>,
+        key: &K,
+        now: Instant,
+    ) -> Result<Option<&'a mut Bucket>, Duration> {
+        let Some(limit) = self.limit else {
+            return Ok(None);
+        };
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(key) {
+            sweep(buckets, limit, now);
+        }
+        let bucket = buckets.entry(key.clone()).or_insert(Bucket {
+            tokens: limit.burst,
+            updated: now,
+        });
+        limit.refill(bucket, now);
+        if bucket.tokens >= 1.0 {
+            Ok(Some(bucket))
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / limit.per_sec,
+            ))
+        }
+    }
+}
+
+/// Make room for a new bucket: drop full buckets, then the fullest until
+/// a quarter of the capacity is free so the next sweep is a while off.
+fn sweep<K: std::hash::Hash + Eq + Clone>(
+    buckets: &mut HashMap<K, Bucket>,
+    limit: Limit,
+    now: Instant,
+) {
+    buckets.retain(|_, bucket| {
+        limit.refill(bucket, now);
+        bucket.tokens < limit.burst
+    });
+    let keep = MAX_BUCKETS - MAX_BUCKETS / 4;
+    if buckets.len() > keep {
+        let mut by_tokens: Vec<(K, f64)> =
+            buckets.iter().map(|(k, b)| (k.clone(), b.tokens)).collect();
+        by_tokens.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for (key, _) in by_tokens.into_iter().take(buckets.len() - keep) {
+            buckets.remove(&key);
+        }
+    }
+}
+
+pub struct RateLimiter {
+    enabled: bool,
+    exempt: Vec<IpAddr>,
+    trust_forwarded_for: bool,
+    trusted_proxies: Vec<IpAddr>,
+    by_ip: Buckets<IpAddr>,
+    by_token: Buckets<String>,
+}
+
+impl RateLimiter {
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            exempt: parse_ips(&config.exempt_ips, "exempt_ips"),
+            trust_forwarded_for: config.trust_forwarded_for,
+            trusted_proxies: parse_ips(&config.trusted_proxies, "trusted_proxies"),
+            by_ip: Buckets::new(Limit::new(config.per_ip_per_minute, config.per_ip_burst)),
+            by_token: Buckets::new(Limit::new(
+                config.per_token_per_minute,
+                config.per_token_burst,
+            )),
+        }
+    }
+
+    /// The client's address. `X-Forwarded-For` is only read when the peer is
+    /// a trusted proxy; walking it from the right, the first hop that is not
+    /// a trusted proxy is the client, since anything left of that came from
+    /// the client itself. `None` when the request was not forwarded.
+    fn forwarded_client(&self, peer: IpAddr, headers: &HeaderMap) -> Option<IpAddr> {
+        if !self.trust_forwarded_for || !self.trusted_proxies.contains(&peer) {
+            return None;
+        }
+        let mut hops: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .collect();
+        if hops.is_empty() {
+            return None;
+        }
+        let mut client = peer;
+        while let Some(hop) = hops.pop() {
+            let Ok(hop) = hop.trim().parse::<IpAddr>() else {
+                break;
+            };
+            client = hop;
+            if !self.trusted_proxies.contains(&hop) {
+                break;
+            }
+        }
+        Some(client)
+    }
+
+    /// Admit a request, or say how long the caller should wait. Both the
+    /// address and the token bucket must have a token before either is
+    /// charged.
+    pub fn check(&self, peer: IpAddr, headers: &HeaderMap, now: Instant) -> Result<(), Duration> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let ip = match self.forwarded_client(peer, headers) {
+            Some(client) => client,
+            None if self.exempt.contains(&peer) => return Ok(()),
+            None => peer,
+        };
+        let token = presented_token(headers);
+
+        // Always address before token, so the locks are taken in one order.
+        let mut ips = self.by_ip.lock();
+        let mut tokens = self.by_token.lock();
+        let ip_bucket = self.by_ip.ready(&mut ips, &ip, now)?;
+        let token_bucket = match &token {
+            Some(token) => self.by_token.ready(&mut tokens, token, now)?,
+            None => None,
+        };
+        for bucket in [ip_bucket, token_bucket].into_iter().flatten() {
+            bucket.tokens -= 1.0;
+        }
+        Ok(())
+    }
+}
+
+fn parse_ips(ips: &[String], key: &str) -> Vec<IpAddr> {
+    ips.iter()
+        .filter_map(|ip| match ip.trim().parse() {
+            Ok(ip) => Some(ip),
+            Err(_) => {
+                warn!(
+                    ip = ip.as_str(),
+                    key, "ignoring unparseable server.rate_limit entry"
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+fn presented_token(headers: &HeaderMap) -> Option<String> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let secret = || {
+        headers
+            .get(telegram::WEBHOOK_SECRET_HEADER)
+            .and_then(|v| v.to_str().ok())
+    };
+    bearer
+        .or_else(secret)
+        .map(|token| token.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Middleware for the limited routes. Needs the server's connect info.
+pub async fn enforce(
+    State(limiter): State<Arc<RateLimiter>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match limiter.check(peer.ip(), request.headers(), Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let retry_after = wait.as_secs() + 1;
+            debug!(peer = %peer, path = request.uri().path(), retry_after, "rate limited");
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(serde_json::json!({ "error": "rate limit exceeded", "retry_after_secs": retry_after })),
+            )
+                .into_response();
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter::from_config(&config)
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, value.parse().unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn addresses_get_a_burst_then_the_rate() {
+        let limiter = limiter(RateLimitConfig {
+            per_ip_per_minute: 60,
+            per_ip_burst: 2,
+            ..Default::default()
+        });
+        let (peer, other) = (
+            "203.0.113.7".parse().unwrap(),
+            "203.0.113.8".parse().unwrap(),
+        );
+        let now = Instant::now();
+        let none = HeaderMap::new();
+        assert!(limiter.check(peer, &none, now).is_ok());
+        assert!(limiter.check(peer, &none, now).is_ok());
+        let wait = limiter.check(peer, &none, now).unwrap_err();
+        assert!(wait <= Duration::from_secs(1), "{wait:?}");
+        assert!(limiter.check(other, &none, now).is_ok());
+        assert!(
+            limiter
+                .check(peer, &none, now + Duration::from_secs(1))
+                .is_ok()
+        );
+
+        let loopback = "127.0.0.1".parse().unwrap();
+        assert!((0..10).all(|_| limiter.check(loopback, &none, now).is_ok()));
+    }
+
+    #[test]
+    fn tokens_are_limited_across_addresses() {
+        let limiter = limiter(RateLimitConfig {
+            per_token_per_minute: 1,
+            per_token_burst: 1,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let token = headers(&[("authorization", "Bearer abc")]);
+        assert!(
+            limiter
+                .check("198.51.100.1".parse().unwrap(), &token, now)
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .check("198.51.100.2".parse().unwrap(), &token, now)
+                .is_err()
+        );
+        let secret = headers(&[(telegram::WEBHOOK_SECRET_HEADER, "other")]);
+        assert!(
+            limiter
+                .check("198.51.100.2".parse().unwrap(), &secret, now)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn forwarded_addresses_only_count_when_trusted() {
+        let config = RateLimitConfig {
+            per_ip_per_minute: 1,
+            per_ip_burst: 1,
+            ..Default::default()
+        };
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let now = Instant::now();
+        let from = |client: &str| headers(&[("x-forwarded-for", client)]);
+
+        let direct = limiter(config.clone());
+        assert!(direct.check(proxy, &from("203.0.113.1"), now).is_ok());
+        assert!(direct.check(proxy, &from("203.0.113.2"), now).is_err());
+
+        let proxied = limiter(RateLimitConfig {
+            trust_forwarded_for: true,
+            trusted_proxies: vec!["10.0.0.1".to_string()],
+            ..config
+        });
+        assert!(
+            proxied
+                .check(proxy, &from("203.0.113.1, 10.0.0.1"), now)
+                .is_ok()
+        );
+        assert!(proxied.check(proxy, &from("203.0.113.2"), now).is_ok());
+        assert!(proxied.check(proxy, &from("203.0.113.1"), now).is_err());
+
+        // Only a trusted proxy's header is read.
+        let stranger: IpAddr = "198.51.100.9".parse().unwrap();
+        assert!(proxied.check(stranger, &from("203.0.113.3"), now).is_ok());
+        assert!(proxied.check(stranger, &from("203.0.113.4"), now).is_err());
+    }
+
+    #[test]
+    fn spoofed_loopback_is_not_exempt() {
+        let config = RateLimitConfig {
+            per_ip_per_minute: 1,
+            per_ip_burst: 1,
+            ..Default::default()
+        };
+        let now = Instant::now();
+        let spoofed = headers(&[("x-forwarded-for", "127.0.0.1")]);
+        let client: IpAddr = "203.0.113.9".parse().unwrap();
+        let loopback: IpAddr = "127.0.0.1".parse().unwrap();
+
+        let direct = limiter(config.clone());
+        assert!(direct.check(client, &spoofed, now).is_ok());
+        assert!(direct.check(client, &spoofed, now).is_err());
+
+        let proxied = limiter(RateLimitConfig {
+            trust_forwarded_for: true,
+            ..config
+        });
+        assert!(proxied.check(client, &spoofed, now).is_ok());
+        assert!(proxied.check(client, &spoofed, now).is_err());
+        // Through the local proxy the forwarded loopback is a client like
+        // any other, while the Node host calling directly stays exempt.
+        assert!(proxied.check(loopback, &spoofed, now).is_ok());
+        assert!(proxied.check(loopback, &spoofed, now).is_err());
+        let none = HeaderMap::new();
+        assert!((0..10).all(|_| proxied.check(loopback, &none, now).is_ok()));
+    }
+
+    #[test]
+    fn rotating_forwarded_addresses_share_one_bucket() {
+        let limiter = limiter(RateLimitConfig {
+            per_ip_per_minute: 1,
+            per_ip_burst: 2,
+            trust_forwarded_for: true,
+            trusted_proxies: vec!["10.0.0.1".to_string()],
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let stranger: IpAddr = "198.51.100.9".parse().unwrap();
+        let fake = |i: u32| format!("192.0.2.{}", i % 250);
+
+        // The proxy appends the real client after whatever was sent.
+        let via_proxy = |i| headers(&[("x-forwarded-for", &format!("{}, 203.0.113.5", fake(i)))]);
+        assert!(limiter.check(proxy, &via_proxy(1), now).is_ok());
+        assert!(limiter.check(proxy, &via_proxy(2), now).is_ok());
+        assert!(limiter.check(proxy, &via_proxy(3), now).is_err());
+
+        let direct = |i| headers(&[("x-forwarded-for", &fake(i))]);
+        assert!(limiter.check(stranger, &direct(1), now).is_ok());
+        assert!(limiter.check(stranger, &direct(2), now).is_ok());
+        assert!(limiter.check(stranger, &direct(3), now).is_err());
+    }
+
+    #[test]
+    fn rejected_requests_charge_neither_bucket() {
+        let limiter = limiter(RateLimitConfig {
+            per_ip_per_minute: 1,
+            per_ip_burst: 2,
+            per_token_per_minute: 1,
+            per_token_burst: 1,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let peer: IpAddr = "203.0.113.7".parse().unwrap();
+        let token = headers(&[("authorization", "Bearer abc")]);
+        let none = HeaderMap::new();
+        assert!(limiter.check(peer, &token, now).is_ok());
+        assert!(limiter.check(peer, &token, now).is_err());
+        // The address still has the token the rejected request did not use.
+        assert!(limiter.check(peer, &none, now).is_ok());
+        assert!(limiter.check(peer, &none, now).is_err());
+    }
+
+    #[test]
+    fn bucket_count_stays_bounded() {
+        let limiter = limiter(RateLimitConfig {
+            per_ip_per_minute: 1,
+            per_ip_burst: 2,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let none = HeaderMap::new();
+        for i in 0..(MAX_BUCKETS as u32 + 500) {
+            let peer = IpAddr::from(std::net::Ipv4Addr::from(0x0a00_0000 + i));
+            assert!(limiter.check(peer, &none, now).is_ok());
+        }
+        assert!(limiter.by_ip.lock().len() <= MAX_BUCKETS);
+    }
+}
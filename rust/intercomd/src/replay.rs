@@ -0,0 +1,474 @@
+//! Replay a stored message through the pipeline (`POST /v1/admin/replay`).
+//!
+//! For diagnosing "why did the agent say that": the batch the message was
+//! processed in is rebuilt from storage, formatted exactly as the message
+//! loop formats it, and run through the group's queue slot and a container —
+//! optionally on a different runtime or model. The `echo` runtime skips the
+//! container and replies with the prompt the agent would have received.
+//!
+//! A replay is kept out of the live conversation: it starts without a
+//! session, leaves cursors and sessions untouched, stores nothing in the
+//! group's chat, and delivers its reply to `deliver_to` tagged with the
+//! replay id. It still runs with the group's mounts, so workspace snapshots
+//! are taken around it like any other run, labelled with the replay id.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{anyhow, bail};
+use intercom_core::{
+    ContainerInput, ContainerOutput, ContainerStatus, NewMessage, RegisteredGroup, RuntimeKind,
+    SharedStorage,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+use crate::channels::ChannelRegistry;
+use crate::container::context::{ContextInputs, GroupContext, write_group_context};
+use crate::container::mounts::GroupInfo;
+use crate::container::runner::{
+    OutputCallback, RunConfig, record_run, run_container_agent, write_snapshots,
+};
+use crate::container::security::ContainerConfig;
+use crate::health;
+use crate::message_loop;
+use crate::outbound::strip_internal_blocks;
+use crate::process_group::resolve_runtime;
+use crate::queue::{GroupQueue, TaskFn};
+use crate::workspace_git::{self, RunPhase};
+
+/// Runtime name that answers with the formatted prompt instead of running
+/// a container.
+pub const ECHO_RUNTIME: &str = "echo";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplayRequest {
+    /// Chat the message was stored in; must be a registered group.
+    pub chat_jid: String,
+    pub message_id: String,
+    /// Chat that receives the replay's output instead of the group.
+    pub deliver_to: String,
+    /// `claude`, `gemini`, `codex` or `echo`. Defaults to the group's runtime.
+    #[serde(default)]
+    pub runtime: Option<String>,
+    /// Model override. Defaults to the group's model.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayResponse {
+    pub ok: bool,
+    pub replay_id: String,
+    pub group_folder: String,
+    pub runtime: String,
+    pub model: Option<String>,
+    /// Messages in the replayed batch, oldest first.
+    pub message_ids: Vec<String>,
+    pub prompt: String,
+    pub error: Option<String>,
+}
+
+impl ReplayResponse {
+    pub fn from_error(err: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            replay_id: String::new(),
+            group_folder: String::new(),
+            runtime: String::new(),
+            model: None,
+            message_ids: Vec::new(),
+            prompt: String::new(),
+            error: Some(err.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReplayRuntime {
+    Echo,
+    Agent(RuntimeKind),
+}
+
+impl ReplayRuntime {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Echo => ECHO_RUNTIME,
+            Self::Agent(kind) => kind.as_str(),
+        }
+    }
+}
+
+fn parse_runtime(name: Option<&str>, group: &RegisteredGroup) -> anyhow::Result<ReplayRuntime> {
+    match name.map(str::trim) {
+        None | Some("") => Ok(ReplayRuntime::Agent(resolve_runtime(group))),
+        Some(ECHO_RUNTIME) => Ok(ReplayRuntime::Echo),
+        Some("claude") => Ok(ReplayRuntime::Agent(RuntimeKind::Claude)),
+        Some("gemini") => Ok(ReplayRuntime::Agent(RuntimeKind::Gemini)),
+        Some("codex") => Ok(ReplayRuntime::Agent(RuntimeKind::Codex)),
+        Some(other) => bail!("unknown runtime `{other}` (expected claude, gemini, codex or echo)"),
+    }
+}
+
+/// Everything a queued replay needs once its turn comes.
+struct ReplayPlan {
+    replay_id: String,
+    group: RegisteredGroup,
+    is_main: bool,
+    deliver_to: String,
+    runtime: ReplayRuntime,
+    model: Option<String>,
+    prompt: String,
+    batch: Vec<NewMessage>,
+}
+
+impl ReplayPlan {
+    fn tag(&self) -> String {
+        match &self.model {
+            Some(model) => format!(
+                "[replay {} · {}/{}]",
+                self.replay_id,
+                self.runtime.as_str(),
+                model
+            ),
+            None => format!("[replay {} · {}]", self.replay_id, self.runtime.as_str()),
+        }
+    }
+}
+
+/// Validate the request, rebuild the message batch and queue the replay on
+/// the group's slot. Returns as soon as the replay is queued; its output
+/// arrives in `deliver_to`.
+pub async fn start_replay(
+    request: ReplayRequest,
+    pool: &SharedStorage,
+    queue: &Arc<GroupQueue>,
+    groups: &Arc<RwLock<HashMap<String, RegisteredGroup>>>,
+    channels: &Arc<ChannelRegistry>,
+    run_config: &RunConfig,
+    assistant_name: &str,
+    main_group_folder: &str,
+) -> anyhow::Result<ReplayResponse> {
+    if request.message_id.trim().is_empty() {
+        bail!("message_id is required");
+    }
+    if request.deliver_to.trim().is_empty() {
+        bail!("deliver_to is required");
+    }
+    let group = groups
+        .read()
+        .await
+        .get(&request.chat_jid)
+        .cloned()
+        .ok_or_else(|| anyhow!("{} is not a registered group", request.chat_jid))?;
+    let runtime = parse_runtime(request.runtime.as_deref(), &group)?;
+
+    let batch = pool
+        .get_replay_batch(&request.chat_jid, &request.message_id, assistant_name)
+        .await?;
+    if batch.is_empty() {
+        bail!(
+            "message {} not found in {} (or it has no replayable content)",
+            request.message_id,
+            request.chat_jid
+        );
+    }
+
+    let model = request
+        .model
+        .filter(|m| !m.trim().is_empty())
+        .or_else(|| group.model.clone());
+    let model = match runtime {
+        ReplayRuntime::Agent(kind) => run_config.resolve_model(kind, model.as_deref()),
+        ReplayRuntime::Echo => model,
+    };
+    let plan = ReplayPlan {
+        replay_id: format!("replay-{}", workspace_git::new_run_id()),
+        is_main: group.folder == main_group_folder,
+        deliver_to: request.deliver_to,
+        runtime,
+        model,
+        prompt: message_loop::format_messages_pub(&batch),
+        batch,
+        group,
+    };
+
+    let response = ReplayResponse {
+        ok: true,
+        replay_id: plan.replay_id.clone(),
+        group_folder: plan.group.folder.clone(),
+        runtime: runtime.as_str().to_string(),
+        model: plan.model.clone(),
+        message_ids: plan.batch.iter().map(|m| m.id.clone()).collect(),
+        prompt: plan.prompt.clone(),
+        error: None,
+    };
+    info!(
+        replay_id = plan.replay_id.as_str(),
+        chat_jid = request.chat_jid.as_str(),
+        message_id = request.message_id.as_str(),
+        runtime = runtime.as_str(),
+        deliver_to = plan.deliver_to.as_str(),
+        "queueing replay"
+    );
+
+    let pool = pool.clone();
+    let queue_for_task = queue.clone();
+    let groups = groups.clone();
+    let channels = channels.clone();
+    let run_config = run_config.clone();
+    let assistant_name = assistant_name.to_string();
+    let task_fn: TaskFn = Box::new(move || {
+        Box::pin(async move {
+            run_replay(
+                plan,
+                &pool,
+                &queue_for_task,
+                &groups,
+                &channels,
+                &run_config,
+                &assistant_name,
+            )
+            .await;
+        })
+    });
+    queue
+        .enqueue_task(&request.chat_jid, &response.replay_id, task_fn)
+        .await;
+
+    Ok(response)
+}
+
+async fn deliver(channels: &ChannelRegistry, plan: &ReplayPlan, text: &str) {
+    let text = format!("{}\n\n{}", plan.tag(), text);
+    if let Err(e) = channels.send_text(&plan.deliver_to, &text).await {
+        error!(replay_id = plan.replay_id.as_str(), err = %e, "failed to deliver replay output");
+    }
+}
+
+async fn run_replay(
+    plan: ReplayPlan,
+    pool: &SharedStorage,
+    queue: &Arc<GroupQueue>,
+    groups: &Arc<RwLock<HashMap<String, RegisteredGroup>>>,
+    channels: &Arc<ChannelRegistry>,
+    run_config: &RunConfig,
+    assistant_name: &str,
+) {
+    let kind = match plan.runtime {
+        ReplayRuntime::Echo => {
+            deliver(channels, &plan, &plan.prompt).await;
+            info!(replay_id = plan.replay_id.as_str(), "echo replay delivered");
+            return;
+        }
+        ReplayRuntime::Agent(kind) => kind,
+    };
+    let plan = Arc::new(plan);
+    let group = &plan.group;
+    let start = Instant::now();
+
+    let input = ContainerInput {
+        prompt: plan.prompt.clone(),
+        session_id: None, // never resume or advance the live session
+        group_folder: group.folder.clone(),
+        chat_jid: group.jid.clone(),
+        is_main: plan.is_main,
+        is_scheduled_task: None,
+        assistant_name: Some(assistant_name.to_string()),
+        model: plan.model.clone(),
+        secrets: None,
+        attachments: plan
+            .batch
+            .iter()
+            .flat_map(|m| m.attachments.iter().cloned())
+            .collect(),
+        correlation_id: Some(plan.replay_id.clone()),
+        reply_to_message_id: None,
+        stdin_keepalive: None,
+    };
+
+    let group_info = GroupInfo {
+        folder: group.folder.clone(),
+        name: group.name.clone(),
+        container_config: group
+            .container_config
+            .as_ref()
+            .and_then(|v| serde_json::from_value::<ContainerConfig>(v.clone()).ok()),
+    };
+
+    // Same snapshots and context.json a live run would see
+    {
+        let tasks = pool.get_all_tasks().await.unwrap_or_else(|e| {
+            warn!(err = %e, "failed to load tasks for snapshot");
+            Vec::new()
+        });
+        let visible: Vec<_> = tasks
+            .iter()
+            .filter(|t| plan.is_main || t.group_folder == group.folder)
+            .collect();
+        let tasks_json = serde_json::to_string(&visible).unwrap_or_else(|_| "[]".into());
+        let groups_json = {
+            let g = groups.read().await;
+            let entries: Vec<_> = g
+                .values()
+                .map(|rg| {
+                    serde_json::json!({
+                        "jid": rg.jid,
+                        "name": rg.name,
+                        "folder": rg.folder,
+                    })
+                })
+                .collect();
+            serde_json::to_string(&entries).unwrap_or_else(|_| "[]".into())
+        };
+        write_snapshots(
+            &run_config.data_dir,
+            &group.folder,
+            plan.is_main,
+            &tasks_json,
+            &groups_json,
+        )
+        .await;
+
+        let context = GroupContext::build(
+            &ContextInputs {
+                group,
+                container_config: group_info.container_config.as_ref(),
+                is_main: plan.is_main,
+                runtime: kind,
+                is_scheduled_task: false,
+                tasks: &tasks,
+            },
+            run_config,
+            chrono::Utc::now(),
+        );
+        write_group_context(&run_config.data_dir, &group.folder, &context).await;
+    }
+
+    // Output goes to the operator chat only; the container is closed after
+    // its first result since nothing else will be piped into it.
+    let output_sent = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let plan_cb = plan.clone();
+    let channels_cb = channels.clone();
+    let queue_cb = queue.clone();
+    let output_sent_cb = output_sent.clone();
+    let on_output: Option<Arc<OutputCallback>> =
+        Some(Arc::new(Box::new(move |output: ContainerOutput| {
+            let plan = plan_cb.clone();
+            let channels = channels_cb.clone();
+            let queue = queue_cb.clone();
+            let output_sent = output_sent_cb.clone();
+
+            Box::pin(async move {
+                if let Some(ref result_text) = output.result {
+                    let text = strip_internal_blocks(result_text);
+                    if !text.is_empty() {
+                        deliver(&channels, &plan, &text).await;
+                        output_sent.store(true, std::sync::atomic::Ordering::SeqCst);
+                    }
+                }
+                if output.status == ContainerStatus::Success {
+                    queue.close_stdin(&plan.group.jid).await;
+                }
+            })
+        })));
+
+    let run_id = workspace_git::new_run_id();
+    workspace_git::snapshot_run(
+        run_config,
+        &group.folder,
+        &run_id,
+        RunPhase::Before,
+        Some(&plan.replay_id),
+    )
+    .await;
+    let started_at = chrono::Utc::now();
+    let result = run_container_agent(
+        &group_info,
+        &input,
+        kind,
+        plan.is_main,
+        "replay",
+        run_config,
+        on_output,
+    )
+    .await;
+    record_run(pool, "replay", &input, started_at, &result).await;
+    workspace_git::snapshot_run(
+        run_config,
+        &group.folder,
+        &run_id,
+        RunPhase::After,
+        Some(&plan.replay_id),
+    )
+    .await;
+
+    let run_error = match result {
+        Ok(r) if r.output.status == ContainerStatus::Error => {
+            Some(r.output.error.unwrap_or_else(|| "Unknown error".into()))
+        }
+        Ok(_) => None,
+        Err(e) => {
+            health::record_error(health::SUBSYSTEM_DOCKER, &e);
+            Some(e.to_string())
+        }
+    };
+    let sent = output_sent.load(std::sync::atomic::Ordering::SeqCst);
+    match (&run_error, sent) {
+        (Some(err), _) => deliver(channels, &plan, &format!("Replay failed: {err}")).await,
+        (None, false) => deliver(channels, &plan, "Replay finished without output.").await,
+        (None, true) => {}
+    }
+    info!(
+        replay_id = plan.replay_id.as_str(),
+        group = group.name.as_str(),
+        duration_ms = start.elapsed().as_millis() as u64,
+        error = run_error.as_deref().unwrap_or(""),
+        "replay finished"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(runtime: Option<&str>) -> RegisteredGroup {
+        RegisteredGroup {
+            jid: "tg:1".into(),
+            name: "Team".into(),
+            folder: "team".into(),
+            trigger: String::new(),
+            added_at: String::new(),
+            container_config: None,
+            requires_trigger: None,
+            runtime: runtime.map(Into::into),
+            model: None,
+            muted_until: None,
+            locale: None,
+        }
+    }
+
+    #[test]
+    fn runtime_defaults_to_group_and_accepts_echo() {
+        let g = group(Some("gemini"));
+        assert_eq!(
+            parse_runtime(None, &g).unwrap(),
+            ReplayRuntime::Agent(RuntimeKind::Gemini)
+        );
+        assert_eq!(
+            parse_runtime(Some(" "), &g).unwrap(),
+            ReplayRuntime::Agent(RuntimeKind::Gemini)
+        );
+        assert_eq!(
+            parse_runtime(Some("echo"), &g).unwrap(),
+            ReplayRuntime::Echo
+        );
+        assert_eq!(
+            parse_runtime(Some("codex"), &g).unwrap(),
+            ReplayRuntime::Agent(RuntimeKind::Codex)
+        );
+        assert!(parse_runtime(Some("gpt"), &g).is_err());
+    }
+}
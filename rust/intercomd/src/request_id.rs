@@ -0,0 +1,227 @@
+//! `X-Request-Id` for every HTTP request.
+//!
+//! The middleware takes the caller's `X-Request-Id` when it is a sane
+//! token, or makes one up, and runs the handler inside an `http_request`
+//! span carrying it. Work the handler starts inherits the span. Messages
+//! are answered later, by a run the message loop queues, so routes that
+//! store one [`note_message`] and the queue tags the run with the id it
+//! [`take_for_chat`]s when the chat is enqueued. The id is echoed in the
+//! response header and added as `request_id` to JSON error bodies, so a
+//! failed call can be found in the logs from the reply alone.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use axum::body::{Body, HttpBody, to_bytes};
+use axum::extract::Request;
+use axum::http::{HeaderValue, header};
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::{Instrument, debug, info, info_span, warn};
+
+pub const HEADER: &str = "x-request-id";
+
+/// Error bodies larger than this, or streamed, are passed through untouched.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the HTTP request being handled on this task, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Chats with a message stored over HTTP and not yet picked up, with the
+/// id of the request that stored the newest one.
+fn pending() -> &'static Mutex<HashMap<String, String>> {
+    static PENDING: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+    PENDING.get_or_init(Default::default)
+}
+
+/// Chats remembered at most. The map is emptied when full, so chats that
+/// are never answered can't grow it forever.
+const MAX_PENDING: usize = 1024;
+
+/// Remember that the current request stored a message for `chat_jid`.
+pub fn note_message(chat_jid: &str) {
+    let Some(request_id) = current() else {
+        return;
+    };
+    let mut pending = pending().lock().unwrap_or_else(|e| e.into_inner());
+    if pending.len() >= MAX_PENDING && !pending.contains_key(chat_jid) {
+        pending.clear();
+    }
+    pending.insert(chat_jid.to_string(), request_id);
+}
+
+/// The request that stored `chat_jid`'s newest message, once: the run or
+/// container that picks the messages up claims it.
+pub fn take_for_chat(chat_jid: &str) -> Option<String> {
+    pending()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(chat_jid)
+}
+
+/// A caller's id is kept if it is 1–128 characters of letters, digits and
+/// `-_.:`; anything else could break log lines or headers.
+fn accept(value: &str) -> bool {
+    (1..=128).contains(&value.len())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c))
+}
+
+/// Probes are polled constantly; their successes are only worth a debug line.
+fn is_probe(path: &str) -> bool {
+    matches!(path, "/healthz" | "/readyz" | "/metrics")
+}
+
+pub async fn propagate(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| accept(v))
+        .map(str::to_string)
+        .unwrap_or_else(intercom_core::new_correlation_id);
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let span = info_span!("http_request", request_id = %request_id, %method, path = %path);
+
+    let started = Instant::now();
+    let response = REQUEST_ID
+        .scope(
+            request_id.clone(),
+            next.run(request).instrument(span.clone()),
+        )
+        .await;
+    let status = response.status();
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    span.in_scope(|| {
+        if status.is_server_error() {
+            warn!(status = status.as_u16(), elapsed_ms, "request failed");
+        } else if status.is_success() && is_probe(&path) {
+            debug!(status = status.as_u16(), elapsed_ms, "request finished");
+        } else {
+            info!(status = status.as_u16(), elapsed_ms, "request finished");
+        }
+    });
+
+    let mut response = if status.is_client_error() || status.is_server_error() {
+        tag_error_body(response, &request_id).await
+    } else {
+        response
+    };
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(HEADER, value);
+    }
+    response
+}
+
+/// Add `request_id` to a JSON object error body; other bodies are returned
+/// as they were.
+async fn tag_error_body(response: Response, request_id: &str) -> Response {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let small = response
+        .body()
+        .size_hint()
+        .exact()
+        .is_some_and(|len| len <= MAX_ERROR_BODY as u64);
+    if !is_json || !small {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_ERROR_BODY).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(serde_json::Value::Object(mut object)) => {
+            object.insert("request_id".into(), request_id.into());
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(serde_json::Value::Object(object).to_string())
+        }
+        _ => Body::from(bytes),
+    };
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+
+    /// Serve a router with the middleware on a local port.
+    async fn serve() -> String {
+        let app = Router::new()
+            .route("/ok", get(|| async { current().unwrap_or_default() }))
+            .route(
+                "/missing",
+                get(|| async {
+                    (
+                        StatusCode::NOT_FOUND,
+                        axum::Json(serde_json::json!({ "error": "no task" })),
+                    )
+                }),
+            )
+            .layer(axum::middleware::from_fn(propagate));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}")
+    }
+
+    async fn call(url: &str, request_id: Option<&str>) -> reqwest::Response {
+        let mut request = reqwest::Client::new().get(url);
+        if let Some(id) = request_id {
+            request = request.header(HEADER, id);
+        }
+        request.send().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn ids_are_kept_or_made_up_and_echoed() {
+        let base = serve().await;
+        let response = call(&format!("{base}/ok"), Some("host-7f3a")).await;
+        assert_eq!(response.headers()[HEADER], "host-7f3a");
+        assert_eq!(response.text().await.unwrap(), "host-7f3a");
+
+        let response = call(&format!("{base}/ok"), Some("bad id/with slash")).await;
+        let made_up = response.headers()[HEADER].to_str().unwrap().to_string();
+        assert_ne!(made_up, "bad id/with slash");
+        assert_eq!(response.text().await.unwrap(), made_up);
+        assert!(current().is_none());
+    }
+
+    #[tokio::test]
+    async fn stored_messages_hand_their_id_to_the_run() {
+        REQUEST_ID
+            .scope("req-9".to_string(), async { note_message("tg:note") })
+            .await;
+        note_message("tg:outside");
+        assert_eq!(take_for_chat("tg:note").as_deref(), Some("req-9"));
+        assert_eq!(take_for_chat("tg:note"), None);
+        assert_eq!(take_for_chat("tg:outside"), None);
+    }
+
+    #[tokio::test]
+    async fn error_bodies_carry_the_id() {
+        let base = serve().await;
+        let response = call(&format!("{base}/missing"), Some("req-1")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let json: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "error": "no task", "request_id": "req-1" })
+        );
+    }
+}
@@ -5,18 +5,45 @@
 //! and `status = 'active'`, and passes them to a callback for container execution.
 //!
 //! Next-run calculation supports three schedule types:
-//! - `cron`: parsed via the `cron` crate with timezone support
+//! - `cron`: 5 fields (minute first) or 6 (seconds first), with ranges,
+//!   steps, lists, month and day names and the `@daily`-style macros,
+//!   evaluated on the wall clock of the configured timezone
 //! - `interval`: millisecond offset from now
-//! - `once`: no next run (task moves to `completed`)
+//! - `once`: a timestamp; no next run after it (task moves to `completed`)
+//!
+//! Cron times follow the wall clock across DST changes: a time skipped when
+//! clocks go forward runs at the end of the gap, and a time repeated when
+//! they go back runs once, at its first occurrence.
+//!
+//! Jitter: a task may start up to its `jitterMs` (else `[scheduler]
+//! jitter_ms`) after it comes due, at an offset fixed by its id, so tasks
+//! scheduled for the same minute do not all claim containers at once.
+//!
+//! Overlap: a task is not dispatched while the queue still holds a run of
+//! it. Its `next_run` only advances when that run ends, from the time it
+//! ends, so occurrences a slow run spans are skipped rather than queued.
+//!
+//! Admission control: when interactive messages have been stuck behind the
+//! container concurrency cap for longer than `max_interactive_wait`, recurring
+//! tasks of non-main groups are left due instead of dispatched. They stay in
+//! `scheduled_tasks` with their original `next_run`, so once the backlog
+//! clears they are picked up again in due-time order.
 
-use std::str::FromStr;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
-use chrono::{TimeZone, Utc};
-use intercom_core::PgPool;
+use chrono::{DateTime, Datelike, LocalResult, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use intercom_core::{RegisteredGroup, ScheduledTask, SharedStorage, TaskRunLog, TaskTemplate};
+use serde::{Deserialize, Serialize};
 use tokio::sync::watch;
 use tracing::{debug, error, info, warn};
 
+use crate::health;
+use crate::i18n;
+use crate::queue::GroupQueue;
+
 /// Configuration for the scheduler loop.
 #[derive(Debug, Clone)]
 pub struct SchedulerConfig {
@@ -26,6 +53,8 @@ pub struct SchedulerConfig {
     pub timezone: String,
     /// Whether the scheduler is enabled.
     pub enabled: bool,
+    /// Jitter for tasks that do not set their own.
+    pub jitter: Duration,
 }
 
 impl Default for SchedulerConfig {
@@ -34,13 +63,146 @@ impl Default for SchedulerConfig {
             poll_interval: Duration::from_secs(10),
             timezone: "UTC".to_string(),
             enabled: false,
+            jitter: Duration::ZERO,
+        }
+    }
+}
+
+/// Load-aware gate consulted before dispatching each due task.
+pub struct TaskAdmission {
+    queue: Arc<GroupQueue>,
+    max_interactive_wait: Duration,
+    main_group_folder: String,
+}
+
+impl TaskAdmission {
+    pub fn new(
+        queue: Arc<GroupQueue>,
+        max_interactive_wait: Duration,
+        main_group_folder: String,
+    ) -> Self {
+        Self {
+            queue,
+            max_interactive_wait,
+            main_group_folder,
         }
     }
+
+    /// Main-group tasks and one-shot reminders are never deferred; recurring
+    /// background work for other groups can wait for capacity.
+    fn is_low_priority(&self, task: &ScheduledTask) -> bool {
+        !is_critical(
+            &task.group_folder,
+            &task.schedule_type,
+            &self.main_group_folder,
+        )
+    }
+}
+
+/// Main-group tasks and one-shot reminders, which are never deferred and
+/// may preempt a busy container; the rest is background work.
+pub fn is_critical(group_folder: &str, schedule_type: &str, main_group_folder: &str) -> bool {
+    group_folder == main_group_folder || schedule_type == "once"
+}
+
+/// Whether a task should be held back given the current interactive wait.
+fn should_defer(interactive_wait: Duration, max_wait: Duration, low_priority: bool) -> bool {
+    low_priority && !max_wait.is_zero() && interactive_wait > max_wait
+}
+
+/// Per-task settings, from a task's `task_config`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskConfig {
+    /// Longest a run may start after it comes due (milliseconds).
+    pub jitter_ms: Option<u64>,
+    /// Hard limit on each run in place of the group's container timeout
+    /// (milliseconds).
+    pub timeout_ms: Option<u64>,
+    /// Reporting and auto-pause for failed runs.
+    pub on_failure: Option<FailurePolicy>,
+}
+
+impl TaskConfig {
+    /// The task's settings; unreadable settings are ignored.
+    pub fn of(task: &ScheduledTask) -> Self {
+        task.task_config
+            .as_ref()
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// What happens when a run of the task fails.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailurePolicy {
+    /// Chat told about each failed run.
+    pub notify: Option<String>,
+    /// Pause the task after this many failed runs in a row; 0 never does.
+    #[serde(default)]
+    pub pause_after: u32,
+}
+
+/// Failed runs at the head of `runs`, which are newest first.
+pub fn consecutive_failures(runs: &[TaskRunLog]) -> usize {
+    runs.iter().take_while(|run| run.status == "error").count()
+}
+
+/// Message for the failure chat about a failed run, in `locale`.
+pub fn failure_notice(
+    task_id: &str,
+    error: &str,
+    paused_after: Option<usize>,
+    locale: &str,
+) -> String {
+    let mut notice = i18n::fill(
+        locale,
+        "notice.task_failed",
+        &[("task", &task_id), ("error", &error)],
+    );
+    if let Some(failures) = paused_after {
+        notice.push_str(&i18n::fill(
+            locale,
+            "notice.task_paused",
+            &[("count", &failures)],
+        ));
+    }
+    notice
+}
+
+/// How long after coming due a task starts: a stable share of `jitter`
+/// picked by its id (FNV-1a), the same on every run.
+pub fn jitter_offset(task_id: &str, jitter: Duration) -> Duration {
+    let jitter_ms = jitter.as_millis() as u64;
+    if jitter_ms == 0 {
+        return Duration::ZERO;
+    }
+    let hash = task_id.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    Duration::from_millis(hash % (jitter_ms + 1))
+}
+
+/// Whether a due task's jitter has run out by `now`.
+fn jitter_elapsed(task: &ScheduledTask, default_jitter: Duration, now: DateTime<Utc>) -> bool {
+    let jitter = TaskConfig::of(task)
+        .jitter_ms
+        .map_or(default_jitter, Duration::from_millis);
+    let offset = jitter_offset(&task.id, jitter);
+    let Some(due) = task
+        .next_run
+        .as_deref()
+        .and_then(|next| DateTime::parse_from_rfc3339(next).ok())
+    else {
+        return true;
+    };
+    chrono::Duration::from_std(offset).is_ok_and(|offset| due + offset <= now)
 }
 
 /// Callback invoked for each due task. The scheduler passes the task details
 /// and expects the callback to enqueue container execution.
-pub type TaskCallback = Box<dyn Fn(DueTask) + Send + Sync>;
+pub type TaskCallback = Arc<dyn Fn(DueTask) + Send + Sync>;
 
 /// A task that is due for execution.
 #[derive(Debug, Clone)]
@@ -52,6 +214,281 @@ pub struct DueTask {
     pub schedule_type: String,
     pub schedule_value: String,
     pub context_mode: String,
+    pub config: TaskConfig,
+}
+
+impl From<ScheduledTask> for DueTask {
+    fn from(task: ScheduledTask) -> Self {
+        Self {
+            config: TaskConfig::of(&task),
+            id: task.id,
+            group_folder: task.group_folder,
+            chat_jid: task.chat_jid,
+            prompt: task.prompt,
+            schedule_type: task.schedule_type,
+            schedule_value: task.schedule_value,
+            context_mode: task.context_mode,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Schedules
+// ---------------------------------------------------------------------------
+
+/// How far ahead a cron expression is searched before it is taken to
+/// never match (e.g. `0 0 30 2 *`).
+const CRON_SEARCH_YEARS: i32 = 5;
+
+/// A parsed cron expression: the allowed values of each field as bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CronSchedule {
+    seconds: u64,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    /// Sunday is 0.
+    days_of_week: u64,
+    /// Day of month or day of week was `*`: the other alone decides the
+    /// day. Otherwise a day matching either runs, as in Vixie cron.
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// One field's bits, from a list of `*`, `n`, `a-b` and `/step` items.
+fn parse_field(
+    field: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+    name_base: u32,
+) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let lower = text.to_ascii_lowercase();
+        if let Some(i) = names.iter().position(|name| *name == lower) {
+            return Ok(name_base + i as u32);
+        }
+        text.parse().map_err(|_| format!("invalid value `{text}`"))
+    };
+    let mut bits = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("invalid step `{step}`"))?;
+                if step == 0 {
+                    return Err("step must be at least 1".into());
+                }
+                (range, step)
+            }
+            None => (item, 1),
+        };
+        let (start, end) = match range {
+            "*" | "?" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (value(a)?, value(b)?),
+                // `5/15` runs from 5 to the end of the range
+                None if step > 1 => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("`{item}` is outside {min}-{max}"));
+        }
+        for v in (start..=end).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+impl CronSchedule {
+    /// Parse 5 fields (minute hour day-of-month month day-of-week), 6
+    /// with seconds first, or a macro such as `@daily`.
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expanded = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let (seconds, rest) = match fields.len() {
+            5 => ("0", &fields[..]),
+            6 => (fields[0], &fields[1..]),
+            n => return Err(format!("expected 5 or 6 fields, got {n}")),
+        };
+        let any = |field: &str| field == "*" || field == "?";
+        // Day of week 7 is Sunday too
+        let mut days_of_week = parse_field(rest[4], 0, 7, &DAY_NAMES, 0)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            seconds: parse_field(seconds, 0, 59, &[], 0)?,
+            minutes: parse_field(rest[0], 0, 59, &[], 0)?,
+            hours: parse_field(rest[1], 0, 23, &[], 0)?,
+            days_of_month: parse_field(rest[2], 1, 31, &[], 0)?,
+            months: parse_field(rest[3], 1, 12, &MONTH_NAMES, 1)?,
+            days_of_week,
+            any_day_of_month: any(rest[2]),
+            any_day_of_week: any(rest[4]),
+        })
+    }
+
+    fn day_matches(&self, t: &NaiveDateTime) -> bool {
+        let dom = self.days_of_month & (1 << t.day()) != 0;
+        let dow = self.days_of_week & (1 << t.weekday().num_days_from_sunday()) != 0;
+        if self.any_day_of_month || self.any_day_of_week {
+            dom && dow
+        } else {
+            dom || dow
+        }
+    }
+
+    /// The first wall-clock time at or after `t` the expression matches.
+    fn next_local(&self, mut t: NaiveDateTime) -> Option<NaiveDateTime> {
+        let limit = t.with_year(t.year() + CRON_SEARCH_YEARS)?;
+        let midnight = |date: chrono::NaiveDate| date.and_hms_opt(0, 0, 0);
+        while t <= limit {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = midnight(chrono::NaiveDate::from_ymd_opt(year, month, 1)?)?;
+            } else if !self.day_matches(&t) {
+                t = midnight(t.date().succ_opt()?)?;
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)?.with_second(0)? + chrono::Duration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t = t.with_second(0)? + chrono::Duration::minutes(1);
+            } else if self.seconds & (1 << t.second()) == 0 {
+                t += chrono::Duration::seconds(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+
+    /// The first matching instant after `after`, on `tz`'s wall clock.
+    pub fn next_after(&self, after: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        let mut from = after.with_timezone(&tz).naive_local().with_nanosecond(0)?
+            + chrono::Duration::seconds(1);
+        loop {
+            let local = self.next_local(from)?;
+            let instant = match tz.from_local_datetime(&local) {
+                LocalResult::Single(t) => Some(t),
+                // Repeated by a fall-back: the first occurrence not yet past
+                LocalResult::Ambiguous(earlier, later) => {
+                    [earlier, later].into_iter().find(|t| *t > after)
+                }
+                // Skipped by a spring-forward: the end of the gap
+                LocalResult::None => (1..=24 * 60).find_map(|minutes| {
+                    let shifted = local.with_second(0)? + chrono::Duration::minutes(minutes);
+                    tz.from_local_datetime(&shifted).earliest()
+                }),
+            };
+            if let Some(instant) = instant
+                .map(|t| t.with_timezone(&Utc))
+                .filter(|t| *t > after)
+            {
+                return Some(instant);
+            }
+            from = local + chrono::Duration::seconds(1);
+        }
+    }
+}
+
+/// A task's `schedule_type` and `schedule_value`, parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    Cron(CronSchedule),
+    Interval(chrono::Duration),
+    /// A one-shot run: RFC 3339, or a local `YYYY-MM-DDTHH:MM[:SS]` in the
+    /// scheduler's timezone.
+    Once(DateTime<Utc>),
+}
+
+impl Schedule {
+    pub fn parse(schedule_type: &str, schedule_value: &str, tz: Tz) -> Result<Self, String> {
+        match schedule_type {
+            "cron" => CronSchedule::parse(schedule_value).map(Self::Cron),
+            "interval" => match schedule_value.trim().parse::<i64>() {
+                Ok(ms) if ms > 0 => Ok(Self::Interval(chrono::Duration::milliseconds(ms))),
+                _ => Err(format!("invalid interval ms `{schedule_value}`")),
+            },
+            "once" => {
+                let value = schedule_value.trim();
+                if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+                    return Ok(Self::Once(at.with_timezone(&Utc)));
+                }
+                [
+                    "%Y-%m-%dT%H:%M:%S",
+                    "%Y-%m-%dT%H:%M",
+                    "%Y-%m-%d %H:%M:%S",
+                    "%Y-%m-%d %H:%M",
+                ]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+                .and_then(|local| tz.from_local_datetime(&local).earliest())
+                .map(|at| Self::Once(at.with_timezone(&Utc)))
+                .ok_or_else(|| format!("invalid timestamp `{schedule_value}`"))
+            }
+            other => Err(format!("unknown schedule type `{other}`")),
+        }
+    }
+
+    /// The first run after `after`; `None` once a one-shot has passed.
+    pub fn next_after(&self, after: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Cron(cron) => cron.next_after(after, tz),
+            Self::Interval(every) => Some(after + *every),
+            Self::Once(at) => (*at > after).then_some(*at),
+        }
+    }
+}
+
+fn parse_timezone(timezone: &str) -> Tz {
+    timezone.parse().unwrap_or_else(|_| {
+        warn!(tz = timezone, "invalid timezone, falling back to UTC");
+        Tz::UTC
+    })
+}
+
+/// The next `count` times a schedule would fire after `after`, for checking
+/// a schedule before creating a task with it. Unlike task scheduling, an
+/// unknown timezone is an error rather than UTC.
+pub fn preview(
+    schedule_type: &str,
+    schedule_value: &str,
+    timezone: &str,
+    after: DateTime<Utc>,
+    count: usize,
+) -> Result<Vec<DateTime<Utc>>, String> {
+    let tz: Tz = timezone
+        .parse()
+        .map_err(|_| format!("unknown timezone `{timezone}`"))?;
+    let schedule = Schedule::parse(schedule_type, schedule_value, tz)?;
+    let mut times = Vec::with_capacity(count);
+    let mut from = after;
+    while times.len() < count {
+        let Some(next) = schedule.next_after(from, tz) else {
+            break;
+        };
+        times.push(next);
+        from = next;
+    }
+    Ok(times)
 }
 
 /// Calculate the next run time for a task after it completes.
@@ -60,43 +497,17 @@ pub fn calculate_next_run(
     schedule_value: &str,
     timezone: &str,
 ) -> Option<String> {
-    match schedule_type {
-        "cron" => {
-            let schedule = match cron::Schedule::from_str(schedule_value) {
-                Ok(s) => s,
-                Err(e) => {
-                    error!(cron = schedule_value, err = %e, "invalid cron expression");
-                    return None;
-                }
-            };
-            // Find next occurrence in the configured timezone
-            let tz: chrono_tz::Tz = match timezone.parse() {
-                Ok(t) => t,
-                Err(_) => {
-                    warn!(tz = timezone, "invalid timezone, falling back to UTC");
-                    chrono_tz::Tz::UTC
-                }
-            };
-            let now = Utc::now().with_timezone(&tz);
-            schedule
-                .after(&now)
-                .next()
-                .map(|dt| dt.with_timezone(&Utc).to_rfc3339())
-        }
-        "interval" => {
-            let ms: u64 = match schedule_value.parse() {
-                Ok(v) => v,
-                Err(e) => {
-                    error!(value = schedule_value, err = %e, "invalid interval ms");
-                    return None;
-                }
-            };
-            let next = Utc::now() + chrono::Duration::milliseconds(ms as i64);
-            Some(next.to_rfc3339())
-        }
-        "once" => None, // one-shot tasks complete after first run
-        other => {
-            warn!(schedule_type = other, "unknown schedule type");
+    // One-shot tasks complete after their run
+    if schedule_type == "once" {
+        return None;
+    }
+    let tz = parse_timezone(timezone);
+    match Schedule::parse(schedule_type, schedule_value, tz) {
+        Ok(schedule) => schedule
+            .next_after(Utc::now(), tz)
+            .map(|next| next.to_rfc3339()),
+        Err(e) => {
+            error!(schedule_type, schedule_value, err = %e, "invalid schedule");
             None
         }
     }
@@ -117,11 +528,445 @@ pub fn result_summary(result: Option<&str>, error: Option<&str>) -> String {
     }
 }
 
+/// Hand a task to `on_task`, re-reading it first in case it was paused,
+/// deleted or changed since it was found due. With `due_at`, the next run
+/// it was found due for, a task whose run ended meanwhile is skipped.
+async fn dispatch(
+    pool: &SharedStorage,
+    on_task: &TaskCallback,
+    task_id: &str,
+    due_at: Option<&str>,
+) {
+    match pool.get_task_by_id(task_id).await {
+        Ok(Some(current)) if due_at.is_some_and(|due| current.next_run.as_deref() != Some(due)) => {
+            debug!(task_id, "task ran since it was found due, skipping");
+        }
+        Ok(Some(current)) if current.status == "active" => {
+            debug!(task_id = %current.id, group = %current.group_folder, "dispatching task");
+            on_task(current.into());
+        }
+        Ok(Some(_)) => {
+            debug!(task_id, "task no longer active, skipping");
+        }
+        Ok(None) => {
+            debug!(task_id, "task deleted, skipping");
+        }
+        Err(e) => {
+            error!(task_id, err = %e, "failed to re-check task");
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Operator controls
+// ---------------------------------------------------------------------------
+
+/// Outcome of pausing, resuming or running a task on request.
+#[derive(Debug)]
+pub enum TaskControl {
+    /// Done; the task as it now stands.
+    Done(Box<ScheduledTask>),
+    NotFound,
+    /// The task has completed and can no longer be changed or run.
+    Completed,
+}
+
+async fn not_updated(pool: &SharedStorage, id: &str) -> anyhow::Result<TaskControl> {
+    Ok(match pool.get_task_by_id(id).await? {
+        Some(_) => TaskControl::Completed,
+        None => TaskControl::NotFound,
+    })
+}
+
+/// Stop a task from coming due. A run already queued or in progress is
+/// not affected.
+pub async fn pause_task(pool: &SharedStorage, id: &str) -> anyhow::Result<TaskControl> {
+    match pool.set_task_status(id, "paused", None).await? {
+        Some(task) => Ok(TaskControl::Done(Box::new(task))),
+        None => not_updated(pool, id).await,
+    }
+}
+
+/// Make a paused task active again. A recurring task whose next run
+/// passed while it was paused skips the missed runs and is rescheduled
+/// from now; a one-shot task keeps its time and runs at the next poll if
+/// that has passed.
+pub async fn resume_task(
+    pool: &SharedStorage,
+    id: &str,
+    timezone: &str,
+) -> anyhow::Result<TaskControl> {
+    let Some(task) = pool.get_task_by_id(id).await? else {
+        return Ok(TaskControl::NotFound);
+    };
+    if task.status == "completed" {
+        return Ok(TaskControl::Completed);
+    }
+    let stale = task
+        .next_run
+        .as_deref()
+        .and_then(|next| DateTime::parse_from_rfc3339(next).ok())
+        .is_none_or(|next| next <= Utc::now());
+    let next_run = (task.schedule_type != "once" && stale)
+        .then(|| calculate_next_run(&task.schedule_type, &task.schedule_value, timezone))
+        .flatten();
+    match pool
+        .set_task_status(id, "active", next_run.as_deref())
+        .await?
+    {
+        Some(task) => Ok(TaskControl::Done(Box::new(task))),
+        None => not_updated(pool, id).await,
+    }
+}
+
+/// Hand a task to `on_task` now, as if it had come due, whatever its
+/// status or next run. The run advances `next_run` like a scheduled one.
+pub async fn run_task_now(
+    pool: &SharedStorage,
+    on_task: &TaskCallback,
+    id: &str,
+) -> anyhow::Result<TaskControl> {
+    match pool.get_task_by_id(id).await? {
+        Some(task) if task.status == "completed" => Ok(TaskControl::Completed),
+        Some(task) => {
+            info!(task_id = %task.id, group = %task.group_folder, "running task on request");
+            on_task(task.clone().into());
+            Ok(TaskControl::Done(Box::new(task)))
+        }
+        None => Ok(TaskControl::NotFound),
+    }
+}
+
+/// When a new task with `schedule` first runs: the schedule's next time
+/// after `now`, or a one-shot's time even if that has passed.
+pub fn first_run(schedule: &Schedule, timezone: &str, now: DateTime<Utc>) -> Option<String> {
+    match schedule {
+        Schedule::Once(at) => Some(*at),
+        recurring => recurring.next_after(now, parse_timezone(timezone)),
+    }
+    .map(|at| at.to_rfc3339())
+}
+
+/// Tasks to create for several groups at once, from a template, explicit
+/// fields, or a template with some fields overridden.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BulkTaskRequest {
+    pub template: Option<String>,
+    pub prompt: Option<String>,
+    pub schedule_type: Option<String>,
+    pub schedule_value: Option<String>,
+    pub context_mode: Option<String>,
+    pub task_config: Option<serde_json::Value>,
+    /// Group folders or chat JIDs.
+    pub groups: Vec<String>,
+}
+
+/// A group a bulk request could not create its task for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BulkFailure {
+    pub group: String,
+    pub error: String,
+}
+
+/// Outcome of a bulk request.
+#[derive(Debug)]
+pub enum BulkTasks {
+    /// The tasks created, and the groups that got none.
+    Done {
+        created: Vec<ScheduledTask>,
+        failed: Vec<BulkFailure>,
+    },
+    UnknownTemplate,
+    Invalid(String),
+}
+
+/// A prompt and schedule checked against the scheduler's parser, shared by
+/// templates and bulk requests.
+pub fn validate_schedule(
+    schedule_type: &str,
+    schedule_value: &str,
+    timezone: &str,
+) -> Result<Schedule, String> {
+    Schedule::parse(schedule_type, schedule_value, parse_timezone(timezone))
+}
+
+/// A schedule as people write it in chat, turned into a task's type and
+/// value, with the words that followed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpokenSchedule {
+    pub schedule_type: &'static str,
+    pub schedule_value: String,
+    pub rest: String,
+}
+
+/// Parse the schedule at the start of `text`:
+///
+/// - `in 10m`, `in 2 hours`: once, that long from `now`
+/// - `at 9:30`, `tomorrow at 7pm`, `on 2026-12-24 at 18:00`: once, in
+///   `timezone` (`at` alone is the next time it comes round)
+/// - `every 15m`, `every hour`: an interval
+/// - `daily at 9am`, `every weekday at 8:30`, `every monday at 10`: cron
+/// - `cron 0 9 * * 1-5`: a cron expression as is
+pub fn parse_spoken_schedule(
+    text: &str,
+    timezone: &str,
+    now: DateTime<Utc>,
+) -> Result<SpokenSchedule, String> {
+    let tz = parse_timezone(timezone);
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let lower: Vec<String> = words.iter().map(|w| w.to_lowercase()).collect();
+    let word = |i: usize| lower.get(i).map(String::as_str).unwrap_or("");
+    let local_now = now.with_timezone(&tz);
+    let once_at =
+        |date: chrono::NaiveDate, (hour, minute): (u32, u32)| -> Result<DateTime<Utc>, String> {
+            let local = date.and_hms_opt(hour, minute, 0).ok_or("invalid time")?;
+            tz.from_local_datetime(&local)
+                .earliest()
+                .map(|at| at.with_timezone(&Utc))
+                .ok_or_else(|| format!("{local} does not exist in {tz}"))
+        };
+
+    let (schedule_type, schedule_value, used) = match word(0) {
+        "in" => {
+            let (every, used) = spoken_duration(&lower[1..])
+                .ok_or("expected a duration after `in`, e.g. `in 10m`")?;
+            ("once", (now + every).to_rfc3339(), 1 + used)
+        }
+        "every"
+            if matches!(word(1), "day" | "weekday" | "weekend")
+                || DAY_NAMES.contains(&day_prefix(word(1))) =>
+        {
+            let days = match word(1) {
+                "day" => "*".to_string(),
+                "weekday" => "1-5".to_string(),
+                "weekend" => "0,6".to_string(),
+                name => day_prefix(name).to_string(),
+            };
+            let ((hour, minute), used) = spoken_at(&lower[2..])?;
+            ("cron", format!("{minute} {hour} * * {days}"), 2 + used)
+        }
+        "every" => {
+            let (every, used) = spoken_duration(&lower[1..])
+                .ok_or("expected a duration after `every`, e.g. `every 30m`")?;
+            ("interval", every.num_milliseconds().to_string(), 1 + used)
+        }
+        "daily" => {
+            let ((hour, minute), used) = spoken_at(&lower[1..])?;
+            ("cron", format!("{minute} {hour} * * *"), 1 + used)
+        }
+        "at" => {
+            let (time, used) = spoken_at(&lower)?;
+            let today = local_now.date_naive();
+            let mut at = once_at(today, time)?;
+            if at <= now {
+                at = once_at(today.succ_opt().ok_or("date out of range")?, time)?;
+            }
+            ("once", at.to_rfc3339(), used)
+        }
+        "tomorrow" => {
+            let (time, used) = spoken_at(&lower[1..])?;
+            let tomorrow = local_now
+                .date_naive()
+                .succ_opt()
+                .ok_or("date out of range")?;
+            ("once", once_at(tomorrow, time)?.to_rfc3339(), 1 + used)
+        }
+        "on" => {
+            let date = chrono::NaiveDate::parse_from_str(word(1), "%Y-%m-%d")
+                .map_err(|_| "expected a date after `on`, e.g. `on 2026-12-24 at 18:00`")?;
+            let (time, used) = spoken_at(&lower[2..])?;
+            ("once", once_at(date, time)?.to_rfc3339(), 2 + used)
+        }
+        "cron" => {
+            if words.len() < 6 {
+                return Err("expected five cron fields after `cron`".into());
+            }
+            let expression = words[1..6].join(" ");
+            CronSchedule::parse(&expression)?;
+            ("cron", expression, 6)
+        }
+        _ => {
+            return Err(
+                "start with `in`, `at`, `tomorrow`, `on`, `every`, `daily` or `cron`".into(),
+            );
+        }
+    };
+    Ok(SpokenSchedule {
+        schedule_type,
+        schedule_value,
+        rest: words[used.min(words.len())..].join(" "),
+    })
+}
+
+/// A duration on its own, as `/mute` takes it: `30m`, `for 2 hours`, `day`.
+pub fn parse_spoken_duration(text: &str) -> Option<chrono::Duration> {
+    let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+    let words = words.strip_prefix(&["for".to_string()]).unwrap_or(&words);
+    let (duration, used) = spoken_duration(words)?;
+    (used == words.len()).then_some(duration)
+}
+
+/// `monday` and `mon` alike become `mon`.
+fn day_prefix(word: &str) -> &str {
+    word.get(..3)
+        .filter(|_| word.len() == 3 || word.ends_with("day"))
+        .unwrap_or("")
+}
+
+/// `10m`, `10 min`, `hour` (one of it); the duration and words used.
+fn spoken_duration(words: &[String]) -> Option<(chrono::Duration, usize)> {
+    let first = words.first()?;
+    let digits = first
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(first.len());
+    let (count, unit, used) = match (&first[..digits], &first[digits..]) {
+        ("", unit) => (1, unit, 1),
+        (count, "") => (count.parse::<i64>().ok()?, words.get(1)?.as_str(), 2),
+        (count, unit) => (count.parse::<i64>().ok()?, unit, 1),
+    };
+    let unit = match unit.trim_end_matches('s') {
+        "" | "sec" | "second" => chrono::Duration::seconds(1),
+        "m" | "min" | "minute" => chrono::Duration::minutes(1),
+        "h" | "hr" | "hour" => chrono::Duration::hours(1),
+        "d" | "day" => chrono::Duration::days(1),
+        "w" | "week" => chrono::Duration::weeks(1),
+        _ => return None,
+    };
+    let every = unit.checked_mul(i32::try_from(count).ok()?)?;
+    (count > 0).then_some((every, used))
+}
+
+/// `at 9`, `at 9:30pm`, `at 21:05`, `at 7 am`; the hour, minute and words used.
+fn spoken_at(words: &[String]) -> Result<((u32, u32), usize), String> {
+    const EXPECTED: &str = "expected a time, e.g. `at 9:30` or `at 7pm`";
+    if words.first().map(String::as_str) != Some("at") {
+        return Err(EXPECTED.into());
+    }
+    let time = words.get(1).ok_or(EXPECTED)?;
+    let (clock, mut meridiem) = match time.strip_suffix("am").or_else(|| time.strip_suffix("pm")) {
+        Some(clock) => (clock, Some(&time[clock.len()..])),
+        None => (time.as_str(), None),
+    };
+    let mut used = 2;
+    if meridiem.is_none() && matches!(words.get(2).map(String::as_str), Some("am" | "pm")) {
+        meridiem = words.get(2).map(String::as_str);
+        used = 3;
+    }
+    let (hour, minute) = clock.split_once(':').unwrap_or((clock, "0"));
+    let (Ok(mut hour), Ok(minute)) = (hour.parse::<u32>(), minute.parse::<u32>()) else {
+        return Err(EXPECTED.into());
+    };
+    match meridiem {
+        Some(_) if !(1..=12).contains(&hour) => return Err(format!("invalid time `{time}`")),
+        Some("am") if hour == 12 => hour = 0,
+        Some("pm") if hour < 12 => hour += 12,
+        _ => {}
+    }
+    if hour > 23 || minute > 59 {
+        return Err(format!("invalid time `{time}`"));
+    }
+    Ok(((hour, minute), used))
+}
+
+/// Create one task per group in `request.groups`. The task's id is
+/// `task-<millis>-<folder>`; its first run is the schedule's next time
+/// from now, or a one-shot's time even if that has passed.
+pub async fn create_bulk_tasks(
+    pool: &SharedStorage,
+    groups: &HashMap<String, RegisteredGroup>,
+    request: BulkTaskRequest,
+    timezone: &str,
+) -> anyhow::Result<BulkTasks> {
+    let template = match request.template.as_deref() {
+        Some(name) => match pool.get_task_template(name).await? {
+            Some(template) => Some(template),
+            None => return Ok(BulkTasks::UnknownTemplate),
+        },
+        None => None,
+    };
+    let pick = |field: Option<String>, from_template: fn(&TaskTemplate) -> &String| {
+        field.or_else(|| template.as_ref().map(|t| from_template(t).clone()))
+    };
+    let (Some(prompt), Some(schedule_type), Some(schedule_value)) = (
+        pick(request.prompt, |t| &t.prompt).filter(|p| !p.trim().is_empty()),
+        pick(request.schedule_type, |t| &t.schedule_type),
+        pick(request.schedule_value, |t| &t.schedule_value),
+    ) else {
+        return Ok(BulkTasks::Invalid(
+            "prompt, schedule_type and schedule_value are required".into(),
+        ));
+    };
+    let context_mode =
+        pick(request.context_mode, |t| &t.context_mode).unwrap_or_else(|| "isolated".into());
+    let task_config = request
+        .task_config
+        .or_else(|| template.as_ref().and_then(|t| t.task_config.clone()));
+    if request.groups.is_empty() {
+        return Ok(BulkTasks::Invalid("groups is empty".into()));
+    }
+    let schedule = match validate_schedule(&schedule_type, &schedule_value, timezone) {
+        Ok(schedule) => schedule,
+        Err(e) => return Ok(BulkTasks::Invalid(e)),
+    };
+
+    let now = Utc::now();
+    let next_run = first_run(&schedule, timezone, now);
+    let mut created = Vec::new();
+    let mut failed = Vec::new();
+    for target in &request.groups {
+        let Some((jid, group)) = groups
+            .iter()
+            .find(|(jid, g)| *jid == target || g.folder == *target)
+        else {
+            failed.push(BulkFailure {
+                group: target.clone(),
+                error: "no such group".into(),
+            });
+            continue;
+        };
+        let task = ScheduledTask {
+            id: format!("task-{}-{}", now.timestamp_millis(), group.folder),
+            group_folder: group.folder.clone(),
+            chat_jid: jid.clone(),
+            prompt: prompt.clone(),
+            schedule_type: schedule_type.clone(),
+            schedule_value: schedule_value.clone(),
+            context_mode: context_mode.clone(),
+            next_run: next_run.clone(),
+            last_run: None,
+            last_result: None,
+            status: "active".into(),
+            created_at: now.to_rfc3339(),
+            task_config: task_config.clone(),
+        };
+        match pool.create_task(&task).await {
+            Ok(()) => created.push(task),
+            Err(e) => failed.push(BulkFailure {
+                group: target.clone(),
+                error: format!("{e:#}"),
+            }),
+        }
+    }
+    info!(
+        template = request.template.as_deref().unwrap_or(""),
+        created = created.len(),
+        failed = failed.len(),
+        "created tasks in bulk"
+    );
+    Ok(BulkTasks::Done { created, failed })
+}
+
 /// Run the scheduler poll loop. Exits when `shutdown` signal fires.
+///
+/// `restored` are ids of tasks the queue held when intercomd last stopped;
+/// they are dispatched first, in order, if still active.
 pub async fn run_scheduler_loop(
     config: SchedulerConfig,
-    pool: PgPool,
+    pool: SharedStorage,
+    queue: Arc<GroupQueue>,
     on_task: TaskCallback,
+    admission: Option<TaskAdmission>,
+    restored: Vec<String>,
     mut shutdown: watch::Receiver<bool>,
 ) {
     if !config.enabled {
@@ -131,8 +976,15 @@ pub async fn run_scheduler_loop(
     info!(
         poll_interval_ms = config.poll_interval.as_millis(),
         timezone = %config.timezone,
+        restored = restored.len(),
         "scheduler loop started"
     );
+    for task_id in &restored {
+        dispatch(&pool, &on_task, task_id, None).await;
+    }
+
+    // Number of tasks held back on the previous poll, for resume logging.
+    let mut deferred_last_poll = 0usize;
 
     loop {
         tokio::select! {
@@ -146,39 +998,60 @@ pub async fn run_scheduler_loop(
         }
 
         match pool.get_due_tasks().await {
-            Ok(tasks) => {
+            Ok(mut tasks) => {
                 if !tasks.is_empty() {
                     info!(count = tasks.len(), "found due tasks");
                 }
+                // Earliest-due first, so tasks deferred across several polls
+                // run ahead of ones that only just became due.
+                tasks.sort_by(|a, b| a.next_run.cmp(&b.next_run));
+
+                let interactive_wait = match admission {
+                    Some(ref a) => a.queue.interactive_wait().await,
+                    None => Duration::ZERO,
+                };
+                let mut deferred = 0usize;
+                let now = Utc::now();
+
                 for task in tasks {
-                    // Re-verify status in case it changed between query and processing
-                    match pool.get_task_by_id(&task.id).await {
-                        Ok(Some(current)) if current.status == "active" => {
-                            debug!(task_id = %current.id, group = %current.group_folder, "dispatching task");
-                            on_task(DueTask {
-                                id: current.id,
-                                group_folder: current.group_folder,
-                                chat_jid: current.chat_jid,
-                                prompt: current.prompt,
-                                schedule_type: current.schedule_type,
-                                schedule_value: current.schedule_value,
-                                context_mode: current.context_mode,
-                            });
-                        }
-                        Ok(Some(_)) => {
-                            debug!(task_id = %task.id, "task no longer active, skipping");
-                        }
-                        Ok(None) => {
-                            debug!(task_id = %task.id, "task deleted, skipping");
-                        }
-                        Err(e) => {
-                            error!(task_id = %task.id, err = %e, "failed to re-check task");
+                    if !jitter_elapsed(&task, config.jitter, now) {
+                        continue;
+                    }
+                    if queue.has_task(&task.id).await {
+                        debug!(task_id = %task.id, "previous run still active, skipping");
+                        continue;
+                    }
+                    if let Some(ref a) = admission {
+                        if should_defer(
+                            interactive_wait,
+                            a.max_interactive_wait,
+                            a.is_low_priority(&task),
+                        ) {
+                            deferred += 1;
+                            continue;
                         }
                     }
+
+                    dispatch(&pool, &on_task, &task.id, task.next_run.as_deref()).await;
                 }
+
+                if deferred > 0 {
+                    warn!(
+                        deferred,
+                        interactive_wait_ms = interactive_wait.as_millis() as u64,
+                        "interactive queue backed up, deferring low-priority tasks"
+                    );
+                } else if deferred_last_poll > 0 {
+                    info!(
+                        released = deferred_last_poll,
+                        "interactive queue recovered, resuming deferred tasks"
+                    );
+                }
+                deferred_last_poll = deferred;
             }
             Err(e) => {
                 error!(err = %e, "failed to query due tasks");
+                health::record_error(health::SUBSYSTEM_DB, &e);
             }
         }
     }
@@ -210,6 +1083,170 @@ mod tests {
         assert!(next.is_some());
     }
 
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn next(expression: &str, after: &str, tz: Tz) -> String {
+        CronSchedule::parse(expression)
+            .unwrap()
+            .next_after(utc(after), tz)
+            .unwrap()
+            .to_rfc3339()
+    }
+
+    #[test]
+    fn calculate_next_run_five_field_cron() {
+        assert!(calculate_next_run("cron", "0 9 * * *", "Europe/Berlin").is_some());
+        assert!(calculate_next_run("cron", "*/15 9-17 * * mon-fri", "UTC").is_some());
+    }
+
+    #[test]
+    fn cron_fields_ranges_steps_and_names() {
+        let tz = Tz::UTC;
+        assert_eq!(
+            next("*/20 * * * *", "2026-01-01T10:05:00Z", tz),
+            "2026-01-01T10:20:00+00:00"
+        );
+        assert_eq!(
+            next("5/20 * * * *", "2026-01-01T10:45:00Z", tz),
+            "2026-01-01T11:05:00+00:00"
+        );
+        assert_eq!(
+            next("0 9 * * MON-FRI", "2026-01-02T09:00:00Z", tz),
+            "2026-01-05T09:00:00+00:00"
+        );
+        assert_eq!(
+            next("0 0 1 jan,jul *", "2026-02-01T00:00:00Z", tz),
+            "2026-07-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            next("0 0 * * 7", "2026-01-01T00:00:00Z", tz),
+            "2026-01-04T00:00:00+00:00"
+        );
+        assert_eq!(
+            next("30 0 12 * * *", "2026-01-01T12:00:00Z", tz),
+            "2026-01-01T12:00:30+00:00"
+        );
+        assert_eq!(
+            next("@weekly", "2026-01-01T00:00:00Z", tz),
+            "2026-01-04T00:00:00+00:00"
+        );
+        // Day of month and day of week both restricted: either matches.
+        assert_eq!(
+            next("0 0 13 * 5", "2026-02-01T00:00:00Z", tz),
+            "2026-02-06T00:00:00+00:00"
+        );
+        assert_eq!(
+            next("0 0 13 * *", "2026-02-01T00:00:00Z", tz),
+            "2026-02-13T00:00:00+00:00"
+        );
+        assert!(
+            CronSchedule::parse("0 0 30 2 *")
+                .unwrap()
+                .next_after(utc("2026-01-01T00:00:00Z"), tz)
+                .is_none()
+        );
+
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "* * * * * * *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "0 0 * foo *",
+        ] {
+            assert!(CronSchedule::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn cron_keeps_wall_clock_across_dst() {
+        let ny: Tz = "America/New_York".parse().unwrap();
+        // 09:00 local is 14:00Z in winter and 13:00Z once clocks go forward.
+        assert_eq!(
+            next("0 9 * * *", "2026-03-07T14:00:00Z", ny),
+            "2026-03-08T13:00:00+00:00"
+        );
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        assert_eq!(
+            next("0 9 * * *", "2026-10-24T07:00:00Z", berlin),
+            "2026-10-25T08:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn cron_time_skipped_by_spring_forward_runs_at_the_gap_end() {
+        let ny: Tz = "America/New_York".parse().unwrap();
+        // 02:30 does not exist on 2026-03-08: it runs at 03:00 EDT.
+        assert_eq!(
+            next("30 2 * * *", "2026-03-07T07:30:00Z", ny),
+            "2026-03-08T07:00:00+00:00"
+        );
+        assert_eq!(
+            next("30 2 * * *", "2026-03-08T07:00:00Z", ny),
+            "2026-03-09T06:30:00+00:00"
+        );
+        // The whole skipped hour collapses into one run.
+        assert_eq!(
+            next("*/15 * * * *", "2026-03-08T06:45:00Z", ny),
+            "2026-03-08T07:00:00+00:00"
+        );
+        assert_eq!(
+            next("*/15 * * * *", "2026-03-08T07:00:00Z", ny),
+            "2026-03-08T07:15:00+00:00"
+        );
+    }
+
+    #[test]
+    fn cron_time_repeated_by_fall_back_runs_once() {
+        let ny: Tz = "America/New_York".parse().unwrap();
+        // 01:30 happens at 05:30Z (EDT) and again at 06:30Z (EST) on 2026-11-01.
+        assert_eq!(
+            next("30 1 * * *", "2026-10-31T05:30:00Z", ny),
+            "2026-11-01T05:30:00+00:00"
+        );
+        assert_eq!(
+            next("30 1 * * *", "2026-11-01T05:30:00Z", ny),
+            "2026-11-02T06:30:00+00:00"
+        );
+        // Found from within the repeated hour, the second occurrence is next.
+        assert_eq!(
+            next("30 1 * * *", "2026-11-01T06:10:00Z", ny),
+            "2026-11-01T06:30:00+00:00"
+        );
+        // Hourly runs go by the wall clock, so the repeated 01:00 runs once.
+        assert_eq!(
+            next("0 * * * *", "2026-11-01T05:00:00Z", ny),
+            "2026-11-01T07:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn interval_and_once_schedules() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let after = utc("2026-06-01T10:00:00Z");
+        let every = Schedule::parse("interval", "90000", berlin).unwrap();
+        assert_eq!(
+            every.next_after(after, berlin),
+            Some(utc("2026-06-01T10:01:30Z"))
+        );
+        assert!(Schedule::parse("interval", "0", berlin).is_err());
+
+        let local = Schedule::parse("once", "2026-06-01T14:30", berlin).unwrap();
+        assert_eq!(local, Schedule::Once(utc("2026-06-01T12:30:00Z")));
+        assert_eq!(
+            local.next_after(after, berlin),
+            Some(utc("2026-06-01T12:30:00Z"))
+        );
+        assert_eq!(local.next_after(utc("2026-06-02T00:00:00Z"), berlin), None);
+        let absolute = Schedule::parse("once", "2026-06-01T14:30:00Z", berlin).unwrap();
+        assert_eq!(absolute, Schedule::Once(utc("2026-06-01T14:30:00Z")));
+        assert!(Schedule::parse("once", "tomorrow", berlin).is_err());
+    }
+
     #[test]
     fn calculate_next_run_invalid_cron() {
         let next = calculate_next_run("cron", "not a cron", "UTC");
@@ -228,6 +1265,399 @@ mod tests {
         assert!(next.is_none());
     }
 
+    #[test]
+    fn should_defer_only_low_priority_over_threshold() {
+        let max = Duration::from_secs(30);
+        assert!(should_defer(Duration::from_secs(31), max, true));
+        assert!(!should_defer(Duration::from_secs(31), max, false));
+        assert!(!should_defer(Duration::from_secs(29), max, true));
+        // Zero threshold disables admission control.
+        assert!(!should_defer(
+            Duration::from_secs(600),
+            Duration::ZERO,
+            true
+        ));
+    }
+
+    #[test]
+    fn main_group_and_one_shot_tasks_are_not_low_priority() {
+        let admission = TaskAdmission::new(
+            Arc::new(GroupQueue::new(
+                1,
+                std::path::PathBuf::from("/tmp/test-queue"),
+            )),
+            Duration::from_secs(30),
+            "main".into(),
+        );
+        let task = |folder: &str, schedule_type: &str| ScheduledTask {
+            id: "t".into(),
+            group_folder: folder.into(),
+            chat_jid: "tg:1".into(),
+            prompt: "p".into(),
+            schedule_type: schedule_type.into(),
+            schedule_value: String::new(),
+            context_mode: "isolated".into(),
+            next_run: None,
+            last_run: None,
+            last_result: None,
+            status: "active".into(),
+            created_at: String::new(),
+            task_config: None,
+        };
+        assert!(admission.is_low_priority(&task("team", "cron")));
+        assert!(admission.is_low_priority(&task("team", "interval")));
+        assert!(!admission.is_low_priority(&task("team", "once")));
+        assert!(!admission.is_low_priority(&task("main", "cron")));
+    }
+
+    #[test]
+    fn jitter_spreads_tasks_at_fixed_offsets() {
+        let jitter = Duration::from_secs(60);
+        let offsets: Vec<_> = (0..20)
+            .map(|i| jitter_offset(&format!("task-{i}"), jitter))
+            .collect();
+        assert!(offsets.iter().all(|offset| *offset <= jitter));
+        assert!(offsets.iter().any(|offset| *offset != offsets[0]));
+        assert_eq!(jitter_offset("task-3", jitter), offsets[3]);
+        assert_eq!(jitter_offset("task-3", Duration::ZERO), Duration::ZERO);
+
+        let mut task = ScheduledTask {
+            id: "task-3".into(),
+            group_folder: "team".into(),
+            chat_jid: "tg:1".into(),
+            prompt: "p".into(),
+            schedule_type: "cron".into(),
+            schedule_value: "0 9 * * *".into(),
+            context_mode: "isolated".into(),
+            next_run: Some("2026-01-01T09:00:00.000Z".into()),
+            last_run: None,
+            last_result: None,
+            status: "active".into(),
+            created_at: String::new(),
+            task_config: None,
+        };
+        let due = utc("2026-01-01T09:00:00Z");
+        let at = |offset: Duration| due + chrono::Duration::from_std(offset).unwrap();
+        assert!(jitter_elapsed(&task, Duration::ZERO, due));
+        assert!(!jitter_elapsed(
+            &task,
+            jitter,
+            at(offsets[3]) - chrono::Duration::milliseconds(1)
+        ));
+        assert!(jitter_elapsed(&task, jitter, at(offsets[3])));
+        // The task's own setting wins over the default.
+        task.task_config = Some(serde_json::json!({ "jitterMs": 0 }));
+        assert!(jitter_elapsed(&task, jitter, due));
+    }
+
+    #[tokio::test]
+    async fn tasks_that_ran_since_they_were_found_due_are_not_dispatched_again() {
+        let pool: SharedStorage = Arc::new(intercom_core::SqliteStore::new(":memory:"));
+        pool.create_task(&ScheduledTask {
+            id: "hourly".into(),
+            group_folder: "team".into(),
+            chat_jid: "tg:1".into(),
+            prompt: "p".into(),
+            schedule_type: "interval".into(),
+            schedule_value: "3600000".into(),
+            context_mode: "isolated".into(),
+            next_run: Some("2024-01-01T00:00:00Z".into()),
+            last_run: None,
+            last_result: None,
+            status: "active".into(),
+            created_at: "2024-01-01T00:00:00Z".into(),
+            task_config: None,
+        })
+        .await
+        .unwrap();
+        let due = pool.get_due_tasks().await.unwrap().remove(0);
+
+        let ran = Arc::new(std::sync::Mutex::new(0));
+        let ran_cb = ran.clone();
+        let on_task: TaskCallback = Arc::new(move |_| *ran_cb.lock().unwrap() += 1);
+        // The previous run ends between the poll and the dispatch.
+        pool.update_task_after_run("hourly", Some("2099-01-01T00:00:00Z"), "done")
+            .await
+            .unwrap();
+        dispatch(&pool, &on_task, "hourly", due.next_run.as_deref()).await;
+        assert_eq!(*ran.lock().unwrap(), 0);
+
+        let due = pool.get_task_by_id("hourly").await.unwrap().unwrap();
+        dispatch(&pool, &on_task, "hourly", due.next_run.as_deref()).await;
+        assert_eq!(*ran.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn tasks_pause_resume_and_run_on_request() {
+        let pool: SharedStorage = Arc::new(intercom_core::SqliteStore::new(":memory:"));
+        let task = |id: &str, schedule_type: &str, schedule_value: &str| ScheduledTask {
+            id: id.into(),
+            group_folder: "team".into(),
+            chat_jid: "tg:1".into(),
+            prompt: "p".into(),
+            schedule_type: schedule_type.into(),
+            schedule_value: schedule_value.into(),
+            context_mode: "isolated".into(),
+            next_run: Some("2024-01-01T00:00:00Z".into()),
+            last_run: None,
+            last_result: None,
+            status: "active".into(),
+            created_at: "2024-01-01T00:00:00Z".into(),
+            task_config: None,
+        };
+        pool.create_task(&task("hourly", "interval", "3600000"))
+            .await
+            .unwrap();
+        pool.create_task(&task("reminder", "once", "2024-01-01T00:00:00Z"))
+            .await
+            .unwrap();
+
+        let TaskControl::Done(paused) = pause_task(&pool, "hourly").await.unwrap() else {
+            panic!()
+        };
+        assert_eq!(paused.status, "paused");
+        assert!(
+            pool.get_due_tasks()
+                .await
+                .unwrap()
+                .iter()
+                .all(|t| t.id != "hourly")
+        );
+        assert!(matches!(
+            pause_task(&pool, "missing").await.unwrap(),
+            TaskControl::NotFound
+        ));
+
+        // The missed runs are skipped: the next one is an interval from now.
+        let TaskControl::Done(resumed) = resume_task(&pool, "hourly", "UTC").await.unwrap() else {
+            panic!()
+        };
+        assert_eq!(resumed.status, "active");
+        let next = DateTime::parse_from_rfc3339(resumed.next_run.as_deref().unwrap()).unwrap();
+        assert!(next > Utc::now() + chrono::Duration::minutes(59));
+
+        pause_task(&pool, "reminder").await.unwrap();
+        let TaskControl::Done(reminder) = resume_task(&pool, "reminder", "UTC").await.unwrap()
+        else {
+            panic!()
+        };
+        assert_eq!(
+            reminder.next_run.as_deref(),
+            Some("2024-01-01T00:00:00.000Z")
+        );
+
+        let ran = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let ran_cb = ran.clone();
+        let on_task: TaskCallback =
+            Arc::new(move |task: DueTask| ran_cb.lock().unwrap().push(task.id));
+        pause_task(&pool, "hourly").await.unwrap();
+        assert!(matches!(
+            run_task_now(&pool, &on_task, "hourly").await.unwrap(),
+            TaskControl::Done(_)
+        ));
+        assert_eq!(*ran.lock().unwrap(), ["hourly"]);
+
+        pool.update_task_after_run("reminder", None, "done")
+            .await
+            .unwrap();
+        assert!(matches!(
+            run_task_now(&pool, &on_task, "reminder").await.unwrap(),
+            TaskControl::Completed
+        ));
+        assert!(matches!(
+            resume_task(&pool, "reminder", "UTC").await.unwrap(),
+            TaskControl::Completed
+        ));
+        assert_eq!(ran.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn bulk_requests_roll_a_template_out_to_groups() {
+        let pool: SharedStorage = Arc::new(intercom_core::SqliteStore::new(":memory:"));
+        pool.upsert_task_template(&TaskTemplate {
+            name: "standup".into(),
+            prompt: "Post the standup".into(),
+            schedule_type: "cron".into(),
+            schedule_value: "0 9 * * 1-5".into(),
+            context_mode: "group".into(),
+            task_config: Some(serde_json::json!({"jitterMs": 60000})),
+            created_at: String::new(),
+            updated_at: String::new(),
+        })
+        .await
+        .unwrap();
+        let groups: HashMap<String, RegisteredGroup> = ["team-a", "team-b"]
+            .into_iter()
+            .map(|folder| {
+                let jid = format!("tg:{folder}");
+                let group = RegisteredGroup {
+                    jid: jid.clone(),
+                    name: folder.into(),
+                    folder: folder.into(),
+                    trigger: "@Andy".into(),
+                    added_at: "2024-01-01T00:00:00Z".into(),
+                    container_config: None,
+                    requires_trigger: None,
+                    runtime: None,
+                    model: None,
+                    muted_until: None,
+                    locale: None,
+                };
+                (jid, group)
+            })
+            .collect();
+        let request = |template: Option<&str>, groups: &[&str]| BulkTaskRequest {
+            template: template.map(Into::into),
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+            ..Default::default()
+        };
+
+        let mut by_folder_and_jid = request(Some("standup"), &["team-a", "tg:team-b", "team-c"]);
+        by_folder_and_jid.schedule_value = Some("30 9 * * 1-5".into());
+        let BulkTasks::Done { created, failed } =
+            create_bulk_tasks(&pool, &groups, by_folder_and_jid, "UTC")
+                .await
+                .unwrap()
+        else {
+            panic!("expected tasks to be created");
+        };
+        assert_eq!(
+            failed,
+            [BulkFailure {
+                group: "team-c".into(),
+                error: "no such group".into()
+            }]
+        );
+        assert_eq!(created.len(), 2);
+        for task in &created {
+            let stored = pool.get_task_by_id(&task.id).await.unwrap().unwrap();
+            assert_eq!(stored.chat_jid, format!("tg:{}", stored.group_folder));
+            assert_eq!(
+                (stored.prompt.as_str(), stored.schedule_value.as_str()),
+                ("Post the standup", "30 9 * * 1-5")
+            );
+            assert_eq!(stored.context_mode, "group");
+            assert_eq!(TaskConfig::of(&stored).jitter_ms, Some(60_000));
+            assert!(stored.next_run.is_some());
+        }
+
+        assert!(matches!(
+            create_bulk_tasks(&pool, &groups, request(Some("retro"), &["team-a"]), "UTC")
+                .await
+                .unwrap(),
+            BulkTasks::UnknownTemplate
+        ));
+        assert!(matches!(
+            create_bulk_tasks(&pool, &groups, request(None, &["team-a"]), "UTC")
+                .await
+                .unwrap(),
+            BulkTasks::Invalid(_)
+        ));
+        let mut bad_cron = request(Some("standup"), &["team-a"]);
+        bad_cron.schedule_value = Some("every morning".into());
+        assert!(matches!(
+            create_bulk_tasks(&pool, &groups, bad_cron, "UTC")
+                .await
+                .unwrap(),
+            BulkTasks::Invalid(_)
+        ));
+    }
+
+    #[test]
+    fn previews_list_upcoming_fire_times() {
+        let after = DateTime::parse_from_rfc3339("2026-03-27T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let times = |kind: &str, value: &str, tz: &str, count: usize| {
+            preview(kind, value, tz, after, count)
+                .map(|t| t.iter().map(|t| t.to_rfc3339()).collect::<Vec<_>>())
+        };
+        // Berlin moves to summer time on the 29th
+        assert_eq!(
+            times("cron", "0 9 * * *", "Europe/Berlin", 3).unwrap(),
+            [
+                "2026-03-28T08:00:00+00:00",
+                "2026-03-29T07:00:00+00:00",
+                "2026-03-30T07:00:00+00:00"
+            ]
+        );
+        assert_eq!(
+            times("interval", "3600000", "UTC", 2).unwrap(),
+            ["2026-03-27T13:00:00+00:00", "2026-03-27T14:00:00+00:00"]
+        );
+        assert_eq!(
+            times("once", "2026-04-01T10:00", "UTC", 5).unwrap(),
+            ["2026-04-01T10:00:00+00:00"]
+        );
+        assert!(
+            times("once", "2026-01-01T10:00:00Z", "UTC", 5)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            times("cron", "0 9 * *", "UTC", 1)
+                .unwrap_err()
+                .contains("5 or 6 fields")
+        );
+        assert!(
+            times("cron", "0 9 * * *", "Mars/Olympus", 1)
+                .unwrap_err()
+                .contains("timezone")
+        );
+    }
+
+    #[test]
+    fn spoken_schedules() {
+        let now: DateTime<Utc> = "2026-10-17T08:00:00Z".parse().unwrap();
+        let spoken = |text: &str| parse_spoken_schedule(text, "UTC", now);
+
+        let s = spoken("in 10m Check the Build").unwrap();
+        assert_eq!(
+            (s.schedule_type, s.schedule_value.as_str()),
+            ("once", "2026-10-17T08:10:00+00:00")
+        );
+        assert_eq!(s.rest, "Check the Build");
+
+        // 7:30 has passed today, so it rolls over to tomorrow.
+        let s = spoken("at 7:30 stretch").unwrap();
+        assert_eq!(s.schedule_value, "2026-10-18T07:30:00+00:00");
+        let s = spoken("tomorrow at 7 pm dinner").unwrap();
+        assert_eq!(
+            (s.schedule_value.as_str(), s.rest.as_str()),
+            ("2026-10-18T19:00:00+00:00", "dinner")
+        );
+
+        let s = spoken("every 2 hours ping").unwrap();
+        assert_eq!(
+            (s.schedule_type, s.schedule_value.as_str()),
+            ("interval", "7200000")
+        );
+        let s = spoken("every weekday at 8:30am standup").unwrap();
+        assert_eq!(
+            (s.schedule_type, s.schedule_value.as_str()),
+            ("cron", "30 8 * * 1-5")
+        );
+        assert_eq!(
+            spoken("every Monday at 10 plan").unwrap().schedule_value,
+            "0 10 * * mon"
+        );
+        assert_eq!(
+            spoken("daily at 12am digest").unwrap().schedule_value,
+            "0 0 * * *"
+        );
+        assert_eq!(spoken("cron 0 9 * * 1-5 report").unwrap().rest, "report");
+
+        // Times are read in the scheduler's timezone.
+        let s =
+            parse_spoken_schedule("on 2026-12-24 at 18:00 lights", "Europe/Berlin", now).unwrap();
+        assert_eq!(s.schedule_value, "2026-12-24T17:00:00+00:00");
+
+        assert!(spoken("soon do it").is_err());
+        assert!(spoken("in forever").is_err());
+        assert!(spoken("daily at 25:00").is_err());
+        assert!(spoken("cron 0 9 *").is_err());
+    }
+
     #[test]
     fn result_summary_error() {
         let s = result_summary(None, Some("connection refused"));
@@ -4,65 +4,99 @@
 //! due task. The callback enqueues a `TaskFn` into `GroupQueue` that:
 //! 1. Resolves group and session state
 //! 2. Runs `run_container_agent()` with the task prompt
-//! 3. Sends output to Telegram
+//! 3. Sends output to the chat's channel
 //! 4. Logs the run and advances next_run in Postgres
 
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use intercom_core::{ContainerInput, ContainerOutput, ContainerStatus, PgPool, RegisteredGroup};
+use intercom_core::{
+    ContainerInput, ContainerOutput, ContainerStatus, RegisteredGroup, SharedStorage,
+    new_correlation_id,
+};
 use tokio::sync::RwLock;
-use tracing::{error, info, warn};
+use tracing::{Instrument, error, info, info_span, warn};
 
+use crate::channels::ChannelRegistry;
+use crate::container::context::{ContextInputs, GroupContext, write_group_context};
 use crate::container::mounts::GroupInfo;
-use crate::container::runner::{RunConfig, run_container_agent, write_snapshots};
+use crate::container::runner::{RunConfig, record_run, run_container_agent, write_snapshots};
 use crate::container::security::ContainerConfig;
+use crate::event_stream::{self, LiveEvent, LiveEventKind};
+use crate::health;
+use crate::i18n;
+use crate::outbound::strip_internal_blocks;
 use crate::process_group::resolve_runtime;
 use crate::queue::GroupQueue;
-use crate::scheduler::{DueTask, TaskCallback, calculate_next_run, result_summary};
-use crate::telegram::TelegramBridge;
+use crate::scheduler::{
+    DueTask, FailurePolicy, TaskCallback, calculate_next_run, consecutive_failures, failure_notice,
+    is_critical, result_summary,
+};
+use crate::webhooks::{self, LifecycleEvent, LifecycleEventKind};
+use crate::workspace_git::{self, RunPhase};
 
 /// Build the `TaskCallback` that the scheduler loop invokes for each due task.
 ///
 /// The callback captures all shared state and enqueues a `TaskFn` into the
 /// `GroupQueue` for per-group serialized execution.
 pub fn build_task_callback(
-    pool: PgPool,
+    pool: SharedStorage,
     queue: Arc<GroupQueue>,
     groups: Arc<RwLock<HashMap<String, RegisteredGroup>>>,
     sessions: Arc<RwLock<HashMap<String, String>>>,
-    telegram: Arc<TelegramBridge>,
+    channels: Arc<ChannelRegistry>,
     run_config: RunConfig,
     timezone: String,
+    main_group_folder: String,
 ) -> TaskCallback {
-    Box::new(move |task: DueTask| {
+    Arc::new(move |task: DueTask| {
         let pool = pool.clone();
         let queue = queue.clone();
         let groups = groups.clone();
         let sessions = sessions.clone();
-        let telegram = telegram.clone();
+        let channels = channels.clone();
         let run_config = run_config.clone();
         let timezone = timezone.clone();
 
         let task_id = task.id.clone();
         let chat_jid = task.chat_jid.clone();
+        let critical = is_critical(&task.group_folder, &task.schedule_type, &main_group_folder);
+        // Each due run is its own ingress event.
+        let correlation_id = new_correlation_id();
+        let span =
+            info_span!("scheduled_task", task_id = %task_id, correlation_id = %correlation_id);
 
         // Clone queue before moving it into the task_fn closure
         let queue_for_enqueue = queue.clone();
 
-        let task_fn = Box::new(move || -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>> {
-            Box::pin(async move {
-                run_scheduled_task(
-                    task, &pool, &queue, &groups, &sessions, &telegram, &run_config, &timezone,
+        let task_fn = Box::new(
+            move || -> std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>> {
+                Box::pin(
+                    async move {
+                        run_scheduled_task(
+                            task,
+                            &correlation_id,
+                            &pool,
+                            &queue,
+                            &groups,
+                            &sessions,
+                            &channels,
+                            &run_config,
+                            &timezone,
+                        )
+                        .await;
+                    }
+                    .instrument(span),
                 )
-                .await;
-            })
-        });
+            },
+        );
 
         // Fire-and-forget: enqueue_task is async, so spawn a small task to call it
         tokio::spawn(async move {
-            queue_for_enqueue.enqueue_task(&chat_jid, &task_id, task_fn).await;
+            queue_for_enqueue
+                .enqueue_task_as(&chat_jid, &task_id, critical, task_fn)
+                .await;
         });
     })
 }
@@ -70,15 +104,26 @@ pub fn build_task_callback(
 /// Execute a single scheduled task inside a container.
 async fn run_scheduled_task(
     task: DueTask,
-    pool: &PgPool,
+    correlation_id: &str,
+    pool: &SharedStorage,
     queue: &Arc<GroupQueue>,
     groups: &Arc<RwLock<HashMap<String, RegisteredGroup>>>,
     sessions: &Arc<RwLock<HashMap<String, String>>>,
-    telegram: &Arc<TelegramBridge>,
+    channels: &Arc<ChannelRegistry>,
     run_config: &RunConfig,
     timezone: &str,
 ) {
     let start = Instant::now();
+    event_stream::publish(
+        LiveEvent::new(LiveEventKind::TaskFired, &task.chat_jid)
+            .with_group(&task.group_folder)
+            .with_task(&task.id)
+            .with_correlation_id(Some(correlation_id)),
+    );
+    let run_config = &RunConfig {
+        run_timeout: task.config.timeout_ms.map(Duration::from_millis),
+        ..run_config.clone()
+    };
     let assistant_name = std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into());
 
     // Look up group
@@ -92,7 +137,18 @@ async fn run_scheduled_task(
                     group_folder = task.group_folder.as_str(),
                     "scheduled task references unknown group folder"
                 );
-                log_and_update(pool, &task, start, None, Some("Unknown group folder"), timezone).await;
+                log_and_update(
+                    pool,
+                    channels,
+                    &task,
+                    correlation_id,
+                    start,
+                    None,
+                    Some("Unknown group folder"),
+                    timezone,
+                    i18n::DEFAULT_LOCALE,
+                )
+                .await;
                 return;
             }
         }
@@ -109,6 +165,9 @@ async fn run_scheduled_task(
     };
 
     let runtime = resolve_runtime(&group);
+    queue
+        .set_correlation_id(&task.chat_jid, correlation_id)
+        .await;
 
     let input = ContainerInput {
         prompt: task.prompt.clone(),
@@ -120,6 +179,10 @@ async fn run_scheduled_task(
         assistant_name: Some(assistant_name),
         model: group.model.clone(),
         secrets: None,
+        attachments: Vec::new(),
+        correlation_id: Some(correlation_id.to_string()),
+        reply_to_message_id: None,
+        stdin_keepalive: None,
     };
 
     let group_info = GroupInfo {
@@ -130,9 +193,13 @@ async fn run_scheduled_task(
             .as_ref()
             .and_then(|v| serde_json::from_value::<ContainerConfig>(v.clone()).ok()),
     };
+    let webhook = group_info
+        .container_config
+        .as_ref()
+        .and_then(|c| c.webhook.clone());
 
-    // Output callback — sends results to Telegram, tracks session
-    let telegram_cb = telegram.clone();
+    // Output callback — sends results to the chat, tracks session
+    let channels_cb = channels.clone();
     let sessions_cb = sessions.clone();
     let pool_cb = pool.clone();
     let queue_cb = queue.clone();
@@ -146,7 +213,7 @@ async fn run_scheduled_task(
 
     let on_output: Option<Arc<crate::container::runner::OutputCallback>> = Some(Arc::new(Box::new(
         move |output: ContainerOutput| {
-            let telegram = telegram_cb.clone();
+            let channels = channels_cb.clone();
             let sessions = sessions_cb.clone();
             let pool = pool_cb.clone();
             let queue = queue_cb.clone();
@@ -166,10 +233,12 @@ async fn run_scheduled_task(
                 }
 
                 // Send results to user
-                if let Some(ref text) = output.result {
+                if let Some(ref result_text) = output.result {
+                    let text = strip_internal_blocks(result_text);
                     if !text.is_empty() {
-                        if let Err(e) = telegram.send_text_to_jid(&chat_jid, text).await {
-                            error!(err = %e, "failed to send task output via Telegram");
+                        match channels.send_text(&chat_jid, &text).await {
+                            Ok(()) => info!("task output sent"),
+                            Err(e) => error!(err = %e, "failed to send task output"),
                         }
                         *result_cb.write().await = Some(text.clone());
                     }
@@ -191,18 +260,15 @@ async fn run_scheduled_task(
 
     // Write task/group snapshots for container consumption
     {
-        let tasks_json = match pool.get_all_tasks().await {
-            Ok(tasks) => {
-                let filtered: Vec<_> = tasks.into_iter()
-                    .filter(|t| t.group_folder == task.group_folder)
-                    .collect();
-                serde_json::to_string(&filtered).unwrap_or_else(|_| "[]".into())
-            }
-            Err(e) => {
-                warn!(err = %e, "failed to load tasks for snapshot");
-                "[]".into()
-            }
-        };
+        let tasks = pool.get_all_tasks().await.unwrap_or_else(|e| {
+            warn!(err = %e, "failed to load tasks for snapshot");
+            Vec::new()
+        });
+        let filtered: Vec<_> = tasks
+            .iter()
+            .filter(|t| t.group_folder == task.group_folder)
+            .collect();
+        let tasks_json = serde_json::to_string(&filtered).unwrap_or_else(|_| "[]".into());
         let groups_json = {
             let g = groups.read().await;
             let entries: Vec<_> = g.values().map(|rg| serde_json::json!({
@@ -213,6 +279,20 @@ async fn run_scheduled_task(
             serde_json::to_string(&entries).unwrap_or_else(|_| "[]".into())
         };
         write_snapshots(&run_config.data_dir, &task.group_folder, is_main, &tasks_json, &groups_json).await;
+
+        let context = GroupContext::build(
+            &ContextInputs {
+                group: &group,
+                container_config: group_info.container_config.as_ref(),
+                is_main,
+                runtime,
+                is_scheduled_task: true,
+                tasks: &tasks,
+            },
+            run_config,
+            chrono::Utc::now(),
+        );
+        write_group_context(&run_config.data_dir, &task.group_folder, &context).await;
     }
 
     info!(
@@ -220,16 +300,46 @@ async fn run_scheduled_task(
         group = group.name.as_str(),
         "running scheduled task"
     );
+    let run_id = workspace_git::new_run_id();
+    workspace_git::snapshot_run(
+        run_config,
+        &task.group_folder,
+        &run_id,
+        RunPhase::Before,
+        Some(&task.id),
+    )
+    .await;
+    webhooks::dispatch(
+        webhook.as_ref(),
+        LifecycleEvent::new(
+            LifecycleEventKind::RunStarted,
+            &task.group_folder,
+            &task.chat_jid,
+        )
+        .with_task(&task.id)
+        .with_correlation_id(correlation_id),
+    );
 
+    let started_at = chrono::Utc::now();
     let container_result = run_container_agent(
         &group_info,
         &input,
         runtime,
         is_main,
+        "task",
         run_config,
         on_output,
     )
     .await;
+    record_run(pool, "task", &input, started_at, &container_result).await;
+    workspace_git::snapshot_run(
+        run_config,
+        &task.group_folder,
+        &run_id,
+        RunPhase::After,
+        Some(&task.id),
+    )
+    .await;
 
     // Collect final state
     let result = result_text.read().await.clone();
@@ -256,21 +366,61 @@ async fn run_scheduled_task(
         }
         Err(e) => {
             error!(task_id = task.id.as_str(), err = %e, "task container error");
+            health::record_error(health::SUBSYSTEM_DOCKER, &e);
             (result, Some(e.to_string()))
         }
     };
 
-    log_and_update(pool, &task, start, final_result.as_deref(), final_error.as_deref(), timezone).await;
+    log_and_update(
+        pool,
+        channels,
+        &task,
+        correlation_id,
+        start,
+        final_result.as_deref(),
+        final_error.as_deref(),
+        timezone,
+        i18n::locale_or_default(group.locale.as_deref()),
+    )
+    .await;
+
+    // Report to the group's own webhook: the run outcome, then the task result.
+    let duration_ms = start.elapsed().as_millis() as i64;
+    webhooks::dispatch(
+        webhook.as_ref(),
+        LifecycleEvent::new(
+            LifecycleEventKind::RunFinished,
+            &task.group_folder,
+            &task.chat_jid,
+        )
+        .with_task(&task.id)
+        .with_correlation_id(correlation_id)
+        .with_outcome(duration_ms, None, final_error.as_deref()),
+    );
+    webhooks::dispatch(
+        webhook.as_ref(),
+        LifecycleEvent::new(
+            LifecycleEventKind::TaskResult,
+            &task.group_folder,
+            &task.chat_jid,
+        )
+        .with_task(&task.id)
+        .with_correlation_id(correlation_id)
+        .with_outcome(duration_ms, final_result.as_deref(), final_error.as_deref()),
+    );
 }
 
 /// Log the task run and update next_run in Postgres.
 async fn log_and_update(
-    pool: &PgPool,
+    pool: &SharedStorage,
+    channels: &ChannelRegistry,
     task: &DueTask,
+    correlation_id: &str,
     start: Instant,
     result: Option<&str>,
     error: Option<&str>,
     timezone: &str,
+    locale: &str,
 ) {
     let duration_ms = start.elapsed().as_millis() as i64;
     let status = if error.is_some() { "error" } else { "success" };
@@ -283,6 +433,7 @@ async fn log_and_update(
         status: status.into(),
         result: result.map(|s| s.to_string()),
         error: error.map(|s| s.to_string()),
+        correlation_id: Some(correlation_id.to_string()),
     };
     if let Err(e) = pool.log_task_run(&log).await {
         error!(task_id = task.id.as_str(), err = %e, "failed to log task run");
@@ -298,6 +449,9 @@ async fn log_and_update(
     {
         error!(task_id = task.id.as_str(), err = %e, "failed to update task after run");
     }
+    if let (Some(error), Some(policy)) = (error, task.config.on_failure.as_ref()) {
+        report_failure(pool, channels, task, policy, error, locale).await;
+    }
 
     info!(
         task_id = task.id.as_str(),
@@ -308,6 +462,51 @@ async fn log_and_update(
     );
 }
 
+/// Tell the failure chat about a failed run, in the task group's `locale`,
+/// first pausing the task if it has now failed `pause_after` times in a row.
+async fn report_failure(
+    pool: &SharedStorage,
+    channels: &ChannelRegistry,
+    task: &DueTask,
+    policy: &FailurePolicy,
+    error: &str,
+    locale: &str,
+) {
+    let mut paused_after = None;
+    if policy.pause_after > 0 {
+        match pool
+            .get_task_runs(&task.id, i64::from(policy.pause_after), 0)
+            .await
+        {
+            Ok(runs) if consecutive_failures(&runs) >= policy.pause_after as usize => {
+                match pool.set_task_status(&task.id, "paused", None).await {
+                    Ok(Some(_)) => {
+                        warn!(
+                            task_id = task.id.as_str(),
+                            failures = runs.len(),
+                            "task paused after repeated failures"
+                        );
+                        paused_after = Some(runs.len());
+                    }
+                    // A one-shot task has already completed
+                    Ok(None) => {}
+                    Err(e) => {
+                        error!(task_id = task.id.as_str(), err = %e, "failed to pause failing task")
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(e) => error!(task_id = task.id.as_str(), err = %e, "failed to load task runs"),
+        }
+    }
+    if let Some(ref chat_jid) = policy.notify {
+        let notice = failure_notice(&task.id, error, paused_after, locale);
+        if let Err(e) = channels.send_text(chat_jid, &notice).await {
+            error!(task_id = task.id.as_str(), err = %e, "failed to send task failure notice");
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -317,4 +516,123 @@ mod tests {
         assert_eq!(result_summary(Some("ok"), None), "ok");
         assert_eq!(result_summary(None, Some("fail")), "Error: fail");
     }
+
+    /// Records what is sent to `ops:` chats.
+    #[derive(Default)]
+    struct OpsChannel {
+        profile: intercom_core::ChannelProfile,
+        sent: std::sync::Mutex<Vec<(String, String)>>,
+    }
+
+    impl crate::channels::ChannelBridge for OpsChannel {
+        fn name(&self) -> &'static str {
+            "ops"
+        }
+
+        fn profile(&self) -> &intercom_core::ChannelProfile {
+            &self.profile
+        }
+
+        fn send<'a>(
+            &'a self,
+            message: &'a crate::channels::OutboundMessage,
+        ) -> crate::channels::ChannelFuture<'a, Vec<String>> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((message.jid.clone(), message.text.clone()));
+            Box::pin(async { Ok(vec![]) })
+        }
+
+        fn edit<'a>(
+            &'a self,
+            _jid: &'a str,
+            _id: &'a str,
+            _text: &'a str,
+        ) -> crate::channels::ChannelFuture<'a, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn normalize_ingress(
+            &self,
+            content: &str,
+            _attachments: &[intercom_core::Attachment],
+        ) -> String {
+            content.to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_failures_are_reported_and_pause_the_task() {
+        let pool: SharedStorage = Arc::new(intercom_core::SqliteStore::new(":memory:"));
+        let task = intercom_core::ScheduledTask {
+            id: "digest".into(),
+            group_folder: "team".into(),
+            chat_jid: "tg:1".into(),
+            prompt: "p".into(),
+            schedule_type: "interval".into(),
+            schedule_value: "3600000".into(),
+            context_mode: "isolated".into(),
+            next_run: Some("2099-01-01T00:00:00Z".into()),
+            last_run: None,
+            last_result: None,
+            status: "active".into(),
+            created_at: "2024-01-01T00:00:00Z".into(),
+            task_config: Some(serde_json::json!({
+                "timeoutMs": 60000,
+                "onFailure": { "notify": "ops:alerts", "pauseAfter": 2 }
+            })),
+        };
+        pool.create_task(&task).await.unwrap();
+        let due = DueTask::from(task);
+        assert_eq!(due.config.timeout_ms, Some(60_000));
+        let policy = due.config.on_failure.clone().unwrap();
+
+        let ops = Arc::new(OpsChannel {
+            profile: intercom_core::ChannelProfile {
+                jid_prefix: "ops:".into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let channels = ChannelRegistry::new("ops").register(ops.clone());
+        let fail = |at: &str| intercom_core::TaskRunLog {
+            task_id: "digest".into(),
+            run_at: at.into(),
+            duration_ms: 10,
+            status: "error".into(),
+            result: None,
+            error: Some("boom".into()),
+            correlation_id: None,
+        };
+
+        pool.log_task_run(&fail("2026-01-01T00:00:00Z"))
+            .await
+            .unwrap();
+        report_failure(&pool, &channels, &due, &policy, "boom", "en").await;
+        assert_eq!(
+            pool.get_task_by_id("digest").await.unwrap().unwrap().status,
+            "active"
+        );
+
+        pool.log_task_run(&fail("2026-01-01T01:00:00Z"))
+            .await
+            .unwrap();
+        report_failure(&pool, &channels, &due, &policy, "boom", "en").await;
+        assert_eq!(
+            pool.get_task_by_id("digest").await.unwrap().unwrap().status,
+            "paused"
+        );
+
+        let sent = ops.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(
+            sent[0],
+            (
+                "ops:alerts".to_string(),
+                "Scheduled task digest failed: boom".to_string()
+            )
+        );
+        assert!(sent[1].1.contains("Paused after 2 failed runs in a row"));
+    }
 }
@@ -0,0 +1,127 @@
+//! Graceful shutdown on SIGTERM, SIGINT or `POST /v1/admin/shutdown`.
+//!
+//! The first request wins. `serve` then pauses the queue and waits for
+//! running containers up to the grace period (or the deadline the admin
+//! call gave), writes the queue journal and cursors, stops the HTTP
+//! server and the background loops, and returns. Containers still
+//! running at the deadline are detached, not killed, and work still
+//! queued is journaled for the next start. A second signal while that is
+//! in progress exits at once.
+
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+/// Why and how the daemon is stopping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownRequest {
+    pub reason: String,
+    /// How long to wait for running containers.
+    pub grace: Duration,
+}
+
+/// Set once, when shutdown starts; cloned into `AppState`.
+#[derive(Clone)]
+pub struct Shutdown {
+    tx: watch::Sender<Option<ShutdownRequest>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self {
+            tx: watch::channel(None).0,
+        }
+    }
+}
+
+impl Shutdown {
+    /// Start shutting down. False if shutdown had already started.
+    pub fn request(&self, reason: impl Into<String>, grace: Duration) -> bool {
+        let request = ShutdownRequest {
+            reason: reason.into(),
+            grace,
+        };
+        self.tx.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(request);
+            true
+        })
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.tx.borrow().is_some()
+    }
+
+    /// Wait until shutdown is requested.
+    pub async fn requested(&self) -> ShutdownRequest {
+        let mut rx = self.tx.subscribe();
+        let request = rx
+            .wait_for(Option::is_some)
+            .await
+            .expect("sender is held by self");
+        request.clone().expect("waited for Some")
+    }
+}
+
+/// Request shutdown on the first SIGTERM or SIGINT, and exit the process on
+/// the second.
+pub async fn watch_signals(shutdown: Shutdown, grace: Duration) {
+    let name = match next_signal().await {
+        Ok(name) => name,
+        Err(e) => {
+            error!(err = %e, "failed to install signal handlers, only the admin route can stop the daemon");
+            return;
+        }
+    };
+    if shutdown.request(name, grace) {
+        info!(signal = name, "shutdown requested");
+    }
+    if let Ok(name) = next_signal().await {
+        warn!(
+            signal = name,
+            "second signal, exiting without waiting for containers"
+        );
+        std::process::exit(1);
+    }
+}
+
+#[cfg(unix)]
+async fn next_signal() -> std::io::Result<&'static str> {
+    use tokio::signal::unix::{SignalKind, signal};
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    Ok(tokio::select! {
+        _ = terminate.recv() => "SIGTERM",
+        _ = interrupt.recv() => "SIGINT",
+    })
+}
+
+#[cfg(not(unix))]
+async fn next_signal() -> std::io::Result<&'static str> {
+    tokio::signal::ctrl_c().await.map(|()| "ctrl-c")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn the_first_request_wins() {
+        let shutdown = Shutdown::default();
+        assert!(!shutdown.is_requested());
+        let waiter = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.requested().await }
+        });
+        assert!(shutdown.request("admin API", Duration::from_secs(5)));
+        assert!(!shutdown.request("SIGTERM", Duration::from_secs(60)));
+        let request = waiter.await.unwrap();
+        assert_eq!(request.reason, "admin API");
+        assert_eq!(request.grace, Duration::from_secs(5));
+        // Later waiters see it too
+        assert_eq!(shutdown.requested().await, request);
+    }
+}
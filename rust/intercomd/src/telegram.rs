@@ -1,19 +1,65 @@
+use std::collections::HashMap;
+use std::future::Future;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, anyhow};
-use intercom_core::IntercomConfig;
+use intercom_core::{
+    Attachment, ChannelProfile, IngressGroupSource, IntercomConfig, MarkdownDialect,
+    RegisteredGroup, SharedStorage, TelegramConfig,
+};
 use reqwest::Client;
 use rusqlite::{Connection, OptionalExtension, params};
 use serde::{Deserialize, Serialize};
-
+use sha2::{Digest, Sha256};
+use tokio::sync::{RwLock, watch};
+use tracing::{info, warn};
+
+use crate::channels::{self, ChannelBridge, ChannelFuture, OutboundMessage};
+use crate::container::security::ContainerConfig;
+use crate::health::{SUBSYSTEM_DB, SUBSYSTEM_TELEGRAM, record_error};
+use crate::markdown;
+use crate::message_loop::is_muted;
+use crate::outbound::{split_text, truncate_text};
+
+/// Bot API limit on message text. The channel profile may lower it.
 pub const TELEGRAM_MAX_TEXT_CHARS: usize = 4096;
+/// Name of the Telegram profile under `[channels.profiles]`.
+pub const TELEGRAM_CHANNEL: &str = "telegram";
 const TELEGRAM_API_BASE: &str = "https://api.telegram.org";
 
+/// Bot API limit on a button's `callback_data`, in bytes.
+pub const MAX_CALLBACK_DATA_BYTES: usize = 64;
+/// `callback_data` prefix for a button that runs a slash command.
+pub const CALLBACK_COMMAND_PREFIX: &str = "cmd:";
+/// `callback_data` prefix for a button that posts its text into the chat.
+pub const CALLBACK_MESSAGE_PREFIX: &str = "msg:";
+
+/// Per-group bot tokens must live in variables named with this prefix, so a
+/// group's config can't point the bridge at an unrelated secret.
+pub const BOT_TOKEN_ENV_PREFIX: &str = "TELEGRAM_BOT_TOKEN_";
+
+/// Header Telegram uses to echo the `secret_token` given to `setWebhook`.
+pub const WEBHOOK_SECRET_HEADER: &str = "x-telegram-bot-api-secret-token";
+
 #[derive(Clone)]
 pub struct TelegramBridge {
     client: Client,
     bot_token: Option<String>,
     sqlite_path: PathBuf,
+    /// Filled by the first successful `getMe`.
+    bot_username: Arc<OnceLock<String>>,
+    profile: ChannelProfile,
+    pacer: Arc<SendPacer>,
+    /// Where sent messages are recorded as the bot's, so conversation
+    /// history includes its own replies.
+    store: Option<SharedStorage>,
+    sender_name: String,
+    /// Registered groups, consulted for a per-group `botTokenEnv`.
+    groups: Option<Arc<RwLock<HashMap<String, RegisteredGroup>>>>,
+    /// Set when streaming output is shown as an edited draft message.
+    draft_interval: Option<Duration>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -28,6 +74,22 @@ pub struct TelegramIngressRequest {
     pub timestamp: String,
     #[serde(default)]
     pub persist: bool,
+    #[serde(default)]
+    pub kind: TelegramUpdateKind,
+    /// Photos, documents, voice notes, ... already downloaded by the host.
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+/// Which Telegram update produced an ingress request. Edits and deletions
+/// update the stored message but never dispatch a new agent run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TelegramUpdateKind {
+    #[default]
+    Message,
+    EditedMessage,
+    DeletedMessage,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -55,6 +117,22 @@ pub struct TelegramIngressParity {
 pub struct TelegramSendRequest {
     pub jid: String,
     pub text: String,
+    /// Markup to render `text` in; defaults to the channel profile's.
+    #[serde(default)]
+    pub format: Option<MarkdownDialect>,
+    /// Inline keyboard attached to the last chunk.
+    #[serde(default)]
+    pub reply_markup: Option<InlineKeyboardMarkup>,
+    /// Message the first chunk replies to. Sent even if that message has
+    /// since been deleted; ignored if it is not a Telegram message id.
+    #[serde(default)]
+    pub reply_to_message_id: Option<String>,
+    /// Forum topic to post in.
+    #[serde(default)]
+    pub message_thread_id: Option<i64>,
+    /// Recorded on the stored copy of the sent message.
+    #[serde(default)]
+    pub correlation_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -66,6 +144,11 @@ pub struct TelegramSendResponse {
     pub chunks_sent: usize,
     pub chunk_lengths: Vec<usize>,
     pub parity: TelegramSendParity,
+    /// 429 retries spent on this request, out of `max_retries`.
+    pub retries: u32,
+    pub max_retries: u32,
+    /// Total `retry_after` waited across those retries.
+    pub retry_wait_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -79,6 +162,9 @@ pub struct TelegramEditRequest {
     pub jid: String,
     pub message_id: String,
     pub text: String,
+    /// Markup to render `text` in; defaults to the channel profile's.
+    #[serde(default)]
+    pub format: Option<MarkdownDialect>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -96,19 +182,119 @@ pub struct InlineKeyboardButton {
     pub callback_data: String,
 }
 
+impl InlineKeyboardButton {
+    /// Button that runs `/command args` for whoever taps it.
+    pub fn command(text: impl Into<String>, command_line: &str) -> Self {
+        Self {
+            text: text.into(),
+            callback_data: format!("{CALLBACK_COMMAND_PREFIX}{command_line}"),
+        }
+    }
+
+    /// Button that posts `message` into the chat as the user who taps it.
+    pub fn message(text: impl Into<String>, message: &str) -> Self {
+        Self {
+            text: text.into(),
+            callback_data: format!("{CALLBACK_MESSAGE_PREFIX}{message}"),
+        }
+    }
+}
+
 /// Inline keyboard markup (array of button rows).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InlineKeyboardMarkup {
     pub inline_keyboard: Vec<Vec<InlineKeyboardButton>>,
 }
 
+impl InlineKeyboardMarkup {
+    /// Check the keyboard against Bot API limits before sending it.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.inline_keyboard.iter().all(Vec::is_empty) {
+            return Err(anyhow!("inline keyboard has no buttons"));
+        }
+        for button in self.inline_keyboard.iter().flatten() {
+            if button.text.trim().is_empty() {
+                return Err(anyhow!("inline keyboard button has no text"));
+            }
+            let len = button.callback_data.len();
+            if len == 0 || len > MAX_CALLBACK_DATA_BYTES {
+                return Err(anyhow!(
+                    "callback_data for button `{}` is {len} bytes (must be 1-{MAX_CALLBACK_DATA_BYTES})",
+                    button.text
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keyboard with one `msg:` button per option, two to a row. Options too
+/// long for `callback_data` are left out.
+pub fn options_keyboard(options: &[String]) -> Option<InlineKeyboardMarkup> {
+    let buttons: Vec<InlineKeyboardButton> = options
+        .iter()
+        .map(|option| option.trim())
+        .filter(|option| !option.is_empty())
+        .filter(|option| {
+            let fits = CALLBACK_MESSAGE_PREFIX.len() + option.len() <= MAX_CALLBACK_DATA_BYTES;
+            if !fits {
+                warn!(
+                    option,
+                    "dropping reply option too long for a Telegram button"
+                );
+            }
+            fits
+        })
+        .map(|option| InlineKeyboardButton::message(option, option))
+        .collect();
+    (!buttons.is_empty()).then(|| InlineKeyboardMarkup {
+        inline_keyboard: buttons.chunks(2).map(<[_]>::to_vec).collect(),
+    })
+}
+
+/// What a button press asks for, decoded from its `callback_data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallbackAction {
+    /// `cmd:model claude-sonnet-4-6`: run the slash command.
+    Command { command: String, args: String },
+    /// `msg:yes, ship it`: post the text into the chat as the user.
+    Message(String),
+    /// `approve:<gate>` and the other kernel actions `handle_callback` knows.
+    Kernel,
+}
+
+impl CallbackAction {
+    pub fn parse(data: &str) -> Self {
+        if let Some(line) = data.strip_prefix(CALLBACK_COMMAND_PREFIX) {
+            let line = line.trim().trim_start_matches('/');
+            let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            if !command.is_empty() {
+                return Self::Command {
+                    command: command.to_string(),
+                    args: args.trim().to_string(),
+                };
+            }
+        }
+        if let Some(text) = data.strip_prefix(CALLBACK_MESSAGE_PREFIX)
+            && !text.trim().is_empty()
+        {
+            return Self::Message(text.trim().to_string());
+        }
+        Self::Kernel
+    }
+}
+
 /// Extended send request with optional inline keyboard.
+#[allow(dead_code)] // button sends still go through the Node host
 #[derive(Debug, Clone, Deserialize)]
 pub struct TelegramSendWithButtonsRequest {
     pub jid: String,
     pub text: String,
     #[serde(default)]
     pub reply_markup: Option<InlineKeyboardMarkup>,
+    /// Markup to render `text` in; defaults to the channel profile's.
+    #[serde(default)]
+    pub format: Option<MarkdownDialect>,
 }
 
 /// Incoming callback query from Telegram (button press).
@@ -136,25 +322,127 @@ struct TelegramApiEnvelope {
     ok: bool,
     result: Option<serde_json::Value>,
     description: Option<String>,
+    #[serde(default)]
+    error_code: Option<i64>,
+    #[serde(default)]
+    parameters: Option<TelegramResponseParameters>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TelegramResponseParameters {
+    #[serde(default)]
+    retry_after: Option<u64>,
+}
+
+impl TelegramApiEnvelope {
+    /// How long Telegram asked us to back off, if this is a 429.
+    fn retry_after(&self) -> Option<Duration> {
+        if self.ok {
+            return None;
+        }
+        let seconds = self.parameters.as_ref().and_then(|p| p.retry_after);
+        match (self.error_code, seconds) {
+            (_, Some(seconds)) => Some(Duration::from_secs(seconds)),
+            (Some(429), None) => Some(Duration::from_secs(1)),
+            _ => None,
+        }
+    }
+}
+
+/// Chats tracked before slots that already passed are pruned.
+const PACER_PRUNE_AT: usize = 1024;
+
+/// Per-chat outbound pacing and the 429 retry policy, shared by all clones
+/// of a bridge.
+#[derive(Debug)]
+struct SendPacer {
+    interval: Duration,
+    max_retries: u32,
+    max_retry_after: Duration,
+    /// Earliest time the next message to each chat may go out.
+    next_slot: Mutex<HashMap<String, Instant>>,
+}
+
+impl SendPacer {
+    fn new(config: &TelegramConfig) -> Self {
+        Self {
+            interval: Duration::from_millis(config.send_interval_ms),
+            max_retries: config.max_send_retries,
+            max_retry_after: Duration::from_secs(config.max_retry_after_secs),
+            next_slot: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Claim the next send slot for `chat_id` and return how long to wait
+    /// for it.
+    fn reserve(&self, chat_id: &str, now: Instant) -> Duration {
+        let mut slots = self.next_slot.lock().unwrap_or_else(|e| e.into_inner());
+        if slots.len() >= PACER_PRUNE_AT {
+            slots.retain(|_, at| *at > now);
+        }
+        let slot = slots
+            .get(chat_id)
+            .copied()
+            .filter(|at| *at > now)
+            .unwrap_or(now);
+        slots.insert(chat_id.to_string(), slot + self.interval);
+        slot - now
+    }
+
+    /// Hold every send to `chat_id` until `until`, after a 429.
+    fn back_off(&self, chat_id: &str, until: Instant) {
+        let mut slots = self.next_slot.lock().unwrap_or_else(|e| e.into_inner());
+        let slot = slots.entry(chat_id.to_string()).or_insert(until);
+        *slot = (*slot).max(until);
+    }
+
+    /// Wait before retrying `envelope`, or `None` if it is not a 429 or the
+    /// retry is not worth making.
+    fn retry_delay(&self, envelope: &TelegramApiEnvelope, retries_used: u32) -> Option<Duration> {
+        let wait = envelope.retry_after()?;
+        (retries_used < self.max_retries && wait <= self.max_retry_after).then_some(wait)
+    }
+}
+
+/// Retry accounting for one send request.
+#[derive(Debug, Default)]
+struct SendRetries {
+    count: u32,
+    waited: Duration,
 }
 
 #[derive(Debug, Clone)]
-struct RegisteredGroupRow {
-    name: String,
-    folder: String,
-    trigger_pattern: String,
-    requires_trigger: bool,
+pub(crate) struct RegisteredGroupRow {
+    pub(crate) name: String,
+    pub(crate) folder: String,
+    pub(crate) trigger_pattern: String,
+    pub(crate) requires_trigger: bool,
     runtime: Option<String>,
     model: Option<String>,
+    pub(crate) muted_until: Option<String>,
+}
+
+impl From<&RegisteredGroup> for RegisteredGroupRow {
+    fn from(group: &RegisteredGroup) -> Self {
+        Self {
+            name: group.name.clone(),
+            folder: group.folder.clone(),
+            trigger_pattern: group.trigger.clone(),
+            requires_trigger: group.requires_trigger.unwrap_or(true),
+            runtime: group.runtime.clone(),
+            model: group.model.clone(),
+            muted_until: group.muted_until.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
-struct RuntimeResolution {
-    runtime: String,
-    model: String,
-    runtime_profile_found: bool,
-    runtime_fallback_used: bool,
-    model_fallback_used: bool,
+pub(crate) struct RuntimeResolution {
+    pub(crate) runtime: String,
+    pub(crate) model: String,
+    pub(crate) runtime_profile_found: bool,
+    pub(crate) runtime_fallback_used: bool,
+    pub(crate) model_fallback_used: bool,
 }
 
 impl TelegramBridge {
@@ -168,7 +456,104 @@ impl TelegramBridge {
             client: Client::new(),
             bot_token,
             sqlite_path: PathBuf::from(&config.storage.sqlite_legacy_path),
+            bot_username: Arc::new(OnceLock::new()),
+            profile: config.channels.profile(TELEGRAM_CHANNEL),
+            pacer: Arc::new(SendPacer::new(&config.telegram)),
+            store: None,
+            sender_name: String::new(),
+            groups: None,
+            draft_interval: config
+                .telegram
+                .draft_edits
+                .then(|| Duration::from_millis(config.telegram.draft_edit_interval_ms)),
+        }
+    }
+
+    /// Pick the bot token per chat from each group's `botTokenEnv`.
+    pub fn with_groups(mut self, groups: Arc<RwLock<HashMap<String, RegisteredGroup>>>) -> Self {
+        self.groups = Some(groups);
+        self
+    }
+
+    fn default_token(&self) -> anyhow::Result<&str> {
+        self.bot_token
+            .as_deref()
+            .ok_or_else(|| anyhow!("TELEGRAM_BOT_TOKEN is not set for intercomd"))
+    }
+
+    /// Bot token for `jid`: the one named by its group's `botTokenEnv`
+    /// (a topic uses its chat's group), else `TELEGRAM_BOT_TOKEN`. A group
+    /// whose variable is unset fails rather than posting as the wrong bot.
+    async fn token_for(&self, jid: &str) -> anyhow::Result<String> {
+        let Some(var) = self.group_token_env(jid).await else {
+            return self.default_token().map(str::to_string);
+        };
+        if !var.starts_with(BOT_TOKEN_ENV_PREFIX) {
+            return Err(anyhow!(
+                "botTokenEnv `{var}` for {jid} must start with {BOT_TOKEN_ENV_PREFIX}"
+            ));
+        }
+        std::env::var(&var)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| anyhow!("bot token variable {var} for {jid} is not set"))
+    }
+
+    async fn group_folder(&self, jid: &str) -> Option<String> {
+        let groups = self.groups.as_ref()?.read().await;
+        let group_jid = registered_jid(jid, |jid| groups.contains_key(jid))?;
+        Some(groups.get(group_jid)?.folder.clone())
+    }
+
+    async fn group_token_env(&self, jid: &str) -> Option<String> {
+        let groups = self.groups.as_ref()?.read().await;
+        let group_jid = registered_jid(jid, |jid| groups.contains_key(jid))?;
+        let config = groups.get(group_jid)?.container_config.clone()?;
+        serde_json::from_value::<ContainerConfig>(config)
+            .ok()?
+            .bot_token_env
+            .filter(|var| !var.trim().is_empty())
+    }
+
+    /// Record every sent message in `store` as a bot message from
+    /// `sender_name`, keyed by its Telegram message id.
+    pub fn with_storage(
+        mut self,
+        store: Option<SharedStorage>,
+        sender_name: impl Into<String>,
+    ) -> Self {
+        self.store = store;
+        self.sender_name = sender_name.into();
+        self
+    }
+
+    /// Per-message limit: the profile's, capped at what the Bot API accepts.
+    pub fn max_chars(&self) -> usize {
+        self.profile.max_chars.clamp(1, TELEGRAM_MAX_TEXT_CHARS)
+    }
+
+    /// Gap between draft edits, or `None` when drafts are off or the
+    /// profile does not allow editing.
+    pub fn draft_interval(&self) -> Option<Duration> {
+        self.draft_interval
+            .filter(|_| self.profile.supports_edit && self.is_enabled())
+    }
+
+    /// Bot username from the last successful `getMe`, if any.
+    pub fn cached_bot_username(&self) -> Option<String> {
+        self.bot_username.get().cloned()
+    }
+
+    /// Secret expected in [`WEBHOOK_SECRET_HEADER`]: the configured one, or
+    /// one derived from the bot token so webhooks are never unauthenticated.
+    pub fn webhook_secret(&self, config: &TelegramConfig) -> Option<String> {
+        if let Some(secret) = config.webhook_secret.as_ref().filter(|s| !s.is_empty()) {
+            return Some(secret.clone());
         }
+        let token = self.bot_token.as_ref()?;
+        let digest = Sha256::digest(format!("intercom-telegram-webhook:{token}"));
+        Some(hex::encode(digest))
     }
 
     pub fn is_enabled(&self) -> bool {
@@ -177,26 +562,68 @@ impl TelegramBridge {
 
     /// Convenience: send a text message to a JID (chat_id).
     /// Used by the orchestrator to deliver agent output.
+    #[allow(dead_code)] // kept for the Node host's send path, not yet ported
     pub async fn send_text_to_jid(&self, jid: &str, text: &str) -> anyhow::Result<()> {
+        self.send_reply_to_jid(jid, text, None, None).await
+    }
+
+    /// Like [`Self::send_text_to_jid`], threaded under `reply_to` if given
+    /// and stored with `correlation_id`.
+    pub async fn send_reply_to_jid(
+        &self,
+        jid: &str,
+        text: &str,
+        reply_to: Option<&str>,
+        correlation_id: Option<&str>,
+    ) -> anyhow::Result<()> {
         self.send_message(TelegramSendRequest {
             jid: jid.to_string(),
             text: text.to_string(),
+            format: None,
+            reply_markup: None,
+            reply_to_message_id: reply_to.map(str::to_string),
+            message_thread_id: None,
+            correlation_id: correlation_id.map(str::to_string),
         })
         .await?;
         Ok(())
     }
 
+    /// Decide whether an inbound message should reach its group's agent.
+    ///
+    /// With [`IngressGroupSource::Live`] the caller passes the group it found
+    /// in the daemon's registry or the live store; the legacy SQLite file is
+    /// only consulted when it found none, and nothing is persisted here (the
+    /// caller stores through the live store). With
+    /// [`IngressGroupSource::Sqlite`] both the lookup and `persist` go to the
+    /// legacy file.
     pub fn route_ingress(
         &self,
         config: &IntercomConfig,
         request: TelegramIngressRequest,
+        live_group: Option<&RegisteredGroup>,
     ) -> anyhow::Result<TelegramIngressResponse> {
-        let conn = self.open_sqlite()?;
-        let group = load_registered_group(&conn, &request.chat_jid)?;
+        let legacy = config.telegram.ingress_groups == IngressGroupSource::Sqlite;
+        let conn = if legacy {
+            Some(self.open_sqlite()?)
+        } else {
+            None
+        };
+        let group = match (&conn, live_group) {
+            (Some(conn), _) => load_registered_group(conn, &request.chat_jid)?,
+            (None, Some(group)) => Some(RegisteredGroupRow::from(group)),
+            (None, None) if self.sqlite_path.exists() => {
+                load_registered_group(&self.open_sqlite()?, &request.chat_jid)?
+            }
+            (None, None) => None,
+        };
+        let mut request = request;
+        request.content = self.normalize_ingress(&request.content, &request.attachments);
+        let persist_conn = conn.as_ref().filter(|_| request.persist);
 
-        if request.persist {
-            ensure_telegram_persistence_schema(&conn)?;
-            persist_chat_metadata(&conn, &request)?;
+        if let Some(conn) = persist_conn {
+            ensure_telegram_persistence_schema(conn)?;
+            persist_chat_metadata(conn, &request)?;
         }
 
         let Some(group) = group else {
@@ -221,17 +648,26 @@ impl TelegramBridge {
         let trigger_required = group.folder != "main" && group.requires_trigger;
         let trigger_present =
             !trigger_required || trigger_matches(&request.content, &group.trigger_pattern);
+        let muted = is_muted(group.muted_until.as_deref(), chrono::Utc::now());
         let runtime = resolve_runtime(config, &group);
 
-        if request.persist {
-            persist_inbound_message(&conn, &request)?;
+        if let Some(conn) = persist_conn {
+            match request.kind {
+                TelegramUpdateKind::Message => {
+                    persist_inbound_message(conn, &request)?;
+                    persist_attachments(conn, &request)?;
+                }
+                TelegramUpdateKind::EditedMessage => persist_message_edit(conn, &request)?,
+                TelegramUpdateKind::DeletedMessage => persist_message_tombstone(conn, &request)?,
+            }
         }
 
-        let accepted = !trigger_required || trigger_present;
-        let reason = if accepted {
-            None
-        } else {
-            Some("trigger_required".to_string())
+        let (accepted, reason) = match request.kind {
+            TelegramUpdateKind::EditedMessage => (false, Some("edited_message".to_string())),
+            TelegramUpdateKind::DeletedMessage => (false, Some("deleted_message".to_string())),
+            TelegramUpdateKind::Message if muted => (false, Some("muted".to_string())),
+            TelegramUpdateKind::Message if !trigger_required || trigger_present => (true, None),
+            TelegramUpdateKind::Message => (false, Some("trigger_required".to_string())),
         };
 
         Ok(TelegramIngressResponse {
@@ -256,46 +692,56 @@ impl TelegramBridge {
         &self,
         request: TelegramSendRequest,
     ) -> anyhow::Result<TelegramSendResponse> {
-        let token = self
-            .bot_token
-            .as_ref()
-            .ok_or_else(|| anyhow!("TELEGRAM_BOT_TOKEN is not set for intercomd"))?;
+        let token = self.token_for(&request.jid).await?;
+        let token = token.as_str();
 
         if request.text.trim().is_empty() {
             return Err(anyhow!("cannot send an empty Telegram message"));
         }
 
         let chat_id = normalize_chat_id(&request.jid);
-        let endpoint = format!("{TELEGRAM_API_BASE}/bot{token}/sendMessage");
-        let chunks = split_for_telegram(&request.text, TELEGRAM_MAX_TEXT_CHARS);
+        let dialect = request.format.unwrap_or(self.profile.markdown);
+        let reply_markup = match &request.reply_markup {
+            Some(markup) => {
+                markup.validate()?;
+                Some(
+                    serde_json::to_value(markup)
+                        .context("failed to serialize InlineKeyboardMarkup")?,
+                )
+            }
+            None => None,
+        };
+        let reply_to = request
+            .reply_to_message_id
+            .as_deref()
+            .and_then(|id| id.trim().parse::<i64>().ok());
+        let max_chars = self.max_chars();
+        let chunks = split_text(&request.text, max_chars);
         let chunk_lengths = chunks
             .iter()
             .map(|chunk| chunk.chars().count())
             .collect::<Vec<_>>();
         let mut sent_calls = 0_usize;
         let mut message_ids = Vec::new();
+        let mut retries = SendRetries::default();
 
-        for chunk in &chunks {
-            let response = self
-                .client
-                .post(&endpoint)
-                .json(&serde_json::json!({
-                    "chat_id": chat_id,
-                    "text": chunk,
-                }))
-                .send()
-                .await
-                .context("failed to call Telegram sendMessage")?;
-
-            let body: TelegramApiEnvelope = response
-                .json()
-                .await
-                .context("failed to parse Telegram sendMessage response")?;
-            if !body.ok {
-                return Err(anyhow!(body.description.unwrap_or_else(|| {
-                    "Telegram sendMessage returned ok=false".to_string()
-                })));
+        for (index, chunk) in chunks.iter().enumerate() {
+            let mut body = serde_json::json!({ "chat_id": chat_id });
+            if let Some(thread_id) = request.message_thread_id.or(split_jid(&request.jid).1) {
+                body["message_thread_id"] = thread_id.into();
+            }
+            if let Some(message_id) = reply_to.filter(|_| index == 0) {
+                body["reply_parameters"] = serde_json::json!({
+                    "message_id": message_id,
+                    "allow_sending_without_reply": true,
+                });
             }
+            if let Some(markup) = reply_markup.as_ref().filter(|_| index + 1 == chunks.len()) {
+                body["reply_markup"] = markup.clone();
+            }
+            let body = self
+                .call_with_text(token, "sendMessage", body, chunk, dialect, &mut retries)
+                .await?;
 
             sent_calls += 1;
             if let Some(message_id) = body
@@ -304,7 +750,18 @@ impl TelegramBridge {
                 .and_then(|value| value.get("message_id"))
                 .and_then(|value| value.as_i64())
             {
-                message_ids.push(message_id.to_string());
+                let message_id = message_id.to_string();
+                channels::record_sent(
+                    self.store.as_ref(),
+                    self.name(),
+                    &self.sender_name,
+                    &request.jid,
+                    &message_id,
+                    chunk,
+                    request.correlation_id.as_deref(),
+                )
+                .await;
+                message_ids.push(message_id);
             }
         }
 
@@ -316,11 +773,12 @@ impl TelegramBridge {
             chunks_sent: sent_calls,
             chunk_lengths: chunk_lengths.clone(),
             parity: TelegramSendParity {
-                max_chars_per_chunk: TELEGRAM_MAX_TEXT_CHARS,
-                all_chunks_within_limit: chunk_lengths
-                    .iter()
-                    .all(|len| *len <= TELEGRAM_MAX_TEXT_CHARS),
+                max_chars_per_chunk: max_chars,
+                all_chunks_within_limit: chunk_lengths.iter().all(|len| *len <= max_chars),
             },
+            retries: retries.count,
+            max_retries: self.pacer.max_retries,
+            retry_wait_ms: retries.waited.as_millis() as u64,
         })
     }
 
@@ -328,130 +786,251 @@ impl TelegramBridge {
         &self,
         request: TelegramEditRequest,
     ) -> anyhow::Result<TelegramEditResponse> {
-        let token = self
-            .bot_token
-            .as_ref()
-            .ok_or_else(|| anyhow!("TELEGRAM_BOT_TOKEN is not set for intercomd"))?;
+        let token = self.token_for(&request.jid).await?;
+        let token = token.as_str();
+        if !self.profile.supports_edit {
+            return Err(anyhow!(
+                "editing is disabled in the telegram channel profile"
+            ));
+        }
         let chat_id = normalize_chat_id(&request.jid);
         let message_id = request
             .message_id
             .parse::<i64>()
             .with_context(|| format!("invalid message_id `{}`", request.message_id))?;
 
-        let (text, truncated) = truncate_for_telegram(&request.text, TELEGRAM_MAX_TEXT_CHARS);
-        let endpoint = format!("{TELEGRAM_API_BASE}/bot{token}/editMessageText");
-        let response = self
-            .client
-            .post(&endpoint)
-            .json(&serde_json::json!({
-                "chat_id": chat_id,
-                "message_id": message_id,
-                "text": text,
-            }))
-            .send()
-            .await
-            .context("failed to call Telegram editMessageText")?;
+        let max_chars = self.max_chars();
+        let (text, truncated) = truncate_text(&request.text, max_chars);
+        self.call_with_text(
+            token,
+            "editMessageText",
+            serde_json::json!({ "chat_id": chat_id, "message_id": message_id }),
+            &text,
+            request.format.unwrap_or(self.profile.markdown),
+            &mut SendRetries::default(),
+        )
+        .await?;
 
-        let body: TelegramApiEnvelope = response
-            .json()
-            .await
-            .context("failed to parse Telegram editMessageText response")?;
-        if !body.ok {
-            return Err(anyhow!(body.description.unwrap_or_else(|| {
-                "Telegram editMessageText returned ok=false".to_string()
-            })));
+        if let Some(store) = &self.store {
+            let edited_at = chrono::Utc::now().to_rfc3339();
+            if let Err(e) = store
+                .edit_message(&request.message_id, &request.jid, &text, &edited_at)
+                .await
+            {
+                warn!(jid = %request.jid, message_id = %request.message_id, err = %e, "failed to store Telegram edit");
+                record_error(SUBSYSTEM_DB, &e);
+            }
         }
 
         Ok(TelegramEditResponse {
             ok: true,
             error: None,
             truncated,
-            parity_max_chars: TELEGRAM_MAX_TEXT_CHARS,
+            parity_max_chars: max_chars,
         })
     }
 
-    /// Send a message with optional inline keyboard buttons.
-    /// Falls back to plain send_message if reply_markup is None.
-    pub async fn send_message_with_buttons(
+    /// Call a text-carrying Bot API method with `text` rendered for
+    /// `dialect`. If Telegram cannot parse the markup, the call is retried
+    /// once as plain text so a formatting slip never drops the message.
+    async fn call_with_text(
         &self,
-        request: TelegramSendWithButtonsRequest,
-    ) -> anyhow::Result<TelegramSendResponse> {
-        if request.reply_markup.is_none() {
-            return self
-                .send_message(TelegramSendRequest {
-                    jid: request.jid,
-                    text: request.text,
-                })
-                .await;
+        token: &str,
+        method: &str,
+        mut body: serde_json::Value,
+        text: &str,
+        dialect: MarkdownDialect,
+        retries: &mut SendRetries,
+    ) -> anyhow::Result<TelegramApiEnvelope> {
+        let endpoint = format!("{TELEGRAM_API_BASE}/bot{token}/{method}");
+        if let Some(mode) = parse_mode(dialect) {
+            body["text"] = markdown::render(text, dialect).into();
+            body["parse_mode"] = mode.into();
+            let envelope = self.post_paced(&endpoint, method, &body, retries).await?;
+            let description = envelope.description.as_deref().unwrap_or_default();
+            if envelope.ok || !is_entity_parse_error(description) {
+                return check_envelope(envelope, method);
+            }
+            warn!(
+                method,
+                error = description,
+                "Telegram rejected formatted text, resending as plain text"
+            );
+            if let Some(fields) = body.as_object_mut() {
+                fields.remove("parse_mode");
+            }
         }
+        body["text"] = markdown::render(text, MarkdownDialect::Plain).into();
+        let envelope = self.post_paced(&endpoint, method, &body, retries).await?;
+        check_envelope(envelope, method)
+    }
 
-        let token = self
-            .bot_token
-            .as_ref()
-            .ok_or_else(|| anyhow!("TELEGRAM_BOT_TOKEN is not set for intercomd"))?;
-
-        let chat_id = normalize_chat_id(&request.jid);
-        let endpoint = format!("{TELEGRAM_API_BASE}/bot{token}/sendMessage");
-
-        let mut body = serde_json::json!({
-            "chat_id": chat_id,
-            "text": &request.text,
-        });
-        if let Some(markup) = &request.reply_markup {
-            body["reply_markup"] = serde_json::to_value(markup)
-                .context("failed to serialize InlineKeyboardMarkup")?;
+    /// Post to a chat once its pacing slot comes up, retrying 429s after
+    /// Telegram's `retry_after` while the request's retry budget lasts.
+    async fn post_paced(
+        &self,
+        endpoint: &str,
+        method: &str,
+        body: &serde_json::Value,
+        retries: &mut SendRetries,
+    ) -> anyhow::Result<TelegramApiEnvelope> {
+        let chat_id = body
+            .get("chat_id")
+            .map(|id| {
+                id.as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| id.to_string())
+            })
+            .unwrap_or_default();
+        loop {
+            let wait = self.pacer.reserve(&chat_id, Instant::now());
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+            let envelope = self.post_envelope(endpoint, method, body).await?;
+            let Some(retry_after) = self.pacer.retry_delay(&envelope, retries.count) else {
+                return Ok(envelope);
+            };
+            retries.count += 1;
+            retries.waited += retry_after;
+            warn!(
+                method,
+                chat_id = chat_id.as_str(),
+                retry_after_secs = retry_after.as_secs(),
+                attempt = retries.count,
+                "Telegram rate limited the send, retrying"
+            );
+            self.pacer.back_off(&chat_id, Instant::now() + retry_after);
         }
+    }
 
-        let response = self
-            .client
-            .post(&endpoint)
-            .json(&body)
+    async fn post_envelope(
+        &self,
+        endpoint: &str,
+        method: &str,
+        body: &serde_json::Value,
+    ) -> anyhow::Result<TelegramApiEnvelope> {
+        self.client
+            .post(endpoint)
+            .json(body)
             .send()
             .await
-            .context("failed to call Telegram sendMessage")?;
+            .with_context(|| format!("failed to call Telegram {method}"))?
+            .json()
+            .await
+            .with_context(|| format!("failed to parse Telegram {method} response"))
+    }
 
-        let envelope: TelegramApiEnvelope = response
+    /// Call `getMe` and return the bot username. Used as a liveness probe.
+    pub async fn get_me(&self) -> anyhow::Result<String> {
+        let token = self.default_token()?;
+        let endpoint = format!("{TELEGRAM_API_BASE}/bot{token}/getMe");
+        let body: TelegramApiEnvelope = self
+            .client
+            .get(&endpoint)
+            .send()
+            .await
+            .context("failed to call Telegram getMe")?
             .json()
             .await
-            .context("failed to parse Telegram sendMessage response")?;
-        if !envelope.ok {
-            return Err(anyhow!(envelope.description.unwrap_or_else(|| {
-                "Telegram sendMessage returned ok=false".to_string()
-            })));
+            .context("failed to parse Telegram getMe response")?;
+        if !body.ok {
+            return Err(anyhow!(
+                body.description
+                    .unwrap_or_else(|| "Telegram getMe returned ok=false".to_string())
+            ));
         }
-
-        let message_id = envelope
+        let username = body
             .result
             .as_ref()
-            .and_then(|v| v.get("message_id"))
-            .and_then(|v| v.as_i64())
-            .map(|id| id.to_string())
-            .unwrap_or_default();
+            .and_then(|value| value.get("username"))
+            .and_then(|value| value.as_str())
+            .unwrap_or_default()
+            .to_string();
+        if !username.is_empty() {
+            let _ = self.bot_username.set(username.clone());
+        }
+        Ok(username)
+    }
 
-        Ok(TelegramSendResponse {
-            ok: true,
-            error: None,
-            message_ids: vec![message_id],
-            chunks_planned: 1,
-            chunks_sent: 1,
-            chunk_lengths: vec![request.text.chars().count()],
-            parity: TelegramSendParity {
-                max_chars_per_chunk: TELEGRAM_MAX_TEXT_CHARS,
-                all_chunks_within_limit: request.text.chars().count() <= TELEGRAM_MAX_TEXT_CHARS,
-            },
+    /// Send a message with optional inline keyboard buttons. Equivalent to
+    /// [`Self::send_message`] with `reply_markup` set.
+    #[allow(dead_code)] // button sends still go through the Node host
+    pub async fn send_message_with_buttons(
+        &self,
+        request: TelegramSendWithButtonsRequest,
+    ) -> anyhow::Result<TelegramSendResponse> {
+        self.send_message(TelegramSendRequest {
+            jid: request.jid,
+            text: request.text,
+            format: request.format,
+            reply_markup: request.reply_markup,
+            reply_to_message_id: None,
+            message_thread_id: None,
+            correlation_id: None,
         })
+        .await
+    }
+
+    /// Send `content` to the chat as a file named `file_name`. The Bot API
+    /// only takes uploads as multipart form data.
+    pub async fn send_document(
+        &self,
+        jid: &str,
+        file_name: &str,
+        content: &[u8],
+        caption: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let token = self.token_for(jid).await?;
+        let endpoint = format!("{TELEGRAM_API_BASE}/bot{token}/sendDocument");
+        let mut fields = vec![("chat_id", normalize_chat_id(jid).to_string())];
+        if let Some(thread_id) = split_jid(jid).1 {
+            fields.push(("message_thread_id", thread_id.to_string()));
+        }
+        if let Some(caption) = caption {
+            fields.push(("caption", caption.to_string()));
+        }
+        let (content_type, body) = multipart_document(&fields, file_name, content);
+        let envelope: TelegramApiEnvelope = self
+            .client
+            .post(&endpoint)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body)
+            .send()
+            .await
+            .context("failed to call Telegram sendDocument")?
+            .json()
+            .await
+            .context("failed to parse Telegram sendDocument response")?;
+        check_envelope(envelope, "sendDocument").map(|_| ())
+    }
+
+    /// Remove the inline keyboard from a sent message, leaving its text.
+    pub async fn clear_keyboard(&self, jid: &str, message_id: &str) -> anyhow::Result<()> {
+        let message_id = message_id
+            .parse::<i64>()
+            .with_context(|| format!("invalid message_id `{message_id}`"))?;
+        let token = self.token_for(jid).await?;
+        self.call_simple(
+            &token,
+            "editMessageReplyMarkup",
+            serde_json::json!({
+                "chat_id": normalize_chat_id(jid),
+                "message_id": message_id,
+                "reply_markup": { "inline_keyboard": [] },
+            }),
+        )
+        .await
     }
 
     /// Answer a Telegram callback query (acknowledge button press).
     pub async fn answer_callback_query(
         &self,
+        chat_jid: &str,
         callback_query_id: &str,
         text: Option<&str>,
     ) -> anyhow::Result<()> {
-        let token = self
-            .bot_token
-            .as_ref()
-            .ok_or_else(|| anyhow!("TELEGRAM_BOT_TOKEN is not set for intercomd"))?;
+        let token = self.token_for(chat_jid).await?;
 
         let endpoint = format!("{TELEGRAM_API_BASE}/bot{token}/answerCallbackQuery");
         let mut body = serde_json::json!({
@@ -481,8 +1060,12 @@ impl TelegramBridge {
     ) -> anyhow::Result<TelegramCallbackResponse> {
         let parts: Vec<&str> = request.data.splitn(2, ':').collect();
         if parts.len() != 2 {
-            self.answer_callback_query(&request.callback_query_id, Some("Invalid action"))
-                .await?;
+            self.answer_callback_query(
+                &request.chat_jid,
+                &request.callback_query_id,
+                Some("Invalid action"),
+            )
+            .await?;
             return Ok(TelegramCallbackResponse {
                 ok: false,
                 action: request.data.clone(),
@@ -495,16 +1078,20 @@ impl TelegramBridge {
         let action = parts[0];
         let target_id = parts[1].to_string();
         let sender = request.sender_name.as_deref().unwrap_or("unknown");
+        let source_group = self.group_folder(&request.chat_jid).await;
 
         let (write_result, status_text) = match action {
             "approve" => {
-                let resp = demarch.execute_write(
-                    intercom_core::WriteOperation::ApproveGate {
-                        gate_id: Some(target_id.clone()),
-                        reason: Some(format!("Approved by {sender} via Telegram")),
-                    },
-                    true,
-                );
+                let resp = demarch
+                    .execute_write(
+                        intercom_core::WriteOperation::ApproveGate {
+                            gate_id: Some(target_id.clone()),
+                            reason: Some(format!("Approved by {sender} via Telegram")),
+                        },
+                        source_group.as_deref(),
+                        true,
+                    )
+                    .await;
                 let ok = resp.status == intercom_core::DemarchStatus::Ok;
                 let status = if ok {
                     format!("✅ Gate {target_id} approved by @{sender}")
@@ -525,6 +1112,7 @@ impl TelegramBridge {
                     _ => unreachable!(),
                 };
                 self.answer_callback_query(
+                    &request.chat_jid,
                     &request.callback_query_id,
                     Some(&format!("{label} not yet implemented")),
                 )
@@ -538,8 +1126,12 @@ impl TelegramBridge {
                 });
             }
             _ => {
-                self.answer_callback_query(&request.callback_query_id, Some("Unknown action"))
-                    .await?;
+                self.answer_callback_query(
+                    &request.chat_jid,
+                    &request.callback_query_id,
+                    Some("Unknown action"),
+                )
+                .await?;
                 return Ok(TelegramCallbackResponse {
                     ok: false,
                     action: action.to_string(),
@@ -556,12 +1148,17 @@ impl TelegramBridge {
                 jid: request.chat_jid.clone(),
                 message_id: request.message_id.clone(),
                 text: status_text.clone(),
+                format: None,
             })
             .await;
 
         // Answer the callback query (dismisses loading spinner)
-        self.answer_callback_query(&request.callback_query_id, Some(&status_text))
-            .await?;
+        self.answer_callback_query(
+            &request.chat_jid,
+            &request.callback_query_id,
+            Some(&status_text),
+        )
+        .await?;
 
         Ok(TelegramCallbackResponse {
             ok: true,
@@ -582,77 +1179,686 @@ impl TelegramBridge {
     }
 }
 
-impl TelegramSendResponse {
-    pub fn from_error(err: impl Into<String>) -> Self {
-        let error = err.into();
-        Self {
-            ok: false,
-            error: Some(error),
-            message_ids: Vec::new(),
-            chunks_planned: 0,
-            chunks_sent: 0,
-            chunk_lengths: Vec::new(),
-            parity: TelegramSendParity {
-                max_chars_per_chunk: TELEGRAM_MAX_TEXT_CHARS,
-                all_chunks_within_limit: true,
-            },
-        }
-    }
+/// Subset of a Bot API `Update` used by long-polling ingestion.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramUpdate {
+    pub message: Option<TelegramMessage>,
+    pub edited_message: Option<TelegramMessage>,
+    pub callback_query: Option<TelegramCallbackQuery>,
 }
 
-impl TelegramEditResponse {
-    pub fn from_error(err: impl Into<String>) -> Self {
-        Self {
-            ok: false,
-            error: Some(err.into()),
-            truncated: false,
-            parity_max_chars: TELEGRAM_MAX_TEXT_CHARS,
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramMessage {
+    pub message_id: i64,
+    pub date: i64,
+    pub edit_date: Option<i64>,
+    pub chat: TelegramChat,
+    pub from: Option<TelegramUser>,
+    pub text: Option<String>,
+    pub caption: Option<String>,
+    #[serde(default)]
+    pub entities: Vec<TelegramEntity>,
+    pub photo: Option<serde_json::Value>,
+    pub video: Option<serde_json::Value>,
+    pub voice: Option<serde_json::Value>,
+    pub audio: Option<serde_json::Value>,
+    pub document: Option<TelegramDocument>,
+    pub sticker: Option<TelegramSticker>,
+    pub location: Option<serde_json::Value>,
+    pub contact: Option<serde_json::Value>,
+    /// Forum topic, or in ordinary groups the reply thread; only the former
+    /// counts, see [`TelegramMessage::topic`].
+    pub message_thread_id: Option<i64>,
+    #[serde(default)]
+    pub is_topic_message: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramChat {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramUser {
+    pub id: i64,
+    pub first_name: Option<String>,
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramEntity {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// UTF-16 code units, as the Bot API counts them.
+    pub offset: usize,
+    pub length: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramDocument {
+    pub file_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramSticker {
+    pub emoji: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramCallbackQuery {
+    pub id: String,
+    pub from: TelegramUser,
+    pub message: Option<TelegramMessage>,
+    pub data: Option<String>,
+}
+
+/// Slash command received from Telegram. `command` has the leading `/` and
+/// any `@botname` suffix removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TelegramCommand {
+    pub chat_jid: String,
+    pub chat_type: String,
+    pub chat_name: String,
+    pub command: String,
+    pub args: String,
+    pub sender_id: Option<String>,
+}
+
+/// A polled or webhook update in the shape the rest of intercomd already
+/// handles.
+#[derive(Debug, Clone)]
+pub enum InboundUpdate {
+    Ingress(TelegramIngressRequest),
+    Command(TelegramCommand),
+    Callback(TelegramCallbackRequest),
+}
+
+impl TelegramUser {
+    fn display_name(&self) -> String {
+        self.first_name
+            .clone()
+            .filter(|name| !name.is_empty())
+            .or_else(|| self.username.clone())
+            .unwrap_or_else(|| self.id.to_string())
+    }
+}
+
+impl TelegramMessage {
+    /// Forum topic the message was posted in.
+    fn topic(&self) -> Option<i64> {
+        self.message_thread_id.filter(|_| self.is_topic_message)
+    }
+
+    fn chat_jid(&self) -> String {
+        chat_jid(self.chat.id, self.topic())
+    }
+
+    /// Placeholder for media the agent can't see, e.g. `[Photo] caption`.
+    fn placeholder(&self) -> Option<String> {
+        let placeholder = if self.photo.is_some() {
+            "[Photo]".to_string()
+        } else if self.video.is_some() {
+            "[Video]".to_string()
+        } else if self.voice.is_some() {
+            "[Voice message]".to_string()
+        } else if self.audio.is_some() {
+            "[Audio]".to_string()
+        } else if let Some(ref document) = self.document {
+            format!(
+                "[Document: {}]",
+                document.file_name.as_deref().unwrap_or("file")
+            )
+        } else if let Some(ref sticker) = self.sticker {
+            format!("[Sticker {}]", sticker.emoji.as_deref().unwrap_or_default())
+        } else if self.location.is_some() {
+            "[Location]".to_string()
+        } else if self.contact.is_some() {
+            "[Contact]".to_string()
+        } else {
+            return None;
+        };
+        Some(match self.caption.as_deref() {
+            Some(caption) => format!("{placeholder} {caption}"),
+            None => placeholder,
+        })
+    }
+
+    /// Whether an entity in `text` is an @mention of `bot_username`.
+    fn mentions(&self, text: &str, bot_username: &str) -> bool {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        self.entities
+            .iter()
+            .filter(|entity| entity.kind == "mention")
+            .filter_map(|entity| units.get(entity.offset..entity.offset + entity.length))
+            .any(|mention| {
+                String::from_utf16_lossy(mention)
+                    .trim_start_matches('@')
+                    .eq_ignore_ascii_case(bot_username)
+            })
+    }
+}
+
+fn unix_to_iso(secs: i64) -> String {
+    chrono::DateTime::from_timestamp(secs, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// Split `/cmd@bot args` into `(cmd, args)`. Commands addressed to a
+/// different bot yield `None`.
+fn parse_command<'a>(text: &'a str, bot_username: Option<&str>) -> Option<(&'a str, &'a str)> {
+    let rest = text.strip_prefix('/')?;
+    let (head, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let (command, target) = head
+        .split_once('@')
+        .map_or((head, None), |(c, t)| (c, Some(t)));
+    if let (Some(target), Some(bot)) = (target, bot_username)
+        && !target.eq_ignore_ascii_case(bot)
+    {
+        return None;
+    }
+    (!command.is_empty()).then_some((command, args.trim()))
+}
+
+/// Convert a Bot API update the same way the Node host does: commands go to
+/// the command handler, text and media placeholders become ingress requests,
+/// and an @mention of the bot is rewritten into the assistant trigger.
+pub fn convert_update(
+    update: TelegramUpdate,
+    bot_username: Option<&str>,
+    assistant_name: &str,
+) -> Option<InboundUpdate> {
+    if let Some(query) = update.callback_query {
+        let message = query.message?;
+        return Some(InboundUpdate::Callback(TelegramCallbackRequest {
+            callback_query_id: query.id,
+            chat_jid: message.chat_jid(),
+            message_id: message.message_id.to_string(),
+            sender_id: Some(query.from.id.to_string()),
+            sender_name: Some(query.from.display_name()),
+            data: query.data?,
+        }));
+    }
+
+    let (message, kind) = match (update.message, update.edited_message) {
+        (Some(message), _) => (message, TelegramUpdateKind::Message),
+        (None, Some(message)) => (message, TelegramUpdateKind::EditedMessage),
+        (None, None) => return None,
+    };
+
+    let chat_jid = message.chat_jid();
+    let sender_name = message
+        .from
+        .as_ref()
+        .map(TelegramUser::display_name)
+        .unwrap_or_else(|| "Unknown".to_string());
+    let sender_id = message.from.as_ref().map(|user| user.id.to_string());
+    let chat_name = if message.chat.kind == "private" {
+        sender_name.clone()
+    } else {
+        message
+            .chat
+            .title
+            .clone()
+            .unwrap_or_else(|| chat_jid.clone())
+    };
+
+    let content = match message.text.as_deref() {
+        Some(text) if text.starts_with('/') => {
+            if kind != TelegramUpdateKind::Message {
+                return None;
+            }
+            let (command, args) = parse_command(text, bot_username)?;
+            return Some(InboundUpdate::Command(TelegramCommand {
+                chat_jid,
+                chat_type: message.chat.kind.clone(),
+                chat_name,
+                command: command.to_string(),
+                args: args.to_string(),
+                sender_id,
+            }));
+        }
+        Some(text) => {
+            let trigger = format!("@{assistant_name}");
+            let mentioned = bot_username.is_some_and(|bot| message.mentions(text, bot));
+            if mentioned && !trigger_matches(text, &trigger) {
+                format!("{trigger} {text}")
+            } else {
+                text.to_string()
+            }
+        }
+        None => message.placeholder()?,
+    };
+
+    let timestamp = match kind {
+        TelegramUpdateKind::EditedMessage => unix_to_iso(message.edit_date.unwrap_or(message.date)),
+        _ => unix_to_iso(message.date),
+    };
+
+    Some(InboundUpdate::Ingress(TelegramIngressRequest {
+        chat_jid,
+        chat_name: Some(chat_name),
+        chat_type: Some(message.chat.kind),
+        message_id: message.message_id.to_string(),
+        sender_id,
+        sender_name: Some(sender_name),
+        content,
+        timestamp,
+        persist: true,
+        kind,
+        attachments: Vec::new(),
+    }))
+}
+
+impl TelegramBridge {
+    /// One `getUpdates` long-poll. Updates are returned raw so a single
+    /// malformed update can be skipped without stalling the offset.
+    pub async fn get_updates(
+        &self,
+        offset: Option<i64>,
+        timeout_secs: u64,
+    ) -> anyhow::Result<Vec<serde_json::Value>> {
+        let token = self.default_token()?;
+        let endpoint = format!("{TELEGRAM_API_BASE}/bot{token}/getUpdates");
+        let body: TelegramApiEnvelope = self
+            .client
+            .post(&endpoint)
+            .json(&serde_json::json!({
+                "offset": offset,
+                "timeout": timeout_secs,
+                "allowed_updates": ["message", "edited_message", "callback_query"],
+            }))
+            .send()
+            .await
+            .context("failed to call Telegram getUpdates")?
+            .json()
+            .await
+            .context("failed to parse Telegram getUpdates response")?;
+        if !body.ok {
+            return Err(anyhow!(body.description.unwrap_or_else(|| {
+                "Telegram getUpdates returned ok=false".to_string()
+            })));
+        }
+        match body.result {
+            Some(serde_json::Value::Array(updates)) => Ok(updates),
+            _ => Ok(Vec::new()),
         }
     }
+
+    /// Call a Bot API method that only reports success or failure.
+    async fn call_simple(
+        &self,
+        token: &str,
+        method: &str,
+        payload: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        let endpoint = format!("{TELEGRAM_API_BASE}/bot{token}/{method}");
+        let body: TelegramApiEnvelope = self
+            .client
+            .post(&endpoint)
+            .json(&payload)
+            .send()
+            .await
+            .with_context(|| format!("failed to call Telegram {method}"))?
+            .json()
+            .await
+            .with_context(|| format!("failed to parse Telegram {method} response"))?;
+        if !body.ok {
+            return Err(anyhow!(
+                body.description
+                    .unwrap_or_else(|| format!("Telegram {method} returned ok=false"))
+            ));
+        }
+        Ok(())
+    }
+
+    pub async fn set_webhook(&self, url: &str, secret: &str) -> anyhow::Result<()> {
+        self.call_simple(
+            self.default_token()?,
+            "setWebhook",
+            serde_json::json!({
+                "url": url,
+                "secret_token": secret,
+                "allowed_updates": ["message", "edited_message", "callback_query"],
+            }),
+        )
+        .await
+    }
+
+    /// Remove any webhook so `getUpdates` is allowed. Pending updates are kept.
+    pub async fn delete_webhook(&self) -> anyhow::Result<()> {
+        self.call_simple(
+            self.default_token()?,
+            "deleteWebhook",
+            serde_json::json!({ "drop_pending_updates": false }),
+        )
+        .await
+    }
 }
 
-fn normalize_chat_id(jid: &str) -> &str {
-    jid.strip_prefix("tg:").unwrap_or(jid)
+/// Constant-time comparison for the webhook secret header.
+pub fn secret_matches(expected: &str, provided: &str) -> bool {
+    let (expected, provided) = (expected.as_bytes(), provided.as_bytes());
+    expected.len() == provided.len()
+        && expected
+            .iter()
+            .zip(provided)
+            .fold(0_u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Resolve the bot username and register the webhook, retrying until both
+/// succeed. Used when `telegram.ingest = "webhook"`.
+pub async fn register_webhook(
+    bridge: TelegramBridge,
+    config: TelegramConfig,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let retry = Duration::from_millis(config.poll_retry_ms.max(100));
+    let Some(secret) = bridge.webhook_secret(&config) else {
+        return;
+    };
+    loop {
+        let outcome = async {
+            let username = bridge.get_me().await?;
+            if let Some(url) = config.webhook_url.as_deref().filter(|u| !u.is_empty()) {
+                bridge.set_webhook(url, &secret).await?;
+                info!(bot = %username, url, "Telegram webhook registered");
+            } else {
+                info!(bot = %username, "Telegram webhook ingest enabled; webhook_url unset, not calling setWebhook");
+            }
+            anyhow::Ok(())
+        }
+        .await;
+        match outcome {
+            Ok(()) => return,
+            Err(e) => {
+                warn!(err = %format!("{e:#}"), "Telegram webhook registration failed, retrying");
+                record_error(SUBSYSTEM_TELEGRAM, &e);
+                if !sleep_or_shutdown(retry, &mut shutdown).await {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Sleep for `delay` unless shutdown is signalled first. Returns false on
+/// shutdown.
+async fn sleep_or_shutdown(delay: Duration, shutdown: &mut watch::Receiver<bool>) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => true,
+        _ = shutdown.changed() => false,
+    }
 }
 
-fn split_for_telegram(text: &str, max_chars: usize) -> Vec<String> {
-    let mut chunks = Vec::new();
-    let mut current = String::new();
-    let mut chars_in_current = 0_usize;
+/// Long-poll `getUpdates` and hand every converted update to `handle`, in
+/// order. Runs until `shutdown` fires. Used when `telegram.ingest = "poll"`.
+pub async fn run_poll_loop<F, Fut>(
+    bridge: TelegramBridge,
+    config: TelegramConfig,
+    assistant_name: String,
+    mut shutdown: watch::Receiver<bool>,
+    mut handle: F,
+) where
+    F: FnMut(InboundUpdate) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let retry = Duration::from_millis(config.poll_retry_ms.max(100));
+
+    // The bot username is needed to recognise @mentions and /cmd@bot.
+    let bot_username = loop {
+        match bridge.get_me().await {
+            Ok(username) => break username,
+            Err(e) => {
+                warn!(err = %format!("{e:#}"), "Telegram getMe failed, retrying before polling");
+                record_error(SUBSYSTEM_TELEGRAM, &e);
+                if !sleep_or_shutdown(retry, &mut shutdown).await {
+                    return;
+                }
+            }
+        }
+    };
+    // getUpdates is refused while a webhook is set.
+    if let Err(e) = bridge.delete_webhook().await {
+        warn!(err = %format!("{e:#}"), "Telegram deleteWebhook failed");
+    }
+    info!(bot = %bot_username, "Telegram long-polling started");
+
+    let mut offset: Option<i64> = None;
+    loop {
+        let batch = tokio::select! {
+            batch = bridge.get_updates(offset, config.poll_timeout_secs) => batch,
+            _ = shutdown.changed() => break,
+        };
+        let updates = match batch {
+            Ok(updates) => updates,
+            Err(e) => {
+                warn!(err = %format!("{e:#}"), "Telegram getUpdates failed");
+                record_error(SUBSYSTEM_TELEGRAM, &e);
+                if !sleep_or_shutdown(retry, &mut shutdown).await {
+                    break;
+                }
+                continue;
+            }
+        };
 
-    for ch in text.chars() {
-        if chars_in_current >= max_chars {
-            chunks.push(current);
-            current = String::new();
-            chars_in_current = 0;
+        for raw in updates {
+            let Some(update_id) = raw.get("update_id").and_then(|id| id.as_i64()) else {
+                continue;
+            };
+            offset = Some(offset.map_or(update_id + 1, |o| o.max(update_id + 1)));
+            match serde_json::from_value::<TelegramUpdate>(raw) {
+                Ok(update) => {
+                    if let Some(polled) =
+                        convert_update(update, Some(&bot_username), &assistant_name)
+                    {
+                        handle(polled).await;
+                    }
+                }
+                Err(e) => warn!(update_id, err = %e, "skipping malformed Telegram update"),
+            }
         }
-        current.push(ch);
-        chars_in_current += 1;
+    }
+    info!("Telegram long-polling stopped");
+}
+
+impl ChannelBridge for TelegramBridge {
+    fn name(&self) -> &'static str {
+        TELEGRAM_CHANNEL
+    }
+
+    fn profile(&self) -> &ChannelProfile {
+        &self.profile
     }
 
-    if !current.is_empty() {
-        chunks.push(current);
+    fn max_chars(&self) -> usize {
+        TelegramBridge::max_chars(self)
     }
 
-    chunks
+    fn send<'a>(&'a self, message: &'a OutboundMessage) -> ChannelFuture<'a, Vec<String>> {
+        Box::pin(async move {
+            let response = self
+                .send_message(TelegramSendRequest {
+                    jid: message.jid.clone(),
+                    text: message.text.clone(),
+                    format: None,
+                    reply_markup: None,
+                    reply_to_message_id: message.reply_to.clone(),
+                    message_thread_id: None,
+                    correlation_id: message.correlation_id.clone(),
+                })
+                .await?;
+            Ok(response.message_ids)
+        })
+    }
+
+    fn edit<'a>(
+        &'a self,
+        jid: &'a str,
+        message_id: &'a str,
+        text: &'a str,
+    ) -> ChannelFuture<'a, ()> {
+        Box::pin(async move {
+            self.edit_message(TelegramEditRequest {
+                jid: jid.to_string(),
+                message_id: message_id.to_string(),
+                text: text.to_string(),
+                format: None,
+            })
+            .await?;
+            Ok(())
+        })
+    }
+
+    fn normalize_ingress(&self, content: &str, attachments: &[Attachment]) -> String {
+        normalize_ingress_content(content, attachments)
+    }
+
+    fn draft_interval(&self) -> Option<Duration> {
+        TelegramBridge::draft_interval(self)
+    }
 }
 
-fn truncate_for_telegram(text: &str, max_chars: usize) -> (String, bool) {
-    let mut output = String::new();
-    let mut count = 0_usize;
+impl TelegramSendResponse {
+    pub fn from_error(err: impl Into<String>) -> Self {
+        let error = err.into();
+        Self {
+            ok: false,
+            error: Some(error),
+            message_ids: Vec::new(),
+            chunks_planned: 0,
+            chunks_sent: 0,
+            chunk_lengths: Vec::new(),
+            parity: TelegramSendParity {
+                max_chars_per_chunk: TELEGRAM_MAX_TEXT_CHARS,
+                all_chunks_within_limit: true,
+            },
+            retries: 0,
+            max_retries: 0,
+            retry_wait_ms: 0,
+        }
+    }
+}
 
-    for ch in text.chars() {
-        if count >= max_chars {
-            return (output, true);
+impl TelegramEditResponse {
+    pub fn from_error(err: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(err.into()),
+            truncated: false,
+            parity_max_chars: TELEGRAM_MAX_TEXT_CHARS,
         }
-        output.push(ch);
-        count += 1;
     }
+}
+
+/// JID for a chat, or for one forum topic in it: `tg:<chat>` or
+/// `tg:<chat>:<topic>`.
+pub fn chat_jid(chat_id: i64, topic: Option<i64>) -> String {
+    match topic {
+        Some(topic) => format!("tg:{chat_id}:{topic}"),
+        None => format!("tg:{chat_id}"),
+    }
+}
+
+/// Split a JID into the Bot API `chat_id` and forum topic, if any.
+fn split_jid(jid: &str) -> (&str, Option<i64>) {
+    let id = jid.strip_prefix("tg:").unwrap_or(jid);
+    match id.rsplit_once(':') {
+        Some((chat, topic)) => match topic.parse() {
+            Ok(topic) => (chat, Some(topic)),
+            Err(_) => (id, None),
+        },
+        None => (id, None),
+    }
+}
+
+/// The whole chat's JID for a topic JID; other JIDs come back unchanged.
+pub fn base_jid(jid: &str) -> &str {
+    match jid.rsplit_once(':') {
+        Some((base, topic)) if base.contains(':') && topic.parse::<i64>().is_ok() => base,
+        _ => jid,
+    }
+}
+
+/// JID of the registered group a chat belongs to: a topic registered on its
+/// own keeps its JID, any other topic falls back to its chat's registration.
+pub fn registered_jid(jid: &str, is_registered: impl Fn(&str) -> bool) -> Option<&str> {
+    if is_registered(jid) {
+        return Some(jid);
+    }
+    let base = base_jid(jid);
+    (base != jid && is_registered(base)).then_some(base)
+}
+
+fn normalize_chat_id(jid: &str) -> &str {
+    split_jid(jid).0
+}
+
+/// A `multipart/form-data` body of text `fields` and a `document` file
+/// part, with its content type. The boundary comes from the file's hash,
+/// so it cannot occur inside the file.
+fn multipart_document(
+    fields: &[(&str, String)],
+    file_name: &str,
+    content: &[u8],
+) -> (String, Vec<u8>) {
+    let boundary = format!("intercom-{}", hex::encode(&Sha256::digest(content)[..12]));
+    let mut body = Vec::with_capacity(content.len() + 512);
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+    let file_name = file_name.replace(['"', '\r', '\n'], "_");
+    body.extend_from_slice(
+        format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"document\"; filename=\"{file_name}\"\r\n\
+             Content-Type: text/plain\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(content);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    (format!("multipart/form-data; boundary={boundary}"), body)
+}
+
+/// Telegram `parse_mode` for a dialect; `None` sends plain text.
+fn parse_mode(dialect: MarkdownDialect) -> Option<&'static str> {
+    match dialect {
+        MarkdownDialect::Plain => None,
+        MarkdownDialect::Markdown => Some("Markdown"),
+        MarkdownDialect::MarkdownV2 => Some("MarkdownV2"),
+        MarkdownDialect::Html => Some("HTML"),
+    }
+}
+
+/// Whether Telegram refused a message because its markup did not parse,
+/// e.g. "Bad Request: can't parse entities: ...".
+fn is_entity_parse_error(description: &str) -> bool {
+    let description = description.to_ascii_lowercase();
+    description.contains("can't parse entities") || description.contains("can't find end of")
+}
 
-    (output, false)
+fn check_envelope(
+    envelope: TelegramApiEnvelope,
+    method: &str,
+) -> anyhow::Result<TelegramApiEnvelope> {
+    if envelope.ok {
+        return Ok(envelope);
+    }
+    Err(anyhow!(envelope.description.unwrap_or_else(|| format!(
+        "Telegram {method} returned ok=false"
+    ))))
 }
 
-fn trigger_matches(content: &str, trigger_pattern: &str) -> bool {
+pub(crate) fn trigger_matches(content: &str, trigger_pattern: &str) -> bool {
     let trigger = trigger_pattern.trim();
     if trigger.is_empty() {
         return true;
@@ -669,7 +1875,10 @@ fn trigger_matches(content: &str, trigger_pattern: &str) -> bool {
         .unwrap_or(false)
 }
 
-fn resolve_runtime(config: &IntercomConfig, group: &RegisteredGroupRow) -> RuntimeResolution {
+pub(crate) fn resolve_runtime(
+    config: &IntercomConfig,
+    group: &RegisteredGroupRow,
+) -> RuntimeResolution {
     let requested_runtime = group
         .runtime
         .as_deref()
@@ -726,6 +1935,7 @@ fn load_registered_group(
     let has_requires_trigger = sqlite_has_column(conn, "registered_groups", "requires_trigger")?;
     let has_runtime = sqlite_has_column(conn, "registered_groups", "runtime")?;
     let has_model = sqlite_has_column(conn, "registered_groups", "model")?;
+    let has_muted_until = sqlite_has_column(conn, "registered_groups", "muted_until")?;
 
     let requires_expr = if has_requires_trigger {
         "COALESCE(requires_trigger, 1)"
@@ -738,9 +1948,14 @@ fn load_registered_group(
         "NULL AS runtime"
     };
     let model_expr = if has_model { "model" } else { "NULL AS model" };
+    let muted_expr = if has_muted_until {
+        "muted_until"
+    } else {
+        "NULL AS muted_until"
+    };
 
     let query = format!(
-        "SELECT name, folder, trigger_pattern, {requires_expr}, {runtime_expr}, {model_expr}
+        "SELECT name, folder, trigger_pattern, {requires_expr}, {runtime_expr}, {model_expr}, {muted_expr}
          FROM registered_groups
          WHERE jid = ?1
          LIMIT 1"
@@ -755,6 +1970,7 @@ fn load_registered_group(
             requires_trigger: requires_trigger != 0,
             runtime: row.get(4)?,
             model: row.get(5)?,
+            muted_until: row.get(6)?,
         })
     })
     .optional()
@@ -781,11 +1997,43 @@ fn ensure_telegram_persistence_schema(conn: &Connection) -> anyhow::Result<()> {
           timestamp TEXT,
           is_from_me INTEGER,
           is_bot_message INTEGER DEFAULT 0,
+          edited_at TEXT,
+          deleted_at TEXT,
           PRIMARY KEY (id, chat_jid)
         );
+
+        CREATE TABLE IF NOT EXISTS message_edits (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          message_id TEXT NOT NULL,
+          chat_jid TEXT NOT NULL,
+          previous_content TEXT,
+          edited_at TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS attachments (
+          id INTEGER PRIMARY KEY AUTOINCREMENT,
+          message_id TEXT NOT NULL,
+          chat_jid TEXT NOT NULL,
+          kind TEXT NOT NULL,
+          mime_type TEXT,
+          file_name TEXT,
+          file_path TEXT,
+          caption TEXT,
+          size_bytes INTEGER
+        );
         ",
     )
-    .context("failed to ensure Telegram sqlite persistence schema")
+    .context("failed to ensure Telegram sqlite persistence schema")?;
+
+    // Databases created by the Node host predate edit tracking.
+    for column in ["edited_at", "deleted_at"] {
+        if !sqlite_has_column(conn, "messages", column)? {
+            conn.execute_batch(&format!("ALTER TABLE messages ADD COLUMN {column} TEXT"))
+                .context("failed to ensure Telegram sqlite persistence schema")?;
+        }
+    }
+
+    Ok(())
 }
 
 fn persist_chat_metadata(
@@ -843,6 +2091,118 @@ fn persist_inbound_message(
     Ok(())
 }
 
+/// A captionless photo arrives with empty text; describe its attachments
+/// so the message is not dropped as empty downstream.
+fn normalize_ingress_content(content: &str, attachments: &[Attachment]) -> String {
+    if !content.trim().is_empty() || attachments.is_empty() {
+        return content.to_string();
+    }
+    attachments
+        .iter()
+        .map(Attachment::describe)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn persist_attachments(conn: &Connection, request: &TelegramIngressRequest) -> anyhow::Result<()> {
+    conn.execute(
+        "DELETE FROM attachments WHERE message_id = ?1 AND chat_jid = ?2",
+        params![request.message_id, request.chat_jid],
+    )
+    .context("failed to persist Telegram attachments")?;
+    for attachment in &request.attachments {
+        conn.execute(
+            "\
+            INSERT INTO attachments
+              (message_id, chat_jid, kind, mime_type, file_name, file_path, caption, size_bytes)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ",
+            params![
+                request.message_id,
+                request.chat_jid,
+                attachment.kind,
+                attachment.mime_type,
+                attachment.file_name,
+                attachment.file_path,
+                attachment.caption,
+                attachment.size_bytes,
+            ],
+        )
+        .context("failed to persist Telegram attachments")?;
+    }
+
+    Ok(())
+}
+
+/// Apply an edit the way the live store does: the prior content moves to
+/// `message_edits` and the row is flagged with `edited_at`. Edits to
+/// messages we never saw are ignored rather than inserted out of order.
+fn persist_message_edit(conn: &Connection, request: &TelegramIngressRequest) -> anyhow::Result<()> {
+    let prior: Option<Option<String>> = conn
+        .query_row(
+            "SELECT content FROM messages WHERE id = ?1 AND chat_jid = ?2",
+            params![request.message_id, request.chat_jid],
+            |r| r.get(0),
+        )
+        .optional()
+        .context("failed to persist Telegram message edit")?;
+    let Some(prior) = prior else {
+        return Ok(());
+    };
+    if prior.as_deref() == Some(request.content.as_str()) {
+        return Ok(());
+    }
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("failed to persist Telegram message edit")?;
+    tx.execute(
+        "\
+        INSERT INTO message_edits (message_id, chat_jid, previous_content, edited_at)
+        VALUES (?1, ?2, ?3, ?4)
+        ",
+        params![
+            request.message_id,
+            request.chat_jid,
+            prior,
+            request.timestamp
+        ],
+    )
+    .context("failed to persist Telegram message edit")?;
+    tx.execute(
+        "UPDATE messages SET content = ?3, edited_at = ?4 WHERE id = ?1 AND chat_jid = ?2",
+        params![
+            request.message_id,
+            request.chat_jid,
+            request.content,
+            request.timestamp
+        ],
+    )
+    .context("failed to persist Telegram message edit")?;
+    tx.commit()
+        .context("failed to persist Telegram message edit")?;
+
+    Ok(())
+}
+
+/// Tombstone a deleted message with `deleted_at`. The row and its content
+/// are kept so message ids stay reserved; readers skip tombstoned rows.
+fn persist_message_tombstone(
+    conn: &Connection,
+    request: &TelegramIngressRequest,
+) -> anyhow::Result<()> {
+    conn.execute(
+        "\
+        UPDATE messages SET deleted_at = COALESCE(deleted_at, ?3)
+        WHERE id = ?1 AND chat_jid = ?2
+        ",
+        params![request.message_id, request.chat_jid, request.timestamp],
+    )
+    .context("failed to persist Telegram message deletion")?;
+
+    Ok(())
+}
+
 fn sqlite_has_table(conn: &Connection, table: &str) -> anyhow::Result<bool> {
     let mut stmt =
         conn.prepare("SELECT 1 FROM sqlite_master WHERE type='table' AND name = ?1 LIMIT 1")?;
@@ -869,22 +2229,321 @@ mod tests {
     use tempfile::TempDir;
 
     #[test]
-    fn split_for_telegram_keeps_chunks_within_limit() {
-        let text = "a".repeat(9005);
-        let chunks = split_for_telegram(&text, TELEGRAM_MAX_TEXT_CHARS);
-        assert_eq!(chunks.len(), 3);
+    fn entity_parse_errors_trigger_plain_fallback() {
+        assert!(is_entity_parse_error(
+            "Bad Request: can't parse entities: Character '.' is reserved and must be escaped"
+        ));
+        assert!(is_entity_parse_error(
+            "Bad Request: Can't find end of Bold entity at byte offset 3"
+        ));
+        assert!(!is_entity_parse_error(
+            "Forbidden: bot was blocked by the user"
+        ));
+        assert_eq!(parse_mode(MarkdownDialect::MarkdownV2), Some("MarkdownV2"));
+        assert_eq!(parse_mode(MarkdownDialect::Plain), None);
+    }
+
+    #[test]
+    fn callback_data_routes_commands_messages_and_kernel_actions() {
+        assert_eq!(
+            CallbackAction::parse("cmd:model claude-sonnet-4-6"),
+            CallbackAction::Command {
+                command: "model".into(),
+                args: "claude-sonnet-4-6".into()
+            }
+        );
+        assert_eq!(
+            CallbackAction::parse("cmd:/status"),
+            CallbackAction::Command {
+                command: "status".into(),
+                args: String::new()
+            }
+        );
+        assert_eq!(
+            CallbackAction::parse("msg: yes, ship it "),
+            CallbackAction::Message("yes, ship it".into())
+        );
+        assert_eq!(
+            CallbackAction::parse("approve:gate-1"),
+            CallbackAction::Kernel
+        );
+        assert_eq!(CallbackAction::parse("cmd:"), CallbackAction::Kernel);
+        assert_eq!(CallbackAction::parse("msg:  "), CallbackAction::Kernel);
+    }
+
+    #[test]
+    fn options_become_message_buttons_within_limits() {
+        let long = "x".repeat(MAX_CALLBACK_DATA_BYTES);
+        let options = vec![
+            "Yes".to_string(),
+            " ".to_string(),
+            long,
+            "No".into(),
+            "Later".into(),
+        ];
+        let keyboard = options_keyboard(&options).expect("keyboard");
+        keyboard.validate().unwrap();
+        let rows: Vec<Vec<&str>> = keyboard
+            .inline_keyboard
+            .iter()
+            .map(|row| row.iter().map(|b| b.callback_data.as_str()).collect())
+            .collect();
+        assert_eq!(rows, vec![vec!["msg:Yes", "msg:No"], vec!["msg:Later"]]);
+        assert!(options_keyboard(&[String::new()]).is_none());
+
+        let oversized = InlineKeyboardMarkup {
+            inline_keyboard: vec![vec![InlineKeyboardButton::message("Go", &"y".repeat(61))]],
+        };
+        assert!(oversized.validate().is_err());
         assert!(
-            chunks
-                .iter()
-                .all(|chunk| chunk.chars().count() <= TELEGRAM_MAX_TEXT_CHARS)
+            InlineKeyboardMarkup {
+                inline_keyboard: vec![vec![]]
+            }
+            .validate()
+            .is_err()
+        );
+    }
+
+    fn pacer(interval_ms: u64) -> SendPacer {
+        SendPacer::new(&TelegramConfig {
+            send_interval_ms: interval_ms,
+            max_send_retries: 2,
+            max_retry_after_secs: 30,
+            ..TelegramConfig::default()
+        })
+    }
+
+    #[test]
+    fn pacer_spaces_sends_per_chat() {
+        let p = pacer(1_000);
+        let t0 = Instant::now();
+        assert_eq!(p.reserve("1", t0), Duration::ZERO);
+        assert_eq!(p.reserve("1", t0), Duration::from_secs(1));
+        assert_eq!(
+            p.reserve("1", t0 + Duration::from_millis(500)),
+            Duration::from_millis(1_500)
+        );
+        assert_eq!(p.reserve("2", t0), Duration::ZERO);
+        assert_eq!(p.reserve("2", t0 + Duration::from_secs(5)), Duration::ZERO);
+
+        p.back_off("2", t0 + Duration::from_secs(10));
+        assert_eq!(
+            p.reserve("2", t0 + Duration::from_secs(6)),
+            Duration::from_secs(4)
+        );
+        // A shorter back-off never pulls the slot earlier.
+        p.back_off("2", t0 + Duration::from_secs(1));
+        assert_eq!(
+            p.reserve("2", t0 + Duration::from_secs(6)),
+            Duration::from_secs(5)
+        );
+    }
+
+    fn envelope(json: serde_json::Value) -> TelegramApiEnvelope {
+        serde_json::from_value(json).expect("valid envelope")
+    }
+
+    #[test]
+    fn retries_honor_retry_after_within_budget() {
+        let p = pacer(0);
+        let limited = envelope(serde_json::json!({
+            "ok": false,
+            "error_code": 429,
+            "description": "Too Many Requests: retry after 7",
+            "parameters": {"retry_after": 7}
+        }));
+        assert_eq!(p.retry_delay(&limited, 0), Some(Duration::from_secs(7)));
+        assert_eq!(p.retry_delay(&limited, 1), Some(Duration::from_secs(7)));
+        assert_eq!(p.retry_delay(&limited, 2), None);
+
+        let too_long = envelope(serde_json::json!({
+            "ok": false, "error_code": 429, "parameters": {"retry_after": 31}
+        }));
+        assert_eq!(p.retry_delay(&too_long, 0), None);
+
+        let bare = envelope(serde_json::json!({"ok": false, "error_code": 429}));
+        assert_eq!(bare.retry_after(), Some(Duration::from_secs(1)));
+        let forbidden = envelope(serde_json::json!({
+            "ok": false, "error_code": 403, "description": "Forbidden: bot was blocked by the user"
+        }));
+        assert_eq!(p.retry_delay(&forbidden, 0), None);
+        let sent = envelope(serde_json::json!({"ok": true, "result": {"message_id": 1}}));
+        assert_eq!(sent.retry_after(), None);
+    }
+
+    fn update(json: serde_json::Value) -> TelegramUpdate {
+        serde_json::from_value(json).expect("valid update")
+    }
+
+    #[test]
+    fn polled_mention_becomes_trigger_and_media_gets_placeholder() {
+        let text = update(serde_json::json!({
+            "update_id": 1,
+            "message": {
+                "message_id": 7,
+                "date": 1_700_000_000,
+                "chat": {"id": -100, "type": "supergroup", "title": "Team"},
+                "from": {"id": 42, "first_name": "Ada"},
+                "text": "héllo @Amtis_Bot what's up",
+                "entities": [{"type": "mention", "offset": 6, "length": 10}],
+            },
+        }));
+        let Some(InboundUpdate::Ingress(request)) =
+            convert_update(text, Some("amtis_bot"), "Amtiskaw")
+        else {
+            panic!("expected ingress");
+        };
+        assert_eq!(request.chat_jid, "tg:-100");
+        assert_eq!(request.chat_name.as_deref(), Some("Team"));
+        assert_eq!(request.sender_id.as_deref(), Some("42"));
+        assert_eq!(request.content, "@Amtiskaw héllo @Amtis_Bot what's up");
+        assert_eq!(request.timestamp, "2023-11-14T22:13:20.000Z");
+        assert!(request.persist);
+
+        let photo = update(serde_json::json!({
+            "update_id": 2,
+            "edited_message": {
+                "message_id": 8,
+                "date": 1_700_000_000,
+                "edit_date": 1_700_000_060,
+                "chat": {"id": 5, "type": "private"},
+                "from": {"id": 5, "username": "ada"},
+                "photo": [{"file_id": "x"}],
+                "caption": "look",
+            },
+        }));
+        let Some(InboundUpdate::Ingress(request)) = convert_update(photo, None, "Amtiskaw") else {
+            panic!("expected ingress");
+        };
+        assert_eq!(request.kind, TelegramUpdateKind::EditedMessage);
+        assert_eq!(request.content, "[Photo] look");
+        assert_eq!(request.chat_name.as_deref(), Some("ada"));
+        assert_eq!(request.timestamp, "2023-11-14T22:14:20.000Z");
+    }
+
+    #[test]
+    fn webhook_secret_defaults_to_token_digest() {
+        let mut bridge = TelegramBridge::new(&IntercomConfig::default());
+        bridge.bot_token = Some("123:abc".into());
+        let mut config = TelegramConfig::default();
+        let derived = bridge.webhook_secret(&config).unwrap();
+        assert_eq!(derived.len(), 64);
+        assert!(!derived.contains("abc"));
+        assert!(secret_matches(&derived, &derived.clone()));
+        assert!(!secret_matches(&derived, &derived[..63]));
+
+        config.webhook_secret = Some("s3cret_token".into());
+        assert_eq!(
+            bridge.webhook_secret(&config).as_deref(),
+            Some("s3cret_token")
         );
+
+        bridge.bot_token = None;
+        config.webhook_secret = None;
+        assert!(bridge.webhook_secret(&config).is_none());
+    }
+
+    #[test]
+    fn polled_commands_respect_bot_suffix() {
         assert_eq!(
-            chunks
-                .iter()
-                .map(|chunk| chunk.chars().count())
-                .sum::<usize>(),
-            text.chars().count()
+            parse_command("/model gemini", Some("bot")),
+            Some(("model", "gemini"))
         );
+        assert_eq!(
+            parse_command("/status@Bot", Some("bot")),
+            Some(("status", ""))
+        );
+        assert_eq!(parse_command("/status@other_bot", Some("bot")), None);
+
+        let command = update(serde_json::json!({
+            "update_id": 3,
+            "message": {
+                "message_id": 9,
+                "date": 1_700_000_000,
+                "chat": {"id": 5, "type": "private"},
+                "from": {"id": 5, "first_name": "Ada"},
+                "text": "/revert_last",
+            },
+        }));
+        let Some(InboundUpdate::Command(command)) =
+            convert_update(command, Some("bot"), "Amtiskaw")
+        else {
+            panic!("expected command");
+        };
+        assert_eq!(command.command, "revert_last");
+        assert_eq!(command.sender_id.as_deref(), Some("5"));
+
+        let callback = update(serde_json::json!({
+            "update_id": 4,
+            "callback_query": {
+                "id": "cb1",
+                "from": {"id": 5, "first_name": "Ada"},
+                "message": {"message_id": 10, "date": 0, "chat": {"id": 5, "type": "private"}},
+                "data": "approve:123",
+            },
+        }));
+        let Some(InboundUpdate::Callback(request)) =
+            convert_update(callback, Some("bot"), "Amtiskaw")
+        else {
+            panic!("expected callback");
+        };
+        assert_eq!(request.chat_jid, "tg:5");
+        assert_eq!(request.data, "approve:123");
+    }
+
+    #[test]
+    fn forum_topics_get_their_own_jid_and_fall_back_to_the_chat() {
+        let in_topic = update(serde_json::json!({
+            "update_id": 5,
+            "message": {
+                "message_id": 20,
+                "date": 0,
+                "chat": {"id": -1001, "type": "supergroup", "title": "Team"},
+                "from": {"id": 5, "first_name": "Ada"},
+                "text": "hello",
+                "message_thread_id": 7,
+                "is_topic_message": true,
+            },
+        }));
+        let Some(InboundUpdate::Ingress(request)) = convert_update(in_topic, None, "Amtiskaw")
+        else {
+            panic!("expected ingress");
+        };
+        assert_eq!(request.chat_jid, "tg:-1001:7");
+
+        // A reply thread in an ordinary group is not a topic.
+        let reply_thread = update(serde_json::json!({
+            "update_id": 6,
+            "message": {
+                "message_id": 21,
+                "date": 0,
+                "chat": {"id": -1002, "type": "supergroup", "title": "Team"},
+                "text": "hi",
+                "message_thread_id": 3,
+            },
+        }));
+        let Some(InboundUpdate::Ingress(request)) = convert_update(reply_thread, None, "Amtiskaw")
+        else {
+            panic!("expected ingress");
+        };
+        assert_eq!(request.chat_jid, "tg:-1002");
+
+        assert_eq!(split_jid("tg:-1001:7"), ("-1001", Some(7)));
+        assert_eq!(split_jid("tg:-1001"), ("-1001", None));
+        assert_eq!(base_jid("tg:-1001:7"), "tg:-1001");
+        assert_eq!(base_jid("tg:-1001"), "tg:-1001");
+
+        let registered = ["tg:-1001", "tg:-1001:9"];
+        let is_registered = |jid: &str| registered.contains(&jid);
+        assert_eq!(
+            registered_jid("tg:-1001:9", is_registered),
+            Some("tg:-1001:9")
+        );
+        assert_eq!(
+            registered_jid("tg:-1001:7", is_registered),
+            Some("tg:-1001")
+        );
+        assert_eq!(registered_jid("tg:-1003:7", is_registered), None);
     }
 
     #[test]
@@ -937,7 +2596,10 @@ mod tests {
                     content: "hello".to_string(),
                     timestamp: "2026-02-25T00:00:00Z".to_string(),
                     persist: false,
+                    kind: TelegramUpdateKind::Message,
+                    attachments: Vec::new(),
                 },
+                None,
             )
             .expect("route ingress");
 
@@ -947,6 +2609,337 @@ mod tests {
         assert_eq!(response.model.as_deref(), Some("gemini-3.1-pro"));
     }
 
+    #[test]
+    fn ingress_edits_and_tombstones_without_dispatch() {
+        let tmp = TempDir::new().expect("create tempdir");
+        let db_path = tmp.path().join("messages.db");
+        let conn = Connection::open(&db_path).expect("open sqlite");
+        conn.execute_batch(
+            "\
+            CREATE TABLE registered_groups (
+              jid TEXT PRIMARY KEY,
+              name TEXT NOT NULL,
+              folder TEXT NOT NULL,
+              trigger_pattern TEXT NOT NULL,
+              added_at TEXT NOT NULL,
+              container_config TEXT,
+              requires_trigger INTEGER DEFAULT 1,
+              runtime TEXT,
+              model TEXT
+            );
+            INSERT INTO registered_groups
+              (jid, name, folder, trigger_pattern, added_at, requires_trigger)
+            VALUES
+              ('tg:1', 'Main', 'main', '@Amtiskaw', '2026-01-01T00:00:00Z', 0);
+            ",
+        )
+        .expect("seed groups");
+        drop(conn);
+
+        let mut config = IntercomConfig::default();
+        config.storage.sqlite_legacy_path = db_path.display().to_string();
+        config.telegram.ingress_groups = IngressGroupSource::Sqlite;
+        let bridge = TelegramBridge::new(&config);
+        let request = |kind, content: &str| TelegramIngressRequest {
+            chat_jid: "tg:1".to_string(),
+            chat_name: None,
+            chat_type: Some("group".to_string()),
+            message_id: "7".to_string(),
+            sender_id: Some("99".to_string()),
+            sender_name: Some("User".to_string()),
+            content: content.to_string(),
+            timestamp: "2026-02-25T00:00:00Z".to_string(),
+            persist: true,
+            kind,
+            attachments: Vec::new(),
+        };
+        let stored = || -> String {
+            Connection::open(&db_path)
+                .unwrap()
+                .query_row("SELECT content FROM messages WHERE id = '7'", [], |r| {
+                    r.get(0)
+                })
+                .unwrap()
+        };
+
+        let original = bridge
+            .route_ingress(&config, request(TelegramUpdateKind::Message, "helo"), None)
+            .unwrap();
+        assert!(original.accepted);
+
+        let edited = bridge
+            .route_ingress(
+                &config,
+                request(TelegramUpdateKind::EditedMessage, "hello"),
+                None,
+            )
+            .unwrap();
+        assert!(!edited.accepted);
+        assert_eq!(edited.reason.as_deref(), Some("edited_message"));
+        assert_eq!(stored(), "hello");
+
+        let deleted = bridge
+            .route_ingress(
+                &config,
+                request(TelegramUpdateKind::DeletedMessage, ""),
+                None,
+            )
+            .unwrap();
+        assert_eq!(deleted.reason.as_deref(), Some("deleted_message"));
+        assert_eq!(stored(), "hello");
+        let deleted_at: Option<String> = Connection::open(&db_path)
+            .unwrap()
+            .query_row("SELECT deleted_at FROM messages WHERE id = '7'", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert_eq!(deleted_at.as_deref(), Some("2026-02-25T00:00:00Z"));
+    }
+
+    /// A `messages` table as the Node host creates it, without the edit
+    /// tracking columns.
+    fn legacy_message_db(content: &str) -> Connection {
+        let conn = Connection::open_in_memory().expect("open sqlite");
+        conn.execute_batch(
+            "\
+            CREATE TABLE messages (
+              id TEXT,
+              chat_jid TEXT,
+              sender TEXT,
+              sender_name TEXT,
+              content TEXT,
+              timestamp TEXT,
+              is_from_me INTEGER,
+              is_bot_message INTEGER DEFAULT 0,
+              PRIMARY KEY (id, chat_jid)
+            );
+            ",
+        )
+        .expect("create legacy messages");
+        conn.execute(
+            "INSERT INTO messages (id, chat_jid, content, timestamp, is_from_me) \
+             VALUES ('7', 'tg:1', ?1, '2026-02-25T00:00:00Z', 0)",
+            [content],
+        )
+        .expect("seed message");
+        ensure_telegram_persistence_schema(&conn).expect("migrate schema");
+        conn
+    }
+
+    fn legacy_request(kind: TelegramUpdateKind, content: &str, at: &str) -> TelegramIngressRequest {
+        TelegramIngressRequest {
+            chat_jid: "tg:1".to_string(),
+            chat_name: None,
+            chat_type: Some("group".to_string()),
+            message_id: "7".to_string(),
+            sender_id: None,
+            sender_name: None,
+            content: content.to_string(),
+            timestamp: at.to_string(),
+            persist: true,
+            kind,
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn legacy_edit_keeps_history_and_flags_row() {
+        let conn = legacy_message_db("helo");
+        let edit = |content: &str, at: &str| {
+            persist_message_edit(
+                &conn,
+                &legacy_request(TelegramUpdateKind::EditedMessage, content, at),
+            )
+            .expect("persist edit")
+        };
+
+        edit("hello", "2026-02-25T00:01:00Z");
+        // A repeated edit with unchanged content adds no history.
+        edit("hello", "2026-02-25T00:02:00Z");
+        edit("hello!", "2026-02-25T00:03:00Z");
+
+        let (content, edited_at): (String, Option<String>) = conn
+            .query_row(
+                "SELECT content, edited_at FROM messages WHERE id = '7'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(content, "hello!");
+        assert_eq!(edited_at.as_deref(), Some("2026-02-25T00:03:00Z"));
+
+        let history: Vec<(String, String)> = conn
+            .prepare("SELECT previous_content, edited_at FROM message_edits ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            history,
+            vec![
+                ("helo".to_string(), "2026-02-25T00:01:00Z".to_string()),
+                ("hello".to_string(), "2026-02-25T00:03:00Z".to_string()),
+            ]
+        );
+
+        // Edits to messages we never stored are dropped.
+        let mut unknown = legacy_request(
+            TelegramUpdateKind::EditedMessage,
+            "x",
+            "2026-02-25T00:04:00Z",
+        );
+        unknown.message_id = "8".to_string();
+        persist_message_edit(&conn, &unknown).expect("persist edit");
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM messages", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(rows, 1);
+    }
+
+    #[test]
+    fn legacy_tombstone_marks_row_and_keeps_content() {
+        let conn = legacy_message_db("hello");
+        let delete = |at: &str| {
+            persist_message_tombstone(
+                &conn,
+                &legacy_request(TelegramUpdateKind::DeletedMessage, "", at),
+            )
+            .expect("persist deletion")
+        };
+
+        delete("2026-02-25T00:05:00Z");
+        // The first deletion time sticks.
+        delete("2026-02-25T00:06:00Z");
+
+        let (content, deleted_at): (String, Option<String>) = conn
+            .query_row(
+                "SELECT content, deleted_at FROM messages WHERE id = '7'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(content, "hello");
+        assert_eq!(deleted_at.as_deref(), Some("2026-02-25T00:05:00Z"));
+    }
+
+    #[test]
+    fn live_group_wins_over_legacy_sqlite() {
+        let tmp = TempDir::new().expect("create tempdir");
+        let mut config = IntercomConfig::default();
+        config.storage.sqlite_legacy_path = tmp.path().join("missing.db").display().to_string();
+        let bridge = TelegramBridge::new(&config);
+        let request = TelegramIngressRequest {
+            chat_jid: "tg:5".to_string(),
+            chat_name: None,
+            chat_type: Some("group".to_string()),
+            message_id: "1".to_string(),
+            sender_id: None,
+            sender_name: None,
+            content: "hello".to_string(),
+            timestamp: "2026-02-25T00:00:00Z".to_string(),
+            persist: true,
+            kind: TelegramUpdateKind::Message,
+            attachments: Vec::new(),
+        };
+        let group = RegisteredGroup {
+            jid: "tg:5".to_string(),
+            name: "Ops".to_string(),
+            folder: "ops".to_string(),
+            trigger: "@Amtiskaw".to_string(),
+            added_at: "2026-01-01T00:00:00Z".to_string(),
+            container_config: None,
+            requires_trigger: Some(false),
+            runtime: None,
+            model: Some("opus".to_string()),
+            muted_until: None,
+            locale: None,
+        };
+
+        let response = bridge
+            .route_ingress(&config, request.clone(), Some(&group))
+            .unwrap();
+        assert!(response.accepted);
+        assert_eq!(response.group_folder.as_deref(), Some("ops"));
+        assert_eq!(response.model.as_deref(), Some("opus"));
+        // Live routing leaves persistence to the live store.
+        assert!(!tmp.path().join("missing.db").exists());
+
+        let muted = RegisteredGroup {
+            muted_until: Some("2999-01-01T00:00:00Z".to_string()),
+            ..group.clone()
+        };
+        let response = bridge
+            .route_ingress(&config, request.clone(), Some(&muted))
+            .unwrap();
+        assert!(!response.accepted);
+        assert_eq!(response.reason.as_deref(), Some("muted"));
+
+        let unregistered = bridge.route_ingress(&config, request, None).unwrap();
+        assert_eq!(unregistered.reason.as_deref(), Some("unregistered_group"));
+        assert!(!tmp.path().join("missing.db").exists());
+    }
+
+    #[tokio::test]
+    async fn bot_token_is_chosen_per_group() {
+        let group = |jid: &str, config: serde_json::Value| RegisteredGroup {
+            jid: jid.to_string(),
+            name: jid.to_string(),
+            folder: jid.replace(':', "-"),
+            trigger: "@Amtiskaw".to_string(),
+            added_at: "2026-01-01T00:00:00Z".to_string(),
+            container_config: Some(config),
+            requires_trigger: None,
+            runtime: None,
+            model: None,
+            muted_until: None,
+            locale: None,
+        };
+        let groups = HashMap::from([
+            ("tg:1".to_string(), group("tg:1", serde_json::json!({}))),
+            (
+                "tg:2".to_string(),
+                group(
+                    "tg:2",
+                    serde_json::json!({"botTokenEnv": "TELEGRAM_BOT_TOKEN_UNSET_IN_TESTS"}),
+                ),
+            ),
+            (
+                "tg:3".to_string(),
+                group("tg:3", serde_json::json!({"botTokenEnv": "DATABASE_URL"})),
+            ),
+        ]);
+        let mut bridge = TelegramBridge::new(&IntercomConfig::default())
+            .with_groups(Arc::new(RwLock::new(groups)));
+        bridge.bot_token = Some("default:token".into());
+
+        assert_eq!(bridge.token_for("tg:1").await.unwrap(), "default:token");
+        assert_eq!(bridge.token_for("tg:9").await.unwrap(), "default:token");
+        assert_eq!(
+            bridge.group_token_env("tg:2:77").await.as_deref(),
+            Some("TELEGRAM_BOT_TOKEN_UNSET_IN_TESTS")
+        );
+        let unset = bridge.token_for("tg:2").await.unwrap_err().to_string();
+        assert!(unset.contains("is not set"), "{unset}");
+        let foreign = bridge.token_for("tg:3").await.unwrap_err().to_string();
+        assert!(foreign.contains("must start with"), "{foreign}");
+    }
+
+    #[test]
+    fn captionless_attachment_gets_descriptive_content() {
+        let photo = Attachment {
+            kind: "photo".to_string(),
+            file_path: Some("media/42.jpg".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            normalize_ingress_content("", std::slice::from_ref(&photo)),
+            "[photo media/42.jpg]"
+        );
+        assert_eq!(normalize_ingress_content("look", &[photo]), "look");
+        assert_eq!(normalize_ingress_content("", &[]), "");
+    }
+
     #[test]
     fn parses_approve_callback_data() {
         let data = "approve:gate-review";
@@ -985,4 +2978,23 @@ mod tests {
             Some("ok:1")
         );
     }
+
+    #[test]
+    fn multipart_document_wraps_fields_and_file() {
+        let fields = [
+            ("chat_id", "-100123".to_string()),
+            ("caption", "run log".to_string()),
+        ];
+        let (content_type, body) =
+            multipart_document(&fields, "container-1.log", b"line one\nline two");
+        let boundary = content_type
+            .strip_prefix("multipart/form-data; boundary=")
+            .unwrap();
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.starts_with(&format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"chat_id\"\r\n\r\n-100123\r\n"
+        )));
+        assert!(body.contains("name=\"document\"; filename=\"container-1.log\"\r\nContent-Type: text/plain\r\n\r\nline one\nline two\r\n"));
+        assert!(body.ends_with(&format!("\r\n--{boundary}--\r\n")));
+    }
 }
@@ -0,0 +1,393 @@
+//! Generic webhook channel for custom frontends.
+//!
+//! Chats are `hook:<id>` JIDs registered like any group, with a
+//! `channelWebhook: { url, secret }` in their container config. The frontend
+//! posts user messages to `/v1/channel/webhook/ingress`; intercomd posts the
+//! agent's replies (and draft edits) back to the group's `url`. Both
+//! directions are signed with the group's secret, the same way lifecycle
+//! webhooks are:
+//!
+//! ```text
+//! X-Intercom-Event: message | edit
+//! X-Intercom-Signature: sha256=<hex digest>
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Context, anyhow};
+use intercom_core::{Attachment, ChannelProfile, IntercomConfig, RegisteredGroup, SharedStorage};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use crate::channels::{self, ChannelBridge, ChannelFuture, OutboundMessage};
+use crate::container::security::ContainerConfig;
+use crate::health::{SUBSYSTEM_DB, record_error};
+use crate::markdown;
+use crate::outbound::{split_text, truncate_text};
+use crate::telegram::{self, TelegramIngressRequest, TelegramUpdateKind};
+use crate::webhooks::{self, EVENT_HEADER, GroupWebhook, SIGNATURE_HEADER};
+
+/// Name of the webhook profile under `[channels.profiles]`, also its health
+/// subsystem.
+pub const WEBHOOK_CHANNEL: &str = "webhook";
+/// JID prefix for webhook chats.
+pub const WEBHOOK_JID_PREFIX: &str = "hook:";
+
+/// A user message posted by the frontend.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookIngressRequest {
+    pub jid: String,
+    /// Frontend user id.
+    pub sender: String,
+    #[serde(default)]
+    pub sender_name: Option<String>,
+    pub content: String,
+    /// Frontend message id; generated when absent.
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// RFC 3339; the receive time when absent.
+    #[serde(default)]
+    pub timestamp: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookIngressResponse {
+    /// Id the message was stored under; replies reference it as `replyTo`.
+    pub message_id: String,
+}
+
+/// Body POSTed to the group's callback URL.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookDelivery<'a> {
+    event: &'static str,
+    jid: &'a str,
+    message_id: &'a str,
+    text: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<&'a str>,
+}
+
+/// The `channelWebhook` of a registered group's container config.
+pub fn group_channel_webhook(group: &RegisteredGroup) -> Option<GroupWebhook> {
+    let config = group.container_config.clone()?;
+    serde_json::from_value::<ContainerConfig>(config)
+        .ok()?
+        .channel_webhook
+        .filter(GroupWebhook::is_usable)
+}
+
+/// Check a frontend post against the group's `channelWebhook` secret.
+pub fn verify_ingress(group: &RegisteredGroup, body: &[u8], provided: &str) -> anyhow::Result<()> {
+    let hook = group_channel_webhook(group)
+        .ok_or_else(|| anyhow!("{} has no usable channelWebhook", group.jid))?;
+    if !telegram::secret_matches(&webhooks::sign(&hook.secret, body), provided.trim()) {
+        return Err(anyhow!("bad signature for {}", group.jid));
+    }
+    Ok(())
+}
+
+pub struct WebhookChannel {
+    profile: ChannelProfile,
+    groups: Arc<RwLock<HashMap<String, RegisteredGroup>>>,
+    /// Ids of sent messages; the start time keeps them unique across
+    /// restarts.
+    id_prefix: String,
+    id_counter: AtomicU64,
+    store: Option<SharedStorage>,
+    sender_name: String,
+}
+
+impl WebhookChannel {
+    pub fn new(
+        config: &IntercomConfig,
+        groups: Arc<RwLock<HashMap<String, RegisteredGroup>>>,
+    ) -> Self {
+        Self {
+            profile: config.channels.profile(WEBHOOK_CHANNEL),
+            groups,
+            id_prefix: format!("hook-{}", chrono::Utc::now().timestamp_millis()),
+            id_counter: AtomicU64::new(0),
+            store: None,
+            sender_name: String::new(),
+        }
+    }
+
+    /// Record every sent message in `store` as a bot message from
+    /// `sender_name`.
+    pub fn with_storage(
+        mut self,
+        store: Option<SharedStorage>,
+        sender_name: impl Into<String>,
+    ) -> Self {
+        self.store = store;
+        self.sender_name = sender_name.into();
+        self
+    }
+
+    /// A fresh message id, for sent replies and frontend posts without one.
+    pub fn next_message_id(&self) -> String {
+        let n = self.id_counter.fetch_add(1, Ordering::Relaxed);
+        format!("{}-{n}", self.id_prefix)
+    }
+
+    /// Convert a verified frontend post to an ingress request.
+    pub fn ingest(&self, request: WebhookIngressRequest) -> TelegramIngressRequest {
+        let timestamp = request
+            .timestamp
+            .filter(|ts| chrono::DateTime::parse_from_rfc3339(ts).is_ok())
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+        TelegramIngressRequest {
+            message_id: request
+                .message_id
+                .filter(|id| !id.trim().is_empty())
+                .unwrap_or_else(|| self.next_message_id()),
+            chat_jid: request.jid,
+            chat_name: None,
+            chat_type: Some("group".to_string()),
+            sender_name: Some(
+                request
+                    .sender_name
+                    .unwrap_or_else(|| request.sender.clone()),
+            ),
+            sender_id: Some(request.sender),
+            content: self.normalize_ingress(&request.content, &[]),
+            timestamp,
+            persist: true,
+            kind: TelegramUpdateKind::Message,
+            attachments: Vec::new(),
+        }
+    }
+
+    async fn callback(&self, jid: &str) -> anyhow::Result<GroupWebhook> {
+        let groups = self.groups.read().await;
+        let group = groups
+            .get(jid)
+            .ok_or_else(|| anyhow!("{jid} is not a registered group"))?;
+        group_channel_webhook(group).ok_or_else(|| anyhow!("{jid} has no usable channelWebhook"))
+    }
+
+    async fn deliver(
+        &self,
+        hook: &GroupWebhook,
+        delivery: &WebhookDelivery<'_>,
+    ) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(delivery)?;
+        let response = webhooks::client()
+            .post(hook.url.trim())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event)
+            .header(SIGNATURE_HEADER, webhooks::sign(&hook.secret, &body))
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("failed to reach channelWebhook for {}", delivery.jid))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!(
+                "channelWebhook for {} responded with {status}",
+                delivery.jid
+            ));
+        }
+        Ok(())
+    }
+
+    /// POST `text` to the group's callback, split to the profile limit.
+    /// Returns the ids given to the sent messages.
+    pub async fn send_message(&self, message: &OutboundMessage) -> anyhow::Result<Vec<String>> {
+        if message.text.trim().is_empty() {
+            return Err(anyhow!("cannot send an empty webhook message"));
+        }
+        let hook = self.callback(&message.jid).await?;
+        let text = markdown::render(&message.text, self.profile.markdown);
+        let mut message_ids = Vec::new();
+        for (index, chunk) in split_text(&text, self.max_chars()).iter().enumerate() {
+            let message_id = self.next_message_id();
+            let delivery = WebhookDelivery {
+                event: "message",
+                jid: &message.jid,
+                message_id: &message_id,
+                text: chunk,
+                reply_to: message.reply_to.as_deref().filter(|_| index == 0),
+                correlation_id: message.correlation_id.as_deref(),
+            };
+            self.deliver(&hook, &delivery).await?;
+            channels::record_sent(
+                self.store.as_ref(),
+                self.name(),
+                &self.sender_name,
+                &message.jid,
+                &message_id,
+                chunk,
+                message.correlation_id.as_deref(),
+            )
+            .await;
+            message_ids.push(message_id);
+        }
+        Ok(message_ids)
+    }
+
+    /// POST an `edit` event replacing a sent message's text.
+    pub async fn edit_message(
+        &self,
+        jid: &str,
+        message_id: &str,
+        text: &str,
+    ) -> anyhow::Result<()> {
+        if !self.profile.supports_edit {
+            return Err(anyhow!(
+                "editing is disabled in the webhook channel profile"
+            ));
+        }
+        let hook = self.callback(jid).await?;
+        let (text, _) = truncate_text(
+            &markdown::render(text, self.profile.markdown),
+            self.max_chars(),
+        );
+        let delivery = WebhookDelivery {
+            event: "edit",
+            jid,
+            message_id,
+            text: &text,
+            reply_to: None,
+            correlation_id: None,
+        };
+        self.deliver(&hook, &delivery).await?;
+
+        if let Some(store) = &self.store {
+            let edited_at = chrono::Utc::now().to_rfc3339();
+            if let Err(e) = store.edit_message(message_id, jid, &text, &edited_at).await {
+                warn!(jid, message_id, err = %e, "failed to store webhook edit");
+                record_error(SUBSYSTEM_DB, &e);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ChannelBridge for WebhookChannel {
+    fn name(&self) -> &'static str {
+        WEBHOOK_CHANNEL
+    }
+
+    fn profile(&self) -> &ChannelProfile {
+        &self.profile
+    }
+
+    fn send<'a>(&'a self, message: &'a OutboundMessage) -> ChannelFuture<'a, Vec<String>> {
+        Box::pin(self.send_message(message))
+    }
+
+    fn edit<'a>(
+        &'a self,
+        jid: &'a str,
+        message_id: &'a str,
+        text: &'a str,
+    ) -> ChannelFuture<'a, ()> {
+        Box::pin(self.edit_message(jid, message_id, text))
+    }
+
+    fn normalize_ingress(&self, content: &str, _attachments: &[Attachment]) -> String {
+        content.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(container_config: serde_json::Value) -> RegisteredGroup {
+        RegisteredGroup {
+            jid: "hook:support".into(),
+            name: "Support".into(),
+            folder: "support".into(),
+            trigger: "@Amtiskaw".into(),
+            added_at: String::new(),
+            container_config: Some(container_config),
+            requires_trigger: None,
+            runtime: None,
+            model: None,
+            muted_until: None,
+            locale: None,
+        }
+    }
+
+    fn hooked() -> RegisteredGroup {
+        group(serde_json::json!({
+            "channelWebhook": { "url": "https://chat.internal/intercom", "secret": "s3cret" }
+        }))
+    }
+
+    #[test]
+    fn ingress_is_verified_with_the_group_secret() {
+        let body = br#"{"jid":"hook:support","sender":"u1","content":"hi"}"#;
+        let signature = webhooks::sign("s3cret", body);
+        assert!(verify_ingress(&hooked(), body, &signature).is_ok());
+        assert!(verify_ingress(&hooked(), body, &webhooks::sign("other", body)).is_err());
+        // Without a callback there is no secret to check against.
+        assert!(verify_ingress(&group(serde_json::json!({})), body, &signature).is_err());
+    }
+
+    #[test]
+    fn ingest_fills_missing_ids_and_names() {
+        let channel = WebhookChannel::new(&IntercomConfig::default(), Arc::default());
+        let request: WebhookIngressRequest = serde_json::from_value(serde_json::json!({
+            "jid": "hook:support",
+            "sender": "u1",
+            "content": "  @Amtiskaw status?  "
+        }))
+        .unwrap();
+        let ingress = channel.ingest(request);
+        assert!(ingress.message_id.starts_with("hook-"));
+        assert_eq!(ingress.sender_name.as_deref(), Some("u1"));
+        assert_eq!(ingress.content, "@Amtiskaw status?");
+        assert!(ingress.persist);
+    }
+
+    #[test]
+    fn delivery_serializes_camel_case() {
+        let delivery = WebhookDelivery {
+            event: "message",
+            jid: "hook:support",
+            message_id: "hook-1-0",
+            text: "Done",
+            reply_to: Some("m1"),
+            correlation_id: None,
+        };
+        let json = serde_json::to_value(&delivery).unwrap();
+        assert_eq!(json["messageId"], "hook-1-0");
+        assert_eq!(json["replyTo"], "m1");
+        assert!(json.get("correlationId").is_none());
+    }
+
+    #[tokio::test]
+    async fn send_needs_a_registered_callback() {
+        let groups = HashMap::from([("hook:bare".to_string(), group(serde_json::json!({})))]);
+        let channel =
+            WebhookChannel::new(&IntercomConfig::default(), Arc::new(RwLock::new(groups)));
+        let unknown = channel
+            .send_message(&OutboundMessage::text("hook:none", "hi"))
+            .await;
+        assert!(
+            unknown
+                .unwrap_err()
+                .to_string()
+                .contains("not a registered group")
+        );
+        let bare = channel
+            .send_message(&OutboundMessage::text("hook:bare", "hi"))
+            .await;
+        assert!(
+            bare.unwrap_err()
+                .to_string()
+                .contains("no usable channelWebhook")
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Per-group lifecycle webhooks.
+//!
+//! A group may set `webhook: { url, secret }` in its container config to
+//! receive its own container run start/finish events and scheduled task
+//! results. Events are only ever emitted for the group whose run produced
+//! them, so teams can wire their agent into their own tooling without
+//! access to the daemon or other groups.
+//!
+//! Each delivery is a JSON POST signed with HMAC-SHA256 over the raw body,
+//! keyed by the group's secret:
+//!
+//! ```text
+//! X-Intercom-Event: run_finished
+//! X-Intercom-Signature: sha256=<hex digest>
+//! ```
+//!
+//! Delivery is fire-and-forget — a slow or failing endpoint never delays or
+//! fails the agent run.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::{debug, warn};
+
+use crate::event_stream::{self, LiveEvent};
+
+pub const SIGNATURE_HEADER: &str = "X-Intercom-Signature";
+pub const EVENT_HEADER: &str = "X-Intercom-Event";
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Webhook target configured on a group's `containerConfig.webhook`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GroupWebhook {
+    pub url: String,
+    /// Shared secret used to sign deliveries.
+    pub secret: String,
+}
+
+// Keep the secret out of logs that print the group's container config.
+impl std::fmt::Debug for GroupWebhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GroupWebhook")
+            .field("url", &self.url)
+            .field("secret", &"<redacted>")
+            .finish()
+    }
+}
+
+impl GroupWebhook {
+    /// Only http(s) targets with a non-empty secret are delivered to;
+    /// unsigned deliveries would be indistinguishable from forgeries.
+    pub fn is_usable(&self) -> bool {
+        let url = self.url.trim();
+        (url.starts_with("https://") || url.starts_with("http://")) && !self.secret.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleEventKind {
+    RunStarted,
+    RunFinished,
+    TaskResult,
+}
+
+impl LifecycleEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::RunStarted => "run_started",
+            Self::RunFinished => "run_finished",
+            Self::TaskResult => "task_result",
+        }
+    }
+}
+
+/// JSON body POSTed to the group's webhook.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LifecycleEvent {
+    pub event: LifecycleEventKind,
+    pub group_folder: String,
+    pub chat_jid: String,
+    pub timestamp: String,
+    /// Set for scheduled task runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+    /// "success" or "error" once the run has finished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl LifecycleEvent {
+    pub fn new(event: LifecycleEventKind, group_folder: &str, chat_jid: &str) -> Self {
+        Self {
+            event,
+            group_folder: group_folder.to_string(),
+            chat_jid: chat_jid.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            task_id: None,
+            correlation_id: None,
+            status: None,
+            duration_ms: None,
+            result: None,
+            error: None,
+        }
+    }
+
+    pub fn with_task(mut self, task_id: &str) -> Self {
+        self.task_id = Some(task_id.to_string());
+        self
+    }
+
+    pub fn with_correlation_id(mut self, correlation_id: &str) -> Self {
+        self.correlation_id = Some(correlation_id.to_string());
+        self
+    }
+
+    /// Record the outcome of a finished run. Status is derived from `error`.
+    pub fn with_outcome(
+        mut self,
+        duration_ms: i64,
+        result: Option<&str>,
+        error: Option<&str>,
+    ) -> Self {
+        self.status = Some(if error.is_some() { "error" } else { "success" }.to_string());
+        self.duration_ms = Some(duration_ms);
+        self.result = result.map(|s| s.to_string());
+        self.error = error.map(|s| s.to_string());
+        self
+    }
+}
+
+/// Hex HMAC-SHA256 of `body` keyed by `secret`, in `sha256=<hex>` form.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Shared client for signed deliveries, with the delivery timeout.
+pub(crate) fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+/// Deliver `event` to the group's webhook in the background, and publish
+/// it on the live event stream.
+///
+/// Delivery is a no-op when the group has no (usable) webhook configured.
+pub fn dispatch(webhook: Option<&GroupWebhook>, event: LifecycleEvent) {
+    if let Some(live) = LiveEvent::from_lifecycle(&event) {
+        event_stream::publish(live);
+    }
+    let Some(webhook) = webhook else {
+        return;
+    };
+    if !webhook.is_usable() {
+        warn!(
+            group_folder = event.group_folder.as_str(),
+            "group webhook needs an http(s) url and a secret, skipping delivery"
+        );
+        return;
+    }
+    let webhook = webhook.clone();
+    tokio::spawn(async move {
+        if let Err(e) = deliver(&webhook, &event).await {
+            warn!(
+                group_folder = event.group_folder.as_str(),
+                event = event.event.as_str(),
+                err = %e,
+                "group webhook delivery failed"
+            );
+        }
+    });
+}
+
+async fn deliver(webhook: &GroupWebhook, event: &LifecycleEvent) -> anyhow::Result<()> {
+    let body = serde_json::to_vec(event)?;
+    let signature = sign(&webhook.secret, &body);
+    let resp = client()
+        .post(webhook.url.trim())
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, event.event.as_str())
+        .header(SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await?;
+    let status = resp.status();
+    if !status.is_success() {
+        anyhow::bail!("webhook responded with {status}");
+    }
+    debug!(
+        group_folder = event.group_folder.as_str(),
+        event = event.event.as_str(),
+        "group webhook delivered"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_matches_rfc4231_vector() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn event_serializes_camel_case_and_skips_empty() {
+        let event = LifecycleEvent::new(LifecycleEventKind::RunStarted, "team", "tg:1");
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "run_started");
+        assert_eq!(json["groupFolder"], "team");
+        assert_eq!(json["chatJid"], "tg:1");
+        assert!(json.get("taskId").is_none());
+        assert!(json.get("status").is_none());
+    }
+
+    #[test]
+    fn outcome_derives_status() {
+        let ok = LifecycleEvent::new(LifecycleEventKind::TaskResult, "team", "tg:1")
+            .with_task("t1")
+            .with_outcome(42, Some("done"), None);
+        assert_eq!(ok.status.as_deref(), Some("success"));
+        assert_eq!(ok.task_id.as_deref(), Some("t1"));
+        assert_eq!(ok.duration_ms, Some(42));
+
+        let err = LifecycleEvent::new(LifecycleEventKind::RunFinished, "team", "tg:1")
+            .with_outcome(5, None, Some("boom"));
+        assert_eq!(err.status.as_deref(), Some("error"));
+        assert_eq!(err.error.as_deref(), Some("boom"));
+    }
+
+    #[test]
+    fn webhook_requires_http_url_and_secret() {
+        let hook = |url: &str, secret: &str| GroupWebhook {
+            url: url.into(),
+            secret: secret.into(),
+        };
+        assert!(hook("https://example.com/hook", "s").is_usable());
+        assert!(!hook("https://example.com/hook", "").is_usable());
+        assert!(!hook("file:///etc/passwd", "s").is_usable());
+    }
+
+    #[test]
+    fn container_config_parses_webhook_and_redacts_secret() {
+        let cfg: crate::container::security::ContainerConfig = serde_json::from_value(
+            serde_json::json!({"webhook": {"url": "https://example.com/h", "secret": "hunter2"}}),
+        )
+        .unwrap();
+        let hook = cfg.webhook.expect("webhook parsed");
+        assert_eq!(hook.url, "https://example.com/h");
+        assert!(!format!("{hook:?}").contains("hunter2"));
+    }
+}
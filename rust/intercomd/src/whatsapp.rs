@@ -0,0 +1,355 @@
+//! WhatsApp parity endpoints, mirroring `/v1/telegram/ingress` and
+//! `/v1/telegram/send`.
+//!
+//! intercomd holds no WhatsApp connection: the Node host keeps the Baileys
+//! socket, asks `/v1/whatsapp/ingress` whether each message should reach an
+//! agent and `/v1/whatsapp/send` how to prefix, format and chunk each reply,
+//! then does what it is told. Once the host only relays, the socket itself
+//! can move into Rust without changing behaviour.
+
+use intercom_core::{ChannelProfile, IntercomConfig, RegisteredGroup};
+use serde::{Deserialize, Serialize};
+
+use crate::markdown;
+use crate::message_loop::is_muted;
+use crate::outbound::split_text;
+use crate::telegram::{
+    RegisteredGroupRow, TelegramIngressParity, TelegramSendParity, resolve_runtime, trigger_matches,
+};
+
+/// Name of the WhatsApp profile under `[channels.profiles]`.
+pub const WHATSAPP_CHANNEL: &str = "whatsapp";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WhatsAppIngressRequest {
+    pub chat_jid: String,
+    pub message_id: String,
+    /// Participant JID in groups, the chat JID in direct chats.
+    pub sender: String,
+    pub sender_name: Option<String>,
+    pub content: String,
+    pub timestamp: String,
+    #[serde(default)]
+    pub from_me: bool,
+    /// Store accepted messages in the live store.
+    #[serde(default)]
+    pub persist: bool,
+}
+
+impl WhatsAppIngressRequest {
+    pub fn is_group(&self) -> bool {
+        self.chat_jid.ends_with("@g.us")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WhatsAppIngressResponse {
+    pub accepted: bool,
+    pub reason: Option<String>,
+    pub normalized_content: String,
+    /// The message is the assistant's own output (see
+    /// [`WhatsAppIdentity::is_bot_message`]).
+    pub is_bot_message: bool,
+    pub group_name: Option<String>,
+    pub group_folder: Option<String>,
+    pub runtime: Option<String>,
+    pub model: Option<String>,
+    pub parity: TelegramIngressParity,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WhatsAppSendRequest {
+    pub text: String,
+}
+
+/// The messages the host should send, in order.
+#[derive(Debug, Clone, Serialize)]
+pub struct WhatsAppSendResponse {
+    pub ok: bool,
+    pub error: Option<String>,
+    pub chunks: Vec<String>,
+    pub chunks_planned: usize,
+    pub chunk_lengths: Vec<usize>,
+    /// Whether the assistant name prefix was added.
+    pub prefixed: bool,
+    pub parity: TelegramSendParity,
+}
+
+/// Identity rules the Node bridge applies to a shared or dedicated number.
+#[derive(Debug, Clone)]
+pub struct WhatsAppIdentity {
+    pub assistant_name: String,
+    /// `ASSISTANT_HAS_OWN_NUMBER=true`: the bot has its own phone number, so
+    /// its messages are exactly the ones sent from it and need no prefix.
+    pub has_own_number: bool,
+}
+
+impl WhatsAppIdentity {
+    pub fn from_env() -> Self {
+        Self {
+            assistant_name: std::env::var("ASSISTANT_NAME").unwrap_or_else(|_| "Amtiskaw".into()),
+            has_own_number: std::env::var("ASSISTANT_HAS_OWN_NUMBER")
+                .is_ok_and(|v| v.trim() == "true"),
+        }
+    }
+
+    fn prefix(&self) -> String {
+        format!("{}:", self.assistant_name)
+    }
+
+    /// On a shared number the bot's messages are recognised by the
+    /// `<name>:` prefix; on its own number by `from_me`.
+    pub fn is_bot_message(&self, content: &str, from_me: bool) -> bool {
+        if self.has_own_number {
+            from_me
+        } else {
+            content.starts_with(&self.prefix())
+        }
+    }
+}
+
+/// Decide whether an inbound message should reach `group`'s agent, with the
+/// same trigger and runtime rules as Telegram ingress.
+pub fn route_ingress(
+    config: &IntercomConfig,
+    identity: &WhatsAppIdentity,
+    request: &WhatsAppIngressRequest,
+    group: Option<&RegisteredGroup>,
+) -> WhatsAppIngressResponse {
+    let normalized_content = request.content.trim().to_string();
+    let is_bot_message = identity.is_bot_message(&normalized_content, request.from_me);
+    let mut response = WhatsAppIngressResponse {
+        accepted: false,
+        reason: None,
+        normalized_content,
+        is_bot_message,
+        group_name: None,
+        group_folder: None,
+        runtime: None,
+        model: None,
+        parity: TelegramIngressParity {
+            trigger_required: false,
+            trigger_present: false,
+            runtime_profile_found: false,
+            runtime_fallback_used: false,
+            model_fallback_used: false,
+        },
+    };
+
+    let Some(group) = group.map(RegisteredGroupRow::from) else {
+        response.reason = Some("unregistered_group".to_string());
+        return response;
+    };
+    let trigger_required = group.folder != "main" && group.requires_trigger;
+    let trigger_present =
+        !trigger_required || trigger_matches(&response.normalized_content, &group.trigger_pattern);
+    let muted = is_muted(group.muted_until.as_deref(), chrono::Utc::now());
+    let runtime = resolve_runtime(config, &group);
+    response.parity = TelegramIngressParity {
+        trigger_required,
+        trigger_present,
+        runtime_profile_found: runtime.runtime_profile_found,
+        runtime_fallback_used: runtime.runtime_fallback_used,
+        model_fallback_used: runtime.model_fallback_used,
+    };
+    response.group_name = Some(group.name);
+    response.group_folder = Some(group.folder);
+    response.runtime = Some(runtime.runtime);
+    response.model = Some(runtime.model);
+
+    // Protocol messages (receipts, key exchanges) arrive with no text.
+    response.reason = if response.normalized_content.is_empty() {
+        Some("empty_content".to_string())
+    } else if is_bot_message {
+        Some("bot_message".to_string())
+    } else if muted {
+        Some("muted".to_string())
+    } else if !trigger_present {
+        Some("trigger_required".to_string())
+    } else {
+        None
+    };
+    response.accepted = response.reason.is_none();
+    response
+}
+
+/// Prefix, format and chunk a reply for `profile`. Only the first chunk
+/// carries the prefix, so every chunk stays within the limit.
+pub fn plan_send(
+    profile: &ChannelProfile,
+    identity: &WhatsAppIdentity,
+    request: &WhatsAppSendRequest,
+) -> WhatsAppSendResponse {
+    let max_chars = profile.max_chars.max(1);
+    if request.text.trim().is_empty() {
+        return WhatsAppSendResponse::from_error(
+            "cannot send an empty WhatsApp message",
+            max_chars,
+        );
+    }
+    let prefixed = !identity.has_own_number;
+    let mut text = markdown::render(&request.text, profile.markdown);
+    if prefixed {
+        text = format!("{} {text}", identity.prefix());
+    }
+    let chunks = split_text(&text, max_chars);
+    let chunk_lengths: Vec<usize> = chunks.iter().map(|c| c.chars().count()).collect();
+    WhatsAppSendResponse {
+        ok: true,
+        error: None,
+        chunks_planned: chunks.len(),
+        chunks,
+        prefixed,
+        parity: TelegramSendParity {
+            max_chars_per_chunk: max_chars,
+            all_chunks_within_limit: chunk_lengths.iter().all(|len| *len <= max_chars),
+        },
+        chunk_lengths,
+    }
+}
+
+impl WhatsAppSendResponse {
+    pub fn from_error(err: impl Into<String>, max_chars: usize) -> Self {
+        Self {
+            ok: false,
+            error: Some(err.into()),
+            chunks: Vec::new(),
+            chunks_planned: 0,
+            chunk_lengths: Vec::new(),
+            prefixed: false,
+            parity: TelegramSendParity {
+                max_chars_per_chunk: max_chars,
+                all_chunks_within_limit: true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(has_own_number: bool) -> WhatsAppIdentity {
+        WhatsAppIdentity {
+            assistant_name: "Amtiskaw".into(),
+            has_own_number,
+        }
+    }
+
+    fn group(folder: &str) -> RegisteredGroup {
+        RegisteredGroup {
+            jid: "120363@g.us".into(),
+            name: "Family".into(),
+            folder: folder.into(),
+            trigger: "@Amtiskaw".into(),
+            added_at: String::new(),
+            container_config: None,
+            requires_trigger: None,
+            runtime: None,
+            model: None,
+            muted_until: None,
+            locale: None,
+        }
+    }
+
+    fn ingress(content: &str, from_me: bool) -> WhatsAppIngressRequest {
+        WhatsAppIngressRequest {
+            chat_jid: "120363@g.us".into(),
+            message_id: "3EB0".into(),
+            sender: "4915@s.whatsapp.net".into(),
+            sender_name: Some("Ana".into()),
+            content: content.into(),
+            timestamp: "2026-01-01T00:00:00.000Z".into(),
+            from_me,
+            persist: false,
+        }
+    }
+
+    #[test]
+    fn ingress_requires_trigger_outside_main() {
+        let config = IntercomConfig::default();
+        let family = group("family");
+        let plain = route_ingress(
+            &config,
+            &identity(false),
+            &ingress("hi all", false),
+            Some(&family),
+        );
+        assert!(!plain.accepted);
+        assert_eq!(plain.reason.as_deref(), Some("trigger_required"));
+
+        let addressed = route_ingress(
+            &config,
+            &identity(false),
+            &ingress(" @amtiskaw hi ", false),
+            Some(&family),
+        );
+        assert!(addressed.accepted);
+        assert_eq!(addressed.normalized_content, "@amtiskaw hi");
+        assert_eq!(addressed.runtime.as_deref(), Some("claude"));
+
+        let main = route_ingress(
+            &config,
+            &identity(false),
+            &ingress("hi", false),
+            Some(&group("main")),
+        );
+        assert!(main.accepted);
+        assert!(!main.parity.trigger_required);
+    }
+
+    #[test]
+    fn ingress_rejects_bot_and_unregistered_messages() {
+        let config = IntercomConfig::default();
+        let main = group("main");
+        let shared = route_ingress(
+            &config,
+            &identity(false),
+            &ingress("Amtiskaw: done", true),
+            Some(&main),
+        );
+        assert!(shared.is_bot_message);
+        assert_eq!(shared.reason.as_deref(), Some("bot_message"));
+
+        // On its own number only from_me counts.
+        let own = route_ingress(
+            &config,
+            &identity(true),
+            &ingress("Amtiskaw: done", false),
+            Some(&main),
+        );
+        assert!(!own.is_bot_message);
+        assert!(own.accepted);
+
+        let unknown = route_ingress(&config, &identity(false), &ingress("hi", false), None);
+        assert_eq!(unknown.reason.as_deref(), Some("unregistered_group"));
+        let empty = route_ingress(
+            &config,
+            &identity(false),
+            &ingress("  ", false),
+            Some(&main),
+        );
+        assert_eq!(empty.reason.as_deref(), Some("empty_content"));
+    }
+
+    #[test]
+    fn send_prefixes_formats_and_chunks() {
+        let mut profile = IntercomConfig::default().channels.profile(WHATSAPP_CHANNEL);
+        profile.max_chars = 20;
+        let request = WhatsAppSendRequest {
+            text: format!("**Done** {}", "x".repeat(20)),
+        };
+        let plan = plan_send(&profile, &identity(false), &request);
+        assert!(plan.ok && plan.prefixed);
+        assert!(plan.chunks[0].starts_with("Amtiskaw: *Done* "));
+        assert_eq!(plan.chunks_planned, 2);
+        assert!(plan.parity.all_chunks_within_limit);
+
+        let own = plan_send(&profile, &identity(true), &request);
+        assert!(!own.prefixed);
+        assert!(own.chunks[0].starts_with("*Done*"));
+
+        let empty = WhatsAppSendRequest { text: " ".into() };
+        assert!(!plan_send(&profile, &identity(false), &empty).ok);
+    }
+}
@@ -0,0 +1,471 @@
+//! Git snapshots of group folders around container runs.
+//!
+//! With `snapshots.enabled`, every group folder gets a private history at
+//! `data/snapshots/<folder>.git`. It lives outside the container mounts, so an
+//! agent can edit its files but never its history. Each run commits the
+//! folder before it starts (capturing out-of-band edits) and after it exits,
+//! with the run id in the message:
+//!
+//! ```text
+//! intercom: before run 20261016T101500.123Z
+//! intercom: after run 20261016T101500.123Z (task daily-digest)
+//! intercom: revert run 20261016T101500.123Z
+//! ```
+//!
+//! `/revert-last` restores the tree from before the most recent run that has
+//! not been reverted yet. Retention squashes old snapshots into a single base
+//! commit once a history grows past `snapshots.keep`.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::Context;
+use intercom_core::SnapshotConfig;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::container::runner::RunConfig;
+
+const MESSAGE_PREFIX: &str = "intercom: ";
+
+/// Git's well-known empty tree, used when reverting the very first snapshot.
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Paths under the group folder that are never snapshotted.
+const EXCLUDES: &str = "logs/\n";
+
+/// Extra commits tolerated past `keep` before history is squashed, so the
+/// rewrite happens every few runs instead of after every one.
+const PRUNE_SLACK: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunPhase {
+    Before,
+    After,
+}
+
+impl RunPhase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Before => "before",
+            Self::After => "after",
+        }
+    }
+}
+
+/// Run id used in snapshot messages: UTC start time, millisecond precision.
+pub fn new_run_id() -> String {
+    chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string()
+}
+
+pub fn run_message(phase: RunPhase, run_id: &str, task_id: Option<&str>) -> String {
+    match task_id {
+        Some(task_id) => format!(
+            "{MESSAGE_PREFIX}{} run {run_id} (task {task_id})",
+            phase.as_str()
+        ),
+        None => format!("{MESSAGE_PREFIX}{} run {run_id}", phase.as_str()),
+    }
+}
+
+/// Snapshot kinds recognized when walking history for `/revert-last`.
+#[derive(Debug, PartialEq, Eq)]
+enum Marker<'a> {
+    After(&'a str),
+    Revert(&'a str),
+}
+
+fn parse_marker(subject: &str) -> Option<Marker<'_>> {
+    let rest = subject.strip_prefix(MESSAGE_PREFIX)?;
+    if let Some(rest) = rest.strip_prefix("after run ") {
+        return rest.split_whitespace().next().map(Marker::After);
+    }
+    if let Some(rest) = rest.strip_prefix("revert run ") {
+        return rest.split_whitespace().next().map(Marker::Revert);
+    }
+    None
+}
+
+/// Outcome of a successful `/revert-last`.
+#[derive(Debug, Clone)]
+pub struct Reverted {
+    pub run_id: String,
+    pub files: Vec<String>,
+}
+
+/// A group folder and its private snapshot history.
+#[derive(Debug, Clone)]
+pub struct WorkspaceRepo {
+    git_dir: PathBuf,
+    work_tree: PathBuf,
+}
+
+impl WorkspaceRepo {
+    pub fn new(data_dir: &Path, groups_dir: &Path, folder: &str) -> Self {
+        Self {
+            git_dir: data_dir.join("snapshots").join(format!("{folder}.git")),
+            work_tree: groups_dir.join(folder),
+        }
+    }
+
+    pub fn for_group(config: &RunConfig, folder: &str) -> Self {
+        Self::new(&config.data_dir, &config.groups_dir, folder)
+    }
+
+    async fn git_with_env(&self, args: &[&str], env: &[(&str, &str)]) -> anyhow::Result<String> {
+        let output = Command::new("git")
+            .arg("--git-dir")
+            .arg(&self.git_dir)
+            .arg("--work-tree")
+            .arg(&self.work_tree)
+            .args([
+                "-c",
+                "user.name=intercom",
+                "-c",
+                "user.email=intercom@localhost",
+                "-c",
+                "commit.gpgsign=false",
+            ])
+            .args(args)
+            .envs(env.iter().copied())
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .context("failed to run git")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string())
+    }
+
+    async fn git(&self, args: &[&str]) -> anyhow::Result<String> {
+        self.git_with_env(args, &[]).await
+    }
+
+    async fn has_head(&self) -> bool {
+        self.git(&["rev-parse", "--verify", "-q", "HEAD"])
+            .await
+            .is_ok()
+    }
+
+    async fn ensure_init(&self) -> anyhow::Result<()> {
+        if self.git_dir.join("HEAD").exists() {
+            return Ok(());
+        }
+        tokio::fs::create_dir_all(&self.work_tree)
+            .await
+            .context("ensure_init")?;
+        if let Some(parent) = self.git_dir.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .context("ensure_init")?;
+        }
+        self.git(&["init", "-q"]).await?;
+        let info = self.git_dir.join("info");
+        tokio::fs::create_dir_all(&info)
+            .await
+            .context("ensure_init")?;
+        tokio::fs::write(info.join("exclude"), EXCLUDES)
+            .await
+            .context("ensure_init")?;
+        info!(git_dir = %self.git_dir.display(), "initialized workspace snapshot history");
+        Ok(())
+    }
+
+    /// Commit the whole folder. Returns the short hash, or `None` when
+    /// nothing changed and `allow_empty` is off.
+    pub async fn commit_all(
+        &self,
+        message: &str,
+        allow_empty: bool,
+    ) -> anyhow::Result<Option<String>> {
+        self.ensure_init().await?;
+        self.git(&["add", "-A"]).await?;
+        let dirty = !self.git(&["status", "--porcelain"]).await?.is_empty();
+        if !dirty && !allow_empty {
+            return Ok(None);
+        }
+        let mut args = vec!["commit", "-q", "--no-verify", "-m", message];
+        if !dirty {
+            args.push("--allow-empty");
+        }
+        self.git(&args).await?;
+        Ok(Some(self.git(&["rev-parse", "--short", "HEAD"]).await?))
+    }
+
+    /// Squash everything but the newest `keep` commits into one base commit.
+    /// Returns how many commits were folded away.
+    pub async fn prune(&self, keep: usize) -> anyhow::Result<usize> {
+        if keep == 0 || !self.has_head().await {
+            return Ok(0);
+        }
+        let count: usize = self
+            .git(&["rev-list", "--count", "HEAD"])
+            .await?
+            .parse()
+            .context("prune")?;
+        if count <= keep + PRUNE_SLACK {
+            return Ok(0);
+        }
+
+        let base = format!("HEAD~{}", keep - 1);
+        let base_tree = self
+            .git(&["rev-parse", &format!("{base}^{{tree}}")])
+            .await?;
+        let squashed = count - keep + 1;
+        let mut parent = self
+            .git(&[
+                "commit-tree",
+                &base_tree,
+                "-m",
+                &format!("{MESSAGE_PREFIX}squashed {squashed} older snapshots"),
+            ])
+            .await?;
+
+        let log = self
+            .git(&[
+                "log",
+                "--reverse",
+                "--format=%T%x00%aI%x00%s",
+                &format!("{base}..HEAD"),
+            ])
+            .await?;
+        for line in log.lines() {
+            let mut fields = line.splitn(3, '\0');
+            let (Some(tree), Some(date), Some(subject)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                anyhow::bail!("prune: unexpected log line {line:?}");
+            };
+            parent = self
+                .git_with_env(
+                    &["commit-tree", tree, "-p", &parent, "-m", subject],
+                    &[("GIT_AUTHOR_DATE", date), ("GIT_COMMITTER_DATE", date)],
+                )
+                .await?;
+        }
+
+        self.git(&["update-ref", "HEAD", &parent]).await?;
+        self.git(&["reflog", "expire", "--expire=now", "--all"])
+            .await?;
+        self.git(&["gc", "-q", "--prune=now"]).await?;
+        Ok(squashed - 1)
+    }
+
+    /// Undo the most recent run that hasn't been reverted: restore the tree
+    /// from before it and commit that as `revert run <id>`. Uncommitted
+    /// edits are snapshotted first so they stay recoverable.
+    pub async fn revert_last(&self) -> anyhow::Result<Option<Reverted>> {
+        if !self.git_dir.join("HEAD").exists() || !self.has_head().await {
+            return Ok(None);
+        }
+        let log = self.git(&["log", "--format=%H%x00%s"]).await?;
+        let mut reverted = HashSet::new();
+        let mut target = None;
+        for line in log.lines() {
+            let Some((hash, subject)) = line.split_once('\0') else {
+                continue;
+            };
+            match parse_marker(subject) {
+                Some(Marker::Revert(run_id)) => {
+                    reverted.insert(run_id);
+                }
+                Some(Marker::After(run_id)) if !reverted.contains(run_id) => {
+                    target = Some((hash, run_id));
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let Some((after, run_id)) = target else {
+            return Ok(None);
+        };
+
+        let before = match self
+            .git(&["rev-parse", "--verify", "-q", &format!("{after}^")])
+            .await
+        {
+            Ok(parent) => parent,
+            Err(_) => EMPTY_TREE.to_string(),
+        };
+        let diff = self.git(&["diff", "--name-only", &before, after]).await?;
+        let files = diff.lines().map(str::to_string).collect();
+
+        self.commit_all(
+            &format!("{MESSAGE_PREFIX}edits before revert of run {run_id}"),
+            false,
+        )
+        .await?;
+        self.git(&["read-tree", "-u", "--reset", &before]).await?;
+        self.commit_all(&format!("{MESSAGE_PREFIX}revert run {run_id}"), true)
+            .await?;
+
+        Ok(Some(Reverted {
+            run_id: run_id.to_string(),
+            files,
+        }))
+    }
+}
+
+/// Snapshot a group folder at one edge of a run. No-op when snapshots are
+/// disabled; failures are logged and never affect the run itself.
+pub async fn snapshot_run(
+    config: &RunConfig,
+    folder: &str,
+    run_id: &str,
+    phase: RunPhase,
+    task_id: Option<&str>,
+) {
+    if !config.snapshots.enabled {
+        return;
+    }
+    let repo = WorkspaceRepo::for_group(config, folder);
+    // Record every finished run, even one that changed nothing, so
+    // `/revert-last` always targets the latest run.
+    let allow_empty = phase == RunPhase::After;
+    match repo
+        .commit_all(&run_message(phase, run_id, task_id), allow_empty)
+        .await
+    {
+        Ok(Some(commit)) => debug!(
+            folder,
+            run_id,
+            commit,
+            phase = phase.as_str(),
+            "workspace snapshot"
+        ),
+        Ok(None) => {}
+        Err(e) => {
+            warn!(folder, run_id, err = %e, "workspace snapshot failed");
+            return;
+        }
+    }
+    if phase == RunPhase::After {
+        prune_history(&repo, &config.snapshots, folder).await;
+    }
+}
+
+async fn prune_history(repo: &WorkspaceRepo, config: &SnapshotConfig, folder: &str) {
+    match repo.prune(config.keep).await {
+        Ok(0) => {}
+        Ok(n) => info!(
+            folder,
+            squashed = n,
+            keep = config.keep,
+            "pruned workspace snapshots"
+        ),
+        Err(e) => warn!(folder, err = %e, "workspace snapshot pruning failed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(tmp: &tempfile::TempDir) -> WorkspaceRepo {
+        WorkspaceRepo::new(&tmp.path().join("data"), &tmp.path().join("groups"), "team")
+    }
+
+    fn write(repo: &WorkspaceRepo, name: &str, body: &str) {
+        std::fs::create_dir_all(&repo.work_tree).unwrap();
+        std::fs::write(repo.work_tree.join(name), body).unwrap();
+    }
+
+    #[test]
+    fn markers_round_trip_through_messages() {
+        let after = run_message(RunPhase::After, "20261016T101500.123Z", Some("digest"));
+        assert_eq!(
+            after,
+            "intercom: after run 20261016T101500.123Z (task digest)"
+        );
+        assert_eq!(
+            parse_marker(&after),
+            Some(Marker::After("20261016T101500.123Z"))
+        );
+        assert_eq!(
+            parse_marker("intercom: revert run abc"),
+            Some(Marker::Revert("abc"))
+        );
+        assert_eq!(
+            parse_marker(&run_message(RunPhase::Before, "abc", None)),
+            None
+        );
+        assert_eq!(parse_marker("after run abc"), None);
+    }
+
+    #[tokio::test]
+    async fn revert_last_restores_pre_run_tree() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = repo(&tmp);
+        write(&repo, "notes.md", "v1");
+        std::fs::create_dir_all(repo.work_tree.join("logs")).unwrap();
+        std::fs::write(repo.work_tree.join("logs/run.log"), "log").unwrap();
+
+        assert!(repo.revert_last().await.unwrap().is_none());
+        repo.commit_all(&run_message(RunPhase::Before, "r1", None), false)
+            .await
+            .unwrap();
+        write(&repo, "notes.md", "v2");
+        write(&repo, "new.txt", "agent file");
+        repo.commit_all(&run_message(RunPhase::After, "r1", None), true)
+            .await
+            .unwrap();
+
+        // The second run changes nothing but is still the latest run.
+        repo.commit_all(&run_message(RunPhase::Before, "r2", None), false)
+            .await
+            .unwrap();
+        repo.commit_all(&run_message(RunPhase::After, "r2", None), true)
+            .await
+            .unwrap();
+
+        let r2 = repo.revert_last().await.unwrap().unwrap();
+        assert_eq!(r2.run_id, "r2");
+        assert!(r2.files.is_empty());
+
+        let r1 = repo.revert_last().await.unwrap().unwrap();
+        assert_eq!(r1.run_id, "r1");
+        assert_eq!(r1.files, ["new.txt", "notes.md"]);
+        assert_eq!(
+            std::fs::read_to_string(repo.work_tree.join("notes.md")).unwrap(),
+            "v1"
+        );
+        assert!(!repo.work_tree.join("new.txt").exists());
+        // Excluded paths are left alone.
+        assert!(repo.work_tree.join("logs/run.log").exists());
+
+        assert!(repo.revert_last().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn prune_squashes_old_snapshots() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = repo(&tmp);
+        let runs = PRUNE_SLACK + 5;
+        for i in 0..runs {
+            write(&repo, "counter.txt", &i.to_string());
+            repo.commit_all(&run_message(RunPhase::After, &format!("r{i}"), None), true)
+                .await
+                .unwrap();
+        }
+        assert_eq!(repo.prune(runs).await.unwrap(), 0);
+        assert_eq!(repo.prune(3).await.unwrap(), runs - 3);
+
+        let log = repo.git(&["log", "--format=%s"]).await.unwrap();
+        let subjects: Vec<&str> = log.lines().collect();
+        assert_eq!(subjects.len(), 3);
+        assert_eq!(subjects[0], format!("intercom: after run r{}", runs - 1));
+        assert!(subjects[2].starts_with("intercom: squashed"));
+        assert_eq!(
+            std::fs::read_to_string(repo.work_tree.join("counter.txt")).unwrap(),
+            (runs - 1).to_string()
+        );
+    }
+}
@@ -5,7 +5,7 @@
 //! No Docker, no Postgres, no Telegram — pure HTTP endpoint validation.
 
 use std::net::TcpListener;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command};
 use std::time::Duration;
 
@@ -77,7 +77,7 @@ struct TestServer {
 }
 
 impl TestServer {
-    fn start(config_path: &PathBuf, port: u16) -> Self {
+    fn start(config_path: &Path, port: u16) -> Self {
         let binary = intercomd_binary();
         let child = Command::new(&binary)
             .args(["serve", "--config", config_path.to_str().unwrap()])
@@ -166,6 +166,9 @@ fn readyz_reports_orchestrator_disabled() {
     assert_eq!(body["orchestrator_enabled"], false);
     assert_eq!(body["postgres_connected"], false);
     assert_eq!(body["active_containers"], 0);
+    // Containers never run without the orchestrator, so they aren't probed
+    assert!(body["dependencies"].is_object());
+    assert!(body["dependencies"].get("docker").is_none());
 }
 
 #[test]
@@ -193,9 +196,10 @@ fn command_reset_returns_effects() {
     let body: serde_json::Value = resp.json().unwrap();
     assert!(body["text"].as_str().unwrap().contains("Session cleared"));
     let effects = body["effects"].as_array().unwrap();
-    assert_eq!(effects.len(), 2);
-    assert_eq!(effects[0], "KillContainer");
-    assert_eq!(effects[1], "ClearSession");
+    assert_eq!(effects.len(), 3);
+    assert_eq!(effects[0], "CancelQueued");
+    assert_eq!(effects[1], "KillContainer");
+    assert_eq!(effects[2], "ClearSession");
 }
 
 #[test]
@@ -250,3 +254,95 @@ fn runtime_profiles_endpoint() {
     assert_eq!(body["default_runtime"], "claude");
     assert!(body["profiles"].as_array().unwrap().contains(&serde_json::json!("claude")));
 }
+
+#[test]
+fn demarch_read_succeeds_against_mock_backend() {
+    let dir = tempfile::tempdir().unwrap();
+    let port = free_port();
+    let config = write_test_config(&dir, port);
+    let toml = std::fs::read_to_string(&config).unwrap();
+    let toml = toml.replace(
+        "[demarch]\nenabled = false",
+        "[demarch]\nenabled = true\nbackend = \"mock\"",
+    );
+    std::fs::write(&config, toml).unwrap();
+    let server = TestServer::start(&config, port);
+
+    let client = reqwest::blocking::Client::new();
+    let resp = client
+        .post(format!("{}/v1/demarch/read", server.base_url))
+        .json(&serde_json::json!({ "op": "sprint_phase" }))
+        .send()
+        .expect("POST /v1/demarch/read");
+
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = resp.json().unwrap();
+    assert_eq!(body["status"], "ok");
+    assert!(body["result"].as_str().unwrap().contains("execute"));
+}
+
+#[test]
+fn event_stream_is_server_sent_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let port = free_port();
+    let config = write_test_config(&dir, port);
+    let server = TestServer::start(&config, port);
+
+    let client = reqwest::blocking::Client::new();
+    let resp = client
+        .get(format!(
+            "{}/v1/events/stream?types=task_fired,send_failed",
+            server.base_url
+        ))
+        .send()
+        .expect("GET /v1/events/stream");
+
+    assert_eq!(resp.status(), 200);
+    let content_type = resp.headers()["content-type"].to_str().unwrap();
+    assert!(
+        content_type.starts_with("text/event-stream"),
+        "{content_type}"
+    );
+}
+
+#[test]
+fn admin_shutdown_needs_the_token_and_exits_cleanly() {
+    let dir = tempfile::tempdir().unwrap();
+    let port = free_port();
+    let config = write_test_config(&dir, port);
+    let toml = std::fs::read_to_string(&config).unwrap();
+    std::fs::write(
+        &config,
+        toml.replace("[server]\n", "[server]\nadmin_token = \"s3cret\"\n"),
+    )
+    .unwrap();
+    let mut server = TestServer::start(&config, port);
+
+    let client = reqwest::blocking::Client::new();
+    let url = format!("{}/v1/admin/shutdown", server.base_url);
+    let resp = client
+        .post(&url)
+        .bearer_auth("wrong")
+        .send()
+        .expect("POST /v1/admin/shutdown");
+    assert_eq!(resp.status(), 401);
+
+    let resp = client
+        .post(&url)
+        .bearer_auth("s3cret")
+        .json(&serde_json::json!({ "timeout_secs": 5 }))
+        .send()
+        .expect("POST /v1/admin/shutdown");
+    assert_eq!(resp.status(), 202);
+    let body: serde_json::Value = resp.json().unwrap();
+    assert_eq!(body["grace_secs"], 5);
+
+    for _ in 0..100 {
+        if let Some(status) = server.child.try_wait().unwrap() {
+            assert!(status.success(), "intercomd exited with {status}");
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    panic!("intercomd did not exit within 10 seconds of the shutdown call");
+}
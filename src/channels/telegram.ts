@@ -1,6 +1,6 @@
 import { Bot } from 'grammy';
 
-import { ASSISTANT_NAME, TRIGGER_PATTERN } from '../config.js';
+import { ASSISTANT_NAME, TELEGRAM_INGEST, TRIGGER_PATTERN } from '../config.js';
 import {
   editTelegramViaIntercomd,
   routeTelegramCallback,
@@ -62,12 +62,36 @@ export class TelegramChannel implements Channel {
     if (this.opts.onCommand) {
       const onCommand = this.opts.onCommand;
 
-      for (const cmd of ['help', 'model', 'reset', 'status']) {
+      for (const cmd of [
+        'help',
+        'model',
+        'reset',
+        'status',
+        'health',
+        'revert_last',
+        'tasks',
+        'schedule',
+        'cancel',
+        'queue',
+        'ps',
+        'logs',
+        'usage',
+        'stats',
+        'mute',
+        'wake',
+        'trigger',
+        'language',
+      ]) {
         this.bot.command(cmd, async (ctx) => {
           const chatJid = `tg:${ctx.chat.id}`;
           const args = (ctx.match as string) || '';
           try {
-            const result = await onCommand(chatJid, cmd, args.trim());
+            const result = await onCommand(
+              chatJid,
+              cmd,
+              args.trim(),
+              ctx.from?.id.toString(),
+            );
             await ctx.reply(result.text, {
               parse_mode: result.parseMode || 'Markdown',
             });
@@ -80,8 +104,38 @@ export class TelegramChannel implements Channel {
     }
 
     this.bot.on('message:text', async (ctx) => {
-      // Skip commands
-      if (ctx.message.text.startsWith('/')) return;
+      if (ctx.message.text.startsWith('/')) {
+        // Commands not registered above, e.g. intercomd's custom prompt
+        // commands, still go to the command handler.
+        const onCommand = this.opts.onCommand;
+        const match = /^\/([a-z0-9_]+)(?:@(\S+))?\s*([\s\S]*)$/.exec(
+          ctx.message.text,
+        );
+        const target = match?.[2]?.toLowerCase();
+        if (
+          !onCommand ||
+          !match ||
+          (target && target !== ctx.me?.username?.toLowerCase())
+        ) {
+          return;
+        }
+        const chatJid = `tg:${ctx.chat.id}`;
+        try {
+          const result = await onCommand(
+            chatJid,
+            match[1],
+            match[3].trim(),
+            ctx.from?.id.toString(),
+          );
+          await ctx.reply(result.text, {
+            parse_mode: result.parseMode || 'Markdown',
+          });
+        } catch (err) {
+          logger.error({ cmd: match[1], chatJid, err }, 'Command handler error');
+          await ctx.reply('Command failed. Check logs for details.');
+        }
+        return;
+      }
 
       const chatJid = `tg:${ctx.chat.id}`;
       let content = ctx.message.text;
@@ -314,10 +368,32 @@ export class TelegramChannel implements Channel {
       { command: 'model', description: 'Show or switch runtime (claude/gemini/codex)' },
       { command: 'reset', description: 'Clear session and stop container' },
       { command: 'status', description: 'Show runtime, session, and container status' },
+      { command: 'health', description: 'Subsystem health report (main group)' },
+      { command: 'revert_last', description: "Undo the last run's file changes (admins)" },
+      { command: 'tasks', description: "List this group's scheduled tasks" },
+      { command: 'schedule', description: 'Schedule a task: /schedule <when> <prompt>' },
+      { command: 'cancel', description: 'Cancel a scheduled task by id' },
+      { command: 'queue', description: "Show this group's container and queued work" },
+      { command: 'ps', description: 'List running containers (main group)' },
+      { command: 'logs', description: 'Tail of the latest container log' },
+      { command: 'usage', description: 'Runs, tokens and estimated cost' },
+      { command: 'stats', description: 'Runs, success rate and run time (24h)' },
+      { command: 'mute', description: 'Stop replying for a while, e.g. /mute 2h' },
+      { command: 'wake', description: 'Start replying again' },
+      { command: 'trigger', description: 'Show or change the trigger pattern' },
+      { command: 'language', description: 'Show or change the reply language' },
       { command: 'ping', description: 'Check if bot is online' },
       { command: 'chatid', description: "Show this chat's registration ID" },
     ]);
 
+    if (TELEGRAM_INGEST === 'poll' || TELEGRAM_INGEST === 'webhook') {
+      logger.info(
+        { ingest: TELEGRAM_INGEST },
+        'Telegram updates are received by intercomd; host is send-only',
+      );
+      return;
+    }
+
     // Start polling — returns a Promise that resolves when started
     return new Promise<void>((resolve) => {
       this.bot!.start({
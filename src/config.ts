@@ -14,6 +14,7 @@ const envConfig = readEnvFile([
   'INTERCOM_RUNTIME',
   'TELEGRAM_BOT_TOKEN',
   'TELEGRAM_ONLY',
+  'TELEGRAM_INGEST',
 ]);
 
 export const ASSISTANT_NAME =
@@ -149,3 +150,7 @@ export const TELEGRAM_BOT_TOKEN =
   process.env.TELEGRAM_BOT_TOKEN || envConfig.TELEGRAM_BOT_TOKEN || '';
 export const TELEGRAM_ONLY =
   (process.env.TELEGRAM_ONLY || envConfig.TELEGRAM_ONLY) === 'true';
+// 'poll' or 'webhook' when intercomd receives Telegram updates itself
+// (telegram.ingest in intercom.toml). The host then only sends.
+export const TELEGRAM_INGEST =
+  process.env.TELEGRAM_INGEST || envConfig.TELEGRAM_INGEST || 'host';
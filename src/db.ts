@@ -101,6 +101,16 @@ function createSchema(database: Database.Database): void {
     /* column already exists */
   }
 
+  // Add edit/delete tracking columns if they don't exist (migration for existing DBs).
+  // intercomd records edits and tombstones here for Telegram ingress.
+  for (const column of ['edited_at', 'deleted_at']) {
+    try {
+      database.exec(`ALTER TABLE messages ADD COLUMN ${column} TEXT`);
+    } catch {
+      /* column already exists */
+    }
+  }
+
   // Add runtime column to registered_groups if it doesn't exist (migration for existing DBs)
   try {
     database.exec(
@@ -310,6 +320,7 @@ export function getRecentConversation(
     SELECT sender_name, content, timestamp, is_bot_message
     FROM messages
     WHERE chat_jid = ? AND content != '' AND content IS NOT NULL
+      AND deleted_at IS NULL
     ORDER BY timestamp DESC
     LIMIT ?
   `).all(chatJid, limit) as { sender_name: string; content: string; timestamp: string; is_bot_message: number }[];
@@ -337,6 +348,7 @@ export function getNewMessages(
     WHERE timestamp > ? AND chat_jid IN (${placeholders})
       AND is_bot_message = 0 AND content NOT LIKE ?
       AND content != '' AND content IS NOT NULL
+      AND deleted_at IS NULL
     ORDER BY timestamp
   `;
 
@@ -365,6 +377,7 @@ export function getMessagesSince(
     WHERE chat_jid = ? AND timestamp > ?
       AND is_bot_message = 0 AND content NOT LIKE ?
       AND content != '' AND content IS NOT NULL
+      AND deleted_at IS NULL
     ORDER BY timestamp
   `;
   return db
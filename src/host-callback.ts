@@ -13,10 +13,16 @@
 
 import http from 'http';
 
+import type { InlineKeyboardMarkup } from './intercomd-client.js';
 import { logger } from './logger.js';
 
 export interface HostCallbackDeps {
-  sendMessage: (jid: string, text: string, sender?: string) => Promise<void>;
+  sendMessage: (
+    jid: string,
+    text: string,
+    sender?: string,
+    replyMarkup?: InlineKeyboardMarkup,
+  ) => Promise<void>;
   forwardTask: (
     task: Record<string, unknown>,
     groupFolder: string,
@@ -111,11 +117,12 @@ export function startHostCallbackServer(
         const jid = data.chat_jid as string;
         const text = data.text as string;
         const sender = data.sender as string | undefined;
+        const replyMarkup = data.reply_markup as InlineKeyboardMarkup | undefined;
         if (!jid || !text) {
           jsonResponse(res, 400, { error: 'Missing chat_jid or text' });
           return;
         }
-        await deps.sendMessage(jid, text, sender);
+        await deps.sendMessage(jid, text, sender, replyMarkup);
         jsonResponse(res, 200, { status: 'ok' });
         return;
       }
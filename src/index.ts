@@ -36,6 +36,10 @@ import {
 import { GroupQueue } from './group-queue.js';
 import { resolveGroupFolderPath } from './group-folder.js';
 import { startHostCallbackServer } from './host-callback.js';
+import {
+  runIntercomdCommand,
+  sendTelegramViaIntercomd,
+} from './intercomd-client.js';
 import { processTaskIpc, startIpcWatcher } from './ipc.js';
 import { findChannel, formatOutbound } from './router.js';
 import {
@@ -161,6 +165,20 @@ function handleHelp(): CommandResult {
       '/model <name> — Switch model by name',
       '/reset — Clear session and stop running container',
       '/new — Start a fresh chat (alias for /reset)',
+      '/health — Subsystem health report (main group only)',
+      "/revert\\_last — Undo the last run's file changes (admins only)",
+      "/tasks — List this group's scheduled tasks",
+      '/schedule <when> <prompt> — Schedule a task (e.g. in 2h, every weekday at 9am)',
+      '/cancel <id> — Cancel a scheduled task',
+      "/queue — Show this group's container and queued work",
+      '/ps — List running containers (main group only)',
+      '/logs [n] — Show the tail of the latest container log',
+      '/usage — Runs, tokens and estimated cost for this group',
+      '/stats — Runs, success rate and run time over the last 24h',
+      '/mute [duration] — Stop replying for a while (default 1h)',
+      '/wake — Start replying again',
+      '/trigger [show|set <pattern>|off] — Show or change the trigger',
+      '/language [code] — Show or change the reply language',
       '/ping — Check if bot is online',
       "/chatid — Show this chat's registration ID",
     ].join('\n'),
@@ -290,14 +308,89 @@ function handleReset(chatJid: string): CommandResult {
   return { text: parts.join(' ') };
 }
 
+async function handleHealth(chatJid: string): Promise<CommandResult> {
+  const group = registeredGroups[chatJid];
+  if (!group) {
+    return { text: 'This chat is not registered.' };
+  }
+  const result = await runIntercomdCommand({
+    chat_jid: chatJid,
+    command: 'health',
+    group_name: group.name,
+    group_folder: group.folder,
+  });
+  if (!result) {
+    return { text: 'intercomd is unreachable — health checks unavailable.' };
+  }
+  return { text: result.text, parseMode: result.parse_mode || undefined };
+}
+
+async function handleRevertLast(
+  chatJid: string,
+  senderId: string | undefined,
+): Promise<CommandResult> {
+  const group = registeredGroups[chatJid];
+  if (!group) {
+    return { text: 'This chat is not registered.' };
+  }
+  const result = await runIntercomdCommand({
+    chat_jid: chatJid,
+    command: 'revert-last',
+    group_name: group.name,
+    group_folder: group.folder,
+    container_active: queue.isActive(chatJid),
+    sender: senderId,
+  });
+  if (!result) {
+    return { text: 'intercomd is unreachable — cannot revert.' };
+  }
+  return { text: result.text, parseMode: result.parse_mode || undefined };
+}
+
+async function handleIntercomdCommand(
+  chatJid: string,
+  command: string,
+  args: string,
+  senderId?: string,
+): Promise<CommandResult> {
+  const group = registeredGroups[chatJid];
+  if (!group) {
+    return { text: 'This chat is not registered.' };
+  }
+  const result = await runIntercomdCommand({
+    chat_jid: chatJid,
+    command,
+    args,
+    group_name: group.name,
+    group_folder: group.folder,
+    sender: senderId,
+  });
+  if (!result) {
+    return { text: `intercomd is unreachable — /${command} unavailable.` };
+  }
+  return { text: result.text, parseMode: result.parse_mode || undefined };
+}
+
 async function handleCommand(
   chatJid: string,
   command: string,
   args: string,
+  senderId?: string,
 ): Promise<CommandResult> {
   switch (command) {
-    case 'help':
-      return handleHelp();
+    case 'help': {
+      // intercomd's list includes the deployment's custom commands.
+      const group = registeredGroups[chatJid];
+      const result = await runIntercomdCommand({
+        chat_jid: chatJid,
+        command,
+        group_name: group?.name,
+        group_folder: group?.folder,
+      });
+      return result
+        ? { text: result.text, parseMode: result.parse_mode || undefined }
+        : handleHelp();
+    }
     case 'status':
       return handleStatus(chatJid);
     case 'model':
@@ -305,8 +398,14 @@ async function handleCommand(
     case 'reset':
     case 'new':
       return handleReset(chatJid);
+    case 'health':
+      return handleHealth(chatJid);
+    case 'revert-last':
+    case 'revert_last':
+      return handleRevertLast(chatJid, senderId);
     default:
-      return { text: `Unknown command: /${command}` };
+      // /tasks, /logs, /mute and the rest, plus [commands.custom] prompt commands.
+      return handleIntercomdCommand(chatJid, command, args, senderId);
   }
 }
 
@@ -378,7 +477,16 @@ async function main(): Promise<void> {
   });
   // Host callback server — intercomd calls back here for message sends + task forwarding
   startHostCallbackServer(HOST_CALLBACK_PORT, {
-    sendMessage: async (jid, text) => {
+    sendMessage: async (jid, text, _sender, replyMarkup) => {
+      // Buttons only exist on Telegram, which intercomd sends directly.
+      if (replyMarkup && jid.startsWith('tg:')) {
+        const routed = await sendTelegramViaIntercomd({
+          jid,
+          text,
+          reply_markup: replyMarkup,
+        });
+        if (routed?.ok) return;
+      }
       const channel = findChannel(channels, jid);
       if (!channel) throw new Error(`No channel for JID: ${jid}`);
       await channel.sendMessage(jid, text);
@@ -32,9 +32,17 @@ export interface TelegramIngressResponse {
   };
 }
 
+export interface InlineKeyboardMarkup {
+  inline_keyboard: { text: string; callback_data: string }[][];
+}
+
 export interface TelegramSendRequest {
   jid: string;
   text: string;
+  reply_markup?: InlineKeyboardMarkup;
+  reply_to_message_id?: string;
+  message_thread_id?: number;
+  correlation_id?: string;
 }
 
 export interface TelegramSendResponse {
@@ -48,6 +56,9 @@ export interface TelegramSendResponse {
     max_chars_per_chunk: number;
     all_chunks_within_limit: boolean;
   };
+  retries: number;
+  max_retries: number;
+  retry_wait_ms: number;
 }
 
 export interface TelegramEditRequest {
@@ -111,6 +122,74 @@ export function editTelegramViaIntercomd(
   return postJson<TelegramEditResponse>('/v1/telegram/edit', request);
 }
 
+export interface WhatsAppIngressRequest {
+  chat_jid: string;
+  message_id: string;
+  sender: string;
+  sender_name?: string;
+  content: string;
+  timestamp: string;
+  from_me?: boolean;
+  persist?: boolean;
+}
+
+export interface WhatsAppIngressResponse {
+  accepted: boolean;
+  reason?: string | null;
+  normalized_content: string;
+  is_bot_message: boolean;
+  group_name?: string | null;
+  group_folder?: string | null;
+  runtime?: string | null;
+  model?: string | null;
+  parity: TelegramIngressResponse['parity'];
+}
+
+export interface WhatsAppSendResponse {
+  ok: boolean;
+  error?: string | null;
+  chunks: string[];
+  chunks_planned: number;
+  chunk_lengths: number[];
+  prefixed: boolean;
+  parity: TelegramSendResponse['parity'];
+}
+
+export function routeWhatsAppIngress(
+  request: WhatsAppIngressRequest,
+): Promise<WhatsAppIngressResponse | null> {
+  return postJson<WhatsAppIngressResponse>('/v1/whatsapp/ingress', request);
+}
+
+export function planWhatsAppSend(
+  jid: string,
+  text: string,
+): Promise<WhatsAppSendResponse | null> {
+  return postJson<WhatsAppSendResponse>('/v1/whatsapp/send', { jid, text });
+}
+
+export interface CommandRequest {
+  chat_jid: string;
+  command: string;
+  args?: string;
+  group_name?: string;
+  group_folder?: string;
+  container_active?: boolean;
+  sender?: string;
+}
+
+export interface CommandResponse {
+  text: string;
+  parse_mode?: 'Markdown' | 'HTML' | null;
+  reply_markup?: InlineKeyboardMarkup;
+}
+
+export function runIntercomdCommand(
+  request: CommandRequest,
+): Promise<CommandResponse | null> {
+  return postJson<CommandResponse>('/v1/commands', request);
+}
+
 export interface TelegramCallbackRequest {
   callback_query_id: string;
   chat_jid: string;
@@ -90,6 +90,7 @@ export type OnCommand = (
   chatJid: string,
   command: string,
   args: string,
+  senderId?: string,
 ) => Promise<CommandResult>;
 
 // --- Channel abstraction ---